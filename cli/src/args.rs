@@ -0,0 +1,429 @@
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use chrono::NaiveDate;
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use dunsumday::config::file::Format;
+use dunsumday::config::parse::{DurationParser, Parser as _};
+use dunsumday::db::ConfigId;
+use dunsumday::types::{Config as ItemConfig, Item, ItemType, Sched};
+use dunsumday::util::{export, report, stats};
+use crate::output;
+use crate::report::Range;
+
+/// Command-line interface for dunsumday.
+#[derive(Clone, Debug, Parser)]
+#[command(name = "dunsumday", version)]
+pub struct Args {
+    /// Path to the config file.
+    #[arg(long, default_value = "dev-config.yaml")]
+    pub config: PathBuf,
+    /// Format of the config file: yaml or toml.  Detected from the file
+    /// extension by default.
+    #[arg(long)]
+    pub config_format: Option<Format>,
+    /// Override a config value, for example: --set db.sqlite.db-path=db.sqlite.
+    /// May be given multiple times.
+    #[arg(long, value_name = "PATH=VALUE")]
+    pub set: Vec<String>,
+    /// Name of the user whose items to use, which is created if it doesn't
+    /// exist.  By default, items created without a user are used.
+    #[arg(long)]
+    pub user: Option<String>,
+    /// Format for results: plain or json.  JSON output uses the same
+    /// representation of items and occurrences as the web API.  Commands
+    /// which write a specific file format (export, report, completions, man)
+    /// ignore this.
+    #[arg(long, value_enum, global = true, default_value_t)]
+    pub output: output::Format,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum Command {
+    /// Manage items.
+    #[command(subcommand)]
+    Item(ItemCommand),
+    /// Manage occurrences.
+    #[command(subcommand)]
+    Occ(OccCommand),
+    /// Manage configs for occurrences, by scope.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Create items from another format.
+    #[command(subcommand)]
+    Import(ImportCommand),
+    /// Add progress to an item's current occurrence.
+    Done {
+        /// Item ID or name.
+        item: String,
+        /// Amount of progress to add.  Defaults to the amount needed to
+        /// complete the occurrence.
+        #[arg(long, allow_negative_numbers = true)]
+        amount: Option<i64>,
+    },
+    /// Log progress on an item's current occurrence.
+    Log {
+        /// Item ID or name.
+        item: String,
+        /// Amount of progress, optionally followed by the item's unit, for
+        /// example: 3 or 3km.  Negative amounts remove progress.
+        #[arg(allow_hyphen_values = true)]
+        amount: String,
+        /// Note to record with the progress.
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// Export all items, and occurrences with their progress.
+    Export {
+        /// Output format: json, csv, ics, taskwarrior or markdown.
+        #[arg(long, default_value = "json")]
+        format: export::Format,
+        /// Only include occurrences which end on or after this day, in UTC.
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Only include occurrences which start on or before this day, in UTC.
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        /// File to write to, instead of standard output.
+        #[arg(long, short = 'o')]
+        output_file: Option<PathBuf>,
+    },
+    /// Write a report of completion statistics for tasks, by item and by
+    /// category.
+    Report {
+        /// Time range ending now: week, month or year for the last 7, 30 or 365
+        /// days, or all.
+        #[arg(long, value_enum, default_value_t)]
+        range: Range,
+        /// Output format: markdown or html.  Detected from the output file's
+        /// extension by default, or markdown when writing to standard output.
+        #[arg(long)]
+        format: Option<report::Format>,
+        /// File to write to, instead of standard output.
+        #[arg(long, short = 'o', visible_alias = "out")]
+        output_file: Option<PathBuf>,
+    },
+    /// Export completion statistics for tasks as CSV, with a row for each
+    /// group in each period.  Unlike export, this doesn't include individual
+    /// occurrences.
+    Stats {
+        /// First day to include, in UTC.  Defaults to 29 days before --to.
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Last day to include, in UTC.  Defaults to today.
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        /// How to group statistics: item, category or type.
+        #[arg(long, default_value = "item")]
+        group_by: stats::GroupBy,
+        /// Length of the periods to compute statistics for separately: all,
+        /// day, week or month.
+        #[arg(long, default_value = "all")]
+        period: stats::Period,
+        /// File to write to, instead of standard output.
+        #[arg(long, short = 'o')]
+        output_file: Option<PathBuf>,
+    },
+    /// Show current items and their progress.
+    Today,
+    /// Show current occurrences which are in their alert period or overdue.
+    /// Exits with status 1 if there are any, and prints nothing in plain
+    /// format if there are none.
+    Due {
+        /// Show occurrences ending within this duration, for example: 2h or
+        /// 1d12h, instead of those in their configured alert period.
+        #[arg(long, value_parser = parse_duration)]
+        within: Option<Duration>,
+        /// Format for results.  Defaults to the value of --output.
+        #[arg(long, value_enum)]
+        format: Option<output::Format>,
+    },
+    /// Send alerts about occurrences entering their alert period or becoming
+    /// overdue, through the configured notification channels.  Alerts are
+    /// also printed, one per line, and each alert is only sent once unless its
+    /// config sets a repeat interval.  Newly reached milestones are sent in
+    /// the same way.
+    Notify {
+        /// Keep checking for alerts at the configured interval.
+        #[arg(long)]
+        daemon: bool,
+        /// Also show alerts as notifications in the desktop session.
+        #[arg(long)]
+        desktop: bool,
+    },
+    /// Sync events from the configured iCalendar URLs as items.  Items are
+    /// created, updated and deleted to match each calendar, and changes made
+    /// to them locally are overwritten.
+    Sync {
+        /// Keep syncing at the configured interval.
+        #[arg(long)]
+        daemon: bool,
+    },
+    /// Sync the database with the configured web server, sending changes made
+    /// here and receiving changes made elsewhere.  If an object was changed in
+    /// both places, the change made last is kept.
+    SyncServer {
+        /// Keep syncing at the configured interval.
+        #[arg(long)]
+        daemon: bool,
+    },
+    /// Push upcoming occurrences to the configured Google Calendar.  Events
+    /// are created, updated and removed to match the occurrences, and changes
+    /// made to them in the calendar are overwritten.
+    SyncGcal {
+        /// Keep pushing at the configured interval.
+        #[arg(long)]
+        daemon: bool,
+    },
+    /// Allow access to a Google Calendar using the configured OAuth client,
+    /// and print the refresh token to configure.
+    GcalAuth,
+    /// Back up the database to the configured destination, and remove old
+    /// backups.
+    Backup,
+    /// Interactive terminal interface for tracking current items.
+    Tui,
+    /// Check the config and database for problems.
+    Doctor,
+    /// Print a shell completion script.
+    Completions {
+        shell: Shell,
+    },
+    /// Print a man page in roff format.
+    Man,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum ItemCommand {
+    /// Create an item.
+    Add {
+        #[arg(required_unless_present = "interactive")]
+        name: Option<String>,
+        /// Schedule as JSON, for example: '{"ProgressTask": {"Days": {"num":
+        /// 1}}}'.  This determines the item's type.
+        #[arg(long, value_parser = parse_sched,
+              required_unless_present = "interactive")]
+        sched: Option<Sched>,
+        #[arg(long)]
+        category: Option<String>,
+        /// Description.
+        #[arg(long)]
+        desc: Option<String>,
+        /// Create the item without tracking it.
+        #[arg(long)]
+        inactive: bool,
+        /// Ask for the item's details, with a preview of the schedule.  Other
+        /// options are used as defaults.
+        #[arg(long, short, conflicts_with = "sched")]
+        interactive: bool,
+    },
+    /// List active items.
+    List {
+        /// Also list inactive items.
+        #[arg(long)]
+        all: bool,
+    },
+    /// Show an item, with its current occurrence.
+    Show {
+        /// Item ID or name.
+        item: String,
+    },
+    /// Change an item.
+    Edit {
+        /// Item ID or name.
+        item: String,
+        #[arg(long)]
+        name: Option<String>,
+        /// Schedule, as for `item add`.
+        #[arg(long, value_parser = parse_sched)]
+        sched: Option<Sched>,
+        /// An empty value removes the category.
+        #[arg(long)]
+        category: Option<String>,
+        /// Description.  An empty value removes the description.
+        #[arg(long)]
+        desc: Option<String>,
+        /// Whether the item is tracked.
+        #[arg(long)]
+        active: Option<bool>,
+    },
+    /// Change all items matching a filter at once, and list the items which
+    /// changed.  At least one filter is required.
+    #[command(group(ArgGroup::new("filter")
+        .args(["category", "type_", "active", "all"])
+        .required(true)
+        .multiple(true)))]
+    Bulk {
+        /// Only change items with this category.
+        #[arg(long)]
+        category: Option<String>,
+        /// Only change items of this type: Event, ProgressTask or
+        /// DeadlineTask.
+        #[arg(long = "type", value_name = "TYPE")]
+        type_: Option<ItemType>,
+        /// Only change items which are tracked, or which aren't.
+        #[arg(long)]
+        active: Option<bool>,
+        /// Change every item.
+        #[arg(long)]
+        all: bool,
+        /// Change to make, as FIELD=VALUE, where FIELD is active, category or
+        /// desc.  An empty value removes the category or description.  May be
+        /// given multiple times.
+        #[arg(long, value_name = "FIELD=VALUE", required = true,
+              value_parser = parse_item_change)]
+        set: Vec<ItemChange>,
+        /// List the items which would change, without changing them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Delete an item.  Items with occurrences cannot be deleted; mark them
+    /// inactive instead.
+    Rm {
+        /// Item ID or name.
+        item: String,
+    },
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum ImportCommand {
+    /// Create items from the events in an iCalendar file.  Recurring events
+    /// become items with equivalent schedules where possible.
+    Ics {
+        file: PathBuf,
+        /// Print the items which would be created, without creating them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Create items from habits exported by Loop Habit Tracker, with their
+    /// history.  Importing the same habits again updates their items.
+    Loop {
+        /// `Habits.csv` from the export archive.
+        habits: PathBuf,
+        /// `Checkmarks.csv` from the export archive.
+        checkmarks: PathBuf,
+        /// Print the items which would be created, without creating them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Create items from habits and dailies in Habitica's task history export,
+    /// with their history.  Importing the same tasks again updates their items.
+    Habitica {
+        file: PathBuf,
+        /// Print the items which would be created, without creating them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Create items from tasks exported by Taskwarrior (`task export`).
+    /// Recurring tasks become progress tasks, and other tasks with a due date
+    /// become deadline tasks.  Importing the same tasks again updates their
+    /// items.
+    Taskwarrior {
+        file: PathBuf,
+        /// Print the items which would be created, without creating them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum OccCommand {
+    /// List an item's occurrences.
+    List {
+        /// Item ID or name.
+        item: String,
+        /// Only list occurrences ending after the start of this day (UTC).
+        #[arg(long)]
+        start: Option<NaiveDate>,
+        /// Only list occurrences starting before the start of this day (UTC).
+        #[arg(long)]
+        end: Option<NaiveDate>,
+    },
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// List all configs.
+    List,
+    /// Show the config for a scope.
+    Show {
+        /// Scope: all, type:TYPE, category:CATEGORY, item:ID or occ:ID.
+        scope: ConfigId,
+    },
+    /// Set the config for a scope, replacing any config it had.
+    Set {
+        /// Scope, as for `config show`.  Items and occurrences must exist.
+        scope: ConfigId,
+        /// Config as JSON, for example: '{"occ_alert": {"secs": 3600,
+        /// "nanos": 0}, "task_completion_conf": {}}'.
+        #[arg(value_parser = parse_item_config)]
+        config: Box<ItemConfig>,
+    },
+    /// Delete the config for a scope.
+    Rm {
+        /// Scope, as for `config show`.
+        scope: ConfigId,
+    },
+}
+
+/// Change to an item's field, made by `item bulk`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ItemChange {
+    Active(bool),
+    Category(Option<String>),
+    Desc(Option<String>),
+}
+
+impl ItemChange {
+    pub fn apply(&self, item: &mut Item) {
+        match self {
+            ItemChange::Active(active) => item.active = *active,
+            ItemChange::Category(category) => item.category = category.clone(),
+            ItemChange::Desc(desc) => item.desc = desc.clone(),
+        }
+    }
+}
+
+fn parse_item_change(value: &str) -> Result<ItemChange, String> {
+    let (field, value) = value.split_once('=')
+        .ok_or_else(|| format!("expected FIELD=VALUE: {value}"))?;
+    let non_empty = (!value.is_empty()).then(|| value.to_owned());
+    match field {
+        "active" => value.parse()
+            .map(ItemChange::Active)
+            .map_err(|e| format!("invalid value for active ({value}): {e}")),
+        "category" => Ok(ItemChange::Category(non_empty)),
+        "desc" => Ok(ItemChange::Desc(non_empty)),
+        _ => Err(format!(
+            "unknown field ({field}): expected active, category or desc")),
+    }
+}
+
+fn parse_sched(value: &str) -> Result<Sched, String> {
+    serde_json::from_str(value).map_err(|e| format!("invalid schedule: {e}"))
+}
+
+fn parse_item_config(value: &str) -> Result<Box<ItemConfig>, String> {
+    serde_json::from_str(value).map_err(|e| format!("invalid config: {e}"))
+}
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    DurationParser.parse(value)
+}
+
+/// Print a completion script for `shell`.
+pub fn print_completions(shell: Shell) {
+    let mut command = Args::command();
+    let name = command.get_name().to_owned();
+    clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+}
+
+/// Print a man page in roff format.
+pub fn print_man() -> Result<(), String> {
+    clap_mangen::Man::new(Args::command())
+        .render(&mut io::stdout())
+        .map_err(|e| format!("error writing man page: {e}"))
+}
@@ -0,0 +1,33 @@
+//! Backing up the database.
+
+use dunsumday::config::{self, Config};
+use dunsumday::configrefs;
+use dunsumday::db::Db;
+use dunsumday::util::backup;
+use dunsumday::util::clock::Clock;
+use crate::output;
+
+/// Back up the database to the configured destination, and print the result.
+pub fn run(
+    cfg: &impl Config,
+    db: &impl Db,
+    clock: &dyn Clock,
+    format: output::Format,
+)
+-> Result<(), String> {
+    let dest = backup::open_destination(cfg)?
+        .ok_or("no backup destination configured (backup.destination)")?;
+    let retention = config::get_parsed(cfg, &configrefs::BACKUP_RETENTION)?;
+    let backed_up = backup::backup(db, dest.as_ref(), retention, clock.now())?;
+
+    match format {
+        output::Format::Plain => {
+            println!("backed up to {}: {}", dest.describe(), backed_up.name);
+            for name in &backed_up.removed {
+                println!("removed {name}");
+            }
+        }
+        output::Format::Json => output::json(&backed_up)?,
+    }
+    Ok(())
+}
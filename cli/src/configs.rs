@@ -0,0 +1,61 @@
+use dunsumday::db::{ConfigId, Db, StoredConfig};
+use dunsumday::db::util as dbutil;
+use crate::args::ConfigCommand;
+use crate::output;
+
+/// Check that the item or occurrence a config applies to exists.
+fn check_scope_exists(db: &impl Db, id: &ConfigId) -> Result<(), String> {
+    let exists = match id {
+        ConfigId::Item { id } => !db.get_items(&[id])?.is_empty(),
+        ConfigId::Occ { id } => !db.get_occs(&[id])?.is_empty(),
+        _ => true,
+    };
+    if exists { Ok(()) } else { Err(format!("config scope not found: {id}")) }
+}
+
+/// Convert a config to JSON on one line.
+fn config_json(config: &StoredConfig) -> Result<String, String> {
+    serde_json::to_string(&config.config)
+        .map_err(|e| format!("error serialising config: {e}"))
+}
+
+fn list(db: &impl Db, format: output::Format) -> Result<(), String> {
+    let mut configs = db.find_configs()?;
+    configs.sort_by_key(|config| config.id.to_string());
+    if format == output::Format::Json {
+        return output::json(&configs);
+    }
+    let rows = configs.iter()
+        .map(|config| Ok([config.id.to_string(), config_json(config)?]))
+        .collect::<Result<Vec<_>, String>>()?;
+    output::table(["SCOPE", "CONFIG"], &rows);
+    Ok(())
+}
+
+pub fn run(db: &mut impl Db, command: ConfigCommand, format: output::Format)
+-> Result<(), String> {
+    match command {
+        ConfigCommand::List => list(db, format),
+        ConfigCommand::Show { scope } => {
+            let config = dbutil::get_config(db, &scope)?
+                .ok_or_else(|| format!("config not found: {scope}"))?;
+            match format {
+                output::Format::Plain => output::json(&config.config),
+                output::Format::Json => output::json(&config),
+            }
+        }
+        ConfigCommand::Set { scope, config } => {
+            check_scope_exists(db, &scope)?;
+            let config = StoredConfig { id: scope, config: *config };
+            dbutil::set_config(db, &config)?;
+            if format == output::Format::Json {
+                output::json(&config)?;
+            }
+            Ok(())
+        }
+        ConfigCommand::Rm { scope } => {
+            dbutil::delete_config(db, &scope)?;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Syncing the database with a web server.
+
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use dunsumday::config::{self, Config};
+use dunsumday::configrefs;
+use dunsumday::db::Db;
+use dunsumday::util::clock::Clock;
+use dunsumday::util::devicesync::{HttpRemote, SyncEngine, SyncState};
+use crate::output;
+
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Sync once, saving the new state, and print the result.
+fn sync_once(
+    db: &mut impl Db,
+    clock: &dyn Clock,
+    remote: &HttpRemote,
+    state_path: &Path,
+    format: output::Format,
+) -> Result<(), String> {
+    let mut engine = SyncEngine::new(SyncState::load(state_path)?);
+    let summary = engine.sync(db, remote, clock.now());
+    // the state may have changed even if the sync failed part-way
+    engine.state().save(state_path)?;
+    let summary = summary?;
+
+    match format {
+        output::Format::Plain => {
+            output::table(["SENT", "RECEIVED", "CONFLICTS"], &[[
+                summary.sent.to_string(),
+                summary.received.to_string(),
+                summary.conflicts.to_string(),
+            ]]);
+        }
+        output::Format::Json => output::json(&summary)?,
+    }
+    Ok(())
+}
+
+/// Sync the database with the configured server, and print the result.
+///
+/// If `daemon` is `true`, keep syncing at the configured interval, logging any
+/// errors, instead of returning.
+pub fn run(
+    cfg: &impl Config,
+    db: &mut impl Db,
+    clock: &dyn Clock,
+    daemon: bool,
+    format: output::Format,
+) -> Result<(), String> {
+    let url = cfg.get_ref(&configrefs::SYNC_SERVER_URL);
+    if url.is_empty() {
+        return Err("no server configured (sync.server.url)".to_owned());
+    }
+    let headers = config::get_parsed(cfg, &configrefs::SYNC_SERVER_HEADERS)?;
+    let remote = HttpRemote::new(url, headers);
+    let state_path = Path::new(
+        cfg.get_ref(&configrefs::SYNC_SERVER_STATE_PATH));
+    if !daemon {
+        return sync_once(db, clock, &remote, state_path, format);
+    }
+
+    let interval = config::get_parsed(cfg, &configrefs::SYNC_SERVER_INTERVAL)?
+        .max(MIN_INTERVAL);
+    loop {
+        if let Err(e) = sync_once(db, clock, &remote, state_path, format) {
+            log::error!("error syncing with server: {e}");
+        }
+        thread::sleep(interval);
+    }
+}
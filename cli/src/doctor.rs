@@ -0,0 +1,128 @@
+//! Diagnosing problems with the config and database.
+
+use dunsumday::config::{self, Config};
+use dunsumday::configrefs;
+use dunsumday::db::{self, Db, SortDirection, StoredUser};
+use dunsumday::util::sched;
+use serde::Serialize;
+use crate::output;
+
+/// Problems found by each check.
+#[derive(Clone, Debug, Default, Serialize)]
+struct Report {
+    config: Vec<String>,
+    database: Vec<String>,
+    /// Empty if the database couldn't be opened.
+    schedules: Vec<String>,
+}
+
+impl Report {
+    fn count(&self) -> usize {
+        self.config.len() + self.database.len() + self.schedules.len()
+    }
+
+    fn print(&self, format: output::Format) -> Result<(), String> {
+        match format {
+            output::Format::Plain => {
+                print_check("config", &self.config);
+                print_check("database", &self.database);
+                print_check("schedules", &self.schedules);
+                if self.count() == 0 {
+                    println!("no problems found");
+                }
+                Ok(())
+            }
+            output::Format::Json => output::json(self),
+        }
+    }
+}
+
+/// Print the problems found by a check.
+fn print_check(name: &str, problems: &[String]) {
+    if problems.is_empty() {
+        println!("{name}: ok");
+    } else {
+        println!("{name}:");
+        for problem in problems {
+            println!("  - {problem}");
+        }
+    }
+}
+
+fn check_config(cfg: &dyn Config) -> Vec<String> {
+    config::check_defaults(configrefs::ALL).err().into_iter()
+        .chain(config::check_all(cfg, configrefs::ALL).err())
+        .flatten()
+        .map(|e| e.to_string())
+        .collect()
+}
+
+/// Check the schedules of all items belonging to `user`, or to no user.
+fn check_schedules(db: &mut impl Db, user: Option<&StoredUser>)
+-> Result<Vec<String>, String> {
+    let user_arg = match user {
+        Some(user) => format!("--user {:?} ", user.user.name),
+        None => "".to_owned(),
+    };
+    db.set_user(user.map(|user| user.id.as_str()))?;
+    let items = match db.find_items(None, None, None, SortDirection::Asc, None,
+                                    u32::MAX) {
+        Ok(items) => items,
+        Err(e) => {
+            return Ok(vec![format!(
+                "can't check items ({}) until database problems are fixed: {e}",
+                user.map_or("no user", |user| &user.user.name))]);
+        }
+    };
+
+    let mut problems = vec![];
+    for item in &items {
+        let name = format!("item {} ({})", item.id, item.item.name);
+        let fix = format!("`dunsumday {user_arg}item edit {} --sched ...`",
+                          item.id);
+        let sched_type = item.item.sched.item_type();
+        if item.item.type_ != sched_type {
+            problems.push(format!(
+                "{name}: type is {}, but its schedule is for {}; set the \
+                 schedule again with {fix} to fix the type",
+                item.item.type_.as_ref(), sched_type.as_ref()));
+        }
+        problems.extend(sched::problems(&item.item.sched).into_iter()
+            .map(|problem| {
+                format!("{name}: {problem}; fix the schedule with {fix}")
+            }));
+    }
+    Ok(problems)
+}
+
+/// Check the config and database, and print the problems found.
+pub fn run(cfg: &impl Config, format: output::Format) -> Result<(), String> {
+    let mut report = Report {
+        config: check_config(cfg),
+        ..Report::default()
+    };
+
+    match db::open(cfg) {
+        Ok(mut db) => {
+            report.database = db.check()?;
+            // a remote database's user is chosen by the server
+            let remote = config::get_parsed(cfg, &configrefs::DB_BACKEND)?
+                == "remote";
+            let users = if remote { vec![] } else { db.find_users(None)? };
+            report.schedules = check_schedules(&mut db, None)?;
+            for user in &users {
+                report.schedules.extend(check_schedules(&mut db, Some(user))?);
+            }
+        }
+        Err(e) => {
+            report.database.push(format!(
+                "can't open the database: {e}; check the db config values"));
+        }
+    }
+
+    report.print(format)?;
+    match report.count() {
+        0 => Ok(()),
+        count => Err(format!("found {count} problems")),
+    }
+}
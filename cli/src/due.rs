@@ -0,0 +1,88 @@
+//! Reporting occurrences which need attention.
+
+use std::time::Duration;
+use dunsumday::config::{self, Config};
+use dunsumday::configrefs;
+use dunsumday::db::Db;
+use dunsumday::util;
+use dunsumday::util::clock::Clock;
+use dunsumday::util::progress;
+use dunsumday::util::status::{self, OccStatus};
+use crate::output::{self, ItemOcc};
+
+/// Find current occurrences which are in their alert period or overdue.
+///
+/// If `within` is given, it's used as the alert period for every occurrence,
+/// instead of the configured alert periods.
+fn find(
+    cfg: &impl Config,
+    db: &mut impl Db,
+    clock: &dyn Clock,
+    within: Option<Duration>,
+) -> Result<Vec<ItemOcc>, String> {
+    let now = clock.now();
+    let current = util::get_current_items(db, now)?;
+    let occs = current.iter()
+        .map(|(item, occ)| (item, occ))
+        .collect::<Vec<_>>();
+    let mut progress = progress::resolve_item_occs_progress(db, &occs)?;
+    let statuses = match within {
+        Some(within) => occs.iter()
+            .map(|(_, occ)| {
+                let status = status::occ_status(
+                    &occ.occ, progress.get(&occ.id), within, now);
+                (occ.id.clone(), status)
+            })
+            .collect(),
+        None => {
+            let default_alert = config::get_parsed(
+                cfg, &configrefs::OCC_ALERT_DEFAULT)?;
+            status::resolve_occs_status(db, &occs, default_alert, now)?
+        }
+    };
+
+    Ok(current.into_iter()
+        .filter_map(|(item, occ)| {
+            let status = statuses.get(&occ.id).copied()?;
+            matches!(status, OccStatus::Alert | OccStatus::Overdue)
+                .then(|| ItemOcc {
+                    progress: progress.remove(&occ.id),
+                    item,
+                    occ,
+                    status: Some(status),
+                })
+        })
+        .collect())
+}
+
+/// Print current occurrences which are in their alert period or overdue, and
+/// return whether there were any.
+pub fn run(
+    cfg: &impl Config,
+    db: &mut impl Db,
+    clock: &dyn Clock,
+    within: Option<Duration>,
+    format: output::Format,
+) -> Result<bool, String> {
+    let due = find(cfg, db, clock, within)?;
+    match format {
+        output::Format::Plain => {
+            // print nothing when there's nothing due, for cron
+            if !due.is_empty() {
+                let rows = due.iter()
+                    .map(|due| [
+                        due.item.id.clone(),
+                        due.item.item.name.clone(),
+                        output::progress(due.progress.as_ref()),
+                        output::date(due.occ.occ.end),
+                        output::status(due.status).to_owned(),
+                    ])
+                    .collect::<Vec<_>>();
+                output::table(["ID", "NAME", "PROGRESS", "DUE", "STATUS"],
+                              &rows);
+            }
+        }
+        output::Format::Json => output::json(&due)?,
+    }
+    Ok(!due.is_empty())
+}
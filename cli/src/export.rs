@@ -0,0 +1,22 @@
+use std::path::Path;
+use chrono::NaiveDate;
+use dunsumday::db::Db;
+use dunsumday::util::clock::Clock;
+use dunsumday::util::export::{self, Format};
+use crate::occ::day_start;
+use crate::output;
+
+/// Export items and occurrences to `output`, or standard output.
+pub fn run(
+    db: &impl Db,
+    clock: &dyn Clock,
+    format: Format,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    output: Option<&Path>,
+) -> Result<(), String> {
+    let start = from.map(day_start);
+    let end = to.and_then(|day| day.succ_opt()).map(day_start);
+    let out = export::fetch(db, start, end)?.write(format, clock.now())?;
+    output::write(&out, output)
+}
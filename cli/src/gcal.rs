@@ -0,0 +1,131 @@
+//! Pushing upcoming occurrences to Google Calendar.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+use dunsumday::config::{self, Config};
+use dunsumday::configrefs;
+use dunsumday::db::Db;
+use dunsumday::util::clock::Clock;
+use dunsumday::util::gcal::{self, Client};
+use crate::output;
+
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Push occurrences once, and print the result.
+fn push_once(
+    cfg: &impl Config,
+    db: &mut impl Db,
+    clock: &dyn Clock,
+    period: Duration,
+    format: output::Format,
+) -> Result<(), String> {
+    // access tokens expire, so get a new one every time
+    let client = Client::from_config(cfg)?
+        .ok_or("no calendar configured (sync.gcal.calendar-id)".to_owned())?;
+    let pushed = gcal::push(db, &client, period, clock.now())?;
+
+    match format {
+        output::Format::Plain => {
+            output::table(["CREATED", "UPDATED", "REMOVED"], &[[
+                pushed.created.to_string(),
+                pushed.updated.to_string(),
+                pushed.removed.to_string(),
+            ]]);
+        }
+        output::Format::Json => output::json(&pushed)?,
+    }
+    Ok(())
+}
+
+/// Push upcoming occurrences to the configured calendar, and print the
+/// result.
+///
+/// If `daemon` is `true`, keep pushing at the configured interval, logging any
+/// errors, instead of returning.
+pub fn run(
+    cfg: &impl Config,
+    db: &mut impl Db,
+    clock: &dyn Clock,
+    daemon: bool,
+    format: output::Format,
+) -> Result<(), String> {
+    if cfg.get_ref(&configrefs::SYNC_GCAL_CALENDAR_ID).is_empty() {
+        return Err("no calendar configured (sync.gcal.calendar-id)"
+                   .to_owned());
+    }
+    let period = config::get_parsed(cfg, &configrefs::SYNC_GCAL_PERIOD)?;
+    if !daemon {
+        return push_once(cfg, db, clock, period, format);
+    }
+
+    let interval = config::get_parsed(cfg, &configrefs::SYNC_GCAL_INTERVAL)?
+        .max(MIN_INTERVAL);
+    loop {
+        if let Err(e) = push_once(cfg, db, clock, period, format) {
+            log::error!("error pushing to calendar: {e}");
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// Wait for the browser to be redirected back to `listener` after allowing
+/// access, and return the authorisation code.
+fn receive_code(listener: &TcpListener) -> Result<String, String> {
+    let (mut stream, _) = listener.accept()
+        .map_err(|e| format!("error receiving redirect: {e}"))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)
+        .map_err(|e| format!("error reading redirect: {e}"))?;
+    // eg. `GET /?code=...&scope=... HTTP/1.1`
+    let query = request_line.split_whitespace()
+        .nth(1)
+        .and_then(|target| target.split_once('?'))
+        .map(|(_, query)| query)
+        .unwrap_or("");
+    let code = gcal::redirect_code(query);
+
+    let message = match &code {
+        Ok(_) => "Access allowed.  You can close this page.".to_owned(),
+        Err(e) => format!("Error: {e}"),
+    };
+    // the browser has already been redirected, so failing to respond doesn't
+    // matter
+    let _ = write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{message}",
+        message.len());
+    code
+}
+
+/// Ask the user to allow access to their calendars, and print the resulting
+/// refresh token.
+pub fn auth(cfg: &impl Config) -> Result<(), String> {
+    let client_id = cfg.get_ref(&configrefs::SYNC_GCAL_CLIENT_ID);
+    let client_secret = config::get_parsed(
+        cfg, &configrefs::SYNC_GCAL_CLIENT_SECRET)?;
+    if client_id.is_empty() || client_secret.is_empty() {
+        return Err("no OAuth client configured (sync.gcal.client-id, \
+                    sync.gcal.client-secret)".to_owned());
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .map_err(|e| format!("error listening for redirect: {e}"))?;
+    let port = listener.local_addr()
+        .map_err(|e| format!("error listening for redirect: {e}"))?
+        .port();
+    let redirect_uri = format!("http://127.0.0.1:{port}");
+    eprintln!("Open this URL to allow access to your calendars:");
+    eprintln!();
+    eprintln!("{}", gcal::authorize_url(client_id, &redirect_uri));
+    eprintln!();
+
+    let code = receive_code(&listener)?;
+    let token = gcal::exchange_code(
+        client_id, &client_secret, &code, &redirect_uri)?;
+    eprintln!("Set sync.gcal.refresh-token to:");
+    println!("{token}");
+    Ok(())
+}
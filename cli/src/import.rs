@@ -0,0 +1,134 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use dunsumday::db::Db;
+use dunsumday::db::util as dbutil;
+use dunsumday::util::{habits, import, taskwarrior};
+use dunsumday::util::import::ImportedHistory;
+use serde::Serialize;
+use crate::args::ImportCommand;
+use crate::output;
+
+/// Result of an import, for JSON output.
+#[derive(Clone, Debug, Serialize)]
+struct ImportResult<T> {
+    /// Created items, or items which would be created for a dry run.
+    items: Vec<T>,
+    /// Description of each entry which couldn't be imported.
+    skipped: Vec<String>,
+}
+
+pub fn run(db: &mut impl Db, command: ImportCommand, format: output::Format)
+-> Result<(), String> {
+    match command {
+        ImportCommand::Ics { file, dry_run } => {
+            let imported = import::ics(open(&file)?)?;
+            if format == output::Format::Json {
+                return if dry_run {
+                    output::json(&ImportResult {
+                        items: imported.items,
+                        skipped: imported.skipped,
+                    })
+                } else {
+                    output::json(&ImportResult {
+                        items: dbutil::create_items(db, &imported.items)?,
+                        skipped: imported.skipped,
+                    })
+                };
+            }
+            for skipped in &imported.skipped {
+                eprintln!("skipped {skipped}");
+            }
+
+            let rows = if dry_run {
+                imported.items.into_iter()
+                    .map(|item| [
+                        "-".to_owned(),
+                        item.category.unwrap_or_default(),
+                        item.name,
+                    ])
+                    .collect::<Vec<_>>()
+            } else {
+                dbutil::create_items(db, &imported.items)?.into_iter()
+                    .map(|item| [
+                        item.id,
+                        item.item.category.unwrap_or_default(),
+                        item.item.name,
+                    ])
+                    .collect()
+            };
+            output::table(["ID", "CATEGORY", "NAME"], &rows);
+            Ok(())
+        }
+
+        ImportCommand::Loop { habits, checkmarks, dry_run } => {
+            let imported = habits::loop_csv(
+                open(&habits)?, open(&checkmarks)?)?;
+            history(db, habits::LOOP_SOURCE, imported, dry_run, format)
+        }
+
+        ImportCommand::Habitica { file, dry_run } => {
+            let imported = habits::habitica(open(&file)?)?;
+            history(db, habits::HABITICA_SOURCE, imported, dry_run, format)
+        }
+
+        ImportCommand::Taskwarrior { file, dry_run } => {
+            let imported = taskwarrior::read(open(&file)?)?;
+            history(db, taskwarrior::SOURCE, imported, dry_run, format)
+        }
+    }
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("error opening file ({}): {e}", path.display()))
+}
+
+/// Create items imported with their history from `source`, and print them.
+fn history(
+    db: &mut impl Db,
+    source: &str,
+    imported: ImportedHistory,
+    dry_run: bool,
+    format: output::Format,
+) -> Result<(), String> {
+    if format == output::Format::Json {
+        return if dry_run {
+            output::json(&ImportResult {
+                items: imported.items.into_iter()
+                    .map(|imported| imported.item)
+                    .collect(),
+                skipped: imported.skipped,
+            })
+        } else {
+            output::json(&ImportResult {
+                items: import::create_history(db, source, &imported.items)?,
+                skipped: imported.skipped,
+            })
+        };
+    }
+    for skipped in &imported.skipped {
+        eprintln!("skipped {skipped}");
+    }
+
+    let rows = if dry_run {
+        imported.items.into_iter()
+            .map(|imported| [
+                "-".to_owned(),
+                imported.item.category.unwrap_or_default(),
+                imported.item.name,
+            ])
+            .collect::<Vec<_>>()
+    } else {
+        import::create_history(db, source, &imported.items)?.into_iter()
+            .map(|item| [
+                item.id,
+                item.item.category.unwrap_or_default(),
+                item.item.name,
+            ])
+            .collect()
+    };
+    output::table(["ID", "CATEGORY", "NAME"], &rows);
+    Ok(())
+}
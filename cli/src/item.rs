@@ -0,0 +1,284 @@
+use dunsumday::db::{Db, DbUpdate, SortDirection, StoredItem, StoredOcc};
+use dunsumday::db::util as dbutil;
+use dunsumday::types::{Item, ItemType};
+use dunsumday::util::{self, config};
+use dunsumday::util::clock::Clock;
+use dunsumday::util::progress::{self, ProgressError, TaskProgress};
+use serde::Serialize;
+use crate::args::{ItemChange, ItemCommand};
+use crate::output::{self, ItemOcc};
+use crate::wizard;
+
+/// Item with its current occurrence, for JSON output.
+#[derive(Clone, Debug, Serialize)]
+struct ItemDetails {
+    item: StoredItem,
+    current_occ: Option<StoredOcc>,
+    /// Missing for events, and if there's no current occurrence.
+    progress: Option<TaskProgress>,
+}
+
+/// Convert an empty value to `None`.
+fn non_empty(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
+
+/// Print an item in the given format, or its ID in plain format.
+fn print_item(item: &StoredItem, format: output::Format)
+-> Result<(), String> {
+    match format {
+        output::Format::Plain => {
+            println!("{}", item.id);
+            Ok(())
+        }
+        output::Format::Json => output::json(item),
+    }
+}
+
+/// Print a list of items in the given format.
+fn print_items(items: Vec<StoredItem>, format: output::Format)
+-> Result<(), String> {
+    if format == output::Format::Json {
+        return output::json(&items);
+    }
+    let rows = items.into_iter()
+        .map(|item| [
+            item.id,
+            item.item.type_.as_ref().to_owned(),
+            item.item.category.unwrap_or_default(),
+            item.item.name,
+            if item.item.active { "" } else { "inactive" }.to_owned(),
+        ])
+        .collect::<Vec<_>>();
+    output::table(["ID", "TYPE", "CATEGORY", "NAME", ""], &rows);
+    Ok(())
+}
+
+fn list(db: &impl Db, all: bool, format: output::Format)
+-> Result<(), String> {
+    let active = if all { None } else { Some(true) };
+    let items = db.find_items(active, None, None, SortDirection::Asc, None,
+                              u32::MAX)?;
+    print_items(items, format)
+}
+
+/// Apply `changes` to all items matching the filters, and print the items
+/// which changed.
+fn bulk(
+    db: &mut impl Db,
+    category: Option<&str>,
+    type_: Option<ItemType>,
+    active: Option<bool>,
+    changes: &[ItemChange],
+    dry_run: bool,
+    format: output::Format,
+) -> Result<(), String> {
+    let items = db.find_items(active, category, None, SortDirection::Asc,
+                              None, u32::MAX)?
+        .into_iter()
+        .filter(|item| type_.is_none_or(|type_| item.item.type_ == type_))
+        .filter_map(|item| {
+            let mut changed = item.clone();
+            for change in changes {
+                change.apply(&mut changed.item);
+            }
+            (changed != item).then_some(changed)
+        })
+        .collect::<Vec<_>>();
+
+    if dry_run || items.is_empty() {
+        return print_items(items, format);
+    }
+    let updates = items.iter()
+        .map(DbUpdate::update_item)
+        .collect::<Vec<_>>();
+    db.write(&updates.iter().collect::<Vec<_>>())?;
+    // fetch again for the new update times
+    let ids = items.iter()
+        .map(|item| item.id.as_str())
+        .collect::<Vec<_>>();
+    print_items(db.get_items(&ids)?, format)
+}
+
+fn show(
+    db: &mut impl Db,
+    clock: &dyn Clock,
+    query: &str,
+    format: output::Format,
+) -> Result<(), String> {
+    let item = dbutil::find_item(db, query)?;
+    let occ = util::get_item_current_occ(db, clock.now(), &item)?;
+    let progress = match &occ {
+        Some(occ) => progress::resolve_item_occs_progress(
+            db, &[(&item, occ)])?.remove(&occ.id),
+        None => None,
+    };
+
+    if format == output::Format::Json {
+        return output::json(&ItemDetails {
+            item,
+            current_occ: occ,
+            progress,
+        });
+    }
+    output::json(&item)?;
+    match occ {
+        Some(occ) => {
+            println!("current occurrence: {} ({} to {}), progress: {}",
+                     occ.id, output::date(occ.occ.start),
+                     output::date(occ.occ.end),
+                     output::progress(progress.as_ref()));
+        }
+        None => println!("no current occurrence"),
+    }
+    Ok(())
+}
+
+pub fn run(
+    db: &mut impl Db,
+    clock: &dyn Clock,
+    command: ItemCommand,
+    format: output::Format,
+) -> Result<(), String> {
+    match command {
+        ItemCommand::Add { interactive: true, name, category, desc,
+                           inactive, .. } => {
+            let item = wizard::run(db, clock, wizard::Defaults {
+                name,
+                category,
+                desc,
+                active: !inactive,
+            })?;
+            print_item(&item, format)
+        }
+        ItemCommand::Add { name: Some(name), sched: Some(sched), category,
+                           desc, inactive, .. } => {
+            let item = dbutil::create_item(db, Item {
+                type_: sched.item_type(),
+                active: !inactive,
+                category,
+                name,
+                desc,
+                sched,
+            })?;
+            print_item(&item, format)
+        }
+        ItemCommand::Add { .. } => {
+            Err("a name and schedule are required".to_owned())
+        }
+        ItemCommand::List { all } => list(db, all, format),
+        ItemCommand::Show { item } => show(db, clock, &item, format),
+        ItemCommand::Edit { item, name, sched, category, desc, active } => {
+            let mut item = dbutil::find_item(db, &item)?;
+            if let Some(name) = name {
+                item.item.name = name;
+            }
+            if let Some(sched) = sched {
+                item.item.type_ = sched.item_type();
+                item.item.sched = sched;
+            }
+            if let Some(category) = category {
+                item.item.category = non_empty(category);
+            }
+            if let Some(desc) = desc {
+                item.item.desc = non_empty(desc);
+            }
+            if let Some(active) = active {
+                item.item.active = active;
+            }
+            dbutil::update_item(db, &item)?;
+            if format == output::Format::Json {
+                // fetch again for the new update time
+                output::json(&dbutil::find_item(db, &item.id)?)?;
+            }
+            Ok(())
+        }
+        ItemCommand::Bulk { category, type_, active, set, dry_run, .. } => {
+            bulk(db, category.as_deref(), type_, active, &set, dry_run,
+                 format)
+        }
+        ItemCommand::Rm { item } => {
+            let item = dbutil::find_item(db, &item)?;
+            dbutil::delete_item(db, &item.id)?;
+            if format == output::Format::Json {
+                output::json(&item)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Describe an error adding progress to the item found by `query`.
+fn progress_error(e: ProgressError, query: &str) -> String {
+    match e {
+        ProgressError::NotTracked => format!("item is an event: {query}"),
+        ProgressError::NoCurrentOcc => {
+            format!("item has no current occurrence: {query}")
+        }
+        e => e.to_string(),
+    }
+}
+
+/// Add progress to an item's current occurrence, and print the new progress.
+pub fn done(
+    db: &mut impl Db,
+    clock: &dyn Clock,
+    query: &str,
+    amount: Option<i64>,
+    format: output::Format,
+) -> Result<(), String> {
+    let item = dbutil::find_item(db, query)?;
+    let (occ, progress) = progress::add_current_progress(
+        db, &item, amount, None, clock.now())
+        .map_err(|e| progress_error(e, query))?;
+    if format == output::Format::Json {
+        return output::json(&ItemOcc {
+            item,
+            occ,
+            progress: Some(progress),
+            status: None,
+        });
+    }
+    let status = if progress.is_complete() { " complete" } else { "" };
+    println!("{}: {}{status}", item.item.name,
+             output::progress(Some(&progress)));
+    Ok(())
+}
+
+/// Add an amount of progress to an item's current occurrence, and print the
+/// new progress.
+pub fn log(
+    db: &mut impl Db,
+    clock: &dyn Clock,
+    query: &str,
+    amount: &str,
+    note: Option<&str>,
+    format: output::Format,
+) -> Result<(), String> {
+    let item = dbutil::find_item(db, query)?;
+    if item.item.type_ == ItemType::Event {
+        return Err(format!("item is an event: {query}"));
+    }
+    let unit = config::get_item_config(db, &item)?
+        .and_then(|config| config.resolved_config.task_completion_conf.unit);
+    let amount = progress::parse_amount(amount, unit.as_deref())?;
+    let (occ, progress) = progress::add_current_progress(
+        db, &item, Some(amount), note, clock.now())
+        .map_err(|e| progress_error(e, query))?;
+
+    match format {
+        output::Format::Plain => {
+            let unit = unit.map(|unit| format!(" {unit}")).unwrap_or_default();
+            let status = if progress.is_complete() { " complete" } else { "" };
+            println!("{}: {}{unit}{status}", item.item.name,
+                     output::progress(Some(&progress)));
+            Ok(())
+        }
+        output::Format::Json => output::json(&ItemOcc {
+            item,
+            occ,
+            progress: Some(progress),
+            status: None,
+        }),
+    }
+}
@@ -0,0 +1,149 @@
+use std::process;
+use std::sync::Arc;
+use clap::Parser;
+use dunsumday::config::{self, layered};
+use dunsumday::config::file::{self, Format};
+use dunsumday::configrefs;
+use dunsumday::db::{self, Db};
+use dunsumday::db::util as dbutil;
+use dunsumday::util::clock;
+use args::{Command, ImportCommand};
+
+mod args;
+mod backup;
+mod configs;
+mod devicesync;
+mod doctor;
+mod due;
+mod export;
+mod gcal;
+mod import;
+mod item;
+mod notify;
+mod occ;
+mod output;
+mod report;
+mod sync;
+mod today;
+mod tui;
+mod wizard;
+
+/// Check that `args` don't need database operations which the remote backend
+/// doesn't support (see [`dunsumday::db::remote`]), so that commands fail
+/// before doing anything.
+fn check_remote(args: &args::Args) -> Result<(), String> {
+    if args.user.is_some() {
+        return Err("--user is not supported by the remote database: the \
+                    server chooses the user".to_owned());
+    }
+    let command = match &args.command {
+        Command::Import(
+            ImportCommand::Loop { dry_run: false, .. } |
+            ImportCommand::Habitica { dry_run: false, .. } |
+            ImportCommand::Taskwarrior { dry_run: false, .. }
+        ) => "import with history",
+        Command::Report { .. } => "report",
+        Command::Stats { .. } => "stats",
+        Command::Notify { .. } => "notify",
+        Command::Sync { .. } => "sync",
+        Command::SyncServer { .. } => "sync-server",
+        Command::Backup => "backup",
+        _ => return Ok(()),
+    };
+    Err(format!("{command} is not supported by the remote database"))
+}
+
+fn main() -> Result<(), String> {
+    env_logger::init();
+    let args = args::Args::parse();
+    match args.command {
+        Command::Completions { shell } => {
+            args::print_completions(shell);
+            return Ok(());
+        }
+        Command::Man => return args::print_man(),
+        _ => (),
+    }
+
+    let format = args.config_format
+        .unwrap_or_else(|| Format::from_path(&args.config));
+    let cfg = layered::new(vec![
+        Arc::new(config::args::new(&args.set)?),
+        Arc::new(file::new_with_format(&args.config, format)?),
+    ]);
+    if let Command::Doctor = args.command {
+        return doctor::run(&cfg, args.output);
+    }
+    config::check_unresolved(&cfg, configrefs::ALL)
+        .map_err(|errors| {
+            errors.iter().map(ToString::to_string).collect::<Vec<_>>()
+                .join("\n")
+        })?;
+    if let Command::GcalAuth = args.command {
+        return gcal::auth(&cfg);
+    }
+    if config::get_parsed(&cfg, &configrefs::DB_BACKEND)? == "remote" {
+        check_remote(&args)?;
+    }
+    let clock = clock::from_config(&cfg)?;
+    let clock = clock.as_ref();
+    let mut db = db::open(&cfg)?;
+    if let Some(name) = &args.user {
+        let user = dbutil::get_or_create_user(&mut db, name)?;
+        db.set_user(Some(&user.id))?;
+    }
+
+    match args.command {
+        Command::Item(command) => {
+            item::run(&mut db, clock, command, args.output)
+        }
+        Command::Occ(command) => occ::run(&db, command, args.output),
+        Command::Config(command) => {
+            configs::run(&mut db, command, args.output)
+        }
+        Command::Import(command) => {
+            import::run(&mut db, command, args.output)
+        }
+        Command::Export { format, from, to, output_file } => {
+            export::run(&db, clock, format, from, to, output_file.as_deref())
+        }
+        Command::Done { item, amount } => {
+            item::done(&mut db, clock, &item, amount, args.output)
+        }
+        Command::Log { item, amount, note } => {
+            item::log(&mut db, clock, &item, &amount, note.as_deref(),
+                      args.output)
+        }
+        Command::Report { range, format, output_file } => {
+            report::run(&db, clock, range, format, output_file.as_deref())
+        }
+        Command::Stats { from, to, group_by, period, output_file } => {
+            report::stats(&db, clock, from, to, group_by, period,
+                          output_file.as_deref())
+        }
+        Command::Today => today::run(&cfg, &mut db, clock, args.output),
+        Command::Due { within, format } => {
+            let format = format.unwrap_or(args.output);
+            if due::run(&cfg, &mut db, clock, within, format)? {
+                process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Notify { daemon, desktop } => {
+            notify::run(&cfg, &mut db, clock, daemon, desktop, args.output)
+        }
+        Command::Sync { daemon } => {
+            sync::run(&cfg, &mut db, daemon, args.output)
+        }
+        Command::SyncServer { daemon } => {
+            devicesync::run(&cfg, &mut db, clock, daemon, args.output)
+        }
+        Command::SyncGcal { daemon } => {
+            gcal::run(&cfg, &mut db, clock, daemon, args.output)
+        }
+        Command::Backup => backup::run(&cfg, &db, clock, args.output),
+        Command::Tui => tui::run(&cfg, &mut db, clock),
+        Command::Doctor | Command::GcalAuth | Command::Completions { .. } |
+            Command::Man => Ok(()),
+    }
+}
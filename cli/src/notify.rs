@@ -0,0 +1,170 @@
+//! Sending alerts about occurrences which need attention, and milestones
+//! reached by tasks.
+
+use std::thread;
+use std::time::Duration;
+use dunsumday::config::{self, Config};
+use dunsumday::configrefs;
+use dunsumday::db::Db;
+use dunsumday::types::{AlertKind, Config as ItemConfig};
+use dunsumday::util::clock::Clock;
+use dunsumday::util::milestones::{self, PendingMilestone};
+use dunsumday::util::notify::{Channel, Notifier, PendingAlert};
+use dunsumday::util::notify::digest::Digest;
+use dunsumday::util::notify::template::Templates;
+use notify_rust::Notification;
+use crate::output;
+
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Channel which prints alerts to standard output.
+struct PrintChannel {
+    format: output::Format,
+    templates: Templates,
+}
+
+impl Channel for PrintChannel {
+    fn name(&self) -> &str {
+        "stdout"
+    }
+
+    // this is the command's output, so includes alerts for every channel
+    fn enabled(&self, _: &ItemConfig) -> bool {
+        true
+    }
+
+    fn send(&self, alert: &PendingAlert) -> Result<(), String> {
+        match self.format {
+            output::Format::Plain => {
+                println!("{}: {}", self.templates.subject(alert)?,
+                         self.templates.body(alert)?);
+            }
+            // one line per alert, since alerts are printed as they're sent
+            output::Format::Json => println!(
+                "{}", serde_json::to_string(alert)
+                    .map_err(|e| format!("error serialising output: {e}"))?),
+        }
+        Ok(())
+    }
+
+    fn send_digest(&self, digest: &Digest) -> Result<(), String> {
+        match self.format {
+            output::Format::Plain => {
+                println!("{}\n\n{}", digest.subject(), digest.body());
+            }
+            output::Format::Json => println!(
+                "{}", serde_json::to_string(digest)
+                    .map_err(|e| format!("error serialising output: {e}"))?),
+        }
+        Ok(())
+    }
+
+    fn send_milestone(&self, milestone: &PendingMilestone)
+    -> Result<(), String> {
+        match self.format {
+            output::Format::Plain => {
+                println!("{}: {}", milestone.subject(), milestone.body());
+            }
+            output::Format::Json => println!(
+                "{}", serde_json::to_string(milestone)
+                    .map_err(|e| format!("error serialising output: {e}"))?),
+        }
+        Ok(())
+    }
+}
+
+/// Channel which shows alerts as native notifications in the desktop session.
+struct DesktopChannel {
+    templates: Templates,
+}
+
+impl Channel for DesktopChannel {
+    fn name(&self) -> &str {
+        "desktop"
+    }
+
+    fn send(&self, alert: &PendingAlert) -> Result<(), String> {
+        let mut notification = Notification::new();
+        notification
+            .appname("dunsumday")
+            .summary(&self.templates.subject(alert)?)
+            .body(&self.templates.body(alert)?);
+        #[cfg(any(windows, all(unix, not(target_os = "macos"))))]
+        notification.urgency(match alert.kind {
+            AlertKind::Upcoming => notify_rust::Urgency::Normal,
+            AlertKind::Overdue | AlertKind::Escalated =>
+                notify_rust::Urgency::Critical,
+        });
+        show(&notification)
+    }
+
+    fn send_digest(&self, digest: &Digest) -> Result<(), String> {
+        let mut notification = Notification::new();
+        notification
+            .appname("dunsumday")
+            .summary(&digest.subject())
+            .body(&digest.body());
+        show(&notification)
+    }
+
+    fn send_milestone(&self, milestone: &PendingMilestone)
+    -> Result<(), String> {
+        let mut notification = Notification::new();
+        notification
+            .appname("dunsumday")
+            .summary(&milestone.subject())
+            .body(&milestone.body());
+        show(&notification)
+    }
+}
+
+fn show(notification: &Notification) -> Result<(), String> {
+    notification.show()
+        .map(|_| ())
+        .map_err(|e| format!("error showing notification: {e}"))
+}
+
+/// Send alerts which are due, or a digest if enabled, and newly reached
+/// milestones through the configured channels and by printing them.  If
+/// `desktop` is `true`, also show them as desktop notifications.
+///
+/// If `daemon` is `true`, keep checking for alerts and milestones at the
+/// configured interval, logging any errors, instead of returning.
+pub fn run(
+    cfg: &impl Config,
+    db: &mut impl Db,
+    clock: &dyn Clock,
+    daemon: bool,
+    desktop: bool,
+    format: output::Format,
+) -> Result<(), String> {
+    let mut notifier = Notifier::new(cfg, clock.utc_offset())?;
+    let defaults = milestones::Defaults::new(cfg)?;
+    let templates = Templates::new(cfg)?;
+    notifier.add_channel(Box::new(PrintChannel {
+        format,
+        templates: templates.clone(),
+    }));
+    if desktop {
+        notifier.add_channel(Box::new(DesktopChannel { templates }));
+    }
+    if !daemon {
+        notifier.check(db, clock.now())?;
+        milestones::check(db, &defaults, &notifier, clock.now())?;
+        return Ok(());
+    }
+
+    let interval = config::get_parsed(
+        cfg, &configrefs::NOTIFICATIONS_INTERVAL)?
+        .max(MIN_INTERVAL);
+    loop {
+        if let Err(e) = notifier.check(db, clock.now()) {
+            log::error!("error sending alerts: {e}");
+        }
+        if let Err(e) = milestones::check(db, &defaults, &notifier, clock.now())
+        {
+            log::error!("error checking milestones: {e}");
+        }
+        thread::sleep(interval);
+    }
+}
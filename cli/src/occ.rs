@@ -0,0 +1,63 @@
+use chrono::{NaiveDate, NaiveTime};
+use dunsumday::db::{Db, SortDirection};
+use dunsumday::db::util as dbutil;
+use dunsumday::types::OccDate;
+use dunsumday::util::export::ExportOcc;
+use dunsumday::util::progress;
+use crate::args::OccCommand;
+use crate::output;
+
+/// Start of a day, in UTC.
+pub fn day_start(day: NaiveDate) -> OccDate {
+    day.and_time(NaiveTime::MIN).and_utc()
+}
+
+pub fn run(db: &impl Db, command: OccCommand, format: output::Format)
+-> Result<(), String> {
+    match command {
+        OccCommand::List { item, start, end } => {
+            let item = dbutil::find_item(db, &item)?;
+            let occs = db.find_occs(
+                &[&item.id], start.map(day_start), end.map(day_start),
+                SortDirection::Asc, None, u32::MAX)?
+                .remove(&item.id)
+                .unwrap_or_default();
+            let occ_refs = occs.iter()
+                .map(|occ| (&item, occ))
+                .collect::<Vec<_>>();
+            let mut progress = progress::resolve_item_occs_progress(
+                db, &occ_refs)?;
+            if format == output::Format::Json {
+                let occ_ids = occs.iter()
+                    .map(|occ| occ.id.as_str())
+                    .collect::<Vec<_>>();
+                let mut entries = db.find_progress_entries(
+                    &occ_ids, SortDirection::Asc, u32::MAX)?;
+                let occs = occs.into_iter()
+                    .map(|occ| ExportOcc {
+                        item_id: item.id.clone(),
+                        progress: progress.remove(&occ.id),
+                        entries: entries.remove(&occ.id)
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|entry| entry.entry)
+                            .collect(),
+                        occ,
+                    })
+                    .collect::<Vec<_>>();
+                return output::json(&occs);
+            }
+
+            let rows = occs.iter()
+                .map(|occ| [
+                    occ.id.clone(),
+                    output::date(occ.occ.start),
+                    output::date(occ.occ.end),
+                    output::progress(progress.get(&occ.id)),
+                ])
+                .collect::<Vec<_>>();
+            output::table(["ID", "START", "END", "PROGRESS"], &rows);
+            Ok(())
+        }
+    }
+}
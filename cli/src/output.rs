@@ -0,0 +1,103 @@
+//! Formatting values for display.
+
+use std::fs;
+use std::path::Path;
+use dunsumday::db::{StoredItem, StoredOcc};
+use dunsumday::types::OccDate;
+use dunsumday::util::progress::TaskProgress;
+use dunsumday::util::status::OccStatus;
+use serde::Serialize;
+
+/// How to print results.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    /// Human-readable text.
+    #[default]
+    Plain,
+    Json,
+}
+
+/// Occurrence along with its item, for JSON output.
+#[derive(Clone, Debug, Serialize)]
+pub struct ItemOcc {
+    pub item: StoredItem,
+    pub occ: StoredOcc,
+    /// Missing for events.
+    pub progress: Option<TaskProgress>,
+    /// Only included by commands which check the status.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<OccStatus>,
+}
+
+/// Print a value as pretty-printed JSON.
+pub fn json(value: &impl Serialize) -> Result<(), String> {
+    println!("{}", serde_json::to_string_pretty(value)
+        .map_err(|e| format!("error serialising output: {e}"))?);
+    Ok(())
+}
+
+/// Write `out` to a file at `path`, or print it if `path` is missing.
+pub fn write(out: &str, path: Option<&Path>) -> Result<(), String> {
+    match path {
+        Some(path) => fs::write(path, out)
+            .map_err(|e| format!("error writing file ({}): {e}",
+                                 path.display())),
+        None => {
+            if out.ends_with('\n') {
+                print!("{out}");
+            } else {
+                println!("{out}");
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Format a date, in UTC.
+pub fn date(date: OccDate) -> String {
+    date.format("%Y-%m-%d %H:%M").to_string()
+}
+
+/// Format task progress as `effective/total`, or `-` for events.
+pub fn progress(progress: Option<&TaskProgress>) -> String {
+    match progress {
+        Some(progress) => {
+            format!("{}/{}", progress.effective_progress(), progress.total)
+        }
+        None => "-".to_owned(),
+    }
+}
+
+/// Describe an occurrence's status, or return an empty string if it doesn't
+/// need mentioning.
+pub fn status(status: Option<OccStatus>) -> &'static str {
+    match status {
+        Some(OccStatus::Alert) => "due soon",
+        Some(OccStatus::Complete) => "complete",
+        Some(OccStatus::Overdue) => "overdue",
+        Some(OccStatus::Idle) | None => "",
+    }
+}
+
+/// Print rows of values as a table with a header row, aligning columns.
+pub fn table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+    let mut widths = header.map(|name| name.chars().count());
+    for row in rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let print_row = |row: [&str; N]| {
+        let line = row.iter()
+            .zip(widths)
+            .map(|(value, width)| format!("{value:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    };
+    print_row(header);
+    for row in rows {
+        print_row(row.each_ref().map(|value| value.as_str()));
+    }
+}
@@ -0,0 +1,83 @@
+//! Writing completion reports.
+
+use std::path::Path;
+use chrono::{Days, NaiveDate, TimeDelta};
+use dunsumday::db::Db;
+use dunsumday::types::OccDate;
+use dunsumday::util::clock::Clock;
+use dunsumday::util::report::{self, Format};
+use dunsumday::util::stats::{self, GroupBy, Period};
+use crate::output;
+
+/// Time range ending now.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum Range {
+    /// The last 7 days.
+    Week,
+    /// The last 30 days.
+    #[default]
+    Month,
+    /// The last 365 days.
+    Year,
+    All,
+}
+
+impl Range {
+    fn start(self, now: OccDate) -> Option<OccDate> {
+        let days = match self {
+            Range::Week => 7,
+            Range::Month => 30,
+            Range::Year => 365,
+            Range::All => return None,
+        };
+        Some(now - TimeDelta::days(days))
+    }
+}
+
+/// Determine the report format from a file's extension, defaulting to
+/// Markdown.
+fn format_from_path(path: &Path) -> Format {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("html") ||
+            ext.eq_ignore_ascii_case("htm") => Format::Html,
+        _ => Format::Markdown,
+    }
+}
+
+/// Write a report to `output`, or standard output.
+pub fn run(
+    db: &impl Db,
+    clock: &dyn Clock,
+    range: Range,
+    format: Option<Format>,
+    output: Option<&Path>,
+) -> Result<(), String> {
+    let format = format
+        .or_else(|| output.map(format_from_path))
+        .unwrap_or_default();
+    let now = clock.now();
+    let report = report::fetch(db, range.start(now), now, now)?;
+    output::write(&report.write(format), output)
+}
+
+/// Write completion statistics as CSV to `output`, or standard output.
+pub fn stats(
+    db: &impl Db,
+    clock: &dyn Clock,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    group_by: GroupBy,
+    period: Period,
+    output: Option<&Path>,
+) -> Result<(), String> {
+    let now = clock.now();
+    let to = to.unwrap_or_else(|| now.date_naive());
+    let from = from
+        .or_else(|| to.checked_sub_days(Days::new(29)))
+        .unwrap_or(to);
+    if from > to {
+        return Err("--from must not be after --to".to_owned());
+    }
+    let csv = stats::export_csv(db, from..=to, group_by, period, now)?;
+    output::write(&csv, output)
+}
@@ -0,0 +1,86 @@
+//! Syncing events from subscribed iCalendar URLs as items.
+
+use std::thread;
+use std::time::Duration;
+use dunsumday::config::{self, Config};
+use dunsumday::configrefs;
+use dunsumday::db::Db;
+use dunsumday::util::sync::{self, Synced};
+use serde::Serialize;
+use crate::output;
+
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Result of syncing one URL, for JSON output.
+#[derive(Clone, Debug, Serialize)]
+struct SyncResult<'a> {
+    url: &'a str,
+    #[serde(flatten)]
+    synced: Synced,
+}
+
+/// Sync every configured URL once, and print the results.  A failure for one
+/// URL doesn't stop the others being synced.
+fn sync_all(urls: &[String], db: &mut impl Db, format: output::Format)
+-> Result<(), String> {
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    for url in urls {
+        match sync::ics(db, url) {
+            Ok(synced) => results.push(SyncResult { url, synced }),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    match format {
+        output::Format::Plain => {
+            for result in &results {
+                for skipped in &result.synced.skipped {
+                    eprintln!("skipped {skipped} ({})", result.url);
+                }
+            }
+            let rows = results.iter()
+                .map(|result| [
+                    result.url.to_owned(),
+                    result.synced.items.len().to_string(),
+                    result.synced.removed.len().to_string(),
+                ])
+                .collect::<Vec<_>>();
+            output::table(["URL", "ITEMS", "REMOVED"], &rows);
+        }
+        output::Format::Json => output::json(&results)?,
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Sync events from the configured URLs as items, and print the results.
+///
+/// If `daemon` is `true`, keep syncing at the configured interval, logging any
+/// errors, instead of returning.
+pub fn run(
+    cfg: &impl Config,
+    db: &mut impl Db,
+    daemon: bool,
+    format: output::Format,
+) -> Result<(), String> {
+    let urls = config::get_parsed(cfg, &configrefs::SYNC_ICS_URLS)?;
+    if urls.is_empty() {
+        return Err("no calendars configured (sync.ics.urls)".to_owned());
+    }
+    if !daemon {
+        return sync_all(&urls, db, format);
+    }
+
+    let interval = config::get_parsed(cfg, &configrefs::SYNC_ICS_INTERVAL)?
+        .max(MIN_INTERVAL);
+    loop {
+        if let Err(e) = sync_all(&urls, db, format) {
+            log::error!("error syncing calendars: {e}");
+        }
+        thread::sleep(interval);
+    }
+}
@@ -0,0 +1,54 @@
+use dunsumday::config::{self, Config};
+use dunsumday::configrefs;
+use dunsumday::db::Db;
+use dunsumday::util;
+use dunsumday::util::clock::Clock;
+use dunsumday::util::progress;
+use dunsumday::util::status;
+use crate::output::{self, ItemOcc};
+
+/// Print current items, with their progress and status.
+pub fn run(
+    cfg: &impl Config,
+    db: &mut impl Db,
+    clock: &dyn Clock,
+    format: output::Format,
+) -> Result<(), String> {
+    let now = clock.now();
+    let default_alert = config::get_parsed(
+        cfg, &configrefs::OCC_ALERT_DEFAULT)?;
+    let current = util::get_current_items(db, now)?;
+    let occs = current.iter()
+        .map(|(item, occ)| (item, occ))
+        .collect::<Vec<_>>();
+    let mut progress = progress::resolve_item_occs_progress(db, &occs)?;
+    let mut statuses = status::resolve_occs_status(
+        db, &occs, default_alert, now)?;
+
+    match format {
+        output::Format::Plain => {
+            let rows = current.iter()
+                .map(|(item, occ)| [
+                    item.id.clone(),
+                    item.item.name.clone(),
+                    output::progress(progress.get(&occ.id)),
+                    output::date(occ.occ.end),
+                    output::status(statuses.get(&occ.id).copied()).to_owned(),
+                ])
+                .collect::<Vec<_>>();
+            output::table(["ID", "NAME", "PROGRESS", "DUE", "STATUS"], &rows);
+        }
+        output::Format::Json => {
+            let current = current.into_iter()
+                .map(|(item, occ)| ItemOcc {
+                    progress: progress.remove(&occ.id),
+                    status: statuses.remove(&occ.id),
+                    item,
+                    occ,
+                })
+                .collect::<Vec<_>>();
+            output::json(&current)?;
+        }
+    }
+    Ok(())
+}
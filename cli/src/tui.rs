@@ -0,0 +1,321 @@
+//! Interactive terminal interface.
+
+use std::collections::BTreeSet;
+use std::iter;
+use std::time::Duration;
+use dunsumday::config::{self, Config};
+use dunsumday::configrefs;
+use dunsumday::db::{Db, SortDirection, StoredItem, StoredOcc};
+use dunsumday::db::util as dbutil;
+use dunsumday::util;
+use dunsumday::util::clock::Clock;
+use dunsumday::util::progress::{self, ProgressError, TaskProgress};
+use dunsumday::util::status::{self, OccStatus};
+use ratatui::{DefaultTerminal, Frame};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Paragraph, Row, Table, TableState, Tabs};
+use crate::output;
+
+/// How often to reload items when there's no input, so that statuses stay
+/// current.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+/// Width of progress bars, in characters.
+const PROGRESS_BAR_WIDTH: u16 = 20;
+const HELP: &str =
+    "q quit  ↑↓ select  ←→ category  enter done  +/- progress  \
+     p pause  r refresh";
+
+/// Item shown in the interface.
+struct Entry {
+    item: StoredItem,
+    /// Missing for paused items.
+    occ: Option<StoredOcc>,
+    /// Missing for events and paused items.
+    progress: Option<TaskProgress>,
+    status: Option<OccStatus>,
+}
+
+struct App<'c> {
+    clock: &'c dyn Clock,
+    default_alert: Duration,
+    /// Current items, followed by paused items.
+    entries: Vec<Entry>,
+    /// Categories of all entries, sorted.
+    categories: Vec<String>,
+    /// Category to show, or `None` to show all entries.
+    category: Option<String>,
+    table: TableState,
+    /// Result of the last action, shown instead of the help text.
+    message: String,
+}
+
+impl<'c> App<'c> {
+    fn new(clock: &'c dyn Clock, default_alert: Duration) -> App<'c> {
+        App {
+            clock,
+            default_alert,
+            entries: vec![],
+            categories: vec![],
+            category: None,
+            table: TableState::default(),
+            message: String::new(),
+        }
+    }
+
+    /// Entries in the selected category.
+    fn visible(&self) -> Vec<&Entry> {
+        self.entries.iter()
+            .filter(|entry| {
+                self.category.is_none() ||
+                    entry.item.item.category == self.category
+            })
+            .collect()
+    }
+
+    fn selected(&self) -> Option<&Entry> {
+        self.table.selected()
+            .and_then(|index| self.visible().into_iter().nth(index))
+    }
+
+    /// Index of the selected category's tab.
+    fn tab_index(&self) -> usize {
+        self.category.as_ref()
+            .and_then(|category| {
+                self.categories.iter().position(|name| name == category)
+            })
+            .map_or(0, |index| index + 1)
+    }
+
+    /// Select the entry for an item, or the nearest remaining entry if it's
+    /// no longer shown.
+    fn select_item(&mut self, item_id: Option<&str>) {
+        let visible = self.visible();
+        let index = item_id
+            .and_then(|id| visible.iter().position(|e| e.item.id == id))
+            .or_else(|| {
+                self.table.selected()
+                    .map(|index| index.min(visible.len().saturating_sub(1)))
+            })
+            .or(Some(0))
+            .filter(|_| !visible.is_empty());
+        self.table.select(index);
+    }
+
+    /// Fetch items from the database.
+    fn load(&mut self, db: &mut impl Db) -> Result<(), String> {
+        let now = self.clock.now();
+        let current = util::get_current_items(db, now)?;
+        let occs = current.iter()
+            .map(|(item, occ)| (item, occ))
+            .collect::<Vec<_>>();
+        let mut progress = progress::resolve_item_occs_progress(db, &occs)?;
+        let mut statuses = status::resolve_occs_status(
+            db, &occs, self.default_alert, now)?;
+        let paused = db.find_items(Some(false), None, None, SortDirection::Asc,
+                                   None, u32::MAX)?;
+
+        let selected_id = self.selected().map(|entry| entry.item.id.clone());
+        self.entries = current.into_iter()
+            .map(|(item, occ)| Entry {
+                progress: progress.remove(&occ.id),
+                status: statuses.remove(&occ.id),
+                item,
+                occ: Some(occ),
+            })
+            .chain(paused.into_iter().map(|item| Entry {
+                item,
+                occ: None,
+                progress: None,
+                status: None,
+            }))
+            .collect();
+        self.categories = self.entries.iter()
+            .filter_map(|entry| entry.item.item.category.clone())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        if self.tab_index() == 0 {
+            self.category = None;
+        }
+        self.select_item(selected_id.as_deref());
+        Ok(())
+    }
+
+    /// Switch to the category `offset` tabs away, wrapping around.
+    fn move_category(&mut self, offset: isize) {
+        let count = self.categories.len() + 1;
+        let index = (self.tab_index() + count).wrapping_add_signed(offset) %
+            count;
+        self.category = index.checked_sub(1)
+            .map(|index| self.categories[index].clone());
+        self.table.select(None);
+        self.select_item(None);
+    }
+
+    /// Add progress to the selected item's current occurrence.  `amount`
+    /// defaults to the amount needed to complete it.
+    fn add_progress(&mut self, db: &mut impl Db, amount: Option<i64>)
+    -> Result<(), String> {
+        let Some(entry) = self.selected() else { return Ok(()) };
+        let name = entry.item.item.name.clone();
+        if entry.progress.is_none() {
+            return Err(format!("{name} has no progress to track"));
+        }
+        let item = entry.item.clone();
+        let (_, progress) = progress::add_current_progress(
+            db, &item, amount, None, self.clock.now())
+            .map_err(|e| match e {
+                ProgressError::NoCurrentOcc => {
+                    format!("{name} has no current occurrence")
+                }
+                e => e.to_string(),
+            })?;
+        self.message = format!("{name}: {}",
+                               output::progress(Some(&progress)));
+        self.load(db)
+    }
+
+    /// Pause the selected item, or resume it if it's paused.
+    fn toggle_pause(&mut self, db: &mut impl Db) -> Result<(), String> {
+        let Some(entry) = self.selected() else { return Ok(()) };
+        let mut item = entry.item.clone();
+        item.item.active = !item.item.active;
+        dbutil::update_item(db, &item)?;
+        let action = if item.item.active { "resumed" } else { "paused" };
+        self.message = format!("{action} {}", item.item.name);
+        self.load(db)
+    }
+
+    /// Handle a key press, returning whether to quit.
+    fn handle_key(&mut self, db: &mut impl Db, key: KeyCode)
+    -> Result<bool, String> {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(true),
+            KeyCode::Up | KeyCode::Char('k') => self.table.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => self.table.select_next(),
+            KeyCode::Left | KeyCode::Char('h') | KeyCode::BackTab =>
+                self.move_category(-1),
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Tab =>
+                self.move_category(1),
+            KeyCode::Enter | KeyCode::Char(' ') =>
+                self.add_progress(db, None)?,
+            KeyCode::Char('+') => self.add_progress(db, Some(1))?,
+            KeyCode::Char('-') => self.add_progress(db, Some(-1))?,
+            KeyCode::Char('p') => self.toggle_pause(db)?,
+            KeyCode::Char('r') => self.load(db)?,
+            _ => (),
+        }
+        Ok(false)
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [tabs_area, table_area, footer_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ]).areas(frame.area());
+
+        let titles = iter::once("All")
+            .chain(self.categories.iter().map(String::as_str));
+        frame.render_widget(
+            Tabs::new(titles)
+                .select(self.tab_index())
+                .highlight_style(Style::new().add_modifier(Modifier::REVERSED)),
+            tabs_area);
+
+        let rows = self.visible().into_iter()
+            .map(entry_row)
+            .collect::<Vec<_>>();
+        let table = Table::new(rows, [
+            Constraint::Fill(1),
+            Constraint::Length(PROGRESS_BAR_WIDTH + 12),
+            Constraint::Length(16),
+            Constraint::Length(8),
+        ])
+            .header(Row::new(["NAME", "PROGRESS", "DUE", "STATUS"])
+                .style(Style::new().add_modifier(Modifier::BOLD)))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+
+        let footer = if self.message.is_empty() { HELP } else { &self.message };
+        frame.render_widget(Paragraph::new(footer), footer_area);
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal, db: &mut impl Db)
+    -> Result<(), String> {
+        let terminal_error = |e| format!("terminal error: {e}");
+        loop {
+            terminal.draw(|frame| self.draw(frame)).map_err(terminal_error)?;
+            let result = if event::poll(REFRESH_INTERVAL)
+                .map_err(terminal_error)?
+            {
+                match event::read().map_err(terminal_error)? {
+                    Event::Key(key) if key.kind == KeyEventKind::Press => {
+                        self.message.clear();
+                        self.handle_key(db, key.code)
+                    }
+                    _ => Ok(false),
+                }
+            } else {
+                self.load(db).map(|_| false)
+            };
+
+            match result {
+                Ok(true) => return Ok(()),
+                Ok(false) => (),
+                Err(e) => self.message = e,
+            }
+        }
+    }
+}
+
+/// Draw a bar showing progress towards the target completion amount, followed
+/// by the amounts.
+fn progress_bar(progress: &TaskProgress) -> String {
+    let width = usize::from(PROGRESS_BAR_WIDTH);
+    let filled = ((progress.fraction().min(1.0) * width as f64).round()
+                  as usize).min(width);
+    format!("{}{} {}", "█".repeat(filled), "░".repeat(width - filled),
+            output::progress(Some(progress)))
+}
+
+fn entry_row(entry: &Entry) -> Row<'static> {
+    let Some(occ) = &entry.occ else {
+        return Row::new([entry.item.item.name.clone(), String::new(),
+                         String::new(), "paused".to_owned()])
+            .style(Style::new().fg(Color::DarkGray));
+    };
+
+    let progress = match &entry.progress {
+        Some(progress) => progress_bar(progress),
+        None => output::progress(None),
+    };
+    let style = match entry.status {
+        Some(OccStatus::Overdue) => Style::new().fg(Color::Red),
+        Some(OccStatus::Alert) => Style::new().fg(Color::Yellow),
+        Some(OccStatus::Complete) => Style::new().fg(Color::Green),
+        Some(OccStatus::Idle) | None => Style::new(),
+    };
+    Row::new([
+        entry.item.item.name.clone(),
+        progress,
+        output::date(occ.occ.end),
+        output::status(entry.status).to_owned(),
+    ]).style(style)
+}
+
+/// Run the interface until the user quits.
+pub fn run(cfg: &impl Config, db: &mut impl Db, clock: &dyn Clock)
+-> Result<(), String> {
+    let default_alert = config::get_parsed(
+        cfg, &configrefs::OCC_ALERT_DEFAULT)?;
+    let mut app = App::new(clock, default_alert);
+    app.load(db)?;
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal, db);
+    ratatui::restore();
+    result
+}
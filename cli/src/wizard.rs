@@ -0,0 +1,405 @@
+//! Interactive item creation.
+//!
+//! Questions are written to standard error, so that standard output only
+//! contains the result.
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+use chrono::{Datelike, Month, NaiveDate, NaiveTime};
+use dunsumday::config::parse::{DurationParser, Parser};
+use dunsumday::db::{ConfigId, Db, SortDirection, StoredConfig, StoredItem};
+use dunsumday::db::util as dbutil;
+use dunsumday::types::{self, DayFilter, DeadlineTaskSched, EventSched, Item,
+                       ItemType, ProgressTaskSched, Sched, TargetKind};
+use dunsumday::util::clock::Clock;
+use dunsumday::util::sched;
+
+/// Number of days to show when previewing a schedule.
+const PREVIEW_DAYS: usize = 5;
+
+/// Values given on the command line, used as defaults.
+#[derive(Clone, Debug, Default)]
+pub struct Defaults {
+    pub name: Option<String>,
+    pub category: Option<String>,
+    pub desc: Option<String>,
+    pub active: bool,
+}
+
+struct Wizard<'c, R> {
+    input: R,
+    clock: &'c dyn Clock,
+}
+
+impl<R: BufRead> Wizard<'_, R> {
+    /// Ask a question, returning the trimmed answer, or `default` if the
+    /// answer is empty.
+    fn ask(&mut self, question: &str, default: Option<&str>)
+    -> Result<String, String> {
+        match default {
+            Some(default) if !default.is_empty() => {
+                eprint!("{question} [{default}]: ");
+            }
+            _ => eprint!("{question}: "),
+        }
+        io::stderr().flush()
+            .map_err(|e| format!("error writing question: {e}"))?;
+
+        let mut line = String::new();
+        let read = self.input.read_line(&mut line)
+            .map_err(|e| format!("error reading answer: {e}"))?;
+        if read == 0 {
+            eprintln!();
+            return Err("cancelled: input ended".to_owned());
+        }
+        let answer = line.trim();
+        Ok(if answer.is_empty() {
+            default.unwrap_or_default().to_owned()
+        } else {
+            answer.to_owned()
+        })
+    }
+
+    /// Ask a question until `parse` accepts the answer.
+    fn ask_parsed<T>(
+        &mut self,
+        question: &str,
+        default: Option<&str>,
+        parse: impl Fn(&str) -> Result<T, String>,
+    ) -> Result<T, String> {
+        loop {
+            match parse(&self.ask(question, default)?) {
+                Ok(value) => return Ok(value),
+                Err(e) => eprintln!("  {e}"),
+            }
+        }
+    }
+
+    /// Ask a question with an optional answer, until `parse` accepts the
+    /// answer or it's empty.
+    fn ask_opt<T>(
+        &mut self,
+        question: &str,
+        parse: impl Fn(&str) -> Result<T, String>,
+    ) -> Result<Option<T>, String> {
+        self.ask_parsed(question, None, |answer| {
+            if answer.is_empty() {
+                Ok(None)
+            } else {
+                parse(answer).map(Some)
+            }
+        })
+    }
+
+    /// Ask a yes/no question.
+    fn confirm(&mut self, question: &str, default: bool)
+    -> Result<bool, String> {
+        let default = if default { "y" } else { "n" };
+        self.ask_parsed(question, Some(default), |answer| {
+            match answer.to_ascii_lowercase().as_str() {
+                "y" | "yes" => Ok(true),
+                "n" | "no" => Ok(false),
+                _ => Err("answer y or n".to_owned()),
+            }
+        })
+    }
+
+    /// Ask for one of the `options`, which are pairs of descriptions and
+    /// values.  The answer is the option's number, starting from 1.
+    fn choose<T: Clone>(&mut self, question: &str, options: &[(&str, T)])
+    -> Result<T, String> {
+        eprintln!("{question}:");
+        for (index, (desc, _)) in options.iter().enumerate() {
+            eprintln!("  {}. {desc}", index + 1);
+        }
+        self.ask_parsed("choice", Some("1"), |answer| {
+            answer.parse::<usize>().ok()
+                .and_then(|num| num.checked_sub(1))
+                .and_then(|index| options.get(index))
+                .map(|(_, value)| value.clone())
+                .ok_or_else(|| {
+                    format!("enter a number from 1 to {}", options.len())
+                })
+        })
+    }
+
+    fn ask_event_day_filter(&mut self, initial_day: NaiveDate)
+    -> Result<DayFilter, String> {
+        let kind = self.choose("repeat", &[
+            ("every few days", "day"),
+            ("every few weeks, on one day of the week", "dow"),
+            ("every week, on several days of the week", "dows"),
+            ("on days of the month, every few months", "dom"),
+            ("on days of the week in the month (for example, the 2nd \
+              Tuesday), every few months", "wom"),
+            ("on a day of the year, every few years", "doy"),
+            ("never: only on the first day", "date"),
+        ])?;
+        let dow = initial_day.weekday().to_string();
+        let filter = match kind {
+            "day" => DayFilter::Day {
+                days_apart: self.ask_parsed(
+                    "days apart", Some("1"), parse_positive)?,
+            },
+            "dow" => DayFilter::Dow {
+                day: self.ask_parsed("day of the week", Some(&dow), parse)?,
+                weeks_apart: self.ask_parsed(
+                    "weeks apart", Some("1"), parse_positive)?,
+            },
+            "dows" => DayFilter::Dows {
+                days: self.ask_parsed(
+                    "days of the week, separated by commas", Some(&dow),
+                    parse_list)?,
+            },
+            "dom" => DayFilter::Dom {
+                days: self.ask_parsed(
+                    "days of the month (1-31), separated by commas",
+                    Some(&initial_day.day().to_string()), parse_list)?,
+                months_apart: self.ask_parsed(
+                    "months apart", Some("1"), parse_positive)?,
+            },
+            "wom" => DayFilter::Wom {
+                dow: self.ask_parsed("day of the week", Some(&dow), parse)?,
+                weeks: self.ask_parsed(
+                    "weeks of the month (1-5), separated by commas",
+                    Some(&(initial_day.day0() / 7 + 1).to_string()),
+                    parse_list)?,
+                months_apart: self.ask_parsed(
+                    "months apart", Some("1"), parse_positive)?,
+            },
+            "doy" => DayFilter::Doy {
+                month: self.ask_parsed(
+                    "month", Some(month_of(initial_day).name()), parse)?,
+                dom: self.ask_parsed(
+                    "day of the month", Some(&initial_day.day().to_string()),
+                    parse)?,
+                years_apart: self.ask_parsed(
+                    "years apart", Some("1"), parse_positive)?,
+            },
+            _ => DayFilter::Date {
+                dom: initial_day.day() as u8,
+                month: month_of(initial_day),
+                year: initial_day.year(),
+            },
+        };
+        Ok(filter)
+    }
+
+    fn ask_event_sched(&mut self) -> Result<Sched, String> {
+        let today = self.clock.today().to_string();
+        let initial_day = self.ask_parsed(
+            "first day (YYYY-MM-DD, UTC)", Some(&today), parse)?;
+        let days = self.ask_event_day_filter(initial_day)?;
+        let time = self.ask_opt(
+            "time of day (HH:MM, empty for all day)", parse_time)?;
+        Ok(Sched::Event(EventSched { initial_day, days, time }))
+    }
+
+    fn ask_progress_task_sched(&mut self) -> Result<Sched, String> {
+        let unit = self.choose("each period lasts a number of", &[
+            ("days", "days"),
+            ("weeks", "weeks"),
+            ("months", "months"),
+            ("years", "years"),
+        ])?;
+        let num = self.ask_parsed(
+            &format!("number of {unit}"), Some("1"), parse_positive)?;
+        let sched = match unit {
+            "days" => ProgressTaskSched::Days { num },
+            "weeks" => ProgressTaskSched::Weeks {
+                num,
+                start_day: self.ask_parsed(
+                    "start day of the week", Some("Mon"), parse)?,
+            },
+            "months" => ProgressTaskSched::Months {
+                num,
+                start_day: self.ask_parsed(
+                    "start day of the month", Some("1"), parse)?,
+            },
+            _ => ProgressTaskSched::Years {
+                num,
+                start_month: self.ask_parsed(
+                    "start month", Some("January"), parse)?,
+                start_dom: self.ask_parsed(
+                    "start day of the month", Some("1"), parse)?,
+            },
+        };
+        Ok(Sched::ProgressTask(sched))
+    }
+
+    fn ask_deadline_task_sched(&mut self) -> Result<Sched, String> {
+        let duration = self.ask_parsed(
+            "time from completing the task to the next deadline (for \
+             example: 3d or 2w)", None, |answer| DurationParser.parse(answer))?;
+        Ok(Sched::DeadlineTask(DeadlineTaskSched { duration }))
+    }
+
+    /// Ask for a schedule until there are no problems with it and the preview
+    /// is accepted.
+    fn ask_sched(&mut self, type_: ItemType) -> Result<Sched, String> {
+        loop {
+            let sched = match type_ {
+                ItemType::Event => self.ask_event_sched()?,
+                ItemType::ProgressTask => self.ask_progress_task_sched()?,
+                ItemType::DeadlineTask => self.ask_deadline_task_sched()?,
+            };
+
+            let problems = sched::problems(&sched);
+            if !problems.is_empty() {
+                eprintln!("problems with this schedule:");
+                for problem in problems {
+                    eprintln!("  - {problem}");
+                }
+                continue;
+            }
+
+            preview(self.clock, &sched);
+            if self.confirm("use this schedule?", true)? {
+                return Ok(sched);
+            }
+        }
+    }
+
+    /// Ask for config for the item's occurrences.
+    fn ask_config(&mut self, type_: ItemType) -> Result<types::Config, String> {
+        let mut config = types::Config {
+            occ_alert: self.ask_opt(
+                "alert this long before occurrences are due (for example: \
+                 1d, empty for the default)",
+                |answer| DurationParser.parse(answer))?,
+            ..types::Config::default()
+        };
+
+        if type_ == ItemType::ProgressTask {
+            let cmpl = &mut config.task_completion_conf;
+            cmpl.total = self.ask_opt(
+                "target amount per period (empty for the default, usually 1)",
+                parse_positive)?;
+            cmpl.unit = self.ask_opt(
+                "unit for amounts (for example: km, empty for none)",
+                |answer| Ok(answer.to_owned()))?;
+            cmpl.target_kind = Some(self.choose("the target is", &[
+                ("a minimum amount", TargetKind::AtLeast),
+                ("a maximum amount", TargetKind::AtMost),
+            ])?).filter(|kind| *kind != TargetKind::AtLeast);
+        }
+        Ok(config)
+    }
+
+    fn run(&mut self, db: &mut impl Db, defaults: Defaults)
+    -> Result<StoredItem, String> {
+        let name = self.ask_parsed(
+            "name", defaults.name.as_deref(), |answer| {
+                if answer.is_empty() {
+                    Err("the name can't be empty".to_owned())
+                } else {
+                    Ok(answer.to_owned())
+                }
+            })?;
+        let type_ = self.choose("type", &[
+            ("event: happens on certain days", ItemType::Event),
+            ("progress task: reach a target amount in each period",
+             ItemType::ProgressTask),
+            ("deadline task: due a fixed time after it was last done",
+             ItemType::DeadlineTask),
+        ])?;
+        let sched = self.ask_sched(type_)?;
+
+        let categories = db.find_items(None, None, None, SortDirection::Asc,
+                                       None, u32::MAX)?
+            .into_iter()
+            .filter_map(|item| item.item.category)
+            .collect::<BTreeSet<_>>();
+        if !categories.is_empty() {
+            eprintln!("existing categories: {}",
+                      categories.into_iter().collect::<Vec<_>>().join(", "));
+        }
+        let category = self.ask("category (empty for none)",
+                                defaults.category.as_deref())?;
+        let desc = self.ask("description (empty for none)",
+                            defaults.desc.as_deref())?;
+        let config = self.ask_config(type_)?;
+
+        if !self.confirm("create the item?", true)? {
+            return Err("cancelled".to_owned());
+        }
+        let item = dbutil::create_item(db, Item {
+            type_,
+            active: defaults.active,
+            category: (!category.is_empty()).then_some(category),
+            name,
+            desc: (!desc.is_empty()).then_some(desc),
+            sched,
+        })?;
+        if config != types::Config::default() {
+            dbutil::set_config(db, &StoredConfig {
+                id: ConfigId::Item { id: item.id.clone() },
+                config,
+            })?;
+        }
+        Ok(item)
+    }
+}
+
+/// Print the next few days a schedule produces.
+fn preview(clock: &dyn Clock, sched: &Sched) {
+    if let Sched::DeadlineTask(sched) = sched {
+        eprintln!("if created now, the first deadline is {}",
+                  (clock.now() + sched.duration).format("%Y-%m-%d %H:%M"));
+        return;
+    }
+
+    let days = sched::next_days(sched, clock.today(), PREVIEW_DAYS);
+    if days.is_empty() {
+        eprintln!("this schedule has no days from today");
+    } else {
+        let days = days.iter()
+            .map(|day| day.format("%a %Y-%m-%d").to_string())
+            .collect::<Vec<_>>();
+        eprintln!("next days: {}", days.join(", "));
+    }
+}
+
+fn month_of(date: NaiveDate) -> Month {
+    Month::try_from(date.month() as u8).unwrap_or(Month::January)
+}
+
+fn parse<T: FromStr>(answer: &str) -> Result<T, String>
+where T::Err: Display {
+    answer.parse().map_err(|e| format!("invalid value ({answer}): {e}"))
+}
+
+/// Parse a number which must not be `0`.
+fn parse_positive<T: FromStr + Default + PartialEq>(answer: &str)
+-> Result<T, String>
+where T::Err: Display {
+    let value = parse(answer)?;
+    if value == T::default() {
+        Err("must be at least 1".to_owned())
+    } else {
+        Ok(value)
+    }
+}
+
+/// Parse a list of values separated by commas.
+fn parse_list<T: FromStr>(answer: &str) -> Result<Vec<T>, String>
+where T::Err: Display {
+    answer.split(',')
+        .map(|value| parse(value.trim()))
+        .collect()
+}
+
+/// Parse a time as `HH:MM`, or `HH:MM:SS`.
+fn parse_time(answer: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(answer, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(answer, "%H:%M:%S"))
+        .map_err(|e| format!("invalid time ({answer}): {e}"))
+}
+
+/// Ask questions on standard error to build an item, and create it.
+pub fn run(db: &mut impl Db, clock: &dyn Clock, defaults: Defaults)
+-> Result<StoredItem, String> {
+    Wizard { input: io::stdin().lock(), clock }.run(db, defaults)
+}
@@ -0,0 +1,231 @@
+//! Benchmarks for generating occurrences and resolving their progress.
+//!
+//! Run with `cargo bench -p dunsumday --features sqlite`.  Each benchmark uses
+//! a new SQLite database in a temporary file.
+
+use std::time::Duration;
+use chrono::{NaiveDate, TimeZone, Utc, Weekday};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use dunsumday::config;
+use dunsumday::db::{self, ConfigId, Db, StoredItem, StoredOcc, UpdateId,
+                    WriteBatch};
+use dunsumday::types::{Config, DayFilter, EventSched, Item, ItemType, Occ,
+                       OccDate, ProgressEntry, ProgressTaskSched, Sched,
+                       TaskCompletionConfig};
+use dunsumday::util::{self, backup::TempPath, progress, sched};
+use dunsumday::util::config::ResolvedConfig;
+
+/// Number of items used by benchmarks over many items.
+const NUM_ITEMS: usize = 200;
+/// Number of stored occurrences for each item, where occurrences are stored.
+const OCCS_PER_ITEM: usize = 30;
+
+fn now() -> OccDate {
+    Utc.with_ymd_and_hms(2025, 6, 1, 12, 0, 0).unwrap()
+}
+
+/// Open a new, empty database.  It's removed when the returned path is
+/// dropped.
+fn open_db() -> (TempPath, Box<dyn Db + Send>) {
+    let path = TempPath::new();
+    let schema_path = concat!(env!("CARGO_MANIFEST_DIR"),
+                              "/runtime-data/db/schema");
+    let cfg = config::args::new([
+        format!("db.sqlite.db-path={}", path.path().display()),
+        format!("db.sqlite.schema-path={schema_path}"),
+    ]).unwrap();
+    let db = db::open(&cfg).unwrap();
+    (path, db)
+}
+
+fn event(days: DayFilter) -> Item {
+    Item {
+        type_: ItemType::Event,
+        active: true,
+        category: None,
+        name: "event".to_owned(),
+        desc: None,
+        sched: Sched::Event(EventSched {
+            initial_day: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+            days,
+            time: None,
+        }),
+    }
+}
+
+fn daily_task() -> Item {
+    Item {
+        type_: ItemType::ProgressTask,
+        active: true,
+        category: None,
+        name: "task".to_owned(),
+        desc: None,
+        sched: Sched::ProgressTask(ProgressTaskSched::Days { num: 1 }),
+    }
+}
+
+/// Store `items`, each with `num_occs` daily occurrences ending with the one
+/// containing [`now`], and a progress entry for each occurrence.
+fn store_items(db: &mut impl Db, items: &[Item], num_occs: usize)
+-> Vec<StoredItem> {
+    let today = now().date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc();
+    let occs = (0..num_occs)
+        .map(|index| {
+            let days_ago = i64::try_from(num_occs - 1 - index).unwrap();
+            let start = today - chrono::TimeDelta::days(days_ago);
+            Occ {
+                active: true,
+                start,
+                end: start + chrono::TimeDelta::days(1),
+                task_completion_progress: 0,
+                snoozed_until: None,
+            }
+        })
+        .collect::<Vec<_>>();
+    let entries = (0..num_occs)
+        .map(|index| ProgressEntry {
+            date: occs[index].start,
+            // alternate excess and shortfall, so progress is transferred
+            amount: if index % 2 == 0 { 3 } else { 1 },
+            note: None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut item_tokens = Vec::new();
+    let mut batch = WriteBatch::new();
+    for item in items {
+        let item_token = batch.create_item(item);
+        item_tokens.push(item_token);
+        for (occ, entry) in occs.iter().zip(&entries) {
+            let occ_token = batch.create_occ(UpdateId::Token(item_token), occ);
+            batch.create_progress_entry(UpdateId::Token(occ_token), entry);
+        }
+    }
+    let ids = batch.write(db).unwrap();
+    let item_ids = item_tokens.iter()
+        .map(|token| ids[token].as_str())
+        .collect::<Vec<_>>();
+    db.get_items(&item_ids).unwrap()
+}
+
+fn day_filter_days(c: &mut Criterion) {
+    let filters = [
+        ("day", DayFilter::Day { days_apart: 2 }),
+        ("dow", DayFilter::Dow { day: Weekday::Mon, weeks_apart: 1 }),
+        ("dows", DayFilter::Dows {
+            days: vec![Weekday::Mon, Weekday::Wed, Weekday::Fri],
+        }),
+        ("dom", DayFilter::Dom { days: vec![1, 15, 31], months_apart: 1 }),
+        ("wom", DayFilter::Wom {
+            dow: Weekday::Tue,
+            weeks: vec![1, 3],
+            months_apart: 1,
+        }),
+    ];
+    let start = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+    let mut group = c.benchmark_group("day_filter_days");
+    for (name, filter) in &filters {
+        group.bench_with_input(BenchmarkId::from_parameter(name), filter,
+                               |b, filter| {
+            b.iter(|| {
+                sched::DayFilterDaysIter::new(filter, start)
+                    .take(1000)
+                    .count()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn occgen(c: &mut Criterion) {
+    let (_path, db) = open_db();
+    let days = DayFilter::Dows { days: vec![Weekday::Mon, Weekday::Thu] };
+    let items = (0..NUM_ITEMS)
+        .map(|index| {
+            let item = if index % 2 == 0 {
+                event(days.clone())
+            } else {
+                daily_task()
+            };
+            StoredItem {
+                id: index.to_string(),
+                created: now(),
+                updated: now(),
+                item,
+            }
+        })
+        .collect::<Vec<_>>();
+    let item_refs = items.iter().collect::<Vec<_>>();
+    let end = now() + chrono::TimeDelta::days(365);
+
+    // nothing is stored, so every occurrence is generated
+    c.bench_function("occgen_year", |b| {
+        b.iter(|| {
+            util::get_occs_in_range(&db, &item_refs, now(), end, now())
+                .unwrap()
+                .len()
+        })
+    });
+}
+
+fn current_occs(c: &mut Criterion) {
+    let (_path, mut db) = open_db();
+    let items = store_items(&mut db, &vec![daily_task(); NUM_ITEMS], 2);
+    let item_refs = items.iter().collect::<Vec<_>>();
+
+    c.bench_function("preview_items_current_occ", |b| {
+        b.iter(|| {
+            util::preview_items_current_occ(&db, now(), &item_refs)
+                .unwrap()
+                .len()
+        })
+    });
+}
+
+fn resolve_occs_progress(c: &mut Criterion) {
+    let (_path, mut db) = open_db();
+    let items = store_items(&mut db, &vec![daily_task(); NUM_ITEMS / 4],
+                            OCCS_PER_ITEM);
+    let item_ids = items.iter()
+        .map(|item| item.id.as_str())
+        .collect::<Vec<_>>();
+    let occs = db.find_occs(&item_ids, None, None, db::SortDirection::Asc,
+                            None, u32::MAX)
+        .unwrap();
+
+    let cfg = Config {
+        task_completion_conf: TaskCompletionConfig {
+            total: Some(2),
+            excess_past: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+            excess_future: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let resolved = ResolvedConfig {
+        id: ConfigId::All,
+        scope_config: cfg.clone(),
+        resolved_config: cfg,
+        parent: Box::new(None),
+    };
+    let occs_configs = occs.iter()
+        .map(|(item_id, occs)| {
+            let occs = occs.iter()
+                .map(|occ| (occ, &resolved))
+                .collect::<Vec<(&StoredOcc, &ResolvedConfig)>>();
+            (item_id.as_str(), occs)
+        })
+        .collect::<Vec<_>>();
+
+    c.bench_function("resolve_occs_progress", |b| {
+        b.iter(|| {
+            progress::resolve_occs_progress(&db, &occs_configs)
+                .unwrap()
+                .len()
+        })
+    });
+}
+
+criterion_group!(benches, day_filter_days, occgen, current_occs,
+                 resolve_occs_progress);
+criterion_main!(benches);
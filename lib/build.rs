@@ -0,0 +1,21 @@
+//! Generates the C header for the `ffi` feature.
+
+fn main() {
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo::rerun-if-changed=src/ffi.rs");
+        println!("cargo::rerun-if-changed=cbindgen.toml");
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR")
+            .expect("CARGO_MANIFEST_DIR is not set");
+        // only parse the ffi module, since cbindgen can't handle every type
+        // used in the rest of the crate
+        cbindgen::Builder::new()
+            .with_config(cbindgen::Config::from_root_or_default(&crate_dir))
+            .with_src(format!("{crate_dir}/src/ffi.rs"))
+            .generate()
+            .expect("failed to generate C header")
+            .write_to_file(format!("{crate_dir}/include/dunsumday.h"));
+    }
+    #[cfg(not(feature = "ffi"))]
+    println!("cargo::rerun-if-changed=build.rs");
+}
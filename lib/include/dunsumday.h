@@ -0,0 +1,164 @@
+#ifndef DUNSUMDAY_H
+#define DUNSUMDAY_H
+
+/* Generated from src/ffi.rs by cbindgen when building with the `ffi` feature.  Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Connection to a database.
+typedef struct DunsumdayDb DunsumdayDb;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Get the message for the last error on the calling thread, or null if there
+// hasn't been one.  The string is owned by the library, and is valid until the
+// next call to a function which fails on the same thread.
+const char *dunsumday_last_error(void);
+
+// Free a string returned by this library.  Does nothing if `value` is null.
+//
+// # Safety
+//
+// `value` must be null or a string returned by this library which hasn't
+// been freed.  [`dunsumday_last_error`]'s result must not be freed.
+void dunsumday_string_free(char *value);
+
+// Open the SQLite database at `db_path`, creating or upgrading it using the
+// schema files in the directory `schema_path` as necessary.  Close it with
+// [`dunsumday_db_close`].
+//
+// # Safety
+//
+// Arguments must be valid null-terminated strings.
+struct DunsumdayDb *dunsumday_db_open(const char *db_path, const char *schema_path);
+
+// Close a database opened with [`dunsumday_db_open`].  Does nothing if `db`
+// is null.
+//
+// # Safety
+//
+// `db` must be null or returned by [`dunsumday_db_open`] and not yet closed,
+// and must not be used afterwards.
+void dunsumday_db_close(struct DunsumdayDb *db);
+
+// Operate on objects owned by the user with ID `user_id`, or objects without
+// an owner if null.
+//
+// # Safety
+//
+// `db` must be an open database not in use by another thread, and `user_id`
+// must be null or a valid null-terminated string.
+int dunsumday_db_set_user(struct DunsumdayDb *db, const char *user_id);
+
+// Create an item from [`Item`] JSON, returning the new [`StoredItem`] JSON.
+//
+// # Safety
+//
+// `db` must be an open database not in use by another thread, and `item` must
+// be a valid null-terminated string.
+char *dunsumday_item_create(struct DunsumdayDb *db, const char *item);
+
+// Get the item with ID `id`, as [`StoredItem`] JSON.  It's an error if there's
+// no such item.
+//
+// # Safety
+//
+// `db` must be an open database not in use by another thread, and `id` must
+// be a valid null-terminated string.
+char *dunsumday_item_get(struct DunsumdayDb *db, const char *id);
+
+// Get all items, as a JSON array of [`StoredItem`].  If `active` is `0` or
+// `1`, only inactive or active items are included.
+//
+// # Safety
+//
+// `db` must be an open database not in use by another thread.
+char *dunsumday_items_find(struct DunsumdayDb *db, int active);
+
+// Replace an item with [`StoredItem`] JSON.
+//
+// # Safety
+//
+// `db` must be an open database not in use by another thread, and `item` must
+// be a valid null-terminated string.
+int dunsumday_item_update(struct DunsumdayDb *db, const char *item);
+
+// Delete the item with ID `id`.  It's not an error if there's no such item,
+// but it is if the item has occurrences.
+//
+// # Safety
+//
+// `db` must be an open database not in use by another thread, and `id` must
+// be a valid null-terminated string.
+int dunsumday_item_delete(struct DunsumdayDb *db, const char *id);
+
+// Create an occurrence of the item with ID `item_id` from [`Occ`] JSON,
+// returning the new [`StoredOcc`] JSON.
+//
+// # Safety
+//
+// `db` must be an open database not in use by another thread, and other
+// arguments must be valid null-terminated strings.
+char *dunsumday_occ_create(struct DunsumdayDb *db, const char *item_id, const char *occ);
+
+// Get the occurrence with ID `id`, as [`StoredOcc`] JSON.  It's an error if
+// there's no such occurrence.
+//
+// # Safety
+//
+// `db` must be an open database not in use by another thread, and `id` must
+// be a valid null-terminated string.
+char *dunsumday_occ_get(struct DunsumdayDb *db, const char *id);
+
+// Get the stored occurrences of the item with ID `item_id` which overlap the
+// time range from `start` to `end`, as a JSON array of [`StoredOcc`] ordered
+// by start date.  `start` and `end` may be null to leave the range open.
+//
+// # Safety
+//
+// `db` must be an open database not in use by another thread, `item_id` must
+// be a valid null-terminated string, and `start` and `end` must be null or
+// valid null-terminated strings.
+char *dunsumday_occs_find(struct DunsumdayDb *db,
+                          const char *item_id,
+                          const char *start,
+                          const char *end);
+
+// Replace an occurrence with [`StoredOcc`] JSON.
+//
+// # Safety
+//
+// `db` must be an open database not in use by another thread, and `occ` must
+// be a valid null-terminated string.
+int dunsumday_occ_update(struct DunsumdayDb *db, const char *occ);
+
+// Delete the occurrence with ID `id`.  It's not an error if there's no such
+// occurrence.
+//
+// # Safety
+//
+// `db` must be an open database not in use by another thread, and `id` must
+// be a valid null-terminated string.
+int dunsumday_occ_delete(struct DunsumdayDb *db, const char *id);
+
+// Get the occurrences a new item with [`Sched`] JSON `sched` would have in the
+// time range from `start` to `end`, as a JSON array of [`Occ`] (see
+// [`occgen::preview`]).  This doesn't use a database.  It's an error if the
+// schedule has [problems](sched::problems).
+//
+// # Safety
+//
+// Arguments must be valid null-terminated strings.
+char *dunsumday_sched_preview(const char *sched, const char *start, const char *end);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* DUNSUMDAY_H */
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS tbl_progress (
+    id INTEGER PRIMARY KEY,
+    occ_id INTEGER NOT NULL,
+    /* epoch seconds */
+    date INTEGER NOT NULL,
+    amount INTEGER NOT NULL,
+    CONSTRAINT fk_progress_occs
+        FOREIGN KEY (occ_id)
+        REFERENCES tbl_occs (id)
+);
+CREATE INDEX IF NOT EXISTS idx_progress_occ_id_date
+    ON tbl_progress (occ_id, date);
@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS tbl_users (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+/* owner is null for objects created without a user */
+ALTER TABLE tbl_items ADD COLUMN owner INTEGER
+    REFERENCES tbl_users (id);
+CREATE INDEX IF NOT EXISTS idx_items_owner
+    ON tbl_items (owner);
+ALTER TABLE tbl_occs ADD COLUMN owner INTEGER
+    REFERENCES tbl_users (id);
+CREATE INDEX IF NOT EXISTS idx_occs_owner
+    ON tbl_occs (owner);
+ALTER TABLE tbl_configs ADD COLUMN owner INTEGER
+    REFERENCES tbl_users (id);
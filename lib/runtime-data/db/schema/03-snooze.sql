@@ -0,0 +1,2 @@
+/* epoch seconds, null if not snoozed */
+ALTER TABLE tbl_occs ADD COLUMN snoozed_until INTEGER;
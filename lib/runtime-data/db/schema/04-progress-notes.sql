@@ -0,0 +1,2 @@
+/* free text, null if there's no note */
+ALTER TABLE tbl_progress ADD COLUMN note TEXT;
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS tbl_alerts (
+    id INTEGER PRIMARY KEY,
+    occ_id INTEGER NOT NULL,
+    /* types::AlertKind */
+    kind TEXT NOT NULL,
+    /* epoch seconds */
+    sent_date INTEGER NOT NULL,
+    CONSTRAINT fk_alerts_occs
+        FOREIGN KEY (occ_id)
+        REFERENCES tbl_occs (id)
+);
+CREATE INDEX IF NOT EXISTS idx_alerts_occ_id
+    ON tbl_alerts (occ_id);
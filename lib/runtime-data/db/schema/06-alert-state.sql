@@ -0,0 +1,4 @@
+/* epoch seconds, null if not acknowledged */
+ALTER TABLE tbl_alerts ADD COLUMN acked_date INTEGER;
+/* epoch seconds, null if not snoozed */
+ALTER TABLE tbl_alerts ADD COLUMN snoozed_until INTEGER;
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS tbl_digests (
+    id INTEGER PRIMARY KEY,
+    /* null for digests sent without a user */
+    owner INTEGER REFERENCES tbl_users (id),
+    /* epoch seconds */
+    sent_date INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_digests_owner
+    ON tbl_digests (owner);
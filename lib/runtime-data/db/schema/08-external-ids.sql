@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS tbl_external_ids (
+    item_id INTEGER PRIMARY KEY,
+    /* null for items without a user */
+    owner INTEGER REFERENCES tbl_users (id),
+    /* where the item is synced from, such as a calendar URL */
+    source TEXT NOT NULL,
+    /* ID of the item in the source */
+    external_id TEXT NOT NULL,
+    CONSTRAINT fk_external_ids_items
+        FOREIGN KEY (item_id)
+        REFERENCES tbl_items (id)
+);
+CREATE INDEX IF NOT EXISTS idx_external_ids_source
+    ON tbl_external_ids (owner, source, external_id);
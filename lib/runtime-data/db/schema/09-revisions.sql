@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS tbl_revisions (
+    /* increases with every change, and never reused */
+    rev INTEGER PRIMARY KEY AUTOINCREMENT,
+    /* null for objects without a user */
+    owner INTEGER REFERENCES tbl_users (id),
+    /* the changed object's ObjectId, serialised using MessagePack; there is at
+       most one row for each object, for its latest change */
+    object_blob BLOB NOT NULL,
+    deleted INTEGER NOT NULL,
+    /* epoch seconds */
+    change_date INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_revisions_object
+    ON tbl_revisions (owner, object_blob);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS tbl_audit (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    /* null for objects without a user */
+    owner INTEGER REFERENCES tbl_users (id),
+    /* the item changed, or the item whose config or occurrence's config was
+       changed; not a foreign key, so entries are kept after the item is
+       deleted */
+    item_id INTEGER NOT NULL,
+    /* the AuditChange, serialised using MessagePack */
+    change_blob BLOB NOT NULL,
+    /* epoch seconds */
+    change_date INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_audit_item ON tbl_audit (owner, item_id);
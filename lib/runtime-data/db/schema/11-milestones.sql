@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS tbl_milestones (
+    id INTEGER PRIMARY KEY,
+    item_id INTEGER NOT NULL,
+    /* types::MilestoneKind */
+    kind TEXT NOT NULL,
+    threshold INTEGER NOT NULL,
+    /* null except for yearly totals */
+    year INTEGER,
+    /* epoch seconds */
+    reached_date INTEGER NOT NULL,
+    CONSTRAINT fk_milestones_items
+        FOREIGN KEY (item_id)
+        REFERENCES tbl_items (id)
+);
+CREATE INDEX IF NOT EXISTS idx_milestones_item_id
+    ON tbl_milestones (item_id);
@@ -9,9 +9,150 @@
 //! A [`Config`] implementation may or may not allow a value and a section to
 //! exist at the same path.
 //!
-//! All configuration values are strings.
+//! All configuration values are strings.  When values are read using
+//! [`get_parsed`], they may refer to secrets stored elsewhere (see
+//! [`resolve_indirect`]).  Values read any other way, including through a
+//! plain [`ValueRef`], are never resolved, so secrets must always be declared
+//! using a [`ParsedValueRef`].
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::path::PathBuf;
+use std::{fmt, fs, io};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+pub mod args;
+pub mod cached;
+pub mod de;
+pub mod layered;
+pub mod parse;
+pub mod validate;
+#[cfg(feature = "watch")]
+pub mod watch;
+
+/// Error reading configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// A file couldn't be read.
+    Io { path: PathBuf, source: io::Error },
+    /// An environment variable couldn't be read.
+    Env { name: String, source: std::env::VarError },
+    /// A config file couldn't be loaded, because of the problem in `source`.
+    File { path: PathBuf, source: Box<dyn Error + Send + Sync> },
+    /// A reference to an environment variable in a value has no end.
+    UnterminatedReference { value: String },
+    /// The top level of a config file isn't a map.
+    NotAMap { path: PathBuf },
+    /// A config file's `include` entry isn't a path or list of paths.
+    InvalidInclude { path: PathBuf },
+    /// A config file includes itself, directly or indirectly.
+    IncludeCycle { path: PathBuf },
+    /// A setting given as an argument isn't of the form `path=value`.
+    InvalidSetting { setting: String, reason: &'static str },
+    /// A value couldn't be resolved, parsed or validated.  `value` is the
+    /// value before [resolving](resolve_indirect) it.
+    InvalidValue {
+        path: String,
+        value: String,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// A [reference](ValueRef)'s default couldn't be parsed or validated.
+    InvalidDefault {
+        path: String,
+        value: String,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// A section couldn't be read into a typed value.
+    InvalidSection { path: String, source: serde::de::value::Error },
+    /// A config file couldn't be watched for changes.
+    #[cfg(feature = "watch")]
+    Watch { path: PathBuf, source: notify::Error },
+    /// A lock protecting shared config was poisoned.
+    LockPoisoned,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => {
+                write!(f, "error reading file ({path:?}): {source}")
+            }
+            ConfigError::Env { name, source } => {
+                write!(f, "error reading environment variable ({name}): \
+                           {source}")
+            }
+            ConfigError::File { path, source } => {
+                write!(f, "error loading config from file ({path:?}): \
+                           {source}")
+            }
+            ConfigError::UnterminatedReference { value } => {
+                write!(f, "unterminated environment variable reference in \
+                           value ({value:?})")
+            }
+            ConfigError::NotAMap { path } => {
+                write!(f, "invalid config file ({path:?}): top-level must be \
+                           a map")
+            }
+            ConfigError::InvalidInclude { path } => {
+                write!(f, "invalid include in config file ({path:?}): must be \
+                           a list of paths")
+            }
+            ConfigError::IncludeCycle { path } => {
+                write!(f, "config file includes itself ({path:?})")
+            }
+            ConfigError::InvalidSetting { setting, reason } => {
+                write!(f, "invalid setting ({setting}): {reason}")
+            }
+            ConfigError::InvalidValue { path, value, source } => {
+                write!(f, "invalid config value at {path} ({value:?}): \
+                           {source}")
+            }
+            ConfigError::InvalidDefault { path, value, source } => {
+                write!(f, "invalid default config value at {path} \
+                           ({value:?}): {source}")
+            }
+            ConfigError::InvalidSection { path, source } => {
+                write!(f, "invalid config section at {path}: {source}")
+            }
+            #[cfg(feature = "watch")]
+            ConfigError::Watch { path, source } => {
+                write!(f, "error watching config file ({}): {source}",
+                       path.display())
+            }
+            ConfigError::LockPoisoned => write!(f, "config lock poisoned"),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            ConfigError::Env { source, .. } => Some(source),
+            ConfigError::File { source, .. } |
+            ConfigError::InvalidValue { source, .. } |
+            ConfigError::InvalidDefault { source, .. } => Some(source.as_ref()),
+            ConfigError::InvalidSection { source, .. } => Some(source),
+            #[cfg(feature = "watch")]
+            ConfigError::Watch { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<ConfigError> for String {
+    fn from(e: ConfigError) -> String {
+        e.to_string()
+    }
+}
 
 /// Everything needed to read a configuration value.
+///
+/// Values read using [`Config::get_ref`] are never
+/// [resolved](resolve_indirect), so [checking](check_unresolved) a plain
+/// reference rejects values which look like references to secrets.  Use a
+/// [`ParsedValueRef`] for values which may be secrets.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct ValueRef<'a> {
     /// Path to read the value from.
@@ -20,15 +161,272 @@ pub struct ValueRef<'a> {
     pub def: &'a str,
 }
 
+impl ValueRef<'_> {
+    /// Path of the value, for display, with names separated by `.`.
+    pub fn path(&self) -> String {
+        self.names.join(".")
+    }
+}
+
+/// A [`ValueRef`] along with the [parser](parse::Parser) for its value.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ParsedValueRef<'a, P> {
+    pub vref: ValueRef<'a>,
+    pub parser: P,
+}
+
+/// A [`ValueRef`] or [`ParsedValueRef`], with the type of any parser hidden,
+/// for working with many references at once.
+pub trait AnyValueRef {
+    /// The reference's path and default.
+    fn value_ref(&self) -> ValueRef<'_>;
+
+    /// Check that the value in `cfg` can be read, parsed and validated.
+    fn check(&self, cfg: &dyn Config) -> Result<(), ConfigError>;
+
+    /// Check that the value in `cfg` doesn't look like a reference to a secret
+    /// stored elsewhere, if the value is never [resolved](resolve_indirect).
+    fn check_unresolved(&self, cfg: &dyn Config) -> Result<(), ConfigError> {
+        Ok(())
+    }
+
+    /// Check that the reference's default can be parsed and validated.
+    ///
+    /// The default is not [resolved](resolve_indirect) first.
+    fn check_default(&self) -> Result<(), ConfigError>;
+}
+
+impl AnyValueRef for ValueRef<'_> {
+    fn value_ref(&self) -> ValueRef<'_> {
+        *self
+    }
+
+    fn check(&self, cfg: &dyn Config) -> Result<(), ConfigError> {
+        self.check_unresolved(cfg)
+    }
+
+    fn check_unresolved(&self, cfg: &dyn Config) -> Result<(), ConfigError> {
+        let value = cfg.get_ref(self);
+        if value.starts_with(ENV_PREFIX) || value.starts_with(FILE_PREFIX) {
+            return Err(ConfigError::InvalidValue {
+                path: self.path(),
+                value: value.to_owned(),
+                source: format!(
+                    "values starting with {ENV_PREFIX} or {FILE_PREFIX} \
+                     aren't resolved for this setting").into(),
+            });
+        }
+        Ok(())
+    }
+
+    fn check_default(&self) -> Result<(), ConfigError> {
+        Ok(())
+    }
+}
+
+impl<P> AnyValueRef for ParsedValueRef<'_, P>
+where
+    P: parse::Parser,
+{
+    fn value_ref(&self) -> ValueRef<'_> {
+        self.vref
+    }
+
+    fn check(&self, cfg: &dyn Config) -> Result<(), ConfigError> {
+        get_parsed(cfg, self).map(|value| ())
+    }
+
+    fn check_default(&self) -> Result<(), ConfigError> {
+        self.parser.parse(self.vref.def)
+            .map(|value| ())
+            .map_err(|e| ConfigError::InvalidDefault {
+                path: self.vref.path(),
+                value: self.vref.def.to_owned(),
+                source: e.into(),
+            })
+    }
+}
+
 /// Read configuration values.
 pub trait Config {
+    /// Get the value at the path given by `names`, if there is one.
+    fn get_opt<'s>(&'s self, names: &[&str]) -> Option<&'s str>;
+
     /// Get the value at the path given by `names`, or the default `def`.
-    fn get<'s>(&'s self, names: &[&str], def: &'s str) -> &'s str;
+    fn get<'s>(&'s self, names: &[&str], def: &'s str) -> &'s str {
+        self.get_opt(names).unwrap_or(def)
+    }
 
-    /// Get a value using a [reference](ValueRef).
+    /// Get a value using a [reference](ValueRef).  The value is not
+    /// [resolved](resolve_indirect); use [`get_parsed`] for that.
     fn get_ref<'s>(&'s self, vref: &ValueRef<'s>) -> &'s str {
         self.get(vref.names, vref.def)
     }
+
+    /// Get the whole section at the path given by `names`.
+    ///
+    /// Returns `None` if there is no section at the path, or if this
+    /// implementation doesn't support reading whole sections.
+    fn get_section(&self, names: &[&str]) -> Option<map::Entry> {
+        None
+    }
+
+    /// Describe where the value at the path given by `names` comes from, for
+    /// display.
+    ///
+    /// Returns `None` if there is no value at the path, or if the source is
+    /// unknown.
+    fn source(&self, names: &[&str]) -> Option<String> {
+        None
+    }
+}
+
+/// Prefix for values read from an environment variable.
+const ENV_PREFIX: &str = "env:";
+/// Prefix for values read from a file.
+const FILE_PREFIX: &str = "file:";
+
+/// Resolve a value which refers to a secret stored elsewhere.
+///
+/// Values of the form `env:NAME` are replaced by the value of the environment
+/// variable `NAME`, and values of the form `file:PATH` are replaced by the
+/// contents of the file at `PATH`, without any trailing newline.  Other values
+/// are returned unchanged.
+pub fn resolve_indirect(value: &str) -> Result<Cow<'_, str>, ConfigError> {
+    if let Some(name) = value.strip_prefix(ENV_PREFIX) {
+        std::env::var(name)
+            .map(Cow::Owned)
+            .map_err(|source| ConfigError::Env {
+                name: name.to_owned(),
+                source,
+            })
+    } else if let Some(path) = value.strip_prefix(FILE_PREFIX) {
+        fs::read_to_string(path)
+            .map(|content| {
+                Cow::Owned(content.trim_end_matches(['\r', '\n']).to_owned())
+            })
+            .map_err(|source| ConfigError::Io { path: path.into(), source })
+    } else {
+        Ok(Cow::Borrowed(value))
+    }
+}
+
+/// Read and parse a value using a [parsed reference](ParsedValueRef).
+///
+/// The value is [resolved](resolve_indirect) before parsing.  The error
+/// includes the value's path, and the unresolved value, so that secrets are
+/// not revealed.
+pub fn get_parsed<C, P>(cfg: &C, pref: &ParsedValueRef<'_, P>)
+-> Result<P::Value, ConfigError>
+where
+    C: Config + ?Sized,
+    P: parse::Parser,
+{
+    let value = cfg.get_ref(&pref.vref);
+    let invalid = |source| ConfigError::InvalidValue {
+        path: pref.vref.path(),
+        value: value.to_owned(),
+        source,
+    };
+    let resolved = resolve_indirect(value)
+        .map_err(|e| invalid(Box::new(e)))?;
+    pref.parser.parse(&resolved).map_err(|e| invalid(e.into()))
+}
+
+/// Check all values referred to by `refs`, returning every problem found.
+///
+/// Each error includes the value's path.
+pub fn check_all(cfg: &dyn Config, refs: &[&dyn AnyValueRef])
+-> Result<(), Vec<ConfigError>> {
+    let errors = refs.iter()
+        .flat_map(|vref| vref.check(cfg).err())
+        .collect::<Vec<_>>();
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+/// Check that none of the values referred to by `refs` which are never
+/// [resolved](resolve_indirect) look like references to secrets, returning
+/// every problem found.
+///
+/// Unlike [`check_all`], this doesn't parse any values, so is cheap enough to
+/// do on startup.  Each error includes the value's path.
+pub fn check_unresolved(cfg: &dyn Config, refs: &[&dyn AnyValueRef])
+-> Result<(), Vec<ConfigError>> {
+    let errors = refs.iter()
+        .flat_map(|vref| vref.check_unresolved(cfg).err())
+        .collect::<Vec<_>>();
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+/// Check the defaults of all references in `refs`, returning every problem
+/// found.
+///
+/// This catches mistakes in the references themselves, so should be done on
+/// startup, before any values are read.  Each error includes the value's path.
+pub fn check_defaults(refs: &[&dyn AnyValueRef])
+-> Result<(), Vec<ConfigError>> {
+    let errors = refs.iter()
+        .flat_map(|vref| vref.check_default().err())
+        .collect::<Vec<_>>();
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+/// A configuration value along with where it comes from, as returned by
+/// [`dump`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct DumpedValue {
+    pub path: String,
+    /// The value before [resolving](resolve_indirect) it, so that secrets are
+    /// not revealed.
+    pub value: String,
+    /// Description of where the value comes from, if known.
+    pub source: Option<String>,
+    /// Whether the value is the reference's default, since the config has no
+    /// value at the path.
+    pub defaulted: bool,
+}
+
+/// Get the effective values referred to by `refs`, with details of where they
+/// come from.
+///
+/// Results are ordered by path.
+pub fn dump(cfg: &dyn Config, refs: &[&dyn AnyValueRef]) -> Vec<DumpedValue> {
+    let mut values = refs.iter()
+        .map(|any_ref| {
+            let vref = any_ref.value_ref();
+            let value = cfg.get_opt(vref.names);
+            DumpedValue {
+                path: vref.path(),
+                value: value.unwrap_or(vref.def).to_owned(),
+                source: value.and_then(|_| cfg.source(vref.names)),
+                defaulted: value.is_none(),
+            }
+        })
+        .collect::<Vec<_>>();
+    values.sort_by(|a, b| a.path.cmp(&b.path));
+    values.dedup_by(|a, b| a.path == b.path);
+    values
+}
+
+/// Read the section at the path given by `names` into a typed value.
+///
+/// See [`de`] for how values are converted.  A missing section is treated as
+/// empty, so that types whose fields all have defaults can still be read.
+/// Values are not [resolved](resolve_indirect), so sections must not contain
+/// secrets.  The error includes the section's path.
+pub fn get_section_as<T, C>(cfg: &C, names: &[&str])
+-> Result<T, ConfigError>
+where
+    T: DeserializeOwned,
+    C: Config + ?Sized,
+{
+    let section = cfg.get_section(names)
+        .unwrap_or_else(|| map::Entry::Section(Default::default()));
+    T::deserialize(de::EntryDeserializer::new(&section))
+        .map_err(|source| ConfigError::InvalidSection {
+            path: names.join("."),
+            source,
+        })
 }
 
 /// Implementation of [`Config`] using an in-memory map.
@@ -49,18 +447,37 @@ pub mod map {
     }
 
     impl Entry {
-        fn get<'s>(&'s self, names: &[&str], def: &'s str) -> &'s str {
+        /// Merge `other` into this entry, with values in `other` taking
+        /// precedence.
+        ///
+        /// Sections are merged recursively.  Otherwise, `other` replaces this
+        /// entry.
+        pub fn merge(&mut self, other: Entry) {
+            match (self, other) {
+                (Entry::Section(section), Entry::Section(other_section)) => {
+                    for (name, other_entry) in other_section {
+                        match section.get_mut(&name) {
+                            Some(entry) => entry.merge(other_entry),
+                            None => {
+                                section.insert(name, other_entry);
+                            }
+                        }
+                    }
+                }
+                (entry, other) => *entry = other,
+            }
+        }
+
+        /// Get the entry at the path given by `names`.
+        pub fn get_entry(&self, names: &[&str]) -> Option<&Entry> {
             match names.split_first() {
                 Some((first_name, other_names)) => match self {
-                    Entry::Value(_) => def,
+                    Entry::Value(_) => None,
                     Entry::Section(section) => section
-                        .get(&first_name.to_ascii_lowercase().to_string())
-                        .map_or(def, |entry| entry.get(other_names, def))
-                },
-                None => match self {
-                    Entry::Value(value) => value,
-                    Entry::Section(_) => def,
+                        .get(&first_name.to_ascii_lowercase())
+                        .and_then(|entry| entry.get_entry(other_names)),
                 },
+                None => Some(self),
             }
         }
     }
@@ -69,11 +486,40 @@ pub mod map {
     #[derive(Clone, Debug, Eq, PartialEq)]
     pub struct Config {
         cfg: Entry,
+        source: Option<String>,
     }
 
     impl super::Config for Config {
-        fn get<'s>(&'s self, names: &[&str], def: &'s str) -> &'s str {
-            self.cfg.get(names, def)
+        fn get_opt<'s>(&'s self, names: &[&str]) -> Option<&'s str> {
+            match self.cfg.get_entry(names) {
+                Some(Entry::Value(value)) => Some(value),
+                _ => None,
+            }
+        }
+
+        fn get_section(&self, names: &[&str]) -> Option<Entry> {
+            self.cfg.get_entry(names)
+                .filter(|entry| matches!(entry, Entry::Section(_)))
+                .cloned()
+        }
+
+        fn source(&self, names: &[&str]) -> Option<String> {
+            self.get_opt(names).and(self.source.clone())
+        }
+    }
+
+    impl Config {
+        /// Merge `other` into this config, with values in `other` taking
+        /// precedence.
+        ///
+        /// The source of this config is kept.
+        pub fn merge(&mut self, other: Config) {
+            self.cfg.merge(other.cfg);
+        }
+
+        /// Set the description of where all values in this config come from.
+        pub fn with_source(self, source: String) -> Config {
+            Config { source: Some(source), ..self }
         }
     }
 
@@ -91,8 +537,8 @@ pub mod map {
     }
 
     /// Construct a config from a hierarchical map.
-    pub fn new(cfg: HashMap<String, Entry>) -> impl super::Config {
-        Config { cfg: normalise(&Entry::Section(cfg)) }
+    pub fn new(cfg: HashMap<String, Entry>) -> Config {
+        Config { cfg: normalise(&Entry::Section(cfg)), source: None }
     }
 }
 
@@ -117,16 +563,22 @@ pub mod env {
     }
 
     impl super::Config for Config {
-        fn get<'s>(&'s self, names: &[&str], def: &'s str) -> &'s str {
+        fn get_opt<'s>(&'s self, names: &[&str]) -> Option<&'s str> {
             let mapped_names: Vec<String> = names.iter().map(|name| {
                 name.to_ascii_uppercase().replace('-', "_")
             }).collect();
             let env_name = self.prefix.to_owned() + &mapped_names.join("_");
+            self.env.get(&env_name).map(|v| v.as_str())
+        }
 
-            match self.env.get(&env_name) {
-                Some(v) => v,
-                None => def,
-            }
+        fn source(&self, names: &[&str]) -> Option<String> {
+            self.get_opt(names).map(|_| {
+                let mapped_names: Vec<String> = names.iter().map(|name| {
+                    name.to_ascii_uppercase().replace('-', "_")
+                }).collect();
+                format!("environment variable {}{}",
+                        self.prefix, mapped_names.join("_"))
+            })
         }
     }
 
@@ -134,7 +586,7 @@ pub mod env {
     ///
     /// Only environment variables starting with `prefix` are included, and
     /// `prefix` is removed when reading values.
-    pub fn new(prefix: String) -> impl super::Config {
+    pub fn new(prefix: String) -> Config {
         let mut env = HashMap::new();
         for (name_os, val_os) in std::env::vars_os() {
             if let (Ok(name), Ok(val)) =
@@ -147,18 +599,106 @@ pub mod env {
     }
 }
 
-/// Implementation of [`Config`] using a YAML file.
+/// Implementation of [`Config`] using a YAML or TOML file.
 ///
-/// A value and a section may not exist at the same path.
+/// A value and a section may not exist at the same path.  Sequences (YAML) and
+/// arrays (TOML) become sections with names `0`, `1`, etc.
+///
+/// A top-level `include` entry gives a path or list of paths to other config
+/// files, relative to the including file.  These are merged in order, with
+/// values in later files taking precedence, and values in the including file
+/// taking precedence over all included files.  The `include` entry itself is
+/// not part of the resulting config, and the [source](Config::source)
+/// of all values is reported as the including file.
+///
+/// References to environment variables like `${NAME}` in string values are
+/// replaced with the variable's value when the file is loaded, and it is an
+/// error for the variable not to be set.  Use `$${` for a literal `${`.
 ///
 /// When multiple values have equivalent paths (because paths are
 /// case-insensitive), the last matching value in the file is returned.
+///
+/// YAML files require the `yaml` feature.
 pub mod file {
-    use std::{fs::File, path::Path};
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use super::ConfigError;
     use super::map::{self, Entry};
-    use serde_yaml::Value;
 
-    fn parse(value: &Value) -> Entry {
+    /// Name of the top-level entry listing files to include.
+    const INCLUDE: &str = "include";
+    /// Start of a reference to an environment variable within a value.
+    const INTERPOLATE_START: &str = "${";
+    /// End of a reference to an environment variable within a value.
+    const INTERPOLATE_END: &str = "}";
+
+    /// Supported config file formats.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, strum::AsRefStr,
+             strum::EnumString)]
+    #[strum(serialize_all = "lowercase")]
+    pub enum Format {
+        Yaml,
+        Toml,
+    }
+
+    impl Format {
+        /// Determine the format of a file from its extension, defaulting to
+        /// YAML.
+        pub fn from_path(path: &Path) -> Format {
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some(ext) if ext.eq_ignore_ascii_case("toml") => Format::Toml,
+                _ => Format::Yaml,
+            }
+        }
+    }
+
+    /// Replace references to environment variables in `value`.
+    fn interpolate(value: &str) -> Result<String, ConfigError> {
+        let mut result = String::with_capacity(value.len());
+        let mut rest = value;
+        while let Some(start) = rest.find(INTERPOLATE_START) {
+            if rest[..start].ends_with('$') {
+                // escaped
+                result.push_str(&rest[..start - 1]);
+                result.push_str(INTERPOLATE_START);
+                rest = &rest[start + INTERPOLATE_START.len()..];
+                continue;
+            }
+            result.push_str(&rest[..start]);
+            let name_start = start + INTERPOLATE_START.len();
+            let name_len = rest[name_start..].find(INTERPOLATE_END)
+                .ok_or_else(|| ConfigError::UnterminatedReference {
+                    value: value.to_owned(),
+                })?;
+            let name = &rest[name_start..name_start + name_len];
+            let var_value = env::var(name)
+                .map_err(|source| ConfigError::Env {
+                    name: name.to_owned(),
+                    source,
+                })?;
+            result.push_str(&var_value);
+            rest = &rest[name_start + name_len + INTERPOLATE_END.len()..];
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    /// Replace references to environment variables in all values in `entry`.
+    fn interpolate_entry(entry: Entry) -> Result<Entry, ConfigError> {
+        match entry {
+            Entry::Value(value) => interpolate(&value).map(Entry::Value),
+            Entry::Section(section) => section.into_iter()
+                .map(|(name, entry)| Ok((name, interpolate_entry(entry)?)))
+                .collect::<Result<_, ConfigError>>()
+                .map(Entry::Section),
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    fn parse_yaml(value: &serde_yaml::Value) -> Entry {
+        use serde_yaml::Value;
         match value {
             Value::Null => Entry::Value("".to_owned()),
             Value::Bool(b) => Entry::Value(b.to_string()),
@@ -167,7 +707,7 @@ pub mod file {
             Value::Sequence(s) => {
                 Entry::Section(s.iter()
                     .enumerate()
-                    .map(|(i, v)| (i.to_string(), parse(v)))
+                    .map(|(i, v)| (i.to_string(), parse_yaml(v)))
                     .collect())
             }
             Value::Mapping(m) => {
@@ -175,7 +715,7 @@ pub mod file {
                     .filter(|(k, v)| k.is_string())
                     .flat_map(|(k, v)| {
                         k.as_str()
-                            .map(|k_str| (k_str.to_owned(), parse(v)))
+                            .map(|k_str| (k_str.to_owned(), parse_yaml(v)))
                     })
                     .collect())
             }
@@ -183,21 +723,142 @@ pub mod file {
         }
     }
 
-    /// Construct a config from a YAML file.
-    pub fn new<P>(path: P) -> Result<impl super::Config, String>
+    fn parse_toml(value: &toml::Value) -> Entry {
+        use toml::Value;
+        match value {
+            Value::String(s) => Entry::Value(s.to_owned()),
+            Value::Integer(i) => Entry::Value(i.to_string()),
+            Value::Float(f) => Entry::Value(f.to_string()),
+            Value::Boolean(b) => Entry::Value(b.to_string()),
+            Value::Datetime(d) => Entry::Value(d.to_string()),
+            Value::Array(a) => {
+                Entry::Section(a.iter()
+                    .enumerate()
+                    .map(|(i, v)| (i.to_string(), parse_toml(v)))
+                    .collect())
+            }
+            Value::Table(t) => {
+                Entry::Section(t.iter()
+                    .map(|(k, v)| (k.to_owned(), parse_toml(v)))
+                    .collect())
+            }
+        }
+    }
+
+    /// Construct a config from a YAML or TOML file, with the format determined
+    /// by the file extension (see [`Format::from_path`]).
+    pub fn new<P>(path: P) -> Result<map::Config, ConfigError>
     where
         P: AsRef<Path> + core::fmt::Debug
     {
-        let file = File::open(path.as_ref())
-            .map_err(|e| format!("error opening file ({path:?}): {e}"))?;
-        let value: Value = serde_yaml::from_reader(file)
-            .map_err(|e| format!(
-                "error loading config from file ({path:?}): {e}"))?;
-        let entry = parse(&value);
+        let format = Format::from_path(path.as_ref());
+        new_with_format(path, format)
+    }
+
+    /// Construct a config from a file in the given `format`.
+    ///
+    /// Included files (see [module docs](self)) use formats determined by
+    /// their file extensions.
+    pub fn new_with_format<P>(path: P, format: Format)
+    -> Result<map::Config, ConfigError>
+    where
+        P: AsRef<Path> + core::fmt::Debug
+    {
+        load(path.as_ref(), format, &mut Vec::new())
+    }
+
+    /// Read the top-level entries of a file, without processing includes.
+    fn read(path: &Path, format: Format)
+    -> Result<HashMap<String, Entry>, ConfigError> {
+        let invalid = |source| ConfigError::File {
+            path: path.to_owned(),
+            source,
+        };
+        let content = fs::read_to_string(path)
+            .map_err(|source| ConfigError::Io {
+                path: path.to_owned(),
+                source,
+            })?;
+        let entry = match format {
+            #[cfg(feature = "yaml")]
+            Format::Yaml => {
+                let value: serde_yaml::Value = serde_yaml::from_str(&content)
+                    .map_err(|e| invalid(Box::new(e)))?;
+                parse_yaml(&value)
+            }
+            #[cfg(not(feature = "yaml"))]
+            Format::Yaml => {
+                return Err(invalid(
+                    "YAML is not supported by this build".into()));
+            }
+            Format::Toml => {
+                let value: toml::Table = toml::from_str(&content)
+                    .map_err(|e| invalid(Box::new(e)))?;
+                parse_toml(&toml::Value::Table(value))
+            }
+        };
+        let entry = interpolate_entry(entry)
+            .map_err(|e| invalid(Box::new(e)))?;
         if let Entry::Section(e) = entry {
-            Ok(map::new(e))
+            Ok(e)
         } else {
-            Err("invalid config file: top-level must be a map".to_owned())
+            Err(ConfigError::NotAMap { path: path.to_owned() })
+        }
+    }
+
+    /// Get the paths listed by the `include` entry of the file at `path`,
+    /// relative to the file.
+    fn include_paths(include: &Entry, path: &Path)
+    -> Result<Vec<PathBuf>, ConfigError> {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut paths = match include {
+            Entry::Value(path) => vec![(0, path)],
+            Entry::Section(section) => section.iter()
+                .map(|(index, entry)| match (index.parse::<usize>(), entry) {
+                    (Ok(index), Entry::Value(path)) => Ok((index, path)),
+                    _ => Err(ConfigError::InvalidInclude {
+                        path: path.to_owned(),
+                    }),
+                })
+                .collect::<Result<Vec<_>, ConfigError>>()?,
+        };
+        paths.sort_unstable_by_key(|(index, path)| *index);
+        Ok(paths.into_iter()
+            .filter(|(index, path)| !path.is_empty())
+            .map(|(index, path)| dir.join(path))
+            .collect())
+    }
+
+    /// Load a file and all the files it includes.
+    ///
+    /// `loading` contains the files currently being loaded, to detect cycles.
+    #[tracing::instrument(level = "debug", skip_all,
+                          fields(path = %path.display()))]
+    fn load(path: &Path, format: Format, loading: &mut Vec<PathBuf>)
+    -> Result<map::Config, ConfigError> {
+        let canonical_path = fs::canonicalize(path)
+            .unwrap_or_else(|_| path.to_owned());
+        if loading.contains(&canonical_path) {
+            return Err(ConfigError::IncludeCycle { path: path.to_owned() })
+        }
+        let mut entries = read(path, format)?;
+        let include = entries.keys()
+            .find(|name| name.eq_ignore_ascii_case(INCLUDE))
+            .cloned()
+            .and_then(|name| entries.remove(&name));
+        let source = format!("file {}", path.display());
+        let Some(include) = include else {
+            return Ok(map::new(entries).with_source(source))
+        };
+
+        loading.push(canonical_path);
+        let mut cfg = map::new(HashMap::new());
+        for include_path in include_paths(&include, path)? {
+            let include_format = Format::from_path(&include_path);
+            cfg.merge(load(&include_path, include_format, loading)?);
         }
+        loading.pop();
+        cfg.merge(map::new(entries));
+        Ok(cfg.with_source(source))
     }
 }
@@ -0,0 +1,66 @@
+//! Implementation of [`Config`](super::Config) using command-line arguments.
+//!
+//! Each setting has the form `path=value`, where `path` is made up of names
+//! separated by `.`, for example `webserver.server.port=8080`.  These are
+//! usually given using a repeated `--set` argument.
+//!
+//! A value and a section may not exist at the same path.  When a setting is
+//! given more than once, the last one is used.
+
+use std::collections::HashMap;
+use super::ConfigError;
+use super::map::{self, Entry};
+
+/// Implementation of [`Config`](super::Config) using command-line arguments.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Config {
+    cfg: map::Config,
+}
+
+impl super::Config for Config {
+    fn get_opt<'s>(&'s self, names: &[&str]) -> Option<&'s str> {
+        self.cfg.get_opt(names)
+    }
+
+    fn get_section(&self, names: &[&str]) -> Option<Entry> {
+        self.cfg.get_section(names)
+    }
+
+    fn source(&self, names: &[&str]) -> Option<String> {
+        self.cfg.source(names)
+    }
+}
+
+/// Parse a single setting into an entry containing only that setting.
+fn parse_setting(setting: &str) -> Result<Entry, ConfigError> {
+    let invalid = |reason| ConfigError::InvalidSetting {
+        setting: setting.to_owned(),
+        reason,
+    };
+    let (path, value) = setting.split_once('=')
+        .ok_or_else(|| invalid("expected path=value"))?;
+    let names = path.trim().split('.').collect::<Vec<_>>();
+    if names.iter().any(|name| name.is_empty()) {
+        return Err(invalid("empty name in path"))
+    }
+    Ok(names.iter().rev().fold(
+        Entry::Value(value.to_owned()),
+        |entry, name| {
+            Entry::Section(HashMap::from([(name.to_string(), entry)]))
+        }))
+}
+
+/// Construct a config from `settings` of the form `path=value`.
+pub fn new<I, S>(settings: I) -> Result<Config, ConfigError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut cfg = map::new(HashMap::new());
+    for setting in settings {
+        if let Entry::Section(section) = parse_setting(setting.as_ref())? {
+            cfg.merge(map::new(section));
+        }
+    }
+    Ok(Config { cfg: cfg.with_source("command-line argument".to_owned()) })
+}
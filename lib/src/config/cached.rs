@@ -0,0 +1,72 @@
+//! Implementation of [`Config`](super::Config) which remembers parsed values.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use super::{ConfigError, ParsedValueRef};
+use super::layered::SharedConfig;
+use super::map::Entry;
+use super::parse::Parser;
+
+/// Parsed values, by path and value type.
+type ParsedValues = HashMap<(String, TypeId), Arc<dyn Any + Send + Sync>>;
+
+/// Implementation of [`Config`](super::Config) which wraps another config, and
+/// remembers values read using [`get_parsed`](Config::get_parsed).
+///
+/// Values are only parsed the first time they are read, and failures are not
+/// remembered.  The wrapped config must not change, so to pick up changes
+/// (such as from a [`WatchedConfig`](super::watch::WatchedConfig)), construct a
+/// new instance.
+pub struct Config {
+    cfg: SharedConfig,
+    parsed: RwLock<ParsedValues>,
+}
+
+impl Config {
+    /// Read and parse a value, like [`super::get_parsed`].
+    ///
+    /// Values are identified by path and value type, so references with the
+    /// same path and value type must use equivalent parsers.
+    pub fn get_parsed<P>(&self, pref: &ParsedValueRef<'_, P>)
+    -> Result<P::Value, ConfigError>
+    where
+        P: Parser,
+        P::Value: Clone + Send + Sync + 'static,
+    {
+        let key = (pref.vref.path(), TypeId::of::<P::Value>());
+        let cached = self.parsed.read()
+            .map_err(|e| ConfigError::LockPoisoned)?
+            .get(&key)
+            .and_then(|value| value.downcast_ref::<P::Value>())
+            .cloned();
+        if let Some(value) = cached {
+            return Ok(value);
+        }
+
+        let value = super::get_parsed(self.cfg.as_ref(), pref)?;
+        self.parsed.write()
+            .map_err(|e| ConfigError::LockPoisoned)?
+            .insert(key, Arc::new(value.clone()));
+        Ok(value)
+    }
+}
+
+impl super::Config for Config {
+    fn get_opt<'s>(&'s self, names: &[&str]) -> Option<&'s str> {
+        self.cfg.get_opt(names)
+    }
+
+    fn get_section(&self, names: &[&str]) -> Option<Entry> {
+        self.cfg.get_section(names)
+    }
+
+    fn source(&self, names: &[&str]) -> Option<String> {
+        self.cfg.source(names)
+    }
+}
+
+/// Construct a config which remembers values parsed from `cfg`.
+pub fn new(cfg: SharedConfig) -> Config {
+    Config { cfg, parsed: RwLock::new(HashMap::new()) }
+}
@@ -0,0 +1,198 @@
+//! Deserialising configuration sections into typed values.
+//!
+//! All configuration values are strings, so values are parsed according to
+//! the type requested: for example, a `u16` field is parsed from a string
+//! like `"26300"`.  Sections become maps or structs, and sections with names
+//! `0`, `1`, etc. may also become sequences.  An empty value becomes `None`
+//! for optional fields.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use serde::de::{self, IntoDeserializer, Visitor};
+use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+use serde::forward_to_deserialize_any;
+use super::map::Entry;
+
+/// Deserialiser for a configuration [`Entry`].
+pub struct EntryDeserializer<'a> {
+    entry: &'a Entry,
+}
+
+impl<'a> EntryDeserializer<'a> {
+    pub fn new(entry: &'a Entry) -> EntryDeserializer<'a> {
+        EntryDeserializer { entry }
+    }
+
+    fn value(&self) -> Result<&'a str, Error> {
+        match self.entry {
+            Entry::Value(value) => Ok(value),
+            Entry::Section(_) =>
+                Err(de::Error::custom("expected a value, found a section")),
+        }
+    }
+
+    fn section(&self) -> Result<&'a HashMap<String, Entry>, Error> {
+        match self.entry {
+            Entry::Value(_) =>
+                Err(de::Error::custom("expected a section, found a value")),
+            Entry::Section(section) => Ok(section),
+        }
+    }
+
+    fn parse<T>(&self) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        let value = self.value()?;
+        value.trim().parse()
+            .map_err(|e| de::Error::custom(format!("{e}: {value:?}")))
+    }
+}
+
+impl<'a> IntoDeserializer<'a, Error> for &'a Entry {
+    type Deserializer = EntryDeserializer<'a>;
+
+    fn into_deserializer(self) -> EntryDeserializer<'a> {
+        EntryDeserializer::new(self)
+    }
+}
+
+/// Implement deserialisation of a primitive type by parsing the value.
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for EntryDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.entry {
+            Entry::Value(value) => visitor.visit_borrowed_str(value),
+            Entry::Section(_) => self.deserialize_map(visitor),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.entry {
+            Entry::Value(value) if value.is_empty() => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V)
+    -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let section = self.section()?;
+        let mut items = section.iter()
+            .map(|(name, entry)| {
+                name.parse::<usize>()
+                    .map(|index| (index, entry))
+                    .map_err(|e| de::Error::custom(format!(
+                        "expected a sequence, found section name: {name}")))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        items.sort_unstable_by_key(|(index, entry)| *index);
+        let mut seq = SeqDeserializer::new(
+            items.into_iter().map(|(index, entry)| entry));
+        let result = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(result)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V)
+    -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let section = self.section()?;
+        let mut map = MapDeserializer::new(section.iter()
+            .map(|(name, entry)| (name.as_str(), entry)));
+        let result = visitor.visit_map(&mut map)?;
+        map.end()?;
+        Ok(result)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        // only unit variants are supported
+        visitor.visit_enum(self.value()?.into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit_struct tuple_struct identifier
+        ignored_any
+    }
+}
@@ -0,0 +1,44 @@
+//! Implementation of [`Config`](super::Config) which combines other configs.
+
+use std::sync::Arc;
+use super::map::Entry;
+
+/// A config which can be shared between threads.
+pub type SharedConfig = Arc<dyn super::Config + Send + Sync>;
+
+/// Implementation of [`Config`](super::Config) which combines other configs.
+///
+/// Each value is read from the first layer which has a value at the path.
+/// Sections are merged from all layers, with earlier layers taking
+/// precedence.
+#[derive(Clone)]
+pub struct Config {
+    layers: Vec<SharedConfig>,
+}
+
+impl super::Config for Config {
+    fn get_opt<'s>(&'s self, names: &[&str]) -> Option<&'s str> {
+        self.layers.iter().find_map(|layer| layer.get_opt(names))
+    }
+
+    fn get_section(&self, names: &[&str]) -> Option<Entry> {
+        self.layers.iter()
+            .rev()
+            .flat_map(|layer| layer.get_section(names))
+            .reduce(|mut section, layer_section| {
+                section.merge(layer_section);
+                section
+            })
+    }
+
+    fn source(&self, names: &[&str]) -> Option<String> {
+        self.layers.iter()
+            .find(|layer| layer.get_opt(names).is_some())
+            .and_then(|layer| layer.source(names))
+    }
+}
+
+/// Construct a config from `layers`, in order of precedence, highest first.
+pub fn new(layers: Vec<SharedConfig>) -> Config {
+    Config { layers }
+}
@@ -0,0 +1,186 @@
+//! Parsing configuration values from strings.
+//!
+//! Use with [`ParsedValueRef`](super::ParsedValueRef) and
+//! [`get_parsed`](super::get_parsed).
+
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Converts configuration value strings into values of another type.
+pub trait Parser {
+    type Value;
+
+    /// Parse a configuration `value`, returning a description of the problem
+    /// on failure.
+    fn parse(&self, value: &str) -> Result<Self::Value, String>;
+}
+
+/// Parses values using their [`FromStr`] implementation.
+#[derive(Debug)]
+pub struct FromStrParser<T> {
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> FromStrParser<T> {
+    pub const fn new() -> FromStrParser<T> {
+        FromStrParser { phantom: PhantomData }
+    }
+}
+
+impl<T> Default for FromStrParser<T> {
+    fn default() -> FromStrParser<T> {
+        FromStrParser::new()
+    }
+}
+
+// derives would require `T` to implement these traits
+impl<T> Clone for FromStrParser<T> {
+    fn clone(&self) -> FromStrParser<T> {
+        *self
+    }
+}
+
+impl<T> Copy for FromStrParser<T> {}
+
+impl<T> Parser for FromStrParser<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    type Value = T;
+
+    fn parse(&self, value: &str) -> Result<T, String> {
+        value.trim().parse().map_err(|e: T::Err| e.to_string())
+    }
+}
+
+/// Parses `true` or `false`.
+pub const BOOL: FromStrParser<bool> = FromStrParser::new();
+/// Parses any string, returning it unchanged.
+pub const STRING: FromStrParser<String> = FromStrParser::new();
+
+/// Parses comma-separated lists, parsing each item using another parser.
+///
+/// Whitespace around items is ignored, and an empty value is an empty list.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ListParser<P> {
+    pub item: P,
+}
+
+impl<P> Parser for ListParser<P>
+where
+    P: Parser,
+{
+    type Value = Vec<P::Value>;
+
+    fn parse(&self, value: &str) -> Result<Vec<P::Value>, String> {
+        if value.trim().is_empty() {
+            return Ok(vec![])
+        }
+        value.split(',')
+            .map(|item| self.item.parse(item.trim())
+                .map_err(|e| format!("invalid list item ({}): {e}",
+                                     item.trim())))
+            .collect()
+    }
+}
+
+/// Parses optional values using another parser, where an empty value is
+/// `None`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct OptionalParser<P> {
+    pub item: P,
+}
+
+impl<P> Parser for OptionalParser<P>
+where
+    P: Parser,
+{
+    type Value = Option<P::Value>;
+
+    fn parse(&self, value: &str) -> Result<Option<P::Value>, String> {
+        if value.trim().is_empty() {
+            return Ok(None)
+        }
+        self.item.parse(value).map(Some)
+    }
+}
+
+/// Parses HTTP headers in the form `Name: value`, into the name and value.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct HeaderParser;
+
+impl Parser for HeaderParser {
+    type Value = (String, String);
+
+    fn parse(&self, value: &str) -> Result<(String, String), String> {
+        let (name, value) = value.split_once(':')
+            .ok_or(format!("expected 'Name: value' header: {value}"))?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("empty header name".to_owned())
+        }
+        Ok((name.to_owned(), value.trim().to_owned()))
+    }
+}
+
+/// Parses human-friendly durations, such as `30s`, `15m`, `2h` or `1d`.
+///
+/// A duration is one or more whole numbers, each followed by a unit: `s`
+/// (seconds), `m` (minutes), `h` (hours), `d` (days) or `w` (weeks).  Parts may
+/// be combined, for example `1h30m`.  A number without a unit is in seconds.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct DurationParser;
+
+impl DurationParser {
+    /// Number of seconds in a `unit`.
+    fn unit_seconds(unit: char) -> Option<u64> {
+        match unit {
+            's' => Some(1),
+            'm' => Some(60),
+            'h' => Some(60 * 60),
+            'd' => Some(24 * 60 * 60),
+            'w' => Some(7 * 24 * 60 * 60),
+            _ => None,
+        }
+    }
+}
+
+impl Parser for DurationParser {
+    type Value = Duration;
+
+    fn parse(&self, value: &str) -> Result<Duration, String> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err("empty duration".to_owned())
+        }
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Ok(Duration::from_secs(seconds))
+        }
+
+        let mut total: u64 = 0;
+        let mut rest = value;
+        while !rest.is_empty() {
+            let num_len = rest.find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            if num_len == 0 {
+                return Err(format!("expected a number in duration: {value}"))
+            }
+            let (num_str, unit_str) = rest.split_at(num_len);
+            let num: u64 = num_str.parse()
+                .map_err(|e| format!("invalid duration ({value}): {e}"))?;
+            let mut unit_chars = unit_str.chars();
+            let unit = unit_chars.next()
+                .ok_or(format!("missing unit in duration: {value}"))?;
+            let unit_secs = DurationParser::unit_seconds(unit)
+                .ok_or(format!("unknown unit ({unit}) in duration: {value}"))?;
+            total = num.checked_mul(unit_secs)
+                .and_then(|secs| total.checked_add(secs))
+                .ok_or(format!("duration too large: {value}"))?;
+            rest = unit_chars.as_str();
+        }
+        Ok(Duration::from_secs(total))
+    }
+}
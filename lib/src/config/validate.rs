@@ -0,0 +1,81 @@
+//! Validating parsed configuration values.
+//!
+//! Validators are combined with [parsers](super::parse) using [`Validated`].
+
+use std::fmt::Display;
+use super::parse::Parser;
+
+/// Checks whether a parsed configuration value is acceptable.
+pub trait Validator<T: ?Sized> {
+    /// Check a `value`, returning a description of the problem on failure.
+    fn validate(&self, value: &T) -> Result<(), String>;
+}
+
+/// Requires values to be within an inclusive range.
+///
+/// Either bound may be omitted.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RangeValidator<T> {
+    pub min: Option<T>,
+    pub max: Option<T>,
+}
+
+impl<T> Validator<T> for RangeValidator<T>
+where
+    T: PartialOrd + Display,
+{
+    fn validate(&self, value: &T) -> Result<(), String> {
+        match (&self.min, &self.max) {
+            (Some(min), Some(max)) if value < min || value > max =>
+                Err(format!("must be between {min} and {max}")),
+            (Some(min), _) if value < min =>
+                Err(format!("must be at least {min}")),
+            (_, Some(max)) if value > max =>
+                Err(format!("must be at most {max}")),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Requires values to be one of a fixed set of strings.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct OneOfValidator<'a> {
+    pub values: &'a [&'a str],
+}
+
+impl Validator<str> for OneOfValidator<'_> {
+    fn validate(&self, value: &str) -> Result<(), String> {
+        if self.values.contains(&value) {
+            Ok(())
+        } else {
+            Err(format!("must be one of: {}", self.values.join(", ")))
+        }
+    }
+}
+
+impl Validator<String> for OneOfValidator<'_> {
+    fn validate(&self, value: &String) -> Result<(), String> {
+        self.validate(value.as_str())
+    }
+}
+
+/// A [`Parser`] which also checks parsed values using a [`Validator`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Validated<P, V> {
+    pub parser: P,
+    pub validator: V,
+}
+
+impl<P, V> Parser for Validated<P, V>
+where
+    P: Parser,
+    V: Validator<P::Value>,
+{
+    type Value = P::Value;
+
+    fn parse(&self, value: &str) -> Result<P::Value, String> {
+        let parsed = self.parser.parse(value)?;
+        self.validator.validate(&parsed)?;
+        Ok(parsed)
+    }
+}
@@ -0,0 +1,141 @@
+//! Reloading configuration from a file when the file changes.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use super::ConfigError;
+use super::file::{self, Format};
+use super::map;
+
+/// Function called with the new config after each successful reload.
+type Subscriber = Box<dyn Fn(&Arc<map::Config>) + Send>;
+
+/// State shared with the file watcher's event handler.
+struct Shared {
+    path: PathBuf,
+    format: Format,
+    current: RwLock<Arc<map::Config>>,
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+impl Shared {
+    /// Load the file again and notify subscribers if it changed.  On failure,
+    /// the current config is kept.
+    ///
+    /// Returns whether the config changed.
+    fn reload(&self) -> Result<bool, ConfigError> {
+        let new_cfg = Arc::new(file::new_with_format(&self.path, self.format)?);
+        match self.current.write() {
+            Ok(mut current) => {
+                if **current == *new_cfg {
+                    return Ok(false)
+                }
+                *current = new_cfg.clone();
+            }
+            Err(e) => return Err(ConfigError::LockPoisoned),
+        }
+        tracing::info!("reloaded config from file ({:?})", self.path);
+        if let Ok(subscribers) = self.subscribers.lock() {
+            for subscriber in subscribers.iter() {
+                subscriber(&new_cfg);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Configuration loaded from a YAML or TOML file (see
+/// [`file`](mod@super::file)), which is reloaded whenever the file changes.
+///
+/// Values are read from a [snapshot](WatchedConfig::snapshot), which is not
+/// affected by later reloads.  If the changed file can't be loaded, the
+/// previous configuration is kept.
+///
+/// Only the main file is watched: changes to included files are picked up the
+/// next time the main file changes.
+pub struct WatchedConfig {
+    shared: Arc<Shared>,
+    // stops watching when dropped
+    watcher: RecommendedWatcher,
+}
+
+impl WatchedConfig {
+    /// Load the config file at `path`, and start watching it for changes.
+    pub fn new(path: &Path, format: Format)
+    -> Result<WatchedConfig, ConfigError> {
+        let cfg = file::new_with_format(path, format)?;
+        let shared = Arc::new(Shared {
+            path: path.to_owned(),
+            format,
+            current: RwLock::new(Arc::new(cfg)),
+            subscribers: Mutex::new(Vec::new()),
+        });
+
+        let watch_error = |source| ConfigError::Watch {
+            path: path.to_owned(),
+            source,
+        };
+        let file_name = path.file_name().map(|name| name.to_owned());
+        let handler_shared = shared.clone();
+        let mut watcher = notify::recommended_watcher(
+            move |res: notify::Result<notify::Event>| {
+                let event = match res {
+                    Ok(event) => event,
+                    Err(e) => {
+                        tracing::warn!("error watching config file: {e}");
+                        return
+                    }
+                };
+                let changed = matches!(
+                    event.kind,
+                    EventKind::Create(_) | EventKind::Modify(_)) &&
+                    event.paths.iter()
+                        .any(|p| p.file_name() == file_name.as_deref());
+                if changed {
+                    if let Err(e) = handler_shared.reload() {
+                        tracing::warn!("keeping previous config: {e}");
+                    }
+                }
+            })
+            .map_err(watch_error)?;
+
+        // watch the directory, since editors often replace the file
+        let dir = path.parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        watcher.watch(dir, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+
+        Ok(WatchedConfig { shared, watcher })
+    }
+
+    /// Get the current configuration.
+    pub fn snapshot(&self) -> Arc<map::Config> {
+        match self.shared.current.read() {
+            Ok(current) => current.clone(),
+            Err(e) => e.into_inner().clone(),
+        }
+    }
+
+    /// Load the file again now, without waiting for it to change.  This also
+    /// picks up changes to included files.
+    ///
+    /// Returns whether the configuration changed.  If the file can't be
+    /// loaded, the previous configuration is kept.
+    pub fn reload(&self) -> Result<bool, ConfigError> {
+        self.shared.reload()
+    }
+
+    /// Call `subscriber` with the new configuration whenever it is reloaded.
+    ///
+    /// Subscribers are called from the watcher's thread, so should return
+    /// quickly.
+    pub fn subscribe<F>(&self, subscriber: F)
+    where
+        F: Fn(&Arc<map::Config>) + Send + 'static,
+    {
+        if let Ok(mut subscribers) = self.shared.subscribers.lock() {
+            subscribers.push(Box::new(subscriber));
+        }
+    }
+}
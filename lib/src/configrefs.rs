@@ -1,6 +1,46 @@
 //! Configuration value references for configuration used by this library.
 
-use crate::config::ValueRef;
+use crate::config::{AnyValueRef, ParsedValueRef, ValueRef};
+use crate::config::parse::{self, DurationParser, FromStrParser, HeaderParser,
+                           ListParser, OptionalParser};
+use crate::config::validate::{OneOfValidator, Validated};
+use crate::types::{OccDate, Priority};
+use crate::util::backup::DestinationKind;
+use crate::util::notify::ChannelKind;
+use chrono::{FixedOffset, NaiveTime};
+#[cfg(feature = "email")]
+use lettre::message::Mailbox;
+#[cfg(feature = "email")]
+use crate::util::notify::email::Security;
+use crate::util::notify::template::Template;
+
+/// Database implementation to use: `sqlite` for a local file, or `remote` to
+/// use a web server's database through its API.
+pub const DB_BACKEND: ParsedValueRef<
+    '_, Validated<FromStrParser<String>, OneOfValidator<'_>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["db", "backend"],
+        def: "sqlite",
+    },
+    parser: Validated {
+        parser: parse::STRING,
+        validator: OneOfValidator { values: &["sqlite", "remote"] },
+    },
+};
+
+/// Maximum number of each kind of object to keep in a
+/// [cache](crate::db::cached::Db) in front of the database, or `0` for no
+/// cache.  The cache only sees writes made by this process, so it should only
+/// be used when no other process writes to the database.
+pub const DB_CACHE_SIZE: ParsedValueRef<'_, FromStrParser<usize>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["db", "cache-size"],
+            def: "0",
+        },
+        parser: FromStrParser::new(),
+    };
 
 /// SQLite database file path.
 pub const DB_SQLITE_PATH: ValueRef<'_> = ValueRef {
@@ -13,3 +53,826 @@ pub const DB_SQLITE_SCHEMA_PATH: ValueRef<'_> = ValueRef {
     names: &["db", "sqlite", "schema-path"],
     def: "/usr/local/share/dunsumday/lib/db-schema",
 };
+
+/// URL of the web server API to use as the database, with the `remote`
+/// [backend](DB_BACKEND).
+pub const DB_REMOTE_URL: ValueRef<'_> = ValueRef {
+    names: &["db", "remote", "url"],
+    def: "",
+};
+
+/// Headers to include in requests to [`DB_REMOTE_URL`], each in the form
+/// `Name: value`, such as to identify the user.
+pub const DB_REMOTE_HEADERS: ParsedValueRef<'_, ListParser<HeaderParser>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["db", "remote", "headers"],
+            def: "",
+        },
+        parser: ListParser { item: HeaderParser },
+    };
+
+/// How long before an occurrence to show alerts for it, where its config
+/// doesn't set [`occ_alert`](crate::types::Config::occ_alert).
+pub const OCC_ALERT_DEFAULT: ParsedValueRef<'_, DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["occs", "alert-default"],
+            def: "0s",
+        },
+        parser: DurationParser,
+    };
+
+/// How far ahead of the current date to generate occurrences (see
+/// [`generate_ahead`](crate::util::generate_ahead)).
+pub const OCC_GEN_HORIZON: ParsedValueRef<'_, DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["occs", "generation-horizon"],
+            def: "1d",
+        },
+        parser: DurationParser,
+    };
+
+/// How often to generate occurrences ahead of time, when running in the
+/// background.
+pub const OCC_GEN_INTERVAL: ParsedValueRef<'_, DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["occs", "generation-interval"],
+            def: "1h",
+        },
+        parser: DurationParser,
+    };
+
+/// Fixed date to use as the current date, in RFC 3339 format, such as
+/// `2024-03-01T09:00:00Z`.  The system clock is used if empty.  See
+/// [`clock::from_config`](crate::util::clock::from_config).
+pub const CLOCK_NOW: ParsedValueRef<
+    '_, OptionalParser<FromStrParser<OccDate>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["clock", "now"],
+        def: "",
+    },
+    parser: OptionalParser { item: FromStrParser::new() },
+};
+
+/// Offset of the local timezone from UTC, such as `+01:00`, which determines
+/// where day boundaries fall for the current day.
+pub const CLOCK_UTC_OFFSET: ParsedValueRef<'_, FromStrParser<FixedOffset>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["clock", "utc-offset"],
+            def: "+00:00",
+        },
+        parser: FromStrParser::new(),
+    };
+
+/// Channels to send occurrence alerts through, as a list of
+/// [`ChannelKind`] names.  Alerts are not sent if empty.
+pub const NOTIFICATIONS_CHANNELS: ParsedValueRef<
+    '_, ListParser<FromStrParser<ChannelKind>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "channels"],
+        def: "",
+    },
+    parser: ListParser { item: FromStrParser::new() },
+};
+
+/// How often to check for occurrences to send alerts about.
+pub const NOTIFICATIONS_INTERVAL: ParsedValueRef<'_, DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["notifications", "interval"],
+            def: "1m",
+        },
+        parser: DurationParser,
+    };
+
+/// How long after an occurrence ends an overdue alert may still be sent for
+/// it, such as when alerts haven't been checked for a while.
+pub const NOTIFICATIONS_LOOKBACK: ParsedValueRef<'_, DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["notifications", "lookback"],
+            def: "1d",
+        },
+        parser: DurationParser,
+    };
+
+/// [Template](crate::util::notify::template) for the subject of alerts, used
+/// for channels which don't set their own.
+pub const NOTIFICATIONS_TEMPLATES_SUBJECT: ParsedValueRef<
+    '_, FromStrParser<Template>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "templates", "subject"],
+        def: "\
+        {% if kind == \"escalated\" %}Still overdue\
+        {% elif overdue %}Overdue{% else %}Due soon{% endif %}\
+        : {{ item.name }}",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// [Template](crate::util::notify::template) for the body of alerts, used for
+/// channels which don't set their own.
+pub const NOTIFICATIONS_TEMPLATES_BODY: ParsedValueRef<
+    '_, FromStrParser<Template>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "templates", "body"],
+        def: "\
+        {% if overdue %}Was due{% else %}Due{% endif %} at {{ deadline }}.\
+        {% if progress %} \
+        Progress: {{ progress.done }}/{{ progress.total }}.{% endif %}",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// Template for the subject of alerts written to the log.  The
+/// [default](NOTIFICATIONS_TEMPLATES_SUBJECT) is used if empty.
+pub const NOTIFICATIONS_LOG_SUBJECT: ParsedValueRef<
+    '_, FromStrParser<Template>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "log", "subject"],
+        def: "",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// Template for the body of alerts written to the log.  The
+/// [default](NOTIFICATIONS_TEMPLATES_BODY) is used if empty.
+pub const NOTIFICATIONS_LOG_BODY: ParsedValueRef<
+    '_, FromStrParser<Template>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "log", "body"],
+        def: "",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// Whether to send a daily [digest](crate::util::notify::digest) instead of
+/// individual alerts.
+pub const NOTIFICATIONS_DIGEST_ENABLED: ParsedValueRef<
+    '_, FromStrParser<bool>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "digest", "enabled"],
+        def: "false",
+    },
+    parser: parse::BOOL,
+};
+
+/// Time of day to send digests at, in local time (see [`CLOCK_UTC_OFFSET`]).
+pub const NOTIFICATIONS_DIGEST_TIME: ParsedValueRef<
+    '_, FromStrParser<NaiveTime>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "digest", "time"],
+        def: "08:00",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// How far back the completion statistics included in digests cover.
+pub const NOTIFICATIONS_DIGEST_STATS_PERIOD: ParsedValueRef<
+    '_, DurationParser
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "digest", "stats-period"],
+        def: "7d",
+    },
+    parser: DurationParser,
+};
+
+/// Whether the first digest each week includes a
+/// [review](crate::util::report::weekly_review) of the previous week.
+pub const NOTIFICATIONS_DIGEST_WEEKLY_REVIEW: ParsedValueRef<
+    '_, FromStrParser<bool>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "digest", "weekly-review"],
+        def: "false",
+    },
+    parser: parse::BOOL,
+};
+
+/// SMTP server host name for email notifications.
+pub const NOTIFICATIONS_EMAIL_SERVER: ValueRef<'_> = ValueRef {
+    names: &["notifications", "email", "server"],
+    def: "",
+};
+
+/// SMTP server port for email notifications, or `0` for the default port for
+/// the configured security.
+pub const NOTIFICATIONS_EMAIL_PORT: ParsedValueRef<'_, FromStrParser<u16>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["notifications", "email", "port"],
+            def: "0",
+        },
+        parser: FromStrParser::new(),
+    };
+
+/// How connections to the SMTP server are secured: `starttls`, `tls` or
+/// `none`.  Requires the `email` feature.
+#[cfg(feature = "email")]
+pub const NOTIFICATIONS_EMAIL_SECURITY: ParsedValueRef<
+    '_, FromStrParser<Security>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "email", "security"],
+        def: "starttls",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// User name for authenticating with the SMTP server.  No authentication is
+/// performed if empty.
+pub const NOTIFICATIONS_EMAIL_USERNAME: ParsedValueRef<
+    '_, FromStrParser<String>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "email", "username"],
+        def: "",
+    },
+    parser: parse::STRING,
+};
+
+/// Password for authenticating with the SMTP server.
+pub const NOTIFICATIONS_EMAIL_PASSWORD: ParsedValueRef<
+    '_, FromStrParser<String>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "email", "password"],
+        def: "",
+    },
+    parser: parse::STRING,
+};
+
+/// Sender address for email notifications, optionally with a name, for example
+/// `dunsumday <dunsumday@example.com>`.  Requires the `email` feature.
+#[cfg(feature = "email")]
+pub const NOTIFICATIONS_EMAIL_FROM: ParsedValueRef<'_, FromStrParser<Mailbox>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["notifications", "email", "from"],
+            def: "dunsumday <dunsumday@localhost>",
+        },
+        parser: FromStrParser::new(),
+    };
+
+/// Recipient addresses for email notifications.  Requires the `email` feature.
+#[cfg(feature = "email")]
+pub const NOTIFICATIONS_EMAIL_TO: ParsedValueRef<
+    '_, ListParser<FromStrParser<Mailbox>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "email", "to"],
+        def: "",
+    },
+    parser: ListParser { item: FromStrParser::new() },
+};
+
+/// Template for the subject of email notifications.  The
+/// [default](NOTIFICATIONS_TEMPLATES_SUBJECT) is used if empty.
+pub const NOTIFICATIONS_EMAIL_SUBJECT: ParsedValueRef<
+    '_, FromStrParser<Template>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "email", "subject"],
+        def: "",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// Template for the body of email notifications.  The
+/// [default](NOTIFICATIONS_TEMPLATES_BODY) is used if empty.
+pub const NOTIFICATIONS_EMAIL_BODY: ParsedValueRef<
+    '_, FromStrParser<Template>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "email", "body"],
+        def: "",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// URL of the ntfy server for ntfy notifications.
+pub const NOTIFICATIONS_NTFY_URL: ParsedValueRef<'_, FromStrParser<String>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["notifications", "ntfy", "url"],
+            def: "https://ntfy.sh",
+        },
+        parser: parse::STRING,
+    };
+
+/// Topic to publish ntfy notifications to.
+pub const NOTIFICATIONS_NTFY_TOPIC: ParsedValueRef<'_, FromStrParser<String>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["notifications", "ntfy", "topic"],
+            def: "",
+        },
+        parser: parse::STRING,
+    };
+
+/// Access token for publishing ntfy notifications.  No authentication is
+/// performed if empty.
+pub const NOTIFICATIONS_NTFY_TOKEN: ParsedValueRef<'_, FromStrParser<String>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["notifications", "ntfy", "token"],
+            def: "",
+        },
+        parser: parse::STRING,
+    };
+
+/// [`Priority`] of ntfy notifications for upcoming occurrences.
+pub const NOTIFICATIONS_NTFY_PRIORITY_UPCOMING: ParsedValueRef<
+    '_, FromStrParser<Priority>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "ntfy", "priority", "upcoming"],
+        def: "default",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// [`Priority`] of ntfy notifications for overdue occurrences.
+pub const NOTIFICATIONS_NTFY_PRIORITY_OVERDUE: ParsedValueRef<
+    '_, FromStrParser<Priority>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "ntfy", "priority", "overdue"],
+        def: "high",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// Template for the subject of ntfy notifications.  The
+/// [default](NOTIFICATIONS_TEMPLATES_SUBJECT) is used if empty.
+pub const NOTIFICATIONS_NTFY_SUBJECT: ParsedValueRef<
+    '_, FromStrParser<Template>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "ntfy", "subject"],
+        def: "",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// Template for the body of ntfy notifications.  The
+/// [default](NOTIFICATIONS_TEMPLATES_BODY) is used if empty.
+pub const NOTIFICATIONS_NTFY_BODY: ParsedValueRef<
+    '_, FromStrParser<Template>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "ntfy", "body"],
+        def: "",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// URL of the Gotify server for Gotify notifications.
+pub const NOTIFICATIONS_GOTIFY_URL: ParsedValueRef<'_, FromStrParser<String>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["notifications", "gotify", "url"],
+            def: "",
+        },
+        parser: parse::STRING,
+    };
+
+/// Gotify application token for sending Gotify notifications.
+pub const NOTIFICATIONS_GOTIFY_TOKEN: ParsedValueRef<
+    '_, FromStrParser<String>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "gotify", "token"],
+        def: "",
+    },
+    parser: parse::STRING,
+};
+
+/// [`Priority`] of Gotify notifications for upcoming occurrences.
+pub const NOTIFICATIONS_GOTIFY_PRIORITY_UPCOMING: ParsedValueRef<
+    '_, FromStrParser<Priority>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "gotify", "priority", "upcoming"],
+        def: "default",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// [`Priority`] of Gotify notifications for overdue occurrences.
+pub const NOTIFICATIONS_GOTIFY_PRIORITY_OVERDUE: ParsedValueRef<
+    '_, FromStrParser<Priority>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "gotify", "priority", "overdue"],
+        def: "high",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// Template for the subject of Gotify notifications.  The
+/// [default](NOTIFICATIONS_TEMPLATES_SUBJECT) is used if empty.
+pub const NOTIFICATIONS_GOTIFY_SUBJECT: ParsedValueRef<
+    '_, FromStrParser<Template>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "gotify", "subject"],
+        def: "",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// Template for the body of Gotify notifications.  The
+/// [default](NOTIFICATIONS_TEMPLATES_BODY) is used if empty.
+pub const NOTIFICATIONS_GOTIFY_BODY: ParsedValueRef<
+    '_, FromStrParser<Template>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "gotify", "body"],
+        def: "",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// URL which receives webhook notifications.
+pub const NOTIFICATIONS_WEBHOOK_URL: ParsedValueRef<'_, FromStrParser<String>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["notifications", "webhook", "url"],
+            def: "",
+        },
+        parser: parse::STRING,
+    };
+
+/// [`Priority`] included in webhook notifications for upcoming occurrences.
+pub const NOTIFICATIONS_WEBHOOK_PRIORITY_UPCOMING: ParsedValueRef<
+    '_, FromStrParser<Priority>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "webhook", "priority", "upcoming"],
+        def: "default",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// [`Priority`] included in webhook notifications for overdue occurrences.
+pub const NOTIFICATIONS_WEBHOOK_PRIORITY_OVERDUE: ParsedValueRef<
+    '_, FromStrParser<Priority>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "webhook", "priority", "overdue"],
+        def: "high",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// Template for the subject of webhook notifications.  The
+/// [default](NOTIFICATIONS_TEMPLATES_SUBJECT) is used if empty.
+pub const NOTIFICATIONS_WEBHOOK_SUBJECT: ParsedValueRef<
+    '_, FromStrParser<Template>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "webhook", "subject"],
+        def: "",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// Template for the body of webhook notifications.  The
+/// [default](NOTIFICATIONS_TEMPLATES_BODY) is used if empty.
+pub const NOTIFICATIONS_WEBHOOK_BODY: ParsedValueRef<
+    '_, FromStrParser<Template>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["notifications", "webhook", "body"],
+        def: "",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// Numbers of completed occurrences which are
+/// [milestones](crate::util::milestones), where an item's config doesn't set
+/// [`completions`](crate::types::MilestoneConfig::completions).
+pub const MILESTONES_COMPLETIONS: ParsedValueRef<
+    '_, ListParser<FromStrParser<u32>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["milestones", "completions"],
+        def: "10, 50, 100, 250, 500, 1000",
+    },
+    parser: ListParser { item: FromStrParser::new() },
+};
+
+/// Numbers of occurrences completed in a row which are
+/// [milestones](crate::util::milestones), where an item's config doesn't set
+/// [`streaks`](crate::types::MilestoneConfig::streaks).
+pub const MILESTONES_STREAKS: ParsedValueRef<
+    '_, ListParser<FromStrParser<u32>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["milestones", "streaks"],
+        def: "7, 30, 100, 365",
+    },
+    parser: ListParser { item: FromStrParser::new() },
+};
+
+/// How often to look for milestones which have been reached, when running in
+/// the background.
+pub const MILESTONES_INTERVAL: ParsedValueRef<'_, DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["milestones", "interval"],
+            def: "1h",
+        },
+        parser: DurationParser,
+    };
+
+/// URLs of iCalendar objects to sync events from (see
+/// [`util::sync`](crate::util::sync)).
+pub const SYNC_ICS_URLS: ParsedValueRef<
+    '_, ListParser<FromStrParser<String>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["sync", "ics", "urls"],
+        def: "",
+    },
+    parser: ListParser { item: FromStrParser::new() },
+};
+
+/// How often to sync events from [`SYNC_ICS_URLS`].
+pub const SYNC_ICS_INTERVAL: ParsedValueRef<'_, DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["sync", "ics", "interval"],
+            def: "1h",
+        },
+        parser: DurationParser,
+    };
+
+/// URL of the web server API to sync the database with (see
+/// [`util::devicesync`](crate::util::devicesync)).
+pub const SYNC_SERVER_URL: ValueRef<'_> = ValueRef {
+    names: &["sync", "server", "url"],
+    def: "",
+};
+
+/// Headers to include in requests to [`SYNC_SERVER_URL`], each in the form
+/// `Name: value`, such as to identify the user.
+pub const SYNC_SERVER_HEADERS: ParsedValueRef<'_, ListParser<HeaderParser>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["sync", "server", "headers"],
+            def: "",
+        },
+        parser: ListParser { item: HeaderParser },
+    };
+
+/// File path to keep the state of syncing with [`SYNC_SERVER_URL`] in.
+pub const SYNC_SERVER_STATE_PATH: ValueRef<'_> = ValueRef {
+    names: &["sync", "server", "state-path"],
+    def: "/var/lib/dunsumday/sync-state.json",
+};
+
+/// How often to sync with [`SYNC_SERVER_URL`].
+pub const SYNC_SERVER_INTERVAL: ParsedValueRef<'_, DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["sync", "server", "interval"],
+            def: "5m",
+        },
+        parser: DurationParser,
+    };
+
+/// ID of the Google Calendar to push upcoming occurrences to (see
+/// [`util::gcal`](crate::util::gcal)), or empty to not push them.  The main
+/// calendar of the authorised account is `primary`.
+pub const SYNC_GCAL_CALENDAR_ID: ValueRef<'_> = ValueRef {
+    names: &["sync", "gcal", "calendar-id"],
+    def: "",
+};
+
+/// OAuth client ID used to access [`SYNC_GCAL_CALENDAR_ID`].
+pub const SYNC_GCAL_CLIENT_ID: ValueRef<'_> = ValueRef {
+    names: &["sync", "gcal", "client-id"],
+    def: "",
+};
+
+/// OAuth client secret for [`SYNC_GCAL_CLIENT_ID`].
+pub const SYNC_GCAL_CLIENT_SECRET: ParsedValueRef<
+    '_, FromStrParser<String>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["sync", "gcal", "client-secret"],
+        def: "",
+    },
+    parser: parse::STRING,
+};
+
+/// OAuth refresh token allowing access to [`SYNC_GCAL_CALENDAR_ID`].
+pub const SYNC_GCAL_REFRESH_TOKEN: ParsedValueRef<
+    '_, FromStrParser<String>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["sync", "gcal", "refresh-token"],
+        def: "",
+    },
+    parser: parse::STRING,
+};
+
+/// How far ahead to push occurrences to [`SYNC_GCAL_CALENDAR_ID`].
+pub const SYNC_GCAL_PERIOD: ParsedValueRef<'_, DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["sync", "gcal", "period"],
+            def: "14d",
+        },
+        parser: DurationParser,
+    };
+
+/// How often to push occurrences to [`SYNC_GCAL_CALENDAR_ID`].
+pub const SYNC_GCAL_INTERVAL: ParsedValueRef<'_, DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["sync", "gcal", "interval"],
+            def: "15m",
+        },
+        parser: DurationParser,
+    };
+
+/// Where to keep scheduled backups of the database: `none` to not make them,
+/// `dir` for [`BACKUP_DIR_PATH`], or `s3` for [`BACKUP_S3_BUCKET`].
+pub const BACKUP_DESTINATION: ParsedValueRef<
+    '_, FromStrParser<DestinationKind>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["backup", "destination"],
+        def: "none",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// How often to back up the database.
+pub const BACKUP_SCHEDULE: ParsedValueRef<'_, DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["backup", "schedule"],
+            def: "1d",
+        },
+        parser: DurationParser,
+    };
+
+/// Number of the most recent backups to keep.  Older backups are removed.
+pub const BACKUP_RETENTION: ParsedValueRef<'_, FromStrParser<u32>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["backup", "retention"],
+            def: "7",
+        },
+        parser: FromStrParser::new(),
+    };
+
+/// Directory to write backups to.
+pub const BACKUP_DIR_PATH: ValueRef<'_> = ValueRef {
+    names: &["backup", "dir", "path"],
+    def: "/var/lib/dunsumday/backups",
+};
+
+/// Base URL of the S3-compatible server to upload backups to.
+pub const BACKUP_S3_ENDPOINT: ValueRef<'_> = ValueRef {
+    names: &["backup", "s3", "endpoint"],
+    def: "https://s3.amazonaws.com",
+};
+
+/// Region of [`BACKUP_S3_BUCKET`], used for signing requests.
+pub const BACKUP_S3_REGION: ValueRef<'_> = ValueRef {
+    names: &["backup", "s3", "region"],
+    def: "us-east-1",
+};
+
+/// Bucket to upload backups to.
+pub const BACKUP_S3_BUCKET: ValueRef<'_> = ValueRef {
+    names: &["backup", "s3", "bucket"],
+    def: "",
+};
+
+/// Prefix for the keys of uploaded backups, such as `backups/`.
+pub const BACKUP_S3_PREFIX: ValueRef<'_> = ValueRef {
+    names: &["backup", "s3", "prefix"],
+    def: "",
+};
+
+/// Access key ID for uploading backups.
+pub const BACKUP_S3_ACCESS_KEY_ID: ParsedValueRef<
+    '_, FromStrParser<String>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["backup", "s3", "access-key-id"],
+        def: "",
+    },
+    parser: parse::STRING,
+};
+
+/// Secret access key for [`BACKUP_S3_ACCESS_KEY_ID`].
+pub const BACKUP_S3_SECRET_ACCESS_KEY: ParsedValueRef<
+    '_, FromStrParser<String>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["backup", "s3", "secret-access-key"],
+        def: "",
+    },
+    parser: parse::STRING,
+};
+
+/// All configuration value references used by this library.
+pub const ALL: &[&dyn AnyValueRef] = &[
+    &DB_BACKEND,
+    &DB_CACHE_SIZE,
+    &DB_SQLITE_PATH,
+    &DB_SQLITE_SCHEMA_PATH,
+    &DB_REMOTE_URL,
+    &DB_REMOTE_HEADERS,
+    &OCC_ALERT_DEFAULT,
+    &OCC_GEN_HORIZON,
+    &OCC_GEN_INTERVAL,
+    &CLOCK_NOW,
+    &CLOCK_UTC_OFFSET,
+    &NOTIFICATIONS_CHANNELS,
+    &NOTIFICATIONS_INTERVAL,
+    &NOTIFICATIONS_LOOKBACK,
+    &NOTIFICATIONS_TEMPLATES_SUBJECT,
+    &NOTIFICATIONS_TEMPLATES_BODY,
+    &NOTIFICATIONS_LOG_SUBJECT,
+    &NOTIFICATIONS_LOG_BODY,
+    &NOTIFICATIONS_DIGEST_ENABLED,
+    &NOTIFICATIONS_DIGEST_TIME,
+    &NOTIFICATIONS_DIGEST_STATS_PERIOD,
+    &NOTIFICATIONS_DIGEST_WEEKLY_REVIEW,
+    &NOTIFICATIONS_EMAIL_SERVER,
+    &NOTIFICATIONS_EMAIL_PORT,
+    #[cfg(feature = "email")]
+    &NOTIFICATIONS_EMAIL_SECURITY,
+    &NOTIFICATIONS_EMAIL_USERNAME,
+    &NOTIFICATIONS_EMAIL_PASSWORD,
+    #[cfg(feature = "email")]
+    &NOTIFICATIONS_EMAIL_FROM,
+    #[cfg(feature = "email")]
+    &NOTIFICATIONS_EMAIL_TO,
+    &NOTIFICATIONS_EMAIL_SUBJECT,
+    &NOTIFICATIONS_EMAIL_BODY,
+    &NOTIFICATIONS_NTFY_URL,
+    &NOTIFICATIONS_NTFY_TOPIC,
+    &NOTIFICATIONS_NTFY_TOKEN,
+    &NOTIFICATIONS_NTFY_PRIORITY_UPCOMING,
+    &NOTIFICATIONS_NTFY_PRIORITY_OVERDUE,
+    &NOTIFICATIONS_NTFY_SUBJECT,
+    &NOTIFICATIONS_NTFY_BODY,
+    &NOTIFICATIONS_GOTIFY_URL,
+    &NOTIFICATIONS_GOTIFY_TOKEN,
+    &NOTIFICATIONS_GOTIFY_PRIORITY_UPCOMING,
+    &NOTIFICATIONS_GOTIFY_PRIORITY_OVERDUE,
+    &NOTIFICATIONS_GOTIFY_SUBJECT,
+    &NOTIFICATIONS_GOTIFY_BODY,
+    &NOTIFICATIONS_WEBHOOK_URL,
+    &NOTIFICATIONS_WEBHOOK_PRIORITY_UPCOMING,
+    &NOTIFICATIONS_WEBHOOK_PRIORITY_OVERDUE,
+    &NOTIFICATIONS_WEBHOOK_SUBJECT,
+    &NOTIFICATIONS_WEBHOOK_BODY,
+    &MILESTONES_COMPLETIONS,
+    &MILESTONES_STREAKS,
+    &MILESTONES_INTERVAL,
+    &SYNC_ICS_URLS,
+    &SYNC_ICS_INTERVAL,
+    &SYNC_SERVER_URL,
+    &SYNC_SERVER_HEADERS,
+    &SYNC_SERVER_STATE_PATH,
+    &SYNC_SERVER_INTERVAL,
+    &SYNC_GCAL_CALENDAR_ID,
+    &SYNC_GCAL_CLIENT_ID,
+    &SYNC_GCAL_CLIENT_SECRET,
+    &SYNC_GCAL_REFRESH_TOKEN,
+    &SYNC_GCAL_PERIOD,
+    &SYNC_GCAL_INTERVAL,
+    &BACKUP_DESTINATION,
+    &BACKUP_SCHEDULE,
+    &BACKUP_RETENTION,
+    &BACKUP_DIR_PATH,
+    &BACKUP_S3_ENDPOINT,
+    &BACKUP_S3_REGION,
+    &BACKUP_S3_BUCKET,
+    &BACKUP_S3_PREFIX,
+    &BACKUP_S3_ACCESS_KEY_ID,
+    &BACKUP_S3_SECRET_ACCESS_KEY,
+];
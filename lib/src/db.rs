@@ -1,37 +1,96 @@
 //! Database for storing items, occurrences and configs.
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroUsize;
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::atomic;
-use serde::{Deserialize, Serialize};
-use crate::config::Config;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::config::{self, Config};
 use crate::configrefs;
-use crate::types::{Config as ItemConfig, Item, ItemType, Occ, OccDate};
+use crate::types::{Alert, Config as ItemConfig, Item, ItemType, Milestone,
+                   Occ, OccDate, ProgressEntry, User};
+use crate::util::config::{self as config_util, ResolvedConfig};
 
+pub mod cached;
+pub mod notify;
+#[cfg(feature = "http")]
+pub mod remote;
+#[cfg(feature = "sqlite")]
 mod sqlite;
 pub mod util;
 
 /// [`Item`] that has been stored in the database.
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct StoredItem {
     pub id: String,
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = String, format = DateTime))]
     pub created: OccDate,
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = String, format = DateTime))]
     pub updated: OccDate,
     pub item: Item,
 }
 
 /// [`Occ`] that has been stored in the database.
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct StoredOcc {
     pub id: String,
     pub occ: Occ,
 }
 
+/// [`ProgressEntry`] that has been stored in the database.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StoredProgressEntry {
+    pub id: String,
+    /// ID of the occurrence the progress applies to.
+    pub occ_id: String,
+    pub entry: ProgressEntry,
+}
+
+/// [`Alert`] that has been stored in the database.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StoredAlert {
+    pub id: String,
+    /// ID of the occurrence the alert was about.
+    pub occ_id: String,
+    pub alert: Alert,
+}
+
+/// [`Milestone`] that has been stored in the database.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StoredMilestone {
+    pub id: String,
+    /// ID of the item which reached the milestone.
+    pub item_id: String,
+    pub milestone: Milestone,
+}
+
+/// [`User`] that has been stored in the database.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StoredUser {
+    pub id: String,
+    pub user: User,
+}
+
 /// The target of a [`Config`], also serving as a unique identifier.
 ///
 /// Options are in order of precedence when applying to an occurrence---later
 /// options take precedence over earlier options.
-#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+///
+/// Config IDs are written as scopes like `all`, `type:Event`,
+/// `category:<category>`, `item:<id>` or `occ:<id>`, using `Display` and
+/// `FromStr`.  They're serialised in the same form.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum ConfigId {
     /// Applies to all occurrences.
     All,
@@ -45,13 +104,126 @@ pub enum ConfigId {
     Occ { id: String },
 }
 
+impl ConfigId {
+    /// The kind of scope, which starts the ID's string form: `all`, `type`,
+    /// `category`, `item` or `occ`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ConfigId::All => "all",
+            ConfigId::Type(_) => "type",
+            ConfigId::Category(_) => "category",
+            ConfigId::Item { .. } => "item",
+            ConfigId::Occ { .. } => "occ",
+        }
+    }
+}
+
+impl fmt::Display for ConfigId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = self.kind();
+        match self {
+            ConfigId::All => f.write_str(kind),
+            ConfigId::Type(type_) => write!(f, "{kind}:{}", type_.as_ref()),
+            ConfigId::Category(cat) => write!(f, "{kind}:{cat}"),
+            ConfigId::Item { id } | ConfigId::Occ { id } => {
+                write!(f, "{kind}:{id}")
+            }
+        }
+    }
+}
+
+impl FromStr for ConfigId {
+    type Err = String;
+
+    fn from_str(scope: &str) -> Result<ConfigId, String> {
+        let id = match scope.split_once(':') {
+            None if scope == "all" => Some(ConfigId::All),
+            Some(("type", type_)) => type_.parse::<ItemType>().ok()
+                .map(ConfigId::Type),
+            Some(("category", cat)) => Some(ConfigId::Category(cat.to_owned())),
+            Some(("item", id)) => Some(ConfigId::Item { id: id.to_owned() }),
+            Some(("occ", id)) => Some(ConfigId::Occ { id: id.to_owned() }),
+            _ => None,
+        };
+        id.ok_or_else(|| format!("invalid config scope: {scope}"))
+    }
+}
+
+impl Serialize for ConfigId {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ConfigId {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<ConfigId, D::Error> {
+        String::deserialize(d)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 /// [`Config`] that has been stored in the database.
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
 pub struct StoredConfig {
     pub id: ConfigId,
     pub config: ItemConfig,
 }
 
+/// Identifies an object whose changes are recorded, as returned by
+/// [`Db::find_revisions`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+pub enum ObjectId {
+    Item { id: String },
+    Occ { id: String },
+    ProgressEntry { id: String },
+    Config { id: ConfigId },
+}
+
+/// Latest change made to an object, as returned by [`Db::find_revisions`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+pub struct Revision {
+    /// Increases with every change made to the database, for all users.
+    pub rev: u64,
+    pub object: ObjectId,
+    /// Whether the change was deleting the object.
+    pub deleted: bool,
+    /// When the change was written.
+    pub date: OccDate,
+}
+
+/// Change made to an item or to one of its configs, recorded in the audit log.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+pub enum AuditChange {
+    CreateItem(Item),
+    /// The item was updated, and now has this value.
+    UpdateItem(Item),
+    DeleteItem,
+    /// A config scoped to the item or one of its occurrences was set.
+    SetConfig(StoredConfig),
+    DeleteConfig { id: ConfigId },
+}
+
+/// Entry in an item's audit log, as returned by [`Db::find_audit_entries`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+pub struct AuditEntry {
+    pub item_id: String,
+    pub change: AuditChange,
+    /// When the change was written.
+    pub date: OccDate,
+}
+
+/// Number of occurrences of an item with the same progress, as returned by
+/// [`Db::count_occs_by_progress`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct OccProgressCount {
+    pub item_id: String,
+    /// Value of [`Occ::task_completion_progress`] for all counted occurrences.
+    pub progress: u32,
+    /// Whether the counted occurrences ended before the requested date.
+    pub ended: bool,
+    /// Number of occurrences.
+    pub count: u32,
+}
+
 /// The core `Result` type used by database functions.  All database errors
 /// will be strings.
 pub type DbResult<T> = Result<T, String>;
@@ -60,9 +232,11 @@ pub type DbWriteResult = DbResult<HashMap<IdToken, String>>;
 pub type DbResults<T> = DbResult<Vec<T>>;
 
 /// Temporary ID referring to objects that are yet to be written to the
-/// database.
+/// database.  Tokens only need to be unique within a single
+/// [write](Db::write).
 pub type IdToken = u64;
-/// Used to generate `IdToken` values sequentially in a thread-safe manner.
+/// Used by the deprecated [`DbUpdate::id_token`] to generate `IdToken` values
+/// which are unique within the process.
 static UPDATE_TOKEN: atomic::AtomicU64 = atomic::AtomicU64::new(0);
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -85,6 +259,14 @@ pub enum UpdateId<'a> {
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum DbUpdate<'a> {
     CreateItem { id_token: IdToken, item: &'a Item },
+    /// Create or update the item synced from `source` with ID `external_id`
+    /// there.  The token refers to the item whether it's created or updated.
+    UpsertItem {
+        id_token: IdToken,
+        source: &'a str,
+        external_id: &'a str,
+        item: &'a Item,
+    },
     UpdateItem(&'a StoredItem),
     DeleteItem { id: &'a str },
     /// [`Config`] identifiers are known before writing, so this is a
@@ -94,10 +276,28 @@ pub enum DbUpdate<'a> {
     CreateOcc { id_token: IdToken, item_id: UpdateId<'a>, occ: &'a Occ },
     UpdateOcc(&'a StoredOcc),
     DeleteOcc { id: &'a str },
+    CreateProgressEntry {
+        id_token: IdToken,
+        occ_id: UpdateId<'a>,
+        entry: &'a ProgressEntry,
+    },
+    DeleteProgressEntry { id: &'a str },
+    CreateAlert { id_token: IdToken, occ_id: UpdateId<'a>, alert: &'a Alert },
+    UpdateAlert(&'a StoredAlert),
+    CreateDigest { id_token: IdToken, sent: OccDate },
+    CreateUser { id_token: IdToken, user: &'a User },
+    CreateMilestone {
+        id_token: IdToken,
+        item_id: UpdateId<'a>,
+        milestone: &'a Milestone,
+    },
 }
 
 impl<'a> DbUpdate<'a> {
-    /// Generate a token for use as a temporary ID.
+    /// Generate a token for use as a temporary ID, which is unique within the
+    /// process.
+    #[deprecated(note = "tokens only need to be unique within a write: \
+                         use `WriteBatch` or `IdTokens`")]
     pub fn id_token() -> IdToken {
         UPDATE_TOKEN.fetch_add(1, atomic::Ordering::Relaxed)
     }
@@ -106,6 +306,17 @@ impl<'a> DbUpdate<'a> {
         DbUpdate::CreateItem { id_token, item }
     }
 
+    /// Create or update the item synced from `source` with ID `external_id`
+    /// there.  The token refers to the item whether it's created or updated.
+    pub fn upsert_item(
+        id_token: IdToken,
+        source: &'a str,
+        external_id: &'a str,
+        item: &'a Item,
+    ) -> DbUpdate<'a> {
+        DbUpdate::UpsertItem { id_token, source, external_id, item }
+    }
+
     pub fn update_item(item: &'a StoredItem) -> DbUpdate<'a> {
         DbUpdate::UpdateItem(item)
     }
@@ -139,10 +350,198 @@ impl<'a> DbUpdate<'a> {
     pub fn delete_occ(id: &'a str) -> DbUpdate<'a> {
         DbUpdate::DeleteOcc { id }
     }
+
+    pub fn create_progress_entry(
+        id_token: IdToken,
+        occ_id: UpdateId<'a>,
+        entry: &'a ProgressEntry,
+    ) -> DbUpdate<'a> {
+        DbUpdate::CreateProgressEntry { id_token, occ_id, entry }
+    }
+
+    pub fn delete_progress_entry(id: &'a str) -> DbUpdate<'a> {
+        DbUpdate::DeleteProgressEntry { id }
+    }
+
+    pub fn create_alert(
+        id_token: IdToken,
+        occ_id: UpdateId<'a>,
+        alert: &'a Alert,
+    ) -> DbUpdate<'a> {
+        DbUpdate::CreateAlert { id_token, occ_id, alert }
+    }
+
+    pub fn update_alert(alert: &'a StoredAlert) -> DbUpdate<'a> {
+        DbUpdate::UpdateAlert(alert)
+    }
+
+    pub fn create_digest(id_token: IdToken, sent: OccDate) -> DbUpdate<'a> {
+        DbUpdate::CreateDigest { id_token, sent }
+    }
+
+    pub fn create_user(id_token: IdToken, user: &'a User) -> DbUpdate<'a> {
+        DbUpdate::CreateUser { id_token, user }
+    }
+
+    pub fn create_milestone(
+        id_token: IdToken,
+        item_id: UpdateId<'a>,
+        milestone: &'a Milestone,
+    ) -> DbUpdate<'a> {
+        DbUpdate::CreateMilestone { id_token, item_id, milestone }
+    }
+}
+
+/// Allocates [tokens](IdToken) for the objects created by a single write.
+///
+/// Use this where updates need tokens before they can be built, such as
+/// when planning updates from owned data; otherwise, use [`WriteBatch`].
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct IdTokens {
+    next: IdToken,
+}
+
+impl IdTokens {
+    pub fn new() -> IdTokens {
+        IdTokens::default()
+    }
+
+    /// Get a token which hasn't been allocated before.
+    pub fn allocate(&mut self) -> IdToken {
+        let token = self.next;
+        self.next += 1;
+        token
+    }
+}
+
+/// Builds a list of updates to [write](Db::write) together, allocating tokens
+/// for the objects they create.
+///
+/// Functions which create objects return the object's token, for referring to
+/// it in later updates and finding its ID in the write result.
+#[derive(Clone, Debug, Default)]
+pub struct WriteBatch<'a> {
+    tokens: IdTokens,
+    updates: Vec<DbUpdate<'a>>,
+}
+
+impl<'a> WriteBatch<'a> {
+    pub fn new() -> WriteBatch<'a> {
+        WriteBatch::default()
+    }
+
+    /// Start a batch which continues allocating tokens from `tokens`, for
+    /// when some tokens were allocated before the updates could be built.
+    pub fn with_tokens(tokens: IdTokens) -> WriteBatch<'a> {
+        WriteBatch { tokens, updates: vec![] }
+    }
+
+    /// Allocate a token, for updates built with [`DbUpdate`] functions.
+    pub fn token(&mut self) -> IdToken {
+        self.tokens.allocate()
+    }
+
+    /// Add an update.  Any tokens it creates objects with must come from this
+    /// batch.
+    pub fn push(&mut self, update: DbUpdate<'a>) {
+        self.updates.push(update);
+    }
+
+    pub fn create_item(&mut self, item: &'a Item) -> IdToken {
+        let id_token = self.token();
+        self.push(DbUpdate::create_item(id_token, item));
+        id_token
+    }
+
+    /// See [`DbUpdate::upsert_item`].
+    pub fn upsert_item(
+        &mut self,
+        source: &'a str,
+        external_id: &'a str,
+        item: &'a Item,
+    ) -> IdToken {
+        let id_token = self.token();
+        self.push(DbUpdate::upsert_item(id_token, source, external_id, item));
+        id_token
+    }
+
+    pub fn create_occ(&mut self, item_id: UpdateId<'a>, occ: &'a Occ)
+    -> IdToken {
+        let id_token = self.token();
+        self.push(DbUpdate::create_occ(id_token, item_id, occ));
+        id_token
+    }
+
+    pub fn create_progress_entry(
+        &mut self,
+        occ_id: UpdateId<'a>,
+        entry: &'a ProgressEntry,
+    ) -> IdToken {
+        let id_token = self.token();
+        self.push(DbUpdate::create_progress_entry(id_token, occ_id, entry));
+        id_token
+    }
+
+    pub fn create_alert(&mut self, occ_id: UpdateId<'a>, alert: &'a Alert)
+    -> IdToken {
+        let id_token = self.token();
+        self.push(DbUpdate::create_alert(id_token, occ_id, alert));
+        id_token
+    }
+
+    pub fn create_digest(&mut self, sent: OccDate) -> IdToken {
+        let id_token = self.token();
+        self.push(DbUpdate::create_digest(id_token, sent));
+        id_token
+    }
+
+    pub fn create_user(&mut self, user: &'a User) -> IdToken {
+        let id_token = self.token();
+        self.push(DbUpdate::create_user(id_token, user));
+        id_token
+    }
+
+    pub fn create_milestone(
+        &mut self,
+        item_id: UpdateId<'a>,
+        milestone: &'a Milestone,
+    ) -> IdToken {
+        let id_token = self.token();
+        self.push(DbUpdate::create_milestone(id_token, item_id, milestone));
+        id_token
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.updates.len()
+    }
+
+    /// The updates, in the order added, in the form taken by
+    /// [`Db::write`].
+    pub fn updates(&self) -> Vec<&DbUpdate<'a>> {
+        self.updates.iter().collect()
+    }
+
+    /// Write the updates to `db`.
+    pub fn write<D: Db + ?Sized>(&self, db: &mut D) -> DbWriteResult {
+        db.write(&self.updates())
+    }
 }
 
 /// Database for storing items, occurrences and configs.
+///
+/// Items, occurrences, configs, progress entries and alerts are owned by at
+/// most one user, and all operations on them are restricted to those owned by
+/// the current user, set using [`set_user`](Db::set_user).  Objects are
+/// created with the current user as their owner.
 pub trait Db {
+    /// Set the current user, by ID, or `None` to operate on objects without an
+    /// owner.  Initially, there is no current user.
+    fn set_user(&mut self, id: Option<&str>) -> DbResult<()>;
+
     /// Write some changes to the database.
     ///
     /// `updates` are processed in the order provided.  Tokens used must refer
@@ -153,16 +552,21 @@ pub trait Db {
 
     /// Get all items matching the specified criteria.
     ///
-    /// `active` filters to items which are active or not.  `start` filters to
-    /// items which are recurring, or which are non-recurring and occur after
-    /// this date.
+    /// `active` filters to items which are active or not.  `category` filters
+    /// to items with this category.  `start` filters to items which are
+    /// recurring, or which are non-recurring and occur after this date.
     ///
     /// Results are ordered by created date, before applying `max_results`.
+    /// To get the next page of results, pass the ID of the last result as
+    /// `after_id`, with the same criteria.  If no item has this ID, there are
+    /// no results.
     fn find_items(
         &self,
         active: Option<bool>,
+        category: Option<&str>,
         start: Option<OccDate>,
         sort: SortDirection,
+        after_id: Option<&str>,
         max_results: u32,
     ) -> DbResults<StoredItem>;
 
@@ -172,41 +576,386 @@ pub trait Db {
     /// the results.
     fn get_items(&self, ids: &[&str]) -> DbResults<StoredItem>;
 
+    /// Get the items synced from `source` (see
+    /// [`UpsertItem`](DbUpdate::UpsertItem)).
+    ///
+    /// The result maps IDs in the source to item IDs.
+    fn find_external_ids(&self, source: &str)
+    -> DbResult<HashMap<String, String>>;
+
     /// Get configs with the given IDs.
     ///
     /// If an ID doesn't exist, the call succeeds and the config is missing from
     /// the results.
     fn get_configs(&self, ids: &[&ConfigId]) -> DbResults<StoredConfig>;
 
+    /// Get all configs, in no particular order.
+    fn find_configs(&self) -> DbResults<StoredConfig>;
+
+    /// Get and [resolve](config_util::resolve_config) the configs for each
+    /// chain of config IDs.
+    ///
+    /// Each chain is the IDs of the configs applying to a scope and its
+    /// parents, in order from parent to child, as returned by the
+    /// `build_config_ids_...` functions in [`config_util`].  Results are in the
+    /// same order as `chains`, and are `None` for chains where no config
+    /// exists.
+    ///
+    /// The default implementation uses [`get_configs`](Db::get_configs).
+    fn resolve_configs(&self, chains: &[&[ConfigId]])
+    -> DbResults<Option<ResolvedConfig>> {
+        config_util::resolve_configs(self, chains)
+    }
+
     /// Get occurrences with the given IDs.
     ///
     /// If an ID doesn't exist, the call succeeds and the occurrence is missing
     /// from the results.
     fn get_occs(&self, ids: &[&str]) -> DbResults<StoredOcc>;
 
+    /// Get the IDs of the items that the occurrences with the given IDs belong
+    /// to.
+    ///
+    /// The result maps occurrence IDs to item IDs.  If an occurrence doesn't
+    /// exist, the call succeeds and the occurrence is missing from the result.
+    fn get_occs_item_ids(&self, ids: &[&str])
+    -> DbResult<HashMap<String, String>>;
+
     /// Get all occurrences matching the specified criteria.
     ///
     /// `start` and `end` filter to occurrences which overlap the time range.
     ///
     /// The results are a map from item ID to occurrences.  This may not contain
     /// an entry for requested items without any found occurrences.  Results are
-    /// ordered by occurrence start date, before applying `max_results`.
+    /// ordered by occurrence start date, before applying `max_results`.  To get
+    /// the next page of results, pass the ID of the last result as `after_id`,
+    /// with the same criteria.  If no occurrence has this ID, there are no
+    /// results.
     fn find_occs(
         &self,
         item_ids: &[&str],
         start: Option<OccDate>,
         end: Option<OccDate>,
         sort: SortDirection,
+        after_id: Option<&str>,
         max_results: u32,
     ) -> DbResult<HashMap<String, Vec<StoredOcc>>>;
+
+    /// Get occurrences matching the specified criteria, like
+    /// [`find_occs`](Db::find_occs), but limited to `max_per_item` results for
+    /// each item instead of in total.  This avoids a query per item when
+    /// looking for each item's latest occurrence.
+    ///
+    /// Results for each item are ordered by occurrence start date, before
+    /// applying `max_per_item`.
+    fn find_occs_per_item(
+        &self,
+        item_ids: &[&str],
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        sort: SortDirection,
+        max_per_item: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredOcc>>>;
+
+    /// Get progress entries for the occurrences with the given IDs.
+    ///
+    /// The results are a map from occurrence ID to progress entries.  This may
+    /// not contain an entry for requested occurrences without any progress
+    /// entries.  Results are ordered by date, before applying `max_results`.
+    fn find_progress_entries(
+        &self,
+        occ_ids: &[&str],
+        sort: SortDirection,
+        max_results: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredProgressEntry>>>;
+
+    /// Get progress entries with the given IDs.
+    ///
+    /// If an ID doesn't exist, the call succeeds and the progress entry is
+    /// missing from the results.
+    fn get_progress_entries(&self, ids: &[&str])
+    -> DbResults<StoredProgressEntry>;
+
+    /// Get alerts sent about the occurrences with the given IDs.
+    ///
+    /// The results are a map from occurrence ID to alerts, ordered by sent
+    /// date.  This may not contain an entry for requested occurrences without
+    /// any alerts.
+    fn find_alerts(&self, occ_ids: &[&str])
+    -> DbResult<HashMap<String, Vec<StoredAlert>>>;
+
+    /// Get alerts with the given IDs.
+    ///
+    /// If an ID doesn't exist, the call succeeds and the alert is missing from
+    /// the results.
+    fn get_alerts(&self, ids: &[&str]) -> DbResults<StoredAlert>;
+
+    /// Get alerts which haven't been acknowledged, and which aren't snoozed at
+    /// `date`.  Results are ordered by sent date.
+    fn find_unacked_alerts(&self, date: OccDate) -> DbResults<StoredAlert>;
+
+    /// Get when the most recent digest was sent, or `None` if no digest has
+    /// been sent.
+    fn find_last_digest(&self) -> DbResult<Option<OccDate>>;
+
+    /// Get the latest change made to each item, occurrence, progress entry and
+    /// config which has changed since revision `after`, or to every object if
+    /// `after` is `None`.
+    ///
+    /// Objects which were deleted are included, except for objects deleted
+    /// along with another object, such as the occurrences of a deleted item.
+    /// Objects which haven't changed since they were written by an older
+    /// version aren't included.  Results are ordered by revision, before
+    /// applying `max_results`.
+    fn find_revisions(&self, after: Option<u64>, max_results: u32)
+    -> DbResults<Revision>;
+
+    /// Get milestones reached by the item with ID `item_id`, or by every item
+    /// if `item_id` is `None`.  Results are ordered by the date they were
+    /// reached.
+    fn find_milestones(&self, item_id: Option<&str>)
+    -> DbResults<StoredMilestone>;
+
+    /// Get the audit log for the item with ID `item_id`: every change made to
+    /// the item, and to configs scoped to the item or its occurrences.
+    ///
+    /// Entries are kept after the item is deleted.  Changes made by older
+    /// versions aren't included.  Results are ordered by date, oldest first.
+    fn find_audit_entries(&self, item_id: &str) -> DbResults<AuditEntry>;
+
+    /// Get all users, regardless of the current user.
+    ///
+    /// `name` filters to the user with this name.  Results are in no particular
+    /// order.
+    fn find_users(&self, name: Option<&str>) -> DbResults<StoredUser>;
+
+    /// Count active occurrences, grouped by item and progress.
+    ///
+    /// `start` and `end` filter to occurrences which overlap the time range.
+    /// Counts are also split by whether occurrences ended before `now`.
+    fn count_occs_by_progress(
+        &self,
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        now: OccDate,
+    ) -> DbResults<OccProgressCount>;
+
+    /// Write a copy of the whole database, for all users, to a new file at
+    /// `path`.
+    ///
+    /// The format of the copy depends on the backend, and it can only be read
+    /// by [`restore`](Db::restore) using the same backend.
+    fn backup(&self, path: &Path) -> DbResult<()>;
+
+    /// Replace the whole database, for all users, with a copy written by
+    /// [`backup`](Db::backup).
+    ///
+    /// Copies made by older versions are upgraded.  The current user is not
+    /// changed, even if it no longer exists.
+    fn restore(&mut self, path: &Path) -> DbResult<()>;
+
+    /// Check that the whole database, for all users, is in a state this
+    /// version can use, and that all stored items and configs can be read.
+    ///
+    /// Returns a description of each problem found.
+    fn check(&self) -> DbResult<Vec<String>>;
 }
 
-/// Open a connection to the database.
-pub fn open<C>(cfg: &C) -> Result<impl Db, String>
+impl<D> Db for Box<D>
+where
+    D: Db + ?Sized,
+{
+    fn set_user(&mut self, id: Option<&str>) -> DbResult<()> {
+        (**self).set_user(id)
+    }
+
+    fn write(&mut self, updates: &[&DbUpdate]) -> DbWriteResult {
+        (**self).write(updates)
+    }
+
+    fn find_items(
+        &self,
+        active: Option<bool>,
+        category: Option<&str>,
+        start: Option<OccDate>,
+        sort: SortDirection,
+        after_id: Option<&str>,
+        max_results: u32,
+    ) -> DbResults<StoredItem> {
+        (**self).find_items(
+            active, category, start, sort, after_id, max_results)
+    }
+
+    fn get_items(&self, ids: &[&str]) -> DbResults<StoredItem> {
+        (**self).get_items(ids)
+    }
+
+    fn find_external_ids(&self, source: &str)
+    -> DbResult<HashMap<String, String>> {
+        (**self).find_external_ids(source)
+    }
+
+    fn get_configs(&self, ids: &[&ConfigId]) -> DbResults<StoredConfig> {
+        (**self).get_configs(ids)
+    }
+
+    fn find_configs(&self) -> DbResults<StoredConfig> {
+        (**self).find_configs()
+    }
+
+    fn resolve_configs(&self, chains: &[&[ConfigId]])
+    -> DbResults<Option<ResolvedConfig>> {
+        (**self).resolve_configs(chains)
+    }
+
+    fn get_occs(&self, ids: &[&str]) -> DbResults<StoredOcc> {
+        (**self).get_occs(ids)
+    }
+
+    fn get_occs_item_ids(&self, ids: &[&str])
+    -> DbResult<HashMap<String, String>> {
+        (**self).get_occs_item_ids(ids)
+    }
+
+    fn find_occs(
+        &self,
+        item_ids: &[&str],
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        sort: SortDirection,
+        after_id: Option<&str>,
+        max_results: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredOcc>>> {
+        (**self).find_occs(
+            item_ids, start, end, sort, after_id, max_results)
+    }
+
+    fn find_occs_per_item(
+        &self,
+        item_ids: &[&str],
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        sort: SortDirection,
+        max_per_item: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredOcc>>> {
+        (**self).find_occs_per_item(item_ids, start, end, sort, max_per_item)
+    }
+
+    fn find_progress_entries(
+        &self,
+        occ_ids: &[&str],
+        sort: SortDirection,
+        max_results: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredProgressEntry>>> {
+        (**self).find_progress_entries(occ_ids, sort, max_results)
+    }
+
+    fn get_progress_entries(&self, ids: &[&str])
+    -> DbResults<StoredProgressEntry> {
+        (**self).get_progress_entries(ids)
+    }
+
+    fn find_alerts(&self, occ_ids: &[&str])
+    -> DbResult<HashMap<String, Vec<StoredAlert>>> {
+        (**self).find_alerts(occ_ids)
+    }
+
+    fn get_alerts(&self, ids: &[&str]) -> DbResults<StoredAlert> {
+        (**self).get_alerts(ids)
+    }
+
+    fn find_unacked_alerts(&self, date: OccDate) -> DbResults<StoredAlert> {
+        (**self).find_unacked_alerts(date)
+    }
+
+    fn find_last_digest(&self) -> DbResult<Option<OccDate>> {
+        (**self).find_last_digest()
+    }
+
+    fn find_revisions(&self, after: Option<u64>, max_results: u32)
+    -> DbResults<Revision> {
+        (**self).find_revisions(after, max_results)
+    }
+
+    fn find_milestones(&self, item_id: Option<&str>)
+    -> DbResults<StoredMilestone> {
+        (**self).find_milestones(item_id)
+    }
+
+    fn find_audit_entries(&self, item_id: &str) -> DbResults<AuditEntry> {
+        (**self).find_audit_entries(item_id)
+    }
+
+    fn find_users(&self, name: Option<&str>) -> DbResults<StoredUser> {
+        (**self).find_users(name)
+    }
+
+    fn count_occs_by_progress(
+        &self,
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        now: OccDate,
+    ) -> DbResults<OccProgressCount> {
+        (**self).count_occs_by_progress(start, end, now)
+    }
+
+    fn backup(&self, path: &Path) -> DbResult<()> {
+        (**self).backup(path)
+    }
+
+    fn restore(&mut self, path: &Path) -> DbResult<()> {
+        (**self).restore(path)
+    }
+
+    fn check(&self) -> DbResult<Vec<String>> {
+        (**self).check()
+    }
+}
+
+/// Order IDs numerically where possible, like the SQLite backend does, falling
+/// back to ordering them as strings.
+pub(crate) fn cmp_ids(a: &str, b: &str) -> Ordering {
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+/// Open a connection to the database, using the configured
+/// [backend](configrefs::DB_BACKEND).  The `sqlite` backend requires the
+/// `sqlite` feature, and the `remote` backend requires the `http` feature.
+///
+/// Where the backend records dates of changes, they come from the
+/// [configured clock](crate::util::clock::from_config).  The database is
+/// [cached](cached::Db) if [configured](configrefs::DB_CACHE_SIZE).
+pub fn open<C>(cfg: &C) -> Result<Box<dyn Db + Send>, String>
+where
+    C: Config + ?Sized,
+{
+    let db = open_backend(cfg)?;
+    match NonZeroUsize::new(
+        config::get_parsed(cfg, &configrefs::DB_CACHE_SIZE)?)
+    {
+        Some(capacity) => Ok(Box::new(cached::Db::new(db, capacity))),
+        None => Ok(db),
+    }
+}
+
+fn open_backend<C>(cfg: &C) -> Result<Box<dyn Db + Send>, String>
 where
     C: Config + ?Sized,
 {
-    sqlite::open(
-        Path::new(cfg.get_ref(&configrefs::DB_SQLITE_PATH)),
-        Path::new(cfg.get_ref(&configrefs::DB_SQLITE_SCHEMA_PATH)))
+    match config::get_parsed(cfg, &configrefs::DB_BACKEND)?.as_str() {
+        #[cfg(feature = "http")]
+        "remote" => Ok(Box::new(remote::open(
+            cfg.get_ref(&configrefs::DB_REMOTE_URL),
+            config::get_parsed(cfg, &configrefs::DB_REMOTE_HEADERS)?)?)),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => Ok(Box::new(sqlite::open(
+            Path::new(cfg.get_ref(&configrefs::DB_SQLITE_PATH)),
+            Path::new(cfg.get_ref(&configrefs::DB_SQLITE_SCHEMA_PATH)),
+            crate::util::clock::from_config(cfg)?)?)),
+        backend => Err(format!(
+            "database backend not supported by this build: {backend}")),
+    }
 }
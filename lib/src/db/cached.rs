@@ -0,0 +1,320 @@
+//! Implementation of [`Db`](super::Db) which caches reads.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use lru::LruCache;
+use crate::types::OccDate;
+use crate::util::config::{self as config_util, ResolvedConfig};
+use super::{AuditEntry, ConfigId, DbResult, DbResults, DbUpdate,
+            DbWriteResult, IdToken, OccProgressCount, Revision, SortDirection,
+            StoredAlert, StoredConfig, StoredItem, StoredMilestone,
+            StoredOcc, StoredProgressEntry, StoredUser};
+
+/// Database which keeps recently used items, configs and
+/// [resolved config chains](crate::db::Db::resolve_configs) in memory.
+///
+/// Each kind of object is kept in a separate cache, which drops the least
+/// recently used objects beyond its capacity.  Objects which don't exist are
+/// cached too.  Writes made through this database invalidate the objects they
+/// change, so the cache is only accurate when no other process writes to the
+/// underlying database; otherwise, call [`clear`](Db::clear) when the
+/// database may have changed.
+pub struct Db<D: super::Db> {
+    db: D,
+    items: RefCell<LruCache<String, Option<StoredItem>>>,
+    configs: RefCell<LruCache<ConfigId, Option<StoredConfig>>>,
+    resolved: RefCell<LruCache<Vec<ConfigId>, Option<ResolvedConfig>>>,
+}
+
+impl<D: super::Db> Db<D> {
+    /// Cache up to `capacity` of each kind of object read from `db`.
+    pub fn new(db: D, capacity: NonZeroUsize) -> Db<D> {
+        Db {
+            db,
+            items: RefCell::new(LruCache::new(capacity)),
+            configs: RefCell::new(LruCache::new(capacity)),
+            resolved: RefCell::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Drop all cached objects.
+    pub fn clear(&self) {
+        self.items.borrow_mut().clear();
+        self.configs.borrow_mut().clear();
+        self.resolved.borrow_mut().clear();
+    }
+
+    /// Drop cached objects changed by a successful write.
+    fn invalidate(
+        &self,
+        updates: &[&DbUpdate],
+        ids: &HashMap<IdToken, String>,
+    ) {
+        let mut items = self.items.borrow_mut();
+        let mut configs = self.configs.borrow_mut();
+        for update in updates {
+            match update {
+                DbUpdate::CreateItem { id_token, .. } |
+                DbUpdate::UpsertItem { id_token, .. } => {
+                    // the ID might have been cached as missing
+                    if let Some(id) = ids.get(id_token) {
+                        items.pop(id.as_str());
+                    }
+                }
+                DbUpdate::UpdateItem(item) => {
+                    items.pop(item.id.as_str());
+                }
+                DbUpdate::DeleteItem { id } => {
+                    items.pop(*id);
+                }
+                DbUpdate::SetConfig(config) => {
+                    configs.pop(&config.id);
+                    self.resolved.borrow_mut().clear();
+                }
+                DbUpdate::DeleteConfig { id } => {
+                    configs.pop(id);
+                    self.resolved.borrow_mut().clear();
+                }
+                DbUpdate::CreateOcc { .. } |
+                DbUpdate::UpdateOcc(_) |
+                DbUpdate::DeleteOcc { .. } |
+                DbUpdate::CreateProgressEntry { .. } |
+                DbUpdate::DeleteProgressEntry { .. } |
+                DbUpdate::CreateAlert { .. } |
+                DbUpdate::UpdateAlert(_) |
+                DbUpdate::CreateDigest { .. } |
+                DbUpdate::CreateUser { .. } |
+                DbUpdate::CreateMilestone { .. } => (),
+            }
+        }
+    }
+}
+
+/// Look up values by key, using `cache` where possible and `fetch` for the
+/// rest, and cache the fetched values.
+///
+/// `fetch` is called with distinct keys, and returns their values in the same
+/// order.  Results are in the same order as `keys`.
+fn lookup<'k, K, V>(
+    cache: &RefCell<LruCache<K::Owned, V>>,
+    keys: &[&'k K],
+    fetch: impl FnOnce(&[&'k K]) -> DbResults<V>,
+) -> DbResults<V>
+where
+    K: Eq + Hash + ToOwned + ?Sized,
+    K::Owned: Eq + Hash,
+    V: Clone,
+{
+    let mut found = HashMap::<&K, V>::new();
+    let mut missing = HashSet::<&K>::new();
+    {
+        let mut cache = cache.borrow_mut();
+        for key in keys {
+            match cache.get(*key) {
+                Some(value) => { found.insert(key, value.clone()); }
+                None => { missing.insert(key); }
+            }
+        }
+    }
+
+    if !missing.is_empty() {
+        let missing = missing.into_iter().collect::<Vec<_>>();
+        // don't hold the cache while fetching, which may use this database
+        let values = fetch(&missing)?;
+        let mut cache = cache.borrow_mut();
+        for (key, value) in missing.into_iter().zip(values) {
+            cache.put(key.to_owned(), value.clone());
+            found.insert(key, value);
+        }
+    }
+
+    Ok(keys.iter()
+        .flat_map(|key| found.get(key).cloned())
+        .collect())
+}
+
+impl<D: super::Db> super::Db for Db<D> {
+    fn set_user(&mut self, id: Option<&str>) -> DbResult<()> {
+        // cached objects belong to the previous user
+        self.clear();
+        self.db.set_user(id)
+    }
+
+    fn write(&mut self, updates: &[&DbUpdate]) -> DbWriteResult {
+        let result = self.db.write(updates);
+        match &result {
+            Ok(ids) => self.invalidate(updates, ids),
+            // the write may have been partly applied
+            Err(_) => self.clear(),
+        }
+        result
+    }
+
+    fn find_items(
+        &self,
+        active: Option<bool>,
+        category: Option<&str>,
+        start: Option<OccDate>,
+        sort: SortDirection,
+        after_id: Option<&str>,
+        max_results: u32,
+    ) -> DbResults<StoredItem> {
+        self.db.find_items(active, category, start, sort, after_id, max_results)
+    }
+
+    /// Results are in the same order as `ids`.
+    fn get_items(&self, ids: &[&str]) -> DbResults<StoredItem> {
+        let items = lookup(&self.items, ids, |ids| {
+            let mut items = self.db.get_items(ids)?.into_iter()
+                .map(|item| (item.id.clone(), item))
+                .collect::<HashMap<_, _>>();
+            Ok(ids.iter().map(|id| items.remove(*id)).collect())
+        })?;
+        let mut seen = HashSet::new();
+        Ok(ids.iter()
+            .zip(items)
+            .filter(|(id, _)| seen.insert(*id))
+            .flat_map(|(_, item)| item)
+            .collect())
+    }
+
+    fn find_external_ids(&self, source: &str)
+    -> DbResult<HashMap<String, String>> {
+        self.db.find_external_ids(source)
+    }
+
+    /// Results are in the same order as `ids`.
+    fn get_configs(&self, ids: &[&ConfigId]) -> DbResults<StoredConfig> {
+        let configs = lookup(&self.configs, ids, |ids| {
+            let mut configs = self.db.get_configs(ids)?.into_iter()
+                .map(|config| (config.id.clone(), config))
+                .collect::<HashMap<_, _>>();
+            Ok(ids.iter().map(|id| configs.remove(*id)).collect())
+        })?;
+        let mut seen = HashSet::new();
+        Ok(ids.iter()
+            .zip(configs)
+            .filter(|(id, _)| seen.insert(*id))
+            .flat_map(|(_, config)| config)
+            .collect())
+    }
+
+    fn find_configs(&self) -> DbResults<StoredConfig> {
+        self.db.find_configs()
+    }
+
+    /// Configs are read using this database's
+    /// [`get_configs`](super::Db::get_configs), so that they're cached too.
+    fn resolve_configs(&self, chains: &[&[ConfigId]])
+    -> DbResults<Option<ResolvedConfig>> {
+        lookup(&self.resolved, chains, |chains| {
+            config_util::resolve_configs(self, chains)
+        })
+    }
+
+    fn get_occs(&self, ids: &[&str]) -> DbResults<StoredOcc> {
+        self.db.get_occs(ids)
+    }
+
+    fn get_occs_item_ids(&self, ids: &[&str])
+    -> DbResult<HashMap<String, String>> {
+        self.db.get_occs_item_ids(ids)
+    }
+
+    fn find_occs(
+        &self,
+        item_ids: &[&str],
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        sort: SortDirection,
+        after_id: Option<&str>,
+        max_results: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredOcc>>> {
+        self.db.find_occs(item_ids, start, end, sort, after_id, max_results)
+    }
+
+    fn find_occs_per_item(
+        &self,
+        item_ids: &[&str],
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        sort: SortDirection,
+        max_per_item: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredOcc>>> {
+        self.db.find_occs_per_item(item_ids, start, end, sort, max_per_item)
+    }
+
+    fn find_progress_entries(
+        &self,
+        occ_ids: &[&str],
+        sort: SortDirection,
+        max_results: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredProgressEntry>>> {
+        self.db.find_progress_entries(occ_ids, sort, max_results)
+    }
+
+    fn get_progress_entries(&self, ids: &[&str])
+    -> DbResults<StoredProgressEntry> {
+        self.db.get_progress_entries(ids)
+    }
+
+    fn find_alerts(&self, occ_ids: &[&str])
+    -> DbResult<HashMap<String, Vec<StoredAlert>>> {
+        self.db.find_alerts(occ_ids)
+    }
+
+    fn get_alerts(&self, ids: &[&str]) -> DbResults<StoredAlert> {
+        self.db.get_alerts(ids)
+    }
+
+    fn find_unacked_alerts(&self, date: OccDate) -> DbResults<StoredAlert> {
+        self.db.find_unacked_alerts(date)
+    }
+
+    fn find_last_digest(&self) -> DbResult<Option<OccDate>> {
+        self.db.find_last_digest()
+    }
+
+    fn find_revisions(&self, after: Option<u64>, max_results: u32)
+    -> DbResults<Revision> {
+        self.db.find_revisions(after, max_results)
+    }
+
+    fn find_milestones(&self, item_id: Option<&str>)
+    -> DbResults<StoredMilestone> {
+        self.db.find_milestones(item_id)
+    }
+
+    fn find_audit_entries(&self, item_id: &str) -> DbResults<AuditEntry> {
+        self.db.find_audit_entries(item_id)
+    }
+
+    fn find_users(&self, name: Option<&str>) -> DbResults<StoredUser> {
+        self.db.find_users(name)
+    }
+
+    fn count_occs_by_progress(
+        &self,
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        now: OccDate,
+    ) -> DbResults<OccProgressCount> {
+        self.db.count_occs_by_progress(start, end, now)
+    }
+
+    fn backup(&self, path: &Path) -> DbResult<()> {
+        self.db.backup(path)
+    }
+
+    fn restore(&mut self, path: &Path) -> DbResult<()> {
+        self.clear();
+        self.db.restore(path)
+    }
+
+    fn check(&self) -> DbResult<Vec<String>> {
+        self.db.check()
+    }
+}
@@ -0,0 +1,308 @@
+//! Notifying subscribers of changes made to a database.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::types::OccDate;
+use crate::util::config::ResolvedConfig;
+use super::{AuditEntry, ConfigId, Db, DbResult, DbResults, DbUpdate,
+            DbWriteResult, IdToken, OccProgressCount, Revision, SortDirection,
+            StoredAlert, StoredConfig, StoredItem, StoredMilestone,
+            StoredOcc, StoredProgressEntry, StoredUser, UpdateId};
+
+/// The way in which an object was changed.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// Change made to an item or occurrence by a write.
+///
+/// Deleting an item may also delete its occurrences, which are not reported
+/// separately.  Deletions may be reported for objects which didn't exist.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum Change {
+    Item { id: String, kind: ChangeKind },
+    Occ { id: String, kind: ChangeKind },
+}
+
+/// Function called with the owner's user ID and the changes made after each
+/// successful write.
+type Subscriber = Box<dyn Fn(Option<&str>, &[Change]) + Send>;
+
+/// Database which notifies subscribers of the changes made to items and
+/// occurrences by each successful write.
+///
+/// Changes to configs, progress entries, alerts and users are not reported,
+/// except that adding a progress entry is reported as an update to its
+/// occurrence.
+pub struct NotifyingDb<D: Db> {
+    db: D,
+    user: Option<String>,
+    subscribers: Vec<Subscriber>,
+}
+
+impl<D: Db> NotifyingDb<D> {
+    pub fn new(db: D) -> NotifyingDb<D> {
+        NotifyingDb { db, user: None, subscribers: Vec::new() }
+    }
+
+    /// Call `subscriber` after every later successful write.
+    pub fn subscribe<F>(&mut self, subscriber: F)
+    where
+        F: Fn(Option<&str>, &[Change]) + Send + 'static,
+    {
+        self.subscribers.push(Box::new(subscriber));
+    }
+}
+
+/// Resolve the database ID for an object which may have been created in the
+/// same write.
+fn resolve_id(ids: &HashMap<IdToken, String>, id: &UpdateId) -> Option<String> {
+    match id {
+        UpdateId::Id(id) => Some((*id).to_owned()),
+        UpdateId::Token(token) => ids.get(token).cloned(),
+    }
+}
+
+/// Find the tokens of the [upserts](DbUpdate::UpsertItem) in `updates` which
+/// will create items, since no item has been synced from the same source with
+/// the same ID.  This must be called before writing `updates`.
+fn upsert_creations<D: Db>(db: &D, updates: &[&DbUpdate])
+-> DbResult<HashSet<IdToken>> {
+    let mut synced = HashMap::<&str, HashMap<String, String>>::new();
+    let mut created = HashSet::new();
+    for update in updates {
+        let DbUpdate::UpsertItem { id_token, source, external_id, .. } = update
+        else {
+            continue;
+        };
+        if !synced.contains_key(source) {
+            synced.insert(source, db.find_external_ids(source)?);
+        }
+        if !synced[source].contains_key(*external_id) {
+            created.insert(*id_token);
+        }
+    }
+    Ok(created)
+}
+
+/// Determine the changes made by a successful write, in order, without
+/// duplicates.  `upserted` contains the tokens of upserts which created items
+/// (see [`upsert_creations`]).
+fn changes(
+    updates: &[&DbUpdate],
+    ids: &HashMap<IdToken, String>,
+    upserted: &HashSet<IdToken>,
+) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for update in updates {
+        let change = match update {
+            DbUpdate::CreateItem { id_token, .. } => ids.get(id_token)
+                .map(|id| Change::Item {
+                    id: id.clone(), kind: ChangeKind::Created }),
+            DbUpdate::UpsertItem { id_token, .. } => ids.get(id_token)
+                .map(|id| Change::Item {
+                    id: id.clone(),
+                    kind: if upserted.contains(id_token) {
+                        ChangeKind::Created
+                    } else {
+                        ChangeKind::Updated
+                    },
+                }),
+            DbUpdate::UpdateItem(item) => Some(Change::Item {
+                id: item.id.clone(), kind: ChangeKind::Updated }),
+            DbUpdate::DeleteItem { id } => Some(Change::Item {
+                id: (*id).to_owned(), kind: ChangeKind::Deleted }),
+            DbUpdate::CreateOcc { id_token, .. } => ids.get(id_token)
+                .map(|id| Change::Occ {
+                    id: id.clone(), kind: ChangeKind::Created }),
+            DbUpdate::UpdateOcc(occ) => Some(Change::Occ {
+                id: occ.id.clone(), kind: ChangeKind::Updated }),
+            DbUpdate::DeleteOcc { id } => Some(Change::Occ {
+                id: (*id).to_owned(), kind: ChangeKind::Deleted }),
+            DbUpdate::CreateProgressEntry { occ_id, .. } => {
+                resolve_id(ids, occ_id).map(|id| Change::Occ {
+                    id, kind: ChangeKind::Updated })
+            }
+            DbUpdate::SetConfig(_) |
+            DbUpdate::DeleteConfig { .. } |
+            DbUpdate::DeleteProgressEntry { .. } |
+            DbUpdate::CreateAlert { .. } |
+            DbUpdate::UpdateAlert(_) |
+            DbUpdate::CreateDigest { .. } |
+            DbUpdate::CreateUser { .. } |
+            DbUpdate::CreateMilestone { .. } => None,
+        };
+        if let Some(change) = change {
+            if !changes.contains(&change) {
+                changes.push(change);
+            }
+        }
+    }
+    changes
+}
+
+impl<D: Db> Db for NotifyingDb<D> {
+    fn set_user(&mut self, id: Option<&str>) -> DbResult<()> {
+        self.db.set_user(id)?;
+        self.user = id.map(|id| id.to_owned());
+        Ok(())
+    }
+
+    fn write(&mut self, updates: &[&DbUpdate]) -> DbWriteResult {
+        if self.subscribers.is_empty() {
+            return self.db.write(updates);
+        }
+        let upserted = upsert_creations(&self.db, updates)?;
+        let ids = self.db.write(updates)?;
+        let changes = changes(updates, &ids, &upserted);
+        if !changes.is_empty() {
+            for subscriber in &self.subscribers {
+                subscriber(self.user.as_deref(), &changes);
+            }
+        }
+        Ok(ids)
+    }
+
+    fn find_items(
+        &self,
+        active: Option<bool>,
+        category: Option<&str>,
+        start: Option<OccDate>,
+        sort: SortDirection,
+        after_id: Option<&str>,
+        max_results: u32,
+    ) -> DbResults<StoredItem> {
+        self.db.find_items(active, category, start, sort, after_id, max_results)
+    }
+
+    fn get_items(&self, ids: &[&str]) -> DbResults<StoredItem> {
+        self.db.get_items(ids)
+    }
+
+    fn find_external_ids(&self, source: &str)
+    -> DbResult<HashMap<String, String>> {
+        self.db.find_external_ids(source)
+    }
+
+    fn get_configs(&self, ids: &[&ConfigId]) -> DbResults<StoredConfig> {
+        self.db.get_configs(ids)
+    }
+
+    fn find_configs(&self) -> DbResults<StoredConfig> {
+        self.db.find_configs()
+    }
+
+    fn resolve_configs(&self, chains: &[&[ConfigId]])
+    -> DbResults<Option<ResolvedConfig>> {
+        self.db.resolve_configs(chains)
+    }
+
+    fn get_occs(&self, ids: &[&str]) -> DbResults<StoredOcc> {
+        self.db.get_occs(ids)
+    }
+
+    fn get_occs_item_ids(&self, ids: &[&str])
+    -> DbResult<HashMap<String, String>> {
+        self.db.get_occs_item_ids(ids)
+    }
+
+    fn find_occs(
+        &self,
+        item_ids: &[&str],
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        sort: SortDirection,
+        after_id: Option<&str>,
+        max_results: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredOcc>>> {
+        self.db.find_occs(item_ids, start, end, sort, after_id, max_results)
+    }
+
+    fn find_occs_per_item(
+        &self,
+        item_ids: &[&str],
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        sort: SortDirection,
+        max_per_item: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredOcc>>> {
+        self.db.find_occs_per_item(item_ids, start, end, sort, max_per_item)
+    }
+
+    fn find_progress_entries(
+        &self,
+        occ_ids: &[&str],
+        sort: SortDirection,
+        max_results: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredProgressEntry>>> {
+        self.db.find_progress_entries(occ_ids, sort, max_results)
+    }
+
+    fn get_progress_entries(&self, ids: &[&str])
+    -> DbResults<StoredProgressEntry> {
+        self.db.get_progress_entries(ids)
+    }
+
+    fn find_alerts(&self, occ_ids: &[&str])
+    -> DbResult<HashMap<String, Vec<StoredAlert>>> {
+        self.db.find_alerts(occ_ids)
+    }
+
+    fn get_alerts(&self, ids: &[&str]) -> DbResults<StoredAlert> {
+        self.db.get_alerts(ids)
+    }
+
+    fn find_unacked_alerts(&self, date: OccDate) -> DbResults<StoredAlert> {
+        self.db.find_unacked_alerts(date)
+    }
+
+    fn find_last_digest(&self) -> DbResult<Option<OccDate>> {
+        self.db.find_last_digest()
+    }
+
+    fn find_revisions(&self, after: Option<u64>, max_results: u32)
+    -> DbResults<Revision> {
+        self.db.find_revisions(after, max_results)
+    }
+
+    fn find_milestones(&self, item_id: Option<&str>)
+    -> DbResults<StoredMilestone> {
+        self.db.find_milestones(item_id)
+    }
+
+    fn find_audit_entries(&self, item_id: &str) -> DbResults<AuditEntry> {
+        self.db.find_audit_entries(item_id)
+    }
+
+    fn find_users(&self, name: Option<&str>) -> DbResults<StoredUser> {
+        self.db.find_users(name)
+    }
+
+    fn count_occs_by_progress(
+        &self,
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        now: OccDate,
+    ) -> DbResults<OccProgressCount> {
+        self.db.count_occs_by_progress(start, end, now)
+    }
+
+    fn backup(&self, path: &Path) -> DbResult<()> {
+        self.db.backup(path)
+    }
+
+    /// Subscribers are not notified of changes made by restoring.
+    fn restore(&mut self, path: &Path) -> DbResult<()> {
+        self.db.restore(path)
+    }
+
+    fn check(&self) -> DbResult<Vec<String>> {
+        self.db.check()
+    }
+}
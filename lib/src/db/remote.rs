@@ -0,0 +1,520 @@
+//! [`Db`](crate::db::Db) implementation using a web server's API, so that a
+//! central server's database can be used without access to its files.
+//!
+//! The server determines the current user from each request, such as from the
+//! configured headers, so [`set_user`](crate::db::Db::set_user) can't choose
+//! another user.  Items, occurrences, configs, progress entries and milestones
+//! can be read, and all but milestones can be written.  The server's API has
+//! no equivalent for these operations, which fail:
+//!
+//! - finding synced items, and upserting items
+//! - getting progress entries by ID
+//! - finding alerts (they can only be read by ID), and writing them
+//! - finding and writing digests
+//! - finding revisions and audit entries
+//! - finding and creating users
+//! - writing milestones
+//! - counting occurrences by progress
+//! - backing up and restoring
+//!
+//! So anything which uses these, such as statistics, item history, syncing and
+//! sending notifications, should use the server's API instead.
+//!
+//! Writes are sent as a single batch, so they're atomic.  Reads may make many
+//! requests, and aren't consistent with changes made on the server at the same
+//! time.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use crate::types::{Config as ItemConfig, OccDate};
+use crate::db::{cmp_ids, AuditEntry, ConfigId, DbResult, DbResults, DbUpdate,
+                DbWriteResult, IdToken, OccProgressCount, Revision,
+                SortDirection, StoredAlert, StoredConfig, StoredItem,
+                StoredMilestone, StoredOcc, StoredProgressEntry, StoredUser,
+                UpdateId};
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+/// Largest page of results the server returns.
+const MAX_PAGE_SIZE: u32 = 1000;
+
+/// Remote [`Db`](crate::db::Db) implementation.
+#[derive(Debug)]
+pub struct Db {
+    agent: ureq::Agent,
+    /// URL the API is served at.
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+/// Config returned by the server, with its ID as a scope.
+#[derive(Debug, Deserialize)]
+struct ScopedConfig {
+    scope: ConfigId,
+    config: ItemConfig,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchResult {
+    ids: HashMap<IdToken, String>,
+}
+
+/// Use the database of the web server whose API is served at `api_url`.
+/// `headers` are added to every request.
+pub fn open(api_url: &str, headers: Vec<(String, String)>) -> DbResult<Db> {
+    if api_url.is_empty() {
+        return Err("no server URL configured (db.remote.url)".to_owned());
+    }
+    Ok(Db {
+        agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+        url: api_url.trim_end_matches('/').to_owned(),
+        headers,
+    })
+}
+
+fn unsupported<T>(what: &str) -> DbResult<T> {
+    Err(format!("{what} is not supported by the remote database"))
+}
+
+fn update_ref(id: &UpdateId) -> serde_json::Value {
+    match id {
+        UpdateId::Id(id) => json!({ "Id": id }),
+        UpdateId::Token(token) => json!({ "Token": token }),
+    }
+}
+
+/// Convert an update to an operation for the server's `/batch` endpoint.
+fn operation(update: &DbUpdate) -> DbResult<serde_json::Value> {
+    Ok(match update {
+        DbUpdate::CreateItem { id_token, item } => json!({
+            "CreateItem": { "token": id_token, "item": item },
+        }),
+        DbUpdate::UpdateItem(item) => json!({
+            "UpdateItem": { "id": item.id, "item": item.item },
+        }),
+        DbUpdate::DeleteItem { id } => json!({ "DeleteItem": { "id": id } }),
+        DbUpdate::SetConfig(config) => json!({
+            "SetConfig": {
+                "scope": config.id,
+                "config": config.config,
+            },
+        }),
+        DbUpdate::DeleteConfig { id } => json!({
+            "DeleteConfig": { "scope": id },
+        }),
+        DbUpdate::CreateOcc { id_token, item_id, occ } => json!({
+            "CreateOcc": {
+                "token": id_token,
+                "item": update_ref(item_id),
+                "occ": occ,
+            },
+        }),
+        DbUpdate::UpdateOcc(occ) => json!({
+            "UpdateOcc": { "id": occ.id, "occ": occ.occ },
+        }),
+        DbUpdate::DeleteOcc { id } => json!({ "DeleteOcc": { "id": id } }),
+        DbUpdate::CreateProgressEntry { id_token, occ_id, entry } => json!({
+            "CreateProgressEntry": {
+                "token": id_token,
+                "occ": update_ref(occ_id),
+                "entry": entry,
+            },
+        }),
+        DbUpdate::DeleteProgressEntry { id } => json!({
+            "DeleteProgressEntry": { "id": id },
+        }),
+        DbUpdate::UpsertItem { .. } => return unsupported("syncing items"),
+        DbUpdate::CreateAlert { .. } | DbUpdate::UpdateAlert(_) => {
+            return unsupported("writing alerts");
+        }
+        DbUpdate::CreateDigest { .. } => return unsupported("writing digests"),
+        DbUpdate::CreateUser { .. } => return unsupported("creating users"),
+        DbUpdate::CreateMilestone { .. } => {
+            return unsupported("writing milestones");
+        }
+    })
+}
+
+/// Sort `results` by date then ID, in the `sort` direction, and keep the first
+/// `max_results`.
+fn sort_results<T>(
+    results: &mut Vec<T>,
+    sort: SortDirection,
+    max_results: u32,
+    key: impl Fn(&T) -> (OccDate, &str),
+) {
+    results.sort_by(|a, b| {
+        let ((a_date, a_id), (b_date, b_id)) = (key(a), key(b));
+        let order = a_date.cmp(&b_date).then_with(|| cmp_ids(a_id, b_id));
+        match sort {
+            SortDirection::Asc => order,
+            SortDirection::Desc => order.reverse(),
+        }
+    });
+    results.truncate(max_results as usize);
+}
+
+fn sort_query(sort: SortDirection) -> String {
+    match sort {
+        SortDirection::Asc => "asc".to_owned(),
+        SortDirection::Desc => "desc".to_owned(),
+    }
+}
+
+impl Db {
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let url = format!("{}{path}", self.url);
+        let mut req = self.agent.request(method, &url);
+        for (name, value) in &self.headers {
+            req = req.set(name, value);
+        }
+        req
+    }
+
+    /// Send a request, returning `None` if the object wasn't found.
+    fn send(
+        &self,
+        req: ureq::Request,
+        body: Option<&serde_json::Value>,
+    ) -> DbResult<Option<ureq::Response>> {
+        let result = match body {
+            Some(body) => req.send_json(body),
+            None => req.call(),
+        };
+        match result {
+            Ok(res) => Ok(Some(res)),
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(ureq::Error::Status(status, res)) => {
+                let message = res.into_string().unwrap_or_default();
+                Err(format!("error from server ({status}): {message}"))
+            }
+            Err(e) => Err(format!("error connecting to server: {e}")),
+        }
+    }
+
+    /// Get a value from the server, returning `None` if it doesn't exist.
+    fn get<T>(&self, path: &str, query: &[(&str, String)])
+    -> DbResult<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let mut req = self.request("GET", path);
+        for (name, value) in query {
+            req = req.query(name, value);
+        }
+        self.send(req, None)?
+            .map(|res| res.into_json()
+                .map_err(|e| format!("invalid response from server: {e}")))
+            .transpose()
+    }
+
+    /// Get values from the server for each of `ids`, skipping those which
+    /// don't exist.  `path` gives the path for an ID.
+    fn get_each<T>(&self, ids: &[&str], path: impl Fn(&str) -> String)
+    -> DbResults<T>
+    where
+        T: DeserializeOwned,
+    {
+        let mut results = vec![];
+        for id in ids {
+            results.extend(self.get(&path(id), &[])?);
+        }
+        Ok(results)
+    }
+
+    /// Get paged results from the server, following pages until there are
+    /// `max_results`.  Returns an empty list if the server says the
+    /// collection doesn't exist.
+    fn get_pages<T>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+        after_id: Option<&str>,
+        max_results: u32,
+        id: impl Fn(&T) -> &str,
+    ) -> DbResults<T>
+    where
+        T: DeserializeOwned,
+    {
+        let mut results: Vec<T> = vec![];
+        let mut cursor = after_id.map(|id| id.to_owned());
+        loop {
+            let limit = (max_results - results.len() as u32).min(MAX_PAGE_SIZE);
+            if limit == 0 {
+                break;
+            }
+            let mut page_query = query.to_vec();
+            page_query.push(("limit", limit.to_string()));
+            if let Some(cursor) = &cursor {
+                page_query.push(("cursor", cursor.clone()));
+            }
+            let page: Vec<T> = self.get(path, &page_query)?
+                .unwrap_or_default();
+            let done = page.len() < limit as usize;
+            results.extend(page);
+            cursor = results.last().map(|result| id(result).to_owned());
+            if done {
+                break;
+            }
+        }
+        Ok(results)
+    }
+}
+
+impl crate::db::Db for Db {
+    fn set_user(&mut self, id: Option<&str>) -> DbResult<()> {
+        match id {
+            Some(_) => Err("the remote database's user is chosen by the \
+                            server".to_owned()),
+            None => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(level = "debug", skip_all,
+                          fields(updates = updates.len()))]
+    fn write(&mut self, updates: &[&DbUpdate]) -> DbWriteResult {
+        if updates.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let ops = updates.iter()
+            .map(|update| operation(update))
+            .collect::<DbResult<Vec<_>>>()?;
+        let res = self.send(self.request("POST", "/batch"),
+                            Some(&serde_json::Value::Array(ops)))?
+            .ok_or("error from server (404): not found".to_owned())?;
+        let result: BatchResult = res.into_json()
+            .map_err(|e| format!("invalid response from server: {e}"))?;
+        Ok(result.ids)
+    }
+
+    fn find_items(
+        &self,
+        active: Option<bool>,
+        category: Option<&str>,
+        start: Option<OccDate>,
+        sort: SortDirection,
+        after_id: Option<&str>,
+        max_results: u32,
+    ) -> DbResults<StoredItem> {
+        let status = match active {
+            Some(true) => "active",
+            Some(false) => "inactive",
+            None => "all",
+        };
+        let mut query = vec![
+            ("status", status.to_owned()),
+            ("sort", sort_query(sort)),
+        ];
+        query.extend(category.map(|cat| ("category", cat.to_owned())));
+        query.extend(start.map(|start| ("start", start.to_rfc3339())));
+        self.get_pages("/item", &query, after_id, max_results,
+                       |item: &StoredItem| &item.id)
+    }
+
+    fn get_items(&self, ids: &[&str]) -> DbResults<StoredItem> {
+        self.get_each(ids, |id| format!("/item/{id}"))
+    }
+
+    fn find_external_ids(&self, source: &str)
+    -> DbResult<HashMap<String, String>> {
+        unsupported("finding synced items")
+    }
+
+    fn get_configs(&self, ids: &[&ConfigId]) -> DbResults<StoredConfig> {
+        Ok(self.find_configs()?.into_iter()
+            .filter(|config| ids.contains(&&config.id))
+            .collect())
+    }
+
+    fn find_configs(&self) -> DbResults<StoredConfig> {
+        let configs: Vec<ScopedConfig> = self.get("/config", &[])?
+            .unwrap_or_default();
+        Ok(configs.into_iter()
+            .map(|config| StoredConfig {
+                id: config.scope,
+                config: config.config,
+            })
+            .collect())
+    }
+
+    fn get_occs(&self, ids: &[&str]) -> DbResults<StoredOcc> {
+        self.get_each(ids, |id| format!("/occ/{id}"))
+    }
+
+    fn get_occs_item_ids(&self, ids: &[&str])
+    -> DbResult<HashMap<String, String>> {
+        let mut result = HashMap::new();
+        for id in ids {
+            let item: Option<StoredItem> = self.get(
+                &format!("/occ/{id}/item"), &[])?;
+            if let Some(item) = item {
+                result.insert((*id).to_owned(), item.id);
+            }
+        }
+        Ok(result)
+    }
+
+    fn find_occs(
+        &self,
+        item_ids: &[&str],
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        sort: SortDirection,
+        after_id: Option<&str>,
+        max_results: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredOcc>>> {
+        let all_items;
+        let item_ids = if item_ids.is_empty() {
+            all_items = self.find_items(
+                None, None, None, SortDirection::Asc, None, u32::MAX)?;
+            all_items.iter().map(|item| item.id.as_str()).collect()
+        } else {
+            item_ids.to_vec()
+        };
+        let mut query = vec![("sort", sort_query(sort))];
+        query.extend(start.map(|start| ("start", start.to_rfc3339())));
+        query.extend(end.map(|end| ("end", end.to_rfc3339())));
+
+        // the server orders by start date then ID across all items, so a
+        // cursor from any item works for each item
+        let mut occs = vec![];
+        for item_id in item_ids {
+            let item_occs = self.get_pages(
+                &format!("/item/{item_id}/occ"), &query, after_id,
+                max_results, |occ: &StoredOcc| &occ.id)?;
+            occs.extend(item_occs.into_iter()
+                .map(|occ| (item_id.to_owned(), occ)));
+        }
+        sort_results(&mut occs, sort, max_results,
+                     |(_, occ)| (occ.occ.start, &occ.id));
+
+        let mut result = HashMap::<String, Vec<StoredOcc>>::new();
+        for (item_id, occ) in occs {
+            result.entry(item_id).or_default().push(occ);
+        }
+        Ok(result)
+    }
+
+    fn find_occs_per_item(
+        &self,
+        item_ids: &[&str],
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        sort: SortDirection,
+        max_per_item: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredOcc>>> {
+        let all_items;
+        let item_ids = if item_ids.is_empty() {
+            all_items = self.find_items(
+                None, None, None, SortDirection::Asc, None, u32::MAX)?;
+            all_items.iter().map(|item| item.id.as_str()).collect()
+        } else {
+            item_ids.to_vec()
+        };
+        // the server has no equivalent, so this is a request per item
+        let mut result = HashMap::<String, Vec<StoredOcc>>::new();
+        for item_id in item_ids {
+            let item_occs = self.find_occs(
+                &[item_id], start, end, sort, None, max_per_item)?;
+            result.extend(item_occs);
+        }
+        Ok(result)
+    }
+
+    fn find_progress_entries(
+        &self,
+        occ_ids: &[&str],
+        sort: SortDirection,
+        max_results: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredProgressEntry>>> {
+        let query = [("sort", sort_query(sort))];
+        let mut entries = vec![];
+        for occ_id in occ_ids {
+            let occ_entries: Option<Vec<StoredProgressEntry>> = self.get(
+                &format!("/occ/{occ_id}/entries"), &query)?;
+            entries.extend(occ_entries.unwrap_or_default());
+        }
+        sort_results(&mut entries, sort, max_results,
+                     |entry| (entry.entry.date, &entry.id));
+
+        let mut result = HashMap::<String, Vec<StoredProgressEntry>>::new();
+        for entry in entries {
+            result.entry(entry.occ_id.clone()).or_default().push(entry);
+        }
+        Ok(result)
+    }
+
+    fn get_progress_entries(&self, ids: &[&str])
+    -> DbResults<StoredProgressEntry> {
+        unsupported("getting progress entries by ID")
+    }
+
+    fn find_alerts(&self, occ_ids: &[&str])
+    -> DbResult<HashMap<String, Vec<StoredAlert>>> {
+        unsupported("finding alerts")
+    }
+
+    fn get_alerts(&self, ids: &[&str]) -> DbResults<StoredAlert> {
+        self.get_each(ids, |id| format!("/alert/{id}"))
+    }
+
+    fn find_unacked_alerts(&self, date: OccDate) -> DbResults<StoredAlert> {
+        unsupported("finding alerts")
+    }
+
+    fn find_last_digest(&self) -> DbResult<Option<OccDate>> {
+        unsupported("finding digests")
+    }
+
+    fn find_revisions(&self, after: Option<u64>, max_results: u32)
+    -> DbResults<Revision> {
+        unsupported("finding changes")
+    }
+
+    fn find_milestones(&self, item_id: Option<&str>)
+    -> DbResults<StoredMilestone> {
+        let query = item_id.map(|id| ("item", id.to_owned()))
+            .into_iter()
+            .collect::<Vec<_>>();
+        Ok(self.get("/milestone", &query)?.unwrap_or_default())
+    }
+
+    fn find_audit_entries(&self, item_id: &str) -> DbResults<AuditEntry> {
+        unsupported("finding changes")
+    }
+
+    fn find_users(&self, name: Option<&str>) -> DbResults<StoredUser> {
+        unsupported("finding users")
+    }
+
+    fn count_occs_by_progress(
+        &self,
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        now: OccDate,
+    ) -> DbResults<OccProgressCount> {
+        unsupported("counting occurrences")
+    }
+
+    fn backup(&self, path: &Path) -> DbResult<()> {
+        unsupported("backing up")
+    }
+
+    fn restore(&mut self, path: &Path) -> DbResult<()> {
+        unsupported("restoring")
+    }
+
+    /// The server is responsible for its database, so this only checks that
+    /// the server can be used.
+    fn check(&self) -> DbResult<Vec<String>> {
+        let query = [("limit", "1".to_owned())];
+        Ok(match self.get::<Vec<StoredItem>>("/item", &query) {
+            Ok(Some(_)) => vec![],
+            Ok(None) => vec![format!("no API found at {}", self.url)],
+            Err(e) => vec![format!("can't use the server: {e}")],
+        })
+    }
+}
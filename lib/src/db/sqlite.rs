@@ -2,11 +2,16 @@
 
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
-use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+use rusqlite::backup::Progress;
 use crate::types::OccDate;
-use crate::db::{ConfigId, DbResult, DbResults, DbWriteResult, DbUpdate, IdToken,
-                SortDirection, StoredConfig, StoredItem, StoredOcc, UpdateId};
+use crate::util::clock::SharedClock;
+use crate::db::{AuditChange, AuditEntry, ConfigId, DbResult, DbResults,
+                DbWriteResult, DbUpdate, IdToken, ObjectId, OccProgressCount,
+                Revision, SortDirection, StoredAlert, StoredConfig,
+                StoredItem, StoredMilestone, StoredOcc, StoredProgressEntry,
+                StoredUser, UpdateId};
 
 mod dbtypes;
 mod fromdb;
@@ -16,18 +21,36 @@ mod write;
 
 /// SQLite [`Db`](crate::db::Db) implementation.
 #[derive(Debug)]
-pub struct Db { conn: Connection }
+pub struct Db {
+    conn: Connection,
+    /// ID of the current user.
+    owner: Option<dbtypes::Id>,
+    /// Directory containing schema files, used to upgrade restored backups.
+    schema_path: PathBuf,
+    /// Used for the dates objects are created and updated.
+    clock: SharedClock,
+}
 
 /// Initialise the database schema, reading SQL files from the directory given
 /// by `schema_path`.
-fn init_schema(conn: &Connection, schema_path: &Path) -> DbResult<()> {
+///
+/// The number of files applied is stored as the database's `user_version`, and
+/// files which have already been applied are skipped.
+fn init_schema(conn: &mut Connection, schema_path: &Path) -> DbResult<()> {
+    let applied: usize = fromdb::internal_err(
+        conn.query_row("PRAGMA user_version", [], |r| r.get(0)))?;
     dbtypes::SCHEMA_FILES.iter()
-        .try_for_each(|filename| {
+        .enumerate()
+        .skip(applied)
+        .try_for_each(|(i, filename)| {
             let path = schema_path.join(filename);
             let sql = fs::read_to_string(&path)
                 .map_err(|e| format!("error reading schema file ({}): {e}",
                                      path.display()))?;
-            conn.execute_batch(&sql)
+            let tx = fromdb::internal_err(conn.transaction())?;
+            let version_sql = format!("PRAGMA user_version = {};", i + 1);
+            tx.execute_batch(&format!("{sql};\n{version_sql}"))
+                .and_then(|_| tx.commit())
                 .map_err(|e| format!(
                     "error executing schema file ({}): {e}",
                     path.display()))
@@ -35,7 +58,9 @@ fn init_schema(conn: &Connection, schema_path: &Path) -> DbResult<()> {
 }
 
 /// Connect to the database and perform any required initialisation.
-pub fn open(db_path: &Path, schema_path: &Path)
+///
+/// Dates objects are created and updated are taken from the `clock`.
+pub fn open(db_path: &Path, schema_path: &Path, clock: SharedClock)
 -> DbResult<impl crate::db::Db> {
     let db_path_parent = db_path.parent()
         .map(|p| if p.as_os_str().is_empty() { Path::new(".") } else { p })
@@ -44,12 +69,36 @@ pub fn open(db_path: &Path, schema_path: &Path)
     fs::create_dir_all(db_path_parent)
         .map_err(|e| format!("error creating directory ({}): {e}",
                              db_path_parent.display()))?;
-    let conn = Connection::open(db_path)
+    let mut conn = Connection::open(db_path)
         .map_err(|e| format!("error opening database ({}): {e}",
                              db_path.display()))?;
     fromdb::internal_err(rusqlite::vtab::array::load_module(&conn))?;
-    init_schema(&conn, schema_path)?;
-    Ok(Db { conn })
+    init_schema(&mut conn, schema_path)?;
+    Ok(Db {
+        conn,
+        owner: None,
+        schema_path: schema_path.to_owned(),
+        clock,
+    })
+}
+
+/// Check that the file at `path` is a backup which can be restored.
+fn check_backup(path: &Path) -> DbResult<()> {
+    let conn = Connection::open_with_flags(
+        path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("error opening backup ({}): {e}",
+                             path.display()))?;
+    let version: usize = conn
+        .query_row("PRAGMA user_version", [], |r| r.get(0))
+        .map_err(|e| format!("invalid backup ({}): {e}", path.display()))?;
+    if version == 0 {
+        Err(format!("invalid backup ({}): no schema", path.display()))
+    } else if version > dbtypes::SCHEMA_FILES.len() {
+        Err(format!("invalid backup ({}): made by a newer version",
+                    path.display()))
+    } else {
+        Ok(())
+    }
 }
 
 /// Turn a token or ID into an ID, by mapping any token via `ids_map`.
@@ -69,56 +118,250 @@ fn resolve_update_id<'a>(
     }
 }
 
+/// Object created by an update.
+struct Created {
+    id_token: IdToken,
+    id: String,
+    /// Whether a new row was inserted, rather than an existing object updated
+    /// (see [`UpsertItem`](DbUpdate::UpsertItem)).
+    inserted: bool,
+}
+
+impl Created {
+    fn new(id_token: IdToken, id: String) -> Created {
+        Created { id_token, id, inserted: true }
+    }
+}
+
 /// Run a single `update` against the database.
 ///
 /// `ids_map` provides IDs for all objects created so far in this write.
+/// Objects are owned by `owner`, and `now` is the date of the write.
 fn write_update(
     conn: &Connection,
+    owner: Option<dbtypes::Id>,
     ids_map: &HashMap<IdToken, String>,
     update: &DbUpdate,
-) -> DbResult<Option<(IdToken, String)>> {
+    now: OccDate,
+) -> DbResult<Option<Created>> {
     match update {
         DbUpdate::CreateItem { id_token, item } => {
-            write::create_item(conn, item)
-                .map(|id| Some((*id_token, id)))
+            write::create_item(conn, owner, item, now)
+                .map(|id| Some(Created::new(*id_token, id)))
+        }
+        DbUpdate::UpsertItem { id_token, source, external_id, item } => {
+            write::upsert_item(
+                conn, owner, source, external_id, item, now)
+                .map(|(id, inserted)| {
+                    Some(Created { id_token: *id_token, id, inserted })
+                })
         }
         DbUpdate::UpdateItem(item) => {
-            write::update_item(conn, item).map(|_| None)
+            write::update_item(conn, owner, item, now).map(|_| None)
         }
         DbUpdate::DeleteItem { id } => {
-            write::delete_item(conn, id).map(|_| None)
+            write::delete_item(conn, owner, id).map(|_| None)
         }
         DbUpdate::SetConfig(config) => {
-            write::set_config(conn, config).map(|_| None)
+            write::set_config(conn, owner, config).map(|_| None)
         }
         DbUpdate::DeleteConfig { id: config_id } => {
-            write::delete_config(conn, config_id).map(|_| None)
+            write::delete_config(conn, owner, config_id).map(|_| None)
         }
         DbUpdate::CreateOcc { id_token, item_id, occ } => {
             let item_id = resolve_update_id(ids_map, item_id)?;
-            write::create_occ(conn, item_id, occ)
-                .map(|id| Some((*id_token, id)))
+            write::create_occ(conn, owner, item_id, occ)
+                .map(|id| Some(Created::new(*id_token, id)))
+        }
+        DbUpdate::UpdateOcc(occ) => {
+            write::update_occ(conn, owner, occ).map(|_| None)
+        }
+        DbUpdate::DeleteOcc { id } => {
+            write::delete_occ(conn, owner, id).map(|_| None)
+        }
+        DbUpdate::CreateProgressEntry { id_token, occ_id, entry } => {
+            let occ_id = resolve_update_id(ids_map, occ_id)?;
+            write::create_progress_entry(conn, owner, occ_id, entry)
+                .map(|id| Some(Created::new(*id_token, id)))
+        }
+        DbUpdate::DeleteProgressEntry { id } => {
+            write::delete_progress_entry(conn, owner, id).map(|_| None)
+        }
+        DbUpdate::CreateAlert { id_token, occ_id, alert } => {
+            let occ_id = resolve_update_id(ids_map, occ_id)?;
+            write::create_alert(conn, owner, occ_id, alert)
+                .map(|id| Some(Created::new(*id_token, id)))
+        }
+        DbUpdate::UpdateAlert(alert) => {
+            write::update_alert(conn, owner, alert).map(|_| None)
+        }
+        DbUpdate::CreateDigest { id_token, sent } => {
+            write::create_digest(conn, owner, *sent)
+                .map(|id| Some(Created::new(*id_token, id)))
+        }
+        DbUpdate::CreateUser { id_token, user } => {
+            write::create_user(conn, user)
+                .map(|id| Some(Created::new(*id_token, id)))
+        }
+        DbUpdate::CreateMilestone { id_token, item_id, milestone } => {
+            let item_id = resolve_update_id(ids_map, item_id)?;
+            write::create_milestone(conn, owner, item_id, milestone)
+                .map(|id| Some(Created::new(*id_token, id)))
+        }
+    }
+}
+
+/// Determine the item changed by `update` and the change to record in its audit
+/// log, if it's a change to an item or to one of its configs.  `created` is
+/// the item created or upserted by the update, if any.
+///
+/// This must be called straight after writing `update`.  Updates which didn't
+/// change anything aren't recorded.  When an item is created, any audit log
+/// left by a deleted item with the same ID is removed.
+fn audit_change(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    update: &DbUpdate,
+    created: Option<&Created>,
+) -> DbResult<Option<(String, AuditChange)>> {
+    if conn.changes() == 0 {
+        return Ok(None);
+    }
+    let config_item_id = |config_id: &ConfigId| match config_id {
+        ConfigId::Item { id } => Ok(Some(id.clone())),
+        ConfigId::Occ { id } => read::get_occs_item_ids(
+            conn, owner, todb::multi(todb::id, &[id.as_str()])?)
+            .map(|mut item_ids| item_ids.remove(id)),
+        _ => Ok(None),
+    };
+    let (item_id, change) = match update {
+        DbUpdate::CreateItem { item, .. } |
+        DbUpdate::UpsertItem { item, .. } => {
+            let Some(created) = created else { return Ok(None) };
+            (created.id.clone(), if created.inserted {
+                AuditChange::CreateItem((*item).clone())
+            } else {
+                AuditChange::UpdateItem((*item).clone())
+            })
+        }
+        DbUpdate::UpdateItem(item) => {
+            (item.id.clone(), AuditChange::UpdateItem(item.item.clone()))
+        }
+        DbUpdate::DeleteItem { id } => {
+            ((*id).to_owned(), AuditChange::DeleteItem)
+        }
+        DbUpdate::SetConfig(config) => {
+            let Some(item_id) = config_item_id(&config.id)? else {
+                return Ok(None)
+            };
+            (item_id, AuditChange::SetConfig((*config).clone()))
+        }
+        DbUpdate::DeleteConfig { id } => {
+            let Some(item_id) = config_item_id(id)? else { return Ok(None) };
+            (item_id, AuditChange::DeleteConfig { id: id.clone() })
+        }
+        _ => return Ok(None),
+    };
+
+    let item_dbid = todb::id(&item_id)?;
+    match &change {
+        AuditChange::CreateItem(_) => {
+            write::delete_audit_entries(conn, owner, item_dbid)?;
+        }
+        AuditChange::UpdateItem(item) => {
+            let last = read::find_audit_entries(conn, owner, item_dbid)?
+                .into_iter()
+                .rev()
+                .find_map(|entry| match entry.change {
+                    AuditChange::CreateItem(item) |
+                    AuditChange::UpdateItem(item) => Some(item),
+                    _ => None,
+                });
+            if last.as_ref() == Some(item) {
+                return Ok(None);
+            }
+        }
+        _ => {}
+    }
+    Ok(Some((item_id, change)))
+}
+
+/// Determine the object changed by `update`, and whether it was deleted, if
+/// it's an object whose changes are recorded.  `id` is the ID of the object
+/// created by the update, if any.
+fn changed_object(update: &DbUpdate, id: Option<&str>)
+-> Option<(ObjectId, bool)> {
+    let created = |object: fn(String) -> ObjectId| {
+        id.map(|id| (object(id.to_owned()), false))
+    };
+    match update {
+        DbUpdate::CreateItem { .. } | DbUpdate::UpsertItem { .. } => {
+            created(|id| ObjectId::Item { id })
+        }
+        DbUpdate::UpdateItem(item) => {
+            Some((ObjectId::Item { id: item.id.clone() }, false))
+        }
+        DbUpdate::DeleteItem { id } => {
+            Some((ObjectId::Item { id: (*id).to_owned() }, true))
+        }
+        DbUpdate::SetConfig(config) => {
+            Some((ObjectId::Config { id: config.id.clone() }, false))
         }
+        DbUpdate::DeleteConfig { id } => {
+            Some((ObjectId::Config { id: id.clone() }, true))
+        }
+        DbUpdate::CreateOcc { .. } => created(|id| ObjectId::Occ { id }),
         DbUpdate::UpdateOcc(occ) => {
-            write::update_occ(conn, occ).map(|_| None)
+            Some((ObjectId::Occ { id: occ.id.clone() }, false))
         }
         DbUpdate::DeleteOcc { id } => {
-            write::delete_occ(conn, id).map(|_| None)
+            Some((ObjectId::Occ { id: (*id).to_owned() }, true))
+        }
+        DbUpdate::CreateProgressEntry { .. } => {
+            created(|id| ObjectId::ProgressEntry { id })
         }
+        DbUpdate::DeleteProgressEntry { id } => {
+            Some((ObjectId::ProgressEntry { id: (*id).to_owned() }, true))
+        }
+        DbUpdate::CreateAlert { .. } |
+        DbUpdate::UpdateAlert(_) |
+        DbUpdate::CreateDigest { .. } |
+        DbUpdate::CreateUser { .. } |
+        DbUpdate::CreateMilestone { .. } => None,
     }
 }
 
 impl crate::db::Db for Db {
+    fn set_user(&mut self, id: Option<&str>) -> DbResult<()> {
+        self.owner = id.map(todb::id).transpose()?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all,
+                          fields(updates = updates.len()))]
     fn write(&mut self, updates: &[&DbUpdate]) -> DbWriteResult {
         let mut ids_map: HashMap<IdToken, String> = HashMap::new();
+        let now = self.clock.now();
         let tx = self.conn.transaction()
             .map_err(|e| format!("error writing to database: {e}"))?;
 
         for update in updates {
-            write_update(&tx, &ids_map, update)?
-                .and_then(|id_map| {
-                    ids_map.insert(id_map.0, id_map.1)
-                });
+            let created = write_update(
+                &tx, self.owner, &ids_map, update, now)?;
+            let id = created.as_ref().map(|created| created.id.as_str());
+            if let Some((item_id, change)) = audit_change(
+                &tx, self.owner, update, created.as_ref())?
+            {
+                write::record_audit_entry(
+                    &tx, self.owner, &item_id, &change, now)?;
+            }
+            if let Some((object, deleted)) = changed_object(update, id) {
+                write::record_revision(
+                    &tx, self.owner, &object, deleted, now)?;
+            }
+            if let Some(created) = created {
+                ids_map.insert(created.id_token, created.id);
+            }
         }
 
         tx.commit()
@@ -129,24 +372,43 @@ impl crate::db::Db for Db {
     fn find_items(
         &self,
         active: Option<bool>,
+        category: Option<&str>,
         start: Option<OccDate>,
         sort: SortDirection,
+        after_id: Option<&str>,
         max_results: u32,
     ) -> DbResults<StoredItem> {
-        read::find_items(&self.conn, active, start, sort, max_results)
+        let after_dbid = after_id.map(todb::id).transpose()?;
+        read::find_items(&self.conn, self.owner, active, category, start, sort,
+                         after_dbid, max_results)
     }
 
     fn get_items(&self, ids: &[&str]) -> DbResults<StoredItem> {
-        read::get_items(&self.conn, todb::multi(todb::id, ids)?)
+        read::get_items(&self.conn, self.owner, todb::multi(todb::id, ids)?)
+    }
+
+    fn find_external_ids(&self, source: &str)
+    -> DbResult<HashMap<String, String>> {
+        read::find_external_ids(&self.conn, self.owner, source)
     }
 
     fn get_configs(&self, ids: &[&ConfigId])
     -> DbResults<StoredConfig> {
-        read::get_configs(&self.conn, ids)
+        read::get_configs(&self.conn, self.owner, ids)
+    }
+
+    fn find_configs(&self) -> DbResults<StoredConfig> {
+        read::find_configs(&self.conn, self.owner)
     }
 
     fn get_occs(&self, ids: &[&str]) -> DbResults<StoredOcc> {
-        read::get_occs(&self.conn, todb::multi(todb::id, ids)?)
+        read::get_occs(&self.conn, self.owner, todb::multi(todb::id, ids)?)
+    }
+
+    fn get_occs_item_ids(&self, ids: &[&str])
+    -> DbResult<HashMap<String, String>> {
+        read::get_occs_item_ids(
+            &self.conn, self.owner, todb::multi(todb::id, ids)?)
     }
 
     fn find_occs(
@@ -155,9 +417,107 @@ impl crate::db::Db for Db {
         start: Option<OccDate>,
         end: Option<OccDate>,
         sort: SortDirection,
+        after_id: Option<&str>,
         max_results: u32,
     ) -> DbResult<HashMap<String, Vec<StoredOcc>>> {
         let item_dbids = todb::multi(todb::id, item_ids)?;
-        read::find_occs(&self.conn, item_dbids, start, end, sort, max_results)
+        let after_dbid = after_id.map(todb::id).transpose()?;
+        read::find_occs(&self.conn, self.owner, item_dbids, start, end, sort,
+                        after_dbid, max_results)
+    }
+
+    fn find_occs_per_item(
+        &self,
+        item_ids: &[&str],
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        sort: SortDirection,
+        max_per_item: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredOcc>>> {
+        let item_dbids = todb::multi(todb::id, item_ids)?;
+        read::find_occs_per_item(&self.conn, self.owner, item_dbids, start,
+                                 end, sort, max_per_item)
+    }
+
+    fn find_progress_entries(
+        &self,
+        occ_ids: &[&str],
+        sort: SortDirection,
+        max_results: u32,
+    ) -> DbResult<HashMap<String, Vec<StoredProgressEntry>>> {
+        let occ_dbids = todb::multi(todb::id, occ_ids)?;
+        read::find_progress_entries(
+            &self.conn, self.owner, occ_dbids, sort, max_results)
+    }
+
+    fn get_progress_entries(&self, ids: &[&str])
+    -> DbResults<StoredProgressEntry> {
+        read::get_progress_entries(
+            &self.conn, self.owner, todb::multi(todb::id, ids)?)
+    }
+
+    fn find_alerts(&self, occ_ids: &[&str])
+    -> DbResult<HashMap<String, Vec<StoredAlert>>> {
+        let occ_dbids = todb::multi(todb::id, occ_ids)?;
+        read::find_alerts(&self.conn, self.owner, occ_dbids)
+    }
+
+    fn get_alerts(&self, ids: &[&str]) -> DbResults<StoredAlert> {
+        read::get_alerts(
+            &self.conn, self.owner, todb::multi(todb::id, ids)?)
+    }
+
+    fn find_unacked_alerts(&self, date: OccDate) -> DbResults<StoredAlert> {
+        read::find_unacked_alerts(&self.conn, self.owner, date)
+    }
+
+    fn find_last_digest(&self) -> DbResult<Option<OccDate>> {
+        read::find_last_digest(&self.conn, self.owner)
+    }
+
+    fn find_revisions(&self, after: Option<u64>, max_results: u32)
+    -> DbResults<Revision> {
+        read::find_revisions(&self.conn, self.owner, after, max_results)
+    }
+
+    fn find_milestones(&self, item_id: Option<&str>)
+    -> DbResults<StoredMilestone> {
+        read::find_milestones(
+            &self.conn, self.owner, item_id.map(todb::id).transpose()?)
+    }
+
+    fn find_audit_entries(&self, item_id: &str) -> DbResults<AuditEntry> {
+        read::find_audit_entries(&self.conn, self.owner, todb::id(item_id)?)
+    }
+
+    fn find_users(&self, name: Option<&str>) -> DbResults<StoredUser> {
+        read::find_users(&self.conn, name)
+    }
+
+    fn count_occs_by_progress(
+        &self,
+        start: Option<OccDate>,
+        end: Option<OccDate>,
+        now: OccDate,
+    ) -> DbResults<OccProgressCount> {
+        read::count_occs_by_progress(&self.conn, self.owner, start, end, now)
+    }
+
+    fn backup(&self, path: &Path) -> DbResult<()> {
+        self.conn.backup(DatabaseName::Main, path, None)
+            .map_err(|e| format!("error writing backup ({}): {e}",
+                                 path.display()))
+    }
+
+    fn restore(&mut self, path: &Path) -> DbResult<()> {
+        check_backup(path)?;
+        self.conn.restore(DatabaseName::Main, path, None::<fn(Progress)>)
+            .map_err(|e| format!("error restoring backup ({}): {e}",
+                                 path.display()))?;
+        init_schema(&mut self.conn, &self.schema_path)
+    }
+
+    fn check(&self) -> DbResult<Vec<String>> {
+        read::check(&self.conn)
     }
 }
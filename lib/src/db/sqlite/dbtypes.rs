@@ -1,7 +1,21 @@
 //! General data and types for this module.
 
-/// Names of SQL files read to initialise database schema.
-pub const SCHEMA_FILES: [&str; 1] = ["00-init.sql"];
+/// Names of SQL files read to initialise database schema, in the order they
+/// are applied.
+pub const SCHEMA_FILES: [&str; 12] = [
+    "00-init.sql",
+    "01-progress.sql",
+    "02-users.sql",
+    "03-snooze.sql",
+    "04-progress-notes.sql",
+    "05-alerts.sql",
+    "06-alert-state.sql",
+    "07-digests.sql",
+    "08-external-ids.sql",
+    "09-revisions.sql",
+    "10-audit.sql",
+    "11-milestones.sql",
+];
 
 /// Unique ID of an object stored in the database, internal to
 /// [`sqlite`](crate::db::sqlite).
@@ -12,4 +26,12 @@ pub mod table {
     pub const ITEMS: &str = "tbl_items";
     pub const OCCS: &str = "tbl_occs";
     pub const CONFIGS: &str = "tbl_configs";
+    pub const PROGRESS: &str = "tbl_progress";
+    pub const USERS: &str = "tbl_users";
+    pub const ALERTS: &str = "tbl_alerts";
+    pub const DIGESTS: &str = "tbl_digests";
+    pub const EXTERNAL_IDS: &str = "tbl_external_ids";
+    pub const REVISIONS: &str = "tbl_revisions";
+    pub const AUDIT: &str = "tbl_audit";
+    pub const MILESTONES: &str = "tbl_milestones";
 }
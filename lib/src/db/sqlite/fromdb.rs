@@ -2,8 +2,11 @@
 
 use std::str::FromStr;
 use rusqlite::Row;
-use crate::types::{Item, Config, ItemType, Occ, OccDate};
-use crate::db::{ConfigId, DbResult, StoredItem, StoredConfig, StoredOcc};
+use crate::types::{Alert, AlertKind, Item, Config, ItemType, Milestone,
+                   MilestoneKind, Occ, OccDate, ProgressEntry, User};
+use crate::db::{AuditEntry, ConfigId, DbResult, ObjectId, OccProgressCount,
+                Revision, StoredAlert, StoredItem, StoredConfig,
+                StoredMilestone, StoredOcc, StoredProgressEntry, StoredUser};
 use super::dbtypes;
 
 /// Value of the `id_all` occurrence column that means [ConfigId::All].
@@ -88,9 +91,19 @@ pub fn occ_date(r: &Row, i: usize) -> DbResult<OccDate> {
         .ok_or("read invalid date value (column index {i}): {epoch_s}".to_owned())
 }
 
+/// Convert optional occurrence date from database format.
+pub fn opt_occ_date(r: &Row, i: usize) -> DbResult<Option<OccDate>> {
+    let epoch_s: Option<i64> = row_get(r, i)?;
+    epoch_s.map(|epoch_s| {
+        chrono::DateTime::from_timestamp(epoch_s, 0)
+            .ok_or(format!("read invalid date value (column index {i}): \
+                            {epoch_s}"))
+    }).transpose()
+}
+
 /// For use with [`occ_data`].
 pub const OCCS_SQL: &str = "id, item_id, active, start_date, end_date, \
-                            task_completion_progress";
+                            task_completion_progress, snoozed_until";
 /// Name of the column stored occurrence start date.
 pub const OCCS_START_COL: &str = "start_date";
 
@@ -106,6 +119,7 @@ pub fn occ_data(r: &Row) -> DbResult<(String, StoredOcc)> {
             start: occ_date(r, 3)?,
             end: occ_date(r, 4)?,
             task_completion_progress: row_get(r, 5)?,
+            snoozed_until: opt_occ_date(r, 6)?,
         },
     };
     Ok((item_id, occ))
@@ -118,6 +132,97 @@ pub fn occ(r: &Row) -> DbResult<StoredOcc> {
     Ok(occ_data(r)?.1)
 }
 
+/// For use with [`progress_entry`].
+pub const PROGRESS_SQL: &str = "id, occ_id, date, amount, note";
+/// Name of the column storing progress entry date.
+pub const PROGRESS_DATE_COL: &str = "date";
+
+/// Convert progress entry from database result row.
+///
+/// Expected SELECTed columns are given by [`PROGRESS_SQL`].
+pub fn progress_entry(r: &Row) -> DbResult<StoredProgressEntry> {
+    Ok(StoredProgressEntry {
+        id: id(row_get(r, 0)?),
+        occ_id: id(row_get(r, 1)?),
+        entry: ProgressEntry {
+            date: occ_date(r, 2)?,
+            amount: row_get(r, 3)?,
+            note: row_get(r, 4)?,
+        },
+    })
+}
+
+/// Convert alert kind from database format.
+pub fn alert_kind(kind_str: &str) -> DbResult<AlertKind> {
+    AlertKind::from_str(kind_str)
+        .map_err(|e| format!(
+            "error reading alert kind from database ({kind_str}): {e}"))
+}
+
+/// For use with [`alert`].
+pub const ALERTS_SQL: &str = "id, occ_id, kind, sent_date, acked_date, \
+                              snoozed_until";
+/// Name of the column storing alert sent date.
+pub const ALERTS_SENT_COL: &str = "sent_date";
+
+/// Convert alert from database result row.
+///
+/// Expected SELECTed columns are given by [`ALERTS_SQL`].
+pub fn alert(r: &Row) -> DbResult<StoredAlert> {
+    let kind: String = row_get(r, 2)?;
+    Ok(StoredAlert {
+        id: id(row_get(r, 0)?),
+        occ_id: id(row_get(r, 1)?),
+        alert: Alert {
+            kind: alert_kind(&kind)?,
+            sent: occ_date(r, 3)?,
+            acked: opt_occ_date(r, 4)?,
+            snoozed_until: opt_occ_date(r, 5)?,
+        },
+    })
+}
+
+/// Convert milestone kind from database format.
+pub fn milestone_kind(kind_str: &str) -> DbResult<MilestoneKind> {
+    MilestoneKind::from_str(kind_str)
+        .map_err(|e| format!(
+            "error reading milestone kind from database ({kind_str}): {e}"))
+}
+
+/// For use with [`milestone`].
+pub const MILESTONES_SQL: &str = "id, item_id, kind, threshold, year, \
+                                  reached_date";
+
+/// Convert milestone from database result row.
+///
+/// Expected SELECTed columns are given by [`MILESTONES_SQL`].
+pub fn milestone(r: &Row) -> DbResult<StoredMilestone> {
+    let kind: String = row_get(r, 2)?;
+    Ok(StoredMilestone {
+        id: id(row_get(r, 0)?),
+        item_id: id(row_get(r, 1)?),
+        milestone: Milestone {
+            kind: milestone_kind(&kind)?,
+            threshold: row_get(r, 3)?,
+            year: row_get(r, 4)?,
+            reached: occ_date(r, 5)?,
+        },
+    })
+}
+
+/// Convert occurrence progress count from database result row.
+///
+/// Expected SELECTed columns are `item_id, task_completion_progress, ended,
+/// count`.
+pub fn occ_progress_count(r: &Row) -> DbResult<OccProgressCount> {
+    Ok(OccProgressCount {
+        item_id: id(row_get(r, 0)?),
+        progress: row_get(r, 1)?,
+        ended: row_get(r, 2)?,
+        count: row_get(r, 3)?,
+    })
+}
+
 /// For use with [`config`].
 pub const CONFIGS_SQL: &str = "id_all, id_type, id_category, id_item, id_occ, \
                                config_blob";
@@ -147,8 +252,54 @@ pub fn config(r: &Row) -> DbResult<StoredConfig> {
     } else if let Some(id) = id_occ {
         Ok(ConfigId::Occ { id })
     } else {
-        Err("".to_owned())
+        Err("read config with no ID from database".to_owned())
     }?;
 
     Ok(StoredConfig { id, config })
 }
+
+/// For use with [`user`].
+pub const USERS_SQL: &str = "id, name";
+
+/// Convert user from database result row.
+///
+/// Expected SELECTed columns are given by [`USERS_SQL`].
+pub fn user(r: &Row) -> DbResult<StoredUser> {
+    Ok(StoredUser {
+        id: id(row_get(r, 0)?),
+        user: User { name: row_get(r, 1)? },
+    })
+}
+
+/// For use with [`revision`].
+pub const REVISIONS_SQL: &str = "rev, object_blob, deleted, change_date";
+
+/// Convert revision from database result row.
+///
+/// Expected SELECTed columns are given by [`REVISIONS_SQL`].
+pub fn revision(r: &Row) -> DbResult<Revision> {
+    let rev: i64 = row_get(r, 0)?;
+    let bytes: Vec<u8> = row_get(r, 1)?;
+    let object: ObjectId = serde(&bytes)?;
+    Ok(Revision {
+        rev: rev as u64,
+        object,
+        deleted: row_get(r, 2)?,
+        date: occ_date(r, 3)?,
+    })
+}
+
+/// For use with [`audit_entry`].
+pub const AUDIT_SQL: &str = "item_id, change_blob, change_date";
+
+/// Convert audit log entry from database result row.
+///
+/// Expected SELECTed columns are given by [`AUDIT_SQL`].
+pub fn audit_entry(r: &Row) -> DbResult<AuditEntry> {
+    let bytes: Vec<u8> = row_get(r, 1)?;
+    Ok(AuditEntry {
+        item_id: id(row_get(r, 0)?),
+        change: serde(&bytes)?,
+        date: occ_date(r, 2)?,
+    })
+}
@@ -3,69 +3,114 @@
 use std::collections::HashMap;
 use std::rc::Rc;
 use rusqlite::{Connection, named_params, ToSql, types::Value};
-use crate::db::{ConfigId, DbResult, DbResults, SortDirection, StoredConfig,
-                StoredItem, StoredOcc};
+use crate::db::{AuditEntry, ConfigId, DbResult, DbResults, OccProgressCount,
+                Revision, SortDirection, StoredAlert, StoredConfig,
+                StoredItem, StoredMilestone, StoredOcc, StoredProgressEntry,
+                StoredUser};
 use crate::types::{ItemType, OccDate};
-use super::dbtypes::table::{CONFIGS, ITEMS, OCCS};
-use super::fromdb::{self, CONFIG_ID_ALL_DB_VALUE, CONFIGS_SQL,
-                    ITEMS_CREATED_COL, ITEMS_SQL, OCCS_SQL, OCCS_START_COL};
+use super::dbtypes::{self, table::{ALERTS, AUDIT, CONFIGS, DIGESTS,
+                                   EXTERNAL_IDS, ITEMS, MILESTONES, OCCS,
+                                   PROGRESS, REVISIONS, USERS}};
+use super::fromdb::{self, ALERTS_SENT_COL, ALERTS_SQL, AUDIT_SQL,
+                    CONFIG_ID_ALL_DB_VALUE, CONFIGS_SQL, ITEMS_CREATED_COL,
+                    ITEMS_SQL, MILESTONES_SQL, OCCS_SQL, OCCS_START_COL,
+                    PROGRESS_DATE_COL, PROGRESS_SQL, REVISIONS_SQL,
+                    USERS_SQL};
 use super::todb;
 
+/// Build a `WHERE` clause requiring all of `exprs` to be true, or nothing if
+/// there are no `exprs`.
+fn where_sql(exprs: &[String]) -> String {
+    if exprs.is_empty() {
+        "".to_owned()
+    } else {
+        format!("WHERE {}", exprs.join(" AND "))
+    }
+}
+
+/// Get SQL for ordering by `sort`, and the operator for comparing with the
+/// value to continue after.
+fn order_sql(sort: SortDirection) -> (&'static str, &'static str) {
+    match sort {
+        SortDirection::Asc => ("ASC", ">"),
+        SortDirection::Desc => ("DESC", "<"),
+    }
+}
+
 /// See [Db::find_items](crate::db::Db::find_items).
+#[allow(clippy::too_many_arguments)]
 pub fn find_items(
     conn: &Connection,
+    owner: Option<dbtypes::Id>,
     active: Option<bool>,
+    category: Option<&str>,
     start: Option<OccDate>,
     sort: SortDirection,
+    after_dbid: Option<dbtypes::Id>,
     max_results: u32,
 ) -> DbResults<StoredItem> {
-    let mut exprs: Vec<String> = Vec::new();
-    let mut params: Vec<(&str, &dyn ToSql)> = Vec::new();
+    let mut exprs: Vec<String> = vec!["owner IS :owner".to_owned()];
+    let mut params: Vec<(&str, &dyn ToSql)> = vec![(":owner", &owner)];
     let active_value = active.unwrap_or(false);
     if active.is_some() {
         exprs.push("active = :active".to_owned());
         params.push((":active", &active));
     }
+    if category.is_some() {
+        exprs.push("category = :category".to_owned());
+        params.push((":category", &category));
+    }
     let start_db_value = start.map(todb::occ_date).unwrap_or(0);
     if let Some(start) = start {
-        exprs.push("only_occ_end > :min_end".to_owned());
+        exprs.push("(only_occ_end IS NULL OR only_occ_end > :min_end)"
+                   .to_owned());
         params.push((":min_end", &start_db_value));
     }
-    let sort_sql = match sort {
-        SortDirection::Asc => "ASC",
-        SortDirection::Desc => "DESC",
-    };
+    let (sort_sql, after_op) = order_sql(sort);
+    if after_dbid.is_some() {
+        exprs.push(format!("
+            ({ITEMS_CREATED_COL}, id) {after_op}
+                (SELECT {ITEMS_CREATED_COL}, id FROM {ITEMS}
+                 WHERE id = :after_id)"));
+        params.push((":after_id", &after_dbid));
+    }
     params.push((":max_results", &max_results));
 
     fromdb::internal_err_fn(|| {
         let mut stmt = conn.prepare(format!("
-            SELECT {ITEMS_SQL} from {ITEMS} WHERE {}
-            ORDER BY {ITEMS_CREATED_COL} {sort_sql}
+            SELECT {ITEMS_SQL} from {ITEMS} {}
+            ORDER BY {ITEMS_CREATED_COL} {sort_sql}, id {sort_sql}
             LIMIT :max_results
-        ", &exprs.join(", ")).as_ref())?;
+        ", where_sql(&exprs)).as_ref())?;
         let rows = stmt.query_map(&params[..], todb::mapper(fromdb::item))?;
         rows.collect()
     })
 }
 
 /// See [Db::get_items](crate::db::Db::get_items).
-pub fn get_items(conn: &Connection, dbids: Rc<Vec<Value>>)
--> DbResults<StoredItem> {
+pub fn get_items(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    dbids: Rc<Vec<Value>>,
+) -> DbResults<StoredItem> {
     fromdb::internal_err_fn(|| {
         let mut stmt = conn.prepare(format!("
             SELECT {ITEMS_SQL} from {ITEMS}
-            WHERE id IN rarray(:ids)
+            WHERE id IN rarray(:ids) AND owner IS :owner
         ").as_ref())?;
         let rows = stmt.query_map(
-            named_params! { ":ids": dbids },
+            named_params! { ":ids": dbids, ":owner": owner },
             todb::mapper(fromdb::item))?;
         rows.collect()
     })
 }
 
 /// See [Db::get_configs](crate::db::Db::get_configs).
-pub fn get_configs(conn: &Connection, ids: &[&ConfigId])
--> DbResults<StoredConfig> {
+pub fn get_configs(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    ids: &[&ConfigId],
+) -> DbResults<StoredConfig> {
     let mut all: bool = false;
     let mut types: Vec<&ItemType> = Vec::new();
     let mut cats: Vec<&str> = Vec::new();
@@ -82,64 +127,88 @@ pub fn get_configs(conn: &Connection, ids: &[&ConfigId])
         }
     }
 
+    let types_value = todb::multi(
+        |type_| Ok(todb::item_type(type_).to_owned()), &types)?;
+    let cats_value = todb::multi(|c| Ok(c.to_owned()), &cats)?;
+    let item_ids_value = todb::multi(todb::id, &item_ids)?;
+    let occ_ids_value = todb::multi(todb::id, &occ_ids)?;
+
     let mut stmts: Vec<String> = Vec::new();
+    let mut params: Vec<(&str, &dyn ToSql)> = vec![(":owner", &owner)];
     if all {
         stmts.push(format!("
             SELECT {CONFIGS_SQL} from {CONFIGS}
-            WHERE id_all = {CONFIG_ID_ALL_DB_VALUE}
+            WHERE id_all = {CONFIG_ID_ALL_DB_VALUE} AND owner IS :owner
         ").to_owned());
     }
     if !types.is_empty() {
         stmts.push(format!("
             SELECT {CONFIGS_SQL} from {CONFIGS}
-            WHERE id_type IN rarray(:types)
+            WHERE id_type IN rarray(:types) AND owner IS :owner
         ").to_owned());
+        params.push((":types", &types_value));
     }
-    if !types.is_empty() {
+    if !cats.is_empty() {
         stmts.push(format!("
             SELECT {CONFIGS_SQL} from {CONFIGS}
-            WHERE id_category IN rarray(:cats)
+            WHERE id_category IN rarray(:cats) AND owner IS :owner
         ").to_owned());
+        params.push((":cats", &cats_value));
     }
-    if !types.is_empty() {
+    if !item_ids.is_empty() {
         stmts.push(format!("
             SELECT {CONFIGS_SQL} from {CONFIGS}
-            WHERE id_item IN rarray(:item_ids)
+            WHERE id_item IN rarray(:item_ids) AND owner IS :owner
         ").to_owned());
+        params.push((":item_ids", &item_ids_value));
     }
-    if !types.is_empty() {
+    if !occ_ids.is_empty() {
         stmts.push(format!("
             SELECT {CONFIGS_SQL} from {CONFIGS}
-            WHERE id_occ IN rarray(:occ_ids)
+            WHERE id_occ IN rarray(:occ_ids) AND owner IS :owner
         ").to_owned());
+        params.push((":occ_ids", &occ_ids_value));
     }
 
-    let params = named_params! {
-        ":types": todb::multi(
-            |type_| Ok(todb::item_type(type_).to_owned()),
-            &types)?,
-        ":cats": todb::multi(|c| Ok(c.to_owned()), &cats)?,
-        ":item_ids": todb::multi(todb::id, &item_ids)?,
-        ":occ_ids": todb::multi(todb::id, &occ_ids)?,
-    };
+    if stmts.is_empty() {
+        return Ok(vec![])
+    }
     fromdb::internal_err_fn(|| {
         let mut stmt = conn.prepare(&stmts.join(" UNION "))?;
-        let rows = stmt.query_map(params, todb::mapper(fromdb::config))?;
+        let rows = stmt.query_map(&params[..], todb::mapper(fromdb::config))?;
+        rows.collect()
+    })
+}
+
+/// See [Db::find_configs](crate::db::Db::find_configs).
+pub fn find_configs(conn: &Connection, owner: Option<dbtypes::Id>)
+-> DbResults<StoredConfig> {
+    fromdb::internal_err_fn(|| {
+        let mut stmt = conn.prepare(format!("
+            SELECT {CONFIGS_SQL} from {CONFIGS}
+            WHERE owner IS :owner
+        ").as_ref())?;
+        let rows = stmt.query_map(
+            named_params! { ":owner": owner },
+            todb::mapper(fromdb::config))?;
         rows.collect()
     })
 }
 
 /// See [Db::find_occs](crate::db::Db::find_occs).
+#[allow(clippy::too_many_arguments)]
 pub fn find_occs(
     conn: &Connection,
+    owner: Option<dbtypes::Id>,
     item_dbids: Rc<Vec<Value>>,
     start: Option<OccDate>,
     end: Option<OccDate>,
     sort: SortDirection,
+    after_dbid: Option<dbtypes::Id>,
     max_results: u32,
 ) -> DbResult<HashMap<String, Vec<StoredOcc>>> {
-    let mut exprs: Vec<String> = Vec::new();
-    let mut params: Vec<(&str, &dyn ToSql)> = Vec::new();
+    let mut exprs: Vec<String> = vec!["owner IS :owner".to_owned()];
+    let mut params: Vec<(&str, &dyn ToSql)> = vec![(":owner", &owner)];
     if !item_dbids.is_empty() {
         exprs.push("item_id IN rarray(:item_ids)".to_owned());
         params.push((":item_ids", &item_dbids));
@@ -154,20 +223,78 @@ pub fn find_occs(
         exprs.push("start_date < :max_start".to_owned());
         params.push((":max_start", &end_db_value));
     }
-    let sort_sql = match sort {
-        SortDirection::Asc => "ASC",
-        SortDirection::Desc => "DESC",
-    };
+    let (sort_sql, after_op) = order_sql(sort);
+    if after_dbid.is_some() {
+        exprs.push(format!("
+            ({OCCS_START_COL}, id) {after_op}
+                (SELECT {OCCS_START_COL}, id FROM {OCCS}
+                 WHERE id = :after_id)"));
+        params.push((":after_id", &after_dbid));
+    }
     params.push((":max_results", &max_results));
 
 
     let occs: Vec<(String, StoredOcc)> = fromdb::internal_err_fn(|| {
         let mut stmt = conn.prepare(format!("
             SELECT {OCCS_SQL} from {OCCS}
-            WHERE ({})
-            ORDER BY {OCCS_START_COL} {sort_sql}
+            {}
+            ORDER BY {OCCS_START_COL} {sort_sql}, id {sort_sql}
             LIMIT :max_results
-        ", &exprs.join(", ")).as_ref())?;
+        ", where_sql(&exprs)).as_ref())?;
+        let rows = stmt.query_map(&params[..], todb::mapper(fromdb::occ_data))?;
+        rows.collect()
+    })?;
+
+    let mut result = HashMap::<String, Vec<StoredOcc>>::new();
+    for (item_id, occ) in occs {
+        result.entry(item_id).or_default().push(occ);
+    }
+    Ok(result)
+}
+
+/// See [Db::find_occs_per_item](crate::db::Db::find_occs_per_item).
+pub fn find_occs_per_item(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    item_dbids: Rc<Vec<Value>>,
+    start: Option<OccDate>,
+    end: Option<OccDate>,
+    sort: SortDirection,
+    max_per_item: u32,
+) -> DbResult<HashMap<String, Vec<StoredOcc>>> {
+    let mut exprs: Vec<String> = vec!["owner IS :owner".to_owned()];
+    let mut params: Vec<(&str, &dyn ToSql)> = vec![(":owner", &owner)];
+    if !item_dbids.is_empty() {
+        exprs.push("item_id IN rarray(:item_ids)".to_owned());
+        params.push((":item_ids", &item_dbids));
+    }
+    let start_db_value = start.map(todb::occ_date).unwrap_or(0);
+    if let Some(start) = start {
+        exprs.push("end_date > :min_end".to_owned());
+        params.push((":min_end", &start_db_value));
+    }
+    let end_db_value = end.map(todb::occ_date).unwrap_or(0);
+    if let Some(end) = end {
+        exprs.push("start_date < :max_start".to_owned());
+        params.push((":max_start", &end_db_value));
+    }
+    let (sort_sql, _) = order_sql(sort);
+    params.push((":max_per_item", &max_per_item));
+
+    let occs: Vec<(String, StoredOcc)> = fromdb::internal_err_fn(|| {
+        // number each item's occs in order, so the limit applies per item
+        let mut stmt = conn.prepare(format!("
+            SELECT {OCCS_SQL} FROM (
+                SELECT *, ROW_NUMBER() OVER (
+                    PARTITION BY item_id
+                    ORDER BY {OCCS_START_COL} {sort_sql}, id {sort_sql}
+                ) AS item_row
+                FROM {OCCS}
+                {}
+            )
+            WHERE item_row <= :max_per_item
+            ORDER BY item_id, item_row
+        ", where_sql(&exprs)).as_ref())?;
         let rows = stmt.query_map(&params[..], todb::mapper(fromdb::occ_data))?;
         rows.collect()
     })?;
@@ -180,16 +307,361 @@ pub fn find_occs(
 }
 
 /// See [Db::get_occs](crate::db::Db::get_occs).
-pub fn get_occs(conn: &Connection, dbids: Rc<Vec<Value>>)
--> DbResults<StoredOcc> {
+pub fn get_occs(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    dbids: Rc<Vec<Value>>,
+) -> DbResults<StoredOcc> {
     fromdb::internal_err_fn(|| {
         let mut stmt = conn.prepare(format!("
             SELECT {OCCS_SQL} from {OCCS}
-            WHERE id IN rarray(:ids)
+            WHERE id IN rarray(:ids) AND owner IS :owner
         ").as_ref())?;
         let rows = stmt.query_map(
-            named_params! { ":ids": dbids },
+            named_params! { ":ids": dbids, ":owner": owner },
             todb::mapper(fromdb::occ))?;
         rows.collect()
     })
 }
+
+/// See [Db::get_occs_item_ids](crate::db::Db::get_occs_item_ids).
+pub fn get_occs_item_ids(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    dbids: Rc<Vec<Value>>,
+) -> DbResult<HashMap<String, String>> {
+    let occs: Vec<(String, StoredOcc)> = fromdb::internal_err_fn(|| {
+        let mut stmt = conn.prepare(format!("
+            SELECT {OCCS_SQL} from {OCCS}
+            WHERE id IN rarray(:ids) AND owner IS :owner
+        ").as_ref())?;
+        let rows = stmt.query_map(
+            named_params! { ":ids": dbids, ":owner": owner },
+            todb::mapper(fromdb::occ_data))?;
+        rows.collect()
+    })?;
+    Ok(occs.into_iter()
+        .map(|(item_id, occ)| (occ.id, item_id))
+        .collect())
+}
+
+/// See [Db::find_progress_entries](crate::db::Db::find_progress_entries).
+pub fn find_progress_entries(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    occ_dbids: Rc<Vec<Value>>,
+    sort: SortDirection,
+    max_results: u32,
+) -> DbResult<HashMap<String, Vec<StoredProgressEntry>>> {
+    let (sort_sql, _) = order_sql(sort);
+
+    let entries: Vec<StoredProgressEntry> = fromdb::internal_err_fn(|| {
+        let mut stmt = conn.prepare(format!("
+            SELECT {PROGRESS_SQL} from {PROGRESS}
+            WHERE occ_id IN rarray(:occ_ids)
+                AND occ_id IN (SELECT id FROM {OCCS} WHERE owner IS :owner)
+            ORDER BY {PROGRESS_DATE_COL} {sort_sql}, id {sort_sql}
+            LIMIT :max_results
+        ").as_ref())?;
+        let rows = stmt.query_map(
+            named_params! {
+                ":occ_ids": occ_dbids,
+                ":owner": owner,
+                ":max_results": max_results,
+            },
+            todb::mapper(fromdb::progress_entry))?;
+        rows.collect()
+    })?;
+
+    let mut result = HashMap::<String, Vec<StoredProgressEntry>>::new();
+    for entry in entries {
+        result.entry(entry.occ_id.clone()).or_default().push(entry);
+    }
+    Ok(result)
+}
+
+/// See [Db::get_progress_entries](crate::db::Db::get_progress_entries).
+pub fn get_progress_entries(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    dbids: Rc<Vec<Value>>,
+) -> DbResults<StoredProgressEntry> {
+    fromdb::internal_err_fn(|| {
+        let mut stmt = conn.prepare(format!("
+            SELECT {PROGRESS_SQL} from {PROGRESS}
+            WHERE id IN rarray(:ids)
+                AND occ_id IN (SELECT id FROM {OCCS} WHERE owner IS :owner)
+        ").as_ref())?;
+        let rows = stmt.query_map(
+            named_params! { ":ids": dbids, ":owner": owner },
+            todb::mapper(fromdb::progress_entry))?;
+        rows.collect()
+    })
+}
+
+/// See [Db::find_alerts](crate::db::Db::find_alerts).
+pub fn find_alerts(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    occ_dbids: Rc<Vec<Value>>,
+) -> DbResult<HashMap<String, Vec<StoredAlert>>> {
+    let alerts: Vec<StoredAlert> = fromdb::internal_err_fn(|| {
+        let mut stmt = conn.prepare(format!("
+            SELECT {ALERTS_SQL} from {ALERTS}
+            WHERE occ_id IN rarray(:occ_ids)
+                AND occ_id IN (SELECT id FROM {OCCS} WHERE owner IS :owner)
+            ORDER BY {ALERTS_SENT_COL} ASC, id ASC
+        ").as_ref())?;
+        let rows = stmt.query_map(
+            named_params! {
+                ":occ_ids": occ_dbids,
+                ":owner": owner,
+            },
+            todb::mapper(fromdb::alert))?;
+        rows.collect()
+    })?;
+
+    let mut result = HashMap::<String, Vec<StoredAlert>>::new();
+    for alert in alerts {
+        result.entry(alert.occ_id.clone()).or_default().push(alert);
+    }
+    Ok(result)
+}
+
+/// See [Db::get_alerts](crate::db::Db::get_alerts).
+pub fn get_alerts(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    dbids: Rc<Vec<Value>>,
+) -> DbResults<StoredAlert> {
+    fromdb::internal_err_fn(|| {
+        let mut stmt = conn.prepare(format!("
+            SELECT {ALERTS_SQL} from {ALERTS}
+            WHERE id IN rarray(:ids)
+                AND occ_id IN (SELECT id FROM {OCCS} WHERE owner IS :owner)
+        ").as_ref())?;
+        let rows = stmt.query_map(
+            named_params! { ":ids": dbids, ":owner": owner },
+            todb::mapper(fromdb::alert))?;
+        rows.collect()
+    })
+}
+
+/// See [Db::find_unacked_alerts](crate::db::Db::find_unacked_alerts).
+pub fn find_unacked_alerts(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    date: OccDate,
+) -> DbResults<StoredAlert> {
+    fromdb::internal_err_fn(|| {
+        let mut stmt = conn.prepare(format!("
+            SELECT {ALERTS_SQL} from {ALERTS}
+            WHERE acked_date IS NULL
+                AND (snoozed_until IS NULL OR snoozed_until <= :date)
+                AND occ_id IN (SELECT id FROM {OCCS} WHERE owner IS :owner)
+            ORDER BY {ALERTS_SENT_COL} ASC, id ASC
+        ").as_ref())?;
+        let rows = stmt.query_map(
+            named_params! {
+                ":date": todb::occ_date(date),
+                ":owner": owner,
+            },
+            todb::mapper(fromdb::alert))?;
+        rows.collect()
+    })
+}
+
+/// See [Db::find_last_digest](crate::db::Db::find_last_digest).
+pub fn find_last_digest(conn: &Connection, owner: Option<dbtypes::Id>)
+-> DbResult<Option<OccDate>> {
+    fromdb::internal_err_fn(|| {
+        conn.query_row(format!("
+            SELECT MAX(sent_date) from {DIGESTS}
+            WHERE owner IS :owner
+        ").as_ref(), named_params! { ":owner": owner },
+            todb::mapper(|r| fromdb::opt_occ_date(r, 0)))
+    })
+}
+
+/// See [Db::find_revisions](crate::db::Db::find_revisions).
+pub fn find_revisions(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    after: Option<u64>,
+    max_results: u32,
+) -> DbResults<Revision> {
+    fromdb::internal_err_fn(|| {
+        let mut stmt = conn.prepare(format!("
+            SELECT {REVISIONS_SQL} from {REVISIONS}
+            WHERE owner IS :owner AND rev > :after
+            ORDER BY rev ASC
+            LIMIT :max_results
+        ").as_ref())?;
+        let rows = stmt.query_map(
+            named_params! {
+                ":owner": owner,
+                ":after": after.map(|rev| rev as i64).unwrap_or(0),
+                ":max_results": max_results,
+            },
+            todb::mapper(fromdb::revision))?;
+        rows.collect()
+    })
+}
+
+/// See [Db::find_milestones](crate::db::Db::find_milestones).
+pub fn find_milestones(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    item_dbid: Option<dbtypes::Id>,
+) -> DbResults<StoredMilestone> {
+    fromdb::internal_err_fn(|| {
+        let mut stmt = conn.prepare(format!("
+            SELECT {MILESTONES_SQL} from {MILESTONES}
+            WHERE item_id IN (SELECT id FROM {ITEMS} WHERE owner IS :owner)
+                AND (:item_id IS NULL OR item_id = :item_id)
+            ORDER BY reached_date ASC, id ASC
+        ").as_ref())?;
+        let rows = stmt.query_map(
+            named_params! { ":owner": owner, ":item_id": item_dbid },
+            todb::mapper(fromdb::milestone))?;
+        rows.collect()
+    })
+}
+
+/// See [Db::find_audit_entries](crate::db::Db::find_audit_entries).
+pub fn find_audit_entries(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    item_dbid: dbtypes::Id,
+) -> DbResults<AuditEntry> {
+    fromdb::internal_err_fn(|| {
+        let mut stmt = conn.prepare(format!("
+            SELECT {AUDIT_SQL} from {AUDIT}
+            WHERE owner IS :owner AND item_id = :item_id
+            ORDER BY change_date ASC, id ASC
+        ").as_ref())?;
+        let rows = stmt.query_map(
+            named_params! { ":owner": owner, ":item_id": item_dbid },
+            todb::mapper(fromdb::audit_entry))?;
+        rows.collect()
+    })
+}
+
+/// See [Db::find_external_ids](crate::db::Db::find_external_ids).
+pub fn find_external_ids(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    source: &str,
+) -> DbResult<HashMap<String, String>> {
+    fromdb::internal_err_fn(|| {
+        let mut stmt = conn.prepare(format!("
+            SELECT external_id, item_id from {EXTERNAL_IDS}
+            WHERE source = :source AND owner IS :owner
+        ").as_ref())?;
+        let rows = stmt.query_map(
+            named_params! { ":source": source, ":owner": owner },
+            |r| Ok((r.get(0)?, fromdb::id(r.get(1)?))))?;
+        rows.collect()
+    })
+}
+
+/// See [Db::count_occs_by_progress](crate::db::Db::count_occs_by_progress).
+pub fn count_occs_by_progress(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    start: Option<OccDate>,
+    end: Option<OccDate>,
+    now: OccDate,
+) -> DbResults<OccProgressCount> {
+    let mut exprs: Vec<String> = vec![
+        "active = 1".to_owned(),
+        "owner IS :owner".to_owned(),
+    ];
+    let mut params: Vec<(&str, &dyn ToSql)> = vec![(":owner", &owner)];
+    let start_db_value = start.map(todb::occ_date).unwrap_or(0);
+    if let Some(start) = start {
+        exprs.push("end_date > :min_end".to_owned());
+        params.push((":min_end", &start_db_value));
+    }
+    let end_db_value = end.map(todb::occ_date).unwrap_or(0);
+    if let Some(end) = end {
+        exprs.push("start_date < :max_start".to_owned());
+        params.push((":max_start", &end_db_value));
+    }
+    let now_db_value = todb::occ_date(now);
+    params.push((":now", &now_db_value));
+
+    fromdb::internal_err_fn(|| {
+        let mut stmt = conn.prepare(format!("
+            SELECT item_id, task_completion_progress, end_date <= :now AS ended,
+                   COUNT(*)
+            FROM {OCCS}
+            {}
+            GROUP BY item_id, task_completion_progress, ended
+        ", where_sql(&exprs)).as_ref())?;
+        let rows = stmt.query_map(
+            &params[..], todb::mapper(fromdb::occ_progress_count))?;
+        rows.collect()
+    })
+}
+
+/// See [Db::find_users](crate::db::Db::find_users).
+pub fn find_users(conn: &Connection, name: Option<&str>)
+-> DbResults<StoredUser> {
+    let mut exprs: Vec<String> = Vec::new();
+    let mut params: Vec<(&str, &dyn ToSql)> = Vec::new();
+    if name.is_some() {
+        exprs.push("name = :name".to_owned());
+        params.push((":name", &name));
+    }
+
+    fromdb::internal_err_fn(|| {
+        let mut stmt = conn.prepare(format!("
+            SELECT {USERS_SQL} from {USERS} {}
+        ", where_sql(&exprs)).as_ref())?;
+        let rows = stmt.query_map(&params[..], todb::mapper(fromdb::user))?;
+        rows.collect()
+    })
+}
+
+/// See [Db::check](crate::db::Db::check).
+pub fn check(conn: &Connection) -> DbResult<Vec<String>> {
+    let mut problems = vec![];
+    let version: usize = fromdb::internal_err(
+        conn.query_row("PRAGMA user_version", [], |r| r.get(0)))?;
+    let expected_version = dbtypes::SCHEMA_FILES.len();
+    if version > expected_version {
+        problems.push(format!(
+            "database schema version is {version}, but this version only \
+             supports up to {expected_version}: it was written by a newer \
+             version"));
+    } else if version < expected_version {
+        problems.push(format!(
+            "database schema version is {version}, expected \
+             {expected_version}: it should be upgraded when opened"));
+    }
+
+    fromdb::internal_err_fn(|| {
+        let mut stmt = conn.prepare(
+            format!("SELECT {ITEMS_SQL} FROM {ITEMS}").as_ref())?;
+        let mut rows = stmt.query([])?;
+        while let Some(r) = rows.next()? {
+            if let Err(e) = fromdb::item(r) {
+                let id: dbtypes::Id = r.get(0)?;
+                problems.push(format!("item {id}: {e}"));
+            }
+        }
+
+        let mut stmt = conn.prepare(
+            format!("SELECT {CONFIGS_SQL}, rowid FROM {CONFIGS}").as_ref())?;
+        let mut rows = stmt.query([])?;
+        while let Some(r) = rows.next()? {
+            if let Err(e) = fromdb::config(r) {
+                let rowid: i64 = r.get(6)?;
+                problems.push(format!("config (row {rowid}): {e}"));
+            }
+        }
+        Ok(())
+    })?;
+    Ok(problems)
+}
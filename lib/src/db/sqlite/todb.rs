@@ -4,8 +4,9 @@ use std::rc::Rc;
 use chrono::NaiveTime;
 use rusqlite::{Row, types::Value};
 use super::dbtypes;
-use crate::db::{DbResult, DbResults};
-use crate::types::{Config, DayFilter, ItemType, OccDate, Sched};
+use crate::db::{AuditChange, DbResult, DbResults, ObjectId};
+use crate::types::{AlertKind, Config, DayFilter, ItemType, MilestoneKind,
+                   OccDate, Sched};
 
 /// Serialise a serialisable value to bytes using MessagePack.
 fn serde<T>(val: &T) -> DbResult<Vec<u8>>
@@ -39,6 +40,16 @@ pub fn item_type(type_: &ItemType) -> &str {
     type_.as_ref()
 }
 
+/// Convert alert kind to value stored in database.
+pub fn alert_kind(kind: &AlertKind) -> &str {
+    kind.as_ref()
+}
+
+/// Convert milestone kind to value stored in database.
+pub fn milestone_kind(kind: &MilestoneKind) -> &str {
+    kind.as_ref()
+}
+
 /// Produce a value for the `only_occ_date` column for an item.
 pub fn item_only_occ_date(sched: &Sched) -> Option<i64> {
     match &sched {
@@ -71,6 +82,16 @@ pub fn config(config: &Config) -> DbResult<Vec<u8>> {
     serde(&config)
 }
 
+/// Convert object ID to value stored in database.
+pub fn object_id(object: &ObjectId) -> DbResult<Vec<u8>> {
+    serde(object)
+}
+
+/// Convert audit log change to value stored in database.
+pub fn audit_change(change: &AuditChange) -> DbResult<Vec<u8>> {
+    serde(change)
+}
+
 /// Convert a row-mapping function that produces [`DbResult`] to a row-mapping
 /// function suitable for use with [`rusqlite::Statement::query_map`].
 pub fn mapper<T, F>(f: F) -> impl Fn(&Row<'_>) -> rusqlite::Result<T>
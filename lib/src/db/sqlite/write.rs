@@ -1,21 +1,30 @@
 //! Helpers for writing to the database.
 
-use chrono::Utc;
-use rusqlite::{Connection, named_params};
-use crate::db::{ConfigId, DbResult, StoredConfig, StoredItem, StoredOcc};
-use crate::types::{Item, Occ};
-use super::dbtypes::{self, table::{CONFIGS, ITEMS, OCCS}};
+use rusqlite::{Connection, OptionalExtension, named_params};
+use crate::db::{AuditChange, ConfigId, DbResult, ObjectId, StoredAlert,
+                StoredConfig, StoredItem, StoredOcc};
+use crate::types::{Alert, Item, Milestone, Occ, OccDate, ProgressEntry,
+                   User};
+use super::dbtypes::{self, table::{ALERTS, AUDIT, CONFIGS, DIGESTS,
+                                   EXTERNAL_IDS, ITEMS, MILESTONES, OCCS,
+                                   PROGRESS, REVISIONS, USERS}};
 use super::{fromdb, todb};
 
-pub fn create_item(conn: &Connection, item: &Item) -> DbResult<String> {
-    let now: i64 = todb::occ_date(Utc::now());
+pub fn create_item(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    item: &Item,
+    now: OccDate,
+) -> DbResult<String> {
+    let now: i64 = todb::occ_date(now);
 
     conn.execute(format!("
-        INSERT INTO {ITEMS} (created_date, updated_date, type, active, category,
-                             name, desc, sched_blob, only_occ_end)
-        VALUES (:created, :updated, :type, :active, :cat, :name, :desc,
+        INSERT INTO {ITEMS} (owner, created_date, updated_date, type, active,
+                             category, name, desc, sched_blob, only_occ_end)
+        VALUES (:owner, :created, :updated, :type, :active, :cat, :name, :desc,
                 :sched_blob, :only_occ_end)
     ").as_ref(), named_params! {
+        ":owner": owner,
         ":created": now,
         ":updated": now,
         ":type": todb::item_type(&item.type_),
@@ -30,42 +39,121 @@ pub fn create_item(conn: &Connection, item: &Item) -> DbResult<String> {
         .map_err(|e| format!("error creating item ({item:?}): {e}"))
 }
 
-pub fn update_item(conn: &Connection, item: &StoredItem)
--> DbResult<()> {
+/// Update the item with database ID `id`, at date `now`.
+fn update_item_data(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    id: dbtypes::Id,
+    item: &Item,
+    now: OccDate,
+) -> DbResult<()> {
     conn.execute(format!("
         UPDATE {ITEMS}
         SET updated_date = :updated, type = :type, active = :active,
             category = :cat, name = :name, desc = :desc,
             sched_blob = :sched_blob, only_occ_end = :only_occ_end
-        WHERE id = :id
+        WHERE id = :id AND owner IS :owner
     ").as_ref(), named_params! {
-        ":id": todb::id(&item.id)?,
-        ":updated": todb::occ_date(Utc::now()),
-        ":type": todb::item_type(&item.item.type_),
-        ":active": item.item.active,
-        ":cat": item.item.category,
-        ":name": item.item.name,
-        ":desc": item.item.desc,
-        ":sched_blob": todb::sched(&item.item.sched)?,
-        ":only_occ_end": todb::item_only_occ_date(&item.item.sched),
+        ":id": id,
+        ":owner": owner,
+        ":updated": todb::occ_date(now),
+        ":type": todb::item_type(&item.type_),
+        ":active": item.active,
+        ":cat": item.category,
+        ":name": item.name,
+        ":desc": item.desc,
+        ":sched_blob": todb::sched(&item.sched)?,
+        ":only_occ_end": todb::item_only_occ_date(&item.sched),
     })
         .map(|_| ())
-        .map_err(|e| format!("error updating item ({item:?}): {e}"))
+        .map_err(|e| format!("error updating item ({id}, {item:?}): {e}"))
+}
+
+pub fn update_item(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    item: &StoredItem,
+    now: OccDate,
+) -> DbResult<()> {
+    update_item_data(conn, owner, todb::id(&item.id)?, &item.item, now)
 }
 
-pub fn delete_item(conn: &Connection, id: &str) -> DbResult<()> {
+/// Returns the item's ID, and whether it was created.
+pub fn upsert_item(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    source: &str,
+    external_id: &str,
+    item: &Item,
+    now: OccDate,
+) -> DbResult<(String, bool)> {
+    let existing: Option<dbtypes::Id> = conn.query_row(format!("
+        SELECT item_id FROM {EXTERNAL_IDS}
+        WHERE source = :source AND external_id = :external_id
+            AND owner IS :owner
+    ").as_ref(), named_params! {
+        ":source": source,
+        ":external_id": external_id,
+        ":owner": owner,
+    }, |r| r.get(0))
+        .optional()
+        .map_err(|e| format!(
+            "error finding item ({source:?}, {external_id:?}): {e}"))?;
+
+    if let Some(id) = existing {
+        update_item_data(conn, owner, id, item, now)?;
+        return Ok((fromdb::id(id), false));
+    }
+    let id = create_item(conn, owner, item, now)?;
+    conn.execute(format!("
+        INSERT INTO {EXTERNAL_IDS} (item_id, owner, source, external_id)
+        VALUES (:item_id, :owner, :source, :external_id)
+    ").as_ref(), named_params! {
+        ":item_id": todb::id(&id)?,
+        ":owner": owner,
+        ":source": source,
+        ":external_id": external_id,
+    })
+        .map_err(|e| format!(
+            "error linking item ({source:?}, {external_id:?}): {e}"))?;
+    Ok((id, true))
+}
+
+pub fn delete_item(conn: &Connection, owner: Option<dbtypes::Id>, id: &str)
+-> DbResult<()> {
+    conn.execute(format!("
+        DELETE FROM {MILESTONES}
+        WHERE item_id IN (SELECT id FROM {ITEMS}
+                          WHERE id = :id AND owner IS :owner)
+    ").as_ref(), named_params! {
+        ":id": todb::id(id)?,
+        ":owner": owner,
+    })
+        .map_err(|e| format!("error deleting item milestones ({id:?}): {e}"))?;
+    conn.execute(format!("
+        DELETE FROM {EXTERNAL_IDS}
+        WHERE item_id = :id AND owner IS :owner
+    ").as_ref(), named_params! {
+        ":id": todb::id(id)?,
+        ":owner": owner,
+    })
+        .map_err(|e| format!("error deleting item link ({id:?}): {e}"))?;
     conn.execute(format!("
         DELETE FROM {ITEMS}
-        WHERE id = :id
+        WHERE id = :id AND owner IS :owner
     ").as_ref(), named_params! {
         ":id": todb::id(id)?,
+        ":owner": owner,
     })
         .map(|_| ())
         .map_err(|e| format!("error deleting item ({id:?}): {e}"))
 }
 
-pub fn set_config(conn: &Connection, config: &StoredConfig)
--> DbResult<String> {
+pub fn set_config(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    config: &StoredConfig,
+) -> DbResult<String> {
     let mut id_all: Option<u8> = None;
     let mut id_type: Option<&str> = None;
     let mut id_cat: Option<&str> = None;
@@ -80,12 +168,17 @@ pub fn set_config(conn: &Connection, config: &StoredConfig)
         ConfigId::Occ { id } => { id_occ = Some(todb::id(id)?); }
     }
 
+    // the unique constraint doesn't apply to rows containing nulls, so replace
+    // explicitly
+    delete_config(conn, owner, &config.id)?;
     conn.execute(format!("
         INSERT INTO {CONFIGS}
-            (id_all, id_type, id_category, id_item, id_occ, config_blob)
+            (owner, id_all, id_type, id_category, id_item, id_occ, config_blob)
         VALUES
-            (:id_all, :id_type, :id_category, :id_item, :id_occ, :config_blob)
+            (:owner, :id_all, :id_type, :id_category, :id_item, :id_occ,
+             :config_blob)
     ").as_ref(), named_params! {
+        ":owner": owner,
         ":id_all": id_all,
         ":id_type": id_type,
         ":id_category": id_cat,
@@ -97,40 +190,33 @@ pub fn set_config(conn: &Connection, config: &StoredConfig)
         .map_err(|e| format!("error setting config ({config:?}): {e}"))
 }
 
-pub fn delete_config(conn: &Connection, id: &ConfigId) -> DbResult<()> {
+pub fn delete_config(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    id: &ConfigId,
+) -> DbResult<()> {
     let mut id_all: Option<u8> = None;
     let mut id_type: Option<&str> = None;
     let mut id_cat: Option<&str> = None;
     let mut id_item: Option<dbtypes::Id> = None;
     let mut id_occ: Option<dbtypes::Id> = None;
 
-    let id_param = match id {
-        ConfigId::All => {
-            id_all = Some(fromdb::CONFIG_ID_ALL_DB_VALUE);
-            ":id_all"
-        }
-        ConfigId::Type(type_) => {
-            id_type = Some(todb::item_type(type_));
-            ":id_type"
-        }
-        ConfigId::Category(cat) => {
-            id_cat = Some(cat);
-            ":id_cat"
-        }
-        ConfigId::Item { id } => {
-            id_item = Some(todb::id(id)?);
-            ":id_item"
-        }
-        ConfigId::Occ { id } => {
-            id_occ = Some(todb::id(id)?);
-            ":id_occ"
-        }
-    };
+    match id {
+        ConfigId::All => { id_all = Some(fromdb::CONFIG_ID_ALL_DB_VALUE); }
+        ConfigId::Type(type_) => { id_type = Some(todb::item_type(type_)); }
+        ConfigId::Category(cat) => { id_cat = Some(cat); }
+        ConfigId::Item { id } => { id_item = Some(todb::id(id)?); }
+        ConfigId::Occ { id } => { id_occ = Some(todb::id(id)?); }
+    }
 
+    // IS matches nulls
     conn.execute(format!("
         DELETE FROM {CONFIGS}
-        WHERE id = {id_param}
+        WHERE id_all IS :id_all AND id_type IS :id_type
+            AND id_category IS :id_category AND id_item IS :id_item
+            AND id_occ IS :id_occ AND owner IS :owner
     ").as_ref(), named_params! {
+        ":owner": owner,
         ":id_all": id_all,
         ":id_type": id_type,
         ":id_category": id_cat,
@@ -138,52 +224,311 @@ pub fn delete_config(conn: &Connection, id: &ConfigId) -> DbResult<()> {
         ":id_occ": id_occ,
     })
         .map(|_| ())
-        .map_err(|e| format!("error deleting item ({id:?}): {e}"))
+        .map_err(|e| format!("error deleting config ({id:?}): {e}"))
 }
 
-pub fn create_occ(conn: &Connection, item_id: &str, occ: &Occ)
--> DbResult<String> {
-    conn.execute(format!("
+pub fn create_occ(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    item_id: &str,
+    occ: &Occ,
+) -> DbResult<String> {
+    // the item must have the same owner
+    let inserted = conn.execute(format!("
         INSERT INTO {OCCS}
-            (item_id, active, start_date, end_date, task_completion_progress)
-        VALUES
-            (:item_id, :active, :start, :end, :progress)
+            (owner, item_id, active, start_date, end_date,
+             task_completion_progress, snoozed_until)
+        SELECT owner, id, :active, :start, :end, :progress, :snoozed_until
+        FROM {ITEMS}
+        WHERE id = :item_id AND owner IS :owner
     ").as_ref(), named_params! {
+        ":owner": owner,
         ":item_id": todb::id(item_id)?,
         ":active": occ.active,
         ":start": todb::occ_date(occ.start),
         ":end": todb::occ_date(occ.end),
         ":progress": occ.task_completion_progress,
+        ":snoozed_until": occ.snoozed_until.map(todb::occ_date),
     })
-        .map(|_| fromdb::id(conn.last_insert_rowid()))
-        .map_err(|e| format!("error creating occurrence ({occ:?}): {e}"))
+        .map_err(|e| format!("error creating occurrence ({occ:?}): {e}"))?;
+    if inserted == 0 {
+        return Err(format!("error creating occurrence ({occ:?}): \
+                            item does not exist: {item_id}"));
+    }
+    Ok(fromdb::id(conn.last_insert_rowid()))
 }
 
-pub fn update_occ(conn: &Connection, occ: &StoredOcc)
--> DbResult<()> {
+pub fn update_occ(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    occ: &StoredOcc,
+) -> DbResult<()> {
     conn.execute(format!("
         UPDATE {OCCS}
         SET active = :active, start_date = :start, end_date = :end,
-            task_completion_progress = :progress
-        WHERE id = :id
+            task_completion_progress = :progress,
+            snoozed_until = :snoozed_until
+        WHERE id = :id AND owner IS :owner
     ").as_ref(), named_params! {
         ":id": todb::id(&occ.id)?,
+        ":owner": owner,
         ":active": occ.occ.active,
         ":start": todb::occ_date(occ.occ.start),
         ":end": todb::occ_date(occ.occ.end),
         ":progress": occ.occ.task_completion_progress,
+        ":snoozed_until": occ.occ.snoozed_until.map(todb::occ_date),
     })
         .map(|_| ())
         .map_err(|e| format!("error updating occurrence ({occ:?}): {e}"))
 }
 
-pub fn delete_occ(conn: &Connection, id: &str) -> DbResult<()> {
+pub fn delete_occ(conn: &Connection, owner: Option<dbtypes::Id>, id: &str)
+-> DbResult<()> {
+    conn.execute(format!("
+        DELETE FROM {PROGRESS}
+        WHERE occ_id IN (SELECT id FROM {OCCS}
+                         WHERE id = :id AND owner IS :owner)
+    ").as_ref(), named_params! {
+        ":id": todb::id(id)?,
+        ":owner": owner,
+    })
+        .map_err(|e| format!(
+            "error deleting occurrence progress entries ({id:?}): {e}"))?;
+    conn.execute(format!("
+        DELETE FROM {ALERTS}
+        WHERE occ_id IN (SELECT id FROM {OCCS}
+                         WHERE id = :id AND owner IS :owner)
+    ").as_ref(), named_params! {
+        ":id": todb::id(id)?,
+        ":owner": owner,
+    })
+        .map_err(|e| format!(
+            "error deleting occurrence alerts ({id:?}): {e}"))?;
     conn.execute(format!("
         DELETE FROM {OCCS}
-        WHERE id = :id
+        WHERE id = :id AND owner IS :owner
     ").as_ref(), named_params! {
         ":id": todb::id(id)?,
+        ":owner": owner,
     })
         .map(|_| ())
         .map_err(|e| format!("error deleting occurrence ({id:?}): {e}"))
 }
+
+pub fn create_progress_entry(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    occ_id: &str,
+    entry: &ProgressEntry,
+) -> DbResult<String> {
+    // progress entries are owned by their occurrence's owner
+    let inserted = conn.execute(format!("
+        INSERT INTO {PROGRESS} (occ_id, date, amount, note)
+        SELECT id, :date, :amount, :note FROM {OCCS}
+        WHERE id = :occ_id AND owner IS :owner
+    ").as_ref(), named_params! {
+        ":occ_id": todb::id(occ_id)?,
+        ":owner": owner,
+        ":date": todb::occ_date(entry.date),
+        ":amount": entry.amount,
+        ":note": entry.note,
+    })
+        .map_err(|e| format!(
+            "error creating progress entry ({entry:?}): {e}"))?;
+    if inserted == 0 {
+        return Err(format!("error creating progress entry ({entry:?}): \
+                            occurrence does not exist: {occ_id}"));
+    }
+    Ok(fromdb::id(conn.last_insert_rowid()))
+}
+
+pub fn delete_progress_entry(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    id: &str,
+) -> DbResult<()> {
+    conn.execute(format!("
+        DELETE FROM {PROGRESS}
+        WHERE id = :id
+            AND occ_id IN (SELECT id FROM {OCCS} WHERE owner IS :owner)
+    ").as_ref(), named_params! {
+        ":id": todb::id(id)?,
+        ":owner": owner,
+    })
+        .map(|_| ())
+        .map_err(|e| format!("error deleting progress entry ({id:?}): {e}"))
+}
+
+pub fn create_alert(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    occ_id: &str,
+    alert: &Alert,
+) -> DbResult<String> {
+    // alerts are owned by their occurrence's owner
+    let inserted = conn.execute(format!("
+        INSERT INTO {ALERTS} (occ_id, kind, sent_date)
+        SELECT id, :kind, :sent FROM {OCCS}
+        WHERE id = :occ_id AND owner IS :owner
+    ").as_ref(), named_params! {
+        ":occ_id": todb::id(occ_id)?,
+        ":owner": owner,
+        ":kind": todb::alert_kind(&alert.kind),
+        ":sent": todb::occ_date(alert.sent),
+    })
+        .map_err(|e| format!("error creating alert ({alert:?}): {e}"))?;
+    if inserted == 0 {
+        return Err(format!("error creating alert ({alert:?}): \
+                            occurrence does not exist: {occ_id}"));
+    }
+    Ok(fromdb::id(conn.last_insert_rowid()))
+}
+
+pub fn update_alert(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    alert: &StoredAlert,
+) -> DbResult<()> {
+    conn.execute(format!("
+        UPDATE {ALERTS}
+        SET kind = :kind, sent_date = :sent, acked_date = :acked,
+            snoozed_until = :snoozed_until
+        WHERE id = :id
+            AND occ_id IN (SELECT id FROM {OCCS} WHERE owner IS :owner)
+    ").as_ref(), named_params! {
+        ":id": todb::id(&alert.id)?,
+        ":owner": owner,
+        ":kind": todb::alert_kind(&alert.alert.kind),
+        ":sent": todb::occ_date(alert.alert.sent),
+        ":acked": alert.alert.acked.map(todb::occ_date),
+        ":snoozed_until": alert.alert.snoozed_until.map(todb::occ_date),
+    })
+        .map(|_| ())
+        .map_err(|e| format!("error updating alert ({alert:?}): {e}"))
+}
+
+pub fn create_milestone(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    item_id: &str,
+    milestone: &Milestone,
+) -> DbResult<String> {
+    // milestones are owned by their item's owner
+    let inserted = conn.execute(format!("
+        INSERT INTO {MILESTONES}
+            (item_id, kind, threshold, year, reached_date)
+        SELECT id, :kind, :threshold, :year, :reached FROM {ITEMS}
+        WHERE id = :item_id AND owner IS :owner
+    ").as_ref(), named_params! {
+        ":item_id": todb::id(item_id)?,
+        ":owner": owner,
+        ":kind": todb::milestone_kind(&milestone.kind),
+        ":threshold": milestone.threshold,
+        ":year": milestone.year,
+        ":reached": todb::occ_date(milestone.reached),
+    })
+        .map_err(|e| format!("error creating milestone ({milestone:?}): {e}"))?;
+    if inserted == 0 {
+        return Err(format!("error creating milestone ({milestone:?}): \
+                            item does not exist: {item_id}"));
+    }
+    Ok(fromdb::id(conn.last_insert_rowid()))
+}
+
+pub fn create_digest(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    sent: OccDate,
+) -> DbResult<String> {
+    conn.execute(format!("
+        INSERT INTO {DIGESTS} (owner, sent_date)
+        VALUES (:owner, :sent)
+    ").as_ref(), named_params! {
+        ":owner": owner,
+        ":sent": todb::occ_date(sent),
+    })
+        .map_err(|e| format!("error creating digest ({sent}): {e}"))?;
+    Ok(fromdb::id(conn.last_insert_rowid()))
+}
+
+pub fn create_user(conn: &Connection, user: &User) -> DbResult<String> {
+    conn.execute(format!("
+        INSERT INTO {USERS} (name)
+        VALUES (:name)
+    ").as_ref(), named_params! {
+        ":name": user.name,
+    })
+        .map(|_| fromdb::id(conn.last_insert_rowid()))
+        .map_err(|e| format!("error creating user ({user:?}): {e}"))
+}
+
+/// Record a change to `object` at date `now`, replacing any earlier change to
+/// it.
+pub fn record_revision(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    object: &ObjectId,
+    deleted: bool,
+    now: OccDate,
+) -> DbResult<()> {
+    let object_blob = todb::object_id(object)?;
+    conn.execute(format!("
+        DELETE FROM {REVISIONS}
+        WHERE owner IS :owner AND object_blob = :object_blob
+    ").as_ref(), named_params! {
+        ":owner": owner,
+        ":object_blob": object_blob,
+    })
+        .map_err(|e| format!("error recording change ({object:?}): {e}"))?;
+    conn.execute(format!("
+        INSERT INTO {REVISIONS} (owner, object_blob, deleted, change_date)
+        VALUES (:owner, :object_blob, :deleted, :date)
+    ").as_ref(), named_params! {
+        ":owner": owner,
+        ":object_blob": object_blob,
+        ":deleted": deleted,
+        ":date": todb::occ_date(now),
+    })
+        .map(|_| ())
+        .map_err(|e| format!("error recording change ({object:?}): {e}"))
+}
+
+/// Record a change made at `now` to the item with ID `item_id`, or to one of
+/// its configs, in the item's audit log.
+pub fn record_audit_entry(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    item_id: &str,
+    change: &AuditChange,
+    now: OccDate,
+) -> DbResult<()> {
+    conn.execute(format!("
+        INSERT INTO {AUDIT} (owner, item_id, change_blob, change_date)
+        VALUES (:owner, :item_id, :change_blob, :date)
+    ").as_ref(), named_params! {
+        ":owner": owner,
+        ":item_id": todb::id(item_id)?,
+        ":change_blob": todb::audit_change(change)?,
+        ":date": todb::occ_date(now),
+    })
+        .map(|_| ())
+        .map_err(|e| format!(
+            "error recording audit entry ({item_id}, {change:?}): {e}"))
+}
+
+/// Delete all entries in the audit log of the item with database ID `item_id`.
+pub fn delete_audit_entries(
+    conn: &Connection,
+    owner: Option<dbtypes::Id>,
+    item_id: dbtypes::Id,
+) -> DbResult<()> {
+    conn.execute(format!("
+        DELETE FROM {AUDIT}
+        WHERE owner IS :owner AND item_id = :item_id
+    ").as_ref(), named_params! {
+        ":owner": owner,
+        ":item_id": item_id,
+    })
+        .map(|_| ())
+        .map_err(|e| format!("error deleting audit entries ({item_id}): {e}"))
+}
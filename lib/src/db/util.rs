@@ -1,8 +1,11 @@
 //! Utilities for interacting with the database.
 
-use crate::types::{Item, Occ};
-use super::{ConfigId, Db, DbResult, DbResults, DbUpdate, StoredConfig,
-            StoredItem, StoredOcc, UpdateId};
+use std::time::Duration;
+use chrono::SubsecRound;
+use crate::types::{Item, Occ, OccDate, ProgressEntry, User};
+use super::{ConfigId, Db, DbResult, DbResults, DbUpdate, SortDirection,
+            StoredAlert, StoredConfig, StoredItem, StoredOcc, StoredUser,
+            UpdateId, WriteBatch};
 
 /// Extract the only result from the results of a lookup by ID.
 fn get_single_helper<T>(id: &str, r: DbResults<T>) -> DbResult<T> {
@@ -13,13 +16,31 @@ fn get_single_helper<T>(id: &str, r: DbResults<T>) -> DbResult<T> {
 
 /// Create an item.
 pub fn create_item(db: &mut impl Db, item: Item) -> DbResult<StoredItem> {
-    let id_token = DbUpdate::id_token();
-    let mut ids = db.write(&[&DbUpdate::create_item(id_token, &item)])?;
+    let mut batch = WriteBatch::new();
+    let id_token = batch.create_item(&item);
+    let mut ids = batch.write(db)?;
     let id = ids.remove(&id_token)
         .ok_or("unknown error - ID not returned".to_owned())?;
     get_item(db, &id)
 }
 
+/// Create multiple items in a single write, so that either all or none of them
+/// are created.  Results are in the same order as `items`.
+pub fn create_items(db: &mut impl Db, items: &[Item]) -> DbResults<StoredItem> {
+    let mut batch = WriteBatch::new();
+    let tokens = items.iter()
+        .map(|item| batch.create_item(item))
+        .collect::<Vec<_>>();
+    let mut ids = batch.write(db)?;
+    tokens.iter()
+        .map(|id_token| {
+            let id = ids.remove(id_token)
+                .ok_or("unknown error - ID not returned".to_owned())?;
+            get_item(db, &id)
+        })
+        .collect()
+}
+
 /// Update an item to be the same as the provided `item`.
 pub fn update_item(db: &mut impl Db, item: &StoredItem) -> DbResult<()> {
     db.write(&[&DbUpdate::update_item(item)])?;
@@ -47,10 +68,9 @@ pub fn delete_config(db: &mut impl Db, id: &ConfigId) -> DbResult<()> {
 /// Create an occurrence for the item with the given ID.
 pub fn create_occ(db: &mut impl Db, item_id: &str, occ: &Occ)
 -> DbResult<String> {
-    let id_token = DbUpdate::id_token();
-    let mut ids = db.write(&[
-        &DbUpdate::create_occ(id_token, UpdateId::Id(item_id), occ),
-    ])?;
+    let mut batch = WriteBatch::new();
+    let id_token = batch.create_occ(UpdateId::Id(item_id), occ);
+    let mut ids = batch.write(db)?;
     ids.remove(&id_token)
         .ok_or("unknown error - ID not returned".to_owned())
 }
@@ -67,11 +87,171 @@ pub fn delete_occ(db: &mut impl Db, id: &str) -> DbResult<()> {
     Ok(())
 }
 
+/// Add `amount` to an occurrence's progress, recording a progress entry at
+/// `date` with an optional `note`.
+///
+/// Progress cannot go below zero; the recorded entry is for the change actually
+/// made.  Returns the updated occurrence.
+pub fn add_progress(
+    db: &mut impl Db,
+    occ_id: &str,
+    amount: i64,
+    note: Option<&str>,
+    date: OccDate,
+) -> DbResult<StoredOcc> {
+    let mut occ = get_occ(db, occ_id)?;
+    let old_progress = occ.occ.task_completion_progress;
+    let new_progress = i64::from(old_progress)
+        .saturating_add(amount)
+        .clamp(0, u32::MAX.into());
+    occ.occ.task_completion_progress = new_progress as u32;
+    let entry = ProgressEntry {
+        date,
+        amount: new_progress - i64::from(old_progress),
+        note: note.map(str::to_owned),
+    };
+
+    let mut batch = WriteBatch::new();
+    batch.create_progress_entry(UpdateId::Id(occ_id), &entry);
+    batch.push(DbUpdate::update_occ(&occ));
+    batch.write(db)?;
+    Ok(occ)
+}
+
+/// Revert the most recent progress entry for an occurrence, removing the entry.
+///
+/// Does nothing if the occurrence has no progress entries.  Returns the updated
+/// occurrence.
+pub fn undo_progress(db: &mut impl Db, occ_id: &str) -> DbResult<StoredOcc> {
+    let mut occ = get_occ(db, occ_id)?;
+    let last_entry = db.find_progress_entries(
+        &[occ_id], SortDirection::Desc, 1)?
+        .remove(occ_id)
+        .and_then(|entries| entries.into_iter().next());
+
+    if let Some(last_entry) = last_entry {
+        let new_progress = i64::from(occ.occ.task_completion_progress)
+            .saturating_sub(last_entry.entry.amount)
+            .clamp(0, u32::MAX.into());
+        occ.occ.task_completion_progress = new_progress as u32;
+        db.write(&[
+            &DbUpdate::delete_progress_entry(&last_entry.id),
+            &DbUpdate::update_occ(&occ),
+        ])?;
+    }
+    Ok(occ)
+}
+
+/// Stop showing alerts for an occurrence until `duration` after `date`.
+///
+/// If `extend_end` is `true`, the occurrence's end is also moved later by
+/// `duration`.  Returns the updated occurrence.
+pub fn snooze_occ(
+    db: &mut impl Db,
+    occ_id: &str,
+    duration: Duration,
+    extend_end: bool,
+    date: OccDate,
+) -> DbResult<StoredOcc> {
+    let mut occ = get_occ(db, occ_id)?;
+    let add = |date: OccDate| {
+        chrono::TimeDelta::from_std(duration).ok()
+            .and_then(|duration| date.checked_add_signed(duration))
+            .ok_or_else(|| format!("snooze duration too long: {duration:?}"))
+    };
+    // dates are stored with second precision
+    occ.occ.snoozed_until = Some(add(date)?.trunc_subsecs(0));
+    if extend_end {
+        occ.occ.end = add(occ.occ.end)?;
+    }
+    db.write(&[&DbUpdate::update_occ(&occ)])?;
+    Ok(occ)
+}
+
+/// Mark an alert as acknowledged at `date`, so that it's never sent again.
+///
+/// Returns the updated alert.
+pub fn ack_alert(db: &mut impl Db, alert_id: &str, date: OccDate)
+-> DbResult<StoredAlert> {
+    let mut alert = get_alert(db, alert_id)?;
+    alert.alert.acked = Some(date.trunc_subsecs(0));
+    db.write(&[&DbUpdate::update_alert(&alert)])?;
+    Ok(alert)
+}
+
+/// Stop an alert from being sent again until `duration` after `date`.
+///
+/// Returns the updated alert.
+pub fn snooze_alert(
+    db: &mut impl Db,
+    alert_id: &str,
+    duration: Duration,
+    date: OccDate,
+) -> DbResult<StoredAlert> {
+    let mut alert = get_alert(db, alert_id)?;
+    let until = chrono::TimeDelta::from_std(duration).ok()
+        .and_then(|duration| date.checked_add_signed(duration))
+        .ok_or_else(|| format!("snooze duration too long: {duration:?}"))?;
+    // dates are stored with second precision
+    alert.alert.snoozed_until = Some(until.trunc_subsecs(0));
+    db.write(&[&DbUpdate::update_alert(&alert)])?;
+    Ok(alert)
+}
+
+/// Create a user.
+pub fn create_user(db: &mut impl Db, user: &User) -> DbResult<StoredUser> {
+    let mut batch = WriteBatch::new();
+    let id_token = batch.create_user(user);
+    let mut ids = batch.write(db)?;
+    let id = ids.remove(&id_token)
+        .ok_or("unknown error - ID not returned".to_owned())?;
+    Ok(StoredUser { id, user: user.clone() })
+}
+
+/// Get the user with the given name, creating it if it doesn't exist.
+pub fn get_or_create_user(db: &mut impl Db, name: &str)
+-> DbResult<StoredUser> {
+    match db.find_users(Some(name))?.pop() {
+        Some(user) => Ok(user),
+        None => create_user(db, &User { name: name.to_owned() }),
+    }
+}
+
 /// Get an existing item by ID.
 pub fn get_item(db: &impl Db, id: &str) -> DbResult<StoredItem> {
     get_single_helper(id, db.get_items(&[id]))
 }
 
+/// Find an item by ID, or by name if no item has that ID.  Names are compared
+/// case-insensitively.
+pub fn find_item(db: &impl Db, query: &str) -> DbResult<StoredItem> {
+    // not every name is a valid ID
+    if let Some(item) = db.get_items(&[query]).ok()
+        .and_then(|mut items| items.pop())
+    {
+        return Ok(item);
+    }
+
+    let mut items = db.find_items(None, None, None, SortDirection::Asc, None,
+                                  u32::MAX)?
+        .into_iter()
+        .filter(|item| item.item.name.eq_ignore_ascii_case(query))
+        .collect::<Vec<_>>();
+    match items.len() {
+        0 => Err(format!("item not found: {query}")),
+        1 => Ok(items.remove(0)),
+        _ => Err(format!("multiple items are named {query:?}: use the ID")),
+    }
+}
+
+/// Get the item that an existing occurrence belongs to, by occurrence ID.
+pub fn get_occ_item(db: &impl Db, occ_id: &str) -> DbResult<StoredItem> {
+    let item_id = db.get_occs_item_ids(&[occ_id])?
+        .remove(occ_id)
+        .ok_or(format!("object with given ID does not exist: {occ_id}"))?;
+    get_item(db, &item_id)
+}
+
 /// Get an existing config by ID.
 pub fn get_config(db: &impl Db, id: &ConfigId)
 -> DbResult<Option<StoredConfig>> {
@@ -82,3 +262,8 @@ pub fn get_config(db: &impl Db, id: &ConfigId)
 pub fn get_occ(db: &impl Db, id: &str) -> DbResult<StoredOcc> {
     get_single_helper(id, db.get_occs(&[id]))
 }
+
+/// Get an existing alert by ID.
+pub fn get_alert(db: &impl Db, id: &str) -> DbResult<StoredAlert> {
+    get_single_helper(id, db.get_alerts(&[id]))
+}
@@ -0,0 +1,514 @@
+//! C interface to the library, for clients which embed it directly, such as
+//! native mobile apps.
+//!
+//! Items and occurrences are passed as JSON, serialised in the same way as by
+//! the web server's API: [`Item`], [`StoredItem`], [`Occ`] and [`StoredOcc`].
+//! Dates are RFC 3339 strings.
+//!
+//! Functions which fail return a null pointer or `-1`, and the error message
+//! can then be retrieved with [`dunsumday_last_error`].  Panics are caught
+//! and reported in the same way.  Strings returned by
+//! this library must be freed with [`dunsumday_string_free`].  Functions may
+//! be called from any thread, but a [`DunsumdayDb`] must only be used by one
+//! thread at a time.
+//!
+//! A C header is generated at `include/dunsumday.h` when building with this
+//! module's feature, `ffi`.
+
+use std::cell::RefCell;
+use std::any::Any;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use crate::config;
+use crate::db::{self, util as dbutil, Db, SortDirection, StoredItem,
+                StoredOcc};
+use crate::types::{Item, Occ, OccDate, Sched};
+use crate::util::{occgen, sched};
+
+thread_local! {
+    /// Message for the last error on this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Record an error for [`dunsumday_last_error`].
+fn set_error(error: String) {
+    // messages never contain null bytes, but don't fail if they do
+    let error = CString::new(error.replace('\0', ""))
+        .unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(error));
+}
+
+/// Describe a caught panic.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    let message = payload.downcast_ref::<&str>().copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown error");
+    format!("internal error: {message}")
+}
+
+/// Run `f`, failing instead of unwinding into the caller if it panics.
+fn catch<T>(f: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    // objects left inconsistent by a panic are only reachable through handles
+    // the caller passed in, which are as usable as after any other error
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|payload| Err(panic_message(payload.as_ref())))
+}
+
+/// Get the result of `f`, or a null pointer if it fails.
+fn or_null<T>(f: impl FnOnce() -> Result<*mut T, String>) -> *mut T {
+    catch(f).unwrap_or_else(|e| {
+        set_error(e);
+        ptr::null_mut()
+    })
+}
+
+/// Get `0` if `f` succeeds, or `-1` if it fails.
+fn status(f: impl FnOnce() -> Result<(), String>) -> c_int {
+    match catch(f) {
+        Ok(()) => 0,
+        Err(e) => {
+            set_error(e);
+            -1
+        }
+    }
+}
+
+/// Read a string argument.
+///
+/// # Safety
+///
+/// `value` must be null or a valid null-terminated string.
+unsafe fn read_str<'a>(value: *const c_char, name: &str)
+-> Result<&'a str, String> {
+    if value.is_null() {
+        return Err(format!("{name} must not be null"));
+    }
+    // SAFETY: required of the caller
+    unsafe { CStr::from_ptr(value) }.to_str()
+        .map_err(|e| format!("{name} is not valid UTF-8: {e}"))
+}
+
+/// Read an optional string argument, which is missing if null.
+///
+/// # Safety
+///
+/// `value` must be null or a valid null-terminated string.
+unsafe fn read_opt_str<'a>(value: *const c_char, name: &str)
+-> Result<Option<&'a str>, String> {
+    if value.is_null() {
+        Ok(None)
+    } else {
+        // SAFETY: required of the caller
+        unsafe { read_str(value, name) }.map(Some)
+    }
+}
+
+/// Read a JSON argument.
+///
+/// # Safety
+///
+/// `value` must be null or a valid null-terminated string.
+unsafe fn read_json<T: DeserializeOwned>(value: *const c_char, name: &str)
+-> Result<T, String> {
+    // SAFETY: required of the caller
+    let value = unsafe { read_str(value, name) }?;
+    serde_json::from_str(value).map_err(|e| format!("invalid {name}: {e}"))
+}
+
+/// Read a date argument.
+fn parse_date(value: &str, name: &str) -> Result<OccDate, String> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|date| date.to_utc())
+        .map_err(|e| format!("invalid {name} ({value}): {e}"))
+}
+
+/// Allocate a string to return.
+fn new_string(value: String) -> Result<*mut c_char, String> {
+    CString::new(value)
+        .map(CString::into_raw)
+        .map_err(|e| format!("string contains a null byte: {e}"))
+}
+
+/// Allocate a string containing `value` as JSON to return.
+fn new_json(value: &impl Serialize) -> Result<*mut c_char, String> {
+    serde_json::to_string(value)
+        .map_err(|e| format!("error serialising result: {e}"))
+        .and_then(new_string)
+}
+
+/// Get the database behind a handle.
+///
+/// # Safety
+///
+/// `db` must be null or returned by [`dunsumday_db_open`] and not yet closed,
+/// and not in use by another thread.
+unsafe fn get_db<'a>(db: *mut DunsumdayDb)
+-> Result<&'a mut Box<dyn Db + Send>, String> {
+    // SAFETY: required of the caller
+    unsafe { db.as_mut() }
+        .map(|db| &mut db.db)
+        .ok_or_else(|| "db must not be null".to_owned())
+}
+
+/// Connection to a database.
+pub struct DunsumdayDb {
+    db: Box<dyn Db + Send>,
+}
+
+/// Get the message for the last error on the calling thread, or null if there
+/// hasn't been one.  The string is owned by the library, and is valid until the
+/// next call to a function which fails on the same thread.
+#[no_mangle]
+pub extern "C" fn dunsumday_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow().as_ref().map_or(ptr::null(), |error| error.as_ptr())
+    })
+}
+
+/// Free a string returned by this library.  Does nothing if `value` is null.
+///
+/// # Safety
+///
+/// `value` must be null or a string returned by this library which hasn't
+/// been freed.  [`dunsumday_last_error`]'s result must not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        // SAFETY: required of the caller
+        drop(unsafe { CString::from_raw(value) });
+    }
+}
+
+/// Open the SQLite database at `db_path`, creating or upgrading it using the
+/// schema files in the directory `schema_path` as necessary.  Close it with
+/// [`dunsumday_db_close`].
+///
+/// # Safety
+///
+/// Arguments must be valid null-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_db_open(
+    db_path: *const c_char,
+    schema_path: *const c_char,
+) -> *mut DunsumdayDb {
+    or_null(|| {
+        // SAFETY: required of the caller
+        let db_path = unsafe { read_str(db_path, "db_path") }?;
+        // SAFETY: required of the caller
+        let schema_path = unsafe { read_str(schema_path, "schema_path") }?;
+        let cfg = config::args::new([
+            format!("db.sqlite.db-path={db_path}"),
+            format!("db.sqlite.schema-path={schema_path}"),
+        ]).map_err(|e| e.to_string())?;
+        let db = db::open(&cfg)?;
+        Ok(Box::into_raw(Box::new(DunsumdayDb { db })))
+    })
+}
+
+/// Close a database opened with [`dunsumday_db_open`].  Does nothing if `db`
+/// is null.
+///
+/// # Safety
+///
+/// `db` must be null or returned by [`dunsumday_db_open`] and not yet closed,
+/// and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_db_close(db: *mut DunsumdayDb) {
+    if !db.is_null() {
+        // SAFETY: required of the caller
+        let db = unsafe { Box::from_raw(db) };
+        // nothing is returned, but a panic while closing is still recorded
+        if let Err(e) = catch(|| {
+            drop(db);
+            Ok(())
+        }) {
+            set_error(e);
+        }
+    }
+}
+
+/// Operate on objects owned by the user with ID `user_id`, or objects without
+/// an owner if null.
+///
+/// # Safety
+///
+/// `db` must be an open database not in use by another thread, and `user_id`
+/// must be null or a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_db_set_user(
+    db: *mut DunsumdayDb,
+    user_id: *const c_char,
+) -> c_int {
+    status(|| {
+        // SAFETY: required of the caller
+        let db = unsafe { get_db(db) }?;
+        // SAFETY: required of the caller
+        let user_id = unsafe { read_opt_str(user_id, "user_id") }?;
+        db.set_user(user_id)
+    })
+}
+
+/// Create an item from [`Item`] JSON, returning the new [`StoredItem`] JSON.
+///
+/// # Safety
+///
+/// `db` must be an open database not in use by another thread, and `item` must
+/// be a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_item_create(
+    db: *mut DunsumdayDb,
+    item: *const c_char,
+) -> *mut c_char {
+    or_null(|| {
+        // SAFETY: required of the caller
+        let db = unsafe { get_db(db) }?;
+        // SAFETY: required of the caller
+        let item: Item = unsafe { read_json(item, "item") }?;
+        new_json(&dbutil::create_item(db, item)?)
+    })
+}
+
+/// Get the item with ID `id`, as [`StoredItem`] JSON.  It's an error if there's
+/// no such item.
+///
+/// # Safety
+///
+/// `db` must be an open database not in use by another thread, and `id` must
+/// be a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_item_get(
+    db: *mut DunsumdayDb,
+    id: *const c_char,
+) -> *mut c_char {
+    or_null(|| {
+        // SAFETY: required of the caller
+        let db = unsafe { get_db(db) }?;
+        // SAFETY: required of the caller
+        let id = unsafe { read_str(id, "id") }?;
+        new_json(&dbutil::get_item(db, id)?)
+    })
+}
+
+/// Get all items, as a JSON array of [`StoredItem`].  If `active` is `0` or
+/// `1`, only inactive or active items are included.
+///
+/// # Safety
+///
+/// `db` must be an open database not in use by another thread.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_items_find(
+    db: *mut DunsumdayDb,
+    active: c_int,
+) -> *mut c_char {
+    or_null(|| {
+        // SAFETY: required of the caller
+        let db = unsafe { get_db(db) }?;
+        let active = match active {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        };
+        new_json(&db.find_items(
+            active, None, None, SortDirection::Asc, None, u32::MAX)?)
+    })
+}
+
+/// Replace an item with [`StoredItem`] JSON.
+///
+/// # Safety
+///
+/// `db` must be an open database not in use by another thread, and `item` must
+/// be a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_item_update(
+    db: *mut DunsumdayDb,
+    item: *const c_char,
+) -> c_int {
+    status(|| {
+        // SAFETY: required of the caller
+        let db = unsafe { get_db(db) }?;
+        // SAFETY: required of the caller
+        let item: StoredItem = unsafe { read_json(item, "item") }?;
+        dbutil::update_item(db, &item)
+    })
+}
+
+/// Delete the item with ID `id`.  It's not an error if there's no such item,
+/// but it is if the item has occurrences.
+///
+/// # Safety
+///
+/// `db` must be an open database not in use by another thread, and `id` must
+/// be a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_item_delete(
+    db: *mut DunsumdayDb,
+    id: *const c_char,
+) -> c_int {
+    status(|| {
+        // SAFETY: required of the caller
+        let db = unsafe { get_db(db) }?;
+        // SAFETY: required of the caller
+        let id = unsafe { read_str(id, "id") }?;
+        dbutil::delete_item(db, id)
+    })
+}
+
+/// Create an occurrence of the item with ID `item_id` from [`Occ`] JSON,
+/// returning the new [`StoredOcc`] JSON.
+///
+/// # Safety
+///
+/// `db` must be an open database not in use by another thread, and other
+/// arguments must be valid null-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_occ_create(
+    db: *mut DunsumdayDb,
+    item_id: *const c_char,
+    occ: *const c_char,
+) -> *mut c_char {
+    or_null(|| {
+        // SAFETY: required of the caller
+        let db = unsafe { get_db(db) }?;
+        // SAFETY: required of the caller
+        let item_id = unsafe { read_str(item_id, "item_id") }?;
+        // SAFETY: required of the caller
+        let occ: Occ = unsafe { read_json(occ, "occ") }?;
+        let id = dbutil::create_occ(db, item_id, &occ)?;
+        new_json(&dbutil::get_occ(db, &id)?)
+    })
+}
+
+/// Get the occurrence with ID `id`, as [`StoredOcc`] JSON.  It's an error if
+/// there's no such occurrence.
+///
+/// # Safety
+///
+/// `db` must be an open database not in use by another thread, and `id` must
+/// be a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_occ_get(
+    db: *mut DunsumdayDb,
+    id: *const c_char,
+) -> *mut c_char {
+    or_null(|| {
+        // SAFETY: required of the caller
+        let db = unsafe { get_db(db) }?;
+        // SAFETY: required of the caller
+        let id = unsafe { read_str(id, "id") }?;
+        new_json(&dbutil::get_occ(db, id)?)
+    })
+}
+
+/// Get the stored occurrences of the item with ID `item_id` which overlap the
+/// time range from `start` to `end`, as a JSON array of [`StoredOcc`] ordered
+/// by start date.  `start` and `end` may be null to leave the range open.
+///
+/// # Safety
+///
+/// `db` must be an open database not in use by another thread, `item_id` must
+/// be a valid null-terminated string, and `start` and `end` must be null or
+/// valid null-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_occs_find(
+    db: *mut DunsumdayDb,
+    item_id: *const c_char,
+    start: *const c_char,
+    end: *const c_char,
+) -> *mut c_char {
+    or_null(|| {
+        // SAFETY: required of the caller
+        let db = unsafe { get_db(db) }?;
+        // SAFETY: required of the caller
+        let item_id = unsafe { read_str(item_id, "item_id") }?;
+        // SAFETY: required of the caller
+        let start = unsafe { read_opt_str(start, "start") }?
+            .map(|start| parse_date(start, "start"))
+            .transpose()?;
+        // SAFETY: required of the caller
+        let end = unsafe { read_opt_str(end, "end") }?
+            .map(|end| parse_date(end, "end"))
+            .transpose()?;
+        let occs = db.find_occs(
+            &[item_id], start, end, SortDirection::Asc, None, u32::MAX)?
+            .remove(item_id)
+            .unwrap_or_default();
+        new_json(&occs)
+    })
+}
+
+/// Replace an occurrence with [`StoredOcc`] JSON.
+///
+/// # Safety
+///
+/// `db` must be an open database not in use by another thread, and `occ` must
+/// be a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_occ_update(
+    db: *mut DunsumdayDb,
+    occ: *const c_char,
+) -> c_int {
+    status(|| {
+        // SAFETY: required of the caller
+        let db = unsafe { get_db(db) }?;
+        // SAFETY: required of the caller
+        let occ: StoredOcc = unsafe { read_json(occ, "occ") }?;
+        dbutil::update_occ(db, &occ)
+    })
+}
+
+/// Delete the occurrence with ID `id`.  It's not an error if there's no such
+/// occurrence.
+///
+/// # Safety
+///
+/// `db` must be an open database not in use by another thread, and `id` must
+/// be a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_occ_delete(
+    db: *mut DunsumdayDb,
+    id: *const c_char,
+) -> c_int {
+    status(|| {
+        // SAFETY: required of the caller
+        let db = unsafe { get_db(db) }?;
+        // SAFETY: required of the caller
+        let id = unsafe { read_str(id, "id") }?;
+        dbutil::delete_occ(db, id)
+    })
+}
+
+/// Get the occurrences a new item with [`Sched`] JSON `sched` would have in the
+/// time range from `start` to `end`, as a JSON array of [`Occ`] (see
+/// [`occgen::preview`]).  This doesn't use a database.  It's an error if the
+/// schedule has [problems](sched::problems).
+///
+/// # Safety
+///
+/// Arguments must be valid null-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn dunsumday_sched_preview(
+    sched: *const c_char,
+    start: *const c_char,
+    end: *const c_char,
+) -> *mut c_char {
+    or_null(|| {
+        // SAFETY: required of the caller
+        let sched: Sched = unsafe { read_json(sched, "sched") }?;
+        // SAFETY: required of the caller
+        let start = parse_date(unsafe { read_str(start, "start") }?, "start")?;
+        // SAFETY: required of the caller
+        let end = parse_date(unsafe { read_str(end, "end") }?, "end")?;
+        let problems = sched::problems(&sched);
+        if !problems.is_empty() {
+            return Err(problems.iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; "));
+        }
+        new_json(&occgen::preview(&sched, start, end))
+    })
+}
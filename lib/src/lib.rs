@@ -1,9 +1,37 @@
 //! Library for dunsumday, an application for tracking completion of regular
 //! tasks.
+//!
+//! Cargo features (none enabled by default):
+//!
+//! - `sqlite`: the SQLite [database backend](db::open)
+//! - `yaml`: YAML [config files](config::file)
+//! - `http`: the remote database backend, push notifications, calendar and
+//!   device syncing, and S3 backups
+//! - `email`: email notifications
+//! - `watch`: reloading config files when they change
+//! - `openapi`: OpenAPI schemas for types, using `utoipa`
+//! - `ffi`: [C bindings](ffi), for embedding the library in native apps;
+//!   enables `sqlite`
+//! - `python`: [Python bindings](python); enables `sqlite` and `yaml`
+//!
+//! For simple uses, [`Tracker`](tracker::Tracker) covers the common operations
+//! on a database, and the [prelude] imports the types needed to use it.
+//!
+//! Without any features, the library only depends on crates which build for
+//! `wasm32-unknown-unknown`, so [types], [schedules](util::sched),
+//! [occurrence generation](util::occgen) and
+//! [progress](util::progress::resolve_occs_progress_using) can be used
+//! in a web browser.
 
 #![allow(dead_code, unused_variables)]
 pub mod config;
-mod configrefs;
+pub mod configrefs;
 pub mod db;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod prelude;
+pub mod tracker;
 pub mod types;
 pub mod util;
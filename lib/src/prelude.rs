@@ -0,0 +1,11 @@
+//! Commonly used types and traits, for importing with
+//! `use dunsumday::prelude::*`.
+
+pub use crate::config::Config;
+pub use crate::db::{Db, DbResult, StoredItem, StoredOcc, StoredProgressEntry};
+pub use crate::db::util as dbutil;
+pub use crate::tracker::{CurrentItem, Tracker};
+pub use crate::types::{DayFilter, DeadlineTaskSched, EventSched, Item,
+                       ItemType, Occ, OccDate, ProgressTaskSched, Sched};
+pub use crate::util::progress::TaskProgress;
+pub use crate::util::stats::{CompletionStats, Stats};
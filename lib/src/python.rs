@@ -0,0 +1,363 @@
+//! Python bindings, published as the `dunsumday-py` package, which provides
+//! the `dunsumday` Python module.
+//!
+//! [`Db`](PyDb) opens the database configured by a config file, as the CLI
+//! does.  Items, occurrences and other values are passed as dicts and lists,
+//! serialised in the same way as by the web server's API, so dates are
+//! RFC 3339 strings.  Date arguments are timezone-aware `datetime` objects.
+//! Database errors raise `dunsumday.Error`, and invalid arguments raise
+//! `ValueError`.
+//!
+//! Build the module with `make python`, or with
+//! [maturin](https://www.maturin.rs/) using `lib/pyproject.toml`.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use chrono::TimeDelta;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use crate::config::{self, file, layered};
+use crate::db::{self, util as dbutil, Db, SortDirection, StoredItem,
+                StoredOcc};
+use crate::types::{Item, Occ, OccDate, Sched};
+use crate::util::clock::{self, SharedClock};
+use crate::util::occgen;
+use crate::util::sched;
+use crate::util::stats::{self, CompletionStats};
+
+create_exception!(dunsumday, Error, PyException,
+                  "Error reading or writing the database.");
+
+/// How far ahead [`SchedOccs`] looks for the next occurrence.
+const SCHED_OCCS_WINDOW: TimeDelta = TimeDelta::days(366);
+
+/// Convert a database error.
+fn db_error(error: String) -> PyErr {
+    Error::new_err(error)
+}
+
+/// Convert `value` to Python values by way of JSON.
+fn to_py<'py>(py: Python<'py>, value: &impl Serialize)
+-> PyResult<Bound<'py, PyAny>> {
+    let json = serde_json::to_string(value)
+        .map_err(|e| PyValueError::new_err(
+            format!("error serialising result: {e}")))?;
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+/// Convert an argument from Python values by way of JSON.
+fn from_py<T: DeserializeOwned>(value: &Bound<'_, PyAny>, name: &str)
+-> PyResult<T> {
+    let json: String = value.py().import("json")?
+        .call_method1("dumps", (value,))?
+        .extract()?;
+    serde_json::from_str(&json)
+        .map_err(|e| PyValueError::new_err(format!("invalid {name}: {e}")))
+}
+
+/// Convert a date argument, which must be a timezone-aware `datetime`.
+fn date_from_py(value: &Bound<'_, PyAny>, name: &str) -> PyResult<OccDate> {
+    if value.getattr("tzinfo")?.is_none() {
+        return Err(PyValueError::new_err(
+            format!("{name} must be timezone-aware")));
+    }
+    let utc = value.py().import("datetime")?
+        .getattr("timezone")?
+        .getattr("utc")?;
+    value.call_method1("astimezone", (utc,))?.extract()
+}
+
+/// Convert an optional date argument.
+fn opt_date_from_py(value: Option<&Bound<'_, PyAny>>, name: &str)
+-> PyResult<Option<OccDate>> {
+    value.map(|value| date_from_py(value, name)).transpose()
+}
+
+/// Completion statistics for a group of task occurrences, as returned by
+/// [`PyDb::stats`].
+#[derive(Serialize)]
+struct Summary {
+    #[serde(flatten)]
+    stats: CompletionStats,
+    completion_rate: Option<f64>,
+    average_progress: Option<f64>,
+}
+
+impl From<&CompletionStats> for Summary {
+    fn from(stats: &CompletionStats) -> Summary {
+        Summary {
+            stats: *stats,
+            completion_rate: stats.completion_rate(),
+            average_progress: stats.average_progress(),
+        }
+    }
+}
+
+/// Connection to the database configured by a config file.
+///
+/// `Db(config, set=[], user=None)`: `config` is the path to a YAML or TOML
+/// config file, `set` overrides config values, like `db.sqlite.db-path=path`,
+/// and `user` is the name of the user to operate as, who is created if
+/// necessary.
+#[pyclass(name = "Db", module = "dunsumday")]
+pub struct PyDb {
+    db: Mutex<Box<dyn Db + Send>>,
+    clock: SharedClock,
+}
+
+impl PyDb {
+    fn db(&self) -> PyResult<MutexGuard<'_, Box<dyn Db + Send>>> {
+        self.db.lock()
+            .map_err(|_| Error::new_err("database connection is poisoned"))
+    }
+}
+
+#[pymethods]
+impl PyDb {
+    #[new]
+    #[pyo3(signature = (config, set = Vec::new(), user = None))]
+    fn new(config: PathBuf, set: Vec<String>, user: Option<&str>)
+    -> PyResult<PyDb> {
+        let config_error = |e: config::ConfigError| {
+            PyValueError::new_err(e.to_string())
+        };
+        let cfg = layered::new(vec![
+            Arc::new(config::args::new(&set).map_err(config_error)?),
+            Arc::new(file::new(&config).map_err(config_error)?),
+        ]);
+        let clock = clock::from_config(&cfg).map_err(config_error)?;
+        let mut db = db::open(&cfg).map_err(db_error)?;
+        if let Some(name) = user {
+            let user = dbutil::get_or_create_user(&mut db, name)
+                .map_err(db_error)?;
+            db.set_user(Some(&user.id)).map_err(db_error)?;
+        }
+        Ok(PyDb { db: Mutex::new(db), clock })
+    }
+
+    /// Create an item, returning the stored item.
+    fn create_item<'py>(&self, item: &Bound<'py, PyAny>)
+    -> PyResult<Bound<'py, PyAny>> {
+        let new_item: Item = from_py(item, "item")?;
+        let stored = dbutil::create_item(&mut *self.db()?, new_item)
+            .map_err(db_error)?;
+        to_py(item.py(), &stored)
+    }
+
+    /// Get the stored item with ID `id`.
+    fn get_item<'py>(&self, py: Python<'py>, id: &str)
+    -> PyResult<Bound<'py, PyAny>> {
+        let item = dbutil::get_item(&*self.db()?, id).map_err(db_error)?;
+        to_py(py, &item)
+    }
+
+    /// Get all stored items, ordered by created date, optionally only those
+    /// which are `active` or not, or in `category`.
+    #[pyo3(signature = (active = None, category = None))]
+    fn find_items<'py>(
+        &self,
+        py: Python<'py>,
+        active: Option<bool>,
+        category: Option<&str>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let items = self.db()?
+            .find_items(active, category, None, SortDirection::Asc, None,
+                        u32::MAX)
+            .map_err(db_error)?;
+        to_py(py, &items)
+    }
+
+    /// Replace a stored item.
+    fn update_item(&self, item: &Bound<'_, PyAny>) -> PyResult<()> {
+        let item: StoredItem = from_py(item, "item")?;
+        dbutil::update_item(&mut *self.db()?, &item).map_err(db_error)
+    }
+
+    /// Delete the item with ID `id`, if it exists.  It must have no
+    /// occurrences.
+    fn delete_item(&self, id: &str) -> PyResult<()> {
+        dbutil::delete_item(&mut *self.db()?, id).map_err(db_error)
+    }
+
+    /// Create an occurrence of the item with ID `item_id`, returning the stored
+    /// occurrence.
+    fn create_occ<'py>(&self, item_id: &str, occ: &Bound<'py, PyAny>)
+    -> PyResult<Bound<'py, PyAny>> {
+        let new_occ: Occ = from_py(occ, "occ")?;
+        let mut db = self.db()?;
+        let id = dbutil::create_occ(&mut *db, item_id, &new_occ)
+            .map_err(db_error)?;
+        to_py(occ.py(), &dbutil::get_occ(&*db, &id).map_err(db_error)?)
+    }
+
+    /// Get the stored occurrence with ID `id`.
+    fn get_occ<'py>(&self, py: Python<'py>, id: &str)
+    -> PyResult<Bound<'py, PyAny>> {
+        let occ = dbutil::get_occ(&*self.db()?, id).map_err(db_error)?;
+        to_py(py, &occ)
+    }
+
+    /// Get the stored occurrences of the item with ID `item_id`, ordered by
+    /// start date, optionally only those overlapping the time range from
+    /// `start` to `end`.
+    #[pyo3(signature = (item_id, start = None, end = None))]
+    fn find_occs<'py>(
+        &self,
+        py: Python<'py>,
+        item_id: &str,
+        start: Option<&Bound<'py, PyAny>>,
+        end: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let start = opt_date_from_py(start, "start")?;
+        let end = opt_date_from_py(end, "end")?;
+        let occs = self.db()?
+            .find_occs(&[item_id], start, end, SortDirection::Asc, None,
+                       u32::MAX)
+            .map_err(db_error)?
+            .remove(item_id)
+            .unwrap_or_default();
+        to_py(py, &occs)
+    }
+
+    /// Replace a stored occurrence.
+    fn update_occ(&self, occ: &Bound<'_, PyAny>) -> PyResult<()> {
+        let occ: StoredOcc = from_py(occ, "occ")?;
+        dbutil::update_occ(&mut *self.db()?, &occ).map_err(db_error)
+    }
+
+    /// Delete the occurrence with ID `id`, if it exists.
+    fn delete_occ(&self, id: &str) -> PyResult<()> {
+        dbutil::delete_occ(&mut *self.db()?, id).map_err(db_error)
+    }
+
+    /// Get the stored progress entries for the occurrences with IDs `occ_ids`,
+    /// as a dict from occurrence ID to progress entries ordered by date.
+    fn find_progress_entries<'py>(
+        &self,
+        py: Python<'py>,
+        occ_ids: Vec<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let occ_ids = occ_ids.iter().map(String::as_str).collect::<Vec<_>>();
+        let entries = self.db()?
+            .find_progress_entries(&occ_ids, SortDirection::Asc, u32::MAX)
+            .map_err(db_error)?;
+        to_py(py, &entries)
+    }
+
+    /// Get completion statistics for task occurrences overlapping the time
+    /// range from `start` to `end`, as of `now`, which defaults to the current
+    /// time according to the configured clock.
+    ///
+    /// The result has keys `items`, `categories` and `types`, each a dict from
+    /// item ID, category or item type to statistics.
+    #[pyo3(signature = (start = None, end = None, now = None))]
+    fn stats<'py>(
+        &self,
+        py: Python<'py>,
+        start: Option<&Bound<'py, PyAny>>,
+        end: Option<&Bound<'py, PyAny>>,
+        now: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let start = opt_date_from_py(start, "start")?;
+        let end = opt_date_from_py(end, "end")?;
+        let now = opt_date_from_py(now, "now")?
+            .unwrap_or_else(|| self.clock.now());
+        let stats = stats::get_stats(&*self.db()?, start, end, now)
+            .map_err(db_error)?;
+
+        let summaries = |stats: &HashMap<String, CompletionStats>| {
+            stats.iter()
+                .map(|(key, stats)| (key.clone(), Summary::from(stats)))
+                .collect::<HashMap<_, _>>()
+        };
+        let types = stats.types.iter()
+            .map(|(type_, stats)| {
+                (type_.as_ref().to_owned(), Summary::from(stats))
+            })
+            .collect::<HashMap<_, _>>();
+        to_py(py, &HashMap::from([
+            ("items", summaries(&stats.items)),
+            ("categories", summaries(&stats.categories)),
+            ("types", types),
+        ]))
+    }
+}
+
+/// Iterator over the occurrences an item with a schedule would have, starting
+/// with the first which follows or overlaps a date, if it had no earlier
+/// occurrences.
+///
+/// Iteration stops if there is no occurrence within a year of the previous
+/// one, so it may be endless.
+#[pyclass(module = "dunsumday")]
+pub struct SchedOccs {
+    sched: Sched,
+    start: OccDate,
+    last: Option<Occ>,
+    pending: VecDeque<Occ>,
+}
+
+impl SchedOccs {
+    fn next_occ(&mut self) -> Option<Occ> {
+        if self.pending.is_empty() {
+            let occ_gen = occgen::for_sched(&self.sched);
+            match &self.last {
+                None => self.pending.extend(occ_gen.generate_first(self.start)),
+                Some(last) => self.pending.extend(occ_gen.generate_after(
+                    last, last.end + SCHED_OCCS_WINDOW)),
+            }
+        }
+        let occ = self.pending.pop_front()?;
+        self.last = Some(occ.clone());
+        Some(occ)
+    }
+}
+
+#[pymethods]
+impl SchedOccs {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'py>(&mut self, py: Python<'py>)
+    -> PyResult<Option<Bound<'py, PyAny>>> {
+        self.next_occ().map(|occ| to_py(py, &occ)).transpose()
+    }
+}
+
+/// Iterate over the occurrences an item with schedule `sched` would have,
+/// starting with the first which follows or overlaps `start`.  Nothing is
+/// stored.
+#[pyfunction]
+fn sched_occs(sched: &Bound<'_, PyAny>, start: &Bound<'_, PyAny>)
+-> PyResult<SchedOccs> {
+    let sched: Sched = from_py(sched, "sched")?;
+    let problems = sched::problems(&sched);
+    if !problems.is_empty() {
+        return Err(PyValueError::new_err(problems.iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ")));
+    }
+    Ok(SchedOccs {
+        sched,
+        start: date_from_py(start, "start")?,
+        last: None,
+        pending: VecDeque::new(),
+    })
+}
+
+/// The `dunsumday` Python module.
+#[pymodule]
+#[pyo3(name = "dunsumday")]
+fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("Error", m.py().get_type::<Error>())?;
+    m.add_class::<PyDb>()?;
+    m.add_class::<SchedOccs>()?;
+    m.add_function(wrap_pyfunction!(sched_occs, m)?)?;
+    Ok(())
+}
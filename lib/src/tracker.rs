@@ -0,0 +1,173 @@
+//! High-level interface for tracking items, for simple embedding.
+//!
+//! [`Tracker`] wraps a [database](Db) and covers the common operations:
+//! adding items, logging progress, listing what's current and fetching
+//! stats.  Anything more involved can use the database and
+//! [utilities](crate::util) directly, through [`Tracker::db_mut`].
+
+use std::sync::Arc;
+use serde::Serialize;
+use crate::config::Config;
+use crate::db::{self, Db, DbResult, DbResults, SortDirection, StoredItem,
+                StoredOcc, StoredUser};
+use crate::db::util as dbutil;
+use crate::types::{Item, OccDate, Sched};
+use crate::util;
+use crate::util::clock::{self, SharedClock, SystemClock};
+use crate::util::progress::{self, TaskProgress};
+use crate::util::sched;
+use crate::util::stats::{self, Stats};
+
+/// Current item, as returned by [`Tracker::today`].
+#[derive(Clone, Debug, Serialize)]
+pub struct CurrentItem {
+    pub item: StoredItem,
+    /// The item's [current occurrence](util::get_item_current_occ).
+    pub occ: StoredOcc,
+    /// Missing for events.
+    pub progress: Option<TaskProgress>,
+}
+
+/// Tracks items using a database.
+///
+/// Operations apply at the current date, taken from a [clock](clock::Clock).
+pub struct Tracker<D: Db = Box<dyn Db + Send>> {
+    db: D,
+    clock: SharedClock,
+}
+
+impl Tracker {
+    /// Open the configured database (see [`db::open`]), using the
+    /// [configured clock](clock::from_config).
+    pub fn open<C>(cfg: &C) -> Result<Tracker, String>
+    where
+        C: Config + ?Sized,
+    {
+        Ok(Tracker::with_clock(db::open(cfg)?, clock::from_config(cfg)?))
+    }
+}
+
+impl<D: Db> Tracker<D> {
+    /// Track items using an open database and the system clock.
+    pub fn new(db: D) -> Tracker<D> {
+        Tracker::with_clock(db, Arc::new(SystemClock::default()))
+    }
+
+    /// Track items using an open database, taking the current date from
+    /// `clock`.
+    pub fn with_clock(db: D, clock: SharedClock) -> Tracker<D> {
+        Tracker { db, clock }
+    }
+
+    /// The clock providing the current date.
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+
+    /// The underlying database.
+    pub fn db(&self) -> &D {
+        &self.db
+    }
+
+    /// The underlying database, for operations not covered by the tracker.
+    pub fn db_mut(&mut self) -> &mut D {
+        &mut self.db
+    }
+
+    /// Stop tracking, returning the underlying database.
+    pub fn into_db(self) -> D {
+        self.db
+    }
+
+    /// Track items for the user with the given `name`, creating the user if
+    /// necessary.
+    pub fn set_user(&mut self, name: &str) -> DbResult<StoredUser> {
+        let user = dbutil::get_or_create_user(&mut self.db, name)?;
+        self.db.set_user(Some(&user.id))?;
+        Ok(user)
+    }
+
+    /// Add an active item with the given `name` and schedule.  The item type
+    /// is taken from the schedule.
+    ///
+    /// Fails if the schedule has [problems](sched::problems).
+    pub fn add_item(&mut self, name: &str, sched: Sched)
+    -> Result<StoredItem, String> {
+        if let Some(problem) = sched::problems(&sched).into_iter().next() {
+            return Err(problem.to_string());
+        }
+        dbutil::create_item(&mut self.db, Item {
+            type_: sched.item_type(),
+            active: true,
+            category: None,
+            name: name.to_owned(),
+            desc: None,
+            sched,
+        })
+    }
+
+    /// Find an item by ID or name (see [`dbutil::find_item`]).
+    pub fn item(&self, query: &str) -> DbResult<StoredItem> {
+        dbutil::find_item(&self.db, query)
+    }
+
+    /// Get all active items.
+    pub fn items(&self) -> DbResults<StoredItem> {
+        self.db.find_items(
+            Some(true), None, None, SortDirection::Asc, None, u32::MAX)
+    }
+
+    /// Save changes to an item.
+    pub fn update_item(&mut self, item: &StoredItem) -> DbResult<()> {
+        dbutil::update_item(&mut self.db, item)
+    }
+
+    /// Delete an item by ID.  Fails if the item has any occurrences; make it
+    /// inactive instead.
+    pub fn delete_item(&mut self, id: &str) -> DbResult<()> {
+        dbutil::delete_item(&mut self.db, id)
+    }
+
+    /// Log progress for the current occurrence of a task item, found by ID
+    /// or name.
+    ///
+    /// `amount` defaults to the amount needed to complete the occurrence.
+    /// See [`progress::add_current_progress`].
+    pub fn log_progress(
+        &mut self,
+        item: &str,
+        amount: Option<i64>,
+        note: Option<&str>,
+    ) -> Result<(StoredOcc, TaskProgress), String> {
+        let item = self.item(item)?;
+        Ok(progress::add_current_progress(
+            &mut self.db, &item, amount, note, self.clock.now())?)
+    }
+
+    /// Get current items with their current occurrence and progress,
+    /// generating occurrences as necessary (see
+    /// [`util::get_current_items`]).
+    pub fn today(&mut self) -> DbResults<CurrentItem> {
+        let current = util::get_current_items(
+            &mut self.db, self.clock.now())?;
+        let occs = current.iter()
+            .map(|(item, occ)| (item, occ))
+            .collect::<Vec<_>>();
+        let mut progress = progress::resolve_item_occs_progress(
+            &self.db, &occs)?;
+        Ok(current.into_iter()
+            .map(|(item, occ)| CurrentItem {
+                progress: progress.remove(&occ.id),
+                item,
+                occ,
+            })
+            .collect())
+    }
+
+    /// Get completion statistics for occurrences between `start` and `end`,
+    /// either of which may be unbounded (see [`stats::get_stats`]).
+    pub fn stats(&self, start: Option<OccDate>, end: Option<OccDate>)
+    -> DbResult<Stats> {
+        stats::get_stats(&self.db, start, end, self.clock.now())
+    }
+}
@@ -12,9 +12,19 @@ fn opt_duration_to_chrono(duration: &Option<Duration>) -> chrono::TimeDelta {
         .unwrap_or(chrono::TimeDelta::MAX)
 }
 
+/// Schema for [`Duration`] values, as serialised.
+#[cfg(feature = "openapi")]
+#[derive(utoipa::ToSchema)]
+#[schema(as = Duration)]
+pub(crate) struct DurationSchema {
+    secs: u64,
+    nanos: u32,
+}
+
 /// Allowed types for [items](Item).
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize,
          strum::AsRefStr, strum::EnumString)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum ItemType {
     /// Occurrences are fixed points in time according to the schedule.
     Event,
@@ -27,6 +37,7 @@ pub enum ItemType {
 
 /// Describes the days an event occurs on.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum DayFilter {
     /// Once every `days_apart` days.
     Day {
@@ -34,11 +45,13 @@ pub enum DayFilter {
     },
     /// Once every `weeks_apart` weeks, always falling on the same `day`.
     Dow {
+        #[cfg_attr(feature = "openapi", schema(value_type = String))]
         day: chrono::Weekday,
         weeks_apart: u32,
     },
     /// On every one of the specified `days` of the week.
     Dows {
+        #[cfg_attr(feature = "openapi", schema(value_type = Vec<String>))]
         days: Vec<chrono::Weekday>,
     },
     /// On every one of the specified `days` of the month, every `months_apart`
@@ -53,6 +66,7 @@ pub enum DayFilter {
     /// specified day of the week `dow`, every `months_apart` months.  For
     /// example, "every 2nd and 3rd Tuesday of every 6th month".
     Wom {
+        #[cfg_attr(feature = "openapi", schema(value_type = String))]
         dow: chrono::Weekday,
         /// Starting from 1, meaning the first occurrence of the specified day
         /// of the week.
@@ -64,12 +78,14 @@ pub enum DayFilter {
     Doy {
         /// Day starting from 1, using the last day instead if doesn't exist.
         dom: u8,
+        #[cfg_attr(feature = "openapi", schema(value_type = String))]
         month: chrono::Month,
         years_apart: u32,
     },
     /// On the specific day of the month `dom`, `month`, and `year`.
     Date {
         dom: u8,
+        #[cfg_attr(feature = "openapi", schema(value_type = String))]
         month: chrono::Month,
         /// A `chrono` year, i.e. negative values are BCE.
         year: i32,
@@ -79,6 +95,7 @@ pub enum DayFilter {
 
 /// Schedule for events.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct EventSched {
     /// The first date the event occurs on.
     pub initial_day: chrono::NaiveDate,
@@ -90,6 +107,7 @@ pub struct EventSched {
 
 /// Schedule for progress tasks.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum ProgressTaskSched {
     /// Duration of `num` days.
     Days {
@@ -98,6 +116,7 @@ pub enum ProgressTaskSched {
     /// Duration of `num` weeks, always starting on day of the week `start_day`.
     Weeks {
         num: u8,
+        #[cfg_attr(feature = "openapi", schema(value_type = String))]
         start_day: chrono::Weekday,
     },
     /// Duration of `num` months, always starting on day of the month
@@ -111,6 +130,7 @@ pub enum ProgressTaskSched {
     /// and month `start_month`.
     Years {
         num: u8,
+        #[cfg_attr(feature = "openapi", schema(value_type = String))]
         start_month: chrono::Month,
         /// Starting from 1.
         start_dom: u8,
@@ -119,8 +139,10 @@ pub enum ProgressTaskSched {
 
 /// Schedule for deadline tasks.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct DeadlineTaskSched {
     /// Time from completing the task to the next deadline.
+    #[cfg_attr(feature = "openapi", schema(value_type = DurationSchema))]
     pub duration: Duration,
 }
 
@@ -129,15 +151,29 @@ pub struct DeadlineTaskSched {
 /// Should match the [item type](ItemType), but there is nothing to enforce
 /// this.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum Sched {
     Event(EventSched),
     ProgressTask(ProgressTaskSched),
     DeadlineTask(DeadlineTaskSched),
 }
 
+impl Sched {
+    /// The type of item this schedule is for.
+    pub fn item_type(&self) -> ItemType {
+        match self {
+            Sched::Event(_) => ItemType::Event,
+            Sched::ProgressTask(_) => ItemType::ProgressTask,
+            Sched::DeadlineTask(_) => ItemType::DeadlineTask,
+        }
+    }
+}
+
 /// An event or task.
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Item {
+    #[serde(rename = "type")]
     pub type_: ItemType,
     /// Whether the item is being tracked.
     pub active: bool,
@@ -148,6 +184,14 @@ pub struct Item {
     pub sched: Sched,
 }
 
+/// A person tracking their own items.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct User {
+    /// Unique among users.
+    pub name: String,
+}
+
 /// Type of date used for occurrences.
 pub type OccDate = chrono::DateTime<chrono::offset::Utc>;
 
@@ -155,24 +199,204 @@ pub type OccDate = chrono::DateTime<chrono::offset::Utc>;
 ///
 /// This is the period of time across which a task is to be completed, or the
 /// point in time for an instance of an event.
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Occ {
     /// Whether the occurrence is being tracked.
     pub active: bool,
     /// Start of the occurrence period.
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = String, format = DateTime))]
     pub start: OccDate,
     /// End of the occurrence period.
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = String, format = DateTime))]
     pub end: OccDate,
     /// For tasks, this is used to track progress.  Any non-zero value counts as
     /// 'completed' for tasks without a [configured](TaskCompletionConfig)
     /// target completion amount.
     pub task_completion_progress: u32,
+    /// Alerts aren't shown for the occurrence before this date.
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = Option<String>, format = DateTime))]
+    pub snoozed_until: Option<OccDate>,
+}
+
+/// A change to an occurrence's
+/// [progress](Occ::task_completion_progress).
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ProgressEntry {
+    /// When the progress was logged.
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = String, format = DateTime))]
+    pub date: OccDate,
+    /// Change in progress.  This is negative for entries reducing progress.
+    pub amount: i64,
+    /// Free-text note about the progress.
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Reason for alerting about an occurrence.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize,
+         strum::AsRefStr, strum::EnumString)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum AlertKind {
+    /// The occurrence entered its alert period (see
+    /// [`Config::occ_alert`]).
+    Upcoming,
+    /// The occurrence missed its target.
+    Overdue,
+    /// The deadline task occurrence was still incomplete after its
+    /// [escalation](NotificationConfig::escalation) period.
+    Escalated,
+}
+
+/// How urgent an alert is.  Each channel maps these to its own priorities.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Deserialize,
+         Serialize, strum::AsRefStr, strum::EnumString)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Priority {
+    Min,
+    Low,
+    #[default]
+    Default,
+    High,
+    Max,
+}
+
+/// Record of an alert sent about an occurrence.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Alert {
+    pub kind: AlertKind,
+    /// When the alert was sent.
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = String, format = DateTime))]
+    pub sent: OccDate,
+    /// When the alert was acknowledged.  Acknowledged alerts are never sent
+    /// again.
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = Option<String>, format = DateTime))]
+    pub acked: Option<OccDate>,
+    /// The alert is sent again once this date passes, unless it has been
+    /// acknowledged.
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = Option<String>, format = DateTime))]
+    pub snoozed_until: Option<OccDate>,
+}
+
+/// Kind of achievement reached by completing a task.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize,
+         strum::AsRefStr, strum::EnumString)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum MilestoneKind {
+    /// A number of occurrences were completed in total (see
+    /// [`MilestoneConfig::completions`]).
+    Completions,
+    /// A number of occurrences were completed in a row (see
+    /// [`MilestoneConfig::streaks`]).
+    Streak,
+    /// Progress logged over a calendar year reached a total (see
+    /// [`MilestoneConfig::yearly_total`]).
+    YearlyTotal,
+}
+
+/// Record of a milestone reached by an item.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Milestone {
+    pub kind: MilestoneKind,
+    /// Number of completions, length of streak or yearly total reached.
+    pub threshold: u32,
+    /// Year in which a yearly total was reached, in UTC.  Missing for other
+    /// kinds.
+    #[serde(default)]
+    pub year: Option<i32>,
+    /// When the milestone was found to have been reached.
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = String, format = DateTime))]
+    pub reached: OccDate,
+}
+
+/// Which occurrences excess progress may be transferred from, relative to the
+/// receiving occurrence.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Deserialize,
+         Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum TransferDirection {
+    /// No progress is transferred.
+    Disabled,
+    /// Progress is only received from earlier occurrences.
+    PastOnly,
+    /// Progress is only received from later occurrences.
+    FutureOnly,
+    /// Progress is received from both earlier and later occurrences.
+    #[default]
+    Both,
+}
+
+impl TransferDirection {
+    /// Whether progress may be received from earlier occurrences.
+    pub fn allows_past(&self) -> bool {
+        matches!(self, TransferDirection::PastOnly | TransferDirection::Both)
+    }
+
+    /// Whether progress may be received from later occurrences.
+    pub fn allows_future(&self) -> bool {
+        matches!(self, TransferDirection::FutureOnly | TransferDirection::Both)
+    }
+}
+
+/// How an occurrence's excess progress is shared between the occurrences that
+/// may receive it.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Deserialize,
+         Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum TransferOrder {
+    /// Nearer occurrences receive as much as they need first.
+    #[default]
+    NearestFirst,
+    /// Excess is split between occurrences in proportion to how much they need.
+    Proportional,
+}
+
+/// Controls transfer of excess progress between occurrences.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Deserialize,
+         Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TransferPolicy {
+    /// Applies to the occurrence receiving progress.
+    pub direction: TransferDirection,
+    /// Applies to the occurrence donating progress.
+    pub order: TransferOrder,
+}
+
+/// How an occurrence's progress is compared to its target completion amount.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Deserialize,
+         Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum TargetKind {
+    /// Reaching the target counts as success.
+    #[default]
+    AtLeast,
+    /// Staying at or under the target counts as success, for example "at most
+    /// 2 takeaway meals per week".  Progress is never transferred between
+    /// occurrences with this kind of target.
+    AtMost,
 }
 
 /// Configuration that applies to progress tasks.
 ///
 /// Also see [Config].
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct TaskCompletionConfig {
     /// Target completion amount.
     pub total: Option<u32>,
@@ -180,10 +404,22 @@ pub struct TaskCompletionConfig {
     pub unit: Option<String>,
     /// Excess completion from other occurrences can count towards this
     /// occurrence up to this far in the past.
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = Option<DurationSchema>))]
     pub excess_past: Option<Duration>,
     /// Excess completion from other occurrences can count towards this
     /// occurrence up to this far in the future.
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = Option<DurationSchema>))]
     pub excess_future: Option<Duration>,
+    /// How excess completion is transferred between occurrences.  Defaults to
+    /// transferring in both directions, nearest first.
+    #[serde(default)]
+    pub transfer_policy: Option<TransferPolicy>,
+    /// How progress is compared to `total`.  Defaults to
+    /// [`TargetKind::AtLeast`].
+    #[serde(default)]
+    pub target_kind: Option<TargetKind>,
 }
 
 impl TaskCompletionConfig {
@@ -198,6 +434,117 @@ impl TaskCompletionConfig {
     }
 }
 
+/// Configuration for alerts sent about occurrences.
+///
+/// Also see [Config].
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NotificationConfig {
+    /// Whether to send alerts by email, when email notifications are
+    /// configured.  Defaults to `true`.
+    #[serde(default)]
+    pub email: Option<bool>,
+    /// Whether to send alerts at all.  Defaults to `true`.
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    /// Names of the channels to send alerts through, such as `email` or
+    /// `ntfy`.  Defaults to every channel.
+    #[serde(default)]
+    pub channels: Option<Vec<String>>,
+    /// Alerts which are due during this period are held back until it ends.
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    /// Send alerts again this often, for as long as they still apply.
+    /// Defaults to only sending each alert once.
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = Option<DurationSchema>))]
+    pub repeat: Option<Duration>,
+    /// How to escalate alerts for deadline task occurrences which stay
+    /// incomplete after their deadline.  Defaults to no escalation.
+    #[serde(default)]
+    pub escalation: Option<Escalation>,
+}
+
+impl NotificationConfig {
+    /// Whether alerts should be sent through the channel called `name`.
+    pub fn channel_enabled(&self, name: &str) -> bool {
+        self.channels.as_ref()
+            .is_none_or(|channels| channels.iter().any(|c| c == name))
+    }
+}
+
+/// Configuration for [milestones](Milestone) reached by completing a task.
+///
+/// Also see [Config].  Config applied to individual occurrences is ignored.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MilestoneConfig {
+    /// Whether to look for milestones at all.  Defaults to `true`.
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    /// Numbers of completed occurrences which are milestones.  Defaults to the
+    /// globally configured numbers.
+    #[serde(default)]
+    pub completions: Option<Vec<u32>>,
+    /// Numbers of occurrences completed in a row which are milestones.
+    /// Defaults to the globally configured numbers.
+    #[serde(default)]
+    pub streaks: Option<Vec<u32>>,
+    /// Amount of progress logged over a calendar year which is a milestone.
+    /// Defaults to no yearly milestone.
+    #[serde(default)]
+    pub yearly_total: Option<u32>,
+}
+
+/// Rule for escalating alerts about overdue deadline task occurrences.
+///
+/// Once an occurrence has been incomplete for `after` past its deadline, an
+/// [escalated](AlertKind::Escalated) alert is sent in place of the overdue
+/// alert.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Escalation {
+    /// How long after the deadline to escalate.
+    #[cfg_attr(feature = "openapi", schema(value_type = DurationSchema))]
+    pub after: Duration,
+    /// Names of extra channels to send escalated alerts through, in addition
+    /// to the usual [channels](NotificationConfig::channels).  These must
+    /// still be configured globally.
+    #[serde(default)]
+    pub channels: Vec<String>,
+    /// Priority to send escalated alerts with.  Defaults to each channel's
+    /// priority for overdue alerts.
+    #[serde(default)]
+    pub priority: Option<Priority>,
+}
+
+/// Daily period during which alerts aren't sent, in local time (see
+/// [`CLOCK_UTC_OFFSET`](crate::configrefs::CLOCK_UTC_OFFSET)).
+///
+/// If `end` is before `start`, the period crosses midnight, for example from
+/// 22:00 to 07:00.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct QuietHours {
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub start: chrono::NaiveTime,
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub end: chrono::NaiveTime,
+}
+
+impl QuietHours {
+    /// Whether `time` falls within the period.  The start is included, and the
+    /// end is not.
+    pub fn contains(&self, time: chrono::NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
 /// Configuration for occurrences.
 ///
 /// Via [ConfigId](crate::db::ConfigId), this can be applied to different
@@ -209,13 +556,21 @@ impl TaskCompletionConfig {
 /// over config applied to an item.  For each field, the value is taken from the
 /// config with the highest precedence which has a value.
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Config {
     /// How long before an occurrence to show alerts/notifications for it.  For
     /// events and progress tasks, this is the start; for deadline tasks, this
     /// is the deadline (end).
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = Option<DurationSchema>))]
     pub occ_alert: Option<Duration>,
     /// Applies to progress tasks.
     pub task_completion_conf: TaskCompletionConfig,
+    #[serde(default)]
+    pub notification_conf: NotificationConfig,
+    /// Applies to tasks.
+    #[serde(default)]
+    pub milestone_conf: MilestoneConfig,
 }
 
 impl Config {
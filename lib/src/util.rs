@@ -1,16 +1,35 @@
 //! General high-level utilities.
 
 use std::collections::HashMap;
-use chrono::offset::Utc;
-use crate::db::{Db, DbResult, DbResults, DbUpdate, IdToken, UpdateId,
-                SortDirection, StoredItem, StoredOcc};
+use std::time::Duration;
+use crate::db::{Db, DbResult, DbResults, IdToken, UpdateId, SortDirection,
+                StoredItem, StoredOcc, WriteBatch};
 use crate::types::{Occ, OccDate, Sched};
 use self::config::ResolvedConfig;
 
-mod occgen;
+pub mod backup;
+pub mod checklist;
+pub mod clock;
 pub mod config;
+pub mod dashboard;
+pub mod devicesync;
+pub mod export;
+#[cfg(feature = "http")]
+pub mod gcal;
+pub mod habits;
+pub mod history;
+pub mod ical;
+pub mod import;
+pub mod milestones;
+pub mod notify;
+pub mod occgen;
 pub mod progress;
+pub mod report;
 pub mod sched;
+pub mod stats;
+pub mod status;
+pub mod sync;
+pub mod taskwarrior;
 
 /// Determine whether `occ` is valid as an item's "current occurrence", relative
 /// to the given `date`.
@@ -21,76 +40,199 @@ fn occ_is_current(date: OccDate, sched: &Sched, occ: &Occ) -> bool {
     }
 }
 
-/// Get the "current occurrence" for each of the given `items`, relative to the
-/// given `date`.
+/// "Current occurrence" of an item, found without storing anything (see
+/// [`preview_items_current_occ`]).
+#[derive(Clone, Debug)]
+pub struct CurrentOccPreview<'i> {
+    pub item: &'i StoredItem,
+    /// Occurrences generated since the item's last stored occurrence, in
+    /// order.  These need to be stored (see [`store_current_occs`]) for
+    /// occurrences to be generated consistently.
+    pub new_occs: Vec<Occ>,
+    /// The current occurrence, if the item has one.  If it hasn't been stored,
+    /// it's the last of `new_occs`, and has no ID.
+    pub current: Option<RangeOcc<'i>>,
+}
+
+/// Find the "current occurrence" for each of the given `items`, relative to
+/// the given `date`, like [`get_items_current_occ`], but without storing
+/// generated occurrences.
 ///
-/// Not every item has a current occurrence.  For events, this is the next
-/// occurrence.
-pub fn get_items_current_occ<'i>(
-    db: &mut impl Db,
+/// The result contains an entry for every item, in the same order.
+#[tracing::instrument(level = "debug", skip_all,
+                      fields(%date, items = items.len()))]
+pub fn preview_items_current_occ<'i>(
+    db: &impl Db,
     date: OccDate,
-    items: &[&'i StoredItem]
-) -> DbResult<Vec<(&'i StoredItem, StoredOcc)>> {
-    let mut new_occs = HashMap::<IdToken, (&str, Occ)>::new();
-    let mut items_last_token = Vec::<(&StoredItem, IdToken)>::new();
-    let mut items_last_occ = Vec::<(&StoredItem, StoredOcc)>::new();
-
+    items: &[&'i StoredItem],
+) -> DbResult<Vec<CurrentOccPreview<'i>>> {
+    let latest_occs = find_latest_occs(db, items)?;
+    let mut previews = Vec::new();
+    // indices into `previews` of items with occs generated past `date`
+    let mut ahead = Vec::new();
     for item in items {
-        let occ_gen: Box<dyn occgen::OccGen> = match &item.item.sched {
-            Sched::Event(sched) => Box::new(occgen::EventOccGen { sched }),
-            Sched::ProgressTask(sched) =>
-                Box::new(occgen::ProgressTaskOccGen { sched }),
-            Sched::DeadlineTask(sched) =>
-                Box::new(occgen::DeadlineTaskOccGen { sched }),
-        };
-
-        let mut item_occs = db.find_occs(
-            &[&item.id], None, None, SortDirection::Desc, 1)?;
-        let item_occ = item_occs.remove(&item.id)
-            .and_then(|mut occs| occs.pop());
-        let mut item_new_occs = match &item_occ {
+        let occ_gen = occgen::for_item(&item.item);
+        let item_occ = latest_occs.get(&item.id).cloned();
+        let mut new_occs = match &item_occ {
             Some(occ) => occ_gen.generate_after(&occ.occ, date),
             None => occ_gen.generate_first(date).iter().cloned().collect(),
         };
+        // sort so last will become current
+        new_occs.sort_by_key(|occ| occ.start);
 
-        if !item_new_occs.is_empty() {
-            // sort so last will become current
-            item_new_occs.sort_by_key(|occ| occ.start);
-            let mut last_token = 0;
-            for occ in item_new_occs {
-                last_token = DbUpdate::id_token();
-                new_occs.insert(last_token, (&item.id, occ));
+        let current = match (new_occs.last(), item_occ) {
+            (Some(occ), _) => {
+                Some(RangeOcc { item, id: None, occ: occ.clone() })
             }
-            items_last_token.push((item, last_token));
-        } else {
-            // no new occs: current is the one we already found
-            if let Some(item_occ_value) = item_occ {
-                items_last_occ.push((item, item_occ_value));
+            // no new occs: current is the one we already found, unless later
+            // occs were generated ahead of time
+            (None, Some(item_occ)) if item_occ.occ.start > date => {
+                ahead.push(previews.len());
+                None
             }
-        }
+            (None, Some(StoredOcc { id, occ })) => {
+                Some(RangeOcc { item, id: Some(id), occ })
+            }
+            (None, None) => None,
+        };
+        previews.push(CurrentOccPreview { item, new_occs, current });
     }
 
-    let mut updates = Vec::new();
-    for (id_token, (item_id, occ)) in &new_occs {
-        updates.push(DbUpdate::create_occ(
-            *id_token, UpdateId::Id(item_id), occ));
+    let ahead_items = ahead.iter()
+        .map(|index| previews[*index].item)
+        .collect::<Vec<_>>();
+    let current_occs = find_current_occs(db, date, &ahead_items)?;
+    for index in ahead {
+        let preview = &mut previews[index];
+        preview.current = current_occs.get(&preview.item.id)
+            .map(|occ| RangeOcc {
+                item: preview.item,
+                id: Some(occ.id.clone()),
+                occ: occ.occ.clone(),
+            });
     }
-    let update_refs: Vec<&DbUpdate> = updates.iter().collect();
-    let mut new_occ_ids = db.write(&update_refs[..])?;
-    for (item, id_token) in items_last_token {
-        if let Some(occ_id) = new_occ_ids.remove(&id_token) {
-            if let Some((_, occ)) = new_occs.remove(&id_token) {
-                items_last_occ.push((item, StoredOcc { id: occ_id, occ }));
-            }
+    for preview in &mut previews {
+        let sched = &preview.item.item.sched;
+        preview.current = preview.current.take()
+            .filter(|current| occ_is_current(date, sched, &current.occ));
+    }
+    tracing::debug!(
+        occs = previews.iter().map(|p| p.new_occs.len()).sum::<usize>(),
+        "generated occurrences");
+    Ok(previews)
+}
+
+/// Store the occurrences generated by [`preview_items_current_occ`].
+///
+/// Returns each item which has a current occurrence, with that occurrence.
+pub fn store_current_occs<'i>(
+    db: &mut impl Db,
+    previews: Vec<CurrentOccPreview<'i>>,
+) -> DbResult<Vec<(&'i StoredItem, StoredOcc)>> {
+    let mut batch = WriteBatch::new();
+    // index into `previews`, with the ID token of the current occ
+    let mut current_tokens = HashMap::<usize, IdToken>::new();
+    for (index, preview) in previews.iter().enumerate() {
+        for occ in &preview.new_occs {
+            let id_token = batch.create_occ(
+                UpdateId::Id(&preview.item.id), occ);
+            current_tokens.insert(index, id_token);
+        }
+    }
+    // avoid writing when occs were generated ahead of time
+    let mut new_occ_ids = if batch.is_empty() {
+        HashMap::new()
+    } else {
+        batch.write(db)?
+    };
+
+    let mut current_occs = Vec::new();
+    for (index, preview) in previews.into_iter().enumerate() {
+        let Some(current) = preview.current else { continue };
+        let id = match current.id {
+            Some(id) => Some(id),
+            None => current_tokens.get(&index)
+                .and_then(|id_token| new_occ_ids.remove(id_token)),
+        };
+        if let Some(id) = id {
+            current_occs.push(
+                (preview.item, StoredOcc { id, occ: current.occ }));
         }
     }
+    Ok(current_occs)
+}
+
+/// Get the "current occurrence" for each of the given `items`, relative to the
+/// given `date`, storing any occurrences which need to be generated first.
+///
+/// Not every item has a current occurrence.  For events, this is the next
+/// occurrence.
+pub fn get_items_current_occ<'i>(
+    db: &mut impl Db,
+    date: OccDate,
+    items: &[&'i StoredItem]
+) -> DbResult<Vec<(&'i StoredItem, StoredOcc)>> {
+    let previews = preview_items_current_occ(db, date, items)?;
+    store_current_occs(db, previews)
+}
 
-    Ok(items_last_occ.iter()
-        .filter(|(i, o)| occ_is_current(date, &i.item.sched, &o.occ))
-        .cloned()
+/// Find the latest stored occurrence of each of the given `items`, by item
+/// ID, in a single query.
+fn find_latest_occs(db: &impl Db, items: &[&StoredItem])
+-> DbResult<HashMap<String, StoredOcc>> {
+    // no item IDs would mean all items
+    if items.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let item_ids = items.iter()
+        .map(|item| item.id.as_str())
+        .collect::<Vec<_>>();
+    Ok(db.find_occs_per_item(&item_ids, None, None, SortDirection::Desc, 1)?
+        .into_iter()
+        .filter_map(|(item_id, mut occs)| {
+            occs.pop().map(|occ| (item_id, occ))
+        })
         .collect())
 }
 
+/// Find the stored occurrence of each of the given `items` which is current
+/// at `date`, by item ID, where occurrences may have been generated past
+/// `date` (see [`occ_is_current`]).
+fn find_current_occs(db: &impl Db, date: OccDate, items: &[&StoredItem])
+-> DbResult<HashMap<String, StoredOcc>> {
+    // stored dates have second precision
+    let second = chrono::TimeDelta::seconds(1);
+    let (events, tasks): (Vec<&StoredItem>, Vec<&StoredItem>) = items.iter()
+        .partition(|item| matches!(item.item.sched, Sched::Event(_)));
+    let mut current_occs = HashMap::new();
+    for (group, end, sort) in [
+        (events, None, SortDirection::Asc),
+        // where one occ ends as the next starts, the next is current, as when
+        // generating occs
+        (tasks, Some(date + second), SortDirection::Desc),
+    ] {
+        // no item IDs would mean all items
+        if group.is_empty() {
+            continue;
+        }
+        let item_ids = group.iter()
+            .map(|item| item.id.as_str())
+            .collect::<Vec<_>>();
+        let mut occs = db.find_occs_per_item(
+            &item_ids, Some(date - second), end, sort, 2)?;
+        for item in group {
+            let current = occs.remove(&item.id)
+                .unwrap_or_default()
+                .into_iter()
+                .find(|occ| occ_is_current(date, &item.item.sched, &occ.occ));
+            if let Some(occ) = current {
+                current_occs.insert(item.id.clone(), occ);
+            }
+        }
+    }
+    Ok(current_occs)
+}
+
 /// Get the "current occurrence" for an `item`, relative to the given `date`.
 ///
 /// See [`get_items_current_occ`] for details.
@@ -112,7 +254,7 @@ pub fn get_item_current_occ(
 pub fn get_current_items(db: &mut impl Db, date: OccDate)
 -> DbResults<(StoredItem, StoredOcc)> {
     let items = db.find_items(
-        Some(true), Some(date), SortDirection::Asc, u32::MAX)?;
+        Some(true), None, Some(date), SortDirection::Asc, None, u32::MAX)?;
     let item_refs: Vec<&StoredItem> = items.iter().collect();
     let mut occs_by_item = get_items_current_occ(db, date, &item_refs)?
         .into_iter().collect::<HashMap<_, _>>();
@@ -130,11 +272,156 @@ pub fn get_current_items(db: &mut impl Db, date: OccDate)
         }).collect())
 }
 
+/// Occurrence found by [`get_occs_in_range`] or
+/// [`preview_items_current_occ`].
+#[derive(Clone, Debug)]
+pub struct RangeOcc<'i> {
+    pub item: &'i StoredItem,
+    /// Missing for occurrences which haven't been generated yet.
+    pub id: Option<String>,
+    pub occ: Occ,
+}
+
+/// Determine whether `occ` overlaps the time range from `start` to `end`.
+/// Events overlap if they happen at `start`, but not at `end`.
+pub fn occ_overlaps(occ: &Occ, start: OccDate, end: OccDate) -> bool {
+    occ.start < end && (occ.end > start || occ.start >= start)
+}
+
+/// Get occurrences of `items` which overlap the time range from `start` to
+/// `end`, including those which haven't been generated yet.
+///
+/// Occurrences are generated as far as `end`, but aren't stored.  Items with no
+/// occurrences have their first occurrence generated relative to `start` or
+/// `now`, whichever is later.  Generated deadline task occurrences assume each
+/// previous occurrence is completed at its deadline.
+///
+/// The results are ordered by start date.
+#[tracing::instrument(level = "debug", skip_all,
+                      fields(%start, %end, items = items.len()))]
+pub fn get_occs_in_range<'i>(
+    db: &impl Db,
+    items: &[&'i StoredItem],
+    start: OccDate,
+    end: OccDate,
+    now: OccDate,
+) -> DbResult<Vec<RangeOcc<'i>>> {
+    // no item IDs would mean all items
+    if items.is_empty() {
+        return Ok(vec![]);
+    }
+    let item_ids = items.iter()
+        .map(|item| item.id.as_str())
+        .collect::<Vec<_>>();
+    // stored dates have second precision, so this also finds events
+    // happening at `start`
+    let query_start = start - chrono::TimeDelta::seconds(1);
+    let mut stored = db.find_occs(&item_ids, Some(query_start), Some(end),
+                                  SortDirection::Asc, None, u32::MAX)?;
+    let latest_occs = find_latest_occs(db, items)?;
+
+    let mut occs = Vec::new();
+    for item in items {
+        for occ in stored.remove(&item.id).unwrap_or_default() {
+            if occ_overlaps(&occ.occ, start, end) {
+                occs.push(RangeOcc { item, id: Some(occ.id), occ: occ.occ });
+            }
+        }
+
+        let occ_gen = occgen::for_item(&item.item);
+        let new_occs = match latest_occs.get(&item.id) {
+            Some(occ) => occ_gen.generate_after(&occ.occ, end),
+            None => match occ_gen.generate_first(start.max(now)) {
+                Some(first) => {
+                    let mut new_occs = occ_gen.generate_after(&first, end);
+                    new_occs.insert(0, first);
+                    new_occs
+                }
+                None => vec![],
+            },
+        };
+        for occ in new_occs {
+            if occ_overlaps(&occ, start, end) {
+                occs.push(RangeOcc { item, id: None, occ });
+            }
+        }
+    }
+
+    occs.sort_by_key(|occ| occ.occ.start);
+    Ok(occs)
+}
+
+/// Generate and store occurrences for all active items, up to `horizon` after
+/// `now`, so that they don't need to be generated when they become current.
+///
+/// This only operates on items owned by the database's current user.  Returns
+/// the number of occurrences created.
+#[tracing::instrument(level = "debug", skip_all, fields(%now, ?horizon))]
+pub fn generate_ahead(db: &mut impl Db, now: OccDate, horizon: Duration)
+-> DbResult<usize> {
+    let horizon = chrono::TimeDelta::from_std(horizon)
+        .map_err(|e| format!("generation horizon is too long: {e}"))?;
+    let until = now.checked_add_signed(horizon).unwrap_or(OccDate::MAX_UTC);
+    let items = db.find_items(
+        Some(true), None, None, SortDirection::Asc, None, u32::MAX)?;
+
+    let item_refs = items.iter().collect::<Vec<_>>();
+    let mut latest_occs = find_latest_occs(db, &item_refs)?;
+
+    let mut new_occs = Vec::new();
+    for item in &items {
+        let occ_gen = occgen::for_item(&item.item);
+        let last_occ = latest_occs.remove(&item.id);
+        let item_occs = match last_occ {
+            Some(occ) => occ_gen.generate_after(&occ.occ, until),
+            None => match occ_gen.generate_first(now) {
+                Some(first) => {
+                    let mut occs = occ_gen.generate_after(&first, until);
+                    occs.insert(0, first);
+                    occs
+                }
+                None => vec![],
+            },
+        };
+        new_occs.extend(item_occs.into_iter().map(|occ| (&item.id, occ)));
+    }
+
+    let mut batch = WriteBatch::new();
+    for (item_id, occ) in &new_occs {
+        batch.create_occ(UpdateId::Id(item_id), occ);
+    }
+    if !batch.is_empty() {
+        batch.write(db)?;
+    }
+    tracing::debug!(occs = batch.len(), "generated occurrences ahead");
+    Ok(batch.len())
+}
+
 /// Determine whether `date` is in `occ`'s alert period, according to the
 /// `config`.
-pub fn in_alert_period(occ: &Occ, config: &ResolvedConfig, date: OccDate)
--> bool {
-    let alert_start = occ.end - config.resolved_config.occ_alert_chrono();
-    let now = Utc::now();
-    now >= alert_start && now < occ.end
+///
+/// `default_alert` is used when the config doesn't set
+/// [`occ_alert`](crate::types::Config::occ_alert) (see
+/// [`OCC_ALERT_DEFAULT`](crate::configrefs::OCC_ALERT_DEFAULT)).
+pub fn in_alert_period(
+    occ: &Occ,
+    config: &ResolvedConfig,
+    default_alert: Duration,
+    date: OccDate,
+) -> bool {
+    let alert = config.resolved_config.occ_alert.unwrap_or(default_alert);
+    alert_period_contains(occ, alert, date)
+}
+
+/// Determine whether `date` is in `occ`'s alert period, which has length
+/// `alert`.
+///
+/// The alert period doesn't start until the occurrence's snooze expires.
+fn alert_period_contains(occ: &Occ, alert: Duration, date: OccDate) -> bool {
+    // no start if the alert period is too long to represent
+    let alert_start = chrono::TimeDelta::from_std(alert).ok()
+        .and_then(|alert| occ.end.checked_sub_signed(alert));
+    alert_start.is_none_or(|start| date >= start) &&
+        occ.snoozed_until.is_none_or(|until| date >= until) &&
+        date < occ.end
 }
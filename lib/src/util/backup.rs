@@ -0,0 +1,255 @@
+//! Copying backups of the database to somewhere else, and removing old ones.
+//!
+//! Backups are written by [`Db::backup`], and named after the time they were
+//! made, so the most recent backups can be found from a destination's
+//! contents.  Other files at the destination are left alone.
+
+use std::{env, fs, io, process};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicU64};
+use std::time::Duration;
+use chrono::{NaiveDateTime, TimeDelta};
+use serde::Serialize;
+use crate::config::{self, Config};
+use crate::configrefs;
+use crate::db::Db;
+use crate::types::OccDate;
+
+#[cfg(feature = "http")]
+pub mod s3;
+
+/// Start of backup names.
+const NAME_PREFIX: &str = "dunsumday-";
+/// End of backup names.
+const NAME_SUFFIX: &str = ".backup";
+/// Format of the date in backup names.
+const NAME_DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Used to generate unique temporary file names.
+static TEMP_FILE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Path to a temporary file, which is deleted when dropped.
+#[derive(Debug)]
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    /// Choose a path for a new temporary backup file.
+    pub fn new() -> TempPath {
+        let id = TEMP_FILE_ID.fetch_add(1, atomic::Ordering::Relaxed);
+        TempPath(env::temp_dir().join(
+            format!("dunsumday-{}-{id}.backup", process::id())))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Default for TempPath {
+    fn default() -> TempPath {
+        TempPath::new()
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        match fs::remove_file(&self.0) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                tracing::warn!("error removing temporary file ({}): {e}",
+                               self.0.display());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Supported kinds of [`Destination`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, strum::AsRefStr,
+         strum::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum DestinationKind {
+    /// Don't make backups.
+    #[default]
+    None,
+    /// A directory on the local filesystem (see [`DirDestination`]).
+    Dir,
+    /// A bucket on an S3-compatible server (see [`s3::S3Destination`]).
+    /// Requires the `http` feature.
+    S3,
+}
+
+/// Somewhere to keep backups.
+pub trait Destination {
+    /// Short description of the destination, for messages.
+    fn describe(&self) -> String;
+
+    /// Store a file with the given name, replacing any existing file with the
+    /// same name.
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), String>;
+
+    /// Get the names of all files at the destination.
+    fn list(&self) -> Result<Vec<String>, String>;
+
+    /// Remove the file with the given name.  It's not an error if there's no
+    /// such file.
+    fn remove(&self, name: &str) -> Result<(), String>;
+}
+
+/// Destination which writes backups to a directory.
+#[derive(Clone, Debug)]
+pub struct DirDestination {
+    path: PathBuf,
+}
+
+impl DirDestination {
+    /// Write backups to `path`, which is created if it doesn't exist.
+    pub fn new(path: &Path) -> DirDestination {
+        DirDestination { path: path.to_owned() }
+    }
+}
+
+impl Destination for DirDestination {
+    fn describe(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), String> {
+        fs::create_dir_all(&self.path)
+            .map_err(|e| format!("error creating backup directory: {e}"))?;
+        // write then rename, so a partial file never has a backup's name
+        let partial = self.path.join(format!(".{name}.partial"));
+        fs::write(&partial, data)
+            .and_then(|_| fs::rename(&partial, self.path.join(name)))
+            .map_err(|e| format!("error writing backup: {e}"))
+    }
+
+    fn list(&self) -> Result<Vec<String>, String> {
+        let entries = match fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(format!("error listing backups: {e}")),
+        };
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry
+                .map_err(|e| format!("error listing backups: {e}"))?;
+            if let Ok(name) = entry.file_name().into_string() {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+
+    fn remove(&self, name: &str) -> Result<(), String> {
+        match fs::remove_file(self.path.join(name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(format!("error removing backup: {e}"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Open the destination configured in `cfg`, or return `None` if backups are
+/// disabled.
+pub fn open_destination(cfg: &impl Config)
+-> Result<Option<Box<dyn Destination>>, String> {
+    match config::get_parsed(cfg, &configrefs::BACKUP_DESTINATION)? {
+        DestinationKind::None => Ok(None),
+        DestinationKind::Dir => {
+            let path = cfg.get_ref(&configrefs::BACKUP_DIR_PATH);
+            if path.is_empty() {
+                return Err("no backup directory configured".to_owned());
+            }
+            Ok(Some(Box::new(DirDestination::new(Path::new(path)))))
+        }
+        #[cfg(feature = "http")]
+        DestinationKind::S3 => {
+            Ok(Some(Box::new(s3::S3Destination::new(cfg)?)))
+        }
+        #[cfg(not(feature = "http"))]
+        DestinationKind::S3 => {
+            Err("S3 backups are not supported by this build".to_owned())
+        }
+    }
+}
+
+/// Result of making a backup.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct BackedUp {
+    /// Name of the new backup.
+    pub name: String,
+    /// Names of old backups removed.
+    pub removed: Vec<String>,
+}
+
+/// Get the name of a backup made at `date`.
+fn backup_name(date: OccDate) -> String {
+    format!("{NAME_PREFIX}{}{NAME_SUFFIX}", date.format(NAME_DATE_FORMAT))
+}
+
+/// Get the time a backup was made from its name, or `None` if it's not the
+/// name of a backup.
+fn parse_backup_name(name: &str) -> Option<OccDate> {
+    let date = name.strip_prefix(NAME_PREFIX)?.strip_suffix(NAME_SUFFIX)?;
+    NaiveDateTime::parse_from_str(date, NAME_DATE_FORMAT).ok()
+        .map(|date| date.and_utc())
+}
+
+/// Get the backups at a destination, with the times they were made, most
+/// recent first.
+pub fn find(dest: &dyn Destination) -> Result<Vec<(OccDate, String)>, String> {
+    let mut backups = dest.list()?.into_iter()
+        .filter_map(|name| parse_backup_name(&name).map(|date| (date, name)))
+        .collect::<Vec<_>>();
+    backups.sort_by(|a, b| b.cmp(a));
+    Ok(backups)
+}
+
+/// Back up the whole database to `dest`, then remove old backups so that only
+/// the most recent `retention` are kept.
+pub fn backup(
+    db: &impl Db,
+    dest: &dyn Destination,
+    retention: u32,
+    now: OccDate,
+) -> Result<BackedUp, String> {
+    let path = TempPath::new();
+    db.backup(path.path())?;
+    let data = fs::read(path.path())
+        .map_err(|e| format!("error reading backup: {e}"))?;
+    drop(path);
+    let name = backup_name(now);
+    dest.put(&name, &data)?;
+
+    let mut removed = Vec::new();
+    // always keep the backup just made
+    let keep = usize::try_from(retention).unwrap_or(usize::MAX).max(1);
+    for (_, old) in find(dest)?.into_iter().skip(keep) {
+        dest.remove(&old)?;
+        removed.push(old);
+    }
+    Ok(BackedUp { name, removed })
+}
+
+/// Back up the database like [`backup`] if the most recent backup at `dest`
+/// was made at least `schedule` before `now`, or there are none.
+pub fn backup_if_due(
+    db: &impl Db,
+    dest: &dyn Destination,
+    schedule: Duration,
+    retention: u32,
+    now: OccDate,
+) -> Result<Option<BackedUp>, String> {
+    let schedule = TimeDelta::from_std(schedule)
+        .map_err(|e| format!("backup schedule is too long: {e}"))?;
+    let due = match find(dest)?.first() {
+        Some((last, _)) => *last + schedule <= now,
+        None => true,
+    };
+    if due {
+        backup(db, dest, retention, now).map(Some)
+    } else {
+        Ok(None)
+    }
+}
@@ -0,0 +1,216 @@
+//! Backups in a bucket on an [S3]-compatible server, such as Amazon S3 or
+//! MinIO.
+//!
+//! Requests are signed with AWS Signature Version 4, and use path-style URLs
+//! (`{endpoint}/{bucket}/{key}`), which all such servers support.
+//!
+//! [S3]: https://docs.aws.amazon.com/AmazonS3/latest/API/Welcome.html
+
+use std::time::Duration;
+use chrono::Utc;
+use ring::{digest, hmac};
+use crate::config::{self, Config};
+use crate::configrefs;
+use super::Destination;
+
+const TIMEOUT: Duration = Duration::from_secs(300);
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+const SERVICE: &str = "s3";
+
+/// Encode a value for use in a signed URL.  `/` is kept if `keep_slash` is
+/// `true`, for paths.
+fn encode(value: &str, keep_slash: bool) -> String {
+    value.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' |
+            b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            b'/' if keep_slash => "/".to_owned(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Undo escaping of the predefined XML entities.
+fn unescape_xml(value: &str) -> String {
+    value.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Get the contents of each element with the given name in an XML document.
+/// Only suitable for elements containing text.
+fn xml_elements(doc: &str, name: &str) -> Vec<String> {
+    let open = format!("<{name}>");
+    let close = format!("</{name}>");
+    let mut values = Vec::new();
+    let mut rest = doc;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let Some(end) = rest.find(&close) else { break };
+        values.push(unescape_xml(&rest[..end]));
+        rest = &rest[end + close.len()..];
+    }
+    values
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(digest::digest(&digest::SHA256, data))
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> hmac::Tag {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data.as_bytes())
+}
+
+/// Destination which uploads backups to a bucket.
+#[derive(Clone, Debug)]
+pub struct S3Destination {
+    agent: ureq::Agent,
+    /// Base URL of the server, without a trailing `/`.
+    endpoint: String,
+    /// Host name and port of the server, as sent in requests.
+    host: String,
+    region: String,
+    bucket: String,
+    /// Prepended to backup names to get object keys.
+    prefix: String,
+    access_key_id: String,
+    secret_access_key: String,
+}
+
+impl S3Destination {
+    /// Use the bucket configured in `cfg`.
+    pub fn new(cfg: &impl Config) -> Result<S3Destination, String> {
+        let endpoint = cfg.get_ref(&configrefs::BACKUP_S3_ENDPOINT)
+            .trim_end_matches('/');
+        let host = endpoint.split_once("://")
+            .map(|(_, rest)| rest.split('/').next().unwrap_or_default())
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| format!("invalid S3 endpoint: {endpoint}"))?;
+        let bucket = cfg.get_ref(&configrefs::BACKUP_S3_BUCKET);
+        if bucket.is_empty() {
+            return Err("no S3 bucket configured".to_owned());
+        }
+        Ok(S3Destination {
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+            endpoint: endpoint.to_owned(),
+            host: host.to_owned(),
+            region: cfg.get_ref(&configrefs::BACKUP_S3_REGION).to_owned(),
+            bucket: bucket.to_owned(),
+            prefix: cfg.get_ref(&configrefs::BACKUP_S3_PREFIX).to_owned(),
+            access_key_id: config::get_parsed(
+                cfg, &configrefs::BACKUP_S3_ACCESS_KEY_ID)?,
+            secret_access_key: config::get_parsed(
+                cfg, &configrefs::BACKUP_S3_SECRET_ACCESS_KEY)?,
+        })
+    }
+
+    /// Build a signed request.  `key` is the object key, or empty for the
+    /// bucket itself, and `query` must be sorted by name.
+    fn request(
+        &self,
+        method: &str,
+        key: &str,
+        query: &[(&str, &str)],
+        payload: &[u8],
+    ) -> ureq::Request {
+        let now = Utc::now();
+        let date = now.format("%Y%m%d").to_string();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = sha256_hex(payload);
+        let path = format!("/{}/{}", encode(&self.bucket, false),
+                           encode(key, true));
+        let query = query.iter()
+            .map(|(name, value)| {
+                format!("{}={}", encode(name, false), encode(value, false))
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{method}\n{path}\n{query}\n\
+             host:{}\nx-amz-content-sha256:{payload_hash}\n\
+             x-amz-date:{timestamp}\n\n{signed_headers}\n{payload_hash}",
+            self.host);
+        let scope = format!("{date}/{}/{SERVICE}/aws4_request", self.region);
+        let string_to_sign = format!(
+            "{ALGORITHM}\n{timestamp}\n{scope}\n{}",
+            sha256_hex(canonical_request.as_bytes()));
+        let signature = hex::encode(hmac_sha256(
+            &self.signing_key(&date), &string_to_sign));
+
+        let mut url = format!("{}{path}", self.endpoint);
+        if !query.is_empty() {
+            url = format!("{url}?{query}");
+        }
+        self.agent.request(method, &url)
+            .set("x-amz-content-sha256", &payload_hash)
+            .set("x-amz-date", &timestamp)
+            .set("Authorization", &format!(
+                "{ALGORITHM} Credential={}/{scope}, \
+                 SignedHeaders={signed_headers}, Signature={signature}",
+                self.access_key_id))
+    }
+
+    /// Derive the key used to sign requests made on `date`.
+    fn signing_key(&self, date: &str) -> Vec<u8> {
+        let secret = format!("AWS4{}", self.secret_access_key);
+        [date, &self.region, SERVICE, "aws4_request"].iter()
+            .fold(secret.into_bytes(), |key, part| {
+                hmac_sha256(&key, part).as_ref().to_vec()
+            })
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{name}", self.prefix)
+    }
+}
+
+impl Destination for S3Destination {
+    fn describe(&self) -> String {
+        format!("s3://{}/{}", self.bucket, self.prefix)
+    }
+
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), String> {
+        self.request("PUT", &self.key(name), &[], data)
+            .send_bytes(data)
+            .map(|_| ())
+            .map_err(|e| format!("error uploading backup: {e}"))
+    }
+
+    fn list(&self) -> Result<Vec<String>, String> {
+        let mut names = Vec::new();
+        let mut token = None::<String>;
+        loop {
+            // sorted by name, for signing
+            let mut query = vec![];
+            if let Some(token) = &token {
+                query.push(("continuation-token", token.as_str()));
+            }
+            query.push(("list-type", "2"));
+            query.push(("prefix", self.prefix.as_str()));
+            let doc = self.request("GET", "", &query, &[])
+                .call()
+                .map_err(|e| format!("error listing backups: {e}"))?
+                .into_string()
+                .map_err(|e| format!("error listing backups: {e}"))?;
+            names.extend(xml_elements(&doc, "Key").into_iter()
+                .filter_map(|key| {
+                    key.strip_prefix(&self.prefix).map(str::to_owned)
+                }));
+            token = xml_elements(&doc, "NextContinuationToken").pop();
+            if token.is_none() {
+                return Ok(names);
+            }
+        }
+    }
+
+    fn remove(&self, name: &str) -> Result<(), String> {
+        match self.request("DELETE", &self.key(name), &[], &[]).call() {
+            Ok(_) | Err(ureq::Error::Status(404, _)) => Ok(()),
+            Err(e) => Err(format!("error removing backup: {e}")),
+        }
+    }
+}
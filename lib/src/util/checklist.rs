@@ -0,0 +1,116 @@
+//! Writing task occurrences as a Markdown checklist, for pasting into notes or
+//! printing.
+//!
+//! Occurrences are grouped by the day they're due, in UTC, and then by their
+//! item's category.  Occurrences due at midnight are due by the end of the
+//! previous day, and are listed on that day without a time.  Completed
+//! occurrences are checked, and events and inactive occurrences are left out.
+//!
+//! ```markdown
+//! # 2024-05-01
+//!
+//! - [ ] Stretch
+//!
+//! ## Home
+//!
+//! - [ ] Water plants (due 18:00)
+//! - [x] Wash up
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use chrono::{NaiveDate, NaiveTime, TimeDelta};
+use crate::types::ItemType;
+use super::export::{Export, ExportOcc};
+
+/// Escape characters which have special meaning in Markdown text.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' | '\r' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Get the day an occurrence is due, and the time it's due if not at the end
+/// of the day.
+fn due(occ: &ExportOcc) -> (NaiveDate, Option<NaiveTime>) {
+    let end = occ.occ.occ.end;
+    if end.time() == NaiveTime::MIN {
+        let day = (end - TimeDelta::days(1)).date_naive();
+        (day, None)
+    } else {
+        (end.date_naive(), Some(end.time()))
+    }
+}
+
+/// Write a checklist line for an occurrence.
+fn line(name: &str, occ: &ExportOcc, time: Option<NaiveTime>) -> String {
+    let complete = occ.progress.is_some_and(|progress| progress.is_complete());
+    let mut details = Vec::new();
+    if let Some(time) = time {
+        details.push(format!("due {}", time.format("%H:%M")));
+    }
+    if let Some(progress) = occ.progress.filter(|progress| progress.total > 1) {
+        details.push(format!(
+            "{}/{}", progress.effective_progress(), progress.total));
+    }
+    let details = if details.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", details.join(", "))
+    };
+    let check = if complete { 'x' } else { ' ' };
+    format!("- [{check}] {}{details}\n", escape(name))
+}
+
+/// Write active task occurrences in an export as a Markdown checklist.
+pub fn markdown(export: &Export) -> String {
+    let items_by_id = export.items.iter()
+        .map(|item| (item.id.as_str(), item))
+        .collect::<HashMap<_, _>>();
+    // day -> category -> lines, with items without a category first
+    let mut days: BTreeMap<NaiveDate, BTreeMap<Option<&str>, Vec<_>>> =
+        BTreeMap::new();
+    for occ in &export.occs {
+        let Some(item) = items_by_id.get(occ.item_id.as_str()) else {
+            continue
+        };
+        if item.item.type_ == ItemType::Event || !item.item.active ||
+                !occ.occ.occ.active {
+            continue;
+        }
+        let (day, time) = due(occ);
+        days.entry(day)
+            .or_default()
+            .entry(item.item.category.as_deref())
+            .or_default()
+            .push((time, line(&item.item.name, occ, time)));
+    }
+
+    let mut out = String::new();
+    for (day, categories) in days {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("# {day}\n"));
+        for (category, mut lines) in categories {
+            if let Some(category) = category {
+                out.push_str(&format!("\n## {}\n", escape(category)));
+            }
+            out.push('\n');
+            // occurrences due at the end of the day go last
+            lines.sort_by_key(|(time, _)| (time.is_none(), *time));
+            for (_, line) in lines {
+                out.push_str(&line);
+            }
+        }
+    }
+    out
+}
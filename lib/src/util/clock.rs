@@ -0,0 +1,111 @@
+//! Sources of the current date.
+//!
+//! Code which needs the current date should take it from a [`Clock`], so that
+//! it can be fixed in tests, or overridden in config (see [`from_config`]).
+
+use std::fmt::Debug;
+use std::sync::Arc;
+use chrono::{FixedOffset, NaiveDate, Offset, Utc};
+use crate::config::{self, Config, ConfigError};
+use crate::configrefs;
+use crate::types::OccDate;
+
+/// Provides the current date.
+pub trait Clock: Debug {
+    /// Get the current date.
+    fn now(&self) -> OccDate;
+
+    /// Offset of the local timezone from UTC, which determines where day
+    /// boundaries fall.
+    fn utc_offset(&self) -> FixedOffset;
+
+    /// Get the current day, in the local timezone.
+    fn today(&self) -> NaiveDate {
+        self.now().with_timezone(&self.utc_offset()).date_naive()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Box<C> {
+    fn now(&self) -> OccDate {
+        (**self).now()
+    }
+
+    fn utc_offset(&self) -> FixedOffset {
+        (**self).utc_offset()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> OccDate {
+        (**self).now()
+    }
+
+    fn utc_offset(&self) -> FixedOffset {
+        (**self).utc_offset()
+    }
+}
+
+/// Clock which can be shared between threads.
+pub type SharedClock = Arc<dyn Clock + Send + Sync>;
+
+/// Clock using the system time.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SystemClock {
+    pub utc_offset: FixedOffset,
+}
+
+impl Default for SystemClock {
+    /// Use UTC as the local timezone.
+    fn default() -> SystemClock {
+        SystemClock { utc_offset: Utc.fix() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> OccDate {
+        Utc::now()
+    }
+
+    fn utc_offset(&self) -> FixedOffset {
+        self.utc_offset
+    }
+}
+
+/// Clock which is stopped at a fixed date, for tests and for looking at past
+/// or future dates.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FixedClock {
+    pub now: OccDate,
+    pub utc_offset: FixedOffset,
+}
+
+impl FixedClock {
+    /// Stop the clock at `now`, using UTC as the local timezone.
+    pub fn new(now: OccDate) -> FixedClock {
+        FixedClock { now, utc_offset: Utc.fix() }
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> OccDate {
+        self.now
+    }
+
+    fn utc_offset(&self) -> FixedOffset {
+        self.utc_offset
+    }
+}
+
+/// Get the configured clock: a [`FixedClock`] if
+/// [`CLOCK_NOW`](configrefs::CLOCK_NOW) is set, and a [`SystemClock`]
+/// otherwise, using [`CLOCK_UTC_OFFSET`](configrefs::CLOCK_UTC_OFFSET).
+pub fn from_config<C>(cfg: &C) -> Result<SharedClock, ConfigError>
+where
+    C: Config + ?Sized,
+{
+    let utc_offset = config::get_parsed(cfg, &configrefs::CLOCK_UTC_OFFSET)?;
+    Ok(match config::get_parsed(cfg, &configrefs::CLOCK_NOW)? {
+        Some(now) => Arc::new(FixedClock { now, utc_offset }),
+        None => Arc::new(SystemClock { utc_offset }),
+    })
+}
@@ -2,8 +2,10 @@
 
 use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
-use crate::db::{ConfigId, Db, DbResult, StoredConfig, StoredItem, StoredOcc};
-use crate::types::{Config, Item, ItemType, TaskCompletionConfig};
+use crate::db::{ConfigId, Db, DbResult, DbResults, SortDirection,
+                StoredConfig, StoredItem, StoredOcc};
+use crate::types::{Config, Item, ItemType, MilestoneConfig,
+                   NotificationConfig, OccDate, TaskCompletionConfig};
 
 /// A config associated with the scope it applies to, with all values resolved
 /// by inheriting from parent scopes where applicable.
@@ -59,6 +61,10 @@ pub fn build_config_ids_occ(item: &StoredItem, occ: &StoredOcc)
 pub fn resolve_config_direct(parent: &Config, child: &Config) -> Config {
     let pcompl = &parent.task_completion_conf;
     let ccompl = &child.task_completion_conf;
+    let pnotif = &parent.notification_conf;
+    let cnotif = &child.notification_conf;
+    let pmiles = &parent.milestone_conf;
+    let cmiles = &child.milestone_conf;
     Config {
         occ_alert: child.occ_alert.or(parent.occ_alert),
         task_completion_conf: TaskCompletionConfig {
@@ -66,6 +72,24 @@ pub fn resolve_config_direct(parent: &Config, child: &Config) -> Config {
             unit: ccompl.unit.clone().or(pcompl.unit.clone()),
             excess_past: ccompl.excess_past.or(pcompl.excess_past),
             excess_future: ccompl.excess_future.or(pcompl.excess_future),
+            transfer_policy: ccompl.transfer_policy.or(pcompl.transfer_policy),
+            target_kind: ccompl.target_kind.or(pcompl.target_kind),
+        },
+        notification_conf: NotificationConfig {
+            email: cnotif.email.or(pnotif.email),
+            enabled: cnotif.enabled.or(pnotif.enabled),
+            channels: cnotif.channels.clone().or(pnotif.channels.clone()),
+            quiet_hours: cnotif.quiet_hours.or(pnotif.quiet_hours),
+            repeat: cnotif.repeat.or(pnotif.repeat),
+            escalation: cnotif.escalation.clone()
+                .or(pnotif.escalation.clone()),
+        },
+        milestone_conf: MilestoneConfig {
+            enabled: cmiles.enabled.or(pmiles.enabled),
+            completions: cmiles.completions.clone()
+                .or(pmiles.completions.clone()),
+            streaks: cmiles.streaks.clone().or(pmiles.streaks.clone()),
+            yearly_total: cmiles.yearly_total.or(pmiles.yearly_total),
         },
     }
 }
@@ -103,34 +127,51 @@ pub fn resolve_config(configs: &[StoredConfig]) -> Option<ResolvedConfig> {
     }
 }
 
-/// Retrieve and resolve all configs for multiple objects.
-///
-/// `ids_by_obj` specifies the config IDs to try to retrieve for each object of
-/// type `T`.  Objects with no stored config are not included in the result.
-fn get_objects_configs<'t, T>(
-    db: &impl Db,
-    ids_by_obj: &[(&'t T, Vec<ConfigId>)],
-) -> DbResult<Vec<(&'t T, ResolvedConfig)>>
+/// Get and resolve the configs for each chain of config IDs, using
+/// [`Db::get_configs`].  See [`Db::resolve_configs`], which should usually be
+/// used instead.
+pub fn resolve_configs<D>(db: &D, chains: &[&[ConfigId]])
+-> DbResults<Option<ResolvedConfig>>
 where
-    T: Clone + Eq + Hash
+    D: Db + ?Sized,
 {
-    let all_ids = ids_by_obj.iter()
-        .flat_map(|(obj, ids)| ids)
+    let all_ids = chains.iter()
+        .flat_map(|ids| ids.iter())
         .collect::<HashSet<_>>()
         .into_iter().collect::<Vec<_>>();
-    let mut config_by_id: HashMap<ConfigId, StoredConfig> =
+    let config_by_id: HashMap<ConfigId, StoredConfig> =
         db.get_configs(&all_ids)?
             .into_iter()
             .map(|c| (c.id.to_owned(), c))
             .collect();
 
-    let config_by_obj = ids_by_obj.iter()
-        .flat_map(|(obj, ids)| {
+    Ok(chains.iter()
+        .map(|ids| {
             let configs = ids.iter()
-                .flat_map(|id| config_by_id.remove(id))
+                .flat_map(|id| config_by_id.get(id).cloned())
                 .collect::<Vec<_>>();
-            resolve_config(&configs[..]).map(|rc| (*obj, rc))
+            resolve_config(&configs[..])
         })
+        .collect())
+}
+
+/// Retrieve and resolve all configs for multiple objects.
+///
+/// `ids_by_obj` specifies the config IDs to try to retrieve for each object of
+/// type `T`.  Objects with no stored config are not included in the result.
+fn get_objects_configs<'t, T>(
+    db: &impl Db,
+    ids_by_obj: &[(&'t T, Vec<ConfigId>)],
+) -> DbResult<Vec<(&'t T, ResolvedConfig)>>
+where
+    T: Clone + Eq + Hash
+{
+    let chains = ids_by_obj.iter()
+        .map(|(obj, ids)| &ids[..])
+        .collect::<Vec<_>>();
+    let config_by_obj = ids_by_obj.iter()
+        .zip(db.resolve_configs(&chains)?)
+        .flat_map(|((obj, ids), rc)| rc.map(|rc| (*obj, rc)))
         .collect();
     Ok(config_by_obj)
 }
@@ -146,6 +187,45 @@ pub fn get_items_configs<'i>(db: &impl Db, items: &[&'i StoredItem])
     get_objects_configs(db, &ids_by_item)
 }
 
+/// Criteria for [finding items](get_items_with_resolved).
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ItemFilter<'a> {
+    /// Only include items which are active or not.
+    pub active: Option<bool>,
+    /// Only include items with this category.
+    pub category: Option<&'a str>,
+    /// Only include items of this type.
+    pub type_: Option<ItemType>,
+    /// Only include items which are recurring, or which are non-recurring and
+    /// occur after this date.
+    pub start: Option<OccDate>,
+}
+
+/// Find all items matching `filter`, each with its resolved config, or `None`
+/// if the item has no stored config.
+///
+/// This makes the same number of queries however many items there are.
+/// Results are ordered by created date.
+pub fn get_items_with_resolved(db: &impl Db, filter: &ItemFilter)
+-> DbResults<(StoredItem, Option<ResolvedConfig>)> {
+    let items = db.find_items(
+        filter.active, filter.category, filter.start, SortDirection::Asc,
+        None, u32::MAX)?
+        .into_iter()
+        .filter(|item| {
+            filter.type_.is_none_or(|type_| item.item.type_ == type_)
+        })
+        .collect::<Vec<_>>();
+    let chains = items.iter()
+        .map(build_config_ids_item)
+        .collect::<Vec<_>>();
+    let chain_refs = chains.iter()
+        .map(|ids| &ids[..])
+        .collect::<Vec<_>>();
+    let configs = db.resolve_configs(&chain_refs)?;
+    Ok(items.into_iter().zip(configs).collect())
+}
+
 /// Retrieve and resolve configs for an item.
 ///
 /// The result is `None` when the item has no stored config.
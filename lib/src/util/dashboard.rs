@@ -0,0 +1,181 @@
+//! Summaries of each category's health, for an overview of all items.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use chrono::{NaiveTime, TimeDelta};
+use serde::Serialize;
+use crate::db::{Db, DbResults, StoredItem, StoredOcc};
+use crate::types::{ItemType, OccDate};
+use super::clock::Clock;
+use super::config::{self, ItemFilter};
+use super::progress::{self, TaskProgress};
+use super::stats::{self, CompletionStats};
+
+/// Item in a category with its completion rate, as in
+/// [`CategoryHealth::worst_item`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ItemHealth {
+    pub item: StoredItem,
+    pub completion_rate: f64,
+}
+
+/// Overview of a category's items, as returned by [`categories`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CategoryHealth {
+    pub category: String,
+    /// Number of active items.
+    pub active_items: u32,
+    /// Number of active items due today: tasks whose current occurrence ends
+    /// today without having met its target, and events whose current
+    /// occurrence starts today.
+    pub due_today: u32,
+    /// Proportion of task occurrences completed over the period, out of those
+    /// completed or missed (see [`CompletionStats::completion_rate`]).
+    pub completion_rate: Option<f64>,
+    /// Active task item with the lowest completion rate over the period.
+    /// Ties go to the item which missed more occurrences.
+    pub worst_item: Option<ItemHealth>,
+}
+
+/// Whether an item's current occurrence is due during the day from `start` to
+/// `end`.  Tasks ending at the very end of the day are due that day.
+fn due(
+    item: &StoredItem,
+    occ_start: OccDate,
+    occ_end: OccDate,
+    progress: Option<&TaskProgress>,
+    (start, end): (OccDate, OccDate),
+) -> bool {
+    match item.item.type_ {
+        ItemType::Event => start <= occ_start && occ_start < end,
+        _ => start < occ_end && occ_end <= end &&
+            !progress.is_some_and(|p| p.is_complete()),
+    }
+}
+
+/// Order item health from worst to best.
+fn compare_worst(
+    (a, a_stats): &(ItemHealth, CompletionStats),
+    (b, b_stats): &(ItemHealth, CompletionStats),
+) -> Ordering {
+    a.completion_rate.total_cmp(&b.completion_rate)
+        .then(b_stats.missed.cmp(&a_stats.missed))
+        .then_with(|| a.item.item.name.cmp(&b.item.item.name))
+}
+
+/// Summarise every category with active items or with task occurrences over
+/// the `period` up to now, sorted by category.  Items without a category are
+/// not included.
+///
+/// Days start at midnight in the clock's timezone.  Completion rates are taken
+/// from [completion statistics](stats::get_stats).  Current occurrences are
+/// found without storing anything, and this uses the same number of queries
+/// however many items there are.
+pub fn categories(db: &impl Db, clock: &dyn Clock, period: TimeDelta)
+-> DbResults<CategoryHealth> {
+    let now = clock.now();
+    let today = clock.today();
+    let day_start = |day: chrono::NaiveDate| {
+        (day.and_time(NaiveTime::MIN) - clock.utc_offset()).and_utc()
+    };
+    let day = (day_start(today),
+               day_start(today.succ_opt().unwrap_or(today)));
+
+    let filter = ItemFilter {
+        active: Some(true),
+        start: Some(now),
+        ..Default::default()
+    };
+    let items = config::get_items_with_resolved(db, &filter)?
+        .into_iter()
+        .filter(|(item, _)| item.item.category.is_some())
+        .collect::<Vec<_>>();
+    let item_refs = items.iter()
+        .map(|(item, _)| item)
+        .collect::<Vec<_>>();
+    let current = super::preview_items_current_occ(db, now, &item_refs)?
+        .into_iter()
+        .filter_map(|preview| preview.current)
+        .collect::<Vec<_>>();
+    let stored = current.iter()
+        .filter_map(|current| {
+            current.id.clone().map(|id| {
+                (current.item, StoredOcc { id, occ: current.occ.clone() })
+            })
+        })
+        .collect::<Vec<_>>();
+    let stored_refs = stored.iter()
+        .map(|(item, occ)| (*item, occ))
+        .collect::<Vec<_>>();
+    let progress = progress::resolve_item_occs_progress(db, &stored_refs)?;
+    let new_progress = items.iter()
+        .map(|(item, config)| {
+            let cmpl_cfg = config.as_ref()
+                .map(|config| &config.resolved_config.task_completion_conf);
+            (item.id.as_str(), TaskProgress {
+                total: cmpl_cfg.and_then(|c| c.total).unwrap_or(1),
+                target_kind: cmpl_cfg.and_then(|c| c.target_kind)
+                    .unwrap_or_default(),
+                ..Default::default()
+            })
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let stats = stats::get_stats(
+        db, now.checked_sub_signed(period), Some(now), now)?;
+
+    let mut summaries = BTreeMap::<&str, CategoryHealth>::new();
+    let mut worst = BTreeMap::<&str, (ItemHealth, CompletionStats)>::new();
+    let summary = |category: &str| CategoryHealth {
+        category: category.to_owned(),
+        active_items: 0,
+        due_today: 0,
+        completion_rate: stats.categories.get(category)
+            .and_then(|stats| stats.completion_rate()),
+        worst_item: None,
+    };
+    for category in stats.categories.keys() {
+        summaries.insert(category, summary(category));
+    }
+    for (item, _) in &items {
+        let Some(category) = item.item.category.as_deref() else { continue };
+        summaries.entry(category)
+            .or_insert_with(|| summary(category))
+            .active_items += 1;
+        let Some(item_stats) = stats.items.get(&item.id) else { continue };
+        let Some(completion_rate) = item_stats.completion_rate() else {
+            continue
+        };
+        let health = (ItemHealth { item: item.clone(), completion_rate },
+                      *item_stats);
+        let is_worse = worst.get(category)
+            .is_none_or(|current| compare_worst(&health, current).is_lt());
+        if is_worse {
+            worst.insert(category, health);
+        }
+    }
+    for current in &current {
+        let Some(category) = current.item.item.category.as_deref() else {
+            continue
+        };
+        let occ_progress = match &current.id {
+            Some(id) => progress.get(id),
+            None => new_progress.get(current.item.id.as_str()),
+        };
+        if due(current.item, current.occ.start, current.occ.end, occ_progress,
+               day) {
+            if let Some(summary) = summaries.get_mut(category) {
+                summary.due_today += 1;
+            }
+        }
+    }
+
+    for (category, (health, _)) in worst {
+        if let Some(summary) = summaries.get_mut(category) {
+            summary.worst_item = Some(health);
+        }
+    }
+    Ok(summaries.into_values().collect())
+}
@@ -0,0 +1,1270 @@
+//! Syncing a client's database with a server, so that clients which work
+//! offline converge with the server.
+//!
+//! A client sends the changes made to its database since it last synced, and
+//! the server applies them (see [`exchange`]) and replies with the changes made
+//! on the server since then.  The client's [`SyncEngine`] applies these in
+//! turn.  Objects created on a client are given new IDs by the server, and the
+//! client keeps track of which of its objects are which on the server.
+//!
+//! When an object was changed on both sides since the client last synced, the
+//! change made last wins, comparing when the client made its change with when
+//! the server's change was written.  Deleting an object always wins over
+//! changes to it, or to the objects it contains.
+//!
+//! Items, occurrences, progress entries and configs are synced; alerts, digests
+//! and users aren't.  Occurrences are synced like any other object, so a client
+//! shouldn't also generate occurrences for synced items.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::db::{ConfigId, Db, DbResult, DbUpdate, IdToken, IdTokens, ObjectId,
+                Revision, SortDirection, StoredConfig, StoredItem, StoredOcc,
+                UpdateId};
+use crate::types::{Config as ItemConfig, Item, ItemType, Occ, OccDate,
+                   ProgressEntry};
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Kind of object which is given an ID when it's created.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum ObjectKind {
+    Item,
+    Occ,
+    ProgressEntry,
+}
+
+/// Reference to an object in a sync request or response.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum SyncId {
+    /// ID of the object on the server.
+    Server(String),
+    /// ID of an object created on the client, which the server doesn't have
+    /// yet.
+    Client(String),
+}
+
+/// [`ConfigId`] referring to objects using [`SyncId`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum SyncConfigId {
+    All,
+    Type(ItemType),
+    Category(String),
+    Item { id: SyncId },
+    Occ { id: SyncId },
+}
+
+impl SyncConfigId {
+    fn new(id: &ConfigId, sync_id: impl Fn(ObjectKind, &str) -> SyncId)
+    -> SyncConfigId {
+        match id {
+            ConfigId::All => SyncConfigId::All,
+            ConfigId::Type(type_) => SyncConfigId::Type(*type_),
+            ConfigId::Category(cat) => SyncConfigId::Category(cat.clone()),
+            ConfigId::Item { id } => SyncConfigId::Item {
+                id: sync_id(ObjectKind::Item, id),
+            },
+            ConfigId::Occ { id } => SyncConfigId::Occ {
+                id: sync_id(ObjectKind::Occ, id),
+            },
+        }
+    }
+
+    /// Convert to a [`ConfigId`], returning `None` if `resolve` can't find a
+    /// referenced object.
+    fn resolve(&self, resolve: impl Fn(ObjectKind, &SyncId) -> Option<String>)
+    -> Option<ConfigId> {
+        Some(match self {
+            SyncConfigId::All => ConfigId::All,
+            SyncConfigId::Type(type_) => ConfigId::Type(*type_),
+            SyncConfigId::Category(cat) => ConfigId::Category(cat.clone()),
+            SyncConfigId::Item { id } => ConfigId::Item {
+                id: resolve(ObjectKind::Item, id)?,
+            },
+            SyncConfigId::Occ { id } => ConfigId::Occ {
+                id: resolve(ObjectKind::Occ, id)?,
+            },
+        })
+    }
+}
+
+/// Changed object, with its new value, which is `None` if it was deleted.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum SyncObject {
+    Item { id: SyncId, item: Option<Item> },
+    /// `item_id` is required unless the occurrence was deleted.
+    Occ { id: SyncId, item_id: Option<SyncId>, occ: Option<Occ> },
+    /// `occ_id` is required unless the progress entry was deleted.  Progress
+    /// entries can't be changed, so `entry` is ignored if the other side
+    /// already has the progress entry.
+    ProgressEntry {
+        id: SyncId,
+        occ_id: Option<SyncId>,
+        entry: Option<ProgressEntry>,
+    },
+    Config { id: SyncConfigId, config: Option<ItemConfig> },
+}
+
+impl SyncObject {
+    /// Whether the change was deleting the object.
+    fn deleted(&self) -> bool {
+        match self {
+            SyncObject::Item { item, .. } => item.is_none(),
+            SyncObject::Occ { occ, .. } => occ.is_none(),
+            SyncObject::ProgressEntry { entry, .. } => entry.is_none(),
+            SyncObject::Config { config, .. } => config.is_none(),
+        }
+    }
+
+    /// Position when applying changes: objects are created or changed before
+    /// the objects they contain, and deleted after them.
+    fn order(&self) -> (bool, i8) {
+        let depth = match self {
+            SyncObject::Item { .. } => 0,
+            SyncObject::Occ { .. } => 1,
+            SyncObject::ProgressEntry { .. } => 2,
+            SyncObject::Config { .. } => 3,
+        };
+        if self.deleted() {
+            (true, -depth)
+        } else {
+            (false, depth)
+        }
+    }
+}
+
+/// Change made to an object.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SyncChange {
+    /// When the change was made.
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = String, format = DateTime))]
+    pub date: OccDate,
+    pub object: SyncObject,
+}
+
+/// Request sent by a client to sync with the server.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SyncRequest {
+    /// `checkpoint` from the response to the client's previous sync, or `None`
+    /// for its first sync.
+    pub checkpoint: Option<u64>,
+    /// Changes made on the client since its previous sync.  Changes are
+    /// applied in order, so objects must be created before they're referred
+    /// to.
+    pub changes: Vec<SyncChange>,
+}
+
+/// ID given by the server to an object created by a client.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreatedId {
+    pub kind: ObjectKind,
+    /// ID on the client.
+    pub client: String,
+    /// ID on the server.
+    pub server: String,
+}
+
+/// Response from the server to a [`SyncRequest`].
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SyncResponse {
+    /// Pass this in the client's next request.
+    pub checkpoint: u64,
+    /// Changes made on the server since the request's checkpoint, or every
+    /// object on the server for a first sync.  Objects are referred to by
+    /// their IDs on the server, and changes from the request which were
+    /// applied aren't included.
+    pub changes: Vec<SyncChange>,
+    /// IDs given to objects created by the request.
+    pub created: Vec<CreatedId>,
+    /// Number of changes in the request which were discarded because of
+    /// conflicting changes on the server.
+    pub conflicts: u32,
+}
+
+/// Reference to an object in a [`Planned`] update.
+#[derive(Debug)]
+enum PlannedRef {
+    Id(String),
+    Token(IdToken),
+}
+
+impl PlannedRef {
+    fn update_id(&self) -> UpdateId<'_> {
+        match self {
+            PlannedRef::Id(id) => UpdateId::Id(id),
+            PlannedRef::Token(token) => UpdateId::Token(*token),
+        }
+    }
+}
+
+/// Database update for a change, owning its data.
+#[derive(Debug)]
+enum Planned {
+    CreateItem { token: IdToken, item: Item },
+    UpdateItem(StoredItem),
+    DeleteItem(String),
+    SetConfig(StoredConfig),
+    DeleteConfig(ConfigId),
+    CreateOcc { token: IdToken, item_id: PlannedRef, occ: Occ },
+    UpdateOcc(StoredOcc),
+    DeleteOcc(String),
+    CreateProgressEntry {
+        token: IdToken,
+        occ_id: PlannedRef,
+        entry: ProgressEntry,
+    },
+    DeleteProgressEntry(String),
+}
+
+impl Planned {
+    fn update(&self) -> DbUpdate<'_> {
+        match self {
+            Planned::CreateItem { token, item } => {
+                DbUpdate::create_item(*token, item)
+            }
+            Planned::UpdateItem(item) => DbUpdate::update_item(item),
+            Planned::DeleteItem(id) => DbUpdate::delete_item(id),
+            Planned::SetConfig(config) => DbUpdate::set_config(config),
+            Planned::DeleteConfig(id) => DbUpdate::delete_config(id.clone()),
+            Planned::CreateOcc { token, item_id, occ } => {
+                DbUpdate::create_occ(*token, item_id.update_id(), occ)
+            }
+            Planned::UpdateOcc(occ) => DbUpdate::update_occ(occ),
+            Planned::DeleteOcc(id) => DbUpdate::delete_occ(id),
+            Planned::CreateProgressEntry { token, occ_id, entry } => {
+                DbUpdate::create_progress_entry(
+                    *token, occ_id.update_id(), entry)
+            }
+            Planned::DeleteProgressEntry(id) => {
+                DbUpdate::delete_progress_entry(id)
+            }
+        }
+    }
+}
+
+/// Write `planned` updates, returning the IDs of created objects by token.
+fn write(db: &mut impl Db, planned: &[Planned])
+-> DbResult<HashMap<IdToken, String>> {
+    if planned.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let updates = planned.iter().map(Planned::update).collect::<Vec<_>>();
+    db.write(&updates.iter().collect::<Vec<_>>())
+}
+
+/// Get the latest revision of `db`, given that it's no earlier than `after`.
+fn latest_revision(db: &impl Db, after: Option<u64>) -> DbResult<u64> {
+    Ok(db.find_revisions(after, u32::MAX)?.last()
+        .map(|revision| revision.rev)
+        .or(after)
+        .unwrap_or(0))
+}
+
+/// Get every synced object in `db`, as changes made at `now`.
+fn all_objects(db: &impl Db, now: OccDate) -> DbResult<Vec<Revision>> {
+    let items = db.find_items(
+        None, None, None, SortDirection::Asc, None, u32::MAX)?;
+    let item_ids = items.iter()
+        .map(|item| item.id.as_str())
+        .collect::<Vec<_>>();
+    let occs = db.find_occs(
+        &item_ids, None, None, SortDirection::Asc, None, u32::MAX)?
+        .into_values()
+        .flatten()
+        .collect::<Vec<_>>();
+    let occ_ids = occs.iter().map(|occ| occ.id.as_str()).collect::<Vec<_>>();
+    let entries = db.find_progress_entries(
+        &occ_ids, SortDirection::Asc, u32::MAX)?
+        .into_values()
+        .flatten();
+    let configs = db.find_configs()?;
+
+    let objects = items.iter()
+        .map(|item| ObjectId::Item { id: item.id.clone() })
+        .chain(occs.iter().map(|occ| ObjectId::Occ { id: occ.id.clone() }))
+        .chain(entries.map(|entry| ObjectId::ProgressEntry { id: entry.id }))
+        .chain(configs.into_iter().map(|config| {
+            ObjectId::Config { id: config.id }
+        }));
+    Ok(objects
+        .map(|object| Revision { rev: 0, object, deleted: false, date: now })
+        .collect())
+}
+
+/// Read the current values of changed objects from `db`, referring to objects
+/// using `sync_id`.  Objects which no longer exist are treated as deleted.
+fn read_changes(
+    db: &impl Db,
+    revisions: &[Revision],
+    sync_id: impl Fn(ObjectKind, &str) -> SyncId,
+) -> DbResult<Vec<SyncChange>> {
+    let mut item_ids = vec![];
+    let mut occ_ids = vec![];
+    let mut entry_ids = vec![];
+    let mut config_ids = vec![];
+    for revision in revisions.iter().filter(|revision| !revision.deleted) {
+        match &revision.object {
+            ObjectId::Item { id } => item_ids.push(id.as_str()),
+            ObjectId::Occ { id } => occ_ids.push(id.as_str()),
+            ObjectId::ProgressEntry { id } => entry_ids.push(id.as_str()),
+            ObjectId::Config { id } => config_ids.push(id),
+        }
+    }
+    let items = db.get_items(&item_ids)?.into_iter()
+        .map(|item| (item.id, item.item))
+        .collect::<HashMap<_, _>>();
+    let occs = db.get_occs(&occ_ids)?.into_iter()
+        .map(|occ| (occ.id, occ.occ))
+        .collect::<HashMap<_, _>>();
+    let occ_items = db.get_occs_item_ids(&occ_ids)?;
+    let entries = db.get_progress_entries(&entry_ids)?.into_iter()
+        .map(|entry| (entry.id.clone(), entry))
+        .collect::<HashMap<_, _>>();
+    let configs = db.get_configs(&config_ids)?.into_iter()
+        .map(|config| (config.id, config.config))
+        .collect::<HashMap<_, _>>();
+
+    Ok(revisions.iter()
+        .map(|revision| {
+            let object = match &revision.object {
+                ObjectId::Item { id } => SyncObject::Item {
+                    id: sync_id(ObjectKind::Item, id),
+                    item: items.get(id).cloned(),
+                },
+                ObjectId::Occ { id } => SyncObject::Occ {
+                    id: sync_id(ObjectKind::Occ, id),
+                    item_id: occ_items.get(id)
+                        .map(|item_id| sync_id(ObjectKind::Item, item_id)),
+                    occ: occs.get(id).cloned(),
+                },
+                ObjectId::ProgressEntry { id } => {
+                    let entry = entries.get(id);
+                    SyncObject::ProgressEntry {
+                        id: sync_id(ObjectKind::ProgressEntry, id),
+                        occ_id: entry.map(|entry| {
+                            sync_id(ObjectKind::Occ, &entry.occ_id)
+                        }),
+                        entry: entry.map(|entry| entry.entry.clone()),
+                    }
+                }
+                ObjectId::Config { id } => SyncObject::Config {
+                    id: SyncConfigId::new(id, &sync_id),
+                    config: configs.get(id).cloned(),
+                },
+            };
+            SyncChange { date: revision.date, object }
+        })
+        .collect())
+}
+
+/// Check that a request is valid: objects must have the values they refer to,
+/// and objects created on the client must be created once, before they're
+/// referred to.
+pub fn check(request: &SyncRequest) -> Result<(), String> {
+    fn exists(created: &HashSet<(ObjectKind, &str)>, kind: ObjectKind,
+              id: &SyncId) -> Result<(), String> {
+        match id {
+            SyncId::Client(id) if !created.contains(&(kind, id.as_str())) => {
+                Err(format!(
+                    "object not created earlier in the request: {kind:?} {id}"))
+            }
+            _ => Ok(()),
+        }
+    }
+    fn create<'a>(created: &mut HashSet<(ObjectKind, &'a str)>,
+                  kind: ObjectKind, id: &'a SyncId) -> Result<(), String> {
+        match id {
+            SyncId::Client(id) if !created.insert((kind, id.as_str())) => {
+                Err(format!("object created more than once: {kind:?} {id}"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    let mut created = HashSet::new();
+
+    for change in &request.changes {
+        match &change.object {
+            SyncObject::Item { id, item: Some(_) } => {
+                create(&mut created, ObjectKind::Item, id)?;
+            }
+            SyncObject::Occ { id, item_id, occ: Some(_) } => {
+                let item_id = item_id.as_ref()
+                    .ok_or("occurrence has no item".to_owned())?;
+                exists(&created, ObjectKind::Item, item_id)?;
+                create(&mut created, ObjectKind::Occ, id)?;
+            }
+            SyncObject::ProgressEntry { id, occ_id, entry: Some(_) } => {
+                let occ_id = occ_id.as_ref()
+                    .ok_or("progress entry has no occurrence".to_owned())?;
+                exists(&created, ObjectKind::Occ, occ_id)?;
+                create(&mut created, ObjectKind::ProgressEntry, id)?;
+            }
+            SyncObject::Config { id: SyncConfigId::Item { id }, .. } => {
+                exists(&created, ObjectKind::Item, id)?;
+            }
+            SyncObject::Config { id: SyncConfigId::Occ { id }, .. } => {
+                exists(&created, ObjectKind::Occ, id)?;
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Plans the updates for the changes in a request, on the server.
+#[derive(Default)]
+struct Exchange {
+    /// Latest changes made on the server since the request's checkpoint.
+    changed: HashMap<ObjectId, Revision>,
+    id_tokens: IdTokens,
+    /// Generated tokens for created objects, by kind and client ID.
+    tokens: HashMap<(ObjectKind, String), IdToken>,
+    planned: Vec<Planned>,
+    /// Objects changed by the request.
+    applied: HashSet<ObjectId>,
+    conflicts: u32,
+}
+
+impl Exchange {
+    /// Whether `object` was changed on the server after `date`.
+    fn changed_after(&self, object: &ObjectId, date: OccDate) -> bool {
+        self.changed.get(object)
+            .is_some_and(|revision| revision.date > date)
+    }
+
+    /// Resolve a reference to an object which must exist, returning `None` if
+    /// it doesn't.
+    fn resolve(&self, db: &impl Db, kind: ObjectKind, id: &SyncId)
+    -> DbResult<Option<PlannedRef>> {
+        Ok(match id {
+            SyncId::Server(id) => {
+                let exists = match kind {
+                    ObjectKind::Item => !db.get_items(&[id])?.is_empty(),
+                    ObjectKind::Occ => !db.get_occs(&[id])?.is_empty(),
+                    ObjectKind::ProgressEntry => {
+                        !db.get_progress_entries(&[id])?.is_empty()
+                    }
+                };
+                exists.then(|| PlannedRef::Id(id.clone()))
+            }
+            SyncId::Client(id) => self.tokens.get(&(kind, id.clone()))
+                .map(|token| PlannedRef::Token(*token)),
+        })
+    }
+
+    /// Generate a token for an object created by the client.
+    fn create(&mut self, kind: ObjectKind, id: &str) -> IdToken {
+        let token = self.id_tokens.allocate();
+        self.tokens.insert((kind, id.to_owned()), token);
+        token
+    }
+
+    /// Plan the update for a change, other than for configs.
+    fn plan(&mut self, db: &impl Db, change: &SyncChange) -> DbResult<()> {
+        let date = change.date;
+        match &change.object {
+            SyncObject::Item { id: SyncId::Server(id), item } => {
+                let object = ObjectId::Item { id: id.clone() };
+                match item {
+                    None => self.planned.push(Planned::DeleteItem(id.clone())),
+                    Some(item) => {
+                        let existing = db.get_items(&[id])?.pop();
+                        let Some(existing) = existing
+                            .filter(|_| !self.changed_after(&object, date))
+                        else {
+                            self.conflicts += 1;
+                            return Ok(());
+                        };
+                        self.planned.push(Planned::UpdateItem(
+                            StoredItem { item: item.clone(), ..existing }));
+                    }
+                }
+                self.applied.insert(object);
+            }
+            SyncObject::Item { id: SyncId::Client(id), item: Some(item) } => {
+                let token = self.create(ObjectKind::Item, id);
+                self.planned.push(
+                    Planned::CreateItem { token, item: item.clone() });
+            }
+
+            SyncObject::Occ { id: SyncId::Server(id), occ, .. } => {
+                let object = ObjectId::Occ { id: id.clone() };
+                match occ {
+                    None => self.planned.push(Planned::DeleteOcc(id.clone())),
+                    Some(occ) => {
+                        let exists = !db.get_occs(&[id])?.is_empty();
+                        if !exists || self.changed_after(&object, date) {
+                            self.conflicts += 1;
+                            return Ok(());
+                        }
+                        self.planned.push(Planned::UpdateOcc(StoredOcc {
+                            id: id.clone(),
+                            occ: occ.clone(),
+                        }));
+                    }
+                }
+                self.applied.insert(object);
+            }
+            SyncObject::Occ {
+                id: SyncId::Client(id),
+                item_id: Some(item_id),
+                occ: Some(occ),
+            } => {
+                let Some(item_id) = self.resolve(db, ObjectKind::Item, item_id)?
+                else {
+                    self.conflicts += 1;
+                    return Ok(());
+                };
+                let token = self.create(ObjectKind::Occ, id);
+                self.planned.push(
+                    Planned::CreateOcc { token, item_id, occ: occ.clone() });
+            }
+
+            SyncObject::ProgressEntry {
+                id: SyncId::Server(id),
+                entry: None,
+                ..
+            } => {
+                self.planned.push(Planned::DeleteProgressEntry(id.clone()));
+                self.applied.insert(ObjectId::ProgressEntry { id: id.clone() });
+            }
+            SyncObject::ProgressEntry {
+                id: SyncId::Client(id),
+                occ_id: Some(occ_id),
+                entry: Some(entry),
+            } => {
+                let Some(occ_id) = self.resolve(db, ObjectKind::Occ, occ_id)?
+                else {
+                    self.conflicts += 1;
+                    return Ok(());
+                };
+                let token = self.create(ObjectKind::ProgressEntry, id);
+                self.planned.push(Planned::CreateProgressEntry {
+                    token,
+                    occ_id,
+                    entry: entry.clone(),
+                });
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Plan the update for a change to a config, once objects created by the
+    /// client have been given IDs.
+    fn plan_config(
+        &mut self,
+        db: &impl Db,
+        change: &SyncChange,
+        created: &HashMap<(ObjectKind, String), String>,
+    ) -> DbResult<()> {
+        let SyncObject::Config { id, config } = &change.object else {
+            return Ok(());
+        };
+        let id = id.resolve(|kind, id| match id {
+            SyncId::Server(id) => Some(id.clone()),
+            SyncId::Client(id) => created.get(&(kind, id.clone())).cloned(),
+        });
+        let Some(id) = id else {
+            self.conflicts += 1;
+            return Ok(());
+        };
+        let object = ObjectId::Config { id: id.clone() };
+        match config {
+            None => self.planned.push(Planned::DeleteConfig(id)),
+            Some(config) => {
+                let target = match &id {
+                    ConfigId::Item { id } => {
+                        Some((ObjectKind::Item, SyncId::Server(id.clone())))
+                    }
+                    ConfigId::Occ { id } => {
+                        Some((ObjectKind::Occ, SyncId::Server(id.clone())))
+                    }
+                    _ => None,
+                };
+                let exists = match target {
+                    Some((kind, id)) => self.resolve(db, kind, &id)?.is_some(),
+                    None => true,
+                };
+                if !exists || self.changed_after(&object, change.date) {
+                    self.conflicts += 1;
+                    return Ok(());
+                }
+                self.planned.push(Planned::SetConfig(StoredConfig {
+                    id,
+                    config: config.clone(),
+                }));
+            }
+        }
+        self.applied.insert(object);
+        Ok(())
+    }
+}
+
+/// Apply the changes from a client's `request` to the server's `db`, and get
+/// the changes to send back.  The request should have been checked using
+/// [`check`].
+///
+/// Changes are written in two writes: configs are written after other objects,
+/// so that they can refer to objects created by the request.  These aren't
+/// done in one transaction, so if writing configs fails, the other changes
+/// stay applied.  The client doesn't get the IDs of the objects created, so if
+/// it retries the request, they're created again.
+pub fn exchange(db: &mut impl Db, request: &SyncRequest, now: OccDate)
+-> DbResult<SyncResponse> {
+    let revisions = match request.checkpoint {
+        Some(checkpoint) => db.find_revisions(Some(checkpoint), u32::MAX)?,
+        None => all_objects(db, now)?,
+    };
+    let latest = latest_revision(db, request.checkpoint)?;
+
+    let mut exchange = Exchange {
+        changed: revisions.iter()
+            .filter(|_| request.checkpoint.is_some())
+            .map(|revision| (revision.object.clone(), revision.clone()))
+            .collect(),
+        ..Exchange::default()
+    };
+    for change in &request.changes {
+        exchange.plan(db, change)?;
+    }
+    let mut ids = write(db, &std::mem::take(&mut exchange.planned))?;
+    let created = exchange.tokens.iter()
+        .map(|(key, token)| {
+            ids.remove(token)
+                .map(|id| (key.clone(), id))
+                .ok_or("unknown error - ID not returned".to_owned())
+        })
+        .collect::<DbResult<HashMap<_, _>>>()?;
+
+    for change in &request.changes {
+        exchange.plan_config(db, change, &created)?;
+    }
+    write(db, &exchange.planned)?;
+    let Exchange { applied, conflicts, .. } = exchange;
+
+    let revisions = revisions.into_iter()
+        .filter(|revision| !applied.contains(&revision.object))
+        .collect::<Vec<_>>();
+    let changes = read_changes(
+        db, &revisions, |_, id| SyncId::Server(id.to_owned()))?;
+    let mut created = created.into_iter()
+        .map(|((kind, client), server)| CreatedId { kind, client, server })
+        .collect::<Vec<_>>();
+    created.sort_by(|a, b| a.server.cmp(&b.server));
+    Ok(SyncResponse {
+        checkpoint: latest_revision(db, Some(latest))?,
+        changes,
+        created,
+        conflicts,
+    })
+}
+
+/// Objects on a client which the server has, with their IDs on the server.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+pub struct IdMapping {
+    pub kind: ObjectKind,
+    /// ID on the client.
+    pub local: String,
+    /// ID on the server.
+    pub server: String,
+}
+
+/// State of a client, kept between syncs.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Deserialize, Serialize)]
+pub struct SyncState {
+    /// `checkpoint` from the server's last response.
+    pub checkpoint: Option<u64>,
+    /// Latest revision of the client's database which has been synced.
+    pub local_checkpoint: Option<u64>,
+    pub ids: Vec<IdMapping>,
+}
+
+impl SyncState {
+    /// Read state written by [`save`](SyncState::save), or get the initial
+    /// state if there's no file at `path`.
+    pub fn load(path: &Path) -> Result<SyncState, String> {
+        if !path.exists() {
+            return Ok(SyncState::default());
+        }
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("error reading sync state ({}): {e}",
+                                 path.display()))?;
+        serde_json::from_str(&text)
+            .map_err(|e| format!("invalid sync state ({}): {e}",
+                                 path.display()))
+    }
+
+    /// Write the state to a file at `path`.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("error creating directory ({}): {e}",
+                                     parent.display()))?;
+        }
+        let text = serde_json::to_string(self)
+            .map_err(|e| format!("error serialising sync state: {e}"))?;
+        fs::write(path, text)
+            .map_err(|e| format!("error writing sync state ({}): {e}",
+                                 path.display()))
+    }
+}
+
+/// Server which a client syncs with.
+pub trait SyncRemote {
+    /// Send a request to the server, and get its response.
+    fn exchange(&self, request: &SyncRequest) -> Result<SyncResponse, String>;
+}
+
+/// [`SyncRemote`] using the web server's `/sync` endpoint.  Requires the
+/// `http` feature.
+#[cfg(feature = "http")]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct HttpRemote {
+    /// URL of the endpoint.
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+#[cfg(feature = "http")]
+impl HttpRemote {
+    /// `api_url` is the URL the web server's API is served at.  `headers` are
+    /// added to every request, such as to identify the user.
+    pub fn new(api_url: &str, headers: Vec<(String, String)>) -> HttpRemote {
+        HttpRemote {
+            url: format!("{}/sync", api_url.trim_end_matches('/')),
+            headers,
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+impl SyncRemote for HttpRemote {
+    fn exchange(&self, request: &SyncRequest) -> Result<SyncResponse, String> {
+        let mut req = ureq::AgentBuilder::new().timeout(TIMEOUT).build()
+            .post(&self.url);
+        for (name, value) in &self.headers {
+            req = req.set(name, value);
+        }
+        req.send_json(request)
+            .map_err(|e| format!("error syncing with server: {e}"))?
+            .into_json()
+            .map_err(|e| format!("invalid response from server: {e}"))
+    }
+}
+
+/// Result of a sync, from the client's side.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize)]
+pub struct SyncSummary {
+    /// Number of changes sent to the server.
+    pub sent: usize,
+    /// Number of changes received from the server.
+    pub received: usize,
+    /// Number of changes sent which were discarded because of conflicting
+    /// changes on the server.
+    pub conflicts: u32,
+}
+
+/// Syncs a client's database with a server.
+#[derive(Clone, Debug, Default)]
+pub struct SyncEngine {
+    checkpoint: Option<u64>,
+    local_checkpoint: Option<u64>,
+    /// IDs on the server, by kind and ID on the client.
+    to_server: HashMap<(ObjectKind, String), String>,
+    /// IDs on the client, by kind and ID on the server.
+    to_local: HashMap<(ObjectKind, String), String>,
+}
+
+impl SyncEngine {
+    /// Continue from the state of the previous sync.
+    pub fn new(state: SyncState) -> SyncEngine {
+        let mut engine = SyncEngine {
+            checkpoint: state.checkpoint,
+            local_checkpoint: state.local_checkpoint,
+            ..SyncEngine::default()
+        };
+        for mapping in state.ids {
+            engine.map(mapping.kind, mapping.local, mapping.server);
+        }
+        engine
+    }
+
+    /// Get the state to continue from in the next sync.
+    pub fn state(&self) -> SyncState {
+        let mut ids = self.to_server.iter()
+            .map(|((kind, local), server)| IdMapping {
+                kind: *kind,
+                local: local.clone(),
+                server: server.clone(),
+            })
+            .collect::<Vec<_>>();
+        ids.sort_by(|a, b| a.local.cmp(&b.local));
+        SyncState {
+            checkpoint: self.checkpoint,
+            local_checkpoint: self.local_checkpoint,
+            ids,
+        }
+    }
+
+    fn map(&mut self, kind: ObjectKind, local: String, server: String) {
+        self.to_local.insert((kind, server.clone()), local.clone());
+        self.to_server.insert((kind, local), server);
+    }
+
+    fn unmap(&mut self, kind: ObjectKind, local: &str) {
+        if let Some(server) = self.to_server.remove(&(kind, local.to_owned())) {
+            self.to_local.remove(&(kind, server));
+        }
+    }
+
+    fn local_id(&self, kind: ObjectKind, id: &SyncId) -> Option<&String> {
+        match id {
+            SyncId::Server(id) => self.to_local.get(&(kind, id.clone())),
+            SyncId::Client(_) => None,
+        }
+    }
+
+    /// Resolve a reference to a parent object from the server, which either
+    /// exists locally or is created by an earlier change with a token in
+    /// `tokens`.
+    fn parent(
+        &self,
+        kind: ObjectKind,
+        id: &Option<SyncId>,
+        tokens: &HashMap<(ObjectKind, String), IdToken>,
+    ) -> Option<PlannedRef> {
+        let id = id.as_ref()?;
+        if let Some(local) = self.local_id(kind, id) {
+            return Some(PlannedRef::Id(local.clone()));
+        }
+        let SyncId::Server(id) = id else { return None };
+        tokens.get(&(kind, id.clone())).map(|token| PlannedRef::Token(*token))
+    }
+
+    /// Get the changes made to `db` since the last sync, to send.
+    fn local_changes(&self, db: &impl Db, now: OccDate)
+    -> DbResult<Vec<SyncChange>> {
+        let revisions = match self.local_checkpoint {
+            Some(checkpoint) => db.find_revisions(Some(checkpoint), u32::MAX)?,
+            None => all_objects(db, now)?,
+        };
+        let changes = read_changes(db, &revisions, |kind, id| {
+            match self.to_server.get(&(kind, id.to_owned())) {
+                Some(server) => SyncId::Server(server.clone()),
+                None => SyncId::Client(id.to_owned()),
+            }
+        })?;
+        // the server doesn't need to know about objects it never had, and
+        // progress entries can't change
+        let mut changes = changes.into_iter()
+            .filter(|change| !matches!(
+                &change.object,
+                SyncObject::Item { id: SyncId::Client(_), item: None } |
+                SyncObject::Occ { id: SyncId::Client(_), occ: None, .. } |
+                SyncObject::ProgressEntry {
+                    id: SyncId::Client(_), entry: None, ..
+                } |
+                SyncObject::ProgressEntry {
+                    id: SyncId::Server(_), entry: Some(_), ..
+                }
+            ))
+            .collect::<Vec<_>>();
+        changes.sort_by_key(|change| change.object.order());
+        Ok(changes)
+    }
+
+    /// Plan the update for a change from the server, other than for configs.
+    ///
+    /// `tokens` are for objects created by earlier changes, by kind and ID on
+    /// the server, and are allocated from `id_tokens`.
+    fn plan(
+        &mut self,
+        db: &impl Db,
+        change: &SyncChange,
+        id_tokens: &mut IdTokens,
+        tokens: &mut HashMap<(ObjectKind, String), IdToken>,
+        planned: &mut Vec<Planned>,
+    ) -> DbResult<()> {
+        fn create(
+            id_tokens: &mut IdTokens,
+            tokens: &mut HashMap<(ObjectKind, String), IdToken>,
+            kind: ObjectKind,
+            id: &SyncId,
+        ) -> Option<IdToken> {
+            let SyncId::Server(id) = id else { return None };
+            let token = id_tokens.allocate();
+            tokens.insert((kind, id.clone()), token);
+            Some(token)
+        }
+
+        match &change.object {
+            SyncObject::Item { id, item: Some(item) } => {
+                let existing = match self.local_id(ObjectKind::Item, id) {
+                    Some(local) => db.get_items(&[local])?.pop(),
+                    None => None,
+                };
+                if let Some(existing) = existing {
+                    planned.push(Planned::UpdateItem(
+                        StoredItem { item: item.clone(), ..existing }));
+                } else if let Some(token) = create(
+                    id_tokens, tokens, ObjectKind::Item, id)
+                {
+                    planned.push(
+                        Planned::CreateItem { token, item: item.clone() });
+                }
+            }
+            SyncObject::Item { id, item: None } => {
+                if let Some(local) = self.local_id(ObjectKind::Item, id) {
+                    let local = local.clone();
+                    planned.push(Planned::DeleteItem(local.clone()));
+                    self.unmap(ObjectKind::Item, &local);
+                }
+            }
+
+            SyncObject::Occ { id, item_id, occ: Some(occ) } => {
+                let existing = match self.local_id(ObjectKind::Occ, id) {
+                    Some(local) => db.get_occs(&[local])?.pop(),
+                    None => None,
+                };
+                if let Some(existing) = existing {
+                    planned.push(Planned::UpdateOcc(
+                        StoredOcc { occ: occ.clone(), ..existing }));
+                } else if let Some(item_id) = self.parent(
+                    ObjectKind::Item, item_id, tokens)
+                {
+                    if let Some(token) = create(
+                        id_tokens, tokens, ObjectKind::Occ, id)
+                    {
+                        planned.push(Planned::CreateOcc {
+                            token,
+                            item_id,
+                            occ: occ.clone(),
+                        });
+                    }
+                }
+            }
+            SyncObject::Occ { id, occ: None, .. } => {
+                if let Some(local) = self.local_id(ObjectKind::Occ, id) {
+                    let local = local.clone();
+                    planned.push(Planned::DeleteOcc(local.clone()));
+                    self.unmap(ObjectKind::Occ, &local);
+                }
+            }
+
+            SyncObject::ProgressEntry { id, occ_id, entry: Some(entry) } => {
+                if self.local_id(ObjectKind::ProgressEntry, id).is_none() {
+                    if let Some(occ_id) = self.parent(
+                        ObjectKind::Occ, occ_id, tokens)
+                    {
+                        if let Some(token) = create(
+                            id_tokens, tokens, ObjectKind::ProgressEntry, id)
+                        {
+                            planned.push(Planned::CreateProgressEntry {
+                                token,
+                                occ_id,
+                                entry: entry.clone(),
+                            });
+                        }
+                    }
+                }
+            }
+            SyncObject::ProgressEntry { id, entry: None, .. } => {
+                let local = self.local_id(ObjectKind::ProgressEntry, id)
+                    .cloned();
+                if let Some(local) = local {
+                    planned.push(Planned::DeleteProgressEntry(local.clone()));
+                    self.unmap(ObjectKind::ProgressEntry, &local);
+                }
+            }
+
+            SyncObject::Config { .. } => (),
+        }
+        Ok(())
+    }
+
+    /// Apply changes received from the server to `db`.
+    fn apply(&mut self, db: &mut impl Db, changes: &[SyncChange])
+    -> DbResult<()> {
+        let mut changes = changes.iter().collect::<Vec<_>>();
+        changes.sort_by_key(|change| change.object.order());
+
+        let mut id_tokens = IdTokens::new();
+        let mut tokens = HashMap::new();
+        let mut planned = vec![];
+        for change in &changes {
+            self.plan(db, change, &mut id_tokens, &mut tokens, &mut planned)?;
+        }
+        let mut ids = write(db, &planned)?;
+        for ((kind, server), token) in tokens {
+            if let Some(local) = ids.remove(&token) {
+                self.map(kind, local, server);
+            }
+        }
+
+        let mut planned = vec![];
+        for change in &changes {
+            let SyncObject::Config { id, config } = &change.object else {
+                continue;
+            };
+            let Some(id) = id.resolve(|kind, id| {
+                self.local_id(kind, id).cloned()
+            }) else {
+                continue;
+            };
+            planned.push(match config {
+                Some(config) => Planned::SetConfig(StoredConfig {
+                    id,
+                    config: config.clone(),
+                }),
+                None => Planned::DeleteConfig(id),
+            });
+        }
+        write(db, &planned)?;
+        Ok(())
+    }
+
+    /// Sync `db` with the server: send the changes made since the last sync,
+    /// and apply the changes received back.
+    ///
+    /// `db` shouldn't be written to by anything else during the sync.  If the
+    /// sync fails, it's safe to try again.
+    pub fn sync(
+        &mut self,
+        db: &mut impl Db,
+        remote: &impl SyncRemote,
+        now: OccDate,
+    ) -> Result<SyncSummary, String> {
+        let changes = self.local_changes(db, now)?;
+        let sent = changes.len();
+        let response = remote.exchange(&SyncRequest {
+            checkpoint: self.checkpoint,
+            changes,
+        })?;
+
+        for created in &response.created {
+            self.map(created.kind, created.client.clone(),
+                     created.server.clone());
+        }
+        self.apply(db, &response.changes)?;
+        self.checkpoint = Some(response.checkpoint);
+        self.local_checkpoint = Some(latest_revision(
+            db, self.local_checkpoint)?);
+        Ok(SyncSummary {
+            sent,
+            received: response.changes.len(),
+            conflicts: response.conflicts,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use std::cell::RefCell;
+    use std::time::Duration;
+    use chrono::TimeDelta;
+    use crate::config::args;
+    use crate::db::{self, ConfigId, Db, DbUpdate, SortDirection, StoredConfig,
+                    StoredItem, WriteBatch};
+    use crate::types::{Config as ItemConfig, DeadlineTaskSched, Item, ItemType,
+                       OccDate, Sched};
+    use super::{check, exchange, latest_revision, IdMapping, ObjectKind,
+                SyncChange, SyncEngine, SyncId, SyncObject, SyncRemote,
+                SyncRequest, SyncResponse};
+
+    type TestDb = Box<dyn Db + Send>;
+
+    /// Server for a [`SyncEngine`], with its database in the same process.
+    struct LocalRemote(RefCell<TestDb>);
+
+    impl SyncRemote for LocalRemote {
+        fn exchange(&self, request: &SyncRequest)
+        -> Result<SyncResponse, String> {
+            check(request)?;
+            exchange(&mut *self.0.borrow_mut(), request, now())
+        }
+    }
+
+    fn now() -> OccDate {
+        "2026-10-17T12:00:00Z".parse().unwrap()
+    }
+
+    /// Open an empty database in memory, which records changes at [`now`].
+    fn open_db() -> TestDb {
+        let schema_path = concat!(
+            env!("CARGO_MANIFEST_DIR"), "/runtime-data/db/schema");
+        let cfg = args::new([
+            "db.backend=sqlite".to_owned(),
+            "db.sqlite.db-path=:memory:".to_owned(),
+            format!("db.sqlite.schema-path={schema_path}"),
+            format!("clock.now={}", now().to_rfc3339()),
+        ]).unwrap();
+        db::open(&cfg).unwrap()
+    }
+
+    fn item(name: &str) -> Item {
+        Item {
+            type_: ItemType::DeadlineTask,
+            active: true,
+            category: None,
+            name: name.to_owned(),
+            desc: None,
+            sched: Sched::DeadlineTask(DeadlineTaskSched {
+                duration: Duration::from_secs(24 * 60 * 60),
+            }),
+        }
+    }
+
+    fn create_item(db: &mut TestDb, item: &Item) -> String {
+        let mut batch = WriteBatch::new();
+        let token = batch.create_item(item);
+        batch.write(db).unwrap().remove(&token).unwrap()
+    }
+
+    fn get_item(db: &TestDb, id: &str) -> Option<StoredItem> {
+        db.get_items(&[id]).unwrap().pop()
+    }
+
+    /// Open a server database with an item, returning its ID and a checkpoint
+    /// from after it was created.
+    fn server_with_item() -> (TestDb, String, u64) {
+        let mut db = open_db();
+        let id = create_item(&mut db, &item("original"));
+        let checkpoint = latest_revision(&db, None).unwrap();
+        (db, id, checkpoint)
+    }
+
+    /// Change to the item with server ID `id`, made `offset` from [`now`].
+    fn item_change(id: &str, item: Option<Item>, offset: TimeDelta)
+    -> SyncChange {
+        SyncChange {
+            date: now() + offset,
+            object: SyncObject::Item {
+                id: SyncId::Server(id.to_owned()),
+                item,
+            },
+        }
+    }
+
+    #[test]
+    fn client_creates_are_given_server_ids() {
+        let mut client = open_db();
+        let remote = LocalRemote(RefCell::new(open_db()));
+        let created = item("client");
+        let local_id = create_item(&mut client, &created);
+        let config = ItemConfig {
+            occ_alert: Some(Duration::from_secs(60 * 60)),
+            ..ItemConfig::default()
+        };
+        client.write(&[&DbUpdate::set_config(&StoredConfig {
+            id: ConfigId::Item { id: local_id.clone() },
+            config: config.clone(),
+        })]).unwrap();
+
+        let mut engine = SyncEngine::default();
+        let summary = engine.sync(&mut client, &remote, now()).unwrap();
+        assert_eq!((summary.sent, summary.conflicts), (2, 0));
+
+        let server = remote.0.borrow();
+        let items = server.find_items(
+            None, None, None, SortDirection::Asc, None, u32::MAX).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].item, created);
+        let server_id = items[0].id.clone();
+        // the config refers to the item created in the same request
+        assert_eq!(server.find_configs().unwrap(), vec![StoredConfig {
+            id: ConfigId::Item { id: server_id.clone() },
+            config,
+        }]);
+        drop(server);
+        assert_eq!(engine.state().ids, vec![IdMapping {
+            kind: ObjectKind::Item,
+            local: local_id,
+            server: server_id,
+        }]);
+
+        // nothing is created again
+        let summary = engine.sync(&mut client, &remote, now()).unwrap();
+        assert_eq!((summary.sent, summary.received), (0, 0));
+        let items = remote.0.borrow().find_items(
+            None, None, None, SortDirection::Asc, None, u32::MAX).unwrap();
+        assert_eq!(items.len(), 1);
+    }
+
+    #[test]
+    fn last_update_wins() {
+        let (mut db, id, checkpoint) = server_with_item();
+        let stored = get_item(&db, &id).unwrap();
+        db.write(&[&DbUpdate::update_item(&StoredItem {
+            item: item("server"),
+            ..stored
+        })]).unwrap();
+
+        // made before the server's change
+        let response = exchange(&mut db, &SyncRequest {
+            checkpoint: Some(checkpoint),
+            changes: vec![item_change(
+                &id, Some(item("client")), TimeDelta::hours(-1))],
+        }, now()).unwrap();
+        assert_eq!(response.conflicts, 1);
+        assert_eq!(get_item(&db, &id).unwrap().item, item("server"));
+        assert_eq!(response.changes, vec![
+            item_change(&id, Some(item("server")), TimeDelta::zero())]);
+
+        // made after the server's change
+        let response = exchange(&mut db, &SyncRequest {
+            checkpoint: Some(checkpoint),
+            changes: vec![item_change(
+                &id, Some(item("client")), TimeDelta::hours(1))],
+        }, now()).unwrap();
+        assert_eq!(response.conflicts, 0);
+        assert_eq!(get_item(&db, &id).unwrap().item, item("client"));
+        assert_eq!(response.changes, vec![]);
+    }
+
+    #[test]
+    fn delete_wins_over_update() {
+        // deleted on the client, before the server's change
+        let (mut db, id, checkpoint) = server_with_item();
+        let stored = get_item(&db, &id).unwrap();
+        db.write(&[&DbUpdate::update_item(&StoredItem {
+            item: item("server"),
+            ..stored
+        })]).unwrap();
+        let response = exchange(&mut db, &SyncRequest {
+            checkpoint: Some(checkpoint),
+            changes: vec![item_change(&id, None, TimeDelta::hours(-1))],
+        }, now()).unwrap();
+        assert_eq!(response.conflicts, 0);
+        assert_eq!(get_item(&db, &id), None);
+
+        // deleted on the server, before the client's change
+        let (mut db, id, checkpoint) = server_with_item();
+        db.write(&[&DbUpdate::delete_item(&id)]).unwrap();
+        let response = exchange(&mut db, &SyncRequest {
+            checkpoint: Some(checkpoint),
+            changes: vec![item_change(
+                &id, Some(item("client")), TimeDelta::hours(1))],
+        }, now()).unwrap();
+        assert_eq!(response.conflicts, 1);
+        assert_eq!(get_item(&db, &id), None);
+        assert_eq!(response.changes, vec![
+            item_change(&id, None, TimeDelta::zero())]);
+    }
+}
@@ -0,0 +1,312 @@
+//! Writing items and occurrences in other formats.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use serde::Serialize;
+use crate::db::{cmp_ids, Db, DbResult, SortDirection, StoredItem, StoredOcc};
+use crate::types::{OccDate, ProgressEntry};
+use super::checklist;
+use super::ical::{self, TaskFormat};
+use super::progress::{self, TaskProgress};
+use super::taskwarrior;
+
+/// Display name of exported calendars.
+const CALENDAR_NAME: &str = "dunsumday";
+/// Number of occurrences to fetch at a time when streaming.
+const STREAM_PAGE_SIZE: u32 = 500;
+/// Columns of CSV exports.
+const CSV_HEADER: [&str; 10] = [
+    "item_id", "item_name", "item_type", "category", "occ_id", "start", "end",
+    "progress", "target", "complete",
+];
+
+/// Supported export formats.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, strum::AsRefStr,
+         strum::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Format {
+    /// Items and occurrences, with the same structure as the web API.
+    #[default]
+    Json,
+    /// A row for each occurrence, with its item.
+    Csv,
+    /// iCalendar, with events for occurrences (see [`ical::calendar`]).
+    Ics,
+    /// Taskwarrior's JSON format, with tasks for task occurrences (see
+    /// [`taskwarrior::tasks`]).
+    Taskwarrior,
+    /// A Markdown checklist of task occurrences, grouped by day (see
+    /// [`checklist::markdown`]).
+    Markdown,
+}
+
+impl Format {
+    /// Media type of exports in this format.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Json | Format::Taskwarrior => "application/json",
+            Format::Csv => "text/csv",
+            Format::Ics => "text/calendar",
+            Format::Markdown => "text/markdown",
+        }
+    }
+}
+
+/// Occurrence in an [`Export`].
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ExportOcc {
+    pub item_id: String,
+    pub occ: StoredOcc,
+    /// Missing for events.
+    pub progress: Option<TaskProgress>,
+    /// Progress entries, ordered by date.
+    pub entries: Vec<ProgressEntry>,
+}
+
+/// Items and occurrences to export.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Export {
+    /// All items, including inactive items.
+    pub items: Vec<StoredItem>,
+    /// Occurrences, ordered by start date.
+    pub occs: Vec<ExportOcc>,
+}
+
+/// Occurrence in a JSON Lines export (see [`OccStream`]), with its item.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StreamedOcc {
+    pub item: StoredItem,
+    #[serde(flatten)]
+    pub occ: ExportOcc,
+}
+
+/// Quote a CSV field if it contains characters with special meaning.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Write a CSV row, including the line break.
+pub fn csv_row<S: AsRef<str>>(fields: &[S]) -> String {
+    let fields = fields.iter()
+        .map(|field| csv_field(field.as_ref()))
+        .collect::<Vec<_>>();
+    fields.join(",") + "\r\n"
+}
+
+/// Get all items, and the occurrences which overlap the time range given by
+/// `start` and `end`, with their progress.
+pub fn fetch(db: &impl Db, start: Option<OccDate>, end: Option<OccDate>)
+-> DbResult<Export> {
+    let items = db.find_items(None, None, None, SortDirection::Asc, None,
+                              u32::MAX)?;
+    let item_ids = items.iter()
+        .map(|item| item.id.as_str())
+        .collect::<Vec<_>>();
+    let occs_by_item = db.find_occs(
+        &item_ids, start, end, SortDirection::Asc, None, u32::MAX)?;
+
+    let items_by_id = items.iter()
+        .map(|item| (item.id.as_str(), item))
+        .collect::<HashMap<_, _>>();
+    let mut occs = occs_by_item.iter()
+        .flat_map(|(item_id, occs)| {
+            occs.iter().map(|occ| (items_by_id[item_id.as_str()], occ))
+        })
+        .collect::<Vec<_>>();
+    occs.sort_by_key(|(_, occ)| occ.occ.start);
+    let occs = export_occs(db, &occs)?;
+    Ok(Export { items, occs })
+}
+
+/// Get progress and progress entries for occurrences, in the same order.
+fn export_occs(db: &impl Db, occs: &[(&StoredItem, &StoredOcc)])
+-> DbResult<Vec<ExportOcc>> {
+    let mut progress = progress::resolve_item_occs_progress(db, occs)?;
+    let occ_ids = occs.iter()
+        .map(|(_, occ)| occ.id.as_str())
+        .collect::<Vec<_>>();
+    let mut entries = db.find_progress_entries(
+        &occ_ids, SortDirection::Asc, u32::MAX)?;
+    Ok(occs.iter()
+        .map(|(item, occ)| ExportOcc {
+            item_id: item.id.clone(),
+            progress: progress.remove(&occ.id),
+            entries: entries.remove(&occ.id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|entry| entry.entry)
+                .collect(),
+            occ: (*occ).clone(),
+        })
+        .collect())
+}
+
+/// Export of occurrences in [JSON Lines](https://jsonlines.org) format, with
+/// one [`StreamedOcc`] per line, which is fetched a page at a time instead of
+/// all at once.  Occurrences are ordered by start date.
+#[derive(Clone, Debug)]
+pub struct OccStream {
+    start: Option<OccDate>,
+    end: Option<OccDate>,
+    /// ID of the last occurrence written.
+    after_id: Option<String>,
+    /// Items of occurrences written so far, by ID.
+    items: HashMap<String, StoredItem>,
+    done: bool,
+}
+
+impl OccStream {
+    /// Export occurrences which overlap the time range given by `start` and
+    /// `end`.
+    pub fn new(start: Option<OccDate>, end: Option<OccDate>) -> OccStream {
+        OccStream {
+            start,
+            end,
+            after_id: None,
+            items: HashMap::new(),
+            done: false,
+        }
+    }
+
+    /// Fetch and write the next page of occurrences, or return `None` when
+    /// there are no more.
+    pub fn next_page(&mut self, db: &impl Db) -> DbResult<Option<String>> {
+        if self.done {
+            return Ok(None);
+        }
+        let occs_by_item = db.find_occs(
+            &[], self.start, self.end, SortDirection::Asc,
+            self.after_id.as_deref(), STREAM_PAGE_SIZE)?;
+        let mut occs = occs_by_item.into_iter()
+            .flat_map(|(item_id, occs)| {
+                occs.into_iter().map(move |occ| (item_id.clone(), occ))
+            })
+            .collect::<Vec<_>>();
+        occs.sort_by(|(_, a), (_, b)| {
+            a.occ.start.cmp(&b.occ.start).then_with(|| cmp_ids(&a.id, &b.id))
+        });
+        if occs.len() < STREAM_PAGE_SIZE as usize {
+            self.done = true;
+        }
+        let Some((_, last)) = occs.last() else {
+            return Ok(None);
+        };
+        self.after_id = Some(last.id.clone());
+
+        let missing = occs.iter()
+            .map(|(item_id, _)| item_id.as_str())
+            .filter(|id| !self.items.contains_key(*id))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            for item in db.get_items(&missing)? {
+                self.items.insert(item.id.clone(), item);
+            }
+        }
+        let item_occs = occs.iter()
+            .filter_map(|(item_id, occ)| {
+                self.items.get(item_id).map(|item| (item, occ))
+            })
+            .collect::<Vec<_>>();
+
+        let mut out = String::new();
+        for occ in export_occs(db, &item_occs)? {
+            let line = StreamedOcc {
+                item: self.items[&occ.item_id].clone(),
+                occ,
+            };
+            out.push_str(&serde_json::to_string(&line)
+                .map_err(|e| format!("error serialising export: {e}"))?);
+            out.push('\n');
+        }
+        Ok(Some(out))
+    }
+
+    /// Write all remaining occurrences to `out`.
+    pub fn write_all(&mut self, db: &impl Db, out: &mut impl Write)
+    -> Result<(), String> {
+        while let Some(page) = self.next_page(db)? {
+            out.write_all(page.as_bytes())
+                .map_err(|e| format!("error writing export: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Export {
+    fn csv(&self) -> String {
+        let items_by_id = self.items.iter()
+            .map(|item| (item.id.as_str(), item))
+            .collect::<HashMap<_, _>>();
+        let mut out = csv_row(&CSV_HEADER);
+        for occ in &self.occs {
+            let Some(item) = items_by_id.get(occ.item_id.as_str()) else {
+                continue
+            };
+            let (progress, target, complete) = match &occ.progress {
+                Some(progress) => (
+                    progress.effective_progress().to_string(),
+                    progress.total.to_string(),
+                    progress.is_complete().to_string(),
+                ),
+                None => (String::new(), String::new(), String::new()),
+            };
+            out.push_str(&csv_row(&[
+                item.id.as_str(),
+                &item.item.name,
+                item.item.type_.as_ref(),
+                item.item.category.as_deref().unwrap_or_default(),
+                &occ.occ.id,
+                &occ.occ.occ.start.to_rfc3339(),
+                &occ.occ.occ.end.to_rfc3339(),
+                &progress,
+                &target,
+                &complete,
+            ]));
+        }
+        out
+    }
+
+    fn ics(&self, now: OccDate) -> String {
+        let items_by_id = self.items.iter()
+            .map(|item| (item.id.as_str(), item))
+            .collect::<HashMap<_, _>>();
+        let occs = self.occs.iter()
+            .filter_map(|occ| {
+                items_by_id.get(occ.item_id.as_str())
+                    .map(|item| (*item, &occ.occ))
+            })
+            .collect::<Vec<_>>();
+        let progress = self.occs.iter()
+            .filter_map(|occ| {
+                occ.progress.map(|progress| (occ.occ.id.clone(), progress))
+            })
+            .collect();
+        ical::calendar(CALENDAR_NAME, &occs, &progress, TaskFormat::Event, now)
+    }
+
+    /// Write the export in the given format.  `now` is used as the time the
+    /// export was created, where the format records it.
+    pub fn write(&self, format: Format, now: OccDate)
+    -> Result<String, String> {
+        match format {
+            Format::Json => serde_json::to_string_pretty(self)
+                .map_err(|e| format!("error serialising export: {e}")),
+            Format::Csv => Ok(self.csv()),
+            Format::Ics => Ok(self.ics(now)),
+            Format::Taskwarrior => {
+                serde_json::to_string_pretty(&taskwarrior::tasks(self))
+                    .map_err(|e| format!("error serialising export: {e}"))
+            }
+            Format::Markdown => Ok(checklist::markdown(self)),
+        }
+    }
+}
@@ -0,0 +1,435 @@
+//! Mirroring upcoming occurrences into a
+//! [Google Calendar](https://developers.google.com/calendar/api).
+//!
+//! Pushing is one-way: each active occurrence in the period being mirrored has
+//! an event, which is updated to match the occurrence, and events for
+//! occurrences which no longer exist, are inactive, or have moved out of the
+//! period are removed.  Changes made to the events in Google Calendar are
+//! overwritten.  Events are marked so that other events in the calendar are
+//! never changed.
+//!
+//! Events are shown at the same times as in [`ical::calendar`] with
+//! [`TaskFormat::Event`](super::ical::TaskFormat::Event): task occurrences are
+//! shown at their due date, and don't make the calendar's owner busy.
+//! Completed tasks are marked with a tick.
+//!
+//! Requests are authorised using OAuth: see [`authorize_url`] and
+//! [`exchange_code`] for getting a refresh token.
+//!
+//! [`ical::calendar`]: super::ical::calendar
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use chrono::{DateTime, SecondsFormat, TimeDelta};
+use serde::{Deserialize, Serialize};
+use crate::config::{self, Config};
+use crate::configrefs;
+use crate::db::{Db, DbResult, SortDirection, StoredItem, StoredOcc};
+use crate::types::{ItemType, OccDate};
+use super::progress::{self, TaskProgress};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+const AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const API_URL: &str = "https://www.googleapis.com/calendar/v3";
+/// OAuth scope needed to manage events.
+const SCOPE: &str = "https://www.googleapis.com/auth/calendar.events";
+/// Private extended property marking events created by this module.
+const MARKER_PROPERTY: &str = "dunsumday";
+const MARKER_VALUE: &str = "1";
+/// Largest page of events to request.
+const MAX_PAGE_SIZE: &str = "2500";
+
+/// OAuth client details and refresh token used to authorise requests.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Credentials {
+    pub client_id: String,
+    pub client_secret: String,
+    pub refresh_token: String,
+}
+
+/// Result of pushing occurrences to a calendar.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize)]
+pub struct Pushed {
+    /// Number of events created.
+    pub created: usize,
+    /// Number of events changed to match their occurrences.
+    pub updated: usize,
+    /// Number of events removed.
+    pub removed: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+struct EventTime {
+    #[serde(rename = "dateTime")]
+    date_time: Option<String>,
+}
+
+impl EventTime {
+    fn new(date: OccDate) -> EventTime {
+        EventTime {
+            date_time: Some(date.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        }
+    }
+
+    fn date(&self) -> Option<OccDate> {
+        DateTime::parse_from_rfc3339(self.date_time.as_deref()?).ok()
+            .map(|date| date.to_utc())
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+struct ExtendedProperties {
+    #[serde(default)]
+    private: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Event {
+    id: String,
+    #[serde(default)]
+    summary: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    start: EventTime,
+    #[serde(default)]
+    end: EventTime,
+    #[serde(default)]
+    transparency: Option<String>,
+    #[serde(default)]
+    extended_properties: Option<ExtendedProperties>,
+}
+
+impl Event {
+    /// Whether the event shows the same thing as `other`.
+    fn matches(&self, other: &Event) -> bool {
+        let transparency = |event: &Event| {
+            event.transparency.clone().unwrap_or_else(|| "opaque".to_owned())
+        };
+        self.summary == other.summary &&
+            self.description == other.description &&
+            self.start.date() == other.start.date() &&
+            self.end.date() == other.end.date() &&
+            transparency(self) == transparency(other)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EventsPage {
+    #[serde(default)]
+    items: Vec<Event>,
+    next_page_token: Option<String>,
+}
+
+/// Encode a value for use in a URL.
+fn encode(value: &str) -> String {
+    value.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' |
+            b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Decode a value from a URL query string.
+fn decode(value: &str) -> Result<String, String> {
+    let mut bytes = vec![];
+    let mut rest = value.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+        match b {
+            b'+' => bytes.push(b' '),
+            b'%' if rest.len() >= 2 => {
+                let hex = std::str::from_utf8(&rest[..2])
+                    .map_err(|e| e.to_string())?;
+                bytes.push(u8::from_str_radix(hex, 16)
+                    .map_err(|e| format!("invalid escape in URL: %{hex}"))?);
+                rest = &rest[2..];
+            }
+            _ => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).map_err(|e| format!("invalid URL value: {e}"))
+}
+
+/// Get the URL to send someone to, so they can allow access to their
+/// calendars.  They're then sent to `redirect_uri`, with a `code` to pass to
+/// [`exchange_code`].
+pub fn authorize_url(client_id: &str, redirect_uri: &str) -> String {
+    let params = [
+        ("client_id", client_id),
+        ("redirect_uri", redirect_uri),
+        ("response_type", "code"),
+        ("scope", SCOPE),
+        ("access_type", "offline"),
+        // always return a refresh token, even if access was allowed before
+        ("prompt", "consent"),
+    ];
+    let query = params.iter()
+        .map(|(name, value)| format!("{name}={}", encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{AUTH_URL}?{query}")
+}
+
+/// Get the `code` from the query string of the URL someone was redirected to
+/// by [`authorize_url`].
+pub fn redirect_code(query: &str) -> Result<String, String> {
+    let params = query.split('&')
+        .filter_map(|param| param.split_once('='))
+        .collect::<HashMap<_, _>>();
+    if let Some(error) = params.get("error") {
+        return Err(format!("authorisation failed: {}", decode(error)?));
+    }
+    decode(params.get("code").ok_or("no code in redirect".to_owned())?)
+}
+
+/// Exchange a `code` from [`authorize_url`] for a refresh token.
+/// `redirect_uri` must be the same as passed to [`authorize_url`].
+pub fn exchange_code(
+    client_id: &str,
+    client_secret: &str,
+    code: &str,
+    redirect_uri: &str,
+) -> Result<String, String> {
+    let response: TokenResponse = ureq::post(TOKEN_URL)
+        .timeout(TIMEOUT)
+        .send_form(&[
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("grant_type", "authorization_code"),
+        ])
+        .map_err(|e| format!("error getting token: {e}"))?
+        .into_json()
+        .map_err(|e| format!("invalid token response: {e}"))?;
+    response.refresh_token.ok_or("no refresh token in response".to_owned())
+}
+
+/// Authorised connection to a Google Calendar.
+#[derive(Debug)]
+pub struct Client {
+    agent: ureq::Agent,
+    calendar_url: String,
+    access_token: String,
+}
+
+impl Client {
+    /// Connect to the calendar with ID `calendar_id`, which is `primary` for
+    /// the main calendar of the account the credentials belong to.
+    pub fn new(credentials: &Credentials, calendar_id: &str)
+    -> Result<Client, String> {
+        let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+        let response: TokenResponse = agent.post(TOKEN_URL)
+            .send_form(&[
+                ("client_id", &credentials.client_id),
+                ("client_secret", &credentials.client_secret),
+                ("refresh_token", &credentials.refresh_token),
+                ("grant_type", "refresh_token"),
+            ])
+            .map_err(|e| format!("error refreshing token: {e}"))?
+            .into_json()
+            .map_err(|e| format!("invalid token response: {e}"))?;
+        Ok(Client {
+            agent,
+            calendar_url: format!("{API_URL}/calendars/{}",
+                                  encode(calendar_id)),
+            access_token: response.access_token,
+        })
+    }
+
+    /// Connect to the calendar configured in `cfg`, or return `None` if no
+    /// calendar is configured.
+    pub fn from_config(cfg: &impl Config) -> Result<Option<Client>, String> {
+        let calendar_id = cfg.get_ref(&configrefs::SYNC_GCAL_CALENDAR_ID);
+        if calendar_id.is_empty() {
+            return Ok(None);
+        }
+        let credentials = Credentials {
+            client_id: cfg.get_ref(&configrefs::SYNC_GCAL_CLIENT_ID).to_owned(),
+            client_secret: config::get_parsed(
+                cfg, &configrefs::SYNC_GCAL_CLIENT_SECRET)?,
+            refresh_token: config::get_parsed(
+                cfg, &configrefs::SYNC_GCAL_REFRESH_TOKEN)?,
+        };
+        Client::new(&credentials, calendar_id).map(Some)
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        self.agent.request(method, &format!("{}{path}", self.calendar_url))
+            .set("Authorization", &format!("Bearer {}", self.access_token))
+    }
+
+    /// Get the marked events which overlap the period from `start` to `end`.
+    fn list(&self, start: OccDate, end: OccDate) -> Result<Vec<Event>, String> {
+        let mut events = vec![];
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut req = self.request("GET", "/events")
+                .query("privateExtendedProperty",
+                       &format!("{MARKER_PROPERTY}={MARKER_VALUE}"))
+                .query("timeMin", &EventTime::new(start).date_time.unwrap())
+                .query("timeMax", &EventTime::new(end).date_time.unwrap())
+                .query("singleEvents", "true")
+                .query("maxResults", MAX_PAGE_SIZE);
+            if let Some(token) = &page_token {
+                req = req.query("pageToken", token);
+            }
+            let page: EventsPage = req.call()
+                .map_err(|e| format!("error listing events: {e}"))?
+                .into_json()
+                .map_err(|e| format!("invalid events response: {e}"))?;
+            events.extend(page.items);
+            page_token = page.next_page_token;
+            if page_token.is_none() {
+                return Ok(events);
+            }
+        }
+    }
+
+    fn create(&self, event: &Event) -> Result<(), String> {
+        match self.request("POST", "/events").send_json(event) {
+            Ok(_) => Ok(()),
+            // an event with this ID was deleted, and is restored by updating
+            // it
+            Err(ureq::Error::Status(409, _)) => self.update(event),
+            Err(e) => Err(format!("error creating event: {e}")),
+        }
+    }
+
+    fn update(&self, event: &Event) -> Result<(), String> {
+        self.request("PUT", &format!("/events/{}", event.id))
+            .send_json(event)
+            .map(|_| ())
+            .map_err(|e| format!("error updating event: {e}"))
+    }
+
+    fn remove(&self, id: &str) -> Result<(), String> {
+        match self.request("DELETE", &format!("/events/{id}")).call() {
+            Ok(_) |
+            Err(ureq::Error::Status(404, _)) |
+            Err(ureq::Error::Status(410, _)) => Ok(()),
+            Err(e) => Err(format!("error removing event: {e}")),
+        }
+    }
+}
+
+/// Get the ID of the event for an occurrence.  IDs may only contain the
+/// characters `a-v` and `0-9`.
+fn event_id(occ_id: &str) -> String {
+    let hex = occ_id.bytes()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    format!("dsdocc{hex}")
+}
+
+/// Get the event for an occurrence.
+fn occ_event(
+    item: &StoredItem,
+    occ: &StoredOcc,
+    progress: Option<&TaskProgress>,
+) -> Event {
+    let (start, end, transparency) = match item.item.type_ {
+        ItemType::Event => (occ.occ.start, occ.occ.end, None),
+        _ => (occ.occ.end, occ.occ.end, Some("transparent".to_owned())),
+    };
+    let summary = match progress {
+        Some(progress) if progress.is_complete() => {
+            format!("✓ {}", item.item.name)
+        }
+        _ => item.item.name.clone(),
+    };
+    Event {
+        id: event_id(&occ.id),
+        summary: Some(summary),
+        description: item.item.desc.clone(),
+        start: EventTime::new(start),
+        end: EventTime::new(end),
+        transparency,
+        extended_properties: Some(ExtendedProperties {
+            private: HashMap::from([(MARKER_PROPERTY.to_owned(),
+                                     MARKER_VALUE.to_owned())]),
+        }),
+    }
+}
+
+/// Make the marked events in the calendar match the active occurrences of
+/// active items from `now` until `period` later.  Current occurrences are
+/// generated first.
+pub fn push(
+    db: &mut impl Db,
+    client: &Client,
+    period: Duration,
+    now: OccDate,
+) -> DbResult<Pushed> {
+    let end = TimeDelta::from_std(period).ok()
+        .and_then(|period| now.checked_add_signed(period))
+        .ok_or("period to push is too long".to_owned())?;
+    super::get_current_items(db, now)?;
+    let items = db.find_items(
+        Some(true), None, Some(now), SortDirection::Asc, None, u32::MAX)?;
+    let item_ids = items.iter()
+        .map(|item| item.id.as_str())
+        .collect::<Vec<_>>();
+    let occs_by_item = db.find_occs(
+        &item_ids, Some(now), Some(end), SortDirection::Asc, None, u32::MAX)?;
+    let occs = items.iter()
+        .flat_map(|item| {
+            occs_by_item.get(&item.id).into_iter()
+                .flatten()
+                .filter(|occ| occ.occ.active)
+                .map(move |occ| (item, occ))
+        })
+        .collect::<Vec<_>>();
+    let progress = progress::resolve_item_occs_progress(&*db, &occs)?;
+
+    // only events in the period are listed, so events for occurrences which
+    // overlap the period but are shown outside it are left alone
+    let wanted = occs.iter()
+        .map(|(item, occ)| occ_event(item, occ, progress.get(&occ.id)))
+        .filter(|event| {
+            event.end.date().is_some_and(|date| date > now) &&
+                event.start.date().is_some_and(|date| date < end)
+        })
+        .collect::<Vec<_>>();
+    let existing = client.list(now, end)?.into_iter()
+        .map(|event| (event.id.clone(), event))
+        .collect::<HashMap<_, _>>();
+
+    let mut pushed = Pushed::default();
+    for event in &wanted {
+        match existing.get(&event.id) {
+            None => {
+                client.create(event)?;
+                pushed.created += 1;
+            }
+            Some(current) if !current.matches(event) => {
+                client.update(event)?;
+                pushed.updated += 1;
+            }
+            Some(_) => (),
+        }
+    }
+    let wanted_ids = wanted.iter()
+        .map(|event| event.id.as_str())
+        .collect::<HashSet<_>>();
+    for id in existing.keys() {
+        if !wanted_ids.contains(id.as_str()) {
+            client.remove(id)?;
+            pushed.removed += 1;
+        }
+    }
+    Ok(pushed)
+}
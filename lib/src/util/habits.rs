@@ -0,0 +1,395 @@
+//! Reading habits, with their history, from the exports of other habit
+//! trackers.
+//!
+//! Habits become progress tasks, with an occurrence for each period from the
+//! first recorded completion to the last, and a progress entry for each
+//! completion.  Create the items using
+//! [`create_history`](super::import::create_history), with the source for the
+//! format, so that importing the same export again updates the same items.
+
+use std::collections::HashMap;
+use std::io::Read;
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveDateTime, NaiveTime};
+use crate::types::{Config, Item, ItemType, Occ, OccDate, ProgressEntry,
+                   ProgressTaskSched, Sched, TaskCompletionConfig};
+use super::import::{ImportedHistory, ImportedItem};
+use super::sched::ProgressTaskPeriodsIter;
+
+/// Source recorded for items imported from Loop Habit Tracker, with habit
+/// names as their external IDs.
+pub const LOOP_SOURCE: &str = "loop";
+/// Source recorded for items imported from Habitica, with task IDs as their
+/// external IDs.
+pub const HABITICA_SOURCE: &str = "habitica";
+
+/// Checkmark value for a day a Loop habit was marked as done.  Days which are
+/// only considered done because of the habit's frequency have other values.
+const LOOP_CHECKED: i64 = 2;
+/// Factor Loop's numerical habit values are multiplied by in exports.
+const LOOP_NUMERICAL_SCALE: i64 = 1000;
+
+fn day_start(day: NaiveDate) -> OccDate {
+    day.and_time(NaiveTime::MIN).and_utc()
+}
+
+/// Read all of `reader` as text.
+fn read_text(mut reader: impl Read, name: &str) -> Result<String, String> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)
+        .map_err(|e| format!("error reading {name}: {e}"))?;
+    Ok(text)
+}
+
+/// Parse CSV text into records, skipping empty lines.
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted => (),
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                if record.iter().any(|field| !field.is_empty()) {
+                    records.push(std::mem::take(&mut record));
+                } else {
+                    record.clear();
+                }
+            }
+            c => field.push(c),
+        }
+    }
+    record.push(field);
+    if record.iter().any(|field| !field.is_empty()) {
+        records.push(record);
+    }
+    records
+}
+
+/// CSV records, with fields looked up by column name.
+struct Table {
+    columns: HashMap<String, usize>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Parse CSV text with a header row.
+    fn parse(text: &str, name: &str) -> Result<Table, String> {
+        let mut records = parse_csv(text).into_iter();
+        let header = records.next().ok_or_else(|| format!("{name} is empty"))?;
+        Ok(Table {
+            columns: header.into_iter()
+                .enumerate()
+                .map(|(i, column)| (column.trim().to_owned(), i))
+                .collect(),
+            rows: records.collect(),
+        })
+    }
+
+    /// Get the index of the first of `names` which is a column.
+    fn column(&self, names: &[&str]) -> Option<usize> {
+        names.iter().find_map(|name| self.columns.get(*name).copied())
+    }
+
+    /// Like [`column`](Table::column), failing if there's no such column.
+    fn required_column(&self, names: &[&str], name: &str)
+    -> Result<usize, String> {
+        self.column(names)
+            .ok_or_else(|| format!("{name} has no {} column", names[0]))
+    }
+}
+
+/// Get a field from a row, which is empty if missing.
+fn field(row: &[String], column: usize) -> &str {
+    row.get(column).map(|value| value.trim()).unwrap_or_default()
+}
+
+/// Get the schedule for a habit done every `interval` days, first done on
+/// `first_day`.
+fn loop_sched(interval: u32, first_day: NaiveDate)
+-> Result<ProgressTaskSched, String> {
+    Ok(match interval {
+        7 => ProgressTaskSched::Weeks {
+            num: 1,
+            start_day: first_day.weekday(),
+        },
+        14 => ProgressTaskSched::Weeks {
+            num: 2,
+            start_day: first_day.weekday(),
+        },
+        30 | 31 => ProgressTaskSched::Months { num: 1, start_day: 1 },
+        365 | 366 => ProgressTaskSched::Years {
+            num: 1,
+            start_month: chrono::Month::January,
+            start_dom: 1,
+        },
+        _ => ProgressTaskSched::Days {
+            num: u8::try_from(interval).ok()
+                .filter(|num| *num > 0)
+                .ok_or_else(|| format!("unsupported interval: {interval}"))?,
+        },
+    })
+}
+
+/// Create occurrences covering all of the days with `entries`, which must be
+/// ordered by date.
+fn history_occs(sched: &ProgressTaskSched, entries: Vec<ProgressEntry>)
+-> Vec<(Occ, Vec<ProgressEntry>)> {
+    let (Some(first), Some(last)) = (entries.first(), entries.last()) else {
+        return vec![];
+    };
+    let last_day = last.date.date_naive();
+    let mut entries = entries.iter().peekable();
+    ProgressTaskPeriodsIter::new(sched, first.date.date_naive())
+        .take_while(|(start, _)| *start <= last_day)
+        .map(|(start, end)| {
+            let (start, end) = (day_start(start), day_start(end));
+            let mut occ_entries = vec![];
+            while let Some(entry) = entries.next_if(|entry| entry.date < end) {
+                occ_entries.push(entry.clone());
+            }
+            let progress = occ_entries.iter()
+                .map(|entry| entry.amount)
+                .sum::<i64>();
+            let occ = Occ {
+                active: true,
+                start,
+                end,
+                task_completion_progress: u32::try_from(progress)
+                    .unwrap_or_default(),
+                snoozed_until: None,
+            };
+            (occ, occ_entries)
+        })
+        .collect()
+}
+
+/// Create an item for a habit.
+fn habit_item(name: &str, desc: &str, sched: ProgressTaskSched) -> Item {
+    Item {
+        type_: ItemType::ProgressTask,
+        active: true,
+        category: None,
+        name: name.to_owned(),
+        desc: Some(desc.to_owned()).filter(|desc| !desc.is_empty()),
+        sched: Sched::ProgressTask(sched),
+    }
+}
+
+/// Create config for a habit with a target of `total` per occurrence, if it
+/// differs from the default.
+fn habit_config(total: u32, unit: &str) -> Option<Config> {
+    (total != 1 || !unit.is_empty()).then(|| Config {
+        task_completion_conf: TaskCompletionConfig {
+            total: Some(total),
+            unit: Some(unit.to_owned()).filter(|unit| !unit.is_empty()),
+            ..TaskCompletionConfig::default()
+        },
+        ..Config::default()
+    })
+}
+
+/// Read habits exported by [Loop Habit Tracker](https://loophabits.org), given
+/// the `Habits.csv` and `Checkmarks.csv` files from the export archive.
+///
+/// The habit's frequency sets the occurrence period and target: for example, a
+/// habit to be done 3 times every 7 days becomes a weekly task with a target
+/// of 3.  Only days marked as done count as completions.  For numerical habits,
+/// values are rounded down to whole numbers.  Archived habits are imported as
+/// inactive items.
+pub fn loop_csv(habits: impl Read, checkmarks: impl Read)
+-> Result<ImportedHistory, String> {
+    let habits = Table::parse(&read_text(habits, "habits")?, "habits")?;
+    let checkmarks = Table::parse(
+        &read_text(checkmarks, "checkmarks")?, "checkmarks")?;
+    let name_col = habits.required_column(&["Name"], "habits")?;
+    let desc_col = habits.column(&["Description"]);
+    let question_col = habits.column(&["Question"]);
+    let reps_col = habits.required_column(
+        &["NumRepetitions", "FrequencyNumerator"], "habits")?;
+    let interval_col = habits.required_column(
+        &["Interval", "FrequencyDenominator"], "habits")?;
+    let type_col = habits.column(&["Type"]);
+    let target_col = habits.column(&["Target Value", "TargetValue"]);
+    let unit_col = habits.column(&["Unit"]);
+    let archived_col = habits.column(&["Archived?", "Archived"]);
+    let date_col = checkmarks.required_column(&["Date"], "checkmarks")?;
+
+    let mut days = checkmarks.rows.iter()
+        .map(|row| {
+            let date = field(row, date_col);
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map(|day| (day, row))
+                .map_err(|_| format!("invalid checkmark date: {date}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    days.sort_by_key(|(day, _)| *day);
+
+    let mut imported = ImportedHistory::default();
+    for row in &habits.rows {
+        let name = field(row, name_col);
+        let habit = || -> Result<ImportedItem, String> {
+            let column = checkmarks.column(&[name])
+                .ok_or("habit has no checkmarks".to_owned())?;
+            let numerical = type_col.is_some_and(|col| {
+                matches!(field(row, col), "1" | "NUMERICAL")
+            });
+            let entries = days.iter()
+                .filter_map(|(day, checkmarks)| {
+                    let value = field(checkmarks, column).parse::<i64>().ok()?;
+                    let amount = if numerical {
+                        value / LOOP_NUMERICAL_SCALE
+                    } else {
+                        i64::from(value == LOOP_CHECKED)
+                    };
+                    (amount > 0).then(|| ProgressEntry {
+                        date: day_start(*day),
+                        amount,
+                        note: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let first_day = entries.first()
+                .map(|entry| entry.date.date_naive())
+                .ok_or("habit was never done".to_owned())?;
+
+            let reps = field(row, reps_col);
+            let reps = reps.parse::<u32>()
+                .map_err(|_| format!("invalid number of repetitions: {reps}"))?;
+            let interval = field(row, interval_col);
+            let interval = interval.parse::<u32>()
+                .map_err(|_| format!("invalid interval: {interval}"))?;
+            let total = if numerical {
+                let target = target_col.map(|col| field(row, col))
+                    .unwrap_or_default();
+                target.parse::<f64>()
+                    .map(|target| target.floor() as u32)
+                    .map_err(|_| format!("invalid target: {target}"))?
+            } else {
+                reps
+            };
+
+            let sched = loop_sched(interval, first_day)?;
+            let desc = [desc_col, question_col].into_iter()
+                .flatten()
+                .map(|col| field(row, col))
+                .find(|desc| !desc.is_empty())
+                .unwrap_or_default();
+            let mut item = habit_item(name, desc, sched);
+            item.active = !archived_col.is_some_and(|col| {
+                matches!(field(row, col).to_ascii_lowercase().as_str(),
+                         "true" | "1" | "yes")
+            });
+            Ok(ImportedItem {
+                external_id: name.to_owned(),
+                item,
+                config: habit_config(
+                    total, unit_col.map(|col| field(row, col))
+                        .unwrap_or_default()),
+                occs: history_occs(&sched, entries),
+            })
+        };
+        match habit() {
+            Ok(habit) => imported.items.push(habit),
+            Err(e) => imported.skipped.push(format!("habit {name:?}: {e}")),
+        }
+    }
+    Ok(imported)
+}
+
+/// Parse a date from a Habitica export.
+fn parse_habitica_date(value: &str) -> Result<OccDate, String> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .map(|date| date.and_utc())
+        .or_else(|_| {
+            DateTime::parse_from_rfc3339(value).map(|date| date.to_utc())
+        })
+        .map_err(|_| format!("invalid date: {value}"))
+}
+
+/// Read habits and dailies from [Habitica](https://habitica.com)'s task
+/// history export (`Tasks history CSV`).
+///
+/// Habitica records a task's value each time it changes, so each increase in
+/// value counts as a completion.  Dailies have their value recorded when the
+/// day ends, so their completions are counted on the day before.  Both become
+/// daily tasks with a target of 1.
+pub fn habitica(reader: impl Read) -> Result<ImportedHistory, String> {
+    let history = Table::parse(&read_text(reader, "history")?, "history")?;
+    let name_col = history.required_column(&["Task Name"], "history")?;
+    let id_col = history.required_column(&["Task ID"], "history")?;
+    let type_col = history.required_column(&["Task Type"], "history")?;
+    let date_col = history.required_column(&["Date"], "history")?;
+    let value_col = history.required_column(&["Value"], "history")?;
+
+    // task IDs in the order they're first found, with their rows
+    let mut ids = vec![];
+    let mut tasks: HashMap<&str, Vec<&Vec<String>>> = HashMap::new();
+    for row in &history.rows {
+        let id = field(row, id_col);
+        tasks.entry(id).or_insert_with(|| {
+            ids.push(id);
+            vec![]
+        }).push(row);
+    }
+
+    let mut imported = ImportedHistory::default();
+    for id in ids {
+        let rows = &tasks[id];
+        let name = field(rows[0], name_col);
+        let task_type = field(rows[0], type_col);
+        let task = || -> Result<ImportedItem, String> {
+            let offset = match task_type {
+                "habit" => Days::new(0),
+                "daily" => Days::new(1),
+                _ => return Err(format!("unsupported task type: {task_type}")),
+            };
+            let mut values = rows.iter()
+                .map(|row| {
+                    let value = field(row, value_col);
+                    Ok((parse_habitica_date(field(row, date_col))?,
+                        value.parse::<f64>()
+                            .map_err(|_| format!("invalid value: {value}"))?))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            values.sort_by_key(|(date, _)| *date);
+
+            let mut previous = 0.0;
+            let mut entries = vec![];
+            for (date, value) in values {
+                if value > previous {
+                    entries.push(ProgressEntry {
+                        date: date - offset,
+                        amount: 1,
+                        note: None,
+                    });
+                }
+                previous = value;
+            }
+            if entries.is_empty() {
+                return Err("task was never done".to_owned());
+            }
+
+            let sched = ProgressTaskSched::Days { num: 1 };
+            Ok(ImportedItem {
+                external_id: id.to_owned(),
+                item: habit_item(name, "", sched),
+                config: None,
+                occs: history_occs(&sched, entries),
+            })
+        };
+        match task() {
+            Ok(task) => imported.items.push(task),
+            Err(e) => imported.skipped.push(format!("task {name:?}: {e}")),
+        }
+    }
+    Ok(imported)
+}
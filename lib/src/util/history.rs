@@ -0,0 +1,115 @@
+//! Timelines of everything that happened to an item.
+
+use serde::Serialize;
+use crate::db::{AuditChange, ConfigId, Db, DbResults, SortDirection,
+                StoredOcc, StoredProgressEntry};
+use crate::types::{Config, Item, OccDate};
+
+/// Something that happened to an item, as part of a [`HistoryEntry`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum HistoryEvent {
+    Created { item: Item },
+    /// The item was updated to a new value.  `sched_changed` is whether its
+    /// schedule changed, or `None` if the previous value isn't known.
+    Updated { item: Item, sched_changed: Option<bool> },
+    Deleted,
+    /// A config scoped to the item or one of its occurrences was set.
+    ConfigSet {
+        #[cfg_attr(feature = "openapi", schema(value_type = String))]
+        scope: ConfigId,
+        config: Config,
+    },
+    ConfigDeleted {
+        #[cfg_attr(feature = "openapi", schema(value_type = String))]
+        scope: ConfigId,
+    },
+    /// One of the item's occurrences started.
+    OccStarted { occ: StoredOcc },
+    /// Progress was logged against one of the item's occurrences, possibly
+    /// with a note.
+    Progress { entry: StoredProgressEntry },
+}
+
+/// Entry in an item's timeline, as returned by [`item_history`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HistoryEntry {
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = String, format = DateTime))]
+    pub date: OccDate,
+    pub event: HistoryEvent,
+}
+
+/// Convert an audit log change to a history event.  `previous` is the item's
+/// value before the change, if known.
+fn audit_event(change: AuditChange, previous: Option<&Item>) -> HistoryEvent {
+    match change {
+        AuditChange::CreateItem(item) => HistoryEvent::Created { item },
+        AuditChange::UpdateItem(item) => HistoryEvent::Updated {
+            sched_changed: previous.map(|prev| prev.sched != item.sched),
+            item,
+        },
+        AuditChange::DeleteItem => HistoryEvent::Deleted,
+        AuditChange::SetConfig(config) => HistoryEvent::ConfigSet {
+            scope: config.id,
+            config: config.config,
+        },
+        AuditChange::DeleteConfig { id } => {
+            HistoryEvent::ConfigDeleted { scope: id }
+        }
+    }
+}
+
+/// Get everything that happened to the item with ID `item_id`, ordered by
+/// date, oldest first: changes to the item and its configs from the
+/// [audit log](Db::find_audit_entries), the start of each stored occurrence,
+/// and each progress entry.
+///
+/// Changes made to the item before the audit log was kept aren't included.
+/// Events with the same date are ordered changes first, then occurrences, then
+/// progress entries.  The result is empty for items which don't exist and
+/// never existed.
+pub fn item_history(db: &impl Db, item_id: &str) -> DbResults<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut item: Option<Item> = None;
+    for entry in db.find_audit_entries(item_id)? {
+        let next = match &entry.change {
+            AuditChange::CreateItem(next) | AuditChange::UpdateItem(next) => {
+                Some(Some(next.clone()))
+            }
+            AuditChange::DeleteItem => Some(None),
+            _ => None,
+        };
+        entries.push(HistoryEntry {
+            date: entry.date,
+            event: audit_event(entry.change, item.as_ref()),
+        });
+        if let Some(next) = next {
+            item = next;
+        }
+    }
+
+    let occs = db.find_occs(
+        &[item_id], None, None, SortDirection::Asc, None, u32::MAX)?
+        .remove(item_id)
+        .unwrap_or_default();
+    let occ_ids = occs.iter().map(|occ| occ.id.as_str()).collect::<Vec<_>>();
+    let mut progress = db.find_progress_entries(
+        &occ_ids, SortDirection::Asc, u32::MAX)?;
+    let progress = occs.iter()
+        .flat_map(|occ| progress.remove(&occ.id).unwrap_or_default())
+        .collect::<Vec<_>>();
+
+    entries.extend(occs.into_iter().map(|occ| HistoryEntry {
+        date: occ.occ.start,
+        event: HistoryEvent::OccStarted { occ },
+    }));
+    entries.extend(progress.into_iter().map(|entry| HistoryEntry {
+        date: entry.entry.date,
+        event: HistoryEvent::Progress { entry },
+    }));
+    // stable, so events with the same date stay in the order added
+    entries.sort_by_key(|entry| entry.date);
+    Ok(entries)
+}
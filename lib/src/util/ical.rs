@@ -0,0 +1,168 @@
+//! Writing occurrences in [iCalendar](https://www.rfc-editor.org/rfc/rfc5545)
+//! format.
+
+use std::collections::HashMap;
+use crate::db::{StoredItem, StoredOcc};
+use crate::types::{ItemType, OccDate};
+use super::progress::TaskProgress;
+
+const PRODID: &str = "-//dunsumday//dunsumday//EN";
+/// Maximum length of a content line in bytes, excluding the line break.
+const MAX_LINE_LEN: usize = 75;
+
+/// Calendar component used to represent task occurrences.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum TaskFormat {
+    /// `VEVENT` at the occurrence's due date, for calendars which don't
+    /// support to-dos.
+    #[default]
+    Event,
+    /// `VTODO` with start and due dates, and completion status.
+    Todo,
+}
+
+/// Escape a value of type `TEXT`.
+fn escape_text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Format a value of type `DATE-TIME`, in UTC.
+fn format_date(date: OccDate) -> String {
+    date.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Builds an iCalendar object from content lines.
+struct Writer {
+    out: String,
+}
+
+impl Writer {
+    /// Write a content line, folding it to the maximum line length.
+    fn line(&mut self, name: &str, value: &str) {
+        let line = format!("{name}:{value}");
+        let mut len = 0;
+        for c in line.chars() {
+            if len + c.len_utf8() > MAX_LINE_LEN {
+                // the leading space of the continuation counts towards its
+                // length
+                self.out.push_str("\r\n ");
+                len = 1;
+            }
+            self.out.push(c);
+            len += c.len_utf8();
+        }
+        self.out.push_str("\r\n");
+    }
+
+    fn text(&mut self, name: &str, value: &str) {
+        self.line(name, &escape_text(value));
+    }
+
+    fn date(&mut self, name: &str, date: OccDate) {
+        self.line(name, &format_date(date));
+    }
+
+    /// Write the properties shared by all components for an occurrence.
+    fn occ_props(&mut self, item: &StoredItem, occ: &StoredOcc, now: OccDate) {
+        self.line("UID", &format!("occ-{}@dunsumday", occ.id));
+        self.date("DTSTAMP", now);
+        self.text("SUMMARY", &item.item.name);
+        if let Some(desc) = &item.item.desc {
+            self.text("DESCRIPTION", desc);
+        }
+        if let Some(category) = &item.item.category {
+            self.text("CATEGORIES", category);
+        }
+    }
+
+    fn event(&mut self, item: &StoredItem, occ: &StoredOcc, now: OccDate) {
+        self.line("BEGIN", "VEVENT");
+        self.occ_props(item, occ, now);
+        self.date("DTSTART", occ.occ.start);
+        // without an end, an event is a point in time
+        if occ.occ.end > occ.occ.start {
+            self.date("DTEND", occ.occ.end);
+        }
+        self.line("END", "VEVENT");
+    }
+
+    fn task_event(&mut self, item: &StoredItem, occ: &StoredOcc, now: OccDate) {
+        self.line("BEGIN", "VEVENT");
+        self.occ_props(item, occ, now);
+        self.date("DTSTART", occ.occ.end);
+        self.line("END", "VEVENT");
+    }
+
+    fn todo(
+        &mut self,
+        item: &StoredItem,
+        occ: &StoredOcc,
+        progress: Option<&TaskProgress>,
+        now: OccDate,
+    ) {
+        self.line("BEGIN", "VTODO");
+        self.occ_props(item, occ, now);
+        // the due date must be later than the start
+        if occ.occ.end > occ.occ.start {
+            self.date("DTSTART", occ.occ.start);
+        }
+        self.date("DUE", occ.occ.end);
+        if let Some(progress) = progress {
+            let complete = progress.is_complete();
+            self.line("STATUS",
+                      if complete { "COMPLETED" } else { "NEEDS-ACTION" });
+            let percent = if complete {
+                100
+            } else {
+                (progress.fraction() * 100.0).clamp(0.0, 99.0) as u8
+            };
+            self.line("PERCENT-COMPLETE", &percent.to_string());
+        }
+        self.line("END", "VTODO");
+    }
+}
+
+/// Build an iCalendar object containing occurrences.
+///
+/// `occs` is a slice of `(item, occ)` pairs, and `progress` maps task
+/// occurrence IDs to their progress.  `name` is used as the calendar's display
+/// name.  `now` is used as the time the calendar was created.
+pub fn calendar(
+    name: &str,
+    occs: &[(&StoredItem, &StoredOcc)],
+    progress: &HashMap<String, TaskProgress>,
+    task_format: TaskFormat,
+    now: OccDate,
+) -> String {
+    let mut writer = Writer { out: String::new() };
+    writer.line("BEGIN", "VCALENDAR");
+    writer.line("VERSION", "2.0");
+    writer.text("PRODID", PRODID);
+    writer.line("CALSCALE", "GREGORIAN");
+    writer.line("METHOD", "PUBLISH");
+    writer.text("X-WR-CALNAME", name);
+
+    for (item, occ) in occs {
+        match (item.item.type_, task_format) {
+            (ItemType::Event, _) => writer.event(item, occ, now),
+            (_, TaskFormat::Event) => writer.task_event(item, occ, now),
+            (_, TaskFormat::Todo) => {
+                writer.todo(item, occ, progress.get(&occ.id), now)
+            }
+        }
+    }
+
+    writer.line("END", "VCALENDAR");
+    writer.out
+}
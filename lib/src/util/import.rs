@@ -0,0 +1,466 @@
+//! Reading items from other formats.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::str::FromStr;
+use chrono::{Datelike, Month, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use crate::db::{ConfigId, Db, DbResults, DbUpdate, StoredConfig, StoredItem,
+                UpdateId, WriteBatch};
+use crate::db::util as dbutil;
+use crate::types::{Config, DayFilter, EventSched, Item, ItemType, Occ,
+                   ProgressEntry, Sched};
+
+/// Result of an import.
+#[derive(Clone, Debug, Default)]
+pub struct Imported {
+    /// Items which could be imported, in the order they were found.
+    pub items: Vec<Item>,
+    /// Identifier of each item in `items` in the source format, if it has one,
+    /// such as an iCalendar `UID`.
+    pub external_ids: Vec<Option<String>>,
+    /// Description of each entry which couldn't be imported.
+    pub skipped: Vec<String>,
+}
+
+/// Item read from another format, with its history.
+#[derive(Clone, Debug)]
+pub struct ImportedItem {
+    /// Identifier of the entry the item was created from, in the source
+    /// format.
+    pub external_id: String,
+    pub item: Item,
+    /// Config for the item, if the source format has any equivalent.
+    pub config: Option<Config>,
+    /// Occurrences, with their progress entries.
+    pub occs: Vec<(Occ, Vec<ProgressEntry>)>,
+}
+
+/// Result of an import of items with their history.
+#[derive(Clone, Debug, Default)]
+pub struct ImportedHistory {
+    /// Items which could be imported, in the order they were found.
+    pub items: Vec<ImportedItem>,
+    /// Description of each entry which couldn't be imported.
+    pub skipped: Vec<String>,
+}
+
+/// Create items with their history, recording them as coming from `source`.
+///
+/// Items which were imported from the same source before, by external ID, are
+/// updated instead, and their occurrences and config are left alone.  Returns
+/// the items, in the same order as `items`.
+pub fn create_history(db: &mut impl Db, source: &str, items: &[ImportedItem])
+-> DbResults<StoredItem> {
+    let existing = db.find_external_ids(source)?;
+    let mut batch = WriteBatch::new();
+    let mut item_tokens = Vec::new();
+    for imported in items {
+        let item_token = batch.upsert_item(
+            source, &imported.external_id, &imported.item);
+        item_tokens.push(item_token);
+        if existing.contains_key(&imported.external_id) {
+            continue;
+        }
+        for (occ, entries) in &imported.occs {
+            let occ_token = batch.create_occ(UpdateId::Token(item_token), occ);
+            for entry in entries {
+                batch.create_progress_entry(UpdateId::Token(occ_token), entry);
+            }
+        }
+    }
+
+    let mut ids = batch.write(db)?;
+    let ids = item_tokens.iter()
+        .map(|token| {
+            ids.remove(token)
+                .ok_or("unknown error - ID not returned".to_owned())
+        })
+        .collect::<DbResults<_>>()?;
+
+    // config refers to items by ID, so can only be set once they exist
+    let configs = items.iter()
+        .zip(&ids)
+        .filter(|(imported, _)| !existing.contains_key(&imported.external_id))
+        .filter_map(|(imported, id)| {
+            imported.config.clone().map(|config| StoredConfig {
+                id: ConfigId::Item { id: id.clone() },
+                config,
+            })
+        })
+        .collect::<Vec<_>>();
+    if !configs.is_empty() {
+        let updates = configs.iter()
+            .map(DbUpdate::set_config)
+            .collect::<Vec<_>>();
+        db.write(&updates.iter().collect::<Vec<_>>())?;
+    }
+
+    ids.iter().map(|id| dbutil::get_item(db, id)).collect()
+}
+
+/// iCalendar property, from a content line.
+#[derive(Clone, Debug)]
+struct Property {
+    /// Upper-case.
+    name: String,
+    value: String,
+}
+
+/// Join folded lines (RFC 5545 section 3.1).
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_owned()),
+        }
+    }
+    lines
+}
+
+/// Parse a content line, ignoring parameters.
+fn parse_line(line: &str) -> Option<Property> {
+    // the value starts after the first colon which isn't in a quoted parameter
+    // value
+    let mut quoted = false;
+    let (colon, _) = line.char_indices().find(|(_, c)| {
+        if *c == '"' {
+            quoted = !quoted;
+        }
+        *c == ':' && !quoted
+    })?;
+    let name = line[..colon].split(';').next().unwrap_or_default();
+    Some(Property {
+        name: name.to_ascii_uppercase(),
+        value: line[colon + 1..].to_owned(),
+    })
+}
+
+/// Unescape a value of type `TEXT`.
+fn unescape_text(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n' | 'N') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => (),
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
+/// Split a value of type `TEXT` with multiple values, unescaping each one.
+fn split_text(value: &str) -> Vec<String> {
+    let mut values = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == ',' {
+            values.push(unescape_text(&value[start..i]));
+            start = i + 1;
+        }
+    }
+    values.push(unescape_text(&value[start..]));
+    values
+}
+
+/// Parse the value of a `DTSTART` property, which is a `DATE` or `DATE-TIME`.
+///
+/// Returns the day, and the time of day for values with a time.  Times are
+/// used as written, ignoring any timezone.
+fn parse_start(value: &str) -> Result<(NaiveDate, Option<NaiveTime>), String> {
+    let value = value.trim();
+    if let Ok(date) = NaiveDateTime::parse_from_str(
+        value.trim_end_matches('Z'), "%Y%m%dT%H%M%S")
+    {
+        Ok((date.date(), Some(date.time())))
+    } else {
+        NaiveDate::parse_from_str(value, "%Y%m%d")
+            .map(|date| (date, None))
+            .map_err(|_| format!("invalid start date: {value}"))
+    }
+}
+
+fn parse_weekday(code: &str) -> Result<Weekday, String> {
+    match code {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(format!("invalid day of the week: {code}")),
+    }
+}
+
+/// Parse an item of a `BYDAY` recurrence rule part, which is a day of the week
+/// with an optional ordinal.
+fn parse_byday(value: &str) -> Result<(Option<i8>, Weekday), String> {
+    let split = value.len().checked_sub(2)
+        .filter(|split| value.is_char_boundary(*split))
+        .ok_or_else(|| format!("invalid day of the week: {value}"))?;
+    let (ordinal, day) = value.split_at(split);
+    let ordinal = if ordinal.is_empty() {
+        None
+    } else {
+        Some(ordinal.parse()
+             .map_err(|_| format!("invalid week number: {ordinal}"))?)
+    };
+    Ok((ordinal, parse_weekday(day)?))
+}
+
+/// Parse a comma-separated recurrence rule part.
+fn parse_list<T: FromStr>(name: &str, value: &str) -> Result<Vec<T>, String> {
+    value.split(',')
+        .map(|item| {
+            item.parse().map_err(|_| format!("invalid {name} value: {item}"))
+        })
+        .collect()
+}
+
+/// Convert a day of the month to the type used by schedules, if it's valid.
+fn dom(value: i8) -> Result<u8, String> {
+    u8::try_from(value).ok()
+        .filter(|dom| (1..=31).contains(dom))
+        .ok_or_else(|| format!("unsupported day of the month: {value}"))
+}
+
+/// Convert the value of an `RRULE` property to a [`DayFilter`], for an event
+/// starting on `start`.
+///
+/// The end of the recurrence (`COUNT` or `UNTIL`) is ignored, since schedules
+/// don't end.
+fn rrule_filter(rrule: &str, start: NaiveDate) -> Result<DayFilter, String> {
+    let mut parts = HashMap::new();
+    for part in rrule.split(';').filter(|part| !part.is_empty()) {
+        let (name, value) = part.split_once('=')
+            .ok_or_else(|| format!("invalid recurrence rule part: {part}"))?;
+        parts.insert(name.to_ascii_uppercase(), value.to_owned());
+    }
+
+    let freq = parts.remove("FREQ")
+        .ok_or("recurrence rule has no frequency".to_owned())?;
+    let interval: u32 = parts.remove("INTERVAL")
+        .map(|value| {
+            value.parse().map_err(|_| format!("invalid interval: {value}"))
+        })
+        .transpose()?
+        .unwrap_or(1);
+    if interval == 0 {
+        return Err("invalid interval: 0".to_owned());
+    }
+    let by_day = parts.remove("BYDAY")
+        .map(|value| {
+            value.split(',').map(parse_byday).collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?;
+    let by_month_day = parts.remove("BYMONTHDAY")
+        .map(|value| parse_list::<i8>("BYMONTHDAY", &value))
+        .transpose()?;
+    let by_month = parts.remove("BYMONTH")
+        .map(|value| parse_list::<u8>("BYMONTH", &value))
+        .transpose()?;
+    for ignored in ["COUNT", "UNTIL", "WKST"] {
+        parts.remove(ignored);
+    }
+    if let Some(part) = parts.keys().next() {
+        return Err(format!("unsupported recurrence rule part: {part}"));
+    }
+
+    let unsupported = || format!("unsupported recurrence rule: {rrule}");
+    match (freq.as_str(), by_day, by_month_day, by_month) {
+        ("DAILY", None, None, None) => {
+            Ok(DayFilter::Day { days_apart: interval })
+        }
+
+        ("WEEKLY", days, None, None) => {
+            let days = days.unwrap_or_else(|| vec![(None, start.weekday())]);
+            if days.iter().any(|(ordinal, _)| ordinal.is_some()) {
+                Err(unsupported())
+            } else if let [(_, day)] = days[..] {
+                Ok(DayFilter::Dow { day, weeks_apart: interval })
+            } else if interval == 1 {
+                Ok(DayFilter::Dows {
+                    days: days.into_iter().map(|(_, day)| day).collect(),
+                })
+            } else {
+                Err(unsupported())
+            }
+        }
+
+        ("MONTHLY", None, days, None) => {
+            let days = match days {
+                Some(days) => {
+                    days.into_iter().map(dom).collect::<Result<_, _>>()?
+                }
+                None => vec![start.day() as u8],
+            };
+            Ok(DayFilter::Dom { days, months_apart: interval })
+        }
+
+        ("MONTHLY", Some(days), None, None) => {
+            let dow = days[0].1;
+            let weeks = days.iter()
+                .map(|(ordinal, day)| {
+                    ordinal.filter(|week| *day == dow && (1..=5).contains(week))
+                        .map(|week| week as u8)
+                        .ok_or_else(unsupported)
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(DayFilter::Wom { dow, weeks, months_apart: interval })
+        }
+
+        ("YEARLY", None, days, months) => {
+            let dom = match days.as_deref() {
+                None => start.day() as u8,
+                Some([day]) => dom(*day)?,
+                Some(_) => return Err(unsupported()),
+            };
+            let month = match months.as_deref() {
+                None => start.month() as u8,
+                Some([month]) => *month,
+                Some(_) => return Err(unsupported()),
+            };
+            let month = Month::try_from(month)
+                .map_err(|_| format!("invalid month: {month}"))?;
+            Ok(DayFilter::Doy { dom, month, years_apart: interval })
+        }
+
+        _ => Err(unsupported()),
+    }
+}
+
+/// Get the value of the first property called `name`.
+fn prop<'p>(props: &'p [Property], name: &str) -> Option<&'p str> {
+    props.iter()
+        .find(|prop| prop.name == name)
+        .map(|prop| prop.value.as_str())
+}
+
+/// Describe an event for messages, given its properties.
+fn prop_name(props: &[Property]) -> String {
+    match prop(props, "SUMMARY") {
+        Some(summary) => format!("event {:?}", unescape_text(summary)),
+        None => "untitled event".to_owned(),
+    }
+}
+
+/// Get an event's unique identifier, given its properties.
+fn event_uid(props: &[Property]) -> Option<String> {
+    prop(props, "UID").map(unescape_text)
+}
+
+/// Convert a `VEVENT` component to an item, given its properties.
+///
+/// Returns `None` for components which shouldn't be imported.
+fn event_item(props: &[Property]) -> Result<Option<Item>, String> {
+    // changes to single occurrences of another event
+    if prop(props, "RECURRENCE-ID").is_some() {
+        return Ok(None);
+    }
+    if prop(props, "STATUS") == Some("CANCELLED") {
+        return Err("event is cancelled".to_owned());
+    }
+
+    let (initial_day, time) = parse_start(
+        prop(props, "DTSTART").ok_or("event has no start".to_owned())?)?;
+    let days = match prop(props, "RRULE") {
+        Some(rrule) => rrule_filter(rrule, initial_day)?,
+        None => DayFilter::Date {
+            dom: initial_day.day() as u8,
+            month: Month::try_from(initial_day.month() as u8)
+                .map_err(|_| format!("invalid start date: {initial_day}"))?,
+            year: initial_day.year(),
+        },
+    };
+
+    Ok(Some(Item {
+        type_: ItemType::Event,
+        active: true,
+        category: prop(props, "CATEGORIES")
+            .and_then(|value| split_text(value).into_iter().next())
+            .filter(|category| !category.is_empty()),
+        name: prop(props, "SUMMARY")
+            .map(unescape_text)
+            .unwrap_or_else(|| "Untitled event".to_owned()),
+        desc: prop(props, "DESCRIPTION").map(unescape_text),
+        sched: Sched::Event(EventSched { initial_day, days, time }),
+    }))
+}
+
+/// Read events from an [iCalendar](https://www.rfc-editor.org/rfc/rfc5545)
+/// object, converting them to items.
+///
+/// Recurrence rules are converted to schedules where possible; events with
+/// rules which can't be represented are skipped.  Events without a
+/// recurrence rule occur once, on their start date.  Exceptions to recurrence
+/// rules, and the ends of recurrences, are ignored.  Start times are used as
+/// written, ignoring any timezone.
+pub fn ics(mut reader: impl Read) -> Result<Imported, String> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)
+        .map_err(|e| format!("error reading calendar: {e}"))?;
+    let mut imported = Imported::default();
+    let mut found_calendar = false;
+    // names of the components the current line is in
+    let mut components: Vec<String> = vec![];
+    let mut event_props = vec![];
+
+    for line in unfold(&text).iter().filter(|line| !line.is_empty()) {
+        let prop = parse_line(line).ok_or_else(|| {
+            if found_calendar {
+                format!("invalid content line: {line}")
+            } else {
+                "not an iCalendar object".to_owned()
+            }
+        })?;
+        match prop.name.as_str() {
+            "BEGIN" => {
+                let component = prop.value.to_ascii_uppercase();
+                found_calendar |= component == "VCALENDAR";
+                if component == "VEVENT" {
+                    event_props.clear();
+                }
+                components.push(component);
+            }
+            "END" => {
+                let component = components.pop()
+                    .ok_or_else(|| format!("unexpected end of component: \
+                                            {}", prop.value))?;
+                if component == "VEVENT" {
+                    let name = prop_name(&event_props);
+                    match event_item(&event_props) {
+                        Ok(Some(item)) => {
+                            imported.items.push(item);
+                            imported.external_ids.push(event_uid(&event_props));
+                        }
+                        Ok(None) => (),
+                        Err(e) => imported.skipped.push(format!("{name}: {e}")),
+                    }
+                }
+            }
+            _ => {
+                if components.last().map(String::as_str) == Some("VEVENT") {
+                    event_props.push(prop);
+                }
+            }
+        }
+    }
+
+    if found_calendar {
+        Ok(imported)
+    } else {
+        Err("not an iCalendar object".to_owned())
+    }
+}
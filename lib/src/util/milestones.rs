@@ -0,0 +1,272 @@
+//! Milestones reached by completing tasks: a number of occurrences completed
+//! in total or in a row, or a total amount of progress logged over a year.
+//!
+//! Reached milestones are recorded in the database, so that each is only found
+//! once, however often items are checked.  They can also be
+//! [sent](super::notify::Notifier::send_milestone) through notification
+//! channels.
+
+use std::collections::{HashMap, HashSet};
+use chrono::{Datelike, NaiveDate, NaiveTime};
+use serde::Serialize;
+use crate::config::{self, Config};
+use crate::configrefs;
+use crate::db::{Db, DbResult, DbResults, SortDirection, StoredItem,
+                StoredMilestone, UpdateId, WriteBatch};
+use crate::types::{Config as ItemConfig, ItemType, Milestone, MilestoneKind,
+                   OccDate};
+use super::config::{self as itemconfig, ItemFilter};
+use super::notify::Notifier;
+use super::stats;
+
+/// Milestones used for items whose config doesn't set them.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Defaults {
+    /// See [`MilestoneConfig::completions`](crate::types::MilestoneConfig).
+    pub completions: Vec<u32>,
+    /// See [`MilestoneConfig::streaks`](crate::types::MilestoneConfig).
+    pub streaks: Vec<u32>,
+}
+
+impl Defaults {
+    /// Get the defaults configured in `cfg`.
+    pub fn new(cfg: &impl Config) -> Result<Defaults, String> {
+        Ok(Defaults {
+            completions: config::get_parsed(
+                cfg, &configrefs::MILESTONES_COMPLETIONS)?,
+            streaks: config::get_parsed(cfg, &configrefs::MILESTONES_STREAKS)?,
+        })
+    }
+}
+
+/// Milestone reached by an item which hasn't been recorded yet.
+#[derive(Clone, Debug, Serialize)]
+pub struct PendingMilestone {
+    pub item: StoredItem,
+    pub milestone: Milestone,
+    /// Resolved config for the item.
+    pub config: ItemConfig,
+}
+
+impl PendingMilestone {
+    /// Short summary of the milestone, suitable for a notification subject.
+    pub fn subject(&self) -> String {
+        format!("Milestone reached: {}", self.item.item.name)
+    }
+
+    /// Description of the milestone.
+    pub fn body(&self) -> String {
+        let name = &self.item.item.name;
+        let threshold = self.milestone.threshold;
+        match self.milestone.kind {
+            MilestoneKind::Completions => {
+                format!("{name} has been completed {}", times(threshold))
+            }
+            MilestoneKind::Streak => {
+                format!("{name} has been completed {} in a row",
+                        times(threshold))
+            }
+            MilestoneKind::YearlyTotal => {
+                let unit = self.config.task_completion_conf.unit.as_ref()
+                    .map(|unit| format!(" {unit}"))
+                    .unwrap_or_default();
+                let year = self.milestone.year
+                    .map(|year| format!(" in {year}"))
+                    .unwrap_or_default();
+                format!("{name} reached a total of {threshold}{unit}{year}")
+            }
+        }
+    }
+}
+
+/// Phrase a number of completions.
+fn times(n: u32) -> String {
+    if n == 1 { "once".to_owned() } else { format!("{n} times") }
+}
+
+/// Key identifying a milestone, ignoring when it was reached.
+type MilestoneKey = (String, MilestoneKind, u32, Option<i32>);
+
+/// Sum progress logged against each item's occurrences from `start` up to
+/// `end`, by item ID.
+fn progress_totals(db: &impl Db, item_ids: &[&str], start: OccDate,
+                   end: OccDate)
+-> DbResult<HashMap<String, i64>> {
+    if item_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let occs = db.find_occs(
+        item_ids, Some(start), Some(end), SortDirection::Asc, None, u32::MAX)?;
+    let occ_ids = occs.values()
+        .flatten()
+        .map(|occ| occ.id.as_str())
+        .collect::<Vec<_>>();
+    let mut entries = db.find_progress_entries(
+        &occ_ids, SortDirection::Asc, u32::MAX)?;
+    Ok(occs.iter()
+        .map(|(item_id, occs)| {
+            let total = occs.iter()
+                .flat_map(|occ| entries.remove(&occ.id).unwrap_or_default())
+                .filter(|entry| {
+                    start <= entry.entry.date && entry.entry.date <= end
+                })
+                .map(|entry| entry.entry.amount)
+                .sum();
+            (item_id.clone(), total)
+        })
+        .collect())
+}
+
+/// Find milestones reached by the current user's active tasks at `now` which
+/// haven't been recorded yet.
+///
+/// Completions and streaks are counted over all of an item's occurrences (see
+/// [completion statistics](stats::get_stats)).  Yearly totals sum the progress
+/// logged since the start of the year containing `now`, in UTC.  Items whose
+/// [config](crate::types::MilestoneConfig::enabled) disables milestones are
+/// skipped.
+pub fn find(db: &impl Db, defaults: &Defaults, now: OccDate)
+-> DbResults<PendingMilestone> {
+    let filter = ItemFilter {
+        active: Some(true),
+        ..Default::default()
+    };
+    let items = itemconfig::get_items_with_resolved(db, &filter)?
+        .into_iter()
+        .filter(|(item, _)| item.item.type_ != ItemType::Event)
+        .map(|(item, config)| {
+            (item, config.map(|c| c.resolved_config).unwrap_or_default())
+        })
+        .filter(|(_, config)| config.milestone_conf.enabled.unwrap_or(true))
+        .collect::<Vec<_>>();
+    if items.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let stats = stats::get_stats(db, None, Some(now), now)?;
+    let year = now.year();
+    let year_start = NaiveDate::from_ymd_opt(year, 1, 1)
+        .ok_or_else(|| format!("invalid year: {year}"))?
+        .and_time(NaiveTime::MIN)
+        .and_utc();
+    let yearly_ids = items.iter()
+        .filter(|(_, config)| config.milestone_conf.yearly_total.is_some())
+        .map(|(item, _)| item.id.as_str())
+        .collect::<Vec<_>>();
+    let totals = progress_totals(db, &yearly_ids, year_start, now)?;
+    let recorded = db.find_milestones(None)?
+        .into_iter()
+        .map(|m| {
+            (m.item_id, m.milestone.kind, m.milestone.threshold,
+             m.milestone.year)
+        })
+        .collect::<HashSet<MilestoneKey>>();
+
+    let mut pending = Vec::new();
+    for (item, config) in items {
+        let conf = &config.milestone_conf;
+        let item_stats = stats.items.get(&item.id).copied().unwrap_or_default();
+        let mut reached = Vec::new();
+        for &threshold in conf.completions.as_ref()
+            .unwrap_or(&defaults.completions)
+        {
+            if item_stats.completed >= threshold {
+                reached.push((MilestoneKind::Completions, threshold, None));
+            }
+        }
+        for &threshold in conf.streaks.as_ref().unwrap_or(&defaults.streaks) {
+            if item_stats.longest_streak >= threshold {
+                reached.push((MilestoneKind::Streak, threshold, None));
+            }
+        }
+        if let Some(threshold) = conf.yearly_total {
+            let total = totals.get(&item.id).copied().unwrap_or(0);
+            if total >= i64::from(threshold) {
+                reached.push(
+                    (MilestoneKind::YearlyTotal, threshold, Some(year)));
+            }
+        }
+
+        for (kind, threshold, year) in reached {
+            let key = (item.id.clone(), kind, threshold, year);
+            if recorded.contains(&key) {
+                continue;
+            }
+            pending.push(PendingMilestone {
+                item: item.clone(),
+                milestone: Milestone { kind, threshold, year, reached: now },
+                config: config.clone(),
+            });
+        }
+    }
+    Ok(pending)
+}
+
+/// Record `milestones` as reached.  Returns the stored milestones.
+pub fn record(db: &mut impl Db, milestones: &[PendingMilestone])
+-> DbResults<StoredMilestone> {
+    let mut batch = WriteBatch::new();
+    let tokens = milestones.iter()
+        .map(|pending| {
+            batch.create_milestone(
+                UpdateId::Id(&pending.item.id), &pending.milestone)
+        })
+        .collect::<Vec<_>>();
+    let ids = batch.write(db)?;
+    tokens.into_iter()
+        .zip(milestones)
+        .map(|(token, pending)| {
+            let id = ids.get(&token)
+                .ok_or("milestone ID missing from write result")?;
+            Ok(StoredMilestone {
+                id: id.clone(),
+                item_id: pending.item.id.clone(),
+                milestone: pending.milestone.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Find milestones reached by the current user's tasks at `now`, record them,
+/// and send them through `notifier`'s channels.
+///
+/// When an item reaches several milestones of the same kind at once, only the
+/// largest is sent, though all are recorded.  If there are any channels,
+/// milestones are left unrecorded during the item's
+/// [quiet hours](crate::types::NotificationConfig::quiet_hours), so they're
+/// found again after they end.  Returns the milestones which were recorded.
+pub fn check(
+    db: &mut impl Db,
+    defaults: &Defaults,
+    notifier: &Notifier,
+    now: OccDate,
+) -> DbResults<PendingMilestone> {
+    let mut pending = find(db, defaults, now)?;
+    if notifier.has_channels() {
+        pending.retain(|pending| !notifier.is_quiet(&pending.config, now));
+    }
+    if pending.is_empty() {
+        return Ok(pending);
+    }
+    record(db, &pending)?;
+
+    // index of the largest milestone of each kind for each item
+    let mut largest = HashMap::<(&str, MilestoneKind), usize>::new();
+    for (i, milestone) in pending.iter().enumerate() {
+        let key = (milestone.item.id.as_str(), milestone.milestone.kind);
+        let is_larger = largest.get(&key).is_none_or(|&current| {
+            pending[current].milestone.threshold
+                < milestone.milestone.threshold
+        });
+        if is_larger {
+            largest.insert(key, i);
+        }
+    }
+    for (i, milestone) in pending.iter().enumerate() {
+        let key = (milestone.item.id.as_str(), milestone.milestone.kind);
+        if largest.get(&key) == Some(&i) {
+            notifier.send_milestone(milestone);
+        }
+    }
+    Ok(pending)
+}
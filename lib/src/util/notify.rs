@@ -0,0 +1,594 @@
+//! Sending alerts about occurrences which need attention through notification
+//! channels.
+//!
+//! Sent alerts are recorded in the database, so that each alert is only sent
+//! once (or once per configured repeat interval), however often occurrences
+//! are checked.  Alert messages are phrased using configurable
+//! [templates](template).  Alternatively, a single daily [digest] can
+//! be sent instead of individual alerts.  Channels also deliver
+//! [milestones](super::milestones) as they're reached.
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use chrono::{FixedOffset, NaiveTime, TimeDelta, Weekday};
+use serde::Serialize;
+use crate::config::{self, Config};
+use crate::configrefs;
+use crate::db::{Db, DbResult, DbResults, SortDirection, StoredItem, StoredOcc,
+                UpdateId, WriteBatch};
+use crate::types::{Alert, AlertKind, Config as ItemConfig, ItemType,
+                   OccDate};
+use super::{config as itemconfig, get_current_items, in_alert_period,
+            progress};
+use super::milestones::PendingMilestone;
+use super::progress::TaskProgress;
+use super::report;
+use super::stats::{self, CompletionStats};
+use super::status::{self, OccStatus};
+use digest::{Digest, DigestOcc, DigestSchedule};
+use template::Templates;
+
+pub mod digest;
+#[cfg(feature = "email")]
+pub mod email;
+#[cfg(feature = "http")]
+pub mod push;
+pub mod template;
+
+/// Types of notification channel which can be configured (see
+/// [`NOTIFICATIONS_CHANNELS`](configrefs::NOTIFICATIONS_CHANNELS)).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, strum::AsRefStr,
+         strum::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum ChannelKind {
+    /// Write alerts to the log.
+    Log,
+    /// Send alerts by email (see [`email::EmailChannel`]).
+    Email,
+    /// Publish alerts to an ntfy topic (see [`push::NtfyChannel`]).
+    Ntfy,
+    /// Send alerts to a Gotify server (see [`push::GotifyChannel`]).
+    Gotify,
+    /// Send alerts as JSON to a URL (see [`push::WebhookChannel`]).
+    Webhook,
+}
+
+/// Alert about an occurrence which hasn't been sent yet.
+#[derive(Clone, Debug, Serialize)]
+pub struct PendingAlert {
+    pub kind: AlertKind,
+    pub item: StoredItem,
+    pub occ: StoredOcc,
+    /// Missing for events.
+    pub progress: Option<TaskProgress>,
+    /// Resolved config for the occurrence.
+    pub config: ItemConfig,
+}
+
+impl PendingAlert {
+    /// Whether this is an escalated alert whose escalation rule adds the
+    /// channel called `name`.
+    fn escalated_through(&self, name: &str) -> bool {
+        self.kind == AlertKind::Escalated
+            && self.config.notification_conf.escalation.as_ref()
+                .is_some_and(|escalation| {
+                    escalation.channels.iter().any(|c| c == name)
+                })
+    }
+}
+
+/// Destination for alerts.
+pub trait Channel {
+    /// Name used to refer to the channel in logs.
+    fn name(&self) -> &str;
+
+    /// Whether alerts should be sent through this channel for occurrences
+    /// with the given resolved config.  By default, this checks the config's
+    /// [channels](crate::types::NotificationConfig::channels).
+    fn enabled(&self, config: &ItemConfig) -> bool {
+        config.notification_conf.channel_enabled(self.name())
+    }
+
+    /// Deliver an alert.
+    fn send(&self, alert: &PendingAlert) -> Result<(), String>;
+
+    /// Deliver a digest.  This doesn't depend on any occurrence's config.
+    fn send_digest(&self, digest: &Digest) -> Result<(), String>;
+
+    /// Deliver a milestone reached by an item.
+    fn send_milestone(&self, milestone: &PendingMilestone)
+    -> Result<(), String>;
+}
+
+/// Channel which writes alerts to the log.
+#[derive(Clone, Debug, Default)]
+pub struct LogChannel {
+    templates: Templates,
+}
+
+impl LogChannel {
+    /// Create a channel using the templates configured in `cfg`.
+    pub fn new(cfg: &impl Config) -> Result<LogChannel, String> {
+        Ok(LogChannel {
+            templates: Templates::for_channel(cfg, ChannelKind::Log)?,
+        })
+    }
+}
+
+impl Channel for LogChannel {
+    fn name(&self) -> &str {
+        ChannelKind::Log.as_ref()
+    }
+
+    fn send(&self, alert: &PendingAlert) -> Result<(), String> {
+        tracing::info!("{} ({})", self.templates.subject(alert)?,
+                       self.templates.body(alert)?);
+        Ok(())
+    }
+
+    fn send_digest(&self, digest: &Digest) -> Result<(), String> {
+        tracing::info!("{}\n{}", digest.subject(), digest.body());
+        Ok(())
+    }
+
+    fn send_milestone(&self, milestone: &PendingMilestone)
+    -> Result<(), String> {
+        tracing::info!("{} ({})", milestone.subject(), milestone.body());
+        Ok(())
+    }
+}
+
+/// Create a channel of the given type.
+fn open_channel(cfg: &impl Config, kind: ChannelKind)
+-> Result<Box<dyn Channel + Send>, String> {
+    match kind {
+        ChannelKind::Log => Ok(Box::new(LogChannel::new(cfg)?)),
+        #[cfg(feature = "email")]
+        ChannelKind::Email => Ok(Box::new(email::EmailChannel::new(cfg)?)),
+        #[cfg(feature = "http")]
+        ChannelKind::Ntfy => Ok(Box::new(push::NtfyChannel::new(cfg)?)),
+        #[cfg(feature = "http")]
+        ChannelKind::Gotify => Ok(Box::new(push::GotifyChannel::new(cfg)?)),
+        #[cfg(feature = "http")]
+        ChannelKind::Webhook => Ok(Box::new(push::WebhookChannel::new(cfg)?)),
+        #[allow(unreachable_patterns)]
+        kind => Err(format!("notification channel not supported by this \
+                             build: {}", kind.as_ref())),
+    }
+}
+
+/// Whether an alert which was already sent stops the same alert from being sent
+/// again at `date`, given the config's repeat interval.
+fn blocks_resend(
+    alert: &Alert,
+    repeat: Option<chrono::TimeDelta>,
+    date: OccDate,
+) -> bool {
+    if alert.acked.is_some() {
+        true
+    } else if let Some(snoozed_until) = alert.snoozed_until {
+        snoozed_until > date
+    } else {
+        repeat.is_none_or(|repeat| alert.sent + repeat > date)
+    }
+}
+
+/// Whether an overdue occurrence should be escalated at `date`, according to
+/// its resolved config.  Only deadline task occurrences are escalated.
+fn is_escalated(
+    item: &StoredItem,
+    occ: &StoredOcc,
+    config: &ItemConfig,
+    date: OccDate,
+) -> bool {
+    item.item.type_ == ItemType::DeadlineTask
+        && config.notification_conf.escalation.as_ref()
+            .is_some_and(|escalation| {
+                chrono::TimeDelta::from_std(escalation.after).ok()
+                    .and_then(|after| occ.occ.end.checked_add_signed(after))
+                    .is_some_and(|escalate| escalate <= date)
+            })
+}
+
+/// Occurrence considered for alerts, with its current state.
+struct OccState {
+    item: StoredItem,
+    occ: StoredOcc,
+    /// Missing for events.
+    progress: Option<TaskProgress>,
+    /// Resolved config for the occurrence.
+    config: ItemConfig,
+    /// Alert which applies to the occurrence, if any.
+    kind: Option<AlertKind>,
+}
+
+/// Finds occurrences which need attention, and sends alerts about them.
+pub struct Notifier {
+    channels: Vec<Box<dyn Channel + Send>>,
+    default_alert: Duration,
+    lookback: Duration,
+    /// Whether to send digests instead of alerts.
+    digest_enabled: bool,
+    digest_schedule: DigestSchedule,
+    utc_offset: FixedOffset,
+    /// Whether to include a weekly review in the first digest each week.
+    digest_weekly_review: bool,
+}
+
+impl Notifier {
+    /// Create a notifier sending alerts through the channels configured in
+    /// `cfg`.  Times of day in item configs and settings are local times,
+    /// offset from UTC by `utc_offset` (see
+    /// [`Clock::utc_offset`](super::clock::Clock::utc_offset)).
+    pub fn new(cfg: &impl Config, utc_offset: FixedOffset)
+    -> Result<Notifier, String> {
+        let channels = config::get_parsed(
+            cfg, &configrefs::NOTIFICATIONS_CHANNELS)?
+            .into_iter()
+            .map(|kind| open_channel(cfg, kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Notifier {
+            channels,
+            default_alert: config::get_parsed(
+                cfg, &configrefs::OCC_ALERT_DEFAULT)?,
+            lookback: config::get_parsed(
+                cfg, &configrefs::NOTIFICATIONS_LOOKBACK)?,
+            digest_enabled: config::get_parsed(
+                cfg, &configrefs::NOTIFICATIONS_DIGEST_ENABLED)?,
+            digest_schedule: DigestSchedule {
+                time: config::get_parsed(
+                    cfg, &configrefs::NOTIFICATIONS_DIGEST_TIME)?,
+                stats_period: TimeDelta::from_std(config::get_parsed(
+                    cfg, &configrefs::NOTIFICATIONS_DIGEST_STATS_PERIOD)?)
+                    .map_err(|e| format!("digest stats period too long: {e}"))?,
+                utc_offset,
+            },
+            utc_offset,
+            digest_weekly_review: config::get_parsed(
+                cfg, &configrefs::NOTIFICATIONS_DIGEST_WEEKLY_REVIEW)?,
+        })
+    }
+
+    /// Also send alerts through `channel`.
+    pub fn add_channel(&mut self, channel: Box<dyn Channel + Send>) {
+        self.channels.push(channel);
+    }
+
+    /// Whether `date` is in `config`'s
+    /// [quiet hours](crate::types::NotificationConfig::quiet_hours), which are
+    /// in local time.
+    pub fn is_quiet(&self, config: &ItemConfig, date: OccDate) -> bool {
+        let time = date.with_timezone(&self.utc_offset).time();
+        config.notification_conf.quiet_hours
+            .is_some_and(|quiet| quiet.contains(time))
+    }
+
+    /// Whether there are any channels to send alerts through.
+    pub fn has_channels(&self) -> bool {
+        !self.channels.is_empty()
+    }
+
+    /// Get the current user's occurrences which may need attention at `date`,
+    /// along with the alert which applies to each, if any.
+    ///
+    /// This is current occurrences, and occurrences which ended no longer than
+    /// the configured [lookback](configrefs::NOTIFICATIONS_LOOKBACK) ago.
+    /// Occurrences whose config disables notifications are skipped.
+    ///
+    /// This may generate current occurrences.
+    fn occ_states(&self, db: &mut impl Db, date: OccDate)
+    -> DbResults<OccState> {
+        let mut occs = get_current_items(db, date)?;
+        let since = chrono::TimeDelta::from_std(self.lookback).ok()
+            .and_then(|lookback| date.checked_sub_signed(lookback));
+        let filter = itemconfig::ItemFilter {
+            active: Some(true),
+            start: since,
+            ..Default::default()
+        };
+        // occurrence configs are resolved below, since they may override
+        // the items' configs
+        let items = itemconfig::get_items_with_resolved(db, &filter)?
+            .into_iter()
+            .map(|(item, _)| item)
+            .collect::<Vec<_>>();
+        let item_ids = items.iter()
+            .map(|item| item.id.as_str())
+            .collect::<Vec<_>>();
+        let mut ended = db.find_occs(
+            &item_ids, since, Some(date), SortDirection::Asc, None, u32::MAX)?;
+        let current_ids = occs.iter()
+            .map(|(item, occ)| occ.id.clone())
+            .collect::<HashSet<_>>();
+        for item in items {
+            for occ in ended.remove(&item.id).unwrap_or_default() {
+                if occ.occ.end <= date && !current_ids.contains(&occ.id) {
+                    occs.push((item.clone(), occ));
+                }
+            }
+        }
+        occs.retain(|(item, occ)| occ.occ.active);
+
+        let occ_refs = occs.iter()
+            .map(|(item, occ)| (item, occ))
+            .collect::<Vec<_>>();
+        let mut progress = progress::resolve_item_occs_progress(db, &occ_refs)?;
+        let configs = itemconfig::get_occs_configs(db, &occ_refs)?
+            .into_iter()
+            .map(|(occ, config)| (occ.id.clone(), config))
+            .collect::<HashMap<_, _>>();
+
+        Ok(occs.into_iter()
+            .filter_map(|(item, occ)| {
+                let occ_progress = progress.remove(&occ.id);
+                let config = configs.get(&occ.id)
+                    .map(|config| config.resolved_config.clone())
+                    .unwrap_or_default();
+                // the alert period is checked separately, using the config
+                let status = status::occ_status(
+                    &occ.occ, occ_progress.as_ref(), Duration::ZERO, date);
+                let kind = match status {
+                    OccStatus::Overdue => Some(
+                        if is_escalated(&item, &occ, &config, date) {
+                            AlertKind::Escalated
+                        } else {
+                            AlertKind::Overdue
+                        }),
+                    OccStatus::Complete => None,
+                    OccStatus::Idle | OccStatus::Alert => {
+                        let alerting = match configs.get(&occ.id) {
+                            Some(config) => in_alert_period(
+                                &occ.occ, config, self.default_alert, date),
+                            None => status::occ_status(
+                                &occ.occ, None, self.default_alert, date)
+                                == OccStatus::Alert,
+                        };
+                        alerting.then_some(AlertKind::Upcoming)
+                    }
+                };
+                config.notification_conf.enabled.unwrap_or(true)
+                    .then_some(OccState {
+                        item,
+                        occ,
+                        progress: occ_progress,
+                        config,
+                        kind,
+                    })
+            })
+            .collect())
+    }
+
+    /// Find alerts about the current user's occurrences which are due at
+    /// `date` and haven't been sent yet.
+    ///
+    /// Upcoming alerts are for current occurrences in their alert period (see
+    /// [`in_alert_period`]).  Overdue alerts are for task occurrences which
+    /// missed their target, and which are current or ended no longer than the
+    /// configured [lookback](configrefs::NOTIFICATIONS_LOOKBACK) ago.  Overdue
+    /// deadline task occurrences get an escalated alert instead, once their
+    /// config's [escalation](crate::types::NotificationConfig::escalation)
+    /// period has passed.
+    ///
+    /// Occurrences whose config disables notifications are skipped.  Alerts
+    /// which were already sent are found again once the config's
+    /// [repeat](crate::types::NotificationConfig::repeat) interval has passed,
+    /// or once they're no longer [snoozed](Alert::snoozed_until), unless
+    /// they've been [acknowledged](Alert::acked).
+    ///
+    /// This may generate current occurrences.
+    pub fn find(&self, db: &mut impl Db, date: OccDate)
+    -> DbResults<PendingAlert> {
+        let states = self.occ_states(db, date)?;
+        let occ_ids = states.iter()
+            .filter(|state| state.kind.is_some())
+            .map(|state| state.occ.id.as_str())
+            .collect::<Vec<_>>();
+        let sent = db.find_alerts(&occ_ids)?;
+
+        Ok(states.into_iter()
+            .filter_map(|state| {
+                let kind = state.kind?;
+                let repeat = state.config.notification_conf.repeat
+                    .and_then(|repeat| {
+                        chrono::TimeDelta::from_std(repeat).ok()
+                    });
+                let is_sent = sent.get(&state.occ.id)
+                    .is_some_and(|alerts| {
+                        alerts.iter().any(|alert| {
+                            alert.alert.kind == kind
+                                && blocks_resend(&alert.alert, repeat, date)
+                        })
+                    });
+                (!is_sent).then_some(PendingAlert {
+                    kind,
+                    item: state.item,
+                    occ: state.occ,
+                    progress: state.progress,
+                    config: state.config,
+                })
+            })
+            .collect())
+    }
+
+    /// Send `alert` through every channel enabled for it, and record it as sent
+    /// at `date` if any channel succeeded.  Escalated alerts are also sent
+    /// through their escalation rule's extra channels.
+    ///
+    /// Nothing is sent during the config's
+    /// [quiet hours](crate::types::NotificationConfig::quiet_hours), so the
+    /// alert is found again after they end.  Failures are logged.  Returns
+    /// whether the alert was sent.
+    pub fn send(&self, db: &mut impl Db, alert: &PendingAlert, date: OccDate)
+    -> DbResult<bool> {
+        if self.is_quiet(&alert.config, date) {
+            return Ok(false);
+        }
+        let mut sent = false;
+        for channel in &self.channels {
+            if !channel.enabled(&alert.config)
+                && !alert.escalated_through(channel.name())
+            {
+                continue;
+            }
+            match channel.send(alert) {
+                Ok(()) => { sent = true; }
+                Err(e) => tracing::warn!(
+                    "error sending alert through channel ({}): {e}",
+                    channel.name()),
+            }
+        }
+        if sent {
+            let record = Alert {
+                kind: alert.kind,
+                sent: date,
+                acked: None,
+                snoozed_until: None,
+            };
+            let mut batch = WriteBatch::new();
+            batch.create_alert(UpdateId::Id(&alert.occ.id), &record);
+            batch.write(db)?;
+        }
+        Ok(sent)
+    }
+
+    /// Summarise the current user's occurrences for the day containing
+    /// `date`.
+    ///
+    /// This includes the same occurrences as [`find`](Notifier::find),
+    /// regardless of whether alerts have been sent for them.  If
+    /// [enabled](configrefs::NOTIFICATIONS_DIGEST_WEEKLY_REVIEW), a review of
+    /// the previous week is included when no digest has been sent yet this
+    /// week.  This may generate current occurrences.
+    pub fn digest(&self, db: &mut impl Db, date: OccDate) -> DbResult<Digest> {
+        let day_start = date.date_naive().and_time(NaiveTime::MIN).and_utc();
+        let day_end = day_start + TimeDelta::days(1);
+        let in_day = |date: OccDate| day_start <= date && date < day_end;
+        let (overdue, others): (Vec<_>, Vec<_>) = self.occ_states(db, date)?
+            .into_iter()
+            .partition(|state| {
+                matches!(state.kind,
+                         Some(AlertKind::Overdue | AlertKind::Escalated))
+            });
+        let to_digest_occ = |state: OccState| DigestOcc {
+            item: state.item,
+            occ: state.occ,
+            progress: state.progress,
+        };
+        let mut today = others.into_iter()
+            .filter(|state| in_day(state.occ.occ.start)
+                    || in_day(state.occ.occ.end))
+            .map(to_digest_occ)
+            .collect::<Vec<_>>();
+        today.sort_by_key(|occ| occ.occ.occ.start);
+        let mut overdue = overdue.into_iter()
+            .map(to_digest_occ)
+            .collect::<Vec<_>>();
+        overdue.sort_by_key(|occ| occ.occ.occ.end);
+
+        let stats = stats::get_stats(
+            db, date.checked_sub_signed(self.digest_schedule.stats_period),
+            Some(date), date)?;
+        let mut total = CompletionStats::default();
+        for type_stats in stats.types.values() {
+            total.merge(type_stats);
+        }
+
+        let week_start = date.date_naive().week(Weekday::Mon).first_day();
+        let weekly_review = if self.digest_weekly_review &&
+            db.find_last_digest()?.is_none_or(|last| {
+                last.date_naive() < week_start
+            })
+        {
+            let last_week = week_start - TimeDelta::weeks(1);
+            Some(report::weekly_review(db, last_week, date)?)
+        } else {
+            None
+        };
+
+        Ok(Digest {
+            date,
+            today,
+            overdue,
+            stats: total,
+            categories: stats.categories.into_iter().collect(),
+            weekly_review,
+        })
+    }
+
+    /// Send `digest` through every channel, and record it as sent at `date` if
+    /// any channel succeeded.
+    ///
+    /// Failures are logged.  Returns whether the digest was sent.
+    pub fn send_digest(&self, db: &mut impl Db, digest: &Digest, date: OccDate)
+    -> DbResult<bool> {
+        let mut sent = false;
+        for channel in &self.channels {
+            match channel.send_digest(digest) {
+                Ok(()) => { sent = true; }
+                Err(e) => tracing::warn!(
+                    "error sending digest through channel ({}): {e}",
+                    channel.name()),
+            }
+        }
+        if sent {
+            let mut batch = WriteBatch::new();
+            batch.create_digest(date);
+            batch.write(db)?;
+        }
+        Ok(sent)
+    }
+
+    /// Send `milestone` through every channel enabled for the item, unless
+    /// its config disables notifications.
+    ///
+    /// Failures are logged.  Returns whether the milestone was sent.
+    pub fn send_milestone(&self, milestone: &PendingMilestone) -> bool {
+        if !milestone.config.notification_conf.enabled.unwrap_or(true) {
+            return false;
+        }
+        let mut sent = false;
+        for channel in &self.channels {
+            if !channel.enabled(&milestone.config) {
+                continue;
+            }
+            match channel.send_milestone(milestone) {
+                Ok(()) => { sent = true; }
+                Err(e) => tracing::warn!(
+                    "error sending milestone through channel ({}): {e}",
+                    channel.name()),
+            }
+        }
+        sent
+    }
+
+    /// Find alerts which are due at `date` for the current user, and send
+    /// them.
+    ///
+    /// If [digests](configrefs::NOTIFICATIONS_DIGEST_ENABLED) are enabled,
+    /// individual alerts aren't sent.  Instead, a digest is sent if one hasn't
+    /// been sent since the configured time of day was last reached.
+    ///
+    /// Returns the alerts which were sent.  Nothing is sent if there are no
+    /// channels.
+    pub fn check(&self, db: &mut impl Db, date: OccDate)
+    -> DbResults<PendingAlert> {
+        if !self.has_channels() {
+            return Ok(vec![]);
+        }
+        if self.digest_enabled {
+            let due = self.digest_schedule.latest(date);
+            if db.find_last_digest()?.is_none_or(|last| last < due) {
+                let digest = self.digest(db, date)?;
+                self.send_digest(db, &digest, date)?;
+            }
+            return Ok(vec![]);
+        }
+        let mut sent = Vec::new();
+        for alert in self.find(db, date)? {
+            if self.send(db, &alert, date)? {
+                sent.push(alert);
+            }
+        }
+        Ok(sent)
+    }
+}
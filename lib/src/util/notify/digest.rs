@@ -0,0 +1,189 @@
+//! Daily summaries of occurrences, sent instead of individual alerts when
+//! [enabled](crate::configrefs::NOTIFICATIONS_DIGEST_ENABLED).
+//!
+//! Digests aren't phrased using [templates](super::template).
+
+use std::collections::BTreeMap;
+use chrono::{FixedOffset, NaiveTime, TimeDelta};
+use serde::Serialize;
+use crate::db::{StoredItem, StoredOcc};
+use crate::types::OccDate;
+use super::super::progress::TaskProgress;
+use super::super::report::WeeklyReview;
+use super::super::stats::CompletionStats;
+
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Occurrence included in a digest.
+#[derive(Clone, Debug, Serialize)]
+pub struct DigestOcc {
+    pub item: StoredItem,
+    pub occ: StoredOcc,
+    /// Missing for events.
+    pub progress: Option<TaskProgress>,
+}
+
+impl DigestOcc {
+    /// Describe the occurrence in a single line.
+    fn describe(&self, overdue: bool) -> String {
+        let end = self.occ.occ.end.format(DATE_FORMAT);
+        let mut line = format!("- {}: ", self.item.item.name);
+        match &self.progress {
+            None => line.push_str(
+                &self.occ.occ.start.format(DATE_FORMAT).to_string()),
+            Some(progress) => {
+                if overdue {
+                    line.push_str(&format!("was due {end}"));
+                } else if progress.is_complete() {
+                    line.push_str("done");
+                } else {
+                    line.push_str(&format!("due {end}"));
+                }
+                line.push_str(&format!(", progress {}/{}",
+                                       progress.effective_progress(),
+                                       progress.total));
+            }
+        }
+        line
+    }
+}
+
+/// Describe completion statistics in a few words.
+fn describe_stats(stats: &CompletionStats) -> String {
+    let mut desc = format!("{} of {} completed", stats.completed,
+                           stats.completed + stats.missed);
+    if let Some(rate) = stats.completion_rate() {
+        desc.push_str(&format!(" ({:.0}%)", rate * 100.0));
+    }
+    if stats.current_streak > 1 {
+        desc.push_str(&format!(", current streak {}", stats.current_streak));
+    }
+    desc
+}
+
+/// Summary of the current user's occurrences for one day.
+#[derive(Clone, Debug, Serialize)]
+pub struct Digest {
+    /// When the digest was created.
+    pub date: OccDate,
+    /// Occurrences which start or end during the day, excluding those in
+    /// `overdue`.
+    pub today: Vec<DigestOcc>,
+    /// Task occurrences which missed their target.
+    pub overdue: Vec<DigestOcc>,
+    /// Completion statistics for all tasks over the configured
+    /// [period](crate::configrefs::NOTIFICATIONS_DIGEST_STATS_PERIOD).
+    pub stats: CompletionStats,
+    /// Completion statistics for each category over the same period.
+    pub categories: BTreeMap<String, CompletionStats>,
+    /// Review of the previous week, included in the first digest each week
+    /// when [enabled](crate::configrefs::NOTIFICATIONS_DIGEST_WEEKLY_REVIEW).
+    pub weekly_review: Option<WeeklyReview>,
+}
+
+impl Digest {
+    /// Short description of the digest, suitable for a message subject.
+    pub fn subject(&self) -> String {
+        let mut subject = format!("Digest for {}: {} today",
+                                  self.date.format("%Y-%m-%d"),
+                                  self.today.len());
+        if !self.overdue.is_empty() {
+            subject.push_str(&format!(", {} overdue", self.overdue.len()));
+        }
+        subject
+    }
+
+    /// Full contents of the digest, suitable for a message body.
+    pub fn body(&self) -> String {
+        let mut sections = Vec::new();
+        if !self.today.is_empty() {
+            let lines = self.today.iter()
+                .map(|occ| occ.describe(false))
+                .collect::<Vec<_>>();
+            sections.push(format!("Today:\n{}", lines.join("\n")));
+        }
+        if !self.overdue.is_empty() {
+            let lines = self.overdue.iter()
+                .map(|occ| occ.describe(true))
+                .collect::<Vec<_>>();
+            sections.push(format!("Overdue:\n{}", lines.join("\n")));
+        }
+        if sections.is_empty() {
+            sections.push("Nothing due today.".to_owned());
+        }
+        // only include stats for tasks which have been completed or missed
+        if self.stats.completion_rate().is_some() {
+            let mut lines = vec![
+                format!("Recent tasks: {}.", describe_stats(&self.stats))];
+            lines.extend(self.categories.iter()
+                .filter(|(_, stats)| stats.completion_rate().is_some())
+                .map(|(category, stats)| {
+                    format!("- {category}: {}", describe_stats(stats))
+                }));
+            sections.push(lines.join("\n"));
+        }
+        if let Some(review) = &self.weekly_review {
+            sections.push(format!("{}\n\n{}", review.subject(),
+                                  review.body()));
+        }
+        sections.join("\n\n")
+    }
+}
+
+/// When digests are sent.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct DigestSchedule {
+    /// Time of day, in local time.
+    pub time: NaiveTime,
+    /// Offset of local time from UTC (see
+    /// [`Clock::utc_offset`](crate::util::clock::Clock::utc_offset)).
+    pub utc_offset: FixedOffset,
+    /// How far back completion statistics cover.
+    pub stats_period: TimeDelta,
+}
+
+impl DigestSchedule {
+    /// The latest time a digest should have been sent at, as of `date`.
+    pub fn latest(&self, date: OccDate) -> OccDate {
+        let local = date.with_timezone(&self.utc_offset);
+        let today = (local.date_naive().and_time(self.time) - self.utc_offset)
+            .and_utc();
+        if today <= date { today } else { today - TimeDelta::days(1) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, NaiveTime, TimeDelta};
+    use crate::types::OccDate;
+    use crate::util::clock::{Clock, FixedClock};
+    use super::DigestSchedule;
+
+    fn schedule(clock: &dyn Clock) -> DigestSchedule {
+        DigestSchedule {
+            time: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            utc_offset: clock.utc_offset(),
+            stats_period: TimeDelta::days(7),
+        }
+    }
+
+    #[test]
+    fn latest_uses_local_time() {
+        let now = "2026-10-17T23:00:00Z".parse::<OccDate>().unwrap();
+        let clock = FixedClock {
+            now,
+            utc_offset: FixedOffset::east_opt(5 * 3600).unwrap(),
+        };
+        // 04:00 on the 18th locally, so 08:00 on the 17th was last reached
+        let expected = "2026-10-17T03:00:00Z".parse::<OccDate>().unwrap();
+        assert_eq!(schedule(&clock).latest(clock.now()), expected);
+
+        let clock = FixedClock {
+            now,
+            utc_offset: FixedOffset::west_opt(5 * 3600).unwrap(),
+        };
+        // 18:00 on the 17th locally
+        let expected = "2026-10-17T13:00:00Z".parse::<OccDate>().unwrap();
+        assert_eq!(schedule(&clock).latest(clock.now()), expected);
+    }
+}
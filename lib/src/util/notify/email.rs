@@ -0,0 +1,134 @@
+//! Sending alerts by email, over SMTP.
+
+use std::time::Duration;
+use lettre::{Message, SmtpTransport, Transport};
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use crate::config::{self, Config};
+use crate::configrefs;
+use crate::types::Config as ItemConfig;
+use super::{Channel, ChannelKind, PendingAlert};
+use super::digest::Digest;
+use super::template::Templates;
+use super::super::milestones::PendingMilestone;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How connections to the SMTP server are secured.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, strum::AsRefStr,
+         strum::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Security {
+    /// Upgrade to TLS after connecting, on port 587 by default.
+    #[default]
+    StartTls,
+    /// Connect using TLS, on port 465 by default.
+    Tls,
+    /// Don't use TLS, on port 25 by default.  Only suitable for local
+    /// servers.
+    None,
+}
+
+/// Channel which sends alerts by email.
+///
+/// Alerts are sent for occurrences unless their config disables email (see
+/// [`NotificationConfig::email`](crate::types::NotificationConfig::email)) or
+/// doesn't list it in its channels.
+pub struct EmailChannel {
+    transport: SmtpTransport,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    templates: Templates,
+}
+
+impl EmailChannel {
+    /// Create a channel using the SMTP server, credentials, addresses and
+    /// templates configured in `cfg`.
+    ///
+    /// This doesn't connect to the server.
+    pub fn new(cfg: &impl Config) -> Result<EmailChannel, String> {
+        let server = cfg.get_ref(&configrefs::NOTIFICATIONS_EMAIL_SERVER);
+        if server.is_empty() {
+            return Err("email notifications require a server \
+                        (notifications.email.server)".to_owned());
+        }
+        let port = config::get_parsed(
+            cfg, &configrefs::NOTIFICATIONS_EMAIL_PORT)?;
+        let username = config::get_parsed(
+            cfg, &configrefs::NOTIFICATIONS_EMAIL_USERNAME)?;
+        let password = config::get_parsed(
+            cfg, &configrefs::NOTIFICATIONS_EMAIL_PASSWORD)?;
+        let from = config::get_parsed(
+            cfg, &configrefs::NOTIFICATIONS_EMAIL_FROM)?;
+        let to = config::get_parsed(cfg, &configrefs::NOTIFICATIONS_EMAIL_TO)?;
+        if to.is_empty() {
+            return Err("email notifications require recipients \
+                        (notifications.email.to)".to_owned());
+        }
+
+        let mut builder = match config::get_parsed(
+            cfg, &configrefs::NOTIFICATIONS_EMAIL_SECURITY)?
+        {
+            Security::StartTls => SmtpTransport::starttls_relay(server),
+            Security::Tls => SmtpTransport::relay(server),
+            Security::None => Ok(SmtpTransport::builder_dangerous(server)),
+        }
+            .map_err(|e| format!("invalid email server ({server}): {e}"))?
+            .timeout(Some(TIMEOUT));
+        if port != 0 {
+            builder = builder.port(port);
+        }
+        if !username.is_empty() {
+            builder = builder.credentials(
+                Credentials::new(username, password));
+        }
+        Ok(EmailChannel {
+            transport: builder.build(),
+            from,
+            to,
+            templates: Templates::for_channel(cfg, ChannelKind::Email)?,
+        })
+    }
+
+    fn send_message(&self, subject: String, body: String)
+    -> Result<(), String> {
+        let mut message = Message::builder()
+            .from(self.from.clone())
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        let message = message.body(body)
+            .map_err(|e| format!("error building email: {e}"))?;
+        self.transport.send(&message)
+            .map(|_| ())
+            .map_err(|e| format!("error sending email: {e}"))
+    }
+}
+
+impl Channel for EmailChannel {
+    fn name(&self) -> &str {
+        ChannelKind::Email.as_ref()
+    }
+
+    fn enabled(&self, config: &ItemConfig) -> bool {
+        config.notification_conf.email.unwrap_or(true)
+            && config.notification_conf.channel_enabled(self.name())
+    }
+
+    fn send(&self, alert: &PendingAlert) -> Result<(), String> {
+        self.send_message(self.templates.subject(alert)?,
+                          self.templates.body(alert)?)
+    }
+
+    fn send_digest(&self, digest: &Digest) -> Result<(), String> {
+        self.send_message(digest.subject(), digest.body())
+    }
+
+    fn send_milestone(&self, milestone: &PendingMilestone)
+    -> Result<(), String> {
+        self.send_message(milestone.subject(), milestone.body())
+    }
+}
@@ -0,0 +1,359 @@
+//! Sending alerts as push notifications over HTTP, to an
+//! [ntfy](https://ntfy.sh) topic, a [Gotify](https://gotify.net) server or an
+//! arbitrary webhook URL.
+
+use std::time::Duration;
+use serde::Serialize;
+use crate::config::{self, Config, ParsedValueRef};
+use crate::config::parse::FromStrParser;
+use crate::configrefs;
+use crate::db::{StoredItem, StoredOcc};
+use crate::types::{AlertKind, Milestone, Priority};
+use super::{Channel, ChannelKind, PendingAlert};
+use super::digest::Digest;
+use super::super::milestones::PendingMilestone;
+use super::template::Templates;
+use super::super::progress::TaskProgress;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Priorities to send each kind of alert with, for a single channel.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Priorities {
+    pub upcoming: Priority,
+    pub overdue: Priority,
+}
+
+impl Priorities {
+    /// Read priorities from config.
+    fn new(
+        cfg: &impl Config,
+        upcoming: &ParsedValueRef<'_, FromStrParser<Priority>>,
+        overdue: &ParsedValueRef<'_, FromStrParser<Priority>>,
+    ) -> Result<Priorities, String> {
+        Ok(Priorities {
+            upcoming: config::get_parsed(cfg, upcoming)?,
+            overdue: config::get_parsed(cfg, overdue)?,
+        })
+    }
+
+    /// Get the priority to send `alert` with.  Escalated alerts use the
+    /// priority from their escalation rule, if any.
+    pub fn get(&self, alert: &PendingAlert) -> Priority {
+        match alert.kind {
+            AlertKind::Upcoming => self.upcoming,
+            AlertKind::Overdue => self.overdue,
+            AlertKind::Escalated => alert.config.notification_conf.escalation
+                .as_ref()
+                .and_then(|escalation| escalation.priority)
+                .unwrap_or(self.overdue),
+        }
+    }
+}
+
+/// Read the URL for channel `kind` from config, which must not be empty.
+fn required_url(
+    cfg: &impl Config,
+    kind: ChannelKind,
+    pref: &ParsedValueRef<'_, FromStrParser<String>>,
+) -> Result<String, String> {
+    let url = config::get_parsed(cfg, pref)?;
+    if url.is_empty() {
+        Err(format!("{} notifications require a URL ({})",
+                    kind.as_ref(), pref.vref.names.join(".")))
+    } else {
+        Ok(url)
+    }
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new().timeout(TIMEOUT).build()
+}
+
+/// Send a request with a JSON body, failing for error responses.
+fn post_json(
+    request: ureq::Request,
+    body: impl Serialize,
+) -> Result<(), String> {
+    // errors include the URL
+    request.send_json(body)
+        .map(|_| ())
+        .map_err(|e| format!("error sending request: {e}"))
+}
+
+/// Channel which publishes alerts to an ntfy topic.
+pub struct NtfyChannel {
+    http: ureq::Agent,
+    templates: Templates,
+    url: String,
+    topic: String,
+    token: String,
+    priorities: Priorities,
+}
+
+/// Body of ntfy publish requests.
+#[derive(Serialize)]
+struct NtfyMessage<'a> {
+    topic: &'a str,
+    title: String,
+    message: String,
+    /// From 1 (min) to 5 (max).
+    priority: u8,
+    tags: [&'a str; 1],
+}
+
+impl NtfyChannel {
+    /// Create a channel using the server, topic, priorities and templates
+    /// configured in `cfg`.
+    pub fn new(cfg: &impl Config) -> Result<NtfyChannel, String> {
+        let topic = config::get_parsed(
+            cfg, &configrefs::NOTIFICATIONS_NTFY_TOPIC)?;
+        if topic.is_empty() {
+            return Err("ntfy notifications require a topic \
+                        (notifications.ntfy.topic)".to_owned());
+        }
+        Ok(NtfyChannel {
+            http: agent(),
+            templates: Templates::for_channel(cfg, ChannelKind::Ntfy)?,
+            url: required_url(cfg, ChannelKind::Ntfy,
+                              &configrefs::NOTIFICATIONS_NTFY_URL)?,
+            topic,
+            token: config::get_parsed(
+                cfg, &configrefs::NOTIFICATIONS_NTFY_TOKEN)?,
+            priorities: Priorities::new(
+                cfg,
+                &configrefs::NOTIFICATIONS_NTFY_PRIORITY_UPCOMING,
+                &configrefs::NOTIFICATIONS_NTFY_PRIORITY_OVERDUE)?,
+        })
+    }
+
+    fn publish(&self, title: String, message: String, priority: Priority,
+               tag: &str)
+    -> Result<(), String> {
+        let priority = match priority {
+            Priority::Min => 1,
+            Priority::Low => 2,
+            Priority::Default => 3,
+            Priority::High => 4,
+            Priority::Max => 5,
+        };
+        // publishing as JSON allows non-ASCII titles
+        let url = format!("{}/", self.url.trim_end_matches('/'));
+        let mut request = self.http.post(&url);
+        if !self.token.is_empty() {
+            request = request.set(
+                "Authorization", &format!("Bearer {}", self.token));
+        }
+        post_json(request, NtfyMessage {
+            topic: &self.topic,
+            title,
+            message,
+            priority,
+            tags: [tag],
+        })
+    }
+}
+
+impl Channel for NtfyChannel {
+    fn name(&self) -> &str {
+        ChannelKind::Ntfy.as_ref()
+    }
+
+    fn send(&self, alert: &PendingAlert) -> Result<(), String> {
+        let tag = match alert.kind {
+            AlertKind::Upcoming => "alarm_clock",
+            AlertKind::Overdue => "warning",
+            AlertKind::Escalated => "rotating_light",
+        };
+        self.publish(self.templates.subject(alert)?,
+                     self.templates.body(alert)?,
+                     self.priorities.get(alert), tag)
+    }
+
+    fn send_digest(&self, digest: &Digest) -> Result<(), String> {
+        self.publish(digest.subject(), digest.body(), Priority::Default,
+                     "calendar")
+    }
+
+    fn send_milestone(&self, milestone: &PendingMilestone)
+    -> Result<(), String> {
+        self.publish(milestone.subject(), milestone.body(), Priority::Default,
+                     "trophy")
+    }
+}
+
+/// Channel which sends alerts as messages to a Gotify server.
+pub struct GotifyChannel {
+    http: ureq::Agent,
+    templates: Templates,
+    url: String,
+    token: String,
+    priorities: Priorities,
+}
+
+/// Body of Gotify message requests.
+#[derive(Serialize)]
+struct GotifyMessage {
+    title: String,
+    message: String,
+    /// From 0 (min) to 10 (max).
+    priority: u8,
+}
+
+impl GotifyChannel {
+    /// Create a channel using the server, application token, priorities and
+    /// templates configured in `cfg`.
+    pub fn new(cfg: &impl Config) -> Result<GotifyChannel, String> {
+        let token = config::get_parsed(
+            cfg, &configrefs::NOTIFICATIONS_GOTIFY_TOKEN)?;
+        if token.is_empty() {
+            return Err("gotify notifications require an application token \
+                        (notifications.gotify.token)".to_owned());
+        }
+        Ok(GotifyChannel {
+            http: agent(),
+            templates: Templates::for_channel(cfg, ChannelKind::Gotify)?,
+            url: required_url(cfg, ChannelKind::Gotify,
+                              &configrefs::NOTIFICATIONS_GOTIFY_URL)?,
+            token,
+            priorities: Priorities::new(
+                cfg,
+                &configrefs::NOTIFICATIONS_GOTIFY_PRIORITY_UPCOMING,
+                &configrefs::NOTIFICATIONS_GOTIFY_PRIORITY_OVERDUE)?,
+        })
+    }
+
+    fn publish(&self, title: String, message: String, priority: Priority)
+    -> Result<(), String> {
+        // Gotify clients treat 8 and above as high priority
+        let priority = match priority {
+            Priority::Min => 0,
+            Priority::Low => 2,
+            Priority::Default => 5,
+            Priority::High => 8,
+            Priority::Max => 10,
+        };
+        let url = format!("{}/message", self.url.trim_end_matches('/'));
+        let request = self.http.post(&url)
+            .set("X-Gotify-Key", &self.token);
+        post_json(request, GotifyMessage { title, message, priority })
+    }
+}
+
+impl Channel for GotifyChannel {
+    fn name(&self) -> &str {
+        ChannelKind::Gotify.as_ref()
+    }
+
+    fn send(&self, alert: &PendingAlert) -> Result<(), String> {
+        self.publish(self.templates.subject(alert)?,
+                     self.templates.body(alert)?,
+                     self.priorities.get(alert))
+    }
+
+    fn send_digest(&self, digest: &Digest) -> Result<(), String> {
+        self.publish(digest.subject(), digest.body(), Priority::Default)
+    }
+
+    fn send_milestone(&self, milestone: &PendingMilestone)
+    -> Result<(), String> {
+        self.publish(milestone.subject(), milestone.body(), Priority::Default)
+    }
+}
+
+/// Channel which sends alerts as JSON to an arbitrary URL.
+pub struct WebhookChannel {
+    http: ureq::Agent,
+    templates: Templates,
+    url: String,
+    priorities: Priorities,
+}
+
+/// Body of webhook requests.
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    kind: AlertKind,
+    priority: Priority,
+    subject: String,
+    body: String,
+    item: &'a StoredItem,
+    occ: &'a StoredOcc,
+    /// Missing for events.
+    progress: Option<&'a TaskProgress>,
+}
+
+/// Body of webhook requests for digests.
+#[derive(Serialize)]
+struct WebhookDigestPayload<'a> {
+    /// Always `digest`, to distinguish from alerts.
+    kind: &'static str,
+    subject: String,
+    body: String,
+    digest: &'a Digest,
+}
+
+/// Body of webhook requests for milestones.
+#[derive(Serialize)]
+struct WebhookMilestonePayload<'a> {
+    /// Always `milestone`, to distinguish from alerts.
+    kind: &'static str,
+    subject: String,
+    body: String,
+    item: &'a StoredItem,
+    milestone: &'a Milestone,
+}
+
+impl WebhookChannel {
+    /// Create a channel using the URL, priorities and templates configured in
+    /// `cfg`.
+    pub fn new(cfg: &impl Config) -> Result<WebhookChannel, String> {
+        Ok(WebhookChannel {
+            http: agent(),
+            templates: Templates::for_channel(cfg, ChannelKind::Webhook)?,
+            url: required_url(cfg, ChannelKind::Webhook,
+                              &configrefs::NOTIFICATIONS_WEBHOOK_URL)?,
+            priorities: Priorities::new(
+                cfg,
+                &configrefs::NOTIFICATIONS_WEBHOOK_PRIORITY_UPCOMING,
+                &configrefs::NOTIFICATIONS_WEBHOOK_PRIORITY_OVERDUE)?,
+        })
+    }
+}
+
+impl Channel for WebhookChannel {
+    fn name(&self) -> &str {
+        ChannelKind::Webhook.as_ref()
+    }
+
+    fn send(&self, alert: &PendingAlert) -> Result<(), String> {
+        post_json(self.http.post(&self.url), WebhookPayload {
+            kind: alert.kind,
+            priority: self.priorities.get(alert),
+            subject: self.templates.subject(alert)?,
+            body: self.templates.body(alert)?,
+            item: &alert.item,
+            occ: &alert.occ,
+            progress: alert.progress.as_ref(),
+        })
+    }
+
+    fn send_digest(&self, digest: &Digest) -> Result<(), String> {
+        post_json(self.http.post(&self.url), WebhookDigestPayload {
+            kind: "digest",
+            subject: digest.subject(),
+            body: digest.body(),
+            digest,
+        })
+    }
+
+    fn send_milestone(&self, milestone: &PendingMilestone)
+    -> Result<(), String> {
+        post_json(self.http.post(&self.url), WebhookMilestonePayload {
+            kind: "milestone",
+            subject: milestone.subject(),
+            body: milestone.body(),
+            item: &milestone.item,
+            milestone: &milestone.milestone,
+        })
+    }
+}
@@ -0,0 +1,197 @@
+//! Rendering alert subjects and bodies from
+//! [MiniJinja](https://docs.rs/minijinja) templates.
+//!
+//! Templates have access to the following values.  Missing values are
+//! undefined, which renders as an empty string.
+//!
+//! - `kind`: `upcoming`, `overdue` or `escalated`
+//! - `overdue`: whether `kind` is `overdue` or `escalated`
+//! - `item`: `name`, `desc`, `category`, `type` and `schedule` (a description
+//!   of the schedule, like `every 2 weeks on Tue`)
+//! - `start`, `deadline`: when the occurrence starts and ends, formatted like
+//!   `2024-06-01 09:00`
+//! - `progress`: for tasks, `done`, `total`, `percent` and `unit` (which may be
+//!   missing); missing for events
+
+use std::fmt;
+use std::str::FromStr;
+use minijinja::Environment;
+use serde::Serialize;
+use crate::config::{self, Config, ParsedValueRef};
+use crate::config::parse::FromStrParser;
+use crate::configrefs;
+use crate::types::AlertKind;
+use super::{ChannelKind, PendingAlert};
+use super::super::sched;
+
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// A template whose syntax has been checked.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Template {
+    source: String,
+}
+
+impl Template {
+    /// Whether the template is empty, which is used to mean that a default
+    /// should be used instead.
+    pub fn is_empty(&self) -> bool {
+        self.source.is_empty()
+    }
+
+    fn render(&self, ctx: &Context) -> Result<String, String> {
+        Environment::new().render_str(&self.source, ctx)
+            .map_err(|e| format!("error rendering template: {e}"))
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Template, String> {
+        Environment::new().template_from_str(source)
+            .map_err(|e| format!("invalid template: {e}"))?;
+        Ok(Template { source: source.to_owned() })
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Serialize)]
+struct ItemContext<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    desc: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    category: Option<&'a str>,
+    #[serde(rename = "type")]
+    type_: &'a str,
+    schedule: String,
+}
+
+#[derive(Serialize)]
+struct ProgressContext<'a> {
+    done: u32,
+    total: u32,
+    percent: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unit: Option<&'a str>,
+}
+
+/// Values available to templates.
+#[derive(Serialize)]
+struct Context<'a> {
+    kind: &'static str,
+    overdue: bool,
+    item: ItemContext<'a>,
+    start: String,
+    deadline: String,
+    progress: Option<ProgressContext<'a>>,
+}
+
+impl<'a> Context<'a> {
+    fn new(alert: &'a PendingAlert) -> Context<'a> {
+        let item = &alert.item.item;
+        let progress = alert.progress.as_ref().map(|progress| {
+            let done = progress.effective_progress();
+            let percent = if progress.total == 0 {
+                100
+            } else {
+                u64::from(done) * 100 / u64::from(progress.total)
+            };
+            ProgressContext {
+                done,
+                total: progress.total,
+                percent: u32::try_from(percent).unwrap_or(u32::MAX),
+                unit: alert.config.task_completion_conf.unit.as_deref(),
+            }
+        });
+        Context {
+            kind: match alert.kind {
+                AlertKind::Upcoming => "upcoming",
+                AlertKind::Overdue => "overdue",
+                AlertKind::Escalated => "escalated",
+            },
+            overdue: alert.kind != AlertKind::Upcoming,
+            item: ItemContext {
+                name: &item.name,
+                desc: item.desc.as_deref(),
+                category: item.category.as_deref(),
+                type_: item.type_.as_ref(),
+                schedule: sched::describe(&item.sched),
+            },
+            start: alert.occ.occ.start.format(DATE_FORMAT).to_string(),
+            deadline: alert.occ.occ.end.format(DATE_FORMAT).to_string(),
+            progress,
+        }
+    }
+}
+
+/// Templates used to phrase alerts for one channel.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Templates {
+    subject: Template,
+    body: Template,
+}
+
+impl Templates {
+    /// Read the default templates, used by channels which don't set their
+    /// own, from config.
+    pub fn new(cfg: &impl Config) -> Result<Templates, String> {
+        Ok(Templates {
+            subject: config::get_parsed(
+                cfg, &configrefs::NOTIFICATIONS_TEMPLATES_SUBJECT)?,
+            body: config::get_parsed(
+                cfg, &configrefs::NOTIFICATIONS_TEMPLATES_BODY)?,
+        })
+    }
+
+    /// Read the templates for channel `kind` from config, using the default
+    /// templates where the channel's are empty.
+    pub fn for_channel(cfg: &impl Config, kind: ChannelKind)
+    -> Result<Templates, String> {
+        let (subject, body) = match kind {
+            ChannelKind::Log => (&configrefs::NOTIFICATIONS_LOG_SUBJECT,
+                                 &configrefs::NOTIFICATIONS_LOG_BODY),
+            ChannelKind::Email => (&configrefs::NOTIFICATIONS_EMAIL_SUBJECT,
+                                   &configrefs::NOTIFICATIONS_EMAIL_BODY),
+            ChannelKind::Ntfy => (&configrefs::NOTIFICATIONS_NTFY_SUBJECT,
+                                  &configrefs::NOTIFICATIONS_NTFY_BODY),
+            ChannelKind::Gotify => (&configrefs::NOTIFICATIONS_GOTIFY_SUBJECT,
+                                    &configrefs::NOTIFICATIONS_GOTIFY_BODY),
+            ChannelKind::Webhook => (
+                &configrefs::NOTIFICATIONS_WEBHOOK_SUBJECT,
+                &configrefs::NOTIFICATIONS_WEBHOOK_BODY),
+        };
+        let defaults = Templates::new(cfg)?;
+        let or_default = |pref: &ParsedValueRef<'_, FromStrParser<Template>>,
+                          default: Template| {
+            config::get_parsed(cfg, pref)
+                .map(|template| {
+                    if template.is_empty() { default } else { template }
+                })
+        };
+        Ok(Templates {
+            subject: or_default(subject, defaults.subject)?,
+            body: or_default(body, defaults.body)?,
+        })
+    }
+
+    /// Render the subject for `alert`.  Leading and trailing whitespace is
+    /// removed.
+    pub fn subject(&self, alert: &PendingAlert) -> Result<String, String> {
+        self.subject.render(&Context::new(alert))
+            .map(|subject| subject.trim().to_owned())
+    }
+
+    /// Render the body for `alert`.  Leading and trailing whitespace is
+    /// removed.
+    pub fn body(&self, alert: &PendingAlert) -> Result<String, String> {
+        self.body.render(&Context::new(alert))
+            .map(|body| body.trim().to_owned())
+    }
+}
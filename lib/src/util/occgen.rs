@@ -1,8 +1,12 @@
 //! Create new occurrences based on an item's schedule.
+//!
+//! Embedders can generate occurrences differently for some items by
+//! [registering](register) their own generators.
 
+use std::sync::{PoisonError, RwLock};
 use chrono::{NaiveDate, NaiveTime};
-use crate::types::{ProgressTaskSched, DeadlineTaskSched, EventSched, Occ,
-                   OccDate};
+use crate::types::{ProgressTaskSched, DeadlineTaskSched, EventSched, Item,
+                   Occ, OccDate, Sched};
 use super::sched;
 
 /// Generates occurrences.
@@ -16,6 +20,66 @@ pub trait OccGen {
     fn generate_first(&self, now: OccDate) -> Option<Occ>;
 }
 
+/// Provides the occurrence generator for an item, or `None` to leave the item
+/// to other generators.
+pub type OccGenFactory =
+    dyn for<'i> Fn(&'i Item) -> Option<Box<dyn OccGen + 'i>> + Send + Sync;
+
+/// Factories added by [`register`], in the order added.
+static REGISTRY: RwLock<Vec<Box<OccGenFactory>>> = RwLock::new(Vec::new());
+
+/// Register a generator `factory`, used for every item it provides a generator
+/// for.  It takes precedence over factories registered earlier, and over the
+/// built-in generators.
+///
+/// The factory is called whenever occurrences are generated, so it should
+/// only look at the item, and it must not register other factories.
+pub fn register<F>(factory: F)
+where
+    F: for<'i> Fn(&'i Item) -> Option<Box<dyn OccGen + 'i>>
+        + Send + Sync + 'static,
+{
+    REGISTRY.write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(Box::new(factory));
+}
+
+/// Get the occurrence generator for an item, from the most recently
+/// [registered](register) factory which provides one, falling back to the
+/// built-in generator for its schedule (see [`for_sched`]).
+pub fn for_item(item: &Item) -> Box<dyn OccGen + '_> {
+    REGISTRY.read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter().rev()
+        .find_map(|factory| factory(item))
+        .unwrap_or_else(|| for_sched(&item.sched))
+}
+
+/// Get the built-in occurrence generator for a schedule.  This ignores
+/// [registered](register) generators, which need the item; use [`for_item`]
+/// where there is one.
+pub fn for_sched(sched: &Sched) -> Box<dyn OccGen + '_> {
+    match sched {
+        Sched::Event(sched) => Box::new(EventOccGen { sched }),
+        Sched::ProgressTask(sched) => Box::new(ProgressTaskOccGen { sched }),
+        Sched::DeadlineTask(sched) => Box::new(DeadlineTaskOccGen { sched }),
+    }
+}
+
+/// Generate the occurrences an item with the schedule `sched` would have
+/// which overlap the time range from `start` to `end`, if it had no
+/// occurrences before `start`.  Nothing is stored.
+///
+/// `sched` should have no [problems](super::sched::problems).
+pub fn preview(sched: &Sched, start: OccDate, end: OccDate) -> Vec<Occ> {
+    let occ_gen = for_sched(sched);
+    let Some(first) = occ_gen.generate_first(start) else { return vec![] };
+    let mut occs = occ_gen.generate_after(&first, end);
+    occs.insert(0, first);
+    occs.retain(|occ| super::occ_overlaps(occ, start, end));
+    occs
+}
+
 /// Return an occurrence date for the start of a `day`.
 fn day_to_occ_date(day: NaiveDate) -> OccDate {
     day.and_time(NaiveTime::MIN).and_utc()
@@ -28,6 +92,7 @@ fn new_occ(start: OccDate, end: OccDate) -> Occ {
         start,
         end,
         task_completion_progress: 0,
+        snoozed_until: None,
     }
 }
 
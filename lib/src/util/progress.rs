@@ -1,33 +1,119 @@
-//! Utilities related to [task progress](Occ::task_completion_progress).
+//! Utilities related to
+//! [task progress](crate::types::Occ::task_completion_progress).
+//!
+//! Where an occurrence's progress is greater than its target completion amount,
+//! the difference is *excess*, which may be transferred to nearby occurrences
+//! which have not reached their target, according to their config.  Transfers
+//! are prioritised as follows:
+//!
+//! 1. Donors sharing excess [nearest first](TransferOrder::NearestFirst) are
+//!    processed before donors sharing
+//!    [proportionally](TransferOrder::Proportional).
+//! 2. Nearest-first transfers are made in order of the time between donor and
+//!    recipient, shortest first.  Ties are broken by the earlier recipient,
+//!    then the earlier donor.
+//! 3. Proportional donors are processed in order of start date, each splitting
+//!    its remaining excess between its possible recipients according to their
+//!    remaining need.
+//!
+//! An occurrence never donates more than its excess, never receives more than
+//! it needs to reach its target, and never both donates and receives.
 
-use std::cmp::{max, min};
+use std::cmp::min;
 use std::collections::{HashMap, HashSet};
-use crate::db::{Db, DbResult, SortDirection, StoredOcc};
-use crate::types::Occ;
+use std::error::Error;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::db::{ConfigId, Db, DbResult, SortDirection, StoredItem, StoredOcc};
+use crate::db::util as dbutil;
+use crate::types::{Config, ItemType, OccDate, TargetKind, TransferOrder};
 use super::config::{self, ResolvedConfig};
+use super::status::TrackedOcc;
+
+/// Number of seconds in a day, for expressing rates of progress.
+const DAY_SECONDS: f64 = 86400.0;
+
+/// Error adding progress to an occurrence.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ProgressError {
+    /// The item is an event, so doesn't track progress.
+    NotTracked,
+    /// The item has no current occurrence to add progress to.
+    NoCurrentOcc,
+    /// An amount isn't a number.
+    InvalidAmount { value: String },
+    /// An amount isn't a whole number.
+    FractionalAmount { value: String },
+    /// An amount has a different unit to the item.
+    WrongUnit { unit: String, expected: String },
+    /// An amount has a unit, but the item has none.
+    UnexpectedUnit { unit: String },
+    /// Error accessing the database.
+    Db(String),
+}
+
+impl fmt::Display for ProgressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgressError::NotTracked => write!(f, "item has no progress"),
+            ProgressError::NoCurrentOcc => {
+                write!(f, "item has no current occurrence")
+            }
+            ProgressError::InvalidAmount { value } => {
+                write!(f, "invalid amount: {value}")
+            }
+            ProgressError::FractionalAmount { value } => {
+                write!(f, "amount must be a whole number: {value}")
+            }
+            ProgressError::WrongUnit { unit, expected } => {
+                write!(f, "amount has the wrong unit ({unit}): expected \
+                           {expected}")
+            }
+            ProgressError::UnexpectedUnit { unit } => {
+                write!(f, "amount has a unit ({unit}), but the item has no \
+                           unit configured")
+            }
+            ProgressError::Db(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for ProgressError {}
+
+impl From<ProgressError> for String {
+    fn from(e: ProgressError) -> String {
+        e.to_string()
+    }
+}
 
 /// Progress details for a task, including donation information (see
 /// [`excess_past`](crate::types::TaskCompletionConfig::excess_past),
 /// [`excess_future`](crate::types::TaskCompletionConfig::excess_future)).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct TaskProgress {
     /// Progress towards completing the occurrence.
     ///
     /// This may be greater than `total`.  This is the progress registered
     /// directly with this occurrence, before transferring progress between
     /// occurrences.
-    progress: u32,
+    pub progress: u32,
     /// Target occurrence completion amount.
-    total: u32,
+    pub total: u32,
     /// Amount of `progress` donated to other occurrences.
     ///
     /// This occurs where transfer is allowed, and `progress` is greater than
     /// `total`.
-    donated_excess: u32,
+    pub donated_excess: u32,
     /// Amount of `progress` received from other occurrences.
     ///
     /// This occurs where transfer is allowed, and `progress` is less than
     /// `total`.
-    received_excess: u32,
+    pub received_excess: u32,
+    /// How progress is compared to `total`.  There is never any transfer for
+    /// [`TargetKind::AtMost`].
+    #[serde(default)]
+    pub target_kind: TargetKind,
 }
 
 impl Default for TaskProgress {
@@ -37,23 +123,240 @@ impl Default for TaskProgress {
             total: 1,
             donated_excess: 0,
             received_excess: 0,
+            target_kind: TargetKind::AtLeast,
+        }
+    }
+}
+
+impl TaskProgress {
+    /// Difference between progress and the target completion amount, before
+    /// transferring progress between occurrences.
+    ///
+    /// This is positive when there is excess progress, and negative when more
+    /// progress is needed.
+    pub fn excess(&self) -> i64 {
+        i64::from(self.progress) - i64::from(self.total)
+    }
+
+    /// Amount of excess progress not yet donated to other occurrences.
+    pub fn available_excess(&self) -> u32 {
+        match self.target_kind {
+            TargetKind::AtLeast =>
+                clamp_u32(self.excess() - i64::from(self.donated_excess)),
+            TargetKind::AtMost => 0,
+        }
+    }
+
+    /// Amount of progress still needed from other occurrences to reach the
+    /// target completion amount.
+    pub fn needed_excess(&self) -> u32 {
+        match self.target_kind {
+            TargetKind::AtLeast =>
+                clamp_u32(-self.excess() - i64::from(self.received_excess)),
+            TargetKind::AtMost => 0,
+        }
+    }
+
+    /// Amount of progress still needed to reach the target completion amount,
+    /// after transferring progress between occurrences.
+    ///
+    /// This is always `0` for [`TargetKind::AtMost`].
+    pub fn remaining(&self) -> u32 {
+        match self.target_kind {
+            TargetKind::AtLeast =>
+                self.total.saturating_sub(self.effective_progress()),
+            TargetKind::AtMost => 0,
+        }
+    }
+
+    /// Progress after transferring progress between occurrences.
+    pub fn effective_progress(&self) -> u32 {
+        clamp_u32(i64::from(self.progress) -
+                  i64::from(self.donated_excess) +
+                  i64::from(self.received_excess))
+    }
+
+    /// Whether the effective progress meets the target completion amount.
+    ///
+    /// For [`TargetKind::AtMost`], this is true until progress exceeds the
+    /// target.
+    pub fn is_complete(&self) -> bool {
+        match self.target_kind {
+            TargetKind::AtLeast => self.effective_progress() >= self.total,
+            TargetKind::AtMost => self.effective_progress() <= self.total,
+        }
+    }
+
+    /// Amount of the target completion amount achieved, from `0` to `total`.
+    ///
+    /// For [`TargetKind::AtLeast`], this is the effective progress.  For
+    /// [`TargetKind::AtMost`], this is reduced by progress over the target.
+    pub fn achieved(&self) -> u32 {
+        achieved(self.target_kind, self.effective_progress(), self.total)
+    }
+
+    /// Effective progress as a proportion of the target completion amount.
+    ///
+    /// This may be greater than `1`.  A target of `0` counts as complete.
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            f64::from(self.effective_progress()) / f64::from(self.total)
+        }
+    }
+
+    /// Whether transfers recorded in this progress are consistent with its
+    /// excess.
+    fn transfers_valid(&self) -> bool {
+        let excess = self.excess();
+        i64::from(self.donated_excess) <= excess.max(0) &&
+            i64::from(self.received_excess) <= (-excess).max(0)
+    }
+}
+
+/// Amount of `total` achieved with `progress`, for a target of `kind`.
+///
+/// See [`TaskProgress::achieved`].
+pub fn achieved(kind: TargetKind, progress: u32, total: u32) -> u32 {
+    match kind {
+        TargetKind::AtLeast => min(progress, total),
+        TargetKind::AtMost =>
+            total.saturating_sub(progress.saturating_sub(total)),
+    }
+}
+
+/// Clamp a signed progress amount into the range of progress values.
+fn clamp_u32(value: i64) -> u32 {
+    value.clamp(0, u32::MAX.into()) as u32
+}
+
+/// Progress summed over multiple task occurrences.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Deserialize,
+         Serialize)]
+pub struct ProgressSummary {
+    /// Number of occurrences.
+    pub occs: u32,
+    /// Number of complete occurrences.
+    pub completed: u32,
+    /// Amount of the target completion amount
+    /// [achieved](TaskProgress::achieved), summed over all occurrences.
+    pub progress: u64,
+    /// Target completion amount summed over all occurrences.
+    pub total: u64,
+}
+
+impl ProgressSummary {
+    /// Add an occurrence's progress to the summary.
+    pub fn add(&mut self, progress: &TaskProgress) {
+        self.occs += 1;
+        if progress.is_complete() {
+            self.completed += 1;
+        }
+        self.progress += u64::from(progress.achieved());
+        self.total += u64::from(progress.total);
+    }
+
+    /// Summed progress as a proportion of the summed target completion amount.
+    ///
+    /// Returns `None` when there are no occurrences.
+    pub fn fraction(&self) -> Option<f64> {
+        if self.total == 0 {
+            None
+        } else {
+            Some(self.progress as f64 / self.total as f64)
         }
     }
 }
 
+/// A transfer of excess progress from one occurrence to another.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+pub struct Donation {
+    /// ID of the occurrence the progress was transferred from.
+    pub donor_occ_id: String,
+    /// ID of the occurrence the progress was transferred to.
+    pub recipient_occ_id: String,
+    /// Amount of progress transferred.
+    pub amount: u32,
+}
+
 /// Return amount of progress to transfer from `donor_prog_detail` to
 /// `recv_prog_detail`.
 fn transfer_progress(
     donor_prog_detail: &TaskProgress,
     recv_prog_detail: &TaskProgress,
 ) -> u32 {
-    let available = donor_prog_detail.total -
-        donor_prog_detail.progress -
-        donor_prog_detail.donated_excess;
-    let needed = recv_prog_detail.total +
-        recv_prog_detail.received_excess -
-        recv_prog_detail.progress;
-    max(0, min(needed, available))
+    min(recv_prog_detail.needed_excess(), donor_prog_detail.available_excess())
+}
+
+/// Record a transfer of `amount` progress from `donor_occ` to `recv_occ`.
+fn apply_transfer(
+    results: &mut HashMap<String, TaskProgress>,
+    donations: &mut Vec<Donation>,
+    donor_occ: &StoredOcc,
+    recv_occ: &StoredOcc,
+    amount: u32,
+) {
+    if amount == 0 {
+        return
+    }
+    let donor = results.get_mut(&donor_occ.id).unwrap();
+    debug_assert!(amount <= donor.available_excess());
+    // amounts are limited by available excess, which fits in a u32
+    donor.donated_excess = donor.donated_excess.checked_add(amount)
+        .expect("donated excess overflowed");
+    let recv = results.get_mut(&recv_occ.id).unwrap();
+    debug_assert!(amount <= recv.needed_excess());
+    recv.received_excess = recv.received_excess.checked_add(amount)
+        .expect("received excess overflowed");
+    donations.push(Donation {
+        donor_occ_id: donor_occ.id.clone(),
+        recipient_occ_id: recv_occ.id.clone(),
+        amount,
+    });
+}
+
+/// Split the available excess of `donor_occ` between `recv_occs` in proportion
+/// to how much each needs.
+///
+/// `recv_occs` must be ordered nearest first; any remainder from rounding is
+/// given out one unit at a time to nearer occurrences.
+fn transfer_proportional(
+    results: &mut HashMap<String, TaskProgress>,
+    donations: &mut Vec<Donation>,
+    donor_occ: &StoredOcc,
+    recv_occs: &[&StoredOcc],
+) {
+    let available = results[&donor_occ.id].available_excess();
+    let needs = recv_occs.iter()
+        .map(|recv_occ| results[&recv_occ.id].needed_excess())
+        .collect::<Vec<_>>();
+    let total_need: u64 = needs.iter().map(|n| u64::from(*n)).sum();
+    if available == 0 || total_need == 0 {
+        return
+    }
+
+    let mut amounts = if total_need <= available.into() {
+        needs.clone()
+    } else {
+        needs.iter()
+            .map(|need| {
+                (u64::from(available) * u64::from(*need) / total_need) as u32
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut remaining = available - amounts.iter().sum::<u32>();
+    // rounding leaves less than one unit per occurrence
+    for (amount, need) in amounts.iter_mut().zip(&needs) {
+        if remaining > 0 && *amount < *need {
+            *amount += 1;
+            remaining -= 1;
+        }
+    }
+
+    for (recv_occ, amount) in recv_occs.iter().zip(amounts) {
+        apply_transfer(results, donations, donor_occ, recv_occ, amount);
+    }
 }
 
 /// Resolve progress for occurrences.
@@ -62,88 +365,135 @@ fn transfer_progress(
 /// occurrences.  Only the given occurrences will be used as sources and targets
 /// of progress transfer.
 ///
-/// When transferring progress between occurrences, nearer donors are
-/// prioritised.
-fn resolve_occs_progress_using(occs: &[(&Occ, &ResolvedConfig)])
--> HashMap<Occ, TaskProgress> {
-    let mut results: HashMap<Occ, TaskProgress> = HashMap::new();
+/// Transfers follow each occurrence's
+/// [transfer policy](crate::types::TaskCompletionConfig::transfer_policy): the
+/// receiving occurrence's policy determines which directions it may receive
+/// from, and the donating occurrence's policy determines how its excess is
+/// shared.  Donors sharing nearest first are processed before donors sharing
+/// proportionally.  Occurrences with a [`TargetKind::AtMost`] target neither
+/// donate nor receive.
+///
+/// This doesn't use the database, so works wherever occurrences and their
+/// configs are already known, such as in a web browser.  Use
+/// [`resolve_occs_progress`] to include neighbouring occurrences from the
+/// database.
+///
+/// Returns progress by occurrence ID, and all transfers made.
+pub fn resolve_occs_progress_using(occs: &[(&StoredOcc, &ResolvedConfig)])
+-> (HashMap<String, TaskProgress>, Vec<Donation>) {
+    let mut results: HashMap<String, TaskProgress> = HashMap::new();
     // (recipient, donor, distance)
-    let mut donations = Vec::<(&Occ, &Occ, chrono::TimeDelta)>::new();
+    let mut candidates =
+        Vec::<(&StoredOcc, &StoredOcc, chrono::TimeDelta)>::new();
+    let mut orders = HashMap::<&str, TransferOrder>::new();
 
-    for (i, (recv_occ, config)) in occs.iter().enumerate() {
+    for (recv_occ, config) in occs {
+        let cmpl_cfg = &config.resolved_config.task_completion_conf;
+        let target_kind = cmpl_cfg.target_kind.unwrap_or_default();
         let prog_detail = TaskProgress {
-            progress: recv_occ.task_completion_progress,
-            total: config.resolved_config
-                .task_completion_conf.total.unwrap_or(1),
+            progress: recv_occ.occ.task_completion_progress,
+            total: cmpl_cfg.total.unwrap_or(1),
+            target_kind,
             ..Default::default()
         };
-        results.insert((*recv_occ).clone(), prog_detail);
+        results.insert(recv_occ.id.clone(), prog_detail);
 
-        let cmpl_cfg = &config.resolved_config.task_completion_conf;
-        let excess_past_min = recv_occ.start - cmpl_cfg.excess_past_chrono();
-        let excess_future_max = recv_occ.end + cmpl_cfg.excess_future_chrono();
-        for (donor_occ, _) in occs {
-            if donor_occ == recv_occ {
+        let recv = &recv_occ.occ;
+        let policy = cmpl_cfg.transfer_policy.unwrap_or_default();
+        orders.insert(&recv_occ.id, policy.order);
+        if target_kind == TargetKind::AtMost {
+            continue
+        }
+        let excess_past_min = recv.start - cmpl_cfg.excess_past_chrono();
+        let excess_future_max = recv.end + cmpl_cfg.excess_future_chrono();
+        for (donor_occ, donor_config) in occs {
+            let donor = &donor_occ.occ;
+            let donor_kind = donor_config.resolved_config
+                .task_completion_conf.target_kind.unwrap_or_default();
+            if donor_occ.id == recv_occ.id || donor_kind == TargetKind::AtMost {
                 continue
             }
-            if donor_occ.start < recv_occ.start &&
-               donor_occ.end > excess_past_min
-            {
-                donations.push((recv_occ, donor_occ,
-                                recv_occ.start - donor_occ.end));
-            } else if donor_occ.start > recv_occ.start &&
-               donor_occ.start < excess_past_min
-            {
-                donations.push((recv_occ, donor_occ,
-                                donor_occ.start - recv_occ.end));
+            let in_past = donor.start < recv.start &&
+                donor.end > excess_past_min;
+            let in_future = donor.start > recv.start &&
+                donor.start < excess_future_max;
+            if in_past && policy.direction.allows_past() {
+                candidates.push((recv_occ, donor_occ, recv.start - donor.end));
+            } else if in_future && policy.direction.allows_future() {
+                candidates.push((recv_occ, donor_occ, donor.start - recv.end));
             }
         }
     }
 
-    donations.sort_unstable_by(|
+    candidates.sort_unstable_by(|
         (a_recv_occ, a_donor_occ, a_dist),
         (b_recv_occ, b_donor_occ, b_dist),
     | {
-        (a_dist, a_recv_occ.start, a_donor_occ.start)
-            .cmp(&(b_dist, b_recv_occ.start, b_donor_occ.start))
+        (a_dist, a_recv_occ.occ.start, a_donor_occ.occ.start)
+            .cmp(&(b_dist, b_recv_occ.occ.start, b_donor_occ.occ.start))
     });
 
-    for (recv_occ, donor_occ, _) in donations {
-        let transfer_amount = transfer_progress(
-            results.get(donor_occ).unwrap(),
-            results.get(recv_occ).unwrap());
-        results.get_mut(donor_occ).unwrap().donated_excess += transfer_amount;
-        results.get_mut(recv_occ).unwrap().received_excess += transfer_amount;
+    let mut donations = Vec::<Donation>::new();
+    // (donor, recipients nearest first)
+    let mut proportional = Vec::<(&StoredOcc, Vec<&StoredOcc>)>::new();
+    for (recv_occ, donor_occ, _) in candidates {
+        match orders[donor_occ.id.as_str()] {
+            TransferOrder::NearestFirst => {
+                let transfer_amount = transfer_progress(
+                    &results[&donor_occ.id], &results[&recv_occ.id]);
+                apply_transfer(&mut results, &mut donations,
+                               donor_occ, recv_occ, transfer_amount);
+            }
+            TransferOrder::Proportional => {
+                match proportional.iter_mut()
+                    .find(|(d, _)| d.id == donor_occ.id)
+                {
+                    Some((_, recv_occs)) => recv_occs.push(recv_occ),
+                    None => proportional.push((donor_occ, vec![recv_occ])),
+                }
+            }
+        }
+    }
+
+    proportional.sort_by_key(|(donor_occ, _)| donor_occ.occ.start);
+    for (donor_occ, recv_occs) in proportional {
+        transfer_proportional(
+            &mut results, &mut donations, donor_occ, &recv_occs);
     }
 
-    results
+    debug_assert!(results.values().all(TaskProgress::transfers_valid));
+
+    (results, donations)
 }
 
 /// Modify `occs` and `configs` to add all occurrences within the total progress
 /// transfer range of the initial `occs`.
+///
+/// `occs` maps item IDs to occurrences by ID, and `configs` maps occurrence IDs
+/// to configs.
 fn expand_occs_for_progress(
     db: &impl Db,
-    occs: &mut HashMap<String, HashSet<Occ>>,
-    configs: &mut HashMap<Occ, ResolvedConfig>,
+    occs: &mut HashMap<String, HashMap<String, StoredOcc>>,
+    configs: &mut HashMap<String, ResolvedConfig>,
 ) -> DbResult<()> {
     let item_ids: Vec<&str> = occs.keys()
         .map(|i| i.as_str()).collect();
 
     let start = occs.iter()
-        .flat_map(|(i, i_occs)| i_occs.iter())
+        .flat_map(|(i, i_occs)| i_occs.values())
         .map(|o| {
-            configs.get(o).map(|c| {
-                o.start - c.resolved_config
+            configs.get(&o.id).map(|c| {
+                o.occ.start - c.resolved_config
                     .task_completion_conf.excess_past_chrono()
             })
         })
         .min()
         .flatten();
     let end = occs.iter()
-        .flat_map(|(i, i_occs)| i_occs.iter())
+        .flat_map(|(i, i_occs)| i_occs.values())
         .map(|o| {
-            configs.get(o).map(|c| {
-                o.end + c.resolved_config
+            configs.get(&o.id).map(|c| {
+                o.occ.end + c.resolved_config
                     .task_completion_conf.excess_future_chrono()
             })
         })
@@ -154,12 +504,14 @@ fn expand_occs_for_progress(
         // update occs
         let retrieved_occs = db.find_occs(
             &item_ids, Some(start), Some(end),
-            SortDirection::Asc, u32::MAX)?;
+            SortDirection::Asc, None, u32::MAX)?;
         let mut new_occs: Vec<(&str, &StoredOcc)> = vec![];
         for (item_id, retrieved_item_occs) in &retrieved_occs {
             let item_occs = occs.entry(item_id.clone()).or_default();
             for retrieved_occ in retrieved_item_occs {
-                if item_occs.insert(retrieved_occ.occ.clone()) {
+                if !item_occs.contains_key(&retrieved_occ.id) {
+                    item_occs.insert(
+                        retrieved_occ.id.clone(), retrieved_occ.clone());
                     new_occs.push((item_id, retrieved_occ));
                 }
             }
@@ -176,26 +528,30 @@ fn expand_occs_for_progress(
             .collect::<Vec<_>>();
         for (occ, config) in
         config::get_occs_configs(db, &new_items_occs[..])? {
-            configs.insert(occ.occ.clone(), config);
+            configs.insert(occ.id.clone(), config);
         }
     }
     Ok(())
 }
 
-/// Get progress details for the given occurrences.
+/// Get progress details for the given occurrences, along with the transfers of
+/// excess progress affecting them.
 ///
-/// `occs` is a slice of `(item_id, occs_and_configs)` pairs.
-pub fn resolve_occs_progress(
+/// `occs` is a slice of `(item_id, occs_and_configs)` pairs.  The result maps
+/// occurrence IDs to progress.  Only transfers to or from the given occurrences
+/// are included.
+pub fn resolve_occs_progress_with_donations(
     db: &impl Db,
-    occs: &[(&str, Vec<(&Occ, &ResolvedConfig)>)],
-) -> DbResult<HashMap<Occ, TaskProgress>> {
-    let mut expanded_occs: HashMap<String, HashSet<Occ>> = HashMap::new();
-    let mut configs: HashMap<Occ, ResolvedConfig> = HashMap::new();
+    occs: &[(&str, Vec<(&StoredOcc, &ResolvedConfig)>)],
+) -> DbResult<(HashMap<String, TaskProgress>, Vec<Donation>)> {
+    let mut expanded_occs: HashMap<String, HashMap<String, StoredOcc>> =
+        HashMap::new();
+    let mut configs: HashMap<String, ResolvedConfig> = HashMap::new();
     for (item_id, occs_configs) in occs {
-        let mut item_occs: HashSet<Occ> = HashSet::new();
+        let mut item_occs: HashMap<String, StoredOcc> = HashMap::new();
         for (occ, config) in occs_configs {
-            item_occs.insert((*occ).clone());
-            configs.insert((*occ).clone(), (*config).clone());
+            item_occs.insert(occ.id.clone(), (*occ).clone());
+            configs.insert(occ.id.clone(), (*config).clone());
         }
         expanded_occs.insert((*item_id).to_owned(), item_occs);
     }
@@ -208,26 +564,47 @@ pub fn resolve_occs_progress(
     expand_occs_for_progress(db, &mut expanded_occs, &mut configs)?;
     expand_occs_for_progress(db, &mut expanded_occs, &mut configs)?;
 
-    let mut occs_progress = HashMap::<Occ, TaskProgress>::new();
+    let mut occs_progress = HashMap::<String, TaskProgress>::new();
+    let mut occs_donations = Vec::<Donation>::new();
     for (item_id, _) in occs {
-        let item_occs_configs = expanded_occs.get(item_id.to_owned()).iter()
-            .flat_map(|item_occs| item_occs.iter())
-            .flat_map(|occ| configs.get(occ).map(|config| (occ, config)))
+        let item_occs_configs = expanded_occs.get(*item_id).iter()
+            .flat_map(|item_occs| item_occs.values())
+            .flat_map(|occ| configs.get(&occ.id).map(|config| (occ, config)))
             .collect::<Vec<_>>();
-        occs_progress.extend(
-            resolve_occs_progress_using(&item_occs_configs[..]));
+        let (item_progress, item_donations) =
+            resolve_occs_progress_using(&item_occs_configs[..]);
+        occs_progress.extend(item_progress);
+        occs_donations.extend(item_donations);
     }
 
     // only return the requested occs - progress may be incorrect for others
-    let mut result = HashMap::<Occ, TaskProgress>::new();
-    for (item_id, occs_configs) in occs {
-        for (occ, config) in occs_configs {
-            if let Some(progress) = occs_progress.remove(occ) {
-                result.insert((*occ).clone(), progress);
-            }
+    let requested_ids = occs.iter()
+        .flat_map(|(item_id, occs_configs)| occs_configs.iter())
+        .map(|(occ, config)| occ.id.as_str())
+        .collect::<HashSet<_>>();
+    let mut result = HashMap::<String, TaskProgress>::new();
+    for occ_id in &requested_ids {
+        if let Some(progress) = occs_progress.remove(*occ_id) {
+            result.insert((*occ_id).to_owned(), progress);
         }
     }
-    Ok(result)
+    occs_donations.retain(|d| {
+        requested_ids.contains(d.donor_occ_id.as_str()) ||
+            requested_ids.contains(d.recipient_occ_id.as_str())
+    });
+    Ok((result, occs_donations))
+}
+
+/// Get progress details for the given occurrences.
+///
+/// `occs` is a slice of `(item_id, occs_and_configs)` pairs.  The result maps
+/// occurrence IDs to progress.
+pub fn resolve_occs_progress(
+    db: &impl Db,
+    occs: &[(&str, Vec<(&StoredOcc, &ResolvedConfig)>)],
+) -> DbResult<HashMap<String, TaskProgress>> {
+    resolve_occs_progress_with_donations(db, occs)
+        .map(|(progress, donations)| progress)
 }
 
 /// Get progress details for `occ`.
@@ -237,7 +614,7 @@ pub fn resolve_occs_progress(
 pub fn resolve_occ_progress(
     db: &impl Db,
     item_id: &str,
-    occ: &Occ,
+    occ: &StoredOcc,
     config: &ResolvedConfig,
 ) -> DbResult<TaskProgress> {
     let results = resolve_occs_progress(db, &[
@@ -248,3 +625,561 @@ pub fn resolve_occ_progress(
         .next()
         .unwrap_or(Default::default()))
 }
+
+/// Get progress details for occurrences of task items, fetching the
+/// occurrences' configs.
+///
+/// `occs` is a slice of `(item, occ)` pairs, and occurrences of events are
+/// ignored.  The result maps occurrence IDs to progress.
+#[tracing::instrument(level = "debug", skip_all, fields(occs = occs.len()))]
+pub fn resolve_item_occs_progress(
+    db: &impl Db,
+    occs: &[(&StoredItem, &StoredOcc)],
+) -> DbResult<HashMap<String, TaskProgress>> {
+    let task_occs = occs.iter()
+        .filter(|(item, occ)| item.item.type_ != ItemType::Event)
+        .copied()
+        .collect::<Vec<_>>();
+    let mut configs: HashMap<&str, ResolvedConfig> =
+        config::get_occs_configs(db, &task_occs)?
+            .into_iter()
+            .map(|(occ, config)| (occ.id.as_str(), config))
+            .collect();
+    for (item, occ) in &task_occs {
+        configs.entry(&occ.id).or_insert_with(|| default_occ_config(occ));
+    }
+
+    let mut occs_by_item =
+        HashMap::<&str, Vec<(&StoredOcc, &ResolvedConfig)>>::new();
+    for (item, occ) in &task_occs {
+        if let Some(config) = configs.get(occ.id.as_str()) {
+            occs_by_item.entry(&item.id).or_default().push((occ, config));
+        }
+    }
+    let occs_by_item = occs_by_item.into_iter().collect::<Vec<_>>();
+    resolve_occs_progress(db, &occs_by_item)
+}
+
+/// Config to use for an occurrence with no stored config.
+fn default_occ_config(occ: &StoredOcc) -> ResolvedConfig {
+    ResolvedConfig {
+        id: ConfigId::Occ { id: occ.id.clone() },
+        scope_config: Config::default(),
+        resolved_config: Config::default(),
+        parent: Box::new(None),
+    }
+}
+
+/// Occurrences and configs fetched up front, for resolving progress for many
+/// occurrences without further database access.
+///
+/// See [`fetch_progress_window`].
+#[derive(Clone, Debug)]
+pub struct ProgressWindow {
+    start: OccDate,
+    end: OccDate,
+    /// Occurrences with configs, by item ID.
+    occs: HashMap<String, Vec<(StoredOcc, ResolvedConfig)>>,
+}
+
+impl ProgressWindow {
+    /// Resolve progress for all occurrences in the window's time range.
+    ///
+    /// Returns progress by occurrence ID, and all transfers to or from those
+    /// occurrences.
+    pub fn resolve(&self) -> (HashMap<String, TaskProgress>, Vec<Donation>) {
+        let mut progress = HashMap::<String, TaskProgress>::new();
+        let mut donations = Vec::<Donation>::new();
+        for item_occs in self.occs.values() {
+            let occs_configs = item_occs.iter()
+                .map(|(occ, config)| (occ, config))
+                .collect::<Vec<_>>();
+            let (item_progress, item_donations) =
+                resolve_occs_progress_using(&occs_configs);
+
+            let in_range = item_occs.iter()
+                .map(|(occ, _)| occ)
+                .filter(|occ| occ.occ.end > self.start &&
+                              occ.occ.start < self.end)
+                .map(|occ| occ.id.as_str())
+                .collect::<HashSet<_>>();
+            progress.extend(item_progress.into_iter()
+                .filter(|(id, _)| in_range.contains(id.as_str())));
+            donations.extend(item_donations.into_iter()
+                .filter(|d| {
+                    in_range.contains(d.donor_occ_id.as_str()) ||
+                        in_range.contains(d.recipient_occ_id.as_str())
+                }));
+        }
+        (progress, donations)
+    }
+
+    /// Occurrences in the window's time range, by item ID.
+    pub fn occs(&self) -> impl Iterator<Item = (&str, &StoredOcc)> {
+        self.occs.iter()
+            .flat_map(|(item_id, item_occs)| {
+                item_occs.iter().map(move |(occ, _)| (item_id.as_str(), occ))
+            })
+            .filter(|(_, occ)| occ.occ.end > self.start &&
+                               occ.occ.start < self.end)
+    }
+}
+
+/// Fetch everything needed to resolve progress for occurrences of `items`
+/// overlapping the time range from `start` to `end`.
+///
+/// This uses a fixed number of database queries, regardless of the number of
+/// items and occurrences.  The time range is widened to include occurrences
+/// which may transfer progress to or from occurrences in the range, based on
+/// the items' configs; config applied to individual occurrences does not widen
+/// the range further.
+pub fn fetch_progress_window(
+    db: &impl Db,
+    items: &[&StoredItem],
+    start: OccDate,
+    end: OccDate,
+) -> DbResult<ProgressWindow> {
+    let configs = config::get_items_configs(db, items)?;
+    let margin = max_transfer_margin(configs.iter().map(|(_, c)| c));
+    fetch_progress_window_with_margin(db, items, start, end, margin)
+}
+
+/// Get the furthest that progress may be transferred between occurrences, for
+/// items with `configs`.
+fn max_transfer_margin<'c>(
+    configs: impl Iterator<Item = &'c ResolvedConfig>,
+) -> chrono::TimeDelta {
+    configs
+        .map(|config| {
+            let cmpl_cfg = &config.resolved_config.task_completion_conf;
+            cmpl_cfg.excess_past_chrono() + cmpl_cfg.excess_future_chrono()
+        })
+        .max()
+        .unwrap_or_default()
+}
+
+/// Like [`fetch_progress_window`], but with the range widened by `margin`
+/// already determined from the items' configs.
+fn fetch_progress_window_with_margin(
+    db: &impl Db,
+    items: &[&StoredItem],
+    start: OccDate,
+    end: OccDate,
+    margin: chrono::TimeDelta,
+) -> DbResult<ProgressWindow> {
+    let mut window = ProgressWindow { start, end, occs: HashMap::new() };
+    if items.is_empty() {
+        return Ok(window)
+    }
+
+    // transfers within the margin may depend on occurrences a further margin
+    // away
+    let margin = margin + margin;
+
+    let item_ids = items.iter().map(|i| i.id.as_str()).collect::<Vec<_>>();
+    let occs = db.find_occs(
+        &item_ids, Some(start - margin), Some(end + margin),
+        SortDirection::Asc, None, u32::MAX)?;
+    let items_by_id = items.iter()
+        .map(|item| (item.id.as_str(), *item))
+        .collect::<HashMap<_, _>>();
+    let items_occs = occs.iter()
+        .flat_map(|(item_id, item_occs)| {
+            let item = items_by_id.get(item_id.as_str());
+            item_occs.iter().flat_map(move |occ| item.map(|i| (*i, occ)))
+        })
+        .collect::<Vec<_>>();
+    let configs: HashMap<&str, ResolvedConfig> =
+        config::get_occs_configs(db, &items_occs)?
+            .into_iter()
+            .map(|(occ, config)| (occ.id.as_str(), config))
+            .collect();
+
+    for (item, occ) in items_occs {
+        let config = configs.get(occ.id.as_str())
+            .cloned()
+            .unwrap_or_else(|| default_occ_config(occ));
+        window.occs.entry(item.id.clone())
+            .or_default()
+            .push((occ.clone(), config));
+    }
+    Ok(window)
+}
+
+/// Get a summary of progress for all active task items in a `category`, over
+/// occurrences overlapping the time range from `start` to `end`.
+pub fn resolve_category_progress(
+    db: &impl Db,
+    category: &str,
+    start: OccDate,
+    end: OccDate,
+) -> DbResult<ProgressSummary> {
+    let filter = config::ItemFilter {
+        active: Some(true),
+        category: Some(category),
+        ..Default::default()
+    };
+    let items = config::get_items_with_resolved(db, &filter)?
+        .into_iter()
+        .filter(|(item, _)| item.item.type_ != ItemType::Event)
+        .collect::<Vec<_>>();
+    let item_refs = items.iter().map(|(item, _)| item).collect::<Vec<_>>();
+    let margin = max_transfer_margin(
+        items.iter().flat_map(|(_, config)| config));
+    let window = fetch_progress_window_with_margin(
+        db, &item_refs, start, end, margin)?;
+    let (progress, _) = window.resolve();
+
+    let mut summary = ProgressSummary::default();
+    for (_, occ) in window.occs().filter(|(_, occ)| occ.occ.active) {
+        if let Some(occ_progress) = progress.get(&occ.id) {
+            summary.add(occ_progress);
+        }
+    }
+    Ok(summary)
+}
+
+/// How an occurrence's progress compares to its target, in a [`Forecast`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum ForecastStatus {
+    /// The target completion amount has been reached.  Not used for
+    /// [`TargetKind::AtMost`] targets, which can't be met until the occurrence
+    /// ends.
+    Complete,
+    /// The target will be met by the end of the occurrence if progress
+    /// continues at the same pace.
+    OnTrack,
+    /// The target will not be met by the end of the occurrence unless the pace
+    /// of progress changes.  For [`TargetKind::AtMost`] targets, this includes
+    /// when the target has already been exceeded.
+    Behind,
+}
+
+/// Estimate of whether an occurrence will be completed on time, as returned by
+/// [`forecast`].
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Forecast {
+    pub occ: TrackedOcc,
+    pub progress: TaskProgress,
+    /// Effective progress expected by the end of the occurrence, if progress
+    /// continues at the pace so far.
+    pub projected_progress: u64,
+    /// Progress needed per day for the rest of the occurrence to reach the
+    /// target completion amount.
+    ///
+    /// This is `None` when the target has been reached, when the occurrence has
+    /// no time remaining, or for [`TargetKind::AtMost`] targets.
+    pub needed_per_day: Option<f64>,
+    pub status: ForecastStatus,
+}
+
+/// Build a forecast for `occ` from its resolved `progress`, as of `as_of`.
+fn build_forecast(occ: TrackedOcc, progress: TaskProgress, as_of: OccDate)
+-> Forecast {
+    let duration = (occ.occ.end - occ.occ.start).num_seconds().max(0);
+    let elapsed = (as_of - occ.occ.start).num_seconds().clamp(0, duration);
+    let remaining = duration - elapsed;
+    let effective = u64::from(progress.effective_progress());
+
+    let projected_progress = if elapsed == 0 {
+        effective
+    } else {
+        let projected = u128::from(effective) * duration as u128 /
+            elapsed as u128;
+        u64::try_from(projected).unwrap_or(u64::MAX)
+    };
+    let total = u64::from(progress.total);
+    let needed = match progress.target_kind {
+        TargetKind::AtLeast => total.saturating_sub(effective),
+        TargetKind::AtMost => 0,
+    };
+    let needed_per_day = if needed == 0 || remaining == 0 {
+        None
+    } else {
+        Some(needed as f64 / (remaining as f64 / DAY_SECONDS))
+    };
+    let status = match progress.target_kind {
+        TargetKind::AtLeast if progress.is_complete() =>
+            ForecastStatus::Complete,
+        TargetKind::AtLeast if projected_progress >= total =>
+            ForecastStatus::OnTrack,
+        TargetKind::AtMost if projected_progress <= total =>
+            ForecastStatus::OnTrack,
+        _ => ForecastStatus::Behind,
+    };
+
+    Forecast {
+        occ,
+        progress,
+        projected_progress,
+        needed_per_day,
+        status,
+    }
+}
+
+/// Estimate whether the current occurrence of an item will be completed on
+/// time, based on the pace of progress so far.
+///
+/// `as_of` is the date to forecast from, which determines the item's current
+/// occurrence (see
+/// [`preview_items_current_occ`](super::preview_items_current_occ)).  Pace is
+/// measured as effective progress over the time elapsed since the occurrence
+/// started.  This doesn't change the database, so the current occurrence may
+/// not have been generated yet.
+///
+/// Returns `None` if the item doesn't exist, is an event, or has no current
+/// occurrence.
+pub fn forecast(db: &impl Db, item_id: &str, as_of: OccDate)
+-> DbResult<Option<Forecast>> {
+    let Some(item) = db.get_items(&[item_id])?.pop() else {
+        return Ok(None)
+    };
+    if item.item.type_ == ItemType::Event {
+        return Ok(None)
+    }
+    let current = super::preview_items_current_occ(db, as_of, &[&item])?
+        .pop()
+        .and_then(|preview| preview.current);
+    let Some(current) = current else {
+        return Ok(None)
+    };
+    let progress = match &current.id {
+        Some(id) => {
+            let occ = StoredOcc { id: id.clone(), occ: current.occ.clone() };
+            item_occ_progress(db, &item, &occ)?
+        }
+        None => new_occ_progress(db, &item)?,
+    };
+    let occ = TrackedOcc { id: current.id, occ: current.occ };
+    Ok(Some(build_forecast(occ, progress, as_of)))
+}
+
+/// Get the progress of an occurrence of a task item which hasn't been stored
+/// yet (see [`preview_items_current_occ`](super::preview_items_current_occ)),
+/// so has no progress or config of its own.  Progress transferred from other
+/// occurrences isn't included.
+pub fn new_occ_progress(db: &impl Db, item: &StoredItem)
+-> DbResult<TaskProgress> {
+    let Some(config) = config::get_item_config(db, item)? else {
+        return Ok(TaskProgress::default())
+    };
+    let cmpl_cfg = &config.resolved_config.task_completion_conf;
+    Ok(TaskProgress {
+        total: cmpl_cfg.total.unwrap_or(1),
+        target_kind: cmpl_cfg.target_kind.unwrap_or_default(),
+        ..Default::default()
+    })
+}
+
+/// Get the progress of an item's occurrence, after transferring progress
+/// between occurrences.
+fn item_occ_progress(db: &impl Db, item: &StoredItem, occ: &StoredOcc)
+-> DbResult<TaskProgress> {
+    Ok(resolve_item_occs_progress(db, &[(item, occ)])?
+        .remove(&occ.id)
+        .unwrap_or_default())
+}
+
+/// Add progress to the current occurrence of a task item at `date`, generating
+/// the occurrence if necessary (see
+/// [`get_item_current_occ`](super::get_item_current_occ)).
+///
+/// `amount` defaults to the amount needed to complete the occurrence (see
+/// [`TaskProgress::remaining`]).  No progress entry is recorded if the amount
+/// is `0`; otherwise, the entry has the optional `note`.
+///
+/// Returns the updated occurrence with its progress.
+pub fn add_current_progress(
+    db: &mut impl Db,
+    item: &StoredItem,
+    amount: Option<i64>,
+    note: Option<&str>,
+    date: OccDate,
+) -> Result<(StoredOcc, TaskProgress), ProgressError> {
+    if item.item.type_ == ItemType::Event {
+        return Err(ProgressError::NotTracked)
+    }
+    let occ = super::get_item_current_occ(db, date, item)
+        .map_err(ProgressError::Db)?
+        .ok_or(ProgressError::NoCurrentOcc)?;
+    let amount = match amount {
+        Some(amount) => amount,
+        None => i64::from(item_occ_progress(db, item, &occ)
+            .map_err(ProgressError::Db)?
+            .remaining()),
+    };
+    let occ = if amount == 0 {
+        occ
+    } else {
+        dbutil::add_progress(db, &occ.id, amount, note, date)
+            .map_err(ProgressError::Db)?
+    };
+    let progress = item_occ_progress(db, item, &occ)
+        .map_err(ProgressError::Db)?;
+    Ok((occ, progress))
+}
+
+/// Parse a progress amount, which may be followed by the item's `unit` (see
+/// [`TaskCompletionConfig::unit`](crate::types::TaskCompletionConfig::unit)).
+/// Units are compared ignoring ASCII case.
+pub fn parse_amount(value: &str, unit: Option<&str>)
+-> Result<i64, ProgressError> {
+    let num_len = value.find(|c: char| {
+        !(c.is_ascii_digit() || c == '-' || c == '+' || c == '.')
+    }).unwrap_or(value.len());
+    let (num, value_unit) = value.split_at(num_len);
+    let value_unit = value_unit.trim();
+    if num.is_empty() {
+        return Err(ProgressError::InvalidAmount { value: value.to_owned() });
+    }
+
+    if !value_unit.is_empty() {
+        match unit {
+            Some(unit) if unit.eq_ignore_ascii_case(value_unit) => (),
+            Some(unit) => {
+                return Err(ProgressError::WrongUnit {
+                    unit: value_unit.to_owned(),
+                    expected: unit.to_owned(),
+                });
+            }
+            None => {
+                return Err(ProgressError::UnexpectedUnit {
+                    unit: value_unit.to_owned(),
+                });
+            }
+        }
+    }
+    num.parse().map_err(|e| {
+        if num.parse::<f64>().is_ok() {
+            ProgressError::FractionalAmount { value: num.to_owned() }
+        } else {
+            ProgressError::InvalidAmount { value: value.to_owned() }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use chrono::{TimeDelta, TimeZone, Utc};
+    use crate::db::{ConfigId, StoredOcc};
+    use crate::types::{Config, Occ, TargetKind, TaskCompletionConfig,
+                       TransferDirection, TransferOrder, TransferPolicy};
+    use super::{resolve_occs_progress_using, Donation, ResolvedConfig,
+                TaskProgress};
+
+    /// Linear congruential generator, so generated cases are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: u32) -> u32 {
+            self.0 = self.0.wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((self.0 >> 33) % u64::from(n)) as u32
+        }
+    }
+
+    /// Generate between 1 and 5 daily occurrences with random progress,
+    /// targets, transfer windows and policies.
+    fn gen_occs(rng: &mut Rng) -> Vec<(StoredOcc, ResolvedConfig)> {
+        let directions = [TransferDirection::Disabled,
+                          TransferDirection::PastOnly,
+                          TransferDirection::FutureOnly,
+                          TransferDirection::Both];
+        let orders = [TransferOrder::NearestFirst, TransferOrder::Proportional];
+        let mut start = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        (0..rng.below(5) + 1)
+            .map(|i| {
+                start += TimeDelta::days(i64::from(rng.below(3)));
+                let end = start + TimeDelta::days(1);
+                let occ = StoredOcc {
+                    id: i.to_string(),
+                    occ: Occ {
+                        active: true,
+                        start,
+                        end,
+                        task_completion_progress: rng.below(6),
+                        snoozed_until: None,
+                    },
+                };
+                start = end;
+                let window = |rng: &mut Rng| Some(Duration::from_secs(
+                    u64::from(rng.below(4)) * 86400));
+                let config = Config {
+                    task_completion_conf: TaskCompletionConfig {
+                        total: Some(rng.below(4)),
+                        excess_past: window(rng),
+                        excess_future: window(rng),
+                        transfer_policy: Some(TransferPolicy {
+                            direction: directions[rng.below(4) as usize],
+                            order: orders[rng.below(2) as usize],
+                        }),
+                        target_kind: Some(if rng.below(5) == 0 {
+                            TargetKind::AtMost
+                        } else {
+                            TargetKind::AtLeast
+                        }),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                };
+                (occ, ResolvedConfig {
+                    id: ConfigId::All,
+                    scope_config: config.clone(),
+                    resolved_config: config,
+                    parent: Box::new(None),
+                })
+            })
+            .collect()
+    }
+
+    /// Sum donations by the occurrence ID picked out by `key`.
+    fn sum_by(donations: &[Donation], key: fn(&Donation) -> &str)
+    -> HashMap<&str, u32> {
+        let mut sums = HashMap::new();
+        for donation in donations {
+            *sums.entry(key(donation)).or_default() += donation.amount;
+        }
+        sums
+    }
+
+    #[test]
+    fn donated_never_exceeds_excess() {
+        let mut rng = Rng(1);
+        let mut transfers = 0;
+        for _ in 0..20000 {
+            let occs = gen_occs(&mut rng);
+            let refs = occs.iter()
+                .map(|(occ, config)| (occ, config))
+                .collect::<Vec<_>>();
+            let (results, donations) = resolve_occs_progress_using(&refs);
+            let donated = sum_by(&donations, |d| &d.donor_occ_id);
+            let received = sum_by(&donations, |d| &d.recipient_occ_id);
+
+            for (occ, _) in &occs {
+                let progress: &TaskProgress = &results[&occ.id];
+                assert!(progress.transfers_valid(), "{occs:?}: {progress:?}");
+                assert_eq!(donated.get(occ.id.as_str()).copied()
+                               .unwrap_or(0),
+                           progress.donated_excess, "{occs:?}");
+                assert_eq!(received.get(occ.id.as_str()).copied()
+                               .unwrap_or(0),
+                           progress.received_excess, "{occs:?}");
+                assert!(progress.donated_excess == 0
+                            || progress.received_excess == 0,
+                        "{occs:?}: {progress:?}");
+                if progress.target_kind == TargetKind::AtMost {
+                    assert_eq!(progress.donated_excess, 0, "{occs:?}");
+                    assert_eq!(progress.received_excess, 0, "{occs:?}");
+                }
+            }
+            assert!(donations.iter().all(|d| d.amount > 0), "{donations:?}");
+            transfers += donations.len();
+        }
+        // make sure the generated cases actually transfer progress
+        assert!(transfers > 1000, "only {transfers} transfers");
+    }
+}
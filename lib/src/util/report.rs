@@ -0,0 +1,575 @@
+//! Completion reports for reading or archiving, built from
+//! [statistics](super::stats), [heatmaps](heatmap) of daily progress and
+//! [weekly reviews](weekly_review).
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+use chrono::{FixedOffset, NaiveDate, NaiveTime, TimeDelta, Weekday};
+use serde::Serialize;
+use crate::db::{Db, DbResult, SortDirection, StoredItem, StoredOcc};
+use crate::db::util as dbutil;
+use crate::types::{ItemType, OccDate};
+use super::config;
+use super::progress::{self, TaskProgress};
+use super::stats::{self, CompletionStats};
+
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Columns of statistics tables, following the name column.
+const STATS_HEADER: [&str; 7] = [
+    "Occurrences", "Completed", "Missed", "Completion rate", "Average progress",
+    "Current streak", "Longest streak",
+];
+
+/// Supported report formats.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, strum::AsRefStr,
+         strum::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Markdown,
+    /// A standalone HTML page.
+    Html,
+}
+
+impl Format {
+    /// Media type of reports in this format.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Markdown => "text/markdown",
+            Format::Html => "text/html",
+        }
+    }
+}
+
+/// Completion statistics for a time range, ready to be written out.
+#[derive(Clone, Debug)]
+pub struct Report {
+    /// Missing for all time.
+    pub start: Option<OccDate>,
+    pub end: OccDate,
+    /// Statistics for all task occurrences in the range.
+    pub overall: CompletionStats,
+    /// Statistics for each item with task occurrences in the range, sorted by
+    /// name.
+    pub items: Vec<(StoredItem, CompletionStats)>,
+    /// Statistics for each category, sorted by name.  Items without a
+    /// category are not included.
+    pub categories: Vec<(String, CompletionStats)>,
+}
+
+/// Table of statistics in a [`Report`].
+struct Section {
+    heading: &'static str,
+    header: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+/// Format a proportion as a percentage, or `-` if it's missing.
+fn percent(value: Option<f64>) -> String {
+    value.map_or("-".to_owned(), |value| format!("{:.0}%", value * 100.0))
+}
+
+/// Row of a statistics table, starting with `name`.
+fn stats_row(name: &str, stats: &CompletionStats) -> Vec<String> {
+    vec![
+        name.to_owned(),
+        stats.occs.to_string(),
+        stats.completed.to_string(),
+        stats.missed.to_string(),
+        percent(stats.completion_rate()),
+        percent(stats.average_progress()),
+        stats.current_streak.to_string(),
+        stats.longest_streak.to_string(),
+    ]
+}
+
+fn markdown_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+fn markdown_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut table = format!("| {} |\n", header.join(" | "));
+    table.push_str(&format!("|{}\n", "---|".repeat(header.len())));
+    for row in rows {
+        let cells = row.iter()
+            .map(|cell| markdown_escape(cell))
+            .collect::<Vec<_>>();
+        table.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    table
+}
+
+fn html_escape(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut table = "<table>\n<tr>".to_owned();
+    for name in header {
+        table.push_str(&format!("<th>{}</th>", html_escape(name)));
+    }
+    table.push_str("</tr>\n");
+    for row in rows {
+        table.push_str("<tr>");
+        for cell in row {
+            table.push_str(&format!("<td>{}</td>", html_escape(cell)));
+        }
+        table.push_str("</tr>\n");
+    }
+    table.push_str("</table>\n");
+    table
+}
+
+impl Report {
+    /// Describe the time range the report covers.
+    fn range(&self) -> String {
+        let end = self.end.format("%Y-%m-%d");
+        match self.start {
+            Some(start) => format!("{} to {end}", start.format("%Y-%m-%d")),
+            None => format!("all time to {end}"),
+        }
+    }
+
+    fn sections(&self) -> [Section; 3] {
+        let header = |name| {
+            [name].into_iter().chain(STATS_HEADER).collect::<Vec<_>>()
+        };
+        [
+            Section {
+                heading: "Overall",
+                header: header(""),
+                rows: vec![stats_row("All tasks", &self.overall)],
+            },
+            Section {
+                heading: "Categories",
+                header: header("Category"),
+                rows: self.categories.iter()
+                    .map(|(category, stats)| stats_row(category, stats))
+                    .collect(),
+            },
+            Section {
+                heading: "Items",
+                header: header("Item"),
+                rows: self.items.iter()
+                    .map(|(item, stats)| stats_row(&item.item.name, stats))
+                    .collect(),
+            },
+        ]
+    }
+
+    fn markdown(&self) -> String {
+        let mut out = format!("# Completion report: {}\n", self.range());
+        for section in self.sections() {
+            out.push_str(&format!("\n## {}\n\n", section.heading));
+            if section.rows.is_empty() {
+                out.push_str("None.\n");
+            } else {
+                out.push_str(&markdown_table(&section.header, &section.rows));
+            }
+        }
+        out
+    }
+
+    fn html(&self) -> String {
+        let title = html_escape(&format!("Completion report: {}",
+                                         self.range()));
+        let mut out = format!("\
+<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; }}
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid #ccc; padding: 0.25em 0.5em; text-align: left; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+");
+        for section in self.sections() {
+            out.push_str(&format!("<h2>{}</h2>\n", section.heading));
+            if section.rows.is_empty() {
+                out.push_str("<p>None.</p>\n");
+            } else {
+                out.push_str(&html_table(&section.header, &section.rows));
+            }
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    /// Write the report in the given `format`.
+    pub fn write(&self, format: Format) -> String {
+        match format {
+            Format::Markdown => self.markdown(),
+            Format::Html => self.html(),
+        }
+    }
+}
+
+/// Build a report of completion statistics for task occurrences overlapping
+/// the time range from `start` to `end`.  See [`stats::get_stats`] for how
+/// statistics are computed.
+pub fn fetch(
+    db: &impl Db,
+    start: Option<OccDate>,
+    end: OccDate,
+    now: OccDate,
+) -> DbResult<Report> {
+    let stats = stats::get_stats(db, start, Some(end), now)?;
+
+    let mut overall = CompletionStats::default();
+    for item_stats in stats.items.values() {
+        overall.merge(item_stats);
+    }
+    let item_ids = stats.items.keys()
+        .map(|id| id.as_str())
+        .collect::<Vec<_>>();
+    let mut items = db.get_items(&item_ids)?
+        .into_iter()
+        .filter_map(|item| {
+            let item_stats = *stats.items.get(&item.id)?;
+            Some((item, item_stats))
+        })
+        .collect::<Vec<_>>();
+    items.sort_by(|(a, _), (b, _)| a.item.name.cmp(&b.item.name));
+    let mut categories = stats.categories.into_iter().collect::<Vec<_>>();
+    categories.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Ok(Report { start, end, overall, items, categories })
+}
+
+/// Number of intensity levels in a [`Heatmap`], including the level for days
+/// without progress.
+pub const HEATMAP_LEVELS: u8 = 5;
+
+/// Items covered by a [heatmap].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum HeatmapSubject<'a> {
+    /// The item with this ID.
+    Item(&'a str),
+    /// All items in this category, including inactive items.
+    Category(&'a str),
+}
+
+/// Progress for one day of a [`Heatmap`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HeatmapDay {
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = String, format = Date))]
+    pub date: NaiveDate,
+    /// Progress logged on the day, as a proportion of the target completion
+    /// amount of the occurrence it was logged for, summed over occurrences.
+    /// This is never negative.
+    pub completion: f64,
+    /// Intensity from 0 to [`HEATMAP_LEVELS`] - 1, scaling `completion`
+    /// relative to the day with the most completion.  Only days without
+    /// completion have level 0.
+    pub level: u8,
+}
+
+/// Daily progress for tasks over a range of days, as returned by [`heatmap`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Heatmap {
+    /// Every day in the range, in order.
+    pub days: Vec<HeatmapDay>,
+    /// The greatest `completion` of any day.
+    pub max_completion: f64,
+}
+
+/// Scale `completion` to a [`HeatmapDay::level`].
+fn heatmap_level(completion: f64, max_completion: f64) -> u8 {
+    if completion <= 0.0 {
+        return 0;
+    }
+    let max_level = HEATMAP_LEVELS - 1;
+    let level = (completion / max_completion * f64::from(max_level)).ceil();
+    (level as u8).clamp(1, max_level)
+}
+
+/// Build a heatmap of progress logged for the task items covered by `subject`
+/// on each day in `days`.  Events are ignored.
+///
+/// Days start at midnight at the given offset from UTC.  Each progress entry
+/// counts towards the day it was logged, in proportion to the target
+/// completion amount of its occurrence, so that items with different units
+/// can be combined.  Only entries for occurrences overlapping the range are
+/// included.  Entries reducing progress count negatively, and targets which
+/// are a maximum amount are treated the same as other targets.
+pub fn heatmap(
+    db: &impl Db,
+    subject: HeatmapSubject,
+    days: RangeInclusive<NaiveDate>,
+    utc_offset: FixedOffset,
+) -> DbResult<Heatmap> {
+    let (first, last) = days.into_inner();
+    let mut completion: BTreeMap<NaiveDate, f64> = first.iter_days()
+        .take_while(|day| *day <= last)
+        .map(|day| (day, 0.0))
+        .collect();
+
+    let items = match subject {
+        HeatmapSubject::Item(id) => vec![dbutil::get_item(db, id)?],
+        HeatmapSubject::Category(category) => db.find_items(
+            None, Some(category), None, SortDirection::Asc, None, u32::MAX)?,
+    };
+    let items = items.into_iter()
+        .filter(|item| item.item.type_ != ItemType::Event)
+        .collect::<Vec<_>>();
+    if !completion.is_empty() && !items.is_empty() {
+        let day_start = |day: NaiveDate| {
+            (day.and_time(NaiveTime::MIN) - utc_offset).and_utc()
+        };
+        let start = day_start(first);
+        let end = day_start(last.succ_opt().unwrap_or(last));
+        let item_ids = items.iter()
+            .map(|item| item.id.as_str())
+            .collect::<Vec<_>>();
+        let occs_by_item = db.find_occs(
+            &item_ids, Some(start), Some(end), SortDirection::Asc, None,
+            u32::MAX)?;
+        let occs: Vec<(&StoredItem, &StoredOcc)> = items.iter()
+            .flat_map(|item| {
+                occs_by_item.get(&item.id).into_iter()
+                    .flatten()
+                    .map(move |occ| (item, occ))
+            })
+            .collect();
+        let totals: HashMap<&str, u32> = config::get_occs_configs(db, &occs)?
+            .into_iter()
+            .flat_map(|(occ, config)| {
+                let total = config.resolved_config.task_completion_conf.total;
+                total.map(|total| (occ.id.as_str(), total))
+            })
+            .collect();
+
+        let occ_ids = occs.iter()
+            .map(|(item, occ)| occ.id.as_str())
+            .collect::<Vec<_>>();
+        let entries = db.find_progress_entries(
+            &occ_ids, SortDirection::Asc, u32::MAX)?;
+        for (occ_id, occ_entries) in entries {
+            let total = totals.get(occ_id.as_str()).copied().unwrap_or(1);
+            for entry in occ_entries {
+                let day = entry.entry.date.with_timezone(&utc_offset)
+                    .date_naive();
+                if let Some(day_completion) = completion.get_mut(&day) {
+                    *day_completion +=
+                        entry.entry.amount as f64 / f64::from(total.max(1));
+                }
+            }
+        }
+    }
+
+    let max_completion = completion.values().copied().fold(0.0, f64::max);
+    let days = completion.into_iter()
+        .map(|(date, completion)| {
+            let completion = completion.max(0.0);
+            HeatmapDay {
+                date,
+                completion,
+                level: heatmap_level(completion, max_completion),
+            }
+        })
+        .collect();
+    Ok(Heatmap { days, max_completion })
+}
+
+/// Task occurrence included in a [`WeeklyReview`].
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReviewOcc {
+    pub item: StoredItem,
+    pub occ: StoredOcc,
+    pub progress: TaskProgress,
+}
+
+impl ReviewOcc {
+    /// Describe the occurrence in a single line.
+    fn describe(&self) -> String {
+        format!("- {}: due {}, progress {}/{}", self.item.item.name,
+                self.occ.occ.end.format(DATE_FORMAT),
+                self.progress.effective_progress(), self.progress.total)
+    }
+}
+
+/// Change in an item's current streak over the week of a [`WeeklyReview`].
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StreakChange {
+    pub item: StoredItem,
+    /// Current streak at the start of the week.
+    pub before: u32,
+    /// Current streak at the end of the week, or now for the current week.
+    pub after: u32,
+}
+
+/// Summary of task completion over a week, as returned by [`weekly_review`].
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WeeklyReview {
+    /// Monday the week starts on.
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = String, format = Date))]
+    pub week: NaiveDate,
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = String, format = DateTime))]
+    pub start: OccDate,
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = String, format = DateTime))]
+    pub end: OccDate,
+    /// Task occurrences which ended during the week and met their target,
+    /// ordered by end date.
+    pub completed: Vec<ReviewOcc>,
+    /// Task occurrences which ended during the week without meeting their
+    /// target, ordered by end date.
+    pub missed: Vec<ReviewOcc>,
+    /// Items whose current streak changed during the week, sorted by name.
+    pub streaks: Vec<StreakChange>,
+    /// Task occurrences which continue past the end of the week without
+    /// having met their target yet, ordered by end date.
+    pub carryovers: Vec<ReviewOcc>,
+}
+
+impl WeeklyReview {
+    /// Short description of the review, suitable for a message subject.
+    pub fn subject(&self) -> String {
+        format!("Weekly review for {}: {} completed, {} missed",
+                self.week.format("%Y-%m-%d"), self.completed.len(),
+                self.missed.len())
+    }
+
+    /// Full contents of the review, suitable for a message body.
+    pub fn body(&self) -> String {
+        let occs_section = |heading: &str, occs: &[ReviewOcc]| {
+            let lines = occs.iter()
+                .map(ReviewOcc::describe)
+                .collect::<Vec<_>>();
+            format!("{heading}:\n{}", lines.join("\n"))
+        };
+        let mut sections = Vec::new();
+        if !self.completed.is_empty() {
+            sections.push(occs_section("Completed", &self.completed));
+        }
+        if !self.missed.is_empty() {
+            sections.push(occs_section("Missed", &self.missed));
+        }
+        if sections.is_empty() {
+            sections.push("No tasks were due this week.".to_owned());
+        }
+        if !self.streaks.is_empty() {
+            let lines = self.streaks.iter()
+                .map(|change| {
+                    format!("- {}: {} to {}", change.item.item.name,
+                            change.before, change.after)
+                })
+                .collect::<Vec<_>>();
+            sections.push(format!("Streaks:\n{}", lines.join("\n")));
+        }
+        if !self.carryovers.is_empty() {
+            sections.push(occs_section("Carried over", &self.carryovers));
+        }
+        sections.join("\n\n")
+    }
+}
+
+/// Review task completion for the week containing the day `week`.
+///
+/// Weeks start on Monday, and days are in UTC.  For the current week, only
+/// occurrences which ended before `now` count as completed or missed.  Unlike
+/// [completion statistics](stats::get_stats), occurrences are completed
+/// according to their progress after transferring excess progress between
+/// occurrences.
+pub fn weekly_review(db: &impl Db, week: NaiveDate, now: OccDate)
+-> DbResult<WeeklyReview> {
+    let week = week.week(Weekday::Mon).first_day();
+    let start = week.and_time(NaiveTime::MIN).and_utc();
+    let end = start + TimeDelta::weeks(1);
+    let decided_end = end.min(now);
+
+    let items = db.find_items(
+        None, None, Some(start), SortDirection::Asc, None, u32::MAX)?
+        .into_iter()
+        .filter(|item| item.item.type_ != ItemType::Event)
+        .collect::<Vec<_>>();
+    let item_ids = items.iter()
+        .map(|item| item.id.as_str())
+        .collect::<Vec<_>>();
+    let occs_by_item = db.find_occs(
+        &item_ids, Some(start), Some(end), SortDirection::Asc, None,
+        u32::MAX)?;
+    let occs: Vec<(&StoredItem, &StoredOcc)> = items.iter()
+        .flat_map(|item| {
+            occs_by_item.get(&item.id).into_iter()
+                .flatten()
+                .filter(|occ| occ.occ.active)
+                .map(move |occ| (item, occ))
+        })
+        .collect();
+    let mut progress = progress::resolve_item_occs_progress(db, &occs)?;
+
+    let mut completed = Vec::new();
+    let mut missed = Vec::new();
+    let mut carryovers = Vec::new();
+    for (item, occ) in occs {
+        let Some(progress) = progress.remove(&occ.id) else { continue };
+        let occ_end = occ.occ.end;
+        let review_occ = ReviewOcc {
+            item: item.clone(),
+            occ: occ.clone(),
+            progress,
+        };
+        if occ_end > start && occ_end <= decided_end {
+            if progress.is_complete() {
+                completed.push(review_occ);
+            } else {
+                missed.push(review_occ);
+            }
+        } else if occ_end > end && !progress.is_complete() {
+            carryovers.push(review_occ);
+        }
+    }
+    for occs in [&mut completed, &mut missed, &mut carryovers] {
+        occs.sort_by_key(|occ| occ.occ.occ.end);
+    }
+
+    let before = stats::get_stats(db, None, Some(start), start.min(now))?;
+    let after = stats::get_stats(db, None, Some(decided_end), decided_end)?;
+    let streak = |stats: &stats::Stats, id: &str| {
+        stats.items.get(id).map_or(0, |stats| stats.current_streak)
+    };
+    let mut changed_ids = before.items.keys()
+        .chain(after.items.keys())
+        .map(|id| id.as_str())
+        .filter(|id| streak(&before, id) != streak(&after, id))
+        .collect::<Vec<_>>();
+    changed_ids.sort_unstable();
+    changed_ids.dedup();
+    let mut streaks = db.get_items(&changed_ids)?
+        .into_iter()
+        .map(|item| StreakChange {
+            before: streak(&before, &item.id),
+            after: streak(&after, &item.id),
+            item,
+        })
+        .collect::<Vec<_>>();
+    streaks.sort_by(|a, b| a.item.item.name.cmp(&b.item.item.name));
+
+    Ok(WeeklyReview {
+        week,
+        start,
+        end,
+        completed,
+        missed,
+        streaks,
+        carryovers,
+    })
+}
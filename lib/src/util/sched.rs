@@ -2,9 +2,11 @@
 
 use std::cmp::min;
 use std::collections::{BTreeSet, HashSet};
+use std::error::Error;
+use std::fmt;
 use std::iter::Iterator;
 use chrono::{Datelike, NaiveDate, naive};
-use crate::types::{ProgressTaskSched::{self, *}, DayFilter};
+use crate::types::{ProgressTaskSched::{self, *}, DayFilter, Sched};
 
 /// Get the `chrono` year for a date (that is, negative values are BCE).
 fn year_of_date(date: NaiveDate) -> i32 {
@@ -52,13 +54,10 @@ fn with_moy_dom_saturating(date: NaiveDate, moy: chrono::Month, dom: u8)
 /// Return the first date after `date` with day of the week `dow` (including
 /// `date` itself).
 fn forwards_to_dow(date: NaiveDate, dow: chrono::Weekday) -> NaiveDate {
-    let dow_diff = dow.number_from_monday() -
-                   date.weekday().number_from_monday();
-    if dow_diff == 0 {
-        date
-    } else {
-        date + naive::Days::new((dow_diff % 7).into())
-    }
+    // add 7 to avoid underflow when `dow` is earlier in the week
+    let dow_diff = (7 + dow.number_from_monday() -
+                    date.weekday().number_from_monday()) % 7;
+    date + naive::Days::new(dow_diff.into())
 }
 
 /// Return the date which is `months` months after `date`.
@@ -85,7 +84,7 @@ impl DayFilterDaysIter<'_> {
     ///
     /// `start_day` may be included in the results.
     pub fn new(day_filter: &DayFilter, start_day: NaiveDate)
-    -> DayFilterDaysIter {
+    -> DayFilterDaysIter<'_> {
         let dows_days = match &day_filter {
             DayFilter::Dows { days } => {
                 HashSet::from_iter(days.iter().cloned())
@@ -150,7 +149,7 @@ impl Iterator for DayFilterDaysIter<'_> {
 
                 let day = match self.dom_days.range(now.day() as u8 ..).next() {
                     Some(dom) => {
-                        if now.day() == (*dom).into() {
+                        if now.day() == u32::from(*dom) {
                             Some(now)
                         } else {
                             let day = with_dom_saturating(now, *dom);
@@ -235,7 +234,7 @@ impl ProgressTaskPeriodsIter<'_> {
     ///
     /// `start_day` is included in the first result.
     pub fn new(sched: &ProgressTaskSched, start_day: NaiveDate)
-    -> ProgressTaskPeriodsIter {
+    -> ProgressTaskPeriodsIter<'_> {
         ProgressTaskPeriodsIter { sched, day: start_day }
     }
 }
@@ -252,13 +251,9 @@ impl Iterator for ProgressTaskPeriodsIter<'_> {
 
             Weeks { num, start_day: dow } => {
                 let now = self.day;
-                let dow_diff = now.weekday().number_from_monday() -
-                               dow.number_from_monday();
-                let start = if dow_diff == 0 {
-                    now
-                } else {
-                    now - naive::Days::new((dow_diff % 7).into())
-                };
+                let dow_diff = (7 + now.weekday().number_from_monday() -
+                                dow.number_from_monday()) % 7;
+                let start = now - naive::Days::new(dow_diff.into());
                 (start, start + naive::Days::new(7 * u64::from(*num)))
             },
 
@@ -276,7 +271,7 @@ impl Iterator for ProgressTaskPeriodsIter<'_> {
                 };
 
                 let end = add_months(start, (*num).into());
-                if end.day() != (*dom).into() {
+                if end.day() != u32::from(*dom) {
                     let end = with_dom_saturating(end, *dom);
                 }
 
@@ -304,3 +299,274 @@ impl Iterator for ProgressTaskPeriodsIter<'_> {
         Some((start, end))
     }
 }
+
+/// Problem with a schedule, found by [`problems`].  Fields are named as in
+/// [`Sched`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum SchedError {
+    /// An interval between days is `0`.
+    ZeroInterval { field: &'static str },
+    /// A day of the month is outside of `1` to `31`.
+    InvalidDom { field: &'static str, dom: u8 },
+    /// An event's day filter has no days.
+    NoDays,
+    /// A week of the month is outside of `1` to `5`.
+    InvalidWeek { week: u8 },
+    /// An event's day filter has no weeks of the month from `1` to `5`.
+    NoValidWeeks,
+    /// A progress task's period is `0` long.
+    ZeroPeriod,
+    /// A deadline task's duration is `0`.
+    ZeroDuration,
+}
+
+impl fmt::Display for SchedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchedError::ZeroInterval { field } => {
+                write!(f, "{field} is 0, so the schedule never moves on to \
+                           later days")
+            }
+            SchedError::InvalidDom { field, dom } => {
+                write!(f, "{field} is {dom}, but must be from 1 to 31")
+            }
+            SchedError::NoDays => {
+                write!(f, "no days are given, so the event never occurs")
+            }
+            SchedError::InvalidWeek { week } => {
+                write!(f, "week is {week}, but must be from 1 to 5, so it \
+                           never matches")
+            }
+            SchedError::NoValidWeeks => {
+                write!(f, "no valid weeks are given, so the event never \
+                           occurs")
+            }
+            SchedError::ZeroPeriod => {
+                write!(f, "num is 0, so occurrences have no duration")
+            }
+            SchedError::ZeroDuration => {
+                write!(f, "duration is 0, so every occurrence ends as soon as \
+                           it starts")
+            }
+        }
+    }
+}
+
+impl Error for SchedError {}
+
+/// Check a day of the month is from `1` to `31`.
+fn dom_problem(field: &'static str, dom: u8) -> Option<SchedError> {
+    (dom == 0 || dom > 31).then_some(SchedError::InvalidDom { field, dom })
+}
+
+/// Check an interval isn't `0`, which would stop a schedule from moving on to
+/// later days.
+fn interval_problem(field: &'static str, interval: u32)
+-> Option<SchedError> {
+    (interval == 0).then_some(SchedError::ZeroInterval { field })
+}
+
+/// Find problems with a [`DayFilter`] (see [`problems`]).
+fn day_filter_problems(day_filter: &DayFilter) -> Vec<SchedError> {
+    match day_filter {
+        DayFilter::Day { days_apart } => {
+            interval_problem("days_apart", *days_apart).into_iter().collect()
+        }
+        DayFilter::Dow { day, weeks_apart } => {
+            interval_problem("weeks_apart", *weeks_apart).into_iter().collect()
+        }
+        DayFilter::Dows { days } => {
+            if days.is_empty() {
+                vec![SchedError::NoDays]
+            } else {
+                vec![]
+            }
+        }
+        DayFilter::Dom { days, months_apart } => {
+            let empty = days.is_empty().then_some(SchedError::NoDays);
+            empty.into_iter()
+                .chain(days.iter().filter_map(|dom| dom_problem("day", *dom)))
+                .chain(interval_problem("months_apart", *months_apart))
+                .collect()
+        }
+        DayFilter::Wom { dow, weeks, months_apart } => {
+            let invalid = weeks.iter()
+                .filter(|week| **week == 0 || **week > 5)
+                .map(|week| SchedError::InvalidWeek { week: *week });
+            let empty = weeks.iter().all(|week| *week == 0 || *week > 5)
+                .then_some(SchedError::NoValidWeeks);
+            invalid.chain(empty).collect()
+        }
+        DayFilter::Doy { dom, month, years_apart } => {
+            dom_problem("dom", *dom).into_iter()
+                .chain(interval_problem("years_apart", *years_apart))
+                .collect()
+        }
+        DayFilter::Date { dom, month, year } => {
+            dom_problem("dom", *dom).into_iter().collect()
+        }
+    }
+}
+
+/// Find problems with a schedule which would stop it from producing sensible
+/// occurrences: intervals of `0`, filters which match no days, and days which
+/// don't exist.
+///
+/// Returns each problem found.
+pub fn problems(sched: &Sched) -> Vec<SchedError> {
+    match sched {
+        Sched::Event(sched) => day_filter_problems(&sched.days),
+        Sched::ProgressTask(sched) => {
+            let (num, dom) = match sched {
+                Days { num } | Weeks { num, .. } => (num, None),
+                Months { num, start_day } => {
+                    (num, dom_problem("start_day", *start_day))
+                }
+                Years { num, start_dom, .. } => {
+                    (num, dom_problem("start_dom", *start_dom))
+                }
+            };
+            let num = (*num == 0).then_some(SchedError::ZeroPeriod);
+            num.into_iter().chain(dom).collect()
+        }
+        Sched::DeadlineTask(sched) => {
+            if sched.duration.is_zero() {
+                vec![SchedError::ZeroDuration]
+            } else {
+                vec![]
+            }
+        }
+    }
+}
+
+/// Get the start days of the first `count` occurrences of a schedule, starting
+/// from `from`.  For progress tasks, the first period is the one containing
+/// `from`.
+///
+/// Deadline tasks have no fixed days, so nothing is returned for them.  Nothing
+/// is returned for schedules with [`problems`] either.
+pub fn next_days(sched: &Sched, from: NaiveDate, count: usize)
+-> Vec<NaiveDate> {
+    if !problems(sched).is_empty() {
+        return vec![]
+    }
+    match sched {
+        Sched::Event(sched) => {
+            DayFilterDaysIter::new(&sched.days, sched.initial_day)
+                .skip_while(|day| *day < from)
+                .take(count)
+                .collect()
+        }
+        Sched::ProgressTask(sched) => {
+            ProgressTaskPeriodsIter::new(sched, from)
+                .map(|(start, end)| start)
+                .take(count)
+                .collect()
+        }
+        Sched::DeadlineTask(_) => vec![],
+    }
+}
+
+/// `1st`, `2nd`, `3rd`, and so on.
+fn ordinal(n: u8) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{n}{suffix}")
+}
+
+/// `every day`, `every 2 days`, and so on.
+fn every(num: u32, unit: &str) -> String {
+    if num == 1 {
+        format!("every {unit}")
+    } else {
+        format!("every {num} {unit}s")
+    }
+}
+
+/// Join descriptions of several values into a list, like `1st, 2nd and 3rd`.
+fn join_list(values: impl IntoIterator<Item = String>) -> String {
+    let mut values = values.into_iter().collect::<Vec<_>>();
+    match values.pop() {
+        None => String::new(),
+        Some(last) if values.is_empty() => last,
+        Some(last) => format!("{} and {last}", values.join(", ")),
+    }
+}
+
+/// Describe a length of time in the largest whole unit, like `3 days`.
+fn describe_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    let (num, unit) = [(7 * 24 * 60 * 60, "week"), (24 * 60 * 60, "day"),
+                       (60 * 60, "hour"), (60, "minute")]
+        .into_iter()
+        .find(|(unit_secs, _)| secs > 0 && secs.is_multiple_of(*unit_secs))
+        .map(|(unit_secs, unit)| (secs / unit_secs, unit))
+        .unwrap_or((secs, "second"));
+    if num == 1 { format!("1 {unit}") } else { format!("{num} {unit}s") }
+}
+
+fn describe_day_filter(day_filter: &DayFilter) -> String {
+    match day_filter {
+        DayFilter::Day { days_apart } => every(*days_apart, "day"),
+        DayFilter::Dow { day, weeks_apart } => {
+            format!("{} on {day}", every(*weeks_apart, "week"))
+        }
+        DayFilter::Dows { days } => {
+            format!("every {}", join_list(days.iter().map(|d| d.to_string())))
+        }
+        DayFilter::Dom { days, months_apart } => {
+            format!("on the {} of {}",
+                    join_list(days.iter().map(|d| ordinal(*d))),
+                    every(*months_apart, "month"))
+        }
+        DayFilter::Wom { dow, weeks, months_apart } => {
+            format!("on the {} {dow} of {}",
+                    join_list(weeks.iter().map(|w| ordinal(*w))),
+                    every(*months_apart, "month"))
+        }
+        DayFilter::Doy { dom, month, years_apart } => {
+            format!("on {} {} {}", ordinal(*dom), month.name(),
+                    every(*years_apart, "year"))
+        }
+        DayFilter::Date { dom, month, year } => {
+            format!("on {} {} {year}", ordinal(*dom), month.name())
+        }
+    }
+}
+
+/// Describe a schedule in English, for example `every 2 weeks on Tue` or
+/// `every month, starting on the 1st`.
+pub fn describe(sched: &Sched) -> String {
+    match sched {
+        Sched::Event(sched) => {
+            let days = describe_day_filter(&sched.days);
+            match sched.time {
+                Some(time) => format!("{days} at {}", time.format("%H:%M")),
+                None => days,
+            }
+        }
+        Sched::ProgressTask(sched) => match sched {
+            Days { num } => every((*num).into(), "day"),
+            Weeks { num, start_day } => {
+                format!("{}, starting on {start_day}",
+                        every((*num).into(), "week"))
+            }
+            Months { num, start_day } => {
+                format!("{}, starting on the {}",
+                        every((*num).into(), "month"), ordinal(*start_day))
+            }
+            Years { num, start_month, start_dom } => {
+                format!("{}, starting on {} {}", every((*num).into(), "year"),
+                        ordinal(*start_dom), start_month.name())
+            }
+        },
+        Sched::DeadlineTask(sched) => {
+            format!("{} after completion", describe_duration(sched.duration))
+        }
+    }
+}
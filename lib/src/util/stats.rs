@@ -0,0 +1,536 @@
+//! Completion statistics for tasks.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+use chrono::{Datelike, Months, NaiveDate, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use crate::db::{Db, DbResult, SortDirection, StoredItem};
+use crate::types::{ItemType, OccDate, TargetKind};
+use super::{config, progress};
+use super::export::csv_row;
+
+/// Columns of CSV statistics exports.
+const CSV_HEADER: [&str; 13] = [
+    "period_start", "period_end", "group", "name", "occs", "completed",
+    "missed", "completion_rate", "average_progress", "progress", "total",
+    "current_streak", "longest_streak",
+];
+
+/// Completion statistics for a group of task occurrences.
+///
+/// Only progress registered directly with each occurrence is counted---excess
+/// progress transferred between occurrences is not taken into account.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Serialize)]
+pub struct CompletionStats {
+    /// Number of occurrences.
+    pub occs: u32,
+    /// Number of occurrences which met their target completion amount.
+    ///
+    /// For [`TargetKind::AtMost`] targets, only occurrences which have ended
+    /// are counted.
+    pub completed: u32,
+    /// Number of occurrences which ended without meeting their target
+    /// completion amount.
+    ///
+    /// For [`TargetKind::AtMost`] targets, this includes occurrences which
+    /// have exceeded their target but not yet ended.
+    pub missed: u32,
+    /// Amount of the target completion amount
+    /// [achieved](super::progress::TaskProgress::achieved), summed over all
+    /// occurrences.
+    pub progress: u64,
+    /// Target completion amount summed over all occurrences.
+    pub total: u64,
+    /// Number of occurrences completed in a row, up to the latest occurrence
+    /// which was completed or missed.
+    ///
+    /// For a group of items, this is the longest current streak of any item.
+    pub current_streak: u32,
+    /// Largest number of occurrences completed in a row.
+    ///
+    /// For a group of items, this is the longest streak of any item.
+    pub longest_streak: u32,
+}
+
+impl CompletionStats {
+    /// Proportion of occurrences that were completed, out of those which were
+    /// either completed or missed.
+    ///
+    /// Returns `None` when there are no such occurrences.
+    pub fn completion_rate(&self) -> Option<f64> {
+        let decided = self.completed + self.missed;
+        if decided == 0 {
+            None
+        } else {
+            Some(f64::from(self.completed) / f64::from(decided))
+        }
+    }
+
+    /// Average proportion of the target completion amount reached by each
+    /// occurrence.
+    ///
+    /// Returns `None` when there are no occurrences.
+    pub fn average_progress(&self) -> Option<f64> {
+        if self.total == 0 {
+            None
+        } else {
+            Some(self.progress as f64 / self.total as f64)
+        }
+    }
+
+    /// Add the statistics from `other` to these statistics.
+    pub fn merge(&mut self, other: &CompletionStats) {
+        self.occs += other.occs;
+        self.completed += other.completed;
+        self.missed += other.missed;
+        self.progress += other.progress;
+        self.total += other.total;
+        self.current_streak = self.current_streak.max(other.current_streak);
+        self.longest_streak = self.longest_streak.max(other.longest_streak);
+    }
+}
+
+/// Completion statistics grouped in different ways.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    /// Statistics by item ID.
+    pub items: HashMap<String, CompletionStats>,
+    /// Statistics by item category.  Items without a category are not
+    /// included.
+    pub categories: HashMap<String, CompletionStats>,
+    /// Statistics by item type.
+    pub types: HashMap<ItemType, CompletionStats>,
+}
+
+impl Stats {
+    /// Get the statistics grouped in the given way, keyed by item ID, category
+    /// or item type.
+    pub fn grouped(&self, group_by: GroupBy)
+    -> BTreeMap<String, CompletionStats> {
+        match group_by {
+            GroupBy::Item => self.items.iter()
+                .map(|(id, stats)| (id.clone(), *stats))
+                .collect(),
+            GroupBy::Category => self.categories.iter()
+                .map(|(category, stats)| (category.clone(), *stats))
+                .collect(),
+            GroupBy::Type => self.types.iter()
+                .map(|(type_, stats)| (type_.as_ref().to_owned(), *stats))
+                .collect(),
+        }
+    }
+}
+
+/// How statistics are grouped.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Deserialize,
+         strum::AsRefStr, strum::EnumString)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum GroupBy {
+    /// By item ID.
+    #[default]
+    Item,
+    /// By item category.  Items without a category are not included.
+    Category,
+    /// By item type.
+    Type,
+}
+
+/// Length of the periods a time range is split into.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Deserialize,
+         strum::AsRefStr, strum::EnumString)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Period {
+    /// The whole range as a single period.
+    #[default]
+    All,
+    Day,
+    /// Weeks starting on Monday.
+    Week,
+    /// Calendar months.
+    Month,
+}
+
+/// Split the days in `days` into periods of the given length, as inclusive
+/// ranges of days in order.  The first and last periods are cut short to fit
+/// the range.
+pub fn periods(days: RangeInclusive<NaiveDate>, period: Period)
+-> Vec<RangeInclusive<NaiveDate>> {
+    let (first, last) = days.into_inner();
+    let mut periods = Vec::new();
+    let mut start = first;
+    while start <= last {
+        let next = match period {
+            Period::All => None,
+            Period::Day => start.succ_opt(),
+            Period::Week => start.week(Weekday::Mon).last_day().succ_opt(),
+            Period::Month => start.with_day(1)
+                .and_then(|day| day.checked_add_months(Months::new(1))),
+        };
+        let end = next.and_then(|next| next.pred_opt())
+            .map_or(last, |end| end.min(last));
+        periods.push(start..=end);
+        match next {
+            Some(next) if end < last => start = next,
+            _ => break,
+        }
+    }
+    periods
+}
+
+/// Format a proportion for CSV, or an empty field if it's missing.
+fn csv_rate(value: Option<f64>) -> String {
+    value.map(|value| format!("{value:.4}")).unwrap_or_default()
+}
+
+/// Export completion statistics for task occurrences as CSV, with a row for
+/// each group in each period that has any occurrences.
+///
+/// The days in `days` are split into [`periods`], in UTC, and statistics are
+/// computed separately for each period, as for [`get_stats`].  This makes a
+/// few queries for each period.  Rows are ordered by period and then by group.
+/// The name column holds the item name when grouping by item, and is empty
+/// otherwise.
+pub fn export_csv(
+    db: &impl Db,
+    days: RangeInclusive<NaiveDate>,
+    group_by: GroupBy,
+    period: Period,
+    now: OccDate,
+) -> DbResult<String> {
+    let day_start = |day: NaiveDate| day.and_time(NaiveTime::MIN).and_utc();
+    let mut rows = Vec::new();
+    for days in periods(days, period) {
+        let (first, last) = days.into_inner();
+        let end = day_start(last.succ_opt().unwrap_or(last));
+        let stats = get_stats(db, Some(day_start(first)), Some(end), now)?;
+        rows.extend(stats.grouped(group_by).into_iter()
+            .map(|(group, stats)| (first, last, group, stats)));
+    }
+
+    let names: HashMap<String, String> = if group_by == GroupBy::Item {
+        let mut ids = rows.iter()
+            .map(|(_, _, id, _)| id.as_str())
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.dedup();
+        db.get_items(&ids)?.into_iter()
+            .map(|item| (item.id, item.item.name))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    let mut out = csv_row(&CSV_HEADER);
+    for (first, last, group, stats) in rows {
+        let name = names.get(&group).cloned().unwrap_or_default();
+        out.push_str(&csv_row(&[
+            first.to_string(),
+            last.to_string(),
+            group,
+            name,
+            stats.occs.to_string(),
+            stats.completed.to_string(),
+            stats.missed.to_string(),
+            csv_rate(stats.completion_rate()),
+            csv_rate(stats.average_progress()),
+            stats.progress.to_string(),
+            stats.total.to_string(),
+            stats.current_streak.to_string(),
+            stats.longest_streak.to_string(),
+        ]));
+    }
+    Ok(out)
+}
+
+/// Determine whether occurrences with the given `progress` were completed or
+/// missed, as `(completed, missed)`.
+fn outcome(kind: TargetKind, progress: u32, total: u32, ended: bool)
+-> (bool, bool) {
+    match kind {
+        TargetKind::AtLeast => (progress >= total, ended),
+        TargetKind::AtMost => (ended && progress <= total, progress > total),
+    }
+}
+
+/// Compute completion statistics for all task occurrences overlapping a time
+/// range.
+///
+/// `start` and `end` give the time range.  Occurrences ending before `now`
+/// count as missed if they have not been completed.  Events are not included,
+/// since they have no completion.
+///
+/// Each occurrence's target completion amount and target kind are taken from
+/// its item's config; config applied to individual occurrences is ignored.
+/// Streaks only count occurrences in the time range.
+pub fn get_stats(
+    db: &impl Db,
+    start: Option<OccDate>,
+    end: Option<OccDate>,
+    now: OccDate,
+) -> DbResult<Stats> {
+    let counts = db.count_occs_by_progress(start, end, now)?;
+
+    let mut item_ids = counts.iter()
+        .map(|c| c.item_id.as_str())
+        .collect::<Vec<_>>();
+    item_ids.sort_unstable();
+    item_ids.dedup();
+    let mut configs = HashMap::new();
+    let items: HashMap<String, StoredItem> =
+        config::get_items_with_resolved(db, &Default::default())?
+            .into_iter()
+            .filter(|(item, _)| {
+                item.item.type_ != ItemType::Event
+                    && item_ids.binary_search(&item.id.as_str()).is_ok()
+            })
+            .map(|(item, config)| {
+                if let Some(config) = config {
+                    configs.insert(item.id.clone(), config);
+                }
+                (item.id.clone(), item)
+            })
+            .collect();
+    let targets: HashMap<&str, (u32, TargetKind)> = configs.iter()
+        .map(|(item_id, config)| {
+            let cmpl_cfg = &config.resolved_config.task_completion_conf;
+            (item_id.as_str(),
+             (cmpl_cfg.total.unwrap_or(1),
+              cmpl_cfg.target_kind.unwrap_or_default()))
+        })
+        .collect();
+
+    let mut stats = Stats::default();
+    for count in counts {
+        let Some(item) = items.get(&count.item_id) else { continue };
+        let (total, kind) = targets.get(item.id.as_str())
+            .copied()
+            .unwrap_or((1, TargetKind::AtLeast));
+        let item_stats = stats.items.entry(item.id.clone()).or_default();
+        item_stats.occs += count.count;
+        let (completed, missed) =
+            outcome(kind, count.progress, total, count.ended);
+        if completed {
+            item_stats.completed += count.count;
+        } else if missed {
+            item_stats.missed += count.count;
+        }
+        let achieved = progress::achieved(kind, count.progress, total);
+        item_stats.progress += u64::from(achieved) * u64::from(count.count);
+        item_stats.total += u64::from(total) * u64::from(count.count);
+    }
+
+    let item_ids = items.keys().map(|id| id.as_str()).collect::<Vec<_>>();
+    let occs = db.find_occs(
+        &item_ids, start, end, SortDirection::Asc, None, u32::MAX)?;
+    for (item_id, occs) in occs {
+        let Some(item_stats) = stats.items.get_mut(&item_id) else { continue };
+        let (total, kind) = targets.get(item_id.as_str())
+            .copied()
+            .unwrap_or((1, TargetKind::AtLeast));
+        let mut streak = 0;
+        for occ in occs {
+            let progress = occ.occ.task_completion_progress;
+            match outcome(kind, progress, total, occ.occ.end <= now) {
+                (true, _) => {
+                    streak += 1;
+                    item_stats.longest_streak =
+                        item_stats.longest_streak.max(streak);
+                }
+                (false, true) => streak = 0,
+                // occurrences in progress don't break the streak
+                (false, false) => {}
+            }
+        }
+        item_stats.current_streak = streak;
+    }
+
+    for (item_id, item_stats) in &stats.items {
+        let item = &items[item_id].item;
+        if let Some(category) = &item.category {
+            stats.categories.entry(category.clone())
+                .or_default()
+                .merge(item_stats);
+        }
+        stats.types.entry(item.type_).or_default().merge(item_stats);
+    }
+
+    Ok(stats)
+}
+
+/// Time taken to complete a deadline task occurrence, in
+/// [`CompletionTimes`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CompletionSample {
+    pub occ_id: String,
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = String, format = DateTime))]
+    pub start: OccDate,
+    /// Time from the start of the occurrence until it met its target.
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = crate::types::DurationSchema))]
+    pub duration: Duration,
+    /// Whether the occurrence was completed after its deadline.
+    pub late: bool,
+}
+
+/// Distribution of the time taken to complete a deadline task's occurrences.
+///
+/// Occurrences are overdue if they were completed late or missed.  Summary
+/// durations are missing when no occurrences were completed.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CompletionTimes {
+    /// Number of occurrences which met their target, including late ones.
+    pub completed: u32,
+    /// Number of occurrences which met their target after their deadline.
+    pub late: u32,
+    /// Number of occurrences which reached their deadline without meeting
+    /// their target.
+    pub missed: u32,
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = Option<crate::types::DurationSchema>))]
+    pub min: Option<Duration>,
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = Option<crate::types::DurationSchema>))]
+    pub median: Option<Duration>,
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = Option<crate::types::DurationSchema>))]
+    pub mean: Option<Duration>,
+    /// 90th percentile.
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = Option<crate::types::DurationSchema>))]
+    pub p90: Option<Duration>,
+    #[cfg_attr(feature = "openapi",
+               schema(value_type = Option<crate::types::DurationSchema>))]
+    pub max: Option<Duration>,
+    /// Completed occurrences, ordered by start date, for seeing how
+    /// completion times change over time.
+    pub samples: Vec<CompletionSample>,
+}
+
+impl CompletionTimes {
+    /// Number of occurrences which were completed late or missed.
+    pub fn overdue(&self) -> u32 {
+        self.late + self.missed
+    }
+
+    /// Fill in summary durations from `samples`.
+    fn summarise(&mut self) {
+        let mut durations = self.samples.iter()
+            .map(|sample| sample.duration)
+            .collect::<Vec<_>>();
+        durations.sort_unstable();
+        // nearest-rank percentile
+        let percentile = |p: usize| {
+            let rank = (p * durations.len()).div_ceil(100).max(1);
+            durations.get(rank - 1).copied()
+        };
+        self.min = durations.first().copied();
+        self.median = percentile(50);
+        self.p90 = percentile(90);
+        self.max = durations.last().copied();
+        self.mean = u32::try_from(durations.len()).ok()
+            .filter(|count| *count > 0)
+            .map(|count| durations.iter().sum::<Duration>() / count);
+    }
+}
+
+/// Compute how long it took to complete deadline task occurrences overlapping
+/// a time range, by item ID.
+///
+/// `start` and `end` give the time range.  An occurrence is completed when the
+/// progress logged for it first reaches its target completion amount, not
+/// taking into account excess progress transferred between occurrences.
+/// Occurrences ending before `now` without being completed are missed, and
+/// those still in progress are ignored, as are occurrences of items whose
+/// target is [`TargetKind::AtMost`].  Only items with counted occurrences are
+/// included.
+pub fn get_completion_times(
+    db: &impl Db,
+    start: Option<OccDate>,
+    end: Option<OccDate>,
+    now: OccDate,
+) -> DbResult<HashMap<String, CompletionTimes>> {
+    let items = db.find_items(
+        None, None, start, SortDirection::Asc, None, u32::MAX)?
+        .into_iter()
+        .filter(|item| item.item.type_ == ItemType::DeadlineTask)
+        .collect::<Vec<_>>();
+    let item_ids = items.iter()
+        .map(|item| item.id.as_str())
+        .collect::<Vec<_>>();
+    let occs_by_item = db.find_occs(
+        &item_ids, start, end, SortDirection::Asc, None, u32::MAX)?;
+    let occs = items.iter()
+        .flat_map(|item| {
+            occs_by_item.get(&item.id).into_iter()
+                .flatten()
+                .filter(|occ| occ.occ.active)
+                .map(move |occ| (item, occ))
+        })
+        .collect::<Vec<_>>();
+    let targets: HashMap<&str, (u32, TargetKind)> =
+        config::get_occs_configs(db, &occs)?
+            .into_iter()
+            .map(|(occ, config)| {
+                let cmpl_cfg = &config.resolved_config.task_completion_conf;
+                (occ.id.as_str(),
+                 (cmpl_cfg.total.unwrap_or(1),
+                  cmpl_cfg.target_kind.unwrap_or_default()))
+            })
+            .collect();
+    let occ_ids = occs.iter()
+        .map(|(_, occ)| occ.id.as_str())
+        .collect::<Vec<_>>();
+    let entries = db.find_progress_entries(
+        &occ_ids, SortDirection::Asc, u32::MAX)?;
+
+    let mut times = HashMap::<String, CompletionTimes>::new();
+    for (item, occ) in occs {
+        let (total, kind) = targets.get(occ.id.as_str())
+            .copied()
+            .unwrap_or((1, TargetKind::AtLeast));
+        if kind == TargetKind::AtMost {
+            continue;
+        }
+        let mut progress = 0;
+        let completed = entries.get(&occ.id).into_iter()
+            .flatten()
+            .find(|entry| {
+                progress += entry.entry.amount;
+                progress >= i64::from(total)
+            })
+            .map(|entry| entry.entry.date);
+        if completed.is_none() && occ.occ.end > now {
+            continue;
+        }
+        let item_times = times.entry(item.id.clone()).or_default();
+        match completed {
+            Some(date) => {
+                let late = date > occ.occ.end;
+                item_times.completed += 1;
+                if late {
+                    item_times.late += 1;
+                }
+                item_times.samples.push(CompletionSample {
+                    occ_id: occ.id.clone(),
+                    start: occ.occ.start,
+                    duration: (date - occ.occ.start).to_std()
+                        .unwrap_or_default(),
+                    late,
+                });
+            }
+            None => item_times.missed += 1,
+        }
+    }
+    for item_times in times.values_mut() {
+        item_times.summarise();
+    }
+    Ok(times)
+}
@@ -0,0 +1,274 @@
+//! Determining which occurrences need attention.
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::db::{Db, DbResult, DbResults, SortDirection, StoredItem, StoredOcc};
+use crate::types::{ItemType, Occ, OccDate, TargetKind};
+use super::{alert_period_contains, config, preview_items_current_occ,
+            progress};
+use super::progress::TaskProgress;
+
+/// State of an occurrence, as far as alerts are concerned.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum OccStatus {
+    /// Doesn't need attention.
+    Idle,
+    /// In its alert period, and not yet complete.
+    Alert,
+    /// Task occurrence which met its target.
+    ///
+    /// For [`TargetKind::AtMost`] targets, this only applies once the
+    /// occurrence has ended.
+    Complete,
+    /// Task occurrence which missed its target.
+    ///
+    /// For [`TargetKind::AtLeast`] targets, this applies once the occurrence
+    /// has ended.  For [`TargetKind::AtMost`] targets, this applies as soon as
+    /// progress exceeds the target.
+    Overdue,
+}
+
+/// Determine the status of an occurrence at `date`.
+///
+/// `alert` is the length of the occurrence's alert period, and `progress` is
+/// missing for events.
+pub fn occ_status(
+    occ: &Occ,
+    progress: Option<&TaskProgress>,
+    alert: Duration,
+    date: OccDate,
+) -> OccStatus {
+    let ended = date >= occ.end;
+    if let Some(progress) = progress {
+        let complete = progress.is_complete();
+        let (is_complete, is_overdue) = match progress.target_kind {
+            TargetKind::AtLeast => (complete, ended && !complete),
+            TargetKind::AtMost => (ended && complete, !complete),
+        };
+        if is_complete {
+            return OccStatus::Complete;
+        } else if is_overdue {
+            return OccStatus::Overdue;
+        }
+    }
+
+    if alert_period_contains(occ, alert, date) {
+        OccStatus::Alert
+    } else {
+        OccStatus::Idle
+    }
+}
+
+/// Determine the status of multiple occurrences at `date`, resolving their
+/// progress and configs.
+///
+/// `occs` is a slice of `(item, occ)` pairs.  `default_alert` is used when an
+/// occurrence's config doesn't set
+/// [`occ_alert`](crate::types::Config::occ_alert) (see
+/// [`OCC_ALERT_DEFAULT`](crate::configrefs::OCC_ALERT_DEFAULT)).  The result
+/// maps occurrence IDs to statuses.
+pub fn resolve_occs_status(
+    db: &impl Db,
+    occs: &[(&StoredItem, &StoredOcc)],
+    default_alert: Duration,
+    date: OccDate,
+) -> DbResult<HashMap<String, OccStatus>> {
+    let progress = progress::resolve_item_occs_progress(db, occs)?;
+    let alerts: HashMap<&str, Duration> = config::get_occs_configs(db, occs)?
+        .into_iter()
+        .filter_map(|(occ, config)| {
+            config.resolved_config.occ_alert
+                .map(|alert| (occ.id.as_str(), alert))
+        })
+        .collect();
+
+    Ok(occs.iter()
+        .map(|(item, occ)| {
+            let alert = alerts.get(occ.id.as_str())
+                .copied()
+                .unwrap_or(default_alert);
+            let status = occ_status(
+                &occ.occ, progress.get(&occ.id), alert, date);
+            (occ.id.clone(), status)
+        })
+        .collect())
+}
+
+/// Occurrence tracked by a [`StatusTracker`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TrackedOcc {
+    /// Missing if the occurrence hasn't been generated yet.
+    pub id: Option<String>,
+    pub occ: Occ,
+}
+
+/// Determine the status of occurrences which may not have been stored yet
+/// (see [`resolve_occs_status`]).  The result is in the same order as `occs`.
+fn tracked_occs_status(
+    db: &impl Db,
+    occs: &[(StoredItem, TrackedOcc)],
+    default_alert: Duration,
+    date: OccDate,
+) -> DbResults<OccStatus> {
+    let stored = occs.iter()
+        .filter_map(|(item, occ)| {
+            let id = occ.id.clone()?;
+            Some((item, StoredOcc { id, occ: occ.occ.clone() }))
+        })
+        .collect::<Vec<_>>();
+    let stored_refs = stored.iter()
+        .map(|(item, occ)| (*item, occ))
+        .collect::<Vec<_>>();
+    let mut statuses = resolve_occs_status(
+        db, &stored_refs, default_alert, date)?;
+
+    occs.iter()
+        .map(|(item, occ)| {
+            if let Some(status) = occ.id.as_ref()
+                .and_then(|id| statuses.remove(id))
+            {
+                return Ok(status);
+            }
+            // not stored, so has no progress or config of its own
+            let progress = match item.item.type_ {
+                ItemType::Event => None,
+                _ => Some(progress::new_occ_progress(db, item)?),
+            };
+            let alert = config::get_item_config(db, item)?
+                .and_then(|config| config.resolved_config.occ_alert)
+                .unwrap_or(default_alert);
+            Ok(occ_status(&occ.occ, progress.as_ref(), alert, date))
+        })
+        .collect()
+}
+
+/// Tracks the statuses of a user's occurrences, to find those which changed.
+///
+/// Occurrences are tracked from when they're current until they end, so that
+/// occurrences which become overdue are found even though they're no longer
+/// current.  Nothing is stored, so current occurrences which haven't been
+/// generated yet are tracked by their item and start date.
+#[derive(Clone, Debug, Default)]
+pub struct StatusTracker {
+    /// Status at the last check of each occurrence which hadn't ended, by item
+    /// ID and start date.
+    statuses: HashMap<(String, OccDate), (StoredItem, TrackedOcc, OccStatus)>,
+}
+
+impl StatusTracker {
+    pub fn new() -> StatusTracker {
+        StatusTracker::default()
+    }
+
+    /// Get current occurrences, along with occurrences which were current at
+    /// the last check and may since have become overdue.
+    fn occs(&self, db: &impl Db, date: OccDate)
+    -> DbResults<(StoredItem, TrackedOcc)> {
+        let items = db.find_items(
+            Some(true), None, Some(date), SortDirection::Asc, None, u32::MAX)?;
+        let item_refs = items.iter().collect::<Vec<_>>();
+        let mut occs = preview_items_current_occ(db, date, &item_refs)?
+            .into_iter()
+            .filter_map(|preview| preview.current)
+            .map(|current| {
+                (current.item.clone(),
+                 TrackedOcc { id: current.id, occ: current.occ })
+            })
+            .collect::<Vec<_>>();
+        let current_keys = occs.iter()
+            .map(|(item, occ)| (item.id.as_str(), occ.occ.start))
+            .collect::<HashSet<_>>();
+        let ended = self.statuses.iter()
+            .filter(|((item_id, start), (_, _, status))| {
+                matches!(status, OccStatus::Idle | OccStatus::Alert)
+                    && !current_keys.contains(&(item_id.as_str(), *start))
+            })
+            .map(|(_, (item, occ, _))| (item, occ))
+            .collect::<Vec<_>>();
+        if ended.is_empty() {
+            return Ok(occs);
+        }
+
+        // get the latest version of ended occurrences, which may have been
+        // stored since the last check
+        let ended_ids = ended.iter()
+            .filter_map(|(item, occ)| occ.id.as_deref())
+            .collect::<Vec<_>>();
+        let mut stored = db.get_occs(&ended_ids)?
+            .into_iter()
+            .map(|occ| (occ.id.clone(), occ))
+            .collect::<HashMap<_, _>>();
+        let unstored = ended.iter()
+            .filter(|(item, occ)| occ.id.is_none())
+            .collect::<Vec<_>>();
+        let unstored_ids = unstored.iter()
+            .map(|(item, occ)| item.id.as_str())
+            .collect::<Vec<_>>();
+        let since = unstored.iter().map(|(item, occ)| occ.occ.start).min();
+        let generated = match since {
+            Some(since) => db.find_occs(
+                &unstored_ids, Some(since), None, SortDirection::Asc, None,
+                u32::MAX)?,
+            None => HashMap::new(),
+        };
+        for (item, occ) in ended {
+            let latest = match &occ.id {
+                Some(id) => stored.remove(id),
+                None => generated.get(&item.id)
+                    .and_then(|occs| {
+                        occs.iter().find(|o| o.occ.start == occ.occ.start)
+                    })
+                    .cloned(),
+            };
+            match latest {
+                Some(latest) => occs.push((item.clone(), TrackedOcc {
+                    id: Some(latest.id),
+                    occ: latest.occ,
+                })),
+                None if occ.id.is_none() => {
+                    occs.push((item.clone(), occ.clone()));
+                }
+                // deleted
+                None => (),
+            }
+        }
+        Ok(occs)
+    }
+
+    /// Check occurrence statuses at `date`, returning occurrences whose status
+    /// changed since the last check, along with their new status.
+    ///
+    /// At the first check, every current occurrence is returned.
+    /// `default_alert` is as for [`resolve_occs_status`].  This doesn't change
+    /// the database.
+    pub fn check(
+        &mut self,
+        db: &impl Db,
+        default_alert: Duration,
+        date: OccDate,
+    ) -> DbResults<(StoredItem, TrackedOcc, OccStatus)> {
+        let occs = self.occs(db, date)?;
+        let statuses = tracked_occs_status(db, &occs, default_alert, date)?;
+
+        let mut changed = Vec::new();
+        let mut current_statuses = HashMap::new();
+        for ((item, occ), status) in occs.into_iter().zip(statuses) {
+            let key = (item.id.clone(), occ.occ.start);
+            let is_changed = self.statuses.get(&key)
+                .is_none_or(|(_, _, previous)| *previous != status);
+            // stop tracking occurrences once they end
+            if occ.occ.end > date {
+                current_statuses.insert(
+                    key, (item.clone(), occ.clone(), status));
+            }
+            if is_changed {
+                changed.push((item, occ, status));
+            }
+        }
+        self.statuses = current_statuses;
+        Ok(changed)
+    }
+}
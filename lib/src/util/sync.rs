@@ -0,0 +1,98 @@
+//! Keeping items up to date with external sources, such as subscribed
+//! [iCalendar](https://www.rfc-editor.org/rfc/rfc5545) URLs.
+//!
+//! Syncing is one-way: the source is authoritative, so changes made to synced
+//! items are overwritten, and items whose entries are removed from the source
+//! are deleted.  Items are matched to entries by their
+//! [external IDs](Imported::external_ids).
+
+use std::collections::HashSet;
+#[cfg(feature = "http")]
+use std::time::Duration;
+use serde::Serialize;
+use crate::db::{Db, DbResult, DbUpdate, WriteBatch};
+#[cfg(feature = "http")]
+use super::import;
+use super::import::Imported;
+
+#[cfg(feature = "http")]
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Result of syncing items from a source.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Synced {
+    /// IDs of items which were created or updated, in the order their entries
+    /// were found.
+    pub items: Vec<String>,
+    /// IDs of items which were deleted because their entries were removed from
+    /// the source.
+    pub removed: Vec<String>,
+    /// Description of each entry which couldn't be synced.
+    pub skipped: Vec<String>,
+}
+
+/// Make the items synced from `source` match `imported`, in a single write.
+///
+/// Entries without an external ID, or with the same external ID as an earlier
+/// entry, are skipped.
+pub fn items(db: &mut impl Db, source: &str, imported: &Imported)
+-> DbResult<Synced> {
+    let existing = db.find_external_ids(source)?;
+    let mut synced = Synced {
+        skipped: imported.skipped.clone(),
+        ..Synced::default()
+    };
+
+    let mut seen = HashSet::new();
+    let mut batch = WriteBatch::new();
+    let mut tokens = Vec::new();
+    for (item, external_id) in imported.items.iter()
+        .zip(&imported.external_ids)
+    {
+        match external_id {
+            Some(external_id) if seen.insert(external_id.as_str()) => {
+                tokens.push(batch.upsert_item(source, external_id, item));
+            }
+            Some(_) => synced.skipped.push(
+                format!("{:?}: duplicate identifier", item.name)),
+            None => synced.skipped.push(
+                format!("{:?}: no identifier", item.name)),
+        }
+    }
+    let mut removed = existing.iter()
+        .filter(|(external_id, _)| !seen.contains(external_id.as_str()))
+        .map(|(_, id)| id.as_str())
+        .collect::<Vec<_>>();
+    removed.sort_unstable();
+
+    for id in &removed {
+        batch.push(DbUpdate::delete_item(id));
+    }
+    let mut ids = batch.write(db)?;
+    synced.items = tokens.iter()
+        .map(|id_token| {
+            ids.remove(id_token)
+                .ok_or("unknown error - ID not returned".to_owned())
+        })
+        .collect::<DbResult<_>>()?;
+    synced.removed = removed.into_iter().map(str::to_owned).collect();
+    Ok(synced)
+}
+
+/// Fetch the iCalendar object at `url`, and sync the events in it as items,
+/// using the URL as the source.
+///
+/// Events are converted in the same way as by [`import::ics`].  Requires the
+/// `http` feature.
+#[cfg(feature = "http")]
+pub fn ics(db: &mut impl Db, url: &str) -> Result<Synced, String> {
+    let text = ureq::AgentBuilder::new().timeout(TIMEOUT).build()
+        .get(url)
+        .call()
+        .map_err(|e| format!("error fetching calendar: {e}"))?
+        .into_string()
+        .map_err(|e| format!("error reading calendar ({url}): {e}"))?;
+    let imported = import::ics(text.as_bytes())
+        .map_err(|e| format!("error reading calendar ({url}): {e}"))?;
+    items(db, url, &imported)
+}
@@ -0,0 +1,417 @@
+//! Converting between items and [Taskwarrior](https://taskwarrior.org)'s JSON
+//! format, as written by `task export` and read by `task import`.
+//!
+//! Recurring tasks become progress tasks, with a schedule based on their
+//! `recur` period, and their instances become occurrences.  Other tasks with a
+//! due date become deadline tasks with a single occurrence, which are inactive
+//! once the task is completed.  Annotations become progress entries with notes.
+//!
+//! Exported tasks have stable UUIDs, so exporting again and importing into
+//! Taskwarrior updates the same tasks.  They're tagged with [`EXPORT_TAG`],
+//! and tasks with this tag are skipped on import.
+
+use std::collections::HashMap;
+use std::io::Read;
+use chrono::{Datelike, NaiveDateTime, TimeDelta};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::types::{DeadlineTaskSched, Item, ItemType, Occ, OccDate,
+                   ProgressEntry, ProgressTaskSched, Sched};
+use super::export::Export;
+use super::import::{ImportedHistory, ImportedItem};
+use super::occgen::{OccGen, ProgressTaskOccGen};
+
+/// Tag added to exported tasks.
+pub const EXPORT_TAG: &str = "dunsumday";
+/// Source recorded for imported items, with task UUIDs as their external IDs
+/// (see [`create_history`](super::import::create_history)).
+pub const SOURCE: &str = "taskwarrior";
+
+const DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+fn parse_date(value: &str) -> Result<OccDate, String> {
+    NaiveDateTime::parse_from_str(value, DATE_FORMAT)
+        .map(|date| date.and_utc())
+        .map_err(|e| format!("invalid date ({value}): {e}"))
+}
+
+fn serialize_date<S: Serializer>(date: &OccDate, s: S)
+-> Result<S::Ok, S::Error> {
+    s.collect_str(&date.format(DATE_FORMAT))
+}
+
+fn deserialize_date<'de, D: Deserializer<'de>>(d: D)
+-> Result<OccDate, D::Error> {
+    parse_date(&String::deserialize(d)?).map_err(serde::de::Error::custom)
+}
+
+fn serialize_opt_date<S: Serializer>(date: &Option<OccDate>, s: S)
+-> Result<S::Ok, S::Error> {
+    match date {
+        Some(date) => serialize_date(date, s),
+        None => s.serialize_none(),
+    }
+}
+
+fn deserialize_opt_date<'de, D: Deserializer<'de>>(d: D)
+-> Result<Option<OccDate>, D::Error> {
+    Option::<String>::deserialize(d)?
+        .map(|value| parse_date(&value))
+        .transpose()
+        .map_err(serde::de::Error::custom)
+}
+
+/// Status of a task.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Deserialize,
+         Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    #[default]
+    Pending,
+    Completed,
+    Deleted,
+    Waiting,
+    /// Template for the instances of a recurring task.
+    Recurring,
+}
+
+/// Note attached to a task.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+pub struct Annotation {
+    #[serde(serialize_with = "serialize_date",
+            deserialize_with = "deserialize_date")]
+    pub entry: OccDate,
+    pub description: String,
+}
+
+/// Task, with the attributes used by this module.  Other attributes are
+/// ignored.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+pub struct Task {
+    pub uuid: String,
+    pub description: String,
+    pub status: Status,
+    /// When the task was created.
+    #[serde(serialize_with = "serialize_date",
+            deserialize_with = "deserialize_date")]
+    pub entry: OccDate,
+    #[serde(default, skip_serializing_if = "Option::is_none",
+            serialize_with = "serialize_opt_date",
+            deserialize_with = "deserialize_opt_date")]
+    pub due: Option<OccDate>,
+    /// When the task was completed or deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none",
+            serialize_with = "serialize_opt_date",
+            deserialize_with = "deserialize_opt_date")]
+    pub end: Option<OccDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Period of a recurring task, like `weekly` or `2d`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recur: Option<String>,
+    /// UUID of the recurring task this is an instance of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+}
+
+/// Unit of a recurrence period.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum RecurUnit {
+    Days,
+    Weeks,
+    Months,
+    Years,
+}
+
+/// Parse a `recur` value into a number of units.
+fn parse_recur(recur: &str) -> Result<(u8, RecurUnit), String> {
+    let recur = recur.trim().to_ascii_lowercase();
+    let named = match recur.as_str() {
+        "daily" | "day" => Some((1, RecurUnit::Days)),
+        "weekly" | "week" => Some((1, RecurUnit::Weeks)),
+        "biweekly" | "fortnight" => Some((2, RecurUnit::Weeks)),
+        "monthly" | "month" => Some((1, RecurUnit::Months)),
+        "bimonthly" => Some((2, RecurUnit::Months)),
+        "quarterly" => Some((3, RecurUnit::Months)),
+        "semiannual" => Some((6, RecurUnit::Months)),
+        "yearly" | "annual" | "year" => Some((1, RecurUnit::Years)),
+        "biannual" | "biyearly" => Some((2, RecurUnit::Years)),
+        _ => None,
+    };
+    if let Some(named) = named {
+        return Ok(named);
+    }
+
+    let unsupported = || format!("unsupported recurrence: {recur}");
+    let split = recur.find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(unsupported)?;
+    let (num, unit) = recur.split_at(split);
+    let num = if num.is_empty() {
+        1
+    } else {
+        num.parse::<u8>().map_err(|_| unsupported())?
+    };
+    let (mult, unit) = match unit {
+        "d" | "day" | "days" => (1, RecurUnit::Days),
+        "w" | "wk" | "wks" | "week" | "weeks" => (1, RecurUnit::Weeks),
+        "mo" | "mth" | "mths" | "month" | "months" => (1, RecurUnit::Months),
+        "q" | "qtr" | "qtrs" | "quarter" | "quarters" => (3, RecurUnit::Months),
+        "y" | "yr" | "yrs" | "year" | "years" => (1, RecurUnit::Years),
+        _ => return Err(unsupported()),
+    };
+    let num = num.checked_mul(mult)
+        .filter(|num| *num > 0)
+        .ok_or_else(unsupported)?;
+    Ok((num, unit))
+}
+
+/// Last day included in the period of a task due at `due`.
+fn due_day(due: OccDate) -> chrono::NaiveDate {
+    (due - TimeDelta::seconds(1)).date_naive()
+}
+
+/// Get the schedule for a recurring task, with periods ending on the day of
+/// its `due` date.
+fn recur_sched(recur: &str, due: OccDate) -> Result<ProgressTaskSched, String> {
+    let (num, unit) = parse_recur(recur)?;
+    let next_day = due_day(due).succ_opt()
+        .ok_or_else(|| format!("invalid due date: {due}"))?;
+    Ok(match unit {
+        RecurUnit::Days => ProgressTaskSched::Days { num },
+        RecurUnit::Weeks => ProgressTaskSched::Weeks {
+            num,
+            start_day: next_day.weekday(),
+        },
+        RecurUnit::Months => ProgressTaskSched::Months {
+            num,
+            start_day: next_day.day() as u8,
+        },
+        RecurUnit::Years => ProgressTaskSched::Years {
+            num,
+            start_month: chrono::Month::try_from(next_day.month() as u8)
+                .map_err(|_| format!("invalid due date: {due}"))?,
+            start_dom: next_day.day() as u8,
+        },
+    })
+}
+
+/// Get the occurrence period of an instance of a recurring task, which is the
+/// period ending on the day of its `due` date.
+fn recur_occ(sched: &ProgressTaskSched, due: OccDate) -> Option<Occ> {
+    let mut day = due_day(due);
+    if let ProgressTaskSched::Days { num } = sched {
+        day = day - chrono::Days::new(u64::from(*num) - 1);
+    }
+    ProgressTaskOccGen { sched }
+        .generate_first(day.and_time(chrono::NaiveTime::MIN).and_utc())
+}
+
+/// Get the progress entries for a task: one for its completion, if completed,
+/// and one with a note for each annotation.
+fn task_entries(task: &Task) -> Vec<ProgressEntry> {
+    let mut entries = task.annotations.iter()
+        .map(|annotation| ProgressEntry {
+            date: annotation.entry,
+            amount: 0,
+            note: Some(annotation.description.clone()),
+        })
+        .collect::<Vec<_>>();
+    if task.status == Status::Completed {
+        entries.push(ProgressEntry {
+            date: task.end.or(task.due).unwrap_or(task.entry),
+            amount: 1,
+            note: None,
+        });
+    }
+    entries.sort_by_key(|entry| entry.date);
+    entries
+}
+
+/// Convert a task which isn't recurring to an item with a single occurrence.
+fn single_task(task: &Task) -> Result<ImportedItem, String> {
+    let due = task.due.ok_or("task has no due date".to_owned())?;
+    let duration = (due - task.entry).to_std()
+        .ok()
+        .filter(|duration| !duration.is_zero())
+        .ok_or("task is due before it was created".to_owned())?;
+    let complete = task.status == Status::Completed;
+    Ok(ImportedItem {
+        external_id: task.uuid.clone(),
+        item: Item {
+            type_: ItemType::DeadlineTask,
+            active: !complete,
+            category: task.project.clone(),
+            name: task.description.clone(),
+            desc: None,
+            sched: Sched::DeadlineTask(DeadlineTaskSched { duration }),
+        },
+        config: None,
+        occs: vec![(
+            Occ {
+                active: true,
+                start: task.entry,
+                end: due,
+                task_completion_progress: complete.into(),
+                snoozed_until: None,
+            },
+            task_entries(task),
+        )],
+    })
+}
+
+/// Convert a recurring task to an item, with an occurrence for each of its
+/// `instances` which hasn't been deleted.
+fn recurring_task(template: &Task, instances: &[&Task])
+-> Result<ImportedItem, String> {
+    let recur = template.recur.as_deref()
+        .ok_or("recurring task has no period".to_owned())?;
+    let due = template.due
+        .ok_or("recurring task has no due date".to_owned())?;
+    let sched = recur_sched(recur, due)?;
+    let mut occs = instances.iter()
+        .filter(|task| task.status != Status::Deleted)
+        .filter_map(|task| {
+            let mut occ = recur_occ(&sched, task.due?)?;
+            if task.status == Status::Completed {
+                occ.task_completion_progress = 1;
+            }
+            Some((occ, task_entries(task)))
+        })
+        .collect::<Vec<_>>();
+    occs.sort_by_key(|(occ, _)| occ.start);
+    occs.dedup_by_key(|(occ, _)| occ.start);
+    Ok(ImportedItem {
+        external_id: template.uuid.clone(),
+        item: Item {
+            type_: ItemType::ProgressTask,
+            active: true,
+            category: template.project.clone(),
+            name: template.description.clone(),
+            desc: None,
+            sched: Sched::ProgressTask(sched),
+        },
+        config: None,
+        occs,
+    })
+}
+
+/// Read tasks exported by Taskwarrior, converting them to items.
+///
+/// Both a JSON array and one JSON object per line are accepted.  Deleted tasks,
+/// and tasks exported by [`tasks`], are skipped.
+pub fn read(mut reader: impl Read) -> Result<ImportedHistory, String> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)
+        .map_err(|e| format!("error reading tasks: {e}"))?;
+    let tasks: Vec<Task> = if text.trim_start().starts_with('[') {
+        serde_json::from_str(&text)
+            .map_err(|e| format!("invalid tasks: {e}"))?
+    } else {
+        serde_json::Deserializer::from_str(&text).into_iter()
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("invalid tasks: {e}"))?
+    };
+
+    let tasks = tasks.iter()
+        .filter(|task| !task.tags.iter().any(|tag| tag == EXPORT_TAG))
+        .collect::<Vec<_>>();
+    let templates = tasks.iter()
+        .filter(|task| task.status == Status::Recurring)
+        .map(|task| task.uuid.as_str())
+        .collect::<Vec<_>>();
+    let mut instances: HashMap<&str, Vec<&Task>> = HashMap::new();
+    for task in &tasks {
+        if let Some(parent) = task.parent.as_deref()
+            .filter(|parent| templates.contains(parent))
+        {
+            instances.entry(parent).or_default().push(task);
+        }
+    }
+
+    let mut import = ImportedHistory::default();
+    for task in tasks {
+        let imported = match task.status {
+            Status::Deleted => continue,
+            Status::Recurring => recurring_task(
+                task, instances.get(task.uuid.as_str())
+                    .map(Vec::as_slice)
+                    .unwrap_or_default()),
+            _ if task.parent.as_deref()
+                .is_some_and(|parent| templates.contains(&parent)) => continue,
+            _ => single_task(task),
+        };
+        match imported {
+            Ok(imported) => import.items.push(imported),
+            Err(e) => import.skipped.push(
+                format!("task {:?}: {e}", task.description)),
+        }
+    }
+    Ok(import)
+}
+
+/// Produce a UUID which is always the same for the same occurrence.
+fn occ_uuid(item_id: &str, occ_id: &str) -> String {
+    // FNV-1a, which unlike the standard library's hasher is stable
+    let hash = |value: &str, basis: u64| {
+        value.bytes().fold(basis, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3)
+        })
+    };
+    let high = hash(item_id, 0xcbf2_9ce4_8422_2325);
+    let low = hash(occ_id, high);
+    // version 4 and variant 1 bits, so Taskwarrior accepts it
+    format!("{:08x}-{:04x}-4{:03x}-8{:03x}-{:012x}",
+            high >> 32, (high >> 16) & 0xffff, high & 0xfff,
+            (low >> 48) & 0xfff, low & 0xffff_ffff_ffff)
+}
+
+/// Convert task occurrences in an export to tasks.  Events and inactive
+/// occurrences are left out.
+///
+/// Each occurrence becomes a task due at the occurrence's end, which is
+/// completed if the occurrence is complete.  Progress entries with notes become
+/// annotations.
+pub fn tasks(export: &Export) -> Vec<Task> {
+    let items = export.items.iter()
+        .map(|item| (item.id.as_str(), item))
+        .collect::<HashMap<_, _>>();
+    export.occs.iter()
+        .filter(|occ| occ.occ.occ.active)
+        .filter_map(|occ| {
+            let item = items.get(occ.item_id.as_str())?;
+            let progress = occ.progress.as_ref()?;
+            let complete = progress.is_complete();
+            Some(Task {
+                uuid: occ_uuid(&item.id, &occ.occ.id),
+                description: item.item.name.clone(),
+                status: if complete {
+                    Status::Completed
+                } else {
+                    Status::Pending
+                },
+                entry: occ.occ.occ.start,
+                due: Some(occ.occ.occ.end),
+                end: complete.then(|| {
+                    occ.entries.last()
+                        .map(|entry| entry.date)
+                        .unwrap_or(occ.occ.occ.end)
+                }),
+                project: item.item.category.clone(),
+                tags: vec![EXPORT_TAG.to_owned()],
+                recur: None,
+                parent: None,
+                annotations: occ.entries.iter()
+                    .filter_map(|entry| {
+                        entry.note.as_ref().map(|note| Annotation {
+                            entry: entry.date,
+                            description: note.clone(),
+                        })
+                    })
+                    .collect(),
+            })
+        })
+        .collect()
+}
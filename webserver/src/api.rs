@@ -1,22 +1,250 @@
-use actix_web::http::StatusCode;
-use actix_web::{web, HttpResponse};
+use actix_cors::Cors;
+use actix_web::http::header::{self, HeaderName};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{guard, middleware, web, HttpResponse};
 use actix_web::dev::HttpServiceFactory;
-use dunsumday::config::Config;
-use crate::configrefs;
+use dunsumday::config::{cached, Config, ConfigError};
+use utoipa_swagger_ui::SwaggerUi;
+use crate::{auth, configrefs};
 
+mod admin;
+mod alert;
+mod batch;
+mod calendar;
+mod config;
+mod current;
+mod dashboard;
+mod etag;
+mod events;
+mod export;
+mod feed;
+pub mod idempotency;
+mod import;
 mod item;
+mod milestone;
 pub mod notfound;
+mod occ;
+mod openapi;
+pub mod page;
+mod report;
+mod stats;
+mod sync;
+mod ws;
 
 pub const GET_ITEMS: &str = "get items";
 pub const CREATE_ITEM: &str = "create item";
+pub const GET_ITEM: &str = "get item";
+pub const UPDATE_ITEM: &str = "update item";
+pub const DELETE_ITEM: &str = "delete item";
+pub const GET_ITEM_OCCS: &str = "get item occurrences";
+pub const GET_ITEM_FORECAST: &str = "get item completion forecast";
+pub const DONE_ITEM: &str = "add progress to item";
+pub const GET_ITEM_HISTORY: &str = "get item history";
+pub const GET_CURRENT: &str = "get current items";
+pub const GET_CALENDAR: &str = "get calendar";
+pub const GET_DASHBOARD: &str = "get category dashboard";
+pub const GET_CONFIGS: &str = "get configs";
+pub const GET_CONFIG: &str = "get config";
+pub const SET_CONFIG: &str = "set config";
+pub const DELETE_CONFIG: &str = "delete config";
+pub const GET_OCC: &str = "get occurrence";
+pub const GET_OCC_ITEM: &str = "get occurrence item";
+pub const GET_OCC_ENTRIES: &str = "get occurrence progress entries";
+pub const GET_OCC_PROGRESS: &str = "get occurrence progress";
+pub const UNDO_OCC_PROGRESS: &str = "undo occurrence progress";
+pub const SNOOZE_OCC: &str = "snooze occurrence";
+pub const GET_ALERTS: &str = "get unacknowledged alerts";
+pub const GET_ALERT: &str = "get alert";
+pub const ACK_ALERT: &str = "acknowledge alert";
+pub const SNOOZE_ALERT: &str = "snooze alert";
+pub const GET_MILESTONES: &str = "get milestones";
+pub const BATCH: &str = "batch write";
+pub const SYNC: &str = "sync changes";
+pub const IMPORT_ICS: &str = "import icalendar";
+pub const GET_STATS: &str = "get completion statistics";
+pub const GET_HEATMAP: &str = "get progress heatmap";
+pub const EXPORT_STATS: &str = "export completion statistics as csv";
+pub const GET_COMPLETION_TIMES: &str = "get deadline task completion times";
+pub const GET_WEEKLY_REVIEW: &str = "get weekly review";
+pub const EXPORT: &str = "export items and occurrences";
+pub const EXPORT_OCCS: &str = "export occurrences as json lines";
+pub const GET_EVENTS: &str = "get occurrence events";
+pub const GET_FEED: &str = "get calendar feed";
+pub const GET_FEED_URL: &str = "get calendar feed url";
+pub const BACKUP: &str = "backup database";
+pub const RESTORE: &str = "restore database";
+pub const RELOAD_CONFIG: &str = "reload config";
+pub const WATCH_CHANGES: &str = "watch changes";
+pub const GET_OPENAPI: &str = "get openapi document";
 
-pub fn service<C>(cfg: &C) -> impl HttpServiceFactory
+const OPENAPI_PATH: &str = "/openapi.json";
+const SWAGGER_UI_PATH: &str = "/docs";
+
+/// API settings which are read once, when the server starts.
+#[derive(Clone, Debug)]
+pub struct Options {
+    swagger_ui: bool,
+    cors_origins: Vec<String>,
+    cors_methods: Vec<Method>,
+    cors_headers: Vec<HeaderName>,
+    cors_credentials: bool,
+}
+
+impl Options {
+    pub fn new(cfg: &cached::Config) -> Result<Options, ConfigError> {
+        Ok(Options {
+            swagger_ui: cfg.get_parsed(&configrefs::API_SWAGGER_UI)?,
+            cors_origins:
+                cfg.get_parsed(&configrefs::API_CORS_ALLOWED_ORIGINS)?,
+            cors_methods:
+                cfg.get_parsed(&configrefs::API_CORS_ALLOWED_METHODS)?,
+            cors_headers:
+                cfg.get_parsed(&configrefs::API_CORS_ALLOWED_HEADERS)?,
+            cors_credentials:
+                cfg.get_parsed(&configrefs::API_CORS_ALLOW_CREDENTIALS)?,
+        })
+    }
+
+    fn cors(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allowed_methods(self.cors_methods.clone())
+            .allowed_headers(self.cors_headers.clone())
+            .expose_headers([header::LINK, header::ETAG]);
+        for origin in &self.cors_origins {
+            cors = if origin == "*" {
+                cors.allow_any_origin()
+            } else {
+                cors.allowed_origin(origin)
+            };
+        }
+        if self.cors_credentials {
+            cors = cors.supports_credentials();
+        }
+        cors
+    }
+}
+
+pub fn service<C>(cfg: &C, options: &Options) -> impl HttpServiceFactory
+where
+    C: Config + ?Sized,
+{
+    // middleware wrapped last runs first, and preflight requests shouldn't
+    // need to log in
+    let scope = web::scope(cfg.get_ref(&configrefs::SERVER_API_PATH))
+        .wrap(middleware::from_fn(idempotency::middleware))
+        .wrap(middleware::Condition::new(
+            auth::enabled(cfg), middleware::from_fn(auth::require_login)))
+        .wrap(middleware::Condition::new(
+            !options.cors_origins.is_empty(), options.cors()))
+        .app_data(web::Data::new(options.clone()))
+        .service(web::resource("/item").name(GET_ITEMS)
+                 .guard(guard::Get()).to(item::list))
+        .service(web::resource("/item").name(CREATE_ITEM)
+                 .guard(guard::Post()).to(item::post))
+        .service(web::resource("/item/{id}").name(GET_ITEM)
+                 .guard(guard::Get()).to(item::get))
+        .service(web::resource("/item/{id}").name(UPDATE_ITEM)
+                 .guard(guard::Put()).to(item::put))
+        .service(web::resource("/item/{id}").name(DELETE_ITEM)
+                 .guard(guard::Delete()).to(item::delete))
+        .service(web::resource("/item/{id}/occ")
+                 .name(GET_ITEM_OCCS).get(item::list_occs))
+        .service(web::resource("/item/{id}/forecast")
+                 .name(GET_ITEM_FORECAST).get(item::forecast))
+        .service(web::resource("/item/{id}/done")
+                 .name(DONE_ITEM).post(item::done))
+        .service(web::resource("/item/{id}/history")
+                 .name(GET_ITEM_HISTORY).get(item::history))
+        .service(web::resource("/current").name(GET_CURRENT)
+                 .guard(guard::Get()).to(current::get))
+        .service(web::resource("/calendar").name(GET_CALENDAR)
+                 .get(calendar::get))
+        .service(web::resource("/dashboard").name(GET_DASHBOARD)
+                 .get(dashboard::get))
+        .service(web::resource("/config").name(GET_CONFIGS)
+                 .guard(guard::Get()).to(config::list))
+        .service(web::resource("/config/{scope:.+}").name(GET_CONFIG)
+                 .guard(guard::Get()).to(config::get))
+        .service(web::resource("/config/{scope:.+}").name(SET_CONFIG)
+                 .guard(guard::Put()).to(config::put))
+        .service(web::resource("/config/{scope:.+}").name(DELETE_CONFIG)
+                 .guard(guard::Delete()).to(config::delete))
+        .service(web::resource("/occ/{id}").name(GET_OCC).get(occ::get))
+        .service(web::resource("/occ/{id}/item")
+                 .name(GET_OCC_ITEM).get(occ::get_item))
+        .service(web::resource("/occ/{id}/entries")
+                 .name(GET_OCC_ENTRIES).get(occ::list_entries))
+        .service(web::resource("/occ/{id}/progress")
+                 .name(GET_OCC_PROGRESS).get(occ::get_progress))
+        .service(web::resource("/occ/{id}/progress/undo")
+                 .name(UNDO_OCC_PROGRESS).post(occ::undo_progress))
+        .service(web::resource("/occ/{id}/snooze")
+                 .name(SNOOZE_OCC).post(occ::snooze))
+        .service(web::resource("/alert").name(GET_ALERTS).get(alert::list))
+        .service(web::resource("/alert/{id}").name(GET_ALERT).get(alert::get))
+        .service(web::resource("/alert/{id}/ack")
+                 .name(ACK_ALERT).post(alert::ack))
+        .service(web::resource("/alert/{id}/snooze")
+                 .name(SNOOZE_ALERT).post(alert::snooze))
+        .service(web::resource("/milestone").name(GET_MILESTONES)
+                 .get(milestone::list))
+        .service(web::resource("/batch").name(BATCH).post(batch::post))
+        .service(web::resource("/sync").name(SYNC).post(sync::post))
+        .service(web::resource("/import/ics").name(IMPORT_ICS)
+                 .post(import::ics))
+        .service(web::resource("/stats").name(GET_STATS).get(stats::get))
+        .service(web::resource("/stats/heatmap")
+                 .name(GET_HEATMAP).get(stats::heatmap))
+        .service(web::resource("/stats/export.csv")
+                 .name(EXPORT_STATS).get(stats::export))
+        .service(web::resource("/stats/completion-times")
+                 .name(GET_COMPLETION_TIMES).get(stats::completion_times))
+        .service(web::resource("/report/weekly")
+                 .name(GET_WEEKLY_REVIEW).get(report::weekly))
+        .service(web::resource("/export").name(EXPORT).get(export::get))
+        .service(web::resource("/export/occs.jsonl")
+                 .name(EXPORT_OCCS).get(export::get_occs))
+        .service(web::resource("/feed").name(GET_FEED_URL).get(feed::get_url))
+        .service(web::resource("/events").name(GET_EVENTS).get(events::get))
+        .service(web::resource("/ws").name(WATCH_CHANGES).get(ws::get))
+        .service(web::resource(OPENAPI_PATH)
+                 .name(GET_OPENAPI).get(openapi::get));
+
+    if options.swagger_ui {
+        let doc_url = join_path(openapi::api_path(cfg), OPENAPI_PATH);
+        scope.service(
+            SwaggerUi::new(format!("{SWAGGER_UI_PATH}/{{_:.*}}"))
+                .config(utoipa_swagger_ui::Config::new([doc_url])))
+    } else {
+        scope
+    }
+}
+
+/// Service for the calendar feed, which doesn't require logging in, so must be
+/// registered before the [main API service](service).
+pub fn feed_service<C>(cfg: &C) -> impl HttpServiceFactory
+where
+    C: Config + ?Sized,
+{
+    web::resource(join_path(
+        cfg.get_ref(&configrefs::SERVER_API_PATH).to_owned(), feed::FEED_PATH))
+        .name(GET_FEED)
+        .get(feed::get)
+}
+
+/// Service for admin endpoints, which use a token instead of logging in, so
+/// must be registered before the [main API service](service).
+pub fn admin_service<C>(cfg: &C) -> impl HttpServiceFactory
 where
     C: Config + ?Sized,
 {
-    web::scope(cfg.get_ref(&configrefs::SERVER_API_PATH))
-        .service(web::resource("/item").name(GET_ITEMS).get(item::list))
-        .service(web::resource("/item").name(CREATE_ITEM).post(item::post))
+    let api_path = cfg.get_ref(&configrefs::SERVER_API_PATH).to_owned();
+    web::scope(&join_path(api_path, admin::ADMIN_PATH))
+        .service(web::resource("/backup").name(BACKUP).get(admin::backup))
+        .service(web::resource("/restore").name(RESTORE)
+                 .post(admin::restore))
+        .service(web::resource("/reload-config").name(RELOAD_CONFIG)
+                 .post(admin::reload_config))
 }
 
 pub fn join_path(root: String, path: &str) -> String {
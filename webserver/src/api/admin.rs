@@ -0,0 +1,146 @@
+use std::fs;
+use std::io::Write;
+use actix_files::NamedFile;
+use actix_web::error::{ErrorBadRequest, ErrorForbidden,
+                       ErrorInternalServerError, ErrorNotFound,
+                       ErrorUnauthorized};
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, Responder};
+use futures_util::StreamExt;
+use ring::{constant_time, digest};
+use serde::Serialize;
+use utoipa::ToSchema;
+use dunsumday::config::cached;
+use dunsumday::util::backup::TempPath;
+use crate::api;
+use crate::configrefs;
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "admin";
+pub const ADMIN_PATH: &str = "/admin";
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReloadResult {
+    /// Whether the config changed since it was last loaded.
+    changed: bool,
+    /// Whether the config changes settings which only take effect when the
+    /// server is restarted.
+    restart_required: bool,
+}
+
+/// Check that the request has the admin token as a bearer token.
+fn authorise(req: &HttpRequest, cfg: &cached::Config)
+-> actix_web::Result<()> {
+    let token = cfg.get_parsed(&configrefs::API_ADMIN_TOKEN)
+        .map_err(ErrorInternalServerError)?;
+    if token.is_empty() {
+        return Err(ErrorNotFound("admin endpoints not enabled"));
+    }
+    let provided = req.headers().get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| ErrorUnauthorized("admin token not provided"))?;
+    // compare digests so the comparison doesn't depend on the token's length
+    constant_time::verify_slices_are_equal(
+        digest::digest(&digest::SHA256, provided.trim().as_bytes()).as_ref(),
+        digest::digest(&digest::SHA256, token.as_bytes()).as_ref())
+        .map_err(|_| ErrorForbidden("invalid admin token"))
+}
+
+/// Download a backup of the whole database, for all users.
+///
+/// Requires the admin token as a bearer token.  The backup's format depends on
+/// the database backend.
+#[utoipa::path(
+    get, path = "/admin/backup", tag = TAG,
+    responses(
+        (status = OK, content_type = "application/octet-stream",
+         body = Vec<u8>),
+        (status = UNAUTHORIZED),
+        (status = FORBIDDEN),
+        (status = NOT_FOUND, description = "admin endpoints are disabled"),
+    ),
+)]
+pub async fn backup(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+) -> actix_web::Result<impl Responder> {
+    authorise(&req, &data.cfg())?;
+    let path = TempPath::new();
+    data.db(&User { id: None })?
+        .backup(path.path())
+        .map_err(ErrorInternalServerError)?;
+    let file = fs::File::open(path.path()).map_err(ErrorInternalServerError)?;
+    // on most platforms, the open file can still be read once deleted
+    drop(path);
+
+    let name = format!("dunsumday-{}.backup",
+                       data.clock.now().format("%Y%m%dT%H%M%SZ"));
+    Ok(NamedFile::from_file(file, name)?)
+}
+
+/// Replace the whole database, for all users, with a backup.
+///
+/// Requires the admin token as a bearer token.  The request body is a backup
+/// downloaded from `/admin/backup`.  Clients watching for changes are not
+/// notified.
+#[utoipa::path(
+    post, path = "/admin/restore", tag = TAG,
+    request_body(content = Vec<u8>,
+                 content_type = "application/octet-stream"),
+    responses(
+        (status = NO_CONTENT),
+        (status = BAD_REQUEST, description = "the backup is invalid"),
+        (status = UNAUTHORIZED),
+        (status = FORBIDDEN),
+        (status = NOT_FOUND, description = "admin endpoints are disabled"),
+    ),
+)]
+pub async fn restore(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    mut payload: web::Payload,
+) -> actix_web::Result<impl Responder> {
+    authorise(&req, &data.cfg())?;
+    let path = TempPath::new();
+    let mut file = fs::File::create(path.path())
+        .map_err(ErrorInternalServerError)?;
+    while let Some(chunk) = payload.next().await {
+        file.write_all(&chunk?).map_err(ErrorInternalServerError)?;
+    }
+    drop(file);
+
+    data.db(&User { id: None })?
+        .restore(path.path())
+        .map_err(ErrorBadRequest)?;
+    Ok(api::no_content())
+}
+
+/// Load the config file again.
+///
+/// Requires the admin token as a bearer token.  The config file is also
+/// reloaded automatically when it changes, but not when only included files
+/// change.  If the file can't be loaded, the previous config is kept.
+#[utoipa::path(
+    post, path = "/admin/reload-config", tag = TAG,
+    responses(
+        (status = OK, body = ReloadResult),
+        (status = UNAUTHORIZED),
+        (status = FORBIDDEN),
+        (status = NOT_FOUND, description = "admin endpoints are disabled"),
+        (status = INTERNAL_SERVER_ERROR,
+         description = "the config file can't be loaded"),
+    ),
+)]
+pub async fn reload_config(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+) -> actix_web::Result<impl Responder> {
+    authorise(&req, &data.cfg())?;
+    let changed = data.cfg.reload().map_err(ErrorInternalServerError)?;
+    Ok(web::Json(ReloadResult {
+        changed,
+        restart_required: data.cfg.restart_required(),
+    }))
+}
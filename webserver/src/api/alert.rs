@@ -0,0 +1,139 @@
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError,
+                       ErrorNotFound};
+use actix_web::{web, HttpRequest, Responder};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use dunsumday::config::parse::{DurationParser, Parser};
+use dunsumday::db::StoredAlert;
+use dunsumday::db::util as dbutil;
+use crate::api::etag;
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "alert";
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SnoozeQuery {
+    /// How long to snooze for, as a number of seconds or with units, like
+    /// `1h30m`.
+    duration: String,
+}
+
+/// Get alerts which have been sent and not acknowledged, excluding snoozed
+/// alerts.
+#[utoipa::path(
+    get, path = "/alert", tag = TAG,
+    responses((status = OK, body = Vec<StoredAlert>)),
+)]
+pub async fn list(
+    data: web::Data<server::State>,
+    user: User,
+) -> actix_web::Result<impl Responder> {
+    let alerts = data.db(&user)?.find_unacked_alerts(data.clock.now())
+        .map_err(ErrorInternalServerError)?;
+    Ok(web::Json(alerts))
+}
+
+#[utoipa::path(
+    get, path = "/alert/{id}", tag = TAG,
+    params(
+        ("id" = String, Path),
+        ("If-None-Match" = Option<String>, Header),
+    ),
+    responses(
+        (status = OK, body = StoredAlert, headers(("ETag" = String))),
+        (status = NOT_MODIFIED),
+        (status = NOT_FOUND),
+    ),
+)]
+pub async fn get(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let alert = data.db(&user)?.get_alerts(&[&id])
+        .map_err(ErrorInternalServerError)?
+        .pop()
+        .ok_or_else(|| ErrorNotFound("alert not found"))?;
+    Ok(etag::response(&req, &alert))
+}
+
+/// Acknowledge an alert, so that it's never sent again.
+///
+/// Acknowledging an alert again keeps the original acknowledgement date.
+#[utoipa::path(
+    post, path = "/alert/{id}/ack", tag = TAG,
+    params(
+        ("id" = String, Path),
+        ("If-Match" = Option<String>, Header),
+        ("Idempotency-Key" = Option<String>, Header),
+    ),
+    responses(
+        (status = OK, body = StoredAlert, headers(("ETag" = String))),
+        (status = NOT_FOUND),
+        (status = PRECONDITION_FAILED),
+    ),
+)]
+pub async fn ack(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let mut db = data.db(&user)?;
+    let existing = db.get_alerts(&[&id])
+        .map_err(ErrorInternalServerError)?
+        .pop()
+        .ok_or_else(|| ErrorNotFound("alert not found"))?;
+    etag::check_if_match(&req, Some(&existing), false)?;
+    if existing.alert.acked.is_some() {
+        return Ok(etag::response(&req, &existing));
+    }
+    let alert = dbutil::ack_alert(&mut *db, &id, data.clock.now())
+        .map_err(ErrorInternalServerError)?;
+    Ok(etag::response(&req, &alert))
+}
+
+/// Stop an alert from being sent again for a while, starting now.  It's sent
+/// again afterwards, if it still applies.
+///
+/// Snoozing again replaces the previous snooze.
+#[utoipa::path(
+    post, path = "/alert/{id}/snooze", tag = TAG,
+    params(
+        ("id" = String, Path),
+        SnoozeQuery,
+        ("If-Match" = Option<String>, Header),
+        ("Idempotency-Key" = Option<String>, Header),
+    ),
+    responses(
+        (status = OK, body = StoredAlert, headers(("ETag" = String))),
+        (status = BAD_REQUEST),
+        (status = NOT_FOUND),
+        (status = PRECONDITION_FAILED),
+    ),
+)]
+pub async fn snooze(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+    query: web::Query<SnoozeQuery>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let duration = DurationParser.parse(&query.duration)
+        .map_err(ErrorBadRequest)?;
+    let mut db = data.db(&user)?;
+    let existing = db.get_alerts(&[&id])
+        .map_err(ErrorInternalServerError)?
+        .pop()
+        .ok_or_else(|| ErrorNotFound("alert not found"))?;
+    etag::check_if_match(&req, Some(&existing), false)?;
+    let alert = dbutil::snooze_alert(&mut *db, &id, duration, data.clock.now())
+        .map_err(ErrorInternalServerError)?;
+    Ok(etag::response(&req, &alert))
+}
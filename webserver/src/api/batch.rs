@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError,
+                       ErrorNotFound};
+use actix_web::{web, Responder};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use dunsumday::db::{ConfigId, Db, DbUpdate, IdToken, IdTokens, StoredConfig,
+                    StoredItem, StoredOcc, UpdateId};
+use dunsumday::types::{Config as ItemConfig, Item, Occ, ProgressEntry};
+use crate::api::{config, item};
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "batch";
+
+/// Reference to an object which already exists, or which is created earlier in
+/// the same batch.
+#[derive(Debug, Deserialize, ToSchema)]
+pub enum Ref {
+    Id(String),
+    /// The token given when creating the object.
+    Token(IdToken),
+}
+
+/// Operation in a batch.
+///
+/// Objects are created with a token chosen by the client, which must be unique
+/// within the batch.  Deleting an object which doesn't exist succeeds.
+#[derive(Debug, Deserialize, ToSchema)]
+pub enum Operation {
+    CreateItem { token: IdToken, item: Item },
+    UpdateItem { id: String, item: Item },
+    DeleteItem { id: String },
+    /// Create or update a config.  `scope` is as for the `/config` endpoints,
+    /// and must refer to an existing item or occurrence.
+    SetConfig { scope: String, config: ItemConfig },
+    DeleteConfig { scope: String },
+    CreateOcc { token: IdToken, item: Ref, occ: Occ },
+    UpdateOcc { id: String, occ: Occ },
+    DeleteOcc { id: String },
+    CreateProgressEntry { token: IdToken, occ: Ref, entry: ProgressEntry },
+    DeleteProgressEntry { id: String },
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchResult {
+    /// IDs of created objects, by token.
+    ids: HashMap<IdToken, String>,
+}
+
+/// Kind of object created with a token.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum TokenKind {
+    Item,
+    Occ,
+    ProgressEntry,
+}
+
+/// Database update for an operation which has been checked, owning its data.
+///
+/// Tokens are replaced with [new tokens](IdTokens) for the write.
+#[derive(Debug)]
+enum Checked {
+    CreateItem { token: IdToken, item: Item },
+    UpdateItem(StoredItem),
+    DeleteItem(String),
+    SetConfig(StoredConfig),
+    DeleteConfig(ConfigId),
+    CreateOcc { token: IdToken, item_id: CheckedRef, occ: Occ },
+    UpdateOcc(StoredOcc),
+    DeleteOcc(String),
+    CreateProgressEntry {
+        token: IdToken,
+        occ_id: CheckedRef,
+        entry: ProgressEntry,
+    },
+    DeleteProgressEntry(String),
+}
+
+/// [`Ref`] using a generated token.
+#[derive(Debug)]
+enum CheckedRef {
+    Id(String),
+    Token(IdToken),
+}
+
+impl CheckedRef {
+    fn update_id(&self) -> UpdateId<'_> {
+        match self {
+            CheckedRef::Id(id) => UpdateId::Id(id),
+            CheckedRef::Token(token) => UpdateId::Token(*token),
+        }
+    }
+}
+
+impl Checked {
+    fn update(&self) -> DbUpdate<'_> {
+        match self {
+            Checked::CreateItem { token, item } => {
+                DbUpdate::create_item(*token, item)
+            }
+            Checked::UpdateItem(item) => DbUpdate::update_item(item),
+            Checked::DeleteItem(id) => DbUpdate::delete_item(id),
+            Checked::SetConfig(config) => DbUpdate::set_config(config),
+            Checked::DeleteConfig(id) => DbUpdate::delete_config(id.clone()),
+            Checked::CreateOcc { token, item_id, occ } => {
+                DbUpdate::create_occ(*token, item_id.update_id(), occ)
+            }
+            Checked::UpdateOcc(occ) => DbUpdate::update_occ(occ),
+            Checked::DeleteOcc(id) => DbUpdate::delete_occ(id),
+            Checked::CreateProgressEntry { token, occ_id, entry } => {
+                DbUpdate::create_progress_entry(
+                    *token, occ_id.update_id(), entry)
+            }
+            Checked::DeleteProgressEntry(id) => {
+                DbUpdate::delete_progress_entry(id)
+            }
+        }
+    }
+}
+
+/// Checks operations in order, tracking the tokens created.
+struct Checker<'a> {
+    db: &'a dyn Db,
+    id_tokens: IdTokens,
+    /// Generated token and kind of object, by client token.
+    tokens: HashMap<IdToken, (IdToken, TokenKind)>,
+}
+
+impl Checker<'_> {
+    /// Generate a token for an object created with the client's `token`.
+    fn create(&mut self, token: IdToken, kind: TokenKind)
+    -> actix_web::Result<IdToken> {
+        if self.tokens.contains_key(&token) {
+            return Err(ErrorBadRequest(format!("duplicate token: {token}")));
+        }
+        let db_token = self.id_tokens.allocate();
+        self.tokens.insert(token, (db_token, kind));
+        Ok(db_token)
+    }
+
+    /// Resolve a reference to an object of type `kind`, which must exist.
+    fn resolve(&self, r: Ref, kind: TokenKind)
+    -> actix_web::Result<CheckedRef> {
+        match r {
+            Ref::Id(id) => {
+                let exists = match kind {
+                    TokenKind::Item => !self.db.get_items(&[&id])
+                        .map_err(ErrorInternalServerError)?.is_empty(),
+                    TokenKind::Occ => !self.db.get_occs(&[&id])
+                        .map_err(ErrorInternalServerError)?.is_empty(),
+                    // progress entries are never referenced
+                    TokenKind::ProgressEntry => false,
+                };
+                if exists {
+                    Ok(CheckedRef::Id(id))
+                } else {
+                    Err(ErrorNotFound(format!("object not found: {id}")))
+                }
+            }
+            Ref::Token(token) => match self.tokens.get(&token) {
+                Some((db_token, token_kind)) if *token_kind == kind => {
+                    Ok(CheckedRef::Token(*db_token))
+                }
+                _ => Err(ErrorBadRequest(format!(
+                    "token not created earlier in the batch: {token}"))),
+            },
+        }
+    }
+
+    fn check(&mut self, op: Operation) -> actix_web::Result<Checked> {
+        Ok(match op {
+            Operation::CreateItem { token, item } => {
+                item::check_item(&item)?;
+                let token = self.create(token, TokenKind::Item)?;
+                Checked::CreateItem { token, item }
+            }
+            Operation::UpdateItem { id, item } => {
+                item::check_item(&item)?;
+                let existing = self.db.get_items(&[&id])
+                    .map_err(ErrorInternalServerError)?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| ErrorNotFound("item not found"))?;
+                Checked::UpdateItem(StoredItem { item, ..existing })
+            }
+            Operation::DeleteItem { id } => Checked::DeleteItem(id),
+            Operation::SetConfig { scope, config } => {
+                let id = config::parse_scope(&scope)?;
+                config::check_scope_exists(self.db, &id)?;
+                Checked::SetConfig(StoredConfig { id, config })
+            }
+            Operation::DeleteConfig { scope } => {
+                Checked::DeleteConfig(config::parse_scope(&scope)?)
+            }
+            Operation::CreateOcc { token, item, occ } => {
+                let item_id = self.resolve(item, TokenKind::Item)?;
+                let token = self.create(token, TokenKind::Occ)?;
+                Checked::CreateOcc { token, item_id, occ }
+            }
+            Operation::UpdateOcc { id, occ } => {
+                self.resolve(Ref::Id(id.clone()), TokenKind::Occ)?;
+                Checked::UpdateOcc(StoredOcc { id, occ })
+            }
+            Operation::DeleteOcc { id } => Checked::DeleteOcc(id),
+            Operation::CreateProgressEntry { token, occ, entry } => {
+                let occ_id = self.resolve(occ, TokenKind::Occ)?;
+                let token = self.create(token, TokenKind::ProgressEntry)?;
+                Checked::CreateProgressEntry { token, occ_id, entry }
+            }
+            Operation::DeleteProgressEntry { id } => {
+                Checked::DeleteProgressEntry(id)
+            }
+        })
+    }
+}
+
+/// Perform multiple operations in order, atomically.
+///
+/// If any operation fails, none of them are performed, and the error message
+/// says which failed.  Occurrences are not generated for created items until
+/// they're next needed.
+#[utoipa::path(
+    post, path = "/batch", tag = TAG,
+    params(("Idempotency-Key" = Option<String>, Header)),
+    request_body = Vec<Operation>,
+    responses(
+        (status = OK, body = BatchResult),
+        (status = BAD_REQUEST),
+        (status = NOT_FOUND),
+    ),
+)]
+pub async fn post(
+    data: web::Data<server::State>,
+    user: User,
+    ops: web::Json<Vec<Operation>>,
+) -> actix_web::Result<impl Responder> {
+    let mut db = data.db(&user)?;
+    let mut checker = Checker {
+        db: &**db,
+        id_tokens: IdTokens::new(),
+        tokens: HashMap::new(),
+    };
+    let checked = ops.into_inner()
+        .into_iter()
+        .enumerate()
+        .map(|(i, op)| {
+            checker.check(op).map_err(|e| {
+                let status = e.as_response_error().status_code();
+                let message = format!("operation {i}: {e}");
+                actix_web::error::InternalError::new(message, status).into()
+            })
+        })
+        .collect::<actix_web::Result<Vec<_>>>()?;
+    let tokens = checker.tokens;
+
+    let updates = checked.iter().map(Checked::update).collect::<Vec<_>>();
+    let update_refs = updates.iter().collect::<Vec<_>>();
+    let mut db_ids = db.write(&update_refs)
+        .map_err(ErrorInternalServerError)?;
+    let ids = tokens.into_iter()
+        .filter_map(|(token, (db_token, _))| {
+            db_ids.remove(&db_token).map(|id| (token, id))
+        })
+        .collect();
+    Ok(web::Json(BatchResult { ids }))
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{web, Responder};
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use dunsumday::db::{SortDirection, StoredItem};
+use dunsumday::types::{Occ, OccDate};
+use dunsumday::util;
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "calendar";
+
+/// Period covered by a calendar.
+#[derive(Clone, Copy, Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum View {
+    #[default]
+    Week,
+    /// Every week which includes a day in the month.
+    Month,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Query {
+    /// Defaults to `week`.
+    #[serde(default)]
+    view: View,
+    /// Day in the week or month to show, in UTC.  Defaults to today.
+    #[param(value_type = Option<String>, format = Date)]
+    date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CalendarOcc {
+    item_id: String,
+    /// Missing for occurrences which haven't been generated yet.
+    id: Option<String>,
+    occ: Occ,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CalendarDay {
+    #[schema(value_type = String, format = Date)]
+    date: NaiveDate,
+    /// Occurrences overlapping the day, ordered by start date.
+    occs: Vec<CalendarOcc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Calendar {
+    /// Consecutive days in whole weeks, starting on Monday.
+    days: Vec<CalendarDay>,
+    /// Items with occurrences in the calendar, by ID.
+    items: HashMap<String, StoredItem>,
+}
+
+fn day_start(day: NaiveDate) -> OccDate {
+    day.and_time(NaiveTime::MIN).and_utc()
+}
+
+/// Get the first and last days shown for a view including `date`.
+fn view_days(view: View, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let (first, last) = match view {
+        View::Week => (date, date),
+        View::Month => {
+            let first = date.with_day(1).unwrap_or(date);
+            let last = first.checked_add_months(Months::new(1))
+                .and_then(|next| next.pred_opt())
+                .unwrap_or(date);
+            (first, last)
+        }
+    };
+    let first = first.week(chrono::Weekday::Mon).first_day();
+    let last = last.week(chrono::Weekday::Mon).last_day();
+    (first, last)
+}
+
+/// Get a calendar of all active items' occurrences, by day.
+///
+/// Occurrences which haven't been generated yet are included, but aren't
+/// stored.  Those for deadline tasks assume each occurrence is completed at its
+/// deadline.
+#[utoipa::path(
+    get, path = "/calendar", tag = TAG, params(Query),
+    responses((status = OK, body = Calendar), (status = BAD_REQUEST)),
+)]
+pub async fn get(
+    data: web::Data<server::State>,
+    user: User,
+    query: web::Query<Query>,
+) -> actix_web::Result<impl Responder> {
+    let now = data.clock.now();
+    let date = query.date.unwrap_or_else(|| now.date_naive());
+    let (first, last) = view_days(query.view, date);
+    let start = day_start(first);
+    let end = day_start(last.checked_add_days(Days::new(1)).unwrap_or(last));
+
+    let db = data.db(&user)?;
+    let items = db.find_items(Some(true), None, Some(start),
+                              SortDirection::Asc, None, u32::MAX)
+        .map_err(ErrorInternalServerError)?;
+    let item_refs = items.iter().collect::<Vec<_>>();
+    let occs = util::get_occs_in_range(&*db, &item_refs, start, end, now)
+        .map_err(ErrorInternalServerError)?;
+
+    let days = first.iter_days()
+        .take_while(|day| *day <= last)
+        .map(|day| {
+            let day_end = day_start(day.succ_opt().unwrap_or(day));
+            let day_occs = occs.iter()
+                .filter(|occ| {
+                    util::occ_overlaps(&occ.occ, day_start(day), day_end)
+                })
+                .map(|occ| CalendarOcc {
+                    item_id: occ.item.id.clone(),
+                    id: occ.id.clone(),
+                    occ: occ.occ.clone(),
+                })
+                .collect();
+            CalendarDay { date: day, occs: day_occs }
+        })
+        .collect();
+    let items = occs.iter()
+        .map(|occ| (occ.item.id.clone(), occ.item.clone()))
+        .collect();
+    Ok(web::Json(Calendar { days, items }))
+}
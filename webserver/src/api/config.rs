@@ -0,0 +1,141 @@
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError,
+                       ErrorNotFound};
+use actix_web::{web, Responder};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use dunsumday::db::{ConfigId, Db, StoredConfig};
+use dunsumday::db::util as dbutil;
+use dunsumday::types::Config as ItemConfig;
+use crate::{api, server};
+use crate::user::User;
+
+pub const TAG: &str = "config";
+
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = ScopedConfig)]
+pub struct Config {
+    /// Formatted like `all`, `type:Event`, `category:<category>`,
+    /// `item:<id>` or `occ:<id>`.
+    #[schema(value_type = String)]
+    scope: ConfigId,
+    config: ItemConfig,
+}
+
+impl From<StoredConfig> for Config {
+    fn from(config: StoredConfig) -> Config {
+        Config { scope: config.id, config: config.config }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListQuery {
+    /// Only include configs with this kind of scope, like `type` or `item`.
+    kind: Option<String>,
+}
+
+/// Parse a config scope, like `type:Event` (see [`ConfigId`]).
+///
+/// Scopes using `/` as the separator, like `type/Event`, are also accepted,
+/// since earlier versions used that form.
+pub fn parse_scope(scope: &str) -> actix_web::Result<ConfigId> {
+    scope.parse::<ConfigId>()
+        .or_else(|e| match scope.split_once('/') {
+            Some((kind, value)) => format!("{kind}:{value}").parse(),
+            None => Err(e),
+        })
+        .map_err(ErrorBadRequest)
+}
+
+/// Check that the item or occurrence a config applies to exists.
+pub fn check_scope_exists(db: &(impl Db + ?Sized), id: &ConfigId)
+-> actix_web::Result<()> {
+    let exists = match id {
+        ConfigId::Item { id } => !db.get_items(&[id])
+            .map_err(ErrorInternalServerError)?.is_empty(),
+        ConfigId::Occ { id } => !db.get_occs(&[id])
+            .map_err(ErrorInternalServerError)?.is_empty(),
+        _ => true,
+    };
+    if exists { Ok(()) } else { Err(ErrorNotFound("config scope not found")) }
+}
+
+#[utoipa::path(
+    get, path = "/config", tag = TAG, params(ListQuery),
+    responses((status = OK, body = Vec<Config>)),
+)]
+pub async fn list(
+    data: web::Data<server::State>,
+    user: User,
+    query: web::Query<ListQuery>,
+) -> actix_web::Result<impl Responder> {
+    let kind = query.into_inner().kind;
+    let configs = data.db(&user)?.find_configs()
+        .map_err(ErrorInternalServerError)?
+        .into_iter()
+        .filter(|config| kind.as_ref()
+            .is_none_or(|kind| kind == config.id.kind()))
+        .map(Config::from)
+        .collect::<Vec<_>>();
+    Ok(web::Json(configs))
+}
+
+#[utoipa::path(
+    get, path = "/config/{scope}", tag = TAG, params(("scope" = String, Path)),
+    responses(
+        (status = OK, body = Config),
+        (status = BAD_REQUEST),
+        (status = NOT_FOUND),
+    ),
+)]
+pub async fn get(
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+) -> actix_web::Result<impl Responder> {
+    let id = parse_scope(&path.into_inner())?;
+    let config = dbutil::get_config(&*data.db(&user)?, &id)
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("config not found"))?;
+    Ok(web::Json(Config::from(config)))
+}
+
+#[utoipa::path(
+    put, path = "/config/{scope}", tag = TAG, params(("scope" = String, Path)),
+    request_body = ItemConfig,
+    responses(
+        (status = OK, body = Config),
+        (status = BAD_REQUEST),
+        (status = NOT_FOUND),
+    ),
+)]
+pub async fn put(
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+    config: web::Json<ItemConfig>,
+) -> actix_web::Result<impl Responder> {
+    let id = parse_scope(&path.into_inner())?;
+    let mut db = data.db(&user)?;
+    check_scope_exists(&*db, &id)?;
+    let config = StoredConfig { id, config: config.into_inner() };
+    dbutil::set_config(&mut *db, &config)
+        .map_err(ErrorInternalServerError)?;
+    Ok(web::Json(Config::from(config)))
+}
+
+#[utoipa::path(
+    delete, path = "/config/{scope}", tag = TAG,
+    params(("scope" = String, Path)),
+    responses((status = NO_CONTENT), (status = BAD_REQUEST)),
+)]
+pub async fn delete(
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+) -> actix_web::Result<impl Responder> {
+    let id = parse_scope(&path.into_inner())?;
+    dbutil::delete_config(&mut *data.db(&user)?, &id)
+        .map_err(ErrorInternalServerError)?;
+    Ok(api::no_content())
+}
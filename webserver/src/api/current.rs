@@ -0,0 +1,87 @@
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{web, Responder};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use dunsumday::db::{Db, SortDirection, StoredItem, StoredOcc};
+use dunsumday::types::{ItemType, Occ, OccDate};
+use dunsumday::util;
+use dunsumday::util::progress::{self, TaskProgress};
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "current";
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Query {
+    /// Defaults to now.
+    #[param(value_type = Option<String>, format = DateTime)]
+    date: Option<OccDate>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CurrentOcc {
+    /// Missing if the occurrence hasn't been generated yet.
+    id: Option<String>,
+    occ: Occ,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CurrentItem {
+    item: StoredItem,
+    occ: CurrentOcc,
+    // missing for events
+    progress: Option<TaskProgress>,
+}
+
+#[utoipa::path(
+    get, path = "/current", tag = TAG, params(Query),
+    responses((status = OK, body = Vec<CurrentItem>)),
+)]
+pub async fn get(
+    data: web::Data<server::State>,
+    user: User,
+    query: web::Query<Query>,
+) -> actix_web::Result<impl Responder> {
+    let date = query.into_inner().date.unwrap_or_else(|| data.clock.now());
+    let db = data.db(&user)?;
+    let items = db.find_items(
+        Some(true), None, Some(date), SortDirection::Asc, None, u32::MAX)
+        .map_err(ErrorInternalServerError)?;
+    let item_refs = items.iter().collect::<Vec<_>>();
+    // generated occurrences aren't stored, so this doesn't change anything
+    let current = util::preview_items_current_occ(&*db, date, &item_refs)
+        .map_err(ErrorInternalServerError)?
+        .into_iter()
+        .filter_map(|preview| preview.current)
+        .collect::<Vec<_>>();
+
+    let stored = current.iter()
+        .filter_map(|current| {
+            current.id.clone().map(|id| {
+                (current.item, StoredOcc { id, occ: current.occ.clone() })
+            })
+        })
+        .collect::<Vec<_>>();
+    let stored_refs = stored.iter()
+        .map(|(item, occ)| (*item, occ))
+        .collect::<Vec<_>>();
+    let mut progress = progress::resolve_item_occs_progress(&*db, &stored_refs)
+        .map_err(ErrorInternalServerError)?;
+
+    let mut current_items = Vec::new();
+    for current in current {
+        let occ_progress = match &current.id {
+            Some(id) => progress.remove(id),
+            None if current.item.item.type_ == ItemType::Event => None,
+            None => Some(progress::new_occ_progress(&*db, current.item)
+                .map_err(ErrorInternalServerError)?),
+        };
+        current_items.push(CurrentItem {
+            item: current.item.clone(),
+            occ: CurrentOcc { id: current.id, occ: current.occ },
+            progress: occ_progress,
+        });
+    }
+    Ok(web::Json(current_items))
+}
@@ -0,0 +1,30 @@
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{web, Responder};
+use chrono::TimeDelta;
+use dunsumday::util::dashboard::{self, CategoryHealth};
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "dashboard";
+
+/// Days covered by completion rates.
+const PERIOD_DAYS: i64 = 30;
+
+/// Get an overview of each category: its active items, items due today,
+/// completion rate over the last 30 days, and the item with the lowest
+/// completion rate.
+///
+/// Items without a category aren't included.
+#[utoipa::path(
+    get, path = "/dashboard", tag = TAG,
+    responses((status = OK, body = Vec<CategoryHealth>)),
+)]
+pub async fn get(
+    data: web::Data<server::State>,
+    user: User,
+) -> actix_web::Result<impl Responder> {
+    let categories = dashboard::categories(
+        &*data.db(&user)?, &*data.clock, TimeDelta::days(PERIOD_DAYS))
+        .map_err(ErrorInternalServerError)?;
+    Ok(web::Json(categories))
+}
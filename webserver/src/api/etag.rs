@@ -0,0 +1,72 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use actix_web::error::{ErrorPreconditionFailed, ErrorPreconditionRequired};
+use actix_web::http::header::{self, EntityTag};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse};
+use serde::Serialize;
+
+/// Get the entity tag for a stored object.
+///
+/// For items, this covers the `updated` timestamp, which is only precise to
+/// the second, so the rest of the object is included to tell apart updates
+/// made within the same second.
+pub fn etag<T: Hash>(obj: &T) -> EntityTag {
+    let mut hasher = DefaultHasher::new();
+    obj.hash(&mut hasher);
+    EntityTag::new_strong(format!("{:016x}", hasher.finish()))
+}
+
+/// Respond with `obj` and its entity tag, or with `304 Not Modified` if the
+/// request's `If-None-Match` header matches the tag.
+pub fn response<T>(req: &HttpRequest, obj: &T) -> HttpResponse
+where
+    T: Hash + Serialize,
+{
+    let etag = etag(obj);
+    let not_modified = match req.get_header::<header::IfNoneMatch>() {
+        Some(header::IfNoneMatch::Any) => true,
+        Some(header::IfNoneMatch::Items(tags)) => {
+            tags.iter().any(|tag| tag.weak_eq(&etag))
+        }
+        None => false,
+    };
+
+    if not_modified {
+        HttpResponse::NotModified()
+            .insert_header(header::ETag(etag))
+            .finish()
+    } else {
+        HttpResponse::Ok()
+            .insert_header(header::ETag(etag))
+            .json(obj)
+    }
+}
+
+/// Check the request's `If-Match` header against the current version of an
+/// object, or `None` if it doesn't exist.
+///
+/// If `required` is true, requests without the header are rejected, so that
+/// clients must show which version they are replacing.
+pub fn check_if_match<T: Hash>(
+    req: &HttpRequest,
+    current: Option<&T>,
+    required: bool,
+) -> actix_web::Result<()> {
+    let matches = match (req.get_header::<header::IfMatch>(), current) {
+        (None, _) if required => {
+            return Err(ErrorPreconditionRequired("If-Match header required"))
+        }
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(header::IfMatch::Any), Some(_)) => true,
+        (Some(header::IfMatch::Items(tags)), Some(current)) => {
+            let etag = etag(current);
+            tags.iter().any(|tag| tag.strong_eq(&etag))
+        }
+    };
+
+    if matches {
+        Ok(())
+    } else {
+        Err(ErrorPreconditionFailed("object has been modified"))
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::rt::time;
+use actix_web::{web, HttpResponse, Responder};
+use bytes::Bytes;
+use futures_util::stream;
+use serde::Serialize;
+use utoipa::ToSchema;
+use dunsumday::db::StoredItem;
+use dunsumday::util::status::{OccStatus, StatusTracker, TrackedOcc};
+use crate::{configrefs, server};
+use crate::user::User;
+
+pub const TAG: &str = "events";
+
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Sent when an occurrence's status changes.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OccEvent {
+    item: StoredItem,
+    occ: TrackedOcc,
+    status: OccStatus,
+}
+
+impl OccEvent {
+    /// Format as a server-sent event, named after the status.
+    fn encode(&self) -> Result<String, String> {
+        let name = match self.status {
+            OccStatus::Idle => "idle",
+            OccStatus::Alert => "alert",
+            OccStatus::Complete => "complete",
+            OccStatus::Overdue => "overdue",
+        };
+        let data = serde_json::to_string(self)
+            .map_err(|e| format!("error serialising event: {e}"))?;
+        Ok(format!("event: {name}\ndata: {data}\n\n"))
+    }
+}
+
+/// Tracks occurrence statuses for a single client.
+struct Watcher {
+    data: web::Data<server::State>,
+    user: User,
+    interval: time::Interval,
+    tracker: StatusTracker,
+}
+
+impl Watcher {
+    /// Check occurrence statuses, returning events for those which changed.
+    fn check(&mut self) -> Result<Vec<OccEvent>, String> {
+        let default_alert = self.data.cfg()
+            .get_parsed(&dunsumday::configrefs::OCC_ALERT_DEFAULT)?;
+        let db = self.data.db(&self.user).map_err(|e| e.to_string())?;
+        let changed = self.tracker.check(
+            &*db, default_alert, self.data.clock.now())?;
+        Ok(changed.into_iter()
+            .filter(|(_, _, status)| *status != OccStatus::Idle)
+            .map(|(item, occ, status)| OccEvent { item, occ, status })
+            .collect())
+    }
+
+    /// Wait until the next check, and encode the resulting events.
+    async fn next(&mut self) -> Bytes {
+        self.interval.tick().await;
+        let encoded = self.check()
+            .and_then(|events| {
+                events.iter()
+                    .map(OccEvent::encode)
+                    .collect::<Result<Vec<_>, _>>()
+            });
+        match encoded {
+            // a comment keeps the connection alive
+            Ok(events) if events.is_empty() => Bytes::from_static(b":\n\n"),
+            Ok(events) => Bytes::from(events.concat()),
+            Err(e) => {
+                tracing::error!("error checking occurrence statuses: {e}");
+                Bytes::from_static(b":\n\n")
+            }
+        }
+    }
+}
+
+/// Stream occurrence status changes as server-sent events.
+///
+/// Events are sent when one of the user's occurrences enters its alert period,
+/// is completed or becomes overdue, and are named `alert`, `complete` and
+/// `overdue` respectively.  When the stream starts, events are sent for
+/// current occurrences which are already in one of these states.  Current
+/// occurrences which haven't been generated yet have no ID.
+#[utoipa::path(
+    get, path = "/events", tag = TAG,
+    responses((
+        status = OK, content_type = "text/event-stream", body = OccEvent,
+        description = "each event's data is a JSON-encoded `OccEvent`",
+    )),
+)]
+pub async fn get(
+    data: web::Data<server::State>,
+    user: User,
+) -> actix_web::Result<impl Responder> {
+    let period = data.cfg().get_parsed(&configrefs::API_EVENTS_INTERVAL)
+        .map_err(ErrorInternalServerError)?
+        .max(MIN_INTERVAL);
+    let watcher = Watcher {
+        data,
+        user,
+        interval: time::interval(period),
+        tracker: StatusTracker::new(),
+    };
+    let events = stream::unfold(watcher, |mut watcher| async move {
+        let chunk = watcher.next().await;
+        Some((Ok::<_, actix_web::Error>(chunk), watcher))
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .streaming(events))
+}
@@ -0,0 +1,154 @@
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{web, HttpResponse, Responder};
+use bytes::Bytes;
+use chrono::{NaiveDate, NaiveTime};
+use futures_util::stream;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use dunsumday::types::OccDate;
+use dunsumday::util::export::{self, Export, Format, OccStream,
+                              StreamedOcc};
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "export";
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Items and occurrences.
+    #[default]
+    Json,
+    /// A row for each occurrence, with its item.
+    Csv,
+    /// Events for occurrences.
+    Ics,
+    /// Taskwarrior tasks for task occurrences.
+    Taskwarrior,
+    /// A checklist of task occurrences, grouped by day and category.
+    Markdown,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    /// Defaults to `json`.
+    #[serde(default)]
+    format: ExportFormat,
+    /// Only include occurrences which end on or after this day, in UTC.
+    #[param(value_type = Option<String>, format = Date)]
+    from: Option<NaiveDate>,
+    /// Only include occurrences which start on or before this day, in UTC.
+    #[param(value_type = Option<String>, format = Date)]
+    to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OccsQuery {
+    /// Only include occurrences which end on or after this day, in UTC.
+    #[param(value_type = Option<String>, format = Date)]
+    from: Option<NaiveDate>,
+    /// Only include occurrences which start on or before this day, in UTC.
+    #[param(value_type = Option<String>, format = Date)]
+    to: Option<NaiveDate>,
+}
+
+fn day_start(day: NaiveDate) -> OccDate {
+    day.and_time(NaiveTime::MIN).and_utc()
+}
+
+/// Export all items, and occurrences with their progress.
+///
+/// The `taskwarrior` format is a JSON array of tasks, rather than the
+/// structure described here.
+#[utoipa::path(
+    get, path = "/export", tag = TAG,
+    params(ExportQuery),
+    responses(
+        (status = OK, content(
+            (Export = "application/json"),
+            (String = "text/csv"),
+            (String = "text/calendar"),
+            (String = "text/markdown"),
+        )),
+    ),
+)]
+pub async fn get(
+    data: web::Data<server::State>,
+    user: User,
+    query: web::Query<ExportQuery>,
+) -> actix_web::Result<impl Responder> {
+    let format = match query.format {
+        ExportFormat::Json => Format::Json,
+        ExportFormat::Csv => Format::Csv,
+        ExportFormat::Ics => Format::Ics,
+        ExportFormat::Taskwarrior => Format::Taskwarrior,
+        ExportFormat::Markdown => Format::Markdown,
+    };
+    let start = query.from.map(day_start);
+    let end = query.to.and_then(|day| day.succ_opt()).map(day_start);
+    let export = export::fetch(&*data.db(&user)?, start, end)
+        .map_err(ErrorInternalServerError)?;
+    let body = export.write(format, data.clock.now())
+        .map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok()
+        .content_type(format!("{}; charset=utf-8", format.content_type()))
+        .body(body))
+}
+
+/// Export occurrences one page at a time, for a single client.
+struct Streamer {
+    data: web::Data<server::State>,
+    user: User,
+    occs: OccStream,
+}
+
+impl Streamer {
+    /// Write the next page of occurrences, or return `None` when there are no
+    /// more.  The database is only locked while fetching the page.
+    fn next(&mut self) -> actix_web::Result<Option<Bytes>> {
+        let db = self.data.db(&self.user)?;
+        let page = self.occs.next_page(&*db)
+            .map_err(ErrorInternalServerError)?;
+        Ok(page.map(Bytes::from))
+    }
+}
+
+/// Export occurrences with their items and progress, as
+/// [JSON Lines](https://jsonlines.org).
+///
+/// Each line is a `StreamedOcc`, and occurrences are ordered by start date.
+/// The export is streamed, so it can be arbitrarily large.
+#[utoipa::path(
+    get, path = "/export/occs.jsonl", tag = TAG,
+    params(OccsQuery),
+    responses((
+        status = OK, content_type = "application/jsonl", body = StreamedOcc,
+        description = "each line is a JSON-encoded `StreamedOcc`",
+    )),
+)]
+pub async fn get_occs(
+    data: web::Data<server::State>,
+    user: User,
+    query: web::Query<OccsQuery>,
+) -> actix_web::Result<impl Responder> {
+    let start = query.from.map(day_start);
+    let end = query.to.and_then(|day| day.succ_opt()).map(day_start);
+    let streamer = Streamer { data, user, occs: OccStream::new(start, end) };
+    let lines = stream::unfold(Some(streamer), |streamer| async move {
+        let mut streamer = streamer?;
+        match streamer.next() {
+            Ok(Some(page)) => Some((Ok(page), Some(streamer))),
+            Ok(None) => None,
+            // the response has started, so failing ends it early
+            Err(e) => {
+                tracing::error!("error exporting occurrences: {e}");
+                Some((Err(e), None))
+            }
+        }
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("application/jsonl; charset=utf-8")
+        .streaming(lines))
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use actix_web::error::{ErrorForbidden, ErrorInternalServerError,
+                       ErrorNotFound};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::TimeDelta;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use dunsumday::config::cached;
+use dunsumday::db::{SortDirection, StoredItem};
+use dunsumday::util::ical::{self, TaskFormat};
+use dunsumday::util::progress;
+use crate::api::{self, openapi};
+use crate::configrefs;
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "feed";
+pub const FEED_PATH: &str = "/feed.ics";
+
+const CALENDAR_NAME: &str = "dunsumday";
+/// Separates the user ID from the signature in a token.
+const TOKEN_SEPARATOR: char = '.';
+
+/// How to represent task occurrences in the feed.
+#[derive(Clone, Copy, Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedTaskFormat {
+    /// Events at each occurrence's due date.
+    #[default]
+    Event,
+    /// To-dos with start and due dates.
+    Todo,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FeedQuery {
+    /// Token from the feed URL.
+    token: String,
+    /// Defaults to `event`.
+    tasks: Option<FeedTaskFormat>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FeedUrl {
+    url: String,
+}
+
+/// Get the key used to sign feed tokens, or `None` if the feed is disabled.
+fn key(cfg: &cached::Config) -> actix_web::Result<Option<hmac::Key>> {
+    let secret = cfg.get_parsed(&configrefs::API_FEED_SECRET)
+        .map_err(ErrorInternalServerError)?;
+    Ok((!secret.is_empty())
+        .then(|| hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes())))
+}
+
+/// Message signed to create a token for the user with database ID `id`.
+fn token_message(id: Option<&str>) -> String {
+    format!("feed\0{}", id.unwrap_or_default())
+}
+
+/// Create the token that identifies `user` when reading the feed.
+fn token(key: &hmac::Key, user: &User) -> String {
+    let tag = hmac::sign(key, token_message(user.id.as_deref()).as_bytes());
+    let signature = hex::encode(tag.as_ref());
+    match &user.id {
+        Some(id) => format!("{id}{TOKEN_SEPARATOR}{signature}"),
+        None => signature,
+    }
+}
+
+/// Determine the user identified by a token, if it's valid.
+fn check_token(key: &hmac::Key, token: &str) -> Option<User> {
+    let (id, signature) = match token.rsplit_once(TOKEN_SEPARATOR) {
+        Some((id, signature)) => (Some(id), signature),
+        None => (None, token),
+    };
+    let signature = hex::decode(signature).ok()?;
+    hmac::verify(key, token_message(id).as_bytes(), &signature).ok()?;
+    Some(User { id: id.map(|id| id.to_owned()) })
+}
+
+/// Get an iCalendar feed of upcoming occurrences, for subscribing to from
+/// calendar applications.
+///
+/// This uses a token instead of the usual authentication, since calendar
+/// applications usually only support a URL.  Get the URL, including the token,
+/// from `/feed`.
+#[utoipa::path(
+    get, path = FEED_PATH, tag = TAG, params(FeedQuery),
+    responses(
+        (status = OK, content_type = "text/calendar", body = String),
+        (status = FORBIDDEN),
+        (status = NOT_FOUND, description = "the feed is disabled"),
+    ),
+)]
+pub async fn get(
+    data: web::Data<server::State>,
+    query: web::Query<FeedQuery>,
+) -> actix_web::Result<impl Responder> {
+    let cfg = data.cfg();
+    let key = key(&cfg)?.ok_or_else(|| ErrorNotFound("feed not enabled"))?;
+    let user = check_token(&key, &query.token)
+        .ok_or_else(|| ErrorForbidden("invalid token"))?;
+    let period = cfg.get_parsed(&configrefs::API_FEED_PERIOD)
+        .map_err(ErrorInternalServerError)?;
+    let now = data.clock.now();
+    let end = TimeDelta::from_std(period).ok()
+        .and_then(|period| now.checked_add_signed(period));
+
+    // occurrences are generated in the background, so reading the feed
+    // doesn't change anything
+    let db = data.db(&user)?;
+    let items = db.find_items(Some(true), None, Some(now), SortDirection::Asc,
+                              None, u32::MAX)
+        .map_err(ErrorInternalServerError)?;
+    let item_ids = items.iter()
+        .map(|item| item.id.as_str())
+        .collect::<Vec<_>>();
+    let mut occs_by_item = db.find_occs(&item_ids, Some(now), end,
+                                        SortDirection::Asc, None, u32::MAX)
+        .map_err(ErrorInternalServerError)?;
+    let items_by_id = items.iter()
+        .map(|item| (item.id.as_str(), item))
+        .collect::<HashMap<&str, &StoredItem>>();
+    let mut occs = occs_by_item.drain()
+        .filter_map(|(item_id, occs)| {
+            items_by_id.get(item_id.as_str()).map(|item| (*item, occs))
+        })
+        .flat_map(|(item, occs)| occs.into_iter().map(move |occ| (item, occ)))
+        .filter(|(_, occ)| occ.occ.active)
+        .collect::<Vec<_>>();
+    occs.sort_by_key(|(_, occ)| occ.occ.start);
+    let occ_refs = occs.iter()
+        .map(|(item, occ)| (*item, occ))
+        .collect::<Vec<_>>();
+    let progress = progress::resolve_item_occs_progress(&*db, &occ_refs)
+        .map_err(ErrorInternalServerError)?;
+
+    let task_format = match query.tasks.unwrap_or_default() {
+        FeedTaskFormat::Event => TaskFormat::Event,
+        FeedTaskFormat::Todo => TaskFormat::Todo,
+    };
+    Ok(HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .body(ical::calendar(
+            CALENDAR_NAME, &occ_refs, &progress, task_format, now)))
+}
+
+/// Get the URL of the user's iCalendar feed.
+///
+/// Anyone with the URL can read the feed.
+#[utoipa::path(
+    get, path = "/feed", tag = TAG,
+    responses(
+        (status = OK, body = FeedUrl),
+        (status = NOT_FOUND, description = "the feed is disabled"),
+    ),
+)]
+pub async fn get_url(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    user: User,
+) -> actix_web::Result<impl Responder> {
+    let cfg = data.cfg();
+    let key = key(&cfg)?.ok_or_else(|| ErrorNotFound("feed not enabled"))?;
+    let query = serde_urlencoded::to_string([("token", token(&key, &user))])
+        .map_err(ErrorInternalServerError)?;
+    let conn = req.connection_info();
+    let path = api::join_path(openapi::api_path(cfg.as_ref()), FEED_PATH);
+    Ok(web::Json(FeedUrl {
+        url: format!("{}://{}{path}?{query}", conn.scheme(), conn.host()),
+    }))
+}
@@ -0,0 +1,253 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use actix_http::h1;
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::{ErrorBadRequest, ErrorConflict,
+                       ErrorInternalServerError, ErrorPayloadTooLarge,
+                       ErrorUnprocessableEntity};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpMessage, HttpResponse};
+use bytes::{Bytes, BytesMut};
+use futures_util::StreamExt;
+use crate::{configrefs, server};
+use crate::user::User;
+
+/// Request header containing the idempotency key.
+const KEY_HEADER: HeaderName = HeaderName::from_static("idempotency-key");
+/// Response header set when a stored response is sent again.
+const REPLAYED_HEADER: HeaderName =
+    HeaderName::from_static("idempotent-replayed");
+const MAX_KEY_LEN: usize = 255;
+
+/// Idempotency key, along with the ID of the user who sent it.
+type Key = (Option<String>, String);
+
+#[derive(Debug)]
+struct StoredResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl StoredResponse {
+    fn replay(&self) -> HttpResponse {
+        let mut response = HttpResponse::with_body(
+            self.status, BoxBody::new(self.body.clone()));
+        for (name, value) in &self.headers {
+            response.headers_mut().append(name.clone(), value.clone());
+        }
+        response.headers_mut()
+            .insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+        response
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    created: Instant,
+    /// Hash of the request's method, path, query and body.
+    fingerprint: u64,
+    /// Missing while the request is being handled.
+    response: Option<StoredResponse>,
+}
+
+/// Result of looking up a request's idempotency key.
+enum Lookup<'a> {
+    /// The key hasn't been used, and is now reserved for this request.
+    New(Reservation<'a>),
+    InProgress,
+    /// The key was used for a different request.
+    Mismatch,
+    Done(HttpResponse),
+}
+
+/// Key reserved for a request which is being handled.  The key is forgotten
+/// when this is dropped, unless a response was stored, so that the request
+/// can be retried if handling it fails or is cancelled.
+struct Reservation<'a> {
+    store: &'a Store,
+    key: Key,
+    stored: bool,
+}
+
+impl Reservation<'_> {
+    /// Store the response to the request.
+    fn complete(mut self, response: StoredResponse) {
+        let mut entries = self.store.entries();
+        if let Some(entry) = entries.by_key.get_mut(&self.key) {
+            entry.response = Some(response);
+        }
+        self.stored = true;
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if !self.stored {
+            self.store.entries().by_key.remove(&self.key);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    by_key: HashMap<Key, Entry>,
+    /// Keys in the order they were first used, with the time they were used.
+    /// This may contain keys which have since been forgotten or used again.
+    by_age: VecDeque<(Instant, Key)>,
+}
+
+impl Entries {
+    /// Forget keys used longer ago than `window`.
+    fn prune(&mut self, now: Instant, window: Duration) {
+        while let Some((created, _)) = self.by_age.front() {
+            if now.duration_since(*created) < window {
+                break;
+            }
+            let Some((created, key)) = self.by_age.pop_front() else { break };
+            if self.by_key.get(&key)
+                .is_some_and(|entry| entry.created == created)
+            {
+                self.by_key.remove(&key);
+            }
+        }
+    }
+}
+
+/// Responses to requests with idempotency keys, shared between workers.
+#[derive(Debug, Default)]
+pub struct Store {
+    entries: Mutex<Entries>,
+}
+
+impl Store {
+    fn entries(&self) -> MutexGuard<'_, Entries> {
+        // entries are always left complete
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Look up a key, forgetting keys used longer ago than `window`.
+    fn lookup(&self, key: Key, fingerprint: u64, window: Duration)
+    -> Lookup<'_> {
+        let mut entries = self.entries();
+        let now = Instant::now();
+        entries.prune(now, window);
+        match entries.by_key.get(&key) {
+            Some(entry) if entry.fingerprint != fingerprint => Lookup::Mismatch,
+            Some(Entry { response: Some(response), .. }) => {
+                Lookup::Done(response.replay())
+            }
+            Some(Entry { response: None, .. }) => Lookup::InProgress,
+            None => {
+                entries.by_key.insert(key.clone(), Entry {
+                    created: now,
+                    fingerprint,
+                    response: None,
+                });
+                entries.by_age.push_back((now, key.clone()));
+                Lookup::New(Reservation { store: self, key, stored: false })
+            }
+        }
+    }
+}
+
+fn fingerprint(req: &ServiceRequest, body: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (req.method().as_str(), req.path(), req.query_string(), body)
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Handle a request whose key was reserved, storing the response unless it's
+/// a server error.
+async fn handle(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+    reservation: Reservation<'_>,
+) -> actix_web::Result<ServiceResponse<BoxBody>> {
+    let res = match next.call(req).await {
+        Ok(res) if !res.status().is_server_error() => res,
+        // retrying may succeed, so the key is forgotten
+        res => return res.map(ServiceResponse::map_into_boxed_body),
+    };
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let body = body::to_bytes(body).await
+        .map_err(|e| ErrorInternalServerError(e.into().to_string()))?;
+    reservation.complete(StoredResponse {
+        status: res.status(),
+        headers: res.headers().clone(),
+        body: body.clone(),
+    });
+    Ok(ServiceResponse::new(req, res.set_body(BoxBody::new(body))))
+}
+
+/// Middleware making `POST` requests with an idempotency key safe to retry.
+///
+/// The response to the first request with each key is stored, and sent again
+/// for later requests with the same key by the same user, without handling
+/// them.  Keys are forgotten after [`configrefs::API_IDEMPOTENCY_WINDOW`].
+/// Server errors aren't stored, and neither are requests which fail or are
+/// cancelled before a response is ready.  Requests with a body larger than
+/// [`configrefs::API_IDEMPOTENCY_MAX_BODY_SIZE`] are rejected.
+pub async fn middleware(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> actix_web::Result<ServiceResponse<BoxBody>> {
+    let header = req.headers().get(KEY_HEADER);
+    let Some(header) = header.filter(|_| req.method() == Method::POST) else {
+        return next.call(req).await
+            .map(ServiceResponse::map_into_boxed_body);
+    };
+    let key = header.to_str()
+        .map_err(|e| ErrorBadRequest(format!("invalid idempotency key: {e}")))?
+        .trim()
+        .to_owned();
+    if key.is_empty() || key.len() > MAX_KEY_LEN {
+        return Err(ErrorBadRequest("invalid idempotency key"));
+    }
+
+    let data = req.app_data::<web::Data<server::State>>()
+        .ok_or_else(|| ErrorInternalServerError("server state missing"))?;
+    let window = data.cfg().get_parsed(&configrefs::API_IDEMPOTENCY_WINDOW)
+        .map_err(ErrorInternalServerError)?;
+    let max_size = data.cfg()
+        .get_parsed(&configrefs::API_IDEMPOTENCY_MAX_BODY_SIZE)
+        .map_err(ErrorInternalServerError)?;
+    let store = req.app_data::<web::Data<Store>>()
+        .ok_or_else(|| ErrorInternalServerError("idempotency store missing"))?
+        .clone();
+    let user = req.extract::<User>().await?;
+    // the whole body is needed for the fingerprint
+    let mut body = BytesMut::new();
+    let mut payload = req.take_payload();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if body.len() + chunk.len() > max_size {
+            return Err(ErrorPayloadTooLarge("request body is too large"));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let body = body.freeze();
+    let fingerprint = fingerprint(&req, &body);
+    let (_, mut payload) = h1::Payload::create(true);
+    payload.unread_data(body);
+    req.set_payload(payload.into());
+
+    let key = (user.id, key);
+    let response = match store.lookup(key, fingerprint, window) {
+        Lookup::New(reservation) => handle(req, next, reservation).await,
+        Lookup::InProgress => Err(ErrorConflict(
+            "request with this idempotency key is in progress")),
+        Lookup::Mismatch => Err(ErrorUnprocessableEntity(
+            "idempotency key was used for a different request")),
+        Lookup::Done(response) => Ok(req.into_response(response)),
+    };
+    response
+}
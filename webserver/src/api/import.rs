@@ -0,0 +1,44 @@
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::{web, Responder};
+use bytes::Bytes;
+use serde::Serialize;
+use utoipa::ToSchema;
+use dunsumday::db::StoredItem;
+use dunsumday::db::util as dbutil;
+use dunsumday::util::import;
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "import";
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportResult {
+    /// Created items, in the order they were found.
+    items: Vec<StoredItem>,
+    /// Description of each entry which couldn't be imported.
+    skipped: Vec<String>,
+}
+
+/// Create items from the events in an iCalendar object.
+///
+/// Recurring events become items with equivalent schedules where possible, and
+/// other events occur once.  Events with recurrence rules which can't be
+/// represented are skipped.  Either all items are created, or none are.
+#[utoipa::path(
+    post, path = "/import/ics", tag = TAG,
+    request_body(content = String, content_type = "text/calendar"),
+    responses(
+        (status = OK, body = ImportResult),
+        (status = BAD_REQUEST, description = "the calendar is invalid"),
+    ),
+)]
+pub async fn ics(
+    data: web::Data<server::State>,
+    user: User,
+    body: Bytes,
+) -> actix_web::Result<impl Responder> {
+    let imported = import::ics(&body[..]).map_err(ErrorBadRequest)?;
+    let items = dbutil::create_items(&mut *data.db(&user)?, &imported.items)
+        .map_err(ErrorInternalServerError)?;
+    Ok(web::Json(ImportResult { items, skipped: imported.skipped }))
+}
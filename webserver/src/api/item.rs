@@ -1,29 +1,332 @@
-use std::fmt::Debug;
-use actix_web::error::ErrorInternalServerError;
-use actix_web::{web, Responder};
-use serde::{Deserialize, Serialize};
-use dunsumday::db::SortDirection;
-use crate::{constant, api, server};
-
-#[derive(Debug, Deserialize, Serialize)]
-pub struct Item { name: String }
-
-#[derive(Debug, Deserialize, Serialize)]
-pub struct NewItem { name: String }
-
-pub async fn list(data: web::Data<server::State>)
--> actix_web::Result<impl Responder> {
-    let items = data.db
-        .find_items(
-            Some(true), None, SortDirection::Asc, constant::ITEMS_PAGE_SIZE)
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError,
+                       ErrorNotFound};
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use dunsumday::db::{StoredItem, StoredOcc};
+use dunsumday::db::util as dbutil;
+use dunsumday::types::{Item, OccDate};
+use dunsumday::util::history::{self, HistoryEntry};
+use dunsumday::util::progress::{self, Forecast, ProgressError};
+use crate::api::{self, etag, occ::Progress, page::{self, PageQuery}};
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "item";
+
+/// Which items to include, by whether they're active.
+#[derive(Clone, Copy, Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemStatus {
+    #[default]
+    Active,
+    Inactive,
+    All,
+}
+
+impl ItemStatus {
+    fn active(self) -> Option<bool> {
+        match self {
+            ItemStatus::Active => Some(true),
+            ItemStatus::Inactive => Some(false),
+            ItemStatus::All => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ItemsQuery {
+    /// Defaults to `active`.
+    #[serde(default)]
+    status: ItemStatus,
+    /// Only include items with this category.
+    category: Option<String>,
+    /// Only include items which are recurring, or which occur after this date.
+    #[param(value_type = Option<String>, format = DateTime)]
+    start: Option<OccDate>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OccsQuery {
+    /// Only include occurrences ending after this date.
+    #[param(value_type = Option<String>, format = DateTime)]
+    start: Option<OccDate>,
+    /// Only include occurrences starting before this date.
+    #[param(value_type = Option<String>, format = DateTime)]
+    end: Option<OccDate>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DoneQuery {
+    /// Amount of progress to add.  Defaults to the amount needed to complete
+    /// the occurrence.
+    amount: Option<i64>,
+    /// Note to record with the progress.
+    note: Option<String>,
+}
+
+pub fn check_item(item: &Item) -> actix_web::Result<()> {
+    if item.sched.item_type() == item.type_ {
+        Ok(())
+    } else {
+        Err(ErrorBadRequest("item schedule does not match item type"))
+    }
+}
+
+#[utoipa::path(
+    get, path = "/item", tag = TAG, params(ItemsQuery, PageQuery),
+    responses(
+        (status = OK, body = Vec<StoredItem>, headers(
+            ("Link" = String, description = "URL of the next page"))),
+        (status = BAD_REQUEST),
+    ),
+)]
+pub async fn list(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    user: User,
+    query: web::Query<ItemsQuery>,
+    page_query: web::Query<PageQuery>,
+) -> actix_web::Result<impl Responder> {
+    let limit = page_query.limit(&data.cfg())?;
+    let items = data.db(&user)?
+        .find_items(query.status.active(), query.category.as_deref(),
+                    query.start, page_query.sort(), page_query.cursor(),
+                    limit + 1)
+        .map_err(ErrorInternalServerError)?;
+    page::response(&req, items, limit, |item| &item.id)
+}
+
+#[utoipa::path(
+    post, path = "/item", tag = TAG,
+    params(("Idempotency-Key" = Option<String>, Header)),
+    request_body = Item,
+    responses(
+        (status = CREATED, body = StoredItem, headers(("ETag" = String))),
+        (status = BAD_REQUEST),
+    ),
+)]
+pub async fn post(
+    data: web::Data<server::State>,
+    user: User,
+    item: web::Json<Item>,
+) -> actix_web::Result<impl Responder> {
+    let item = item.into_inner();
+    check_item(&item)?;
+    let stored_item = dbutil::create_item(&mut *data.db(&user)?, item)
+        .map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Created()
+        .insert_header(header::ETag(etag::etag(&stored_item)))
+        .json(stored_item))
+}
+
+#[utoipa::path(
+    get, path = "/item/{id}", tag = TAG,
+    params(
+        ("id" = String, Path),
+        ("If-None-Match" = Option<String>, Header),
+    ),
+    responses(
+        (status = OK, body = StoredItem, headers(("ETag" = String))),
+        (status = NOT_MODIFIED),
+        (status = NOT_FOUND),
+    ),
+)]
+pub async fn get(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let item = data.db(&user)?.get_items(&[&id])
+        .map_err(ErrorInternalServerError)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| ErrorNotFound("item not found"))?;
+    Ok(etag::response(&req, &item))
+}
+
+#[utoipa::path(
+    put, path = "/item/{id}", tag = TAG,
+    params(("id" = String, Path), ("If-Match" = String, Header)),
+    request_body = Item,
+    responses(
+        (status = OK, body = StoredItem, headers(("ETag" = String))),
+        (status = BAD_REQUEST),
+        (status = NOT_FOUND),
+        (status = PRECONDITION_FAILED),
+        (status = PRECONDITION_REQUIRED),
+    ),
+)]
+pub async fn put(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+    item: web::Json<Item>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let item = item.into_inner();
+    check_item(&item)?;
+    let mut db = data.db(&user)?;
+    let existing = db.get_items(&[&id])
         .map_err(ErrorInternalServerError)?
         .into_iter()
-        .map(|item| Item { name: item.item.name })
-        .collect::<Vec<_>>();
-    Ok(web::Json(items))
+        .next()
+        .ok_or_else(|| ErrorNotFound("item not found"))?;
+    etag::check_if_match(&req, Some(&existing), true)?;
+    dbutil::update_item(&mut *db, &StoredItem { item, ..existing })
+        .map_err(ErrorInternalServerError)?;
+    let stored_item = dbutil::get_item(&*db, &id)
+        .map_err(ErrorInternalServerError)?;
+    Ok(etag::response(&req, &stored_item))
 }
 
-pub async fn post(_item: web::Json<NewItem>)
--> actix_web::Result<impl Responder> {
+#[utoipa::path(
+    delete, path = "/item/{id}", tag = TAG,
+    params(("id" = String, Path), ("If-Match" = Option<String>, Header)),
+    responses((status = NO_CONTENT), (status = PRECONDITION_FAILED)),
+)]
+pub async fn delete(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let mut db = data.db(&user)?;
+    let existing = db.get_items(&[&id])
+        .map_err(ErrorInternalServerError)?
+        .into_iter()
+        .next();
+    etag::check_if_match(&req, existing.as_ref(), false)?;
+    dbutil::delete_item(&mut *db, &id)
+        .map_err(ErrorInternalServerError)?;
     Ok(api::no_content())
 }
+
+#[utoipa::path(
+    get, path = "/item/{id}/occ", tag = TAG,
+    params(("id" = String, Path), OccsQuery, PageQuery),
+    responses(
+        (status = OK, body = Vec<StoredOcc>, headers(
+            ("Link" = String, description = "URL of the next page"))),
+        (status = BAD_REQUEST),
+        (status = NOT_FOUND),
+    ),
+)]
+pub async fn list_occs(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+    query: web::Query<OccsQuery>,
+    page_query: web::Query<PageQuery>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let limit = page_query.limit(&data.cfg())?;
+    let db = data.db(&user)?;
+    if db.get_items(&[&id]).map_err(ErrorInternalServerError)?.is_empty() {
+        return Err(ErrorNotFound("item not found"));
+    }
+    let occs = db.find_occs(&[&id], query.start, query.end, page_query.sort(),
+                            page_query.cursor(), limit + 1)
+        .map_err(ErrorInternalServerError)?
+        .remove(&id)
+        .unwrap_or_default();
+    page::response(&req, occs, limit, |occ| &occ.id)
+}
+
+#[utoipa::path(
+    get, path = "/item/{id}/forecast", tag = TAG,
+    params(("id" = String, Path)),
+    responses((status = OK, body = Option<Forecast>), (status = NOT_FOUND)),
+)]
+pub async fn forecast(
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let db = data.db(&user)?;
+    if db.get_items(&[&id]).map_err(ErrorInternalServerError)?.is_empty() {
+        return Err(ErrorNotFound("item not found"));
+    }
+    let forecast = progress::forecast(&*db, &id, data.clock.now())
+        .map_err(ErrorInternalServerError)?;
+    Ok(web::Json(forecast))
+}
+
+/// Add progress to an item's current occurrence, generating it if necessary.
+#[utoipa::path(
+    post, path = "/item/{id}/done", tag = TAG,
+    params(
+        ("id" = String, Path),
+        DoneQuery,
+        ("Idempotency-Key" = Option<String>, Header),
+    ),
+    responses(
+        (status = OK, body = Progress),
+        (status = NOT_FOUND,
+         description = "the item doesn't exist, is an event, or has no current \
+                        occurrence"),
+    ),
+)]
+pub async fn done(
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+    query: web::Query<DoneQuery>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let mut db = data.db(&user)?;
+    let item = db.get_items(&[&id])
+        .map_err(ErrorInternalServerError)?
+        .pop()
+        .ok_or_else(|| ErrorNotFound("item not found"))?;
+    let (_, progress) = progress::add_current_progress(
+        &mut *db, &item, query.amount, query.note.as_deref(), data.clock.now())
+        .map_err(|e| match e {
+            ProgressError::NotTracked | ProgressError::NoCurrentOcc => {
+                ErrorNotFound(e)
+            }
+            e => ErrorInternalServerError(e),
+        })?;
+    Ok(web::Json(Progress::new(progress)))
+}
+
+/// Get everything that happened to an item, oldest first: changes to the item
+/// and to configs scoped to it or its occurrences, the start of each stored
+/// occurrence, and each progress entry, with its note.
+///
+/// Changes are kept after the item is deleted, but changes made before this
+/// version of the server aren't included.
+#[utoipa::path(
+    get, path = "/item/{id}/history", tag = TAG,
+    params(("id" = String, Path)),
+    responses(
+        (status = OK, body = Vec<HistoryEntry>),
+        (status = NOT_FOUND),
+    ),
+)]
+pub async fn history(
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let db = data.db(&user)?;
+    let history = history::item_history(&*db, &id)
+        .map_err(ErrorInternalServerError)?;
+    if history.is_empty() &&
+        db.get_items(&[&id]).map_err(ErrorInternalServerError)?.is_empty()
+    {
+        return Err(ErrorNotFound("item not found"));
+    }
+    Ok(web::Json(history))
+}
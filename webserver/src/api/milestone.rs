@@ -0,0 +1,42 @@
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use dunsumday::db::StoredMilestone;
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "milestone";
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListQuery {
+    /// Only include milestones reached by the item with this ID.
+    item: Option<String>,
+}
+
+/// Get milestones reached by tasks, ordered by the date they were reached.
+///
+/// Milestones are found in the background at the configured interval.
+#[utoipa::path(
+    get, path = "/milestone", tag = TAG, params(ListQuery),
+    responses(
+        (status = OK, body = Vec<StoredMilestone>),
+        (status = NOT_FOUND, description = "The item doesn't exist."),
+    ),
+)]
+pub async fn list(
+    data: web::Data<server::State>,
+    user: User,
+    query: web::Query<ListQuery>,
+) -> actix_web::Result<impl Responder> {
+    let db = data.db(&user)?;
+    if let Some(id) = &query.item {
+        if db.get_items(&[id]).map_err(ErrorInternalServerError)?.is_empty() {
+            return Err(ErrorNotFound("item not found"));
+        }
+    }
+    let milestones = db.find_milestones(query.item.as_deref())
+        .map_err(ErrorInternalServerError)?;
+    Ok(web::Json(milestones))
+}
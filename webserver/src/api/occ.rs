@@ -0,0 +1,245 @@
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError,
+                       ErrorNotFound};
+use actix_web::{web, HttpRequest, Responder};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use dunsumday::config::parse::{DurationParser, Parser};
+use dunsumday::db::{StoredItem, StoredOcc, StoredProgressEntry};
+use dunsumday::db::util as dbutil;
+use dunsumday::types::ItemType;
+use dunsumday::util::progress::{self, TaskProgress};
+use crate::api::etag;
+use crate::api::page::Sort;
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "occ";
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Progress {
+    #[serde(flatten)]
+    progress: TaskProgress,
+    effective_progress: u32,
+    complete: bool,
+}
+
+impl Progress {
+    pub fn new(progress: TaskProgress) -> Progress {
+        Progress {
+            progress,
+            effective_progress: progress.effective_progress(),
+            complete: progress.is_complete(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EntriesQuery {
+    /// Order by date.  Defaults to `asc`.
+    #[serde(default)]
+    sort: Sort,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SnoozeQuery {
+    /// How long to snooze for, as a number of seconds or with units, like
+    /// `1h30m`.
+    duration: String,
+    /// Also move the deadline later by `duration`.  Only allowed for deadline
+    /// tasks.
+    #[serde(default)]
+    deadline: bool,
+}
+
+#[utoipa::path(
+    get, path = "/occ/{id}", tag = TAG,
+    params(
+        ("id" = String, Path),
+        ("If-None-Match" = Option<String>, Header),
+    ),
+    responses(
+        (status = OK, body = StoredOcc, headers(("ETag" = String))),
+        (status = NOT_MODIFIED),
+        (status = NOT_FOUND),
+    ),
+)]
+pub async fn get(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let occ = data.db(&user)?.get_occs(&[&id])
+        .map_err(ErrorInternalServerError)?
+        .pop()
+        .ok_or_else(|| ErrorNotFound("occurrence not found"))?;
+    Ok(etag::response(&req, &occ))
+}
+
+/// Get the item an occurrence belongs to.
+#[utoipa::path(
+    get, path = "/occ/{id}/item", tag = TAG,
+    params(
+        ("id" = String, Path),
+        ("If-None-Match" = Option<String>, Header),
+    ),
+    responses(
+        (status = OK, body = StoredItem, headers(("ETag" = String))),
+        (status = NOT_MODIFIED),
+        (status = NOT_FOUND),
+    ),
+)]
+pub async fn get_item(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let db = data.db(&user)?;
+    let item_id = db.get_occs_item_ids(&[&id])
+        .map_err(ErrorInternalServerError)?
+        .remove(&id)
+        .ok_or_else(|| ErrorNotFound("occurrence not found"))?;
+    let item = dbutil::get_item(&*db, &item_id)
+        .map_err(ErrorInternalServerError)?;
+    Ok(etag::response(&req, &item))
+}
+
+/// Get the progress entries logged for an occurrence.
+#[utoipa::path(
+    get, path = "/occ/{id}/entries", tag = TAG,
+    params(("id" = String, Path), EntriesQuery),
+    responses(
+        (status = OK, body = Vec<StoredProgressEntry>),
+        (status = NOT_FOUND),
+    ),
+)]
+pub async fn list_entries(
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+    query: web::Query<EntriesQuery>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let db = data.db(&user)?;
+    if db.get_occs(&[&id]).map_err(ErrorInternalServerError)?.is_empty() {
+        return Err(ErrorNotFound("occurrence not found"));
+    }
+    let entries = db.find_progress_entries(
+        &[&id], query.sort.into(), u32::MAX)
+        .map_err(ErrorInternalServerError)?
+        .remove(&id)
+        .unwrap_or_default();
+    Ok(web::Json(entries))
+}
+
+#[utoipa::path(
+    get, path = "/occ/{id}/progress", tag = TAG, params(("id" = String, Path)),
+    responses((status = OK, body = Progress), (status = NOT_FOUND)),
+)]
+pub async fn get_progress(
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let db = data.db(&user)?;
+    let Some(occ) = db.get_occs(&[&id])
+        .map_err(ErrorInternalServerError)?
+        .pop()
+    else {
+        return Err(ErrorNotFound("occurrence not found"));
+    };
+    let item = dbutil::get_occ_item(&*db, &id)
+        .map_err(ErrorInternalServerError)?;
+    if item.item.type_ == ItemType::Event {
+        return Err(ErrorNotFound("occurrence has no progress"));
+    }
+    let progress = progress::resolve_item_occs_progress(&*db, &[(&item, &occ)])
+        .map_err(ErrorInternalServerError)?
+        .remove(&id)
+        .unwrap_or_default();
+    Ok(web::Json(Progress::new(progress)))
+}
+
+#[utoipa::path(
+    post, path = "/occ/{id}/progress/undo", tag = TAG,
+    params(
+        ("id" = String, Path),
+        ("If-Match" = Option<String>, Header),
+        ("Idempotency-Key" = Option<String>, Header),
+    ),
+    responses(
+        (status = OK, body = StoredOcc, headers(("ETag" = String))),
+        (status = NOT_FOUND),
+        (status = PRECONDITION_FAILED),
+    ),
+)]
+pub async fn undo_progress(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let mut db = data.db(&user)?;
+    let existing = db.get_occs(&[&id])
+        .map_err(ErrorInternalServerError)?
+        .pop()
+        .ok_or_else(|| ErrorNotFound("occurrence not found"))?;
+    etag::check_if_match(&req, Some(&existing), false)?;
+    let occ = dbutil::undo_progress(&mut *db, &id)
+        .map_err(ErrorInternalServerError)?;
+    Ok(etag::response(&req, &occ))
+}
+
+/// Stop alerts for an occurrence for a while, starting now.
+///
+/// Snoozing again replaces the previous snooze.
+#[utoipa::path(
+    post, path = "/occ/{id}/snooze", tag = TAG,
+    params(
+        ("id" = String, Path),
+        SnoozeQuery,
+        ("If-Match" = Option<String>, Header),
+        ("Idempotency-Key" = Option<String>, Header),
+    ),
+    responses(
+        (status = OK, body = StoredOcc, headers(("ETag" = String))),
+        (status = BAD_REQUEST),
+        (status = NOT_FOUND),
+        (status = PRECONDITION_FAILED),
+    ),
+)]
+pub async fn snooze(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    user: User,
+    path: web::Path<String>,
+    query: web::Query<SnoozeQuery>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    let duration = DurationParser.parse(&query.duration)
+        .map_err(ErrorBadRequest)?;
+    let mut db = data.db(&user)?;
+    let existing = db.get_occs(&[&id])
+        .map_err(ErrorInternalServerError)?
+        .pop()
+        .ok_or_else(|| ErrorNotFound("occurrence not found"))?;
+    etag::check_if_match(&req, Some(&existing), false)?;
+    if query.deadline {
+        let item = dbutil::get_occ_item(&*db, &id)
+            .map_err(ErrorInternalServerError)?;
+        if item.item.type_ != ItemType::DeadlineTask {
+            return Err(ErrorBadRequest("occurrence has no deadline"));
+        }
+    }
+    let occ = dbutil::snooze_occ(&mut *db, &id, duration, query.deadline,
+                                 data.clock.now())
+        .map_err(ErrorInternalServerError)?;
+    Ok(etag::response(&req, &occ))
+}
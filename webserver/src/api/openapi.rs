@@ -0,0 +1,50 @@
+use actix_web::{web, Responder};
+use utoipa::OpenApi;
+use utoipa::openapi::server::Server;
+use dunsumday::config::Config;
+use crate::{api, configrefs, server};
+use super::{admin, alert, batch, calendar, config, current, dashboard,
+            events, export, feed, import, item, milestone, occ, report, stats,
+            sync, ws};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "dunsumday", description = "dunsumday web API",
+         license(name = "GPL-3.0", identifier = "GPL-3.0-only")),
+    paths(
+        item::list, item::post, item::get, item::put, item::delete,
+        item::list_occs, item::forecast, item::done, item::history,
+        current::get, calendar::get, dashboard::get,
+        config::list, config::get, config::put, config::delete,
+        occ::get, occ::get_item, occ::list_entries, occ::get_progress,
+        occ::undo_progress, occ::snooze,
+        alert::list, alert::get, alert::ack, alert::snooze,
+        milestone::list,
+        batch::post, sync::post,
+        import::ics,
+        stats::get, stats::heatmap, stats::export, stats::completion_times,
+        report::weekly,
+        export::get, export::get_occs,
+        events::get, ws::get,
+        feed::get, feed::get_url,
+        admin::backup, admin::restore, admin::reload_config,
+    ),
+)]
+struct ApiDoc;
+
+/// Absolute path the API is served at.
+pub fn api_path<C>(cfg: &C) -> String
+where
+    C: Config + ?Sized,
+{
+    api::join_path(cfg.get_ref(&configrefs::SERVER_ROOT_PATH).to_owned(),
+                   cfg.get_ref(&configrefs::SERVER_API_PATH))
+}
+
+pub async fn get(
+    data: web::Data<server::State>,
+) -> actix_web::Result<impl Responder> {
+    let mut doc = ApiDoc::openapi();
+    doc.servers = Some(vec![Server::new(api_path(data.cfg().as_ref()))]);
+    Ok(web::Json(doc))
+}
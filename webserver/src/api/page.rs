@@ -0,0 +1,104 @@
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use dunsumday::config::cached;
+use dunsumday::db::SortDirection;
+use crate::configrefs;
+
+pub const MAX_LIMIT: u32 = 1000;
+const LIMIT_PARAM: &str = "limit";
+const CURSOR_PARAM: &str = "cursor";
+
+/// Order of results.
+#[derive(Clone, Copy, Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl From<Sort> for SortDirection {
+    fn from(sort: Sort) -> SortDirection {
+        match sort {
+            Sort::Asc => SortDirection::Asc,
+            Sort::Desc => SortDirection::Desc,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    /// Maximum number of results, defaulting to the configured page size.
+    #[param(minimum = 1, maximum = 1000)]
+    limit: Option<u32>,
+    /// ID of the last result on the previous page.
+    cursor: Option<String>,
+    /// Defaults to `asc`.
+    #[serde(default)]
+    sort: Sort,
+}
+
+impl PageQuery {
+    pub fn limit(&self, cfg: &cached::Config) -> actix_web::Result<u32> {
+        match self.limit {
+            Some(limit) if (1..=MAX_LIMIT).contains(&limit) => Ok(limit),
+            Some(_) => Err(ErrorBadRequest(
+                format!("limit must be between 1 and {MAX_LIMIT}"))),
+            None => cfg.get_parsed(&configrefs::API_PAGE_SIZE)
+                .map_err(ErrorInternalServerError),
+        }
+    }
+
+    pub fn cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+
+    pub fn sort(&self) -> SortDirection {
+        self.sort.into()
+    }
+}
+
+/// Build the URL for the page after the one ending with the result with ID
+/// `cursor`, keeping other query parameters.
+fn next_url(req: &HttpRequest, limit: u32, cursor: &str)
+-> actix_web::Result<String> {
+    let mut params: Vec<(String, String)> =
+        serde_urlencoded::from_str(req.query_string())
+            .map_err(ErrorBadRequest)?;
+    params.retain(|(name, _)| name != LIMIT_PARAM && name != CURSOR_PARAM);
+    params.push((LIMIT_PARAM.to_owned(), limit.to_string()));
+    params.push((CURSOR_PARAM.to_owned(), cursor.to_owned()));
+    let query = serde_urlencoded::to_string(&params)
+        .map_err(ErrorInternalServerError)?;
+    Ok(format!("{}?{query}", req.path()))
+}
+
+/// Respond with a page of `results`, which should have been fetched with a
+/// maximum of one more than `limit`, to determine whether there is a next page.
+///
+/// If there is a next page, its URL is given in a `Link` header.
+pub fn response<T, F>(
+    req: &HttpRequest,
+    mut results: Vec<T>,
+    limit: u32,
+    id: F,
+) -> actix_web::Result<HttpResponse>
+where
+    T: Serialize,
+    F: Fn(&T) -> &str,
+{
+    let mut response = HttpResponse::Ok();
+    if results.len() > limit as usize {
+        results.truncate(limit as usize);
+        if let Some(last) = results.last() {
+            let next = next_url(req, limit, id(last))?;
+            response.insert_header(
+                (header::LINK, format!("<{next}>; rel=\"next\"")));
+        }
+    }
+    Ok(response.json(results))
+}
@@ -0,0 +1,64 @@
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{Days, NaiveDate};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use dunsumday::util::report::{self, WeeklyReview};
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "report";
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewFormat {
+    #[default]
+    Json,
+    /// The review's subject and body as plain text, as sent in digests.
+    Text,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WeeklyQuery {
+    /// Any day in the week to review, in UTC.  Defaults to a day in the
+    /// previous week.
+    #[param(value_type = Option<String>, format = Date)]
+    week: Option<NaiveDate>,
+    /// Defaults to `json`.
+    #[serde(default)]
+    format: ReviewFormat,
+}
+
+/// Review task completion over a week starting on Monday: completed and
+/// missed occurrences, changes in streaks, and incomplete occurrences which
+/// continue into the next week.
+#[utoipa::path(
+    get, path = "/report/weekly", tag = TAG, params(WeeklyQuery),
+    responses(
+        (status = OK, content(
+            (WeeklyReview = "application/json"),
+            (String = "text/plain"),
+        )),
+        (status = BAD_REQUEST),
+    ),
+)]
+pub async fn weekly(
+    data: web::Data<server::State>,
+    user: User,
+    query: web::Query<WeeklyQuery>,
+) -> actix_web::Result<impl Responder> {
+    let now = data.clock.now();
+    let week = query.week.unwrap_or_else(|| {
+        let today = now.date_naive();
+        today.checked_sub_days(Days::new(7)).unwrap_or(today)
+    });
+    let review = report::weekly_review(&*data.db(&user)?, week, now)
+        .map_err(ErrorInternalServerError)?;
+    Ok(match query.format {
+        ReviewFormat::Json => HttpResponse::Ok().json(review),
+        ReviewFormat::Text => HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .body(format!("{}\n\n{}\n", review.subject(), review.body())),
+    })
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError,
+                       ErrorNotFound};
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{Days, NaiveDate, TimeDelta};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use dunsumday::types::OccDate;
+use dunsumday::util::report::{self, Heatmap, HeatmapSubject};
+use dunsumday::util::stats::{self, CompletionStats, CompletionTimes, GroupBy,
+                             Period};
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "stats";
+/// Most days a heatmap may cover.
+const MAX_HEATMAP_DAYS: u64 = 731;
+/// Most periods a statistics export may be split into.
+const MAX_EXPORT_PERIODS: usize = 1000;
+
+/// Time range ending now.
+#[derive(Clone, Copy, Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Range {
+    /// The last 7 days.
+    Week,
+    /// The last 30 days.
+    #[default]
+    Month,
+    /// The last 365 days.
+    Year,
+    All,
+}
+
+impl Range {
+    fn start(self, now: OccDate) -> Option<OccDate> {
+        let days = match self {
+            Range::Week => 7,
+            Range::Month => 30,
+            Range::Year => 365,
+            Range::All => return None,
+        };
+        Some(now - TimeDelta::days(days))
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Query {
+    /// Defaults to `month`.
+    #[serde(default)]
+    range: Range,
+    /// Defaults to `item`.
+    #[serde(default)]
+    group_by: GroupBy,
+}
+
+/// Completion statistics for a group of task occurrences.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Summary {
+    occs: u32,
+    completed: u32,
+    missed: u32,
+    /// Proportion of completed occurrences, out of those completed or missed.
+    completion_rate: Option<f64>,
+    /// Average proportion of the target completion amount reached.
+    average_progress: Option<f64>,
+    /// For groups of items, this is the longest current streak of any item.
+    current_streak: u32,
+    /// For groups of items, this is the longest streak of any item.
+    longest_streak: u32,
+}
+
+impl From<&CompletionStats> for Summary {
+    fn from(stats: &CompletionStats) -> Summary {
+        Summary {
+            occs: stats.occs,
+            completed: stats.completed,
+            missed: stats.missed,
+            completion_rate: stats.completion_rate(),
+            average_progress: stats.average_progress(),
+            current_streak: stats.current_streak,
+            longest_streak: stats.longest_streak,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StatsResult {
+    /// Missing for all time.
+    #[schema(value_type = Option<String>, format = DateTime)]
+    start: Option<OccDate>,
+    #[schema(value_type = String, format = DateTime)]
+    end: OccDate,
+    /// Statistics for all task occurrences in the range.
+    overall: Summary,
+    /// Statistics by item ID, category or item type.
+    groups: HashMap<String, Summary>,
+}
+
+/// Get completion statistics for task occurrences overlapping a time range.
+///
+/// Occurrences count as missed once they end without being completed.  Streaks
+/// only count occurrences in the range.
+#[utoipa::path(
+    get, path = "/stats", tag = TAG, params(Query),
+    responses((status = OK, body = StatsResult), (status = BAD_REQUEST)),
+)]
+pub async fn get(
+    data: web::Data<server::State>,
+    user: User,
+    query: web::Query<Query>,
+) -> actix_web::Result<impl Responder> {
+    let now = data.clock.now();
+    let start = query.range.start(now);
+    let stats = stats::get_stats(&*data.db(&user)?, start, Some(now), now)
+        .map_err(ErrorInternalServerError)?;
+
+    let mut overall = CompletionStats::default();
+    for item_stats in stats.items.values() {
+        overall.merge(item_stats);
+    }
+    let groups = stats.grouped(query.group_by).iter()
+        .map(|(group, stats)| (group.clone(), Summary::from(stats)))
+        .collect();
+    Ok(web::Json(StatsResult {
+        start,
+        end: now,
+        overall: Summary::from(&overall),
+        groups,
+    }))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HeatmapQuery {
+    /// Include only this item.  Exactly one of `item` and `category` is
+    /// required.
+    item: Option<String>,
+    /// Include all items in this category.
+    category: Option<String>,
+    /// First day to include.  Defaults to 364 days before `to`.
+    #[param(value_type = Option<String>, format = Date)]
+    from: Option<NaiveDate>,
+    /// Last day to include.  Defaults to today.
+    #[param(value_type = Option<String>, format = Date)]
+    to: Option<NaiveDate>,
+}
+
+/// Get a heatmap of task progress logged each day, for an item or a category.
+///
+/// Each day's completion sums progress logged that day, as a proportion of the
+/// target completion amount of the occurrence it was logged for.  Days are in
+/// the server's timezone.  Ranges are limited to 731 days.
+#[utoipa::path(
+    get, path = "/stats/heatmap", tag = TAG, params(HeatmapQuery),
+    responses(
+        (status = OK, body = Heatmap),
+        (status = BAD_REQUEST),
+        (status = NOT_FOUND, description = "the item doesn't exist"),
+    ),
+)]
+pub async fn heatmap(
+    data: web::Data<server::State>,
+    user: User,
+    query: web::Query<HeatmapQuery>,
+) -> actix_web::Result<impl Responder> {
+    let subject = match (&query.item, &query.category) {
+        (Some(item), None) => HeatmapSubject::Item(item),
+        (None, Some(category)) => HeatmapSubject::Category(category),
+        _ => return Err(ErrorBadRequest(
+            "exactly one of item and category is required")),
+    };
+    let to = query.to.unwrap_or_else(|| data.clock.today());
+    let from = query.from
+        .or_else(|| to.checked_sub_days(Days::new(364)))
+        .unwrap_or(to);
+    if from > to {
+        return Err(ErrorBadRequest("from must not be after to"));
+    }
+    let max_to = from.checked_add_days(Days::new(MAX_HEATMAP_DAYS - 1));
+    if max_to.is_some_and(|max_to| to > max_to) {
+        return Err(ErrorBadRequest("range is too long"));
+    }
+
+    let db = data.db(&user)?;
+    if let HeatmapSubject::Item(id) = subject {
+        if db.get_items(&[id]).map_err(ErrorInternalServerError)?.is_empty() {
+            return Err(ErrorNotFound("item not found"));
+        }
+    }
+    let heatmap = report::heatmap(
+        &*db, subject, from..=to, data.clock.utc_offset())
+        .map_err(ErrorInternalServerError)?;
+    Ok(web::Json(heatmap))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    /// First day to include, in UTC.  Defaults to 29 days before `to`.
+    #[param(value_type = Option<String>, format = Date)]
+    from: Option<NaiveDate>,
+    /// Last day to include, in UTC.  Defaults to today.
+    #[param(value_type = Option<String>, format = Date)]
+    to: Option<NaiveDate>,
+    /// Defaults to `item`.
+    #[serde(default)]
+    group_by: GroupBy,
+    /// Length of the periods to compute statistics for separately.  Defaults
+    /// to `all`.
+    #[serde(default)]
+    period: Period,
+}
+
+/// Export completion statistics for task occurrences as CSV, with a row for
+/// each group in each period.
+///
+/// Columns are `period_start`, `period_end`, `group`, `name` (the item name,
+/// when grouping by item), `occs`, `completed`, `missed`, `completion_rate`,
+/// `average_progress`, `progress`, `total`, `current_streak` and
+/// `longest_streak`.  Exports are limited to 1000 periods.
+#[utoipa::path(
+    get, path = "/stats/export.csv", tag = TAG, params(ExportQuery),
+    responses(
+        (status = OK, content_type = "text/csv", body = String),
+        (status = BAD_REQUEST),
+    ),
+)]
+pub async fn export(
+    data: web::Data<server::State>,
+    user: User,
+    query: web::Query<ExportQuery>,
+) -> actix_web::Result<impl Responder> {
+    let now = data.clock.now();
+    let to = query.to.unwrap_or_else(|| now.date_naive());
+    let from = query.from
+        .or_else(|| to.checked_sub_days(Days::new(29)))
+        .unwrap_or(to);
+    if from > to {
+        return Err(ErrorBadRequest("from must not be after to"));
+    }
+    if stats::periods(from..=to, query.period).len() > MAX_EXPORT_PERIODS {
+        return Err(ErrorBadRequest("too many periods"));
+    }
+    let csv = stats::export_csv(
+        &*data.db(&user)?, from..=to, query.group_by, query.period, now)
+        .map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .body(csv))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CompletionTimesQuery {
+    /// Defaults to `all`.
+    #[serde(default = "all_range")]
+    range: Range,
+}
+
+fn all_range() -> Range {
+    Range::All
+}
+
+/// Get how long it took to complete deadline task occurrences overlapping a
+/// time range, by item ID.
+///
+/// An occurrence is completed when the progress logged for it first reaches
+/// its target.  Occurrences are overdue if they were completed late or
+/// missed, and occurrences still in progress aren't counted.
+#[utoipa::path(
+    get, path = "/stats/completion-times", tag = TAG,
+    params(CompletionTimesQuery),
+    responses(
+        (status = OK, body = HashMap<String, CompletionTimes>),
+        (status = BAD_REQUEST),
+    ),
+)]
+pub async fn completion_times(
+    data: web::Data<server::State>,
+    user: User,
+    query: web::Query<CompletionTimesQuery>,
+) -> actix_web::Result<impl Responder> {
+    let now = data.clock.now();
+    let times = stats::get_completion_times(
+        &*data.db(&user)?, query.range.start(now), Some(now), now)
+        .map_err(ErrorInternalServerError)?;
+    Ok(web::Json(times))
+}
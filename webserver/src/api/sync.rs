@@ -0,0 +1,40 @@
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::{web, Responder};
+use dunsumday::util::devicesync::{self, SyncObject, SyncRequest,
+                                  SyncResponse};
+use crate::api::item;
+use crate::server;
+use crate::user::User;
+
+pub const TAG: &str = "sync";
+
+/// Sync a client's database: apply the changes made on the client since its
+/// last sync, and get the changes made here since then.
+///
+/// If an object was changed on both sides, the change made last is kept.
+/// Deleting an object wins over changing it.  Pass the response's
+/// `checkpoint` in the client's next request.
+#[utoipa::path(
+    post, path = "/sync", tag = TAG,
+    request_body = SyncRequest,
+    responses(
+        (status = OK, body = SyncResponse),
+        (status = BAD_REQUEST),
+    ),
+)]
+pub async fn post(
+    data: web::Data<server::State>,
+    user: User,
+    request: web::Json<SyncRequest>,
+) -> actix_web::Result<impl Responder> {
+    for change in &request.changes {
+        if let SyncObject::Item { item: Some(item), .. } = &change.object {
+            item::check_item(item)?;
+        }
+    }
+    devicesync::check(&request).map_err(ErrorBadRequest)?;
+    let response = devicesync::exchange(
+        &mut *data.db(&user)?, &request, data.clock.now())
+        .map_err(ErrorInternalServerError)?;
+    Ok(web::Json(response))
+}
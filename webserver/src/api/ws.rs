@@ -0,0 +1,147 @@
+use actix_codec::{Decoder, Encoder};
+use actix_http::ws::{self, CloseCode, Codec, Frame, Message};
+use actix_web::body::BodyStream;
+use actix_web::error::ErrorForbidden;
+use actix_web::http::header;
+use actix_web::web::Payload;
+use actix_web::{rt, web, HttpRequest, HttpResponse};
+use bytes::{Bytes, BytesMut};
+use futures_util::{stream, StreamExt};
+use tokio::sync::{broadcast, mpsc};
+use dunsumday::db::notify::Change;
+use crate::api::Options;
+use crate::server::{self, OwnedChange};
+use crate::user::User;
+
+pub const TAG: &str = "ws";
+
+/// Maximum number of encoded frames waiting to be sent to a client.
+const OUTGOING_CAPACITY: usize = 16;
+
+/// Whether a WebSocket connection from the request's origin is allowed.
+///
+/// Browsers send cookies with WebSocket requests from any site, without a CORS
+/// preflight, so only this server and allowed CORS origins may connect.
+fn check_origin(req: &HttpRequest, options: &Options) -> bool {
+    let Some(origin) = req.headers().get(header::ORIGIN) else {
+        // not sent by a browser
+        return true;
+    };
+    let Ok(origin) = origin.to_str() else {
+        return false;
+    };
+    let conn = req.connection_info();
+    origin == format!("{}://{}", conn.scheme(), conn.host())
+        || options.cors_origins.iter()
+            .any(|allowed| allowed == "*" || allowed == origin)
+}
+
+/// Encode `msg` and queue it for sending, returning `false` if the connection
+/// has closed.
+async fn send(codec: &mut Codec, out: &mpsc::Sender<Bytes>, msg: Message)
+-> bool {
+    let mut buf = BytesMut::new();
+    if let Err(e) = codec.encode(msg, &mut buf) {
+        tracing::error!("error encoding WebSocket message: {e}");
+        return false;
+    }
+    out.send(buf.freeze()).await.is_ok()
+}
+
+/// Handle incoming frames and forward changes to the client until the
+/// connection is closed.
+async fn run(
+    mut payload: Payload,
+    mut changes: broadcast::Receiver<OwnedChange>,
+    owner: Option<String>,
+    out: mpsc::Sender<Bytes>,
+) {
+    let mut codec = Codec::new();
+    let mut buf = BytesMut::new();
+    loop {
+        tokio::select! {
+            chunk = payload.next() => {
+                let Some(Ok(chunk)) = chunk else { return };
+                buf.extend_from_slice(&chunk);
+                loop {
+                    let msg = match codec.decode(&mut buf) {
+                        Ok(None) => break,
+                        Ok(Some(Frame::Ping(data))) => Message::Pong(data),
+                        Ok(Some(Frame::Close(reason))) => {
+                            send(&mut codec, &out, Message::Close(reason))
+                                .await;
+                            return;
+                        }
+                        // clients have nothing to say
+                        Ok(Some(_)) => continue,
+                        Err(e) => {
+                            tracing::debug!("invalid WebSocket frame: {e}");
+                            return;
+                        }
+                    };
+                    if !send(&mut codec, &out, msg).await {
+                        return;
+                    }
+                }
+            }
+
+            change = changes.recv() => {
+                let msg = match change {
+                    Ok(change) if change.owner != owner => continue,
+                    Ok(change) => match serde_json::to_string(&change.change) {
+                        Ok(json) => Message::Text(json.into()),
+                        Err(e) => {
+                            tracing::error!("error serialising change: {e}");
+                            continue;
+                        }
+                    },
+                    // the client must fetch everything again
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        send(&mut codec, &out, Message::Close(Some(
+                            (CloseCode::Again, "missed changes").into())))
+                            .await;
+                        return;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                if !send(&mut codec, &out, msg).await {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Open a WebSocket connection, over which each change to the user's items
+/// and occurrences is sent as a JSON text message.
+#[utoipa::path(
+    get, path = "/ws", tag = TAG,
+    responses(
+        (status = SWITCHING_PROTOCOLS,
+         description = "each message is a JSON-encoded change",
+         body = Change),
+        (status = BAD_REQUEST),
+        (status = FORBIDDEN),
+    ),
+)]
+pub async fn get(
+    req: HttpRequest,
+    data: web::Data<server::State>,
+    options: web::Data<Options>,
+    user: User,
+    payload: Payload,
+) -> actix_web::Result<HttpResponse> {
+    if !check_origin(&req, &options) {
+        return Err(ErrorForbidden("origin not allowed"));
+    }
+    let mut response = ws::handshake(req.head())?;
+
+    let (out, outgoing) = mpsc::channel(OUTGOING_CAPACITY);
+    rt::spawn(run(payload, data.changes.subscribe(), user.id, out));
+    let body = stream::unfold(outgoing, |mut outgoing| async move {
+        outgoing.recv().await
+            .map(|frame| (Ok::<_, actix_web::Error>(frame), outgoing))
+    });
+    let response = response.message_body(BodyStream::new(body))?;
+    Ok(HttpResponse::from(response).map_into_boxed_body())
+}
@@ -0,0 +1,56 @@
+use std::io;
+use std::path::PathBuf;
+use clap::{CommandFactory, Parser, ValueEnum};
+use clap_complete::Shell;
+use dunsumday::config::file::Format;
+
+/// Web server for dunsumday.
+#[derive(Clone, Debug, Parser)]
+#[command(name = "dunsumday-webserver", version)]
+pub struct Args {
+    /// Path to the config file.
+    #[arg(long, default_value = "dev-config.yaml")]
+    pub config: PathBuf,
+    /// Format of the config file: yaml or toml.  Detected from the file
+    /// extension by default.
+    #[arg(long)]
+    pub config_format: Option<Format>,
+    /// Override a config value, for example: --set webserver.server.port=8080.
+    /// May be given multiple times.
+    #[arg(long, value_name = "PATH=VALUE")]
+    pub set: Vec<String>,
+    /// Check all config values, report any problems, and exit.
+    #[arg(long)]
+    pub check_config: bool,
+    /// Print the effective value of every config setting, with where it comes
+    /// from, and exit.
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1,
+          default_missing_value = "yaml")]
+    pub print_config: Option<PrintFormat>,
+    /// Print a shell completion script and exit.
+    #[arg(long, value_name = "SHELL")]
+    pub completions: Option<Shell>,
+    /// Print a man page in roff format and exit.
+    #[arg(long)]
+    pub man: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum PrintFormat {
+    Yaml,
+    Json,
+}
+
+/// Print a completion script for `shell`.
+pub fn print_completions(shell: Shell) {
+    let mut command = Args::command();
+    let name = command.get_name().to_owned();
+    clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+}
+
+/// Print a man page in roff format.
+pub fn print_man() -> Result<(), String> {
+    clap_mangen::Man::new(Args::command())
+        .render(&mut io::stdout())
+        .map_err(|e| format!("error writing man page: {e}"))
+}
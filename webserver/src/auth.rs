@@ -0,0 +1,312 @@
+use actix_session::{Session, SessionExt, SessionMiddleware};
+use actix_session::storage::CookieSessionStore;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::cookie::Key;
+use actix_web::dev::{HttpServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError,
+                       ErrorUnauthorized};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse, Responder};
+use openidconnect::core::{CoreAuthenticationFlow, CoreClient,
+                          CoreProviderMetadata};
+use openidconnect::{reqwest, AuthorizationCode, ClientId, ClientSecret,
+                    CsrfToken, IssuerUrl, Nonce, PkceCodeChallenge,
+                    PkceCodeVerifier, RedirectUrl, Scope, TokenResponse};
+use serde::{Deserialize, Serialize};
+use dunsumday::config::{cached, Config};
+use dunsumday::db::util as dbutil;
+use crate::{api, configrefs, server};
+use crate::user::User;
+
+const LOGIN_PATH: &str = "/login";
+const CALLBACK_PATH: &str = "/callback";
+const LOGOUT_PATH: &str = "/logout";
+
+pub const LOGIN: &str = "log in";
+pub const LOGIN_CALLBACK: &str = "log in callback";
+pub const LOGOUT: &str = "log out";
+
+/// Session key storing the logged-in user's database ID.
+const SESSION_USER_ID: &str = "user_id";
+/// Session key storing a [`PendingLogin`].
+const SESSION_PENDING_LOGIN: &str = "pending_login";
+const MIN_SESSION_KEY_LEN: usize = 32;
+
+/// OpenID Connect provider and client details.
+#[derive(Clone, Debug)]
+pub struct Oidc {
+    metadata: CoreProviderMetadata,
+    client_id: ClientId,
+    client_secret: Option<ClientSecret>,
+    redirect_url: RedirectUrl,
+    user_claim: String,
+    http: reqwest::Client,
+}
+
+/// Login started by [`login`], to be completed by [`callback`].
+#[derive(Debug, Deserialize, Serialize)]
+struct PendingLogin {
+    csrf: String,
+    nonce: String,
+    pkce_verifier: String,
+    redirect: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginQuery {
+    /// Path to return to after logging in.
+    redirect: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    code: String,
+    state: String,
+}
+
+/// Whether OpenID Connect login is configured.
+pub fn enabled<C>(cfg: &C) -> bool
+where
+    C: Config + ?Sized,
+{
+    !cfg.get_ref(&configrefs::AUTH_OIDC_ISSUER_URL).is_empty()
+}
+
+/// Discover the configured OpenID Connect provider, if any.
+pub async fn oidc(cfg: &cached::Config) -> Result<Option<Oidc>, String> {
+    if !enabled(cfg) {
+        return Ok(None);
+    }
+    let issuer_url = IssuerUrl::new(
+        cfg.get_ref(&configrefs::AUTH_OIDC_ISSUER_URL).to_owned())
+        .map_err(|e| format!("invalid OpenID Connect issuer URL: {e}"))?;
+    let redirect_url = RedirectUrl::new(
+        cfg.get_ref(&configrefs::AUTH_OIDC_REDIRECT_URL).to_owned())
+        .map_err(|e| format!("invalid OpenID Connect redirect URL: {e}"))?;
+    let client_secret =
+        cfg.get_parsed(&configrefs::AUTH_OIDC_CLIENT_SECRET)?;
+    let http = reqwest::Client::builder()
+        // following redirects allows requests to unexpected hosts
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|e| format!("error creating HTTP client: {e}"))?;
+    let metadata = CoreProviderMetadata::discover_async(issuer_url, &http)
+        .await
+        .map_err(|e| format!(
+            "error discovering OpenID Connect provider: {e}"))?;
+
+    Ok(Some(Oidc {
+        metadata,
+        client_id: ClientId::new(
+            cfg.get_ref(&configrefs::AUTH_OIDC_CLIENT_ID).to_owned()),
+        client_secret: (!client_secret.is_empty())
+            .then(|| ClientSecret::new(client_secret)),
+        redirect_url,
+        user_claim: cfg.get_parsed(&configrefs::AUTH_OIDC_USER_CLAIM)?,
+        http,
+    }))
+}
+
+/// Get the key used to sign session cookies.
+pub fn session_key(cfg: &cached::Config) -> Result<Key, String> {
+    let key = cfg.get_parsed(&configrefs::AUTH_SESSION_KEY)?;
+    if key.is_empty() {
+        Ok(Key::generate())
+    } else if key.len() < MIN_SESSION_KEY_LEN {
+        Err(format!(
+            "session key must be at least {MIN_SESSION_KEY_LEN} bytes"))
+    } else {
+        Ok(Key::derive_from(key.as_bytes()))
+    }
+}
+
+pub fn session_middleware<C>(cfg: &C, key: Key)
+-> SessionMiddleware<CookieSessionStore>
+where
+    C: Config + ?Sized,
+{
+    SessionMiddleware::builder(CookieSessionStore::default(), key)
+        .cookie_path(cfg.get_ref(&configrefs::SERVER_ROOT_PATH).to_owned())
+        .build()
+}
+
+/// Get the database ID of the logged-in user, if any.
+pub fn session_user(session: &Session) -> actix_web::Result<Option<String>> {
+    session.get(SESSION_USER_ID).map_err(ErrorInternalServerError)
+}
+
+/// Absolute path of the login route.
+fn login_path<C>(cfg: &C) -> String
+where
+    C: Config + ?Sized,
+{
+    api::join_path(
+        api::join_path(cfg.get_ref(&configrefs::SERVER_ROOT_PATH).to_owned(),
+                       cfg.get_ref(&configrefs::SERVER_AUTH_PATH)),
+        LOGIN_PATH)
+}
+
+/// Middleware rejecting requests without a logged-in user.
+pub async fn require_login(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> actix_web::Result<ServiceResponse<impl MessageBody>> {
+    if session_user(&req.get_session())?.is_none() {
+        return Err(ErrorUnauthorized("not logged in"));
+    }
+    next.call(req).await
+}
+
+/// Middleware redirecting requests without a logged-in user to log in.
+pub async fn redirect_to_login(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> actix_web::Result<ServiceResponse<EitherBody<impl MessageBody>>> {
+    if session_user(&req.get_session())?.is_some() {
+        return Ok(next.call(req).await?.map_into_left_body());
+    }
+    let data = req.app_data::<web::Data<server::State>>()
+        .ok_or_else(|| ErrorInternalServerError("server state missing"))?;
+    let query = serde_urlencoded::to_string([("redirect", req.path())])
+        .map_err(ErrorInternalServerError)?;
+    let location = format!("{}?{query}", login_path(data.cfg().as_ref()));
+    let response = HttpResponse::Found()
+        .insert_header((header::LOCATION, location))
+        .finish();
+    Ok(req.into_response(response).map_into_right_body())
+}
+
+fn client(oidc: &Oidc) -> CoreClient<
+    openidconnect::EndpointSet,
+    openidconnect::EndpointNotSet,
+    openidconnect::EndpointNotSet,
+    openidconnect::EndpointNotSet,
+    openidconnect::EndpointMaybeSet,
+    openidconnect::EndpointMaybeSet,
+> {
+    CoreClient::from_provider_metadata(
+        oidc.metadata.clone(),
+        oidc.client_id.clone(),
+        oidc.client_secret.clone(),
+    )
+        .set_redirect_uri(oidc.redirect_url.clone())
+}
+
+/// Only allow redirecting to paths on this server.
+fn check_redirect(redirect: &str) -> actix_web::Result<()> {
+    // browsers treat `//host` and `/\host` as URLs on another host
+    if redirect.starts_with('/') && !redirect.starts_with("//")
+        && !redirect.starts_with("/\\")
+    {
+        Ok(())
+    } else {
+        Err(ErrorBadRequest("invalid redirect path"))
+    }
+}
+
+pub async fn login(
+    data: web::Data<server::State>,
+    oidc: web::Data<Oidc>,
+    session: Session,
+    query: web::Query<LoginQuery>,
+) -> actix_web::Result<impl Responder> {
+    let redirect = match query.into_inner().redirect {
+        Some(redirect) => redirect,
+        None => {
+            let cfg = data.cfg();
+            api::join_path(
+                cfg.get_ref(&configrefs::SERVER_ROOT_PATH).to_owned(),
+                cfg.get_ref(&configrefs::SERVER_UI_PATH))
+        }
+    };
+    check_redirect(&redirect)?;
+
+    let (pkce_challenge, pkce_verifier) =
+        PkceCodeChallenge::new_random_sha256();
+    let (url, csrf, nonce) = client(&oidc)
+        .authorize_url(CoreAuthenticationFlow::AuthorizationCode,
+                       CsrfToken::new_random, Nonce::new_random)
+        .add_scope(Scope::new("profile".to_owned()))
+        .add_scope(Scope::new("email".to_owned()))
+        .set_pkce_challenge(pkce_challenge)
+        .url();
+    session.insert(SESSION_PENDING_LOGIN, PendingLogin {
+        csrf: csrf.into_secret(),
+        nonce: nonce.secret().to_owned(),
+        pkce_verifier: pkce_verifier.into_secret(),
+        redirect,
+    })
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, url.to_string()))
+        .finish())
+}
+
+pub async fn callback(
+    data: web::Data<server::State>,
+    oidc: web::Data<Oidc>,
+    session: Session,
+    query: web::Query<CallbackQuery>,
+) -> actix_web::Result<impl Responder> {
+    let query = query.into_inner();
+    let pending: PendingLogin = session.remove_as(SESSION_PENDING_LOGIN)
+        .and_then(Result::ok)
+        .ok_or_else(|| ErrorBadRequest("no login in progress"))?;
+    if query.state != pending.csrf {
+        return Err(ErrorBadRequest("invalid login state"));
+    }
+
+    let client = client(&oidc);
+    let token = client.exchange_code(AuthorizationCode::new(query.code))
+        .map_err(ErrorInternalServerError)?
+        .set_pkce_verifier(PkceCodeVerifier::new(pending.pkce_verifier))
+        .request_async(&oidc.http)
+        .await
+        .map_err(|e| {
+            tracing::warn!("error requesting OpenID Connect token: {e}");
+            ErrorUnauthorized("login failed")
+        })?;
+    let id_token = token.id_token()
+        .ok_or_else(|| ErrorUnauthorized("login failed"))?;
+    let claims = id_token
+        .claims(&client.id_token_verifier(), &Nonce::new(pending.nonce))
+        .map_err(|e| {
+            tracing::warn!("invalid OpenID Connect ID token: {e}");
+            ErrorUnauthorized("login failed")
+        })?;
+    let name = match oidc.user_claim.as_str() {
+        "email" => claims.email().map(|email| email.as_str()),
+        "sub" => Some(claims.subject().as_str()),
+        _ => claims.preferred_username().map(|name| name.as_str()),
+    }
+        .ok_or_else(|| ErrorUnauthorized(
+            format!("ID token has no {} claim", oidc.user_claim)))?;
+
+    let user = dbutil::get_or_create_user(
+        &mut *data.db(&User { id: None })?, name)
+        .map_err(ErrorInternalServerError)?;
+    session.renew();
+    session.insert(SESSION_USER_ID, user.id)
+        .map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, pending.redirect))
+        .finish())
+}
+
+pub async fn logout(session: Session) -> actix_web::Result<impl Responder> {
+    session.purge();
+    Ok(api::no_content())
+}
+
+pub fn service<C>(cfg: &C) -> impl HttpServiceFactory
+where
+    C: Config + ?Sized,
+{
+    web::scope(cfg.get_ref(&configrefs::SERVER_AUTH_PATH))
+        .service(web::resource(LOGIN_PATH).name(LOGIN).get(login))
+        .service(web::resource(CALLBACK_PATH)
+                 .name(LOGIN_CALLBACK).get(callback))
+        .service(web::resource(LOGOUT_PATH).name(LOGOUT).post(logout))
+}
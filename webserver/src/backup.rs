@@ -0,0 +1,49 @@
+//! Backing up the database on a schedule.
+
+use std::time::Duration;
+use tokio::sync::broadcast;
+use dunsumday::config::cached;
+use dunsumday::configrefs;
+use dunsumday::db::Db;
+use dunsumday::util::{backup, clock};
+use crate::server::{self, OwnedChange};
+
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Back up the database if a backup is due at the configured destination.
+fn backup_if_due(cfg: &cached::Config, db: &impl Db) -> Result<(), String> {
+    let Some(dest) = backup::open_destination(cfg)? else {
+        return Ok(());
+    };
+    let schedule = cfg.get_parsed(&configrefs::BACKUP_SCHEDULE)?;
+    let retention = cfg.get_parsed(&configrefs::BACKUP_RETENTION)?;
+    let backed_up = backup::backup_if_due(
+        db, dest.as_ref(), schedule, retention,
+        clock::from_config(cfg)?.now())?;
+    if let Some(backed_up) = backed_up {
+        tracing::info!("backed up database to {}: {}",
+                       dest.describe(), backed_up.name);
+        for name in backed_up.removed {
+            tracing::info!("removed old backup from {}: {name}",
+                           dest.describe());
+        }
+    }
+    Ok(())
+}
+
+/// Back up the database on the configured schedule until the server stops.
+///
+/// Backups made before the server started count towards the schedule, so
+/// restarting the server doesn't cause extra backups.
+pub async fn run(cfg: server::Cfg, changes: broadcast::Sender<OwnedChange>) {
+    // check often enough that backups aren't made much later than due
+    server::run_periodic(
+        cfg, changes, "backups", MIN_INTERVAL,
+        |cfg| {
+            cfg.get_parsed(&configrefs::BACKUP_SCHEDULE)
+                .map(|schedule| (schedule / 4).min(MAX_INTERVAL))
+        },
+        |cfg, db| backup_if_due(cfg, db),
+    ).await;
+}
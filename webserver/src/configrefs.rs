@@ -1,18 +1,48 @@
-use dunsumday::config::ValueRef;
+use dunsumday::config::{AnyValueRef, ParsedValueRef, ValueRef};
+use actix_web::http::Method;
+use actix_web::http::header::HeaderName;
+use dunsumday::config::parse::{self, FromStrParser, ListParser};
+use dunsumday::config::validate::{OneOfValidator, RangeValidator, Validated};
+use crate::api::page;
 
 pub const UI_PATH: ValueRef<'_> = ValueRef {
     names: &["webserver", "paths", "ui"],
     def: "/usr/share/dunsumday/webserver/resources/ui",
 };
 
-pub const SERVER_ALL_INTERFACES: ValueRef<'_> = ValueRef {
-    names: &["webserver", "server", "all-interfaces"],
-    def: "true",
+pub const SERVER_ALL_INTERFACES: ParsedValueRef<'_, FromStrParser<bool>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["webserver", "server", "all-interfaces"],
+            def: "true",
+        },
+        parser: parse::BOOL,
+    };
+
+pub const SERVER_PORT: ParsedValueRef<
+    '_, Validated<FromStrParser<u16>, RangeValidator<u16>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["webserver", "server", "port"],
+        def: "26300",
+    },
+    parser: Validated {
+        parser: FromStrParser::new(),
+        validator: RangeValidator { min: Some(1), max: None },
+    },
+};
+
+/// Path to a PEM file containing the TLS certificate chain.  The server uses
+/// HTTPS if this and [`SERVER_TLS_KEY`] are set.
+pub const SERVER_TLS_CERT: ValueRef<'_> = ValueRef {
+    names: &["webserver", "server", "tls", "cert"],
+    def: "",
 };
 
-pub const SERVER_PORT: ValueRef<'_> = ValueRef {
-    names: &["webserver", "server", "port"],
-    def: "26300",
+/// Path to a PEM file containing the TLS private key.
+pub const SERVER_TLS_KEY: ValueRef<'_> = ValueRef {
+    names: &["webserver", "server", "tls", "key"],
+    def: "",
 };
 
 pub const SERVER_ROOT_PATH: ValueRef<'_> = ValueRef {
@@ -29,3 +59,300 @@ pub const SERVER_UI_PATH: ValueRef<'_> = ValueRef {
     names: &["webserver", "server", "paths", "ui"],
     def: "/ui",
 };
+
+pub const SERVER_AUTH_PATH: ValueRef<'_> = ValueRef {
+    names: &["webserver", "server", "paths", "auth"],
+    def: "/auth",
+};
+
+pub const API_PAGE_SIZE: ParsedValueRef<
+    '_, Validated<FromStrParser<u32>, RangeValidator<u32>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["webserver", "api", "page-size"],
+        def: "100",
+    },
+    parser: Validated {
+        parser: FromStrParser::new(),
+        validator: RangeValidator { min: Some(1), max: Some(page::MAX_LIMIT) },
+    },
+};
+
+pub const API_SWAGGER_UI: ParsedValueRef<'_, FromStrParser<bool>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["webserver", "api", "swagger-ui"],
+            def: "false",
+        },
+        parser: parse::BOOL,
+    };
+
+/// How often to check for occurrence status changes to send to event stream
+/// clients.
+pub const API_EVENTS_INTERVAL: ParsedValueRef<'_, parse::DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["webserver", "api", "events", "interval"],
+            def: "1m",
+        },
+        parser: parse::DurationParser,
+    };
+
+/// Secret used to sign calendar feed URLs.  The feed is disabled if empty.
+/// Changing this invalidates all existing feed URLs.
+pub const API_FEED_SECRET: ParsedValueRef<'_, FromStrParser<String>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["webserver", "api", "feed", "secret"],
+            def: "",
+        },
+        parser: parse::STRING,
+    };
+
+/// How far ahead of the current date to include occurrences in the calendar
+/// feed.
+pub const API_FEED_PERIOD: ParsedValueRef<'_, parse::DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["webserver", "api", "feed", "period"],
+            def: "30d",
+        },
+        parser: parse::DurationParser,
+    };
+
+/// How long responses to requests with idempotency keys are kept, for
+/// retried requests.
+pub const API_IDEMPOTENCY_WINDOW: ParsedValueRef<'_, parse::DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["webserver", "api", "idempotency", "window"],
+            def: "1d",
+        },
+        parser: parse::DurationParser,
+    };
+
+/// Largest request body, in bytes, accepted with an idempotency key.  The body
+/// is held in memory while the request is handled, so larger requests are
+/// rejected.
+pub const API_IDEMPOTENCY_MAX_BODY_SIZE: ParsedValueRef<
+    '_, FromStrParser<usize>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["webserver", "api", "idempotency", "max-body-size"],
+        def: "2097152",
+    },
+    parser: FromStrParser::new(),
+};
+
+/// Bearer token required by admin endpoints, such as backup and restore.  Admin
+/// endpoints are disabled if empty.
+pub const API_ADMIN_TOKEN: ParsedValueRef<'_, FromStrParser<String>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["webserver", "api", "admin", "token"],
+            def: "",
+        },
+        parser: parse::STRING,
+    };
+
+/// Origins allowed to make cross-origin requests to the API, or `*` for any
+/// origin.  Cross-origin requests are not allowed if empty.
+pub const API_CORS_ALLOWED_ORIGINS: ParsedValueRef<
+    '_, ListParser<FromStrParser<String>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["webserver", "api", "cors", "allowed-origins"],
+        def: "",
+    },
+    parser: ListParser { item: parse::STRING },
+};
+
+pub const API_CORS_ALLOWED_METHODS: ParsedValueRef<
+    '_, ListParser<FromStrParser<Method>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["webserver", "api", "cors", "allowed-methods"],
+        def: "GET, POST, PUT, DELETE",
+    },
+    parser: ListParser { item: FromStrParser::new() },
+};
+
+pub const API_CORS_ALLOWED_HEADERS: ParsedValueRef<
+    '_, ListParser<FromStrParser<HeaderName>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["webserver", "api", "cors", "allowed-headers"],
+        def: "content-type, if-match, if-none-match, idempotency-key",
+    },
+    parser: ListParser { item: FromStrParser::new() },
+};
+
+/// Whether cross-origin requests may include credentials, such as session
+/// cookies.
+pub const API_CORS_ALLOW_CREDENTIALS: ParsedValueRef<'_, FromStrParser<bool>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["webserver", "api", "cors", "allow-credentials"],
+            def: "false",
+        },
+        parser: parse::BOOL,
+    };
+
+/// URLs which receive webhook requests.  Webhooks are disabled if empty.
+pub const WEBHOOKS_URLS: ParsedValueRef<
+    '_, ListParser<FromStrParser<reqwest::Url>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["webserver", "webhooks", "urls"],
+        def: "",
+    },
+    parser: ListParser { item: FromStrParser::new() },
+};
+
+/// Secret used to sign webhook requests.  Requests are not signed if empty.
+pub const WEBHOOKS_SECRET: ParsedValueRef<'_, FromStrParser<String>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["webserver", "webhooks", "secret"],
+            def: "",
+        },
+        parser: parse::STRING,
+    };
+
+/// How often to check for occurrences which were completed or became overdue,
+/// for webhooks.
+pub const WEBHOOKS_INTERVAL: ParsedValueRef<'_, parse::DurationParser> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["webserver", "webhooks", "interval"],
+            def: "1m",
+        },
+        parser: parse::DurationParser,
+    };
+
+/// Name of the user who owns items synced from iCalendar URLs by the server,
+/// and whose occurrences are pushed to Google Calendar.  If empty, synced
+/// items have no owner, and only items with no owner are pushed.
+pub const SYNC_USER: ValueRef<'_> = ValueRef {
+    names: &["webserver", "sync", "user"],
+    def: "",
+};
+
+/// How log lines are written: `text` for a line per event, `pretty` for
+/// several lines per event with their spans, or `json` for a JSON object per
+/// line.
+pub const LOG_FORMAT: ParsedValueRef<
+    '_, Validated<FromStrParser<String>, OneOfValidator<'_>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["webserver", "log", "format"],
+        def: "text",
+    },
+    parser: Validated {
+        parser: parse::STRING,
+        validator: OneOfValidator { values: &["text", "pretty", "json"] },
+    },
+};
+
+/// Which spans and events to log, using the same syntax as the `RUST_LOG`
+/// environment variable, for example `info,dunsumday=debug`.  If empty,
+/// `RUST_LOG` is used, or only errors are logged if that isn't set.
+pub const LOG_FILTER: ValueRef<'_> = ValueRef {
+    names: &["webserver", "log", "filter"],
+    def: "",
+};
+
+pub const USERS_HEADER: ValueRef<'_> = ValueRef {
+    names: &["webserver", "users", "header"],
+    def: "",
+};
+
+/// Key used to sign session cookies, of at least 32 bytes.  If empty, a random
+/// key is used, and sessions end when the server restarts.
+pub const AUTH_SESSION_KEY: ParsedValueRef<'_, FromStrParser<String>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["webserver", "auth", "session-key"],
+            def: "",
+        },
+        parser: parse::STRING,
+    };
+
+/// OpenID Connect login is enabled if this is not empty.
+pub const AUTH_OIDC_ISSUER_URL: ValueRef<'_> = ValueRef {
+    names: &["webserver", "auth", "oidc", "issuer-url"],
+    def: "",
+};
+
+pub const AUTH_OIDC_CLIENT_ID: ValueRef<'_> = ValueRef {
+    names: &["webserver", "auth", "oidc", "client-id"],
+    def: "",
+};
+
+pub const AUTH_OIDC_CLIENT_SECRET: ParsedValueRef<'_, FromStrParser<String>> =
+    ParsedValueRef {
+        vref: ValueRef {
+            names: &["webserver", "auth", "oidc", "client-secret"],
+            def: "",
+        },
+        parser: parse::STRING,
+    };
+
+/// Public URL of the login callback, which is at the auth path.
+pub const AUTH_OIDC_REDIRECT_URL: ValueRef<'_> = ValueRef {
+    names: &["webserver", "auth", "oidc", "redirect-url"],
+    def: "",
+};
+
+/// ID token claim used as the user name.
+pub const AUTH_OIDC_USER_CLAIM: ParsedValueRef<
+    '_, Validated<FromStrParser<String>, OneOfValidator<'_>>
+> = ParsedValueRef {
+    vref: ValueRef {
+        names: &["webserver", "auth", "oidc", "user-claim"],
+        def: "preferred_username",
+    },
+    parser: Validated {
+        parser: parse::STRING,
+        validator: OneOfValidator {
+            values: &["preferred_username", "email", "sub"],
+        },
+    },
+};
+
+pub const ALL: [&dyn AnyValueRef; 34] = [
+    &UI_PATH,
+    &SERVER_ALL_INTERFACES,
+    &SERVER_PORT,
+    &SERVER_TLS_CERT,
+    &SERVER_TLS_KEY,
+    &SERVER_ROOT_PATH,
+    &SERVER_API_PATH,
+    &SERVER_UI_PATH,
+    &SERVER_AUTH_PATH,
+    &API_PAGE_SIZE,
+    &API_SWAGGER_UI,
+    &API_EVENTS_INTERVAL,
+    &API_FEED_SECRET,
+    &API_FEED_PERIOD,
+    &API_IDEMPOTENCY_WINDOW,
+    &API_IDEMPOTENCY_MAX_BODY_SIZE,
+    &API_ADMIN_TOKEN,
+    &API_CORS_ALLOWED_ORIGINS,
+    &API_CORS_ALLOWED_METHODS,
+    &API_CORS_ALLOWED_HEADERS,
+    &API_CORS_ALLOW_CREDENTIALS,
+    &WEBHOOKS_URLS,
+    &WEBHOOKS_SECRET,
+    &WEBHOOKS_INTERVAL,
+    &SYNC_USER,
+    &LOG_FORMAT,
+    &LOG_FILTER,
+    &USERS_HEADER,
+    &AUTH_SESSION_KEY,
+    &AUTH_OIDC_ISSUER_URL,
+    &AUTH_OIDC_CLIENT_ID,
+    &AUTH_OIDC_CLIENT_SECRET,
+    &AUTH_OIDC_REDIRECT_URL,
+    &AUTH_OIDC_USER_CLAIM,
+];
@@ -1 +0,0 @@
-pub const ITEMS_PAGE_SIZE: u32 = 100;
@@ -0,0 +1,46 @@
+//! Pushing upcoming occurrences to Google Calendar.
+
+use std::time::Duration;
+use tokio::sync::broadcast;
+use dunsumday::config::{cached, Config};
+use dunsumday::db::Db;
+use dunsumday::db::util as dbutil;
+use dunsumday::util::clock;
+use dunsumday::util::gcal::{self, Client};
+use crate::configrefs;
+use crate::server::{self, OwnedChange};
+
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Push the configured user's occurrences, if a calendar is configured.
+fn push(cfg: &cached::Config, db: &mut impl Db) -> Result<(), String> {
+    // access tokens expire, so get a new one every time
+    let Some(client) = Client::from_config(cfg)? else {
+        return Ok(());
+    };
+    let period = cfg.get_parsed(&dunsumday::configrefs::SYNC_GCAL_PERIOD)?;
+    let name = cfg.get_ref(&configrefs::SYNC_USER);
+    let owner = if name.is_empty() {
+        None
+    } else {
+        Some(dbutil::get_or_create_user(db, name)?.id)
+    };
+    db.set_user(owner.as_deref())?;
+
+    let pushed = gcal::push(
+        db, &client, period, clock::from_config(cfg)?.now())?;
+    tracing::info!("pushed to calendar: {} created, {} updated, {} removed",
+                   pushed.created, pushed.updated, pushed.removed);
+    Ok(())
+}
+
+/// Push occurrences at the configured interval until the server stops.
+///
+/// Occurrences generated while pushing are sent to `changes`.
+pub async fn run(cfg: server::Cfg, changes: broadcast::Sender<OwnedChange>) {
+    server::run_periodic(
+        cfg, changes, "calendar push", MIN_INTERVAL,
+        |cfg| cfg.get_parsed(&dunsumday::configrefs::SYNC_GCAL_INTERVAL),
+        push,
+    ).await;
+}
@@ -1,42 +1,103 @@
-use std::borrow::Borrow;
-use actix_web::{App, HttpServer, middleware, web};
-use dunsumday::config::{self, Config};
+use std::sync::Arc;
+use actix_web::{rt, App, HttpServer, middleware, web};
+use clap::Parser;
+use tracing_actix_web::TracingLogger;
+use dunsumday::config::{self, Config, ConfigError};
+use dunsumday::config::file::Format;
+use dunsumday::config::watch::WatchedConfig;
 
+mod args;
+mod auth;
+mod backup;
 mod configrefs;
-mod constant;
+mod gcal;
 mod api;
+mod milestones;
+mod notifications;
+mod occgen;
 mod ui;
 mod server;
+mod sync;
+mod user;
+mod webhooks;
 
-fn cfg_factory() -> Result<Box<dyn Config>, String> {
+fn cfg_factory(args: &args::Args) -> Result<WatchedConfig, ConfigError> {
     // /usr/local/etc/dunsumday/config.yaml
-    const CONFIG_PATH: &str = "dev-config.yaml";
-    Ok(Box::new(config::file::new(CONFIG_PATH)?))
+    let format = args.config_format
+        .unwrap_or_else(|| Format::from_path(&args.config));
+    WatchedConfig::new(&args.config, format)
 }
 
 #[actix_web::main]
 async fn main() -> Result<(), String> {
-    env_logger::init();
+    let args = args::Args::parse();
+    if let Some(shell) = args.completions {
+        args::print_completions(shell);
+        return Ok(());
+    }
+    if args.man {
+        return args::print_man();
+    }
 
-    let global_cfg = cfg_factory()?;
-    HttpServer::new(|| {
+    let cfg = server::Cfg::new(
+        vec![Arc::new(config::args::new(&args.set)?)],
+        cfg_factory(&args)?);
+    if args.check_config {
+        return server::check_config(cfg.current().as_ref());
+    }
+    server::check_config_defaults()?;
+    server::check_config_unresolved(cfg.current().as_ref())?;
+    if let Some(format) = args.print_config {
+        return server::print_config(cfg.current().as_ref(), format);
+    }
+    server::init_logging(&cfg.current())?;
+    server::warn_on_restart_required(&cfg);
+    let addr = server::addr(&cfg.current())?;
+    let tls = server::tls_config(&cfg.current())?;
+    let api_options = api::Options::new(&cfg.current())?;
+    let oidc = auth::oidc(&cfg.current()).await?.map(web::Data::new);
+    let session_key = auth::session_key(&cfg.current())?;
+    let changes = server::changes_channel();
+    rt::spawn(webhooks::run(cfg.clone(), changes.clone()));
+    rt::spawn(notifications::run(cfg.clone(), changes.clone()));
+    rt::spawn(sync::run(cfg.clone(), changes.clone()));
+    rt::spawn(gcal::run(cfg.clone(), changes.clone()));
+    rt::spawn(backup::run(cfg.clone(), changes.clone()));
+    rt::spawn(occgen::run(cfg.clone(), changes.clone()));
+    rt::spawn(milestones::run(cfg.clone(), changes.clone()));
+    // shared by all workers, so there's a single database connection
+    let state = web::Data::new(server::State::new(cfg.clone(), changes)?);
+    let idempotency = web::Data::new(api::idempotency::Store::default());
+    let server = HttpServer::new(move || {
         let app = App::new()
-            .data_factory(|| async {
-                server::State::new(cfg_factory()?)
-            })
-            .wrap(middleware::Logger::default())
+            .app_data(state.clone())
+            .app_data(idempotency.clone())
+            .wrap(TracingLogger::default())
             .default_service(web::to(api::notfound::get));
 
-        // no way to handle errors properly here
-        let cfg = cfg_factory().unwrap();
+        let cfg = cfg.current();
         let root_path = cfg.get_ref(&configrefs::SERVER_ROOT_PATH)
             .trim_end_matches('/');
-        let api_service = api::service(cfg.borrow() as &dyn Config);
-        let ui_service = ui::service(cfg.borrow() as &dyn Config);
-        app.service(web::scope(root_path)
-            .service(api_service).service(ui_service))
-    })
-        .bind_auto_h2c(server::addr(global_cfg.borrow() as &dyn Config))
+        let api_service = api::service(cfg.as_ref(), &api_options);
+        let ui_service = ui::service(cfg.as_ref());
+        let mut scope = web::scope(root_path)
+            .wrap(middleware::Condition::new(
+                oidc.is_some(),
+                auth::session_middleware(cfg.as_ref(), session_key.clone())));
+        if let Some(oidc) = &oidc {
+            scope = scope.app_data(oidc.clone())
+                .service(auth::service(cfg.as_ref()));
+        }
+        app.service(scope
+            .service(api::feed_service(cfg.as_ref()))
+            .service(api::admin_service(cfg.as_ref()))
+            .service(api_service)
+            .service(ui_service))
+    });
+    match tls {
+        Some(tls) => server.bind_rustls_0_23(addr, tls),
+        None => server.bind_auto_h2c(addr),
+    }
         .map_err(|e| format!("error binding port: {e}"))?
         .run()
         .await
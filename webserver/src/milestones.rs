@@ -0,0 +1,37 @@
+//! Looking for milestones reached by tasks, and sending them through
+//! configured notification channels.
+
+use std::time::Duration;
+use tokio::sync::broadcast;
+use dunsumday::config::cached;
+use dunsumday::configrefs;
+use dunsumday::db::Db;
+use dunsumday::util::clock;
+use dunsumday::util::milestones::{self, Defaults};
+use dunsumday::util::notify::Notifier;
+use crate::server::{self, OwnedChange};
+
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Record and send milestones reached by every user's tasks.
+fn check_all(cfg: &cached::Config, db: &mut impl Db) -> Result<(), String> {
+    let clock = clock::from_config(cfg)?;
+    let notifier = Notifier::new(cfg, clock.utc_offset())?;
+    let defaults = Defaults::new(cfg)?;
+    let now = clock.now();
+    server::for_each_owner(db, |db, _| {
+        milestones::check(db, &defaults, &notifier, now)?;
+        Ok(())
+    })
+}
+
+/// Look for milestones at the configured interval until the server stops.
+///
+/// Changes made by checking milestones are sent to `changes`.
+pub async fn run(cfg: server::Cfg, changes: broadcast::Sender<OwnedChange>) {
+    server::run_periodic(
+        cfg, changes, "milestones", MIN_INTERVAL,
+        |cfg| cfg.get_parsed(&configrefs::MILESTONES_INTERVAL),
+        check_all,
+    ).await;
+}
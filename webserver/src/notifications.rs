@@ -0,0 +1,37 @@
+//! Sending alerts about occurrences through configured notification channels.
+
+use std::time::Duration;
+use tokio::sync::broadcast;
+use dunsumday::config::cached;
+use dunsumday::configrefs;
+use dunsumday::db::Db;
+use dunsumday::util::clock;
+use dunsumday::util::notify::Notifier;
+use crate::server::{self, OwnedChange};
+
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Send alerts which are due for every user's occurrences.
+fn check_all(cfg: &cached::Config, db: &mut impl Db) -> Result<(), String> {
+    let clock = clock::from_config(cfg)?;
+    let notifier = Notifier::new(cfg, clock.utc_offset())?;
+    if !notifier.has_channels() {
+        return Ok(());
+    }
+    let now = clock.now();
+    server::for_each_owner(db, |db, _| {
+        notifier.check(db, now)?;
+        Ok(())
+    })
+}
+
+/// Send alerts at the configured interval until the server stops.
+///
+/// Changes made by checking occurrences are sent to `changes`.
+pub async fn run(cfg: server::Cfg, changes: broadcast::Sender<OwnedChange>) {
+    server::run_periodic(
+        cfg, changes, "notifications", MIN_INTERVAL,
+        |cfg| cfg.get_parsed(&configrefs::NOTIFICATIONS_INTERVAL),
+        check_all,
+    ).await;
+}
@@ -0,0 +1,39 @@
+//! Generating occurrences ahead of time, so that reading current occurrences
+//! rarely needs to write to the database.
+
+use std::time::Duration;
+use tokio::sync::broadcast;
+use dunsumday::config::cached;
+use dunsumday::configrefs;
+use dunsumday::db::Db;
+use dunsumday::util;
+use dunsumday::util::clock;
+use crate::server::{self, OwnedChange};
+
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Generate occurrences up to the configured horizon for every user's items.
+fn generate_all(cfg: &cached::Config, db: &mut impl Db) -> Result<(), String> {
+    let horizon = cfg.get_parsed(&configrefs::OCC_GEN_HORIZON)?;
+    let now = clock::from_config(cfg)?.now();
+    let mut generated = 0;
+    server::for_each_owner(db, |db, _| {
+        generated += util::generate_ahead(db, now, horizon)?;
+        Ok(())
+    })?;
+    if generated > 0 {
+        tracing::info!("generated {generated} occurrences ahead of time");
+    }
+    Ok(())
+}
+
+/// Generate occurrences at the configured interval until the server stops.
+///
+/// Generated occurrences are sent to `changes`.
+pub async fn run(cfg: server::Cfg, changes: broadcast::Sender<OwnedChange>) {
+    server::run_periodic(
+        cfg, changes, "occurrence generation", MIN_INTERVAL,
+        |cfg| cfg.get_parsed(&configrefs::OCC_GEN_INTERVAL),
+        generate_all,
+    ).await;
+}
@@ -1,30 +1,398 @@
-use std::{borrow::Borrow, net::ToSocketAddrs};
-use std::net::Ipv4Addr;
-use dunsumday::config::Config;
+use std::{env, io};
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::net::{Ipv4Addr, ToSocketAddrs};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError,
+                RwLock};
+use std::time::Duration;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::rt::time;
+use actix_web::web;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+use rustls_pki_types::pem::PemObject;
+use dunsumday::config::{self, cached, layered, map, AnyValueRef, Config,
+                        ConfigError, ValueRef};
+use dunsumday::config::layered::SharedConfig;
+use dunsumday::config::watch::WatchedConfig;
 use dunsumday::db::Db;
+use dunsumday::db::notify::{Change, NotifyingDb};
+use dunsumday::util::clock::{self, SharedClock};
+use tokio::sync::broadcast;
+use tracing_subscriber::EnvFilter;
+use crate::args::PrintFormat;
 use crate::configrefs;
+use crate::user::User;
+
+/// Config values which only take effect when the server starts.
+const RESTART_REQUIRED_REFS: [&ValueRef<'_>; 21] = [
+    &configrefs::SERVER_ALL_INTERFACES.vref,
+    &configrefs::SERVER_PORT.vref,
+    &configrefs::SERVER_TLS_CERT,
+    &configrefs::SERVER_TLS_KEY,
+    &configrefs::SERVER_ROOT_PATH,
+    &configrefs::SERVER_API_PATH,
+    &configrefs::SERVER_UI_PATH,
+    &configrefs::SERVER_AUTH_PATH,
+    &configrefs::API_SWAGGER_UI.vref,
+    &configrefs::API_CORS_ALLOWED_ORIGINS.vref,
+    &configrefs::API_CORS_ALLOWED_METHODS.vref,
+    &configrefs::API_CORS_ALLOWED_HEADERS.vref,
+    &configrefs::API_CORS_ALLOW_CREDENTIALS.vref,
+    &configrefs::AUTH_SESSION_KEY.vref,
+    &configrefs::AUTH_OIDC_ISSUER_URL,
+    &configrefs::AUTH_OIDC_CLIENT_ID,
+    &configrefs::AUTH_OIDC_CLIENT_SECRET.vref,
+    &configrefs::AUTH_OIDC_REDIRECT_URL,
+    &configrefs::AUTH_OIDC_USER_CLAIM.vref,
+    &configrefs::LOG_FORMAT.vref,
+    &configrefs::LOG_FILTER,
+];
+
+#[derive(Clone)]
+pub struct Cfg {
+    overrides: Vec<SharedConfig>,
+    file: Arc<WatchedConfig>,
+    /// Config when the server started.
+    initial: Arc<cached::Config>,
+    current: Arc<RwLock<Arc<cached::Config>>>,
+}
+
+impl Cfg {
+    pub fn new(overrides: Vec<SharedConfig>, file: WatchedConfig) -> Cfg {
+        let initial = Arc::new(layer(&overrides, file.snapshot()));
+        let current = Arc::new(RwLock::new(initial.clone()));
+        let subscribed_overrides = overrides.clone();
+        let subscribed_current = current.clone();
+        file.subscribe(move |new_file_cfg| {
+            let new_cfg = layer(&subscribed_overrides, new_file_cfg.clone());
+            *subscribed_current.write()
+                .unwrap_or_else(PoisonError::into_inner) = Arc::new(new_cfg);
+        });
+        Cfg { overrides, file: Arc::new(file), initial, current }
+    }
+
+    pub fn current(&self) -> Arc<cached::Config> {
+        // a poisoned lock still holds a complete config
+        self.current.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Load the config file again now.  Returns whether the config changed.
+    pub fn reload(&self) -> Result<bool, ConfigError> {
+        self.file.reload()
+    }
+
+    /// Whether the current config changes values which only take effect when
+    /// the server starts.
+    pub fn restart_required(&self) -> bool {
+        restart_required(&self.initial, &self.current())
+    }
+}
+
+fn restart_required(initial: &cached::Config, new: &cached::Config) -> bool {
+    RESTART_REQUIRED_REFS.iter()
+        .any(|vref| new.get_ref(vref) != initial.get_ref(vref))
+}
+
+fn layer(overrides: &[SharedConfig], file_cfg: Arc<map::Config>)
+-> cached::Config {
+    let mut layers = overrides.to_vec();
+    layers.push(file_cfg);
+    cached::new(Arc::new(layered::new(layers)))
+}
+
+/// Maximum number of changes kept for subscribers which are behind.
+const CHANGES_CAPACITY: usize = 256;
+
+/// Change made to the database, with the user ID of the object's owner.
+#[derive(Clone, Debug)]
+pub struct OwnedChange {
+    pub owner: Option<String>,
+    pub change: Change,
+}
+
+/// Create a channel to share database changes between workers.
+pub fn changes_channel() -> broadcast::Sender<OwnedChange> {
+    broadcast::Sender::new(CHANGES_CAPACITY)
+}
+
+/// Open a connection to the database, which sends the changes made through it
+/// to `changes`.
+pub fn open_db(cfg: &Cfg, changes: &broadcast::Sender<OwnedChange>)
+-> Result<Box<dyn Db + Send>, String> {
+    let mut db = NotifyingDb::new(
+        dunsumday::db::open(cfg.current().as_ref())?);
+    let sender = changes.clone();
+    db.subscribe(move |owner, db_changes| {
+        for change in db_changes {
+            // fails when there are no receivers
+            let _ = sender.send(OwnedChange {
+                owner: owner.map(|owner| owner.to_owned()),
+                change: change.clone(),
+            });
+        }
+    });
+    Ok(Box::new(db))
+}
 
 pub struct State {
-    pub cfg: Box<dyn Config>,
-    pub db: Box<dyn Db>,
+    pub cfg: Cfg,
+    pub db: Mutex<Box<dyn Db + Send>>,
+    pub changes: broadcast::Sender<OwnedChange>,
+    /// Provides the current date for requests.
+    pub clock: SharedClock,
 }
 
 impl State {
-    pub fn new(cfg: Box<dyn Config>) -> Result<State, String> {
-        let db = dunsumday::db::open(cfg.borrow() as &dyn Config)?;
-        Ok::<State, String>(State {
-            cfg,
-            db: Box::new(db),
-        })
+    pub fn new(cfg: Cfg, changes: broadcast::Sender<OwnedChange>)
+    -> Result<State, String> {
+        let db = open_db(&cfg, &changes)?;
+        let clock = clock::from_config(cfg.current().as_ref())?;
+        Ok(State { cfg, db: Mutex::new(db), changes, clock })
+    }
+
+    pub fn cfg(&self) -> Arc<cached::Config> {
+        self.cfg.current()
     }
+
+    /// Lock the database, operating on objects owned by `user`.
+    pub fn db(&self, user: &User)
+    -> actix_web::Result<MutexGuard<'_, Box<dyn Db + Send>>> {
+        let mut db = self.db.lock()
+            .map_err(|_| ErrorInternalServerError("database lock poisoned"))?;
+        db.set_user(user.id.as_deref()).map_err(ErrorInternalServerError)?;
+        Ok(db)
+    }
+}
+
+/// Get the owner of each user's objects, and `None` for objects with no owner.
+pub fn owners(db: &impl Db) -> Result<Vec<Option<String>>, String> {
+    Ok(db.find_users(None)?
+        .into_iter()
+        .map(|user| Some(user.id))
+        .chain([None])
+        .collect())
 }
 
-pub fn addr<C>(cfg: &C) -> impl ToSocketAddrs
-where
-    C: Config + ?Sized,
+/// Call `f` with each of the [owners](owners), after setting `db` to operate on
+/// the owner's objects.  Stops at the first error.
+pub fn for_each_owner<D: Db>(
+    db: &mut D,
+    mut f: impl FnMut(&mut D, Option<&str>) -> Result<(), String>,
+) -> Result<(), String> {
+    for owner in owners(db)? {
+        db.set_user(owner.as_deref())?;
+        f(db, owner.as_deref())?;
+    }
+    Ok(())
+}
+
+/// Timer for a background task which runs at a configured interval.  The
+/// interval may change when the config is reloaded.
+pub struct ConfiguredInterval {
+    /// Describes the task in logged errors.
+    name: &'static str,
+    min: Duration,
+    period: Option<Duration>,
+    interval: time::Interval,
+}
+
+impl ConfiguredInterval {
+    pub fn new(name: &'static str, min: Duration) -> ConfiguredInterval {
+        ConfiguredInterval {
+            name,
+            min,
+            period: None,
+            interval: time::interval(min),
+        }
+    }
+
+    /// Get the timer, restarting it first if `period` has changed.  The period
+    /// is at least the minimum, which is also used if reading it failed.
+    pub fn update(&mut self, period: Result<Duration, impl Display>)
+    -> &mut time::Interval {
+        let period = period
+            .unwrap_or_else(|e| {
+                tracing::error!("error reading {} config: {e}", self.name);
+                self.min
+            })
+            .max(self.min);
+        if self.period != Some(period) {
+            self.period = Some(period);
+            self.interval = time::interval(period);
+        }
+        &mut self.interval
+    }
+}
+
+/// Run a background task until the server stops, calling `f` at the interval
+/// returned by `period` for the current config (see [`ConfiguredInterval`]).
+///
+/// `f` is given a connection to the database, which sends the changes made
+/// through it to `changes`.  It may block, so it runs on a separate thread.
+/// `name` describes the task in logged errors.
+pub async fn run_periodic<F>(
+    cfg: Cfg,
+    changes: broadcast::Sender<OwnedChange>,
+    name: &'static str,
+    min: Duration,
+    period: impl Fn(&cached::Config) -> Result<Duration, ConfigError>,
+    f: F,
+) where
+    F: Fn(&cached::Config, &mut Box<dyn Db + Send>) -> Result<(), String>
+        + Clone + Send + 'static,
 {
-    let all_interfaces = cfg.get_ref(&configrefs::SERVER_ALL_INTERFACES);
-    let addr = if all_interfaces == "true" { Ipv4Addr::UNSPECIFIED }
+    let mut db = match open_db(&cfg, &changes) {
+        Ok(db) => db,
+        Err(e) => {
+            tracing::error!("error opening database for {name}: {e}");
+            return;
+        }
+    };
+
+    let mut interval = ConfiguredInterval::new(name, min);
+    loop {
+        let current = cfg.current();
+        interval.update(period(&current)).tick().await;
+        let f = f.clone();
+        let result = web::block(move || (f(&current, &mut db), db)).await;
+        match result {
+            Ok((result, returned)) => {
+                db = returned;
+                if let Err(e) = result {
+                    tracing::error!("error running {name}: {e}");
+                }
+            }
+            Err(e) => {
+                tracing::error!("error running {name}: {e}");
+                return;
+            }
+        }
+    }
+}
+
+pub fn warn_on_restart_required(cfg: &Cfg) {
+    let initial_cfg = cfg.initial.clone();
+    let overrides = cfg.overrides.clone();
+    cfg.file.subscribe(move |new_file_cfg| {
+        let new_cfg = layer(&overrides, new_file_cfg.clone());
+        if restart_required(&initial_cfg, &new_cfg) {
+            tracing::warn!("server config changed: restart to apply");
+        }
+    });
+}
+
+fn all_config_refs() -> Vec<&'static dyn AnyValueRef> {
+    dunsumday::configrefs::ALL.iter()
+        .chain(configrefs::ALL.iter())
+        .copied()
+        .collect()
+}
+
+pub fn check_config_defaults() -> Result<(), String> {
+    config::check_defaults(&all_config_refs())
+        .map_err(|errors| {
+            errors.iter().map(ToString::to_string).collect::<Vec<_>>()
+                .join("\n")
+        })
+}
+
+/// Fail if any setting which is never resolved refers to a secret, since it
+/// would otherwise be used literally.
+pub fn check_config_unresolved(cfg: &dyn Config) -> Result<(), String> {
+    config::check_unresolved(cfg, &all_config_refs())
+        .map_err(|errors| {
+            errors.iter().map(ToString::to_string).collect::<Vec<_>>()
+                .join("\n")
+        })
+}
+
+pub fn check_config(cfg: &dyn Config) -> Result<(), String> {
+    let refs = all_config_refs();
+    let errors = config::check_defaults(&refs).err().into_iter()
+        .chain(config::check_all(cfg, &refs).err())
+        .flatten()
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        println!("config is valid");
+        Ok(())
+    } else {
+        for error in &errors {
+            eprintln!("{error}");
+        }
+        Err(format!("found {} invalid config values", errors.len()))
+    }
+}
+
+pub fn print_config(cfg: &dyn Config, format: PrintFormat)
+-> Result<(), String> {
+    let values = config::dump(cfg, &all_config_refs());
+    let output = match format {
+        PrintFormat::Yaml => serde_yaml::to_string(&values)
+            .map_err(|e| format!("error serialising config: {e}"))?,
+        PrintFormat::Json => serde_json::to_string_pretty(&values)
+            .map_err(|e| format!("error serialising config: {e}"))?,
+    };
+    println!("{output}");
+    Ok(())
+}
+
+pub fn addr(cfg: &cached::Config)
+-> Result<impl ToSocketAddrs, ConfigError> {
+    let all_interfaces = cfg.get_parsed(&configrefs::SERVER_ALL_INTERFACES)?;
+    let addr = if all_interfaces { Ipv4Addr::UNSPECIFIED }
                else { Ipv4Addr::LOCALHOST };
-    (addr, cfg.get_ref(&configrefs::SERVER_PORT).parse().unwrap())
+    Ok((addr, cfg.get_parsed(&configrefs::SERVER_PORT)?))
+}
+
+/// Log filter used when none is configured and `RUST_LOG` isn't set.
+const DEFAULT_LOG_FILTER: &str = "error";
+
+/// Start writing logs in the configured format.  This also collects messages
+/// logged with the `log` crate, such as by Actix.
+pub fn init_logging(cfg: &cached::Config) -> Result<(), String> {
+    let filter = match cfg.get_ref(&configrefs::LOG_FILTER) {
+        "" => env::var(EnvFilter::DEFAULT_ENV)
+            .unwrap_or_else(|_| DEFAULT_LOG_FILTER.to_owned()),
+        filter => filter.to_owned(),
+    };
+    let filter = EnvFilter::try_new(&filter)
+        .map_err(|e| format!("invalid log filter ({filter}): {e}"))?;
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal());
+    match cfg.get_parsed(&configrefs::LOG_FORMAT)?.as_str() {
+        "pretty" => builder.pretty().try_init(),
+        "json" => builder.json().try_init(),
+        _ => builder.try_init(),
+    }
+        .map_err(|e| format!("error initialising logging: {e}"))
+}
+
+/// Load the configured TLS certificate and key, if any.
+pub fn tls_config(cfg: &cached::Config)
+-> Result<Option<rustls::ServerConfig>, String> {
+    let cert_path = cfg.get_ref(&configrefs::SERVER_TLS_CERT);
+    let key_path = cfg.get_ref(&configrefs::SERVER_TLS_KEY);
+    match (cert_path.is_empty(), key_path.is_empty()) {
+        (true, true) => return Ok(None),
+        (false, false) => {}
+        _ => return Err("TLS certificate and key must be set together"
+                        .to_owned()),
+    }
+
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!(
+            "error reading TLS certificate ({cert_path}): {e}"))?;
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| format!("error reading TLS key ({key_path}): {e}"))?;
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder
+            .with_no_client_auth()
+            .with_single_cert(certs, key))
+        .map(Some)
+        .map_err(|e| format!("invalid TLS certificate or key: {e}"))
 }
@@ -0,0 +1,54 @@
+//! Syncing events from subscribed iCalendar URLs as items.
+
+use std::time::Duration;
+use tokio::sync::broadcast;
+use dunsumday::config::{cached, Config};
+use dunsumday::db::Db;
+use dunsumday::db::util as dbutil;
+use dunsumday::util::sync;
+use crate::configrefs;
+use crate::server::{self, OwnedChange};
+
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Sync every configured URL, for the configured user.  A failure for one URL
+/// doesn't stop the others being synced.
+fn sync_all(cfg: &cached::Config, db: &mut impl Db) -> Result<(), String> {
+    let urls = cfg.get_parsed(&dunsumday::configrefs::SYNC_ICS_URLS)?;
+    if urls.is_empty() {
+        return Ok(());
+    }
+    let name = cfg.get_ref(&configrefs::SYNC_USER);
+    let owner = if name.is_empty() {
+        None
+    } else {
+        Some(dbutil::get_or_create_user(db, name)?.id)
+    };
+    db.set_user(owner.as_deref())?;
+
+    for url in urls {
+        match sync::ics(db, &url) {
+            Ok(synced) => {
+                tracing::info!("synced calendar ({url}): {} items, {} removed",
+                               synced.items.len(), synced.removed.len());
+                for skipped in synced.skipped {
+                    tracing::warn!(
+                        "skipped entry in calendar ({url}): {skipped}");
+                }
+            }
+            Err(e) => tracing::error!("error syncing calendar: {e}"),
+        }
+    }
+    Ok(())
+}
+
+/// Sync calendars at the configured interval until the server stops.
+///
+/// Changes made by syncing are sent to `changes`.
+pub async fn run(cfg: server::Cfg, changes: broadcast::Sender<OwnedChange>) {
+    server::run_periodic(
+        cfg, changes, "calendar sync", MIN_INTERVAL,
+        |cfg| cfg.get_parsed(&dunsumday::configrefs::SYNC_ICS_INTERVAL),
+        sync_all,
+    ).await;
+}
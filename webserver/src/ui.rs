@@ -1,18 +1,17 @@
-use actix_web::web;
+use actix_web::{middleware, web};
 use actix_web::dev::HttpServiceFactory;
 use dunsumday::config::Config;
-use crate::configrefs;
+use crate::{auth, configrefs};
 
 pub fn service<C>(cfg: &C) -> impl HttpServiceFactory
 where
     C: Config + ?Sized,
 {
-    let files = actix_files::Files::new(
-            cfg.get_ref(&configrefs::SERVER_UI_PATH),
-            cfg.get_ref(&configrefs::UI_PATH)
-    )
+    let files = actix_files::Files::new("", cfg.get_ref(&configrefs::UI_PATH))
         .index_file("index.html")
         .redirect_to_slash_directory();
-    web::scope("")
+    web::scope(cfg.get_ref(&configrefs::SERVER_UI_PATH))
+        .wrap(middleware::Condition::new(
+            auth::enabled(cfg), middleware::from_fn(auth::redirect_to_login)))
         .service(files)
 }
@@ -0,0 +1,60 @@
+use std::future::{ready, Ready};
+use actix_session::SessionExt;
+use actix_web::dev::Payload;
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError,
+                       ErrorUnauthorized};
+use actix_web::{web, FromRequest, HttpRequest};
+use dunsumday::config::Config;
+use dunsumday::db::util as dbutil;
+use crate::{auth, configrefs, server};
+
+/// The user making a request.
+///
+/// If OpenID Connect login is [enabled](auth::enabled), the user is the one
+/// logged in to the session.  Otherwise, if [`configrefs::USERS_HEADER`] is
+/// set, the user is named by that request header, and is created the first time
+/// it is seen.  Otherwise, there is no user, and requests operate on objects
+/// without an owner.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct User {
+    /// Database ID.
+    pub id: Option<String>,
+}
+
+fn resolve(req: &HttpRequest) -> actix_web::Result<User> {
+    let data = req.app_data::<web::Data<server::State>>()
+        .ok_or_else(|| ErrorInternalServerError("server state missing"))?;
+    let cfg = data.cfg();
+    if auth::enabled(cfg.as_ref()) {
+        return auth::session_user(&req.get_session())?
+            .map(|id| User { id: Some(id) })
+            .ok_or_else(|| ErrorUnauthorized("not logged in"));
+    }
+
+    let header = cfg.get_ref(&configrefs::USERS_HEADER);
+    if header.is_empty() {
+        return Ok(User { id: None });
+    }
+
+    let name = req.headers().get(header)
+        .ok_or_else(|| ErrorUnauthorized("user not provided"))?
+        .to_str()
+        .map_err(|e| ErrorBadRequest(format!("invalid user name: {e}")))?
+        .trim();
+    if name.is_empty() {
+        return Err(ErrorUnauthorized("user not provided"));
+    }
+    let user = dbutil::get_or_create_user(&mut *data.db(&User { id: None })?,
+                                          name)
+        .map_err(ErrorInternalServerError)?;
+    Ok(User { id: Some(user.id) })
+}
+
+impl FromRequest for User {
+    type Error = actix_web::Error;
+    type Future = Ready<actix_web::Result<User>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(resolve(req))
+    }
+}
@@ -0,0 +1,291 @@
+//! Sending requests to configured URLs when certain changes happen.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use actix_web::rt;
+use actix_web::web;
+use reqwest::header::CONTENT_TYPE;
+use ring::hmac;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use dunsumday::config::cached;
+use dunsumday::db::notify::{Change, ChangeKind};
+use dunsumday::db::{Db, StoredItem};
+use dunsumday::db::util as dbutil;
+use dunsumday::util::clock;
+use dunsumday::util::status::{OccStatus, StatusTracker, TrackedOcc};
+use crate::configrefs;
+use crate::server::{self, OwnedChange};
+
+/// Header containing the event name.
+const EVENT_HEADER: &str = "x-dunsumday-event";
+/// Header containing the request body's signature.
+const SIGNATURE_HEADER: &str = "x-dunsumday-signature";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Type of change which triggers a webhook.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Event {
+    ItemCreated,
+    OccComplete,
+    OccOverdue,
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::ItemCreated => "item_created",
+            Event::OccComplete => "occ_complete",
+            Event::OccOverdue => "occ_overdue",
+        }
+    }
+}
+
+/// Body of webhook requests, encoded as JSON.
+#[derive(Debug, Serialize)]
+struct Payload {
+    event: Event,
+    /// Name of the user who owns the item.
+    user: Option<String>,
+    item: StoredItem,
+    /// Missing for item events.
+    occ: Option<TrackedOcc>,
+}
+
+/// Compute the value of the signature header for a request body, as the
+/// hex-encoded HMAC-SHA256 of the body.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    format!("sha256={}", hex::encode(hmac::sign(&key, body).as_ref()))
+}
+
+/// Send a webhook request, logging any failure.
+async fn deliver(
+    http: reqwest::Client,
+    url: reqwest::Url,
+    event: Event,
+    body: Vec<u8>,
+    signature: Option<String>,
+) {
+    let mut request = http.post(url.clone())
+        .header(CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, event.name())
+        .body(body);
+    if let Some(signature) = signature {
+        request = request.header(SIGNATURE_HEADER, signature);
+    }
+    if let Err(e) = request.send().await.and_then(|r| r.error_for_status()) {
+        tracing::warn!("error sending webhook ({url}): {e}");
+    }
+}
+
+/// Send a request for each of `payloads` to every configured URL.
+fn send(http: &reqwest::Client, cfg: &cached::Config, payloads: Vec<Payload>)
+-> Result<(), String> {
+    if payloads.is_empty() {
+        return Ok(());
+    }
+    let urls = cfg.get_parsed(&configrefs::WEBHOOKS_URLS)?;
+    let secret = cfg.get_parsed(&configrefs::WEBHOOKS_SECRET)?;
+    for payload in payloads {
+        let body = serde_json::to_vec(&payload)
+            .map_err(|e| format!("error serialising webhook: {e}"))?;
+        let signature = (!secret.is_empty()).then(|| sign(&secret, &body));
+        for url in &urls {
+            rt::spawn(deliver(http.clone(), url.clone(), payload.event,
+                              body.clone(), signature.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Reason to look for changes which trigger webhooks.
+enum Trigger {
+    /// Periodic check of every user's occurrences.
+    Interval,
+    Change(OwnedChange),
+}
+
+/// Finds changes which trigger webhooks.
+///
+/// This reads from the database, so it mustn't be used on the async runtime.
+struct Checker {
+    db: Box<dyn Db + Send>,
+    /// Occurrence status trackers, by owner's user ID, or `None` before the
+    /// first check since webhooks were enabled.
+    trackers: Option<HashMap<Option<String>, StatusTracker>>,
+}
+
+impl Checker {
+    /// Build the body of a request for `event`.
+    fn payload(
+        &self,
+        event: Event,
+        owner: Option<&str>,
+        item: StoredItem,
+        occ: Option<TrackedOcc>,
+    ) -> Result<Payload, String> {
+        let user = match owner {
+            Some(owner) => self.db.find_users(None)?
+                .into_iter()
+                .find(|user| user.id == owner)
+                .map(|user| user.user.name),
+            None => None,
+        };
+        Ok(Payload { event, user, item, occ })
+    }
+
+    /// Check the statuses of `owner`'s occurrences, returning requests for
+    /// those which were completed or became overdue if `report` is `true`.
+    fn check(
+        &mut self,
+        cfg: &cached::Config,
+        owner: Option<&str>,
+        report: bool,
+    ) -> Result<Vec<Payload>, String> {
+        let default_alert = cfg.get_parsed(
+            &dunsumday::configrefs::OCC_ALERT_DEFAULT)?;
+        let now = clock::from_config(cfg)?.now();
+        let Some(trackers) = &mut self.trackers else { return Ok(vec![]) };
+        self.db.set_user(owner)?;
+        let changed = trackers.entry(owner.map(|owner| owner.to_owned()))
+            .or_default()
+            .check(&self.db, default_alert, now)?;
+        if !report {
+            return Ok(vec![]);
+        }
+
+        let mut payloads = Vec::new();
+        for (item, occ, status) in changed {
+            let event = match status {
+                OccStatus::Complete => Event::OccComplete,
+                OccStatus::Overdue => Event::OccOverdue,
+                OccStatus::Idle | OccStatus::Alert => continue,
+            };
+            payloads.push(self.payload(event, owner, item, Some(occ))?);
+        }
+        Ok(payloads)
+    }
+
+    /// Check the statuses of every user's occurrences.
+    ///
+    /// Requests are not returned for the first check since webhooks were
+    /// enabled, since the statuses found aren't changes.
+    fn check_all(&mut self, cfg: &cached::Config)
+    -> Result<Vec<Payload>, String> {
+        let report = self.trackers.is_some();
+        self.trackers.get_or_insert_with(HashMap::new);
+        let mut payloads = Vec::new();
+        for owner in server::owners(&self.db)? {
+            payloads.extend(self.check(cfg, owner.as_deref(), report)?);
+        }
+        Ok(payloads)
+    }
+
+    /// Get the requests triggered by a change made to the database.
+    fn handle_change(&mut self, cfg: &cached::Config, change: OwnedChange)
+    -> Result<Vec<Payload>, String> {
+        let owner = change.owner.as_deref();
+        match change.change {
+            Change::Item { id, kind: ChangeKind::Created } => {
+                self.db.set_user(owner)?;
+                let item = dbutil::get_item(&self.db, &id)?;
+                Ok(vec![self.payload(Event::ItemCreated, owner, item, None)?])
+            }
+            // adding progress may complete the occurrence
+            Change::Occ { kind: ChangeKind::Updated, .. } => {
+                self.check(cfg, owner, true)
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// Get the requests caused by `trigger`.
+    fn handle(&mut self, cfg: &cached::Config, trigger: Trigger)
+    -> Result<Vec<Payload>, String> {
+        match trigger {
+            Trigger::Interval => self.check_all(cfg),
+            Trigger::Change(change) => self.handle_change(cfg, change),
+        }
+    }
+}
+
+/// Send webhook requests until the server stops.
+///
+/// Changes which trigger requests are received from `changes`.  Checking
+/// occurrences doesn't change the database.
+pub async fn run(cfg: server::Cfg, changes: broadcast::Sender<OwnedChange>) {
+    let mut receiver = changes.subscribe();
+    let db = match server::open_db(&cfg, &changes) {
+        Ok(db) => db,
+        Err(e) => {
+            tracing::error!("error opening database for webhooks: {e}");
+            return;
+        }
+    };
+    let http = match reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+    {
+        Ok(http) => http,
+        Err(e) => {
+            tracing::error!("error creating HTTP client for webhooks: {e}");
+            return;
+        }
+    };
+    let mut checker = Checker { db, trackers: None };
+
+    let mut interval =
+        server::ConfiguredInterval::new("webhooks", MIN_INTERVAL);
+    loop {
+        let current = cfg.current();
+        let period = current.get_parsed(&configrefs::WEBHOOKS_INTERVAL);
+        let enabled = match current.get_parsed(&configrefs::WEBHOOKS_URLS) {
+            Ok(urls) => !urls.is_empty(),
+            Err(e) => {
+                tracing::error!("error reading webhooks config: {e}");
+                false
+            }
+        };
+        if !enabled {
+            // start from scratch when enabled again
+            checker.trackers = None;
+        }
+
+        let trigger = tokio::select! {
+            _ = interval.update(period).tick() => Trigger::Interval,
+            change = receiver.recv() => match change {
+                Ok(change) => Trigger::Change(change),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::error!(
+                        "error processing webhooks: missed {missed} changes");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        };
+        if !enabled {
+            continue;
+        }
+        let block_cfg = current.clone();
+        let result = web::block(move || {
+            (checker.handle(&block_cfg, trigger), checker)
+        }).await;
+        match result {
+            Ok((payloads, returned)) => {
+                checker = returned;
+                if let Err(e) = payloads
+                    .and_then(|payloads| send(&http, &current, payloads))
+                {
+                    tracing::error!("error processing webhooks: {e}");
+                }
+            }
+            Err(e) => {
+                tracing::error!("error processing webhooks: {e}");
+                return;
+            }
+        }
+    }
+}